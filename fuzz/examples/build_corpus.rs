@@ -0,0 +1,124 @@
+//! Build the seed corpora of the fuzz targets from sample HEIC files
+//!
+//! Usage: `cargo run --example build_corpus [FILE.heic...]`
+//!
+//! Without arguments, the sample files behind the decoder's reference
+//! outputs in `tests/references` are used. A reference named
+//! `a_b_c.heic.rgb` is the output for `a/b/c.heic` relative to the
+//! repository root. Seeds are written to `corpus/<target>/`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use heic_decoder::heif;
+use heic_decoder::hevc::bitstream::{self, NalType};
+
+fn main() {
+    let fuzz_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let repo_dir = fuzz_dir
+        .parent()
+        .expect("fuzz crate is inside the repository");
+
+    let mut files: Vec<PathBuf> = std::env::args().skip(1).map(PathBuf::from).collect();
+    if files.is_empty() {
+        files = reference_sources(repo_dir);
+    }
+    if files.is_empty() {
+        eprintln!("No sample files found; pass HEIC files as arguments");
+        process::exit(1);
+    }
+
+    let corpus_dir = fuzz_dir.join("corpus");
+    for target in ["parse", "decode", "hevc_decode", "parse_sps", "parse_pps"] {
+        fs::create_dir_all(corpus_dir.join(target)).expect("Failed to create corpus directory");
+    }
+
+    for path in &files {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Skipping {}: {e}", path.display());
+                continue;
+            }
+        };
+        let stem = path
+            .file_stem()
+            .map_or("seed".into(), |s| s.to_string_lossy());
+        let seeds = add_seeds(&corpus_dir, &stem, &data);
+        println!("{}: {seeds} seeds", path.display());
+    }
+}
+
+/// The sample files behind the reference outputs that exist on disk
+fn reference_sources(repo_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(repo_dir.join("tests/references")) else {
+        return Vec::new();
+    };
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(source) = name.strip_suffix(".rgb") else {
+            continue;
+        };
+        let path = repo_dir.join(source.replace('_', "/"));
+        if path.is_file() {
+            files.push(path);
+        } else {
+            eprintln!("Reference source {} not found", path.display());
+        }
+    }
+    files
+}
+
+/// Write the seeds derived from one HEIC file, returning how many
+fn add_seeds(corpus_dir: &Path, stem: &str, data: &[u8]) -> usize {
+    let mut seeds = 0;
+    let mut write = |target: &str, name: String, bytes: &[u8]| {
+        fs::write(corpus_dir.join(target).join(name), bytes).expect("Failed to write seed");
+        seeds += 1;
+    };
+
+    write("parse", format!("{stem}.heic"), data);
+    write("decode", format!("{stem}.heic"), data);
+
+    let Ok(container) = heif::parse(data) else {
+        return seeds;
+    };
+    for info in &container.item_infos {
+        let Some(item) = container.get_item(info.item_id) else {
+            continue;
+        };
+        let (Some(config), Some(payload)) =
+            (&item.hevc_config, container.get_item_data_owned(item.id))
+        else {
+            continue;
+        };
+
+        // Annex B stream of the parameter sets followed by the coded image
+        let mut stream = Vec::new();
+        for nal in &config.nal_units {
+            stream.extend_from_slice(&[0, 0, 0, 1]);
+            stream.extend_from_slice(nal);
+        }
+        let length_size = usize::from(config.length_size_minus_one) + 1;
+        for nal in bitstream::parse_length_prefixed_ext(&payload, length_size).unwrap_or_default() {
+            stream.extend_from_slice(&[0, 0, 0, 1]);
+            stream.extend_from_slice(nal.raw_data);
+        }
+        write("hevc_decode", format!("{stem}-{}.hevc", item.id), &stream);
+
+        for (i, nal) in config.nal_units.iter().enumerate() {
+            let Ok(nal) = bitstream::parse_single_nal(nal) else {
+                continue;
+            };
+            let target = match nal.nal_type {
+                NalType::SpsNut => "parse_sps",
+                NalType::PpsNut => "parse_pps",
+                _ => continue,
+            };
+            write(target, format!("{stem}-{}-{i}.rbsp", item.id), &nal.payload);
+        }
+    }
+    seeds
+}
@@ -0,0 +1,14 @@
+//! Fuzz full decoding of HEIC files to RGB with the default limits, strict
+//! and lenient
+
+#![no_main]
+
+use heic_decoder::HeicDecoder;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let decoder = HeicDecoder::new();
+    let _ = decoder.get_info(data);
+    let _ = decoder.decode(data);
+    let _ = decoder.decode_lenient(data);
+});
@@ -0,0 +1,13 @@
+//! Fuzz the HEVC decoder on raw Annex B or length-prefixed bitstreams
+
+#![no_main]
+
+use heic_decoder::hevc;
+use heic_decoder::{DecoderOptions, Strictness};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = hevc::decode(data);
+    let options = DecoderOptions::new().strictness(Strictness::Lenient);
+    let _ = hevc::decode_with_options(data, hevc::DecodeMode::Full, &options);
+});
@@ -0,0 +1,26 @@
+//! Fuzz the HEIF container parser and the item lookups built on it
+
+#![no_main]
+
+use heic_decoder::heif;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(container) = heif::parse(data) else {
+        return;
+    };
+
+    for handle in container.image_handles() {
+        let _ = container.image_handle(handle.item_id);
+        let _ = container.item_properties(handle.item_id).count();
+        let _ = container.alternative_item_ids(handle.item_id);
+        let _ = container.alpha_item_id(handle.item_id);
+    }
+    for info in &container.item_infos {
+        let _ = container.get_item_data(info.item_id);
+        if let Some(payload) = container.get_item_data_owned(info.item_id) {
+            let _ = heif::parse_grid_config(&payload);
+            let _ = heif::parse_overlay_config(&payload);
+        }
+    }
+});
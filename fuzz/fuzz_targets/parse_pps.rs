@@ -0,0 +1,10 @@
+//! Fuzz the picture parameter set parser on NAL unit payloads
+
+#![no_main]
+
+use heic_decoder::hevc::params;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = params::parse_pps(data);
+});
@@ -6,6 +6,40 @@ use std::fs;
 use std::path::Path;
 use std::process;
 
+/// Current local time as `YYYY-MM-DD_HHMMSS`
+#[cfg(feature = "local-time")]
+fn timestamp() -> String {
+    chrono::Local::now().format("%Y-%m-%d_%H%M%S").to_string()
+}
+
+/// Current UTC time as `YYYY-MM-DD_HHMMSS`, without the time zone database
+/// that local time needs
+#[cfg(not(feature = "local-time"))]
+fn timestamp() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // Civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}{:02}{:02}",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() != 2 {
@@ -17,7 +51,7 @@ fn main() {
     let data = fs::read(input_path).expect("Failed to read input file");
 
     // Generate timestamp and base filename
-    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H%M%S").to_string();
+    let timestamp = timestamp();
     let input_stem = Path::new(input_path)
         .file_stem()
         .and_then(|s| s.to_str())
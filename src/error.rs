@@ -1,8 +1,20 @@
 //! Error types for HEIC decoding
+//!
+//! Errors carry where they occurred: [`HeicError::Located`] adds the box
+//! path, byte offset and item of a container error, and
+//! [`HevcError::Located`] the NAL unit, slice and CTB of a bitstream error.
+//! Display prints only the outermost error, such as the location; the
+//! underlying error is available through `source()`, so error reporters
+//! print each part once. [`HeicError::kind`] classifies any error without
+//! matching on messages.
 
+use alloc::boxed::Box;
 use alloc::string::String;
+use alloc::sync::Arc;
 use core::fmt;
 
+use crate::hevc::bitstream::NalType;
+
 /// Result type for HEIC operations
 pub type Result<T> = core::result::Result<T, HeicError>;
 
@@ -27,6 +39,199 @@ pub enum HeicError {
         /// Actual size
         actual: usize,
     },
+    /// The input exceeds a configured resource limit
+    LimitExceeded(LimitExceeded),
+    /// I/O error while reading the input
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// An error with the container location and item it occurred at
+    Located {
+        /// Where the error occurred
+        location: ContainerLocation,
+        /// The error itself
+        source: Box<HeicError>,
+    },
+}
+
+/// Stable classification of an error, e.g. for bucketing failures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Malformed HEIF container structure
+    InvalidContainer,
+    /// Malformed or inconsistent item data
+    InvalidData,
+    /// Malformed HEVC bitstream
+    InvalidBitstream,
+    /// A parameter set the bitstream refers to is missing
+    MissingParameterSet,
+    /// Valid input using a feature the decoder does not support
+    Unsupported,
+    /// The container has no primary image
+    NoPrimaryImage,
+    /// An output buffer is too small
+    BufferTooSmall,
+    /// The input exceeds a resource limit
+    LimitExceeded,
+    /// Reading the input failed
+    Io,
+}
+
+impl ErrorKind {
+    /// Short identifier of the kind, stable across releases
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidContainer => "invalid_container",
+            Self::InvalidData => "invalid_data",
+            Self::InvalidBitstream => "invalid_bitstream",
+            Self::MissingParameterSet => "missing_parameter_set",
+            Self::Unsupported => "unsupported",
+            Self::NoPrimaryImage => "no_primary_image",
+            Self::BufferTooSmall => "buffer_too_small",
+            Self::LimitExceeded => "limit_exceeded",
+            Self::Io => "io",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where in a HEIF container an error occurred
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContainerLocation {
+    /// Path of the box from the top level, e.g. `meta/iinf/infe[3]`;
+    /// empty if the error is not tied to a box
+    pub box_path: String,
+    /// Byte offset of the box or item data within the parsed input
+    pub offset: Option<u64>,
+    /// Item being parsed or decoded
+    pub item_id: Option<u32>,
+}
+
+impl fmt::Display for ContainerLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(item_id) = self.item_id {
+            write!(f, "item {item_id}")?;
+        }
+        if !self.box_path.is_empty() {
+            let sep = if self.item_id.is_some() { ", " } else { "" };
+            write!(f, "{sep}box {}", self.box_path)?;
+        }
+        if let Some(offset) = self.offset {
+            let sep = if self.item_id.is_some() || !self.box_path.is_empty() { " " } else { "" };
+            write!(f, "{sep}at offset {offset}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Where in an HEVC bitstream an error occurred
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BitstreamLocation {
+    /// Index of the NAL unit among those decoded, counting the parameter
+    /// sets of the `hvcC` box first for HEIF items
+    pub nal_index: Option<usize>,
+    /// Type of the NAL unit
+    pub nal_type: Option<NalType>,
+    /// `slice_segment_address` of the slice segment
+    pub slice_address: Option<u32>,
+    /// Coordinates of the coding tree block, in CTBs
+    pub ctb: Option<(u32, u32)>,
+}
+
+impl fmt::Display for BitstreamLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        if let Some(index) = self.nal_index {
+            write!(f, "NAL unit {index}")?;
+            sep = " ";
+        }
+        if let Some(nal_type) = self.nal_type {
+            write!(f, "{sep}({nal_type:?})")?;
+            sep = ", ";
+        }
+        if let Some(address) = self.slice_address {
+            write!(f, "{sep}slice segment {address}")?;
+            sep = ", ";
+        }
+        if let Some((x, y)) = self.ctb {
+            write!(f, "{sep}CTB ({x}, {y})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A rectangle of a leniently decoded image that was concealed
+///
+/// See [`HeicDecoder::decode_lenient`](crate::HeicDecoder::decode_lenient).
+#[derive(Debug, Clone)]
+pub struct DamagedRegion {
+    /// Left edge in pixels
+    pub x: u32,
+    /// Top edge in pixels
+    pub y: u32,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Why the region could not be decoded
+    pub cause: Arc<HeicError>,
+}
+
+/// A resource limit of [`DecoderLimits`](crate::DecoderLimits)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Limit {
+    /// Image width in pixels
+    Width,
+    /// Image height in pixels
+    Height,
+    /// Image area in pixels
+    Pixels,
+    /// Number of tiles in a grid image
+    Tiles,
+    /// Number of items in the container
+    Items,
+    /// Size of a single buffer in bytes
+    Allocation,
+    /// Number of nested image derivations
+    DerivationDepth,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Width => "width",
+            Self::Height => "height",
+            Self::Pixels => "pixel count",
+            Self::Tiles => "tile count",
+            Self::Items => "item count",
+            Self::Allocation => "allocation size",
+            Self::DerivationDepth => "derivation depth",
+        })
+    }
+}
+
+/// A value in the input that exceeds a resource limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded {
+    /// The limit that was exceeded
+    pub limit: Limit,
+    /// Value required by the input
+    pub requested: u64,
+    /// Configured maximum
+    pub max: u64,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} limit exceeded: {} > {}", self.limit, self.requested, self.max)
+    }
 }
 
 impl fmt::Display for HeicError {
@@ -36,27 +241,139 @@ impl fmt::Display for HeicError {
             Self::InvalidData(msg) => write!(f, "invalid data: {msg}"),
             Self::Unsupported(msg) => write!(f, "unsupported: {msg}"),
             Self::NoPrimaryImage => write!(f, "no primary image in container"),
-            Self::HevcDecode(e) => write!(f, "HEVC decode error: {e}"),
+            Self::HevcDecode(_) => write!(f, "HEVC decode error"),
             Self::BufferTooSmall { required, actual } => {
                 write!(f, "buffer too small: need {required}, got {actual}")
             }
+            Self::LimitExceeded(e) => write!(f, "{e}"),
+            #[cfg(feature = "std")]
+            Self::Io(_) => write!(f, "I/O error"),
+            Self::Located { location, .. } => write!(f, "{location}"),
         }
     }
 }
 
-#[cfg(feature = "std")]
-impl std::error::Error for HeicError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+impl HeicError {
+    /// Classification of the error, looking through any location context
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidContainer(_) => ErrorKind::InvalidContainer,
+            Self::InvalidData(_) => ErrorKind::InvalidData,
+            Self::Unsupported(_) => ErrorKind::Unsupported,
+            Self::NoPrimaryImage => ErrorKind::NoPrimaryImage,
+            Self::HevcDecode(e) => e.kind(),
+            Self::BufferTooSmall { .. } => ErrorKind::BufferTooSmall,
+            Self::LimitExceeded(_) => ErrorKind::LimitExceeded,
+            #[cfg(feature = "std")]
+            Self::Io(_) => ErrorKind::Io,
+            Self::Located { source, .. } => source.kind(),
+        }
+    }
+
+    /// Where in the container the error occurred, if known
+    #[must_use]
+    pub fn location(&self) -> Option<&ContainerLocation> {
+        match self {
+            Self::Located { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// The error without its container location
+    #[must_use]
+    pub fn without_location(&self) -> &HeicError {
+        match self {
+            Self::Located { source, .. } => source,
+            e => e,
+        }
+    }
+
+    /// Where in the HEVC bitstream the error occurred, if it is a
+    /// bitstream error with a known location
+    #[must_use]
+    pub fn bitstream_location(&self) -> Option<&BitstreamLocation> {
+        match self {
+            Self::HevcDecode(e) => e.location(),
+            Self::Located { source, .. } => source.bitstream_location(),
+            _ => None,
+        }
+    }
+
+    /// Add the box `name` to the front of the error's box path
+    ///
+    /// `offset` is recorded if no box nested in this one recorded an offset.
+    pub(crate) fn in_box(self, name: impl fmt::Display, offset: u64) -> Self {
+        self.locate(|location| {
+            location.box_path = if location.box_path.is_empty() {
+                alloc::format!("{name}")
+            } else {
+                alloc::format!("{name}/{}", location.box_path)
+            };
+            location.offset.get_or_insert(offset);
+        })
+    }
+
+    /// Record the item the error occurred in, unless an item nested in it
+    /// was already recorded
+    pub(crate) fn for_item(self, item_id: u32) -> Self {
+        self.locate(|location| {
+            location.item_id.get_or_insert(item_id);
+        })
+    }
+
+    /// Record the offset of the data the error occurred in, unless a more
+    /// precise one was already recorded
+    pub(crate) fn at_offset(self, offset: u64) -> Self {
+        self.locate(|location| {
+            location.offset.get_or_insert(offset);
+        })
+    }
+
+    /// Update the location, wrapping the error in [`Self::Located`] first
+    /// if it has none yet
+    fn locate(self, update: impl FnOnce(&mut ContainerLocation)) -> Self {
+        let (mut location, source) = match self {
+            Self::Located { location, source } => (location, source),
+            e => (ContainerLocation::default(), Box::new(e)),
+        };
+        update(&mut location);
+        Self::Located { location, source }
+    }
+}
+
+impl core::error::Error for HeicError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
         match self {
             Self::HevcDecode(e) => Some(e),
+            #[cfg(feature = "std")]
+            Self::Io(e) => Some(e),
+            Self::Located { source, .. } => Some(source.as_ref()),
             _ => None,
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl From<std::io::Error> for HeicError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
 impl From<HevcError> for HeicError {
     fn from(e: HevcError) -> Self {
-        Self::HevcDecode(e)
+        match e {
+            // Limits are reported the same way from either layer
+            HevcError::LimitExceeded(e) => Self::LimitExceeded(e),
+            e => Self::HevcDecode(e),
+        }
+    }
+}
+
+impl From<LimitExceeded> for HeicError {
+    fn from(e: LimitExceeded) -> Self {
+        Self::LimitExceeded(e)
     }
 }
 
@@ -71,15 +388,34 @@ pub enum HevcError {
     /// Missing required parameter set
     MissingParameterSet(&'static str),
     /// Invalid parameter set
-    InvalidParameterSet { kind: &'static str, msg: String },
+    InvalidParameterSet {
+        /// Parameter set type, e.g. "VPS"
+        kind: &'static str,
+        /// What is invalid
+        msg: String,
+    },
     /// CABAC decoding error
     CabacError(&'static str),
     /// Unsupported profile/level
-    UnsupportedProfile { profile: u8, level: u8 },
+    UnsupportedProfile {
+        /// general_profile_idc
+        profile: u8,
+        /// general_level_idc
+        level: u8,
+    },
     /// Unsupported feature
     Unsupported(&'static str),
     /// Decoding error
     DecodingError(&'static str),
+    /// The bitstream exceeds a configured resource limit
+    LimitExceeded(LimitExceeded),
+    /// An error with the bitstream location it occurred at
+    Located {
+        /// Where the error occurred
+        location: BitstreamLocation,
+        /// The error itself
+        source: Box<HevcError>,
+    },
 }
 
 impl fmt::Display for HevcError {
@@ -97,9 +433,133 @@ impl fmt::Display for HevcError {
             }
             Self::Unsupported(msg) => write!(f, "unsupported: {msg}"),
             Self::DecodingError(msg) => write!(f, "decoding error: {msg}"),
+            Self::LimitExceeded(e) => write!(f, "{e}"),
+            Self::Located { location, .. } => write!(f, "{location}"),
         }
     }
 }
 
-#[cfg(feature = "std")]
-impl std::error::Error for HevcError {}
+impl HevcError {
+    /// Classification of the error, looking through any location context
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidNalUnit(_)
+            | Self::InvalidBitstream(_)
+            | Self::InvalidParameterSet { .. }
+            | Self::CabacError(_)
+            | Self::DecodingError(_) => ErrorKind::InvalidBitstream,
+            Self::MissingParameterSet(_) => ErrorKind::MissingParameterSet,
+            Self::UnsupportedProfile { .. } | Self::Unsupported(_) => ErrorKind::Unsupported,
+            Self::LimitExceeded(_) => ErrorKind::LimitExceeded,
+            Self::Located { source, .. } => source.kind(),
+        }
+    }
+
+    /// Where in the bitstream the error occurred, if known
+    #[must_use]
+    pub fn location(&self) -> Option<&BitstreamLocation> {
+        match self {
+            Self::Located { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// The error without its bitstream location
+    #[must_use]
+    pub fn without_location(&self) -> &HevcError {
+        match self {
+            Self::Located { source, .. } => source,
+            e => e,
+        }
+    }
+
+    /// Record the NAL unit the error occurred in
+    pub(crate) fn at_nal(self, index: usize, nal_type: NalType) -> Self {
+        self.locate(|location| {
+            location.nal_index = Some(index);
+            location.nal_type = Some(nal_type);
+        })
+    }
+
+    /// Record the slice segment the error occurred in
+    pub(crate) fn at_slice(self, slice_address: u32) -> Self {
+        self.locate(|location| location.slice_address = Some(slice_address))
+    }
+
+    /// Record the CTB the error occurred in
+    pub(crate) fn at_ctb(self, ctb_x: u32, ctb_y: u32) -> Self {
+        self.locate(|location| location.ctb = Some((ctb_x, ctb_y)))
+    }
+
+    /// Update the location, wrapping the error in [`Self::Located`] first
+    /// if it has none yet
+    fn locate(self, update: impl FnOnce(&mut BitstreamLocation)) -> Self {
+        let (mut location, source) = match self {
+            Self::Located { location, source } => (location, source),
+            e => (BitstreamLocation::default(), Box::new(e)),
+        };
+        update(&mut location);
+        Self::Located { location, source }
+    }
+}
+
+impl core::error::Error for HevcError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Located { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<LimitExceeded> for HevcError {
+    fn from(e: LimitExceeded) -> Self {
+        Self::LimitExceeded(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::string::ToString;
+    use core::error::Error;
+
+    #[test]
+    fn test_error_location() {
+        let hevc = HevcError::InvalidBitstream("bad coefficient")
+            .at_ctb(2, 3)
+            .at_slice(10)
+            .at_nal(4, NalType::IdrWRadl);
+        let err = HeicError::from(hevc).for_item(7).at_offset(1200);
+        // An enclosing derived item does not replace the failing tile
+        let err = err.for_item(1);
+
+        assert_eq!(err.kind(), ErrorKind::InvalidBitstream);
+        assert_eq!(err.location().unwrap().item_id, Some(7));
+        let location = err.bitstream_location().unwrap();
+        assert_eq!(
+            (location.nal_index, location.slice_address, location.ctb),
+            (Some(4), Some(10), Some((2, 3)))
+        );
+        assert_eq!(err.to_string(), "item 7 at offset 1200");
+
+        // Located -> HevcDecode -> Located -> InvalidBitstream, each part
+        // printed once
+        let mut chain = 0;
+        let mut message = err.to_string();
+        let mut source = err.source();
+        while let Some(e) = source {
+            chain += 1;
+            message = format!("{message}: {e}");
+            source = e.source();
+        }
+        assert_eq!(chain, 3);
+        assert_eq!(
+            message,
+            "item 7 at offset 1200: HEVC decode error: NAL unit 4 (IdrWRadl), \
+             slice segment 10, CTB (2, 3): invalid bitstream: bad coefficient"
+        );
+    }
+}
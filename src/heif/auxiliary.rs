@@ -0,0 +1,219 @@
+//! Auxiliary image (auxl) decoding
+//!
+//! Auxiliary images carry per-pixel side information for a master image:
+//! alpha planes, depth/disparity maps (portrait mode), semantic
+//! segmentation mattes and HDR gain maps. They are coded like any other
+//! image item and linked to their master with an `auxl` reference.
+
+use alloc::vec::Vec;
+
+use crate::error::HeicError;
+use crate::heif::{AuxiliaryKind, AuxiliaryType, HeifContainer, ItemProperty, decode_image_item};
+use crate::hevc::DecodedFrame;
+use crate::hevc::bitstream::{NalType, parse_single_nal};
+use crate::hevc::sei::{DEPTH_REPRESENTATION_INFO, DepthRepresentationInfo, parse_sei_messages};
+#[cfg(not(feature = "std"))]
+use crate::math::F32Ext as _;
+
+/// Sample format for decoded auxiliary images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuxiliaryFormat {
+    /// 8-bit samples in [0, 255]
+    U8,
+    /// 16-bit samples in [0, 65535]
+    U16,
+    /// Normalized samples in [0.0, 1.0]
+    F32,
+}
+
+/// Single-channel auxiliary sample data
+#[derive(Debug, Clone)]
+pub enum AuxiliaryData {
+    /// 8-bit samples
+    U8(Vec<u8>),
+    /// 16-bit samples
+    U16(Vec<u16>),
+    /// Normalized floating point samples
+    F32(Vec<f32>),
+}
+
+/// Decoded auxiliary image, resampled to its master image's size
+#[derive(Debug, Clone)]
+pub struct AuxiliaryImage {
+    /// Auxiliary item ID
+    pub item_id: u32,
+    /// Master item this image is an auxiliary of
+    pub master_item_id: u32,
+    /// Raw auxiliary type (URN and subtype data)
+    pub aux_type: AuxiliaryType,
+    /// Classified auxiliary type
+    pub kind: AuxiliaryKind,
+    /// Width in pixels (master image output width)
+    pub width: u32,
+    /// Height in pixels (master image output height)
+    pub height: u32,
+    /// Row-major samples, `width * height` entries
+    pub data: AuxiliaryData,
+    /// Depth representation info, for depth maps that signal it
+    pub depth_info: Option<DepthRepresentationInfo>,
+}
+
+/// Decode an auxiliary image as a single channel aligned to its master image
+///
+/// The auxiliary luma plane is bilinearly resampled to the master's output
+/// dimensions (after clap/irot), so sample (x, y) corresponds to master
+/// pixel (x, y).
+pub fn decode_auxiliary(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+    format: AuxiliaryFormat,
+) -> Result<AuxiliaryImage, HeicError> {
+    let aux_type = container
+        .auxiliary_type(item_id)
+        .ok_or(HeicError::InvalidData("Item is not an auxiliary image"))?
+        .clone();
+    let handle = container
+        .image_handle(item_id)
+        .ok_or(HeicError::InvalidData("Auxiliary item is not an image"))?;
+    let master_item_id = handle
+        .auxiliary_of
+        .ok_or(HeicError::InvalidData("Auxiliary image has no auxl reference"))?;
+
+    let frame = decode_image_item(container, item_id)?;
+    let (width, height) = container
+        .image_handle(master_item_id)
+        .and_then(|master| master.output_dimensions())
+        .unwrap_or((frame.cropped_width(), frame.cropped_height()));
+
+    let samples = resample_luma(&frame, width, height);
+    let data = match format {
+        AuxiliaryFormat::U8 => {
+            AuxiliaryData::U8(samples.iter().map(|&v| (v * 255.0).round() as u8).collect())
+        }
+        AuxiliaryFormat::U16 => {
+            AuxiliaryData::U16(samples.iter().map(|&v| (v * 65535.0).round() as u16).collect())
+        }
+        AuxiliaryFormat::F32 => AuxiliaryData::F32(samples),
+    };
+
+    let kind = aux_type.kind();
+    let depth_info = if kind == AuxiliaryKind::Depth {
+        depth_representation_info(container, item_id, &aux_type)
+    } else {
+        None
+    };
+
+    Ok(AuxiliaryImage {
+        item_id,
+        master_item_id,
+        aux_type,
+        kind,
+        width,
+        height,
+        data,
+        depth_info,
+    })
+}
+
+/// Find depth representation info for a depth item
+///
+/// Looks at the auxC subtype data first, then at depth_representation_info
+/// SEI messages stored in the item's (or its first tile's) hvcC.
+pub fn depth_representation_info(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+    aux_type: &AuxiliaryType,
+) -> Option<DepthRepresentationInfo> {
+    if !aux_type.aux_subtype.is_empty()
+        && let Ok(info) = DepthRepresentationInfo::parse(&aux_type.aux_subtype)
+    {
+        return Some(info);
+    }
+
+    // Grid depth maps keep their parameter sets on the tiles
+    let mut candidates = Vec::from([item_id]);
+    if let Some(tiles) = container.get_tile_item_ids(item_id) {
+        candidates.extend(tiles.first());
+    }
+
+    candidates.into_iter().find_map(|id| {
+        container.item_properties(id).find_map(|prop| {
+            let ItemProperty::HevcConfig(config) = prop else {
+                return None;
+            };
+            config.nal_units.iter().find_map(|nal_data| {
+                let nal = parse_single_nal(nal_data).ok()?;
+                if nal.nal_type != NalType::PrefixSeiNut {
+                    return None;
+                }
+                parse_sei_messages(&nal.payload)
+                    .ok()?
+                    .into_iter()
+                    .filter(|msg| msg.payload_type == DEPTH_REPRESENTATION_INFO)
+                    .find_map(|msg| DepthRepresentationInfo::parse(msg.payload).ok())
+            })
+        })
+    })
+}
+
+/// Bilinearly resample the cropped luma plane to `width` x `height`, normalized to [0, 1]
+fn resample_luma(frame: &DecodedFrame, width: u32, height: u32) -> Vec<f32> {
+    let src_w = frame.cropped_width();
+    let src_h = frame.cropped_height();
+    let scale = 1.0 / ((1u32 << frame.bit_depth) - 1) as f32;
+    let mut out = Vec::with_capacity(width as usize * height as usize);
+
+    if src_w == 0 || src_h == 0 {
+        out.resize(width as usize * height as usize, 0.0);
+        return out;
+    }
+
+    let sample = |x: u32, y: u32| frame.get_y(x + frame.crop_left, y + frame.crop_top) as f32;
+
+    if src_w == width && src_h == height {
+        for y in 0..height {
+            for x in 0..width {
+                out.push(sample(x, y) * scale);
+            }
+        }
+        return out;
+    }
+
+    let x_ratio = src_w as f32 / width as f32;
+    let y_ratio = src_h as f32 / height as f32;
+    for y in 0..height {
+        let fy = ((y as f32 + 0.5) * y_ratio - 0.5).clamp(0.0, (src_h - 1) as f32);
+        let y0 = fy as u32;
+        let y1 = (y0 + 1).min(src_h - 1);
+        let wy = fy - y0 as f32;
+        for x in 0..width {
+            let fx = ((x as f32 + 0.5) * x_ratio - 0.5).clamp(0.0, (src_w - 1) as f32);
+            let x0 = fx as u32;
+            let x1 = (x0 + 1).min(src_w - 1);
+            let wx = fx - x0 as f32;
+
+            let top = sample(x0, y0) * (1.0 - wx) + sample(x1, y0) * wx;
+            let bottom = sample(x0, y1) * (1.0 - wx) + sample(x1, y1) * wx;
+            out.push((top * (1.0 - wy) + bottom * wy) * scale);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resample_luma_upscale() {
+        let mut frame = DecodedFrame::with_params(2, 1, 8, 0);
+        frame.y_plane.copy_from_slice(&[0, 255]);
+
+        let out = resample_luma(&frame, 4, 1);
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[0], 0.0);
+        assert_eq!(out[3], 1.0);
+        assert!((out[1] - 0.25).abs() < 1e-6);
+        assert!((out[2] - 0.75).abs() < 1e-6);
+    }
+}
@@ -3,6 +3,10 @@
 use alloc::string::String;
 use alloc::vec::Vec;
 
+use crate::error::HeicError;
+#[cfg(not(feature = "std"))]
+use crate::math::F64Ext as _;
+
 /// Four-character code identifying a box type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FourCC(pub [u8; 4]);
@@ -10,6 +14,7 @@ pub struct FourCC(pub [u8; 4]);
 impl FourCC {
     pub const FTYP: Self = Self(*b"ftyp");
     pub const META: Self = Self(*b"meta");
+    pub const MOOV: Self = Self(*b"moov");
     pub const HDLR: Self = Self(*b"hdlr");
     pub const PITM: Self = Self(*b"pitm");
     pub const ILOC: Self = Self(*b"iloc");
@@ -22,13 +27,25 @@ impl FourCC {
     pub const ISPE: Self = Self(*b"ispe");
     pub const HVCB: Self = Self(*b"hvcB");
     pub const HVCC: Self = Self(*b"hvcC");
+    pub const LHVC: Self = Self(*b"lhvC");
+    pub const LSEL: Self = Self(*b"lsel");
     pub const COLR: Self = Self(*b"colr");
     pub const PIXI: Self = Self(*b"pixi");
     pub const IREF: Self = Self(*b"iref");
     pub const AUXC: Self = Self(*b"auxC");
     pub const DIMG: Self = Self(*b"dimg");
+    pub const AUXL: Self = Self(*b"auxl");
+    pub const CDSC: Self = Self(*b"cdsc");
     pub const THMB: Self = Self(*b"thmb");
+    pub const SBAS: Self = Self(*b"sbas");
+    pub const BASE: Self = Self(*b"base");
     pub const IDAT: Self = Self(*b"idat");
+    pub const IROT: Self = Self(*b"irot");
+    pub const IMIR: Self = Self(*b"imir");
+    pub const CLAP: Self = Self(*b"clap");
+    pub const GRPL: Self = Self(*b"grpl");
+    pub const ALTR: Self = Self(*b"altr");
+    pub const STER: Self = Self(*b"ster");
 
     /// Create from bytes
     pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
@@ -69,6 +86,8 @@ pub struct Box<'a> {
     pub header: BoxHeader,
     /// Box content (excluding header)
     pub content: &'a [u8],
+    /// Offset of the box header in the file
+    pub file_offset: u64,
 }
 
 impl<'a> Box<'a> {
@@ -87,18 +106,51 @@ impl<'a> Box<'a> {
             None
         }
     }
+
+    /// Child boxes of this box, starting `skip` bytes into its content
+    ///
+    /// Full boxes and sample entries have fields before their children.
+    pub fn children(&self, skip: usize) -> BoxIterator<'a> {
+        let header_len = self.header.size - self.content.len() as u64;
+        let content = self.content.get(skip..).unwrap_or_default();
+        BoxIterator::at(content, self.file_offset + header_len + skip as u64)
+    }
+
+    /// Add this box to the location of an error from parsing its content
+    ///
+    /// `index` numbers boxes of the same type within their parent, e.g.
+    /// `trak[1]` for the second track.
+    pub(crate) fn locate_error(
+        &self,
+        index: Option<usize>,
+    ) -> impl FnOnce(HeicError) -> HeicError + use<> {
+        let offset = self.file_offset;
+        let box_type = self.box_type();
+        move |e| match index {
+            Some(index) => e.in_box(format_args!("{box_type}[{index}]"), offset),
+            None => e.in_box(box_type, offset),
+        }
+    }
 }
 
 /// Box iterator for parsing sequential boxes
 pub struct BoxIterator<'a> {
     data: &'a [u8],
     offset: usize,
+    /// Offset of `data` in the file
+    file_offset: u64,
 }
 
 impl<'a> BoxIterator<'a> {
-    /// Create a new box iterator
+    /// Create a new box iterator over the top-level boxes of a file
     pub fn new(data: &'a [u8]) -> Self {
-        Self { data, offset: 0 }
+        Self::at(data, 0)
+    }
+
+    /// Create a box iterator over `data`, which starts at `file_offset` in
+    /// the file
+    pub fn at(data: &'a [u8], file_offset: u64) -> Self {
+        Self { data, offset: 0, file_offset }
     }
 }
 
@@ -132,23 +184,25 @@ impl<'a> Iterator for BoxIterator<'a> {
             (size_32 as u64, 8)
         };
 
-        let content_len = size as usize - header_size;
-        if self.offset + header_size + content_len > self.data.len() {
+        // Boxes smaller than their header or larger than the data are malformed
+        let size = usize::try_from(size).ok()?;
+        if size < header_size || size > data.len() {
             return None;
         }
 
-        let content = &data[header_size..size as usize];
+        let content = &data[header_size..size];
 
         let box_item = Box {
             header: BoxHeader {
                 box_type,
-                size,
+                size: size as u64,
                 content_offset: self.offset + header_size,
             },
             content,
+            file_offset: self.file_offset + self.offset as u64,
         };
 
-        self.offset += size as usize;
+        self.offset += size;
         Some(box_item)
     }
 }
@@ -237,6 +291,129 @@ pub enum ColorInfo {
     },
 }
 
+/// Auxiliary image type from auxC box
+#[derive(Debug, Clone)]
+pub struct AuxiliaryType {
+    /// Auxiliary type URN (e.g. "urn:mpeg:hevc:2015:auxid:1")
+    pub aux_type: String,
+    /// Type-specific subtype data following the URN
+    pub aux_subtype: Vec<u8>,
+}
+
+impl AuxiliaryType {
+    /// Classify the auxiliary type URN
+    pub fn kind(&self) -> AuxiliaryKind {
+        match self.aux_type.as_str() {
+            "urn:mpeg:hevc:2015:auxid:1" | "urn:mpeg:mpegB:cicp:systems:auxiliary:alpha" => {
+                AuxiliaryKind::Alpha
+            }
+            "urn:mpeg:hevc:2015:auxid:2" | "urn:mpeg:mpegB:cicp:systems:auxiliary:depth" => {
+                AuxiliaryKind::Depth
+            }
+            "urn:com:apple:photo:2020:aux:hdrgainmap" => AuxiliaryKind::HdrGainMap,
+            "urn:com:apple:photo:2018:aux:portraiteffectsmatte" => {
+                AuxiliaryKind::Matte(SemanticMatte::PortraitEffects)
+            }
+            "urn:com:apple:photo:2019:aux:semanticskinmatte" => {
+                AuxiliaryKind::Matte(SemanticMatte::Skin)
+            }
+            "urn:com:apple:photo:2019:aux:semantichairmatte" => {
+                AuxiliaryKind::Matte(SemanticMatte::Hair)
+            }
+            "urn:com:apple:photo:2019:aux:semanticteethmatte" => {
+                AuxiliaryKind::Matte(SemanticMatte::Teeth)
+            }
+            "urn:com:apple:photo:2020:aux:semanticskymatte" => {
+                AuxiliaryKind::Matte(SemanticMatte::Sky)
+            }
+            "urn:com:apple:photo:2023:aux:semanticglassesmatte" => {
+                AuxiliaryKind::Matte(SemanticMatte::Glasses)
+            }
+            _ => AuxiliaryKind::Other,
+        }
+    }
+
+    /// Check if this auxiliary image is an alpha plane
+    pub fn is_alpha(&self) -> bool {
+        self.kind() == AuxiliaryKind::Alpha
+    }
+}
+
+/// Classified auxiliary image type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuxiliaryKind {
+    /// Alpha (transparency) plane
+    Alpha,
+    /// Depth or disparity map
+    Depth,
+    /// Apple HDR gain map
+    HdrGainMap,
+    /// Apple semantic segmentation matte
+    Matte(SemanticMatte),
+    /// Unrecognised URN (see `AuxiliaryType::aux_type`)
+    Other,
+}
+
+/// Apple semantic segmentation matte category
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticMatte {
+    /// Portrait effects (person) matte
+    PortraitEffects,
+    /// Skin matte
+    Skin,
+    /// Hair matte
+    Hair,
+    /// Teeth matte
+    Teeth,
+    /// Sky matte
+    Sky,
+    /// Glasses matte
+    Glasses,
+}
+
+/// Clean aperture from clap box (ISO/IEC 14496-12, 12.1.4)
+///
+/// All values are fractions (numerator, denominator).
+#[derive(Debug, Clone, Copy)]
+pub struct CleanAperture {
+    /// Clean aperture width
+    pub width: (u32, u32),
+    /// Clean aperture height
+    pub height: (u32, u32),
+    /// Horizontal offset of the aperture centre from the image centre
+    pub horiz_off: (i32, u32),
+    /// Vertical offset of the aperture centre from the image centre
+    pub vert_off: (i32, u32),
+}
+
+impl CleanAperture {
+    /// Compute the crop rectangle (left, top, width, height) for an image
+    /// of the given size, clamped to the image bounds
+    pub fn crop_rect(&self, image_width: u32, image_height: u32) -> Option<(u32, u32, u32, u32)> {
+        fn frac(n: f64, d: u32) -> Option<f64> {
+            (d != 0).then(|| n / d as f64)
+        }
+
+        let clap_w = frac(self.width.0 as f64, self.width.1)?.round();
+        let clap_h = frac(self.height.0 as f64, self.height.1)?.round();
+        let off_x = frac(self.horiz_off.0 as f64, self.horiz_off.1)?;
+        let off_y = frac(self.vert_off.0 as f64, self.vert_off.1)?;
+
+        let left = (off_x + (image_width as f64 - 1.0) / 2.0 - (clap_w - 1.0) / 2.0).round();
+        let top = (off_y + (image_height as f64 - 1.0) / 2.0 - (clap_h - 1.0) / 2.0).round();
+
+        let left = left.clamp(0.0, image_width as f64) as u32;
+        let top = top.clamp(0.0, image_height as f64) as u32;
+        let width = (clap_w.max(0.0) as u32).min(image_width - left);
+        let height = (clap_h.max(0.0) as u32).min(image_height - top);
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        Some((left, top, width, height))
+    }
+}
+
 /// Item property (indexed in ipco)
 #[derive(Debug, Clone)]
 pub enum ItemProperty {
@@ -244,8 +421,21 @@ pub enum ItemProperty {
     ImageExtents(ImageSpatialExtents),
     /// HEVC decoder config (hvcC)
     HevcConfig(HevcDecoderConfig),
+    /// Layered HEVC decoder config (lhvC): parameter sets of the non-base
+    /// layers; only `length_size_minus_one` and `nal_units` are meaningful
+    LayeredHevcConfig(HevcDecoderConfig),
+    /// Layer selector (lsel): nuh_layer_id of the layer the item outputs
+    LayerSelector(u16),
     /// Color info (colr)
     ColorInfo(ColorInfo),
+    /// Auxiliary image type (auxC)
+    AuxiliaryType(AuxiliaryType),
+    /// Image rotation (irot), anticlockwise in units of 90 degrees
+    Rotation(u8),
+    /// Image mirroring (imir): 0 = about a vertical axis, 1 = about a horizontal axis
+    Mirror(u8),
+    /// Clean aperture (clap)
+    CleanAperture(CleanAperture),
     /// Unknown property
     Unknown,
 }
@@ -259,6 +449,29 @@ pub struct PropertyAssociation {
     pub properties: Vec<(u16, bool)>,
 }
 
+/// Entity group from grpl box (EntityToGroupBox)
+#[derive(Debug, Clone)]
+pub struct EntityGroup {
+    /// Grouping type (e.g., "altr" alternatives, "ster" stereo pair, "brst" burst)
+    pub group_type: FourCC,
+    /// Group ID (shares the ID space with items and tracks)
+    pub group_id: u32,
+    /// Member item or track IDs, in box order
+    pub entity_ids: Vec<u32>,
+}
+
+impl EntityGroup {
+    /// Check if this group lists alternatives of the same content ('altr')
+    pub fn is_alternatives(&self) -> bool {
+        self.group_type == FourCC::ALTR
+    }
+
+    /// Check if this group is a stereo pair ('ster'), left view first
+    pub fn is_stereo_pair(&self) -> bool {
+        self.group_type == FourCC::STER
+    }
+}
+
 /// Item reference from iref box
 #[derive(Debug, Clone)]
 pub struct ItemReference {
@@ -0,0 +1,302 @@
+//! Derived image resolution
+//!
+//! Follows `dimg` references from derived items (grid, overlay, identity)
+//! down to the coded images, decoding each level and applying its
+//! transformative properties (clap, irot, imir) on the way back up.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::HeicError;
+use crate::heif::grid::{decode_grid, decode_tile};
+use crate::heif::layered::{decode_layered_item, is_layered};
+use crate::heif::overlay::composite_overlay;
+use crate::heif::{
+    HeifContainer, Item, ItemProperty, ItemType, parse_grid_config, parse_overlay_config,
+};
+use crate::hevc::{DecodeMode, DecodedFrame};
+
+/// Default maximum number of derivation levels followed from the requested
+/// item, see [`DecoderLimits::max_derivation_depth`](crate::DecoderLimits::max_derivation_depth)
+pub const MAX_DERIVATION_DEPTH: usize = 16;
+
+/// Decode an image item, resolving derivation chains recursively
+///
+/// Handles coded items (hvc1, lhv1) as well as grid, overlay (iovl), identity
+/// (iden) and tone-map (tmap, base image only) derivations nested to any
+/// depth up to the container's derivation depth limit, e.g. iden → grid → hvc1 or
+/// iden → iovl → grid.
+pub fn decode_image_item(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+) -> Result<DecodedFrame, HeicError> {
+    decode_image_item_with_mode(container, item_id, DecodeMode::Full)
+}
+
+/// Decode an image item like [`decode_image_item`] with the given
+/// reconstruction quality for its coded images
+///
+/// Multi-layer images are always decoded in full.
+pub fn decode_image_item_with_mode(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+    mode: DecodeMode,
+) -> Result<DecodedFrame, HeicError> {
+    let mut chain = Vec::new();
+    decode_derived(container, item_id, mode, &mut chain)
+}
+
+/// Decode one level of a derivation chain; `chain` holds the items above it
+fn decode_derived(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+    mode: DecodeMode,
+    chain: &mut Vec<u32>,
+) -> Result<DecodedFrame, HeicError> {
+    if chain.contains(&item_id) {
+        return Err(HeicError::InvalidData("Cyclic image derivation"));
+    }
+    // Levels of the chain including this item
+    container.limits().check_derivation_depth(chain.len() as u64 + 1)?;
+
+    let item = container
+        .get_item(item_id)
+        .ok_or(HeicError::InvalidData("Derived image input not found"))
+        .map_err(container.locate_item_error(item_id))?;
+
+    chain.push(item_id);
+    let frame = decode_item_content(container, &item, mode, chain);
+    chain.pop();
+
+    let mut frame = frame.map_err(container.locate_item_error(item_id))?;
+    apply_transforms(container, item_id, &mut frame).map_err(container.locate_item_error(item_id))?;
+    Ok(frame)
+}
+
+/// Decode the image content of an item before its own transforms are applied
+fn decode_item_content(
+    container: &HeifContainer<'_>,
+    item: &Item,
+    mode: DecodeMode,
+    chain: &mut Vec<u32>,
+) -> Result<DecodedFrame, HeicError> {
+    match item.item_type {
+        ItemType::Hvc1 | ItemType::Lhv1 if is_layered(container, item.id) => {
+            decode_layered_item(container, item.id)
+        }
+        ItemType::Hvc1 => decode_tile(container, item.id, mode),
+        ItemType::Grid => {
+            let grid_bytes = item_payload(container, item.id)
+                .ok_or(HeicError::InvalidData("Missing grid item data"))?;
+            let grid_config = parse_grid_config(&grid_bytes)?;
+            decode_grid(container, item.id, &grid_config, mode)
+        }
+        ItemType::Iovl => {
+            let overlay_bytes = item_payload(container, item.id)
+                .ok_or(HeicError::InvalidData("Missing overlay item data"))?;
+            let overlay_config = parse_overlay_config(&overlay_bytes)?;
+            let (width, height) = (overlay_config.output_width, overlay_config.output_height);
+            container.limits().check_dimensions(width, height)?;
+
+            let mut inputs = Vec::new();
+            for input_id in derivation_inputs(container, item.id)? {
+                let mut input = decode_derived(container, input_id, mode, chain)?;
+                if let Some(alpha_id) = container.alpha_item_id(input_id) {
+                    let alpha = decode_derived(container, alpha_id, mode, chain)?;
+                    attach_alpha(&mut input, &alpha);
+                }
+                inputs.push(input);
+            }
+            if let Some(first) = inputs.first() {
+                container.limits().check_frame(width, height, first.chroma_format)?;
+            }
+            composite_overlay(&overlay_config, &inputs)
+        }
+        ItemType::Iden => match derivation_inputs(container, item.id)?.as_slice() {
+            &[input_id] => decode_derived(container, input_id, mode, chain),
+            _ => Err(HeicError::InvalidData(
+                "Identity item must have exactly one dimg input",
+            )),
+        },
+        // The SDR rendition of a tone-mapped item is its base image; use
+        // `heif::gainmap` to reconstruct the HDR rendition
+        ItemType::Tmap => match derivation_inputs(container, item.id)?.as_slice() {
+            &[base_id, _gain_map_id] => decode_derived(container, base_id, mode, chain),
+            _ => Err(HeicError::InvalidData("tmap item must have two dimg inputs")),
+        },
+        _ => Err(HeicError::Unsupported("Item is not a decodable image")),
+    }
+}
+
+/// Get the `dimg` inputs of a derived item
+fn derivation_inputs(container: &HeifContainer<'_>, item_id: u32) -> Result<Vec<u32>, HeicError> {
+    container
+        .get_tile_item_ids(item_id)
+        .ok_or(HeicError::InvalidData("Derived image has no dimg references in iref"))
+}
+
+/// Get item data, concatenating multiple extents if needed
+pub(crate) fn item_payload(container: &HeifContainer<'_>, item_id: u32) -> Option<Vec<u8>> {
+    container
+        .get_item_data(item_id)
+        .map(|d| d.to_vec())
+        .or_else(|| container.get_item_data_owned(item_id))
+}
+
+/// Apply an item's transformative properties in ipma order
+pub(crate) fn apply_transforms(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+    frame: &mut DecodedFrame,
+) -> Result<(), HeicError> {
+    for prop in container.item_properties(item_id) {
+        match *prop {
+            ItemProperty::CleanAperture(ref clap) => {
+                let rect = clap.crop_rect(frame.cropped_width(), frame.cropped_height());
+                if let Some((left, top, width, height)) = rect {
+                    frame.crop_to(left, top, width, height);
+                }
+            }
+            ItemProperty::Rotation(quarter_turns) => {
+                if quarter_turns % 2 == 1 && frame.chroma_format == 2 {
+                    return Err(HeicError::Unsupported("irot rotation of 4:2:2 images"));
+                }
+                frame.rotate_ccw(quarter_turns);
+            }
+            ItemProperty::Mirror(axis) => frame.mirror(axis),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Attach the luma plane of a decoded alpha image to `frame`
+///
+/// The alpha image is rescaled (nearest neighbour) to the frame's cropped size
+/// and converted to the frame's bit depth. Samples outside the conformance
+/// window are opaque.
+pub(crate) fn attach_alpha(frame: &mut DecodedFrame, alpha: &DecodedFrame) {
+    let max = (1u32 << frame.bit_depth) - 1;
+    let width = frame.cropped_width();
+    let height = frame.cropped_height();
+    let alpha_width = alpha.cropped_width();
+    let alpha_height = alpha.cropped_height();
+
+    let mut plane = vec![max as u16; frame.y_plane.len()];
+    if width > 0 && height > 0 && alpha_width > 0 && alpha_height > 0 {
+        for y in 0..height {
+            let ay = (y as u64 * alpha_height as u64 / height as u64) as u32 + alpha.crop_top;
+            let row = ((y + frame.crop_top) * frame.width) as usize;
+            for x in 0..width {
+                let ax = (x as u64 * alpha_width as u64 / width as u64) as u32 + alpha.crop_left;
+                let value = alpha.get_y(ax, ay) as u32;
+                let value = if alpha.bit_depth >= frame.bit_depth {
+                    value >> (alpha.bit_depth - frame.bit_depth)
+                } else {
+                    value << (frame.bit_depth - alpha.bit_depth)
+                };
+                plane[row + (x + frame.crop_left) as usize] = value.min(max) as u16;
+            }
+        }
+    }
+
+    frame.alpha_plane = Some(plane);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Limit, LimitExceeded};
+    use crate::heif::parse;
+    use crate::heif::test_util::make_box;
+
+    /// Build a minimal HEIF file with `iden` items and the given dimg references
+    fn iden_file(item_ids: &[u16], refs: &[(u16, u16)]) -> Vec<u8> {
+        let mut iinf = vec![0, 0, 0, 0];
+        iinf.extend_from_slice(&(item_ids.len() as u16).to_be_bytes());
+        for &id in item_ids {
+            let mut infe = vec![2, 0, 0, 0];
+            infe.extend_from_slice(&id.to_be_bytes());
+            infe.extend_from_slice(&[0, 0]);
+            infe.extend_from_slice(b"iden\0");
+            iinf.extend(make_box(b"infe", &infe));
+        }
+
+        let mut iref = vec![0, 0, 0, 0];
+        for &(from, to) in refs {
+            let mut dimg = from.to_be_bytes().to_vec();
+            dimg.extend_from_slice(&1u16.to_be_bytes());
+            dimg.extend_from_slice(&to.to_be_bytes());
+            iref.extend(make_box(b"dimg", &dimg));
+        }
+
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend(make_box(b"pitm", &[0, 0, 0, 0, 0, item_ids[0] as u8]));
+        meta.extend(make_box(b"iinf", &iinf));
+        meta.extend(make_box(b"iref", &iref));
+
+        let mut file = make_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        file.extend(make_box(b"meta", &meta));
+        file
+    }
+
+    #[test]
+    fn test_cyclic_derivation_is_rejected() {
+        let data = iden_file(&[1, 2], &[(1, 2), (2, 1)]);
+        let container = parse(&data).unwrap();
+        let err = decode_image_item(&container, 1).unwrap_err();
+        assert!(matches!(
+            err.without_location(),
+            HeicError::InvalidData("Cyclic image derivation")
+        ));
+        // Reported at the item whose input closes the cycle
+        assert_eq!(err.location().and_then(|l| l.item_id), Some(2));
+    }
+
+    #[test]
+    fn test_derivation_depth_is_limited() {
+        let ids: Vec<u16> = (1..=MAX_DERIVATION_DEPTH as u16 + 2).collect();
+        let refs: Vec<(u16, u16)> = ids.windows(2).map(|w| (w[0], w[1])).collect();
+        let data = iden_file(&ids, &refs);
+        let container = parse(&data).unwrap();
+        let err = decode_image_item(&container, 1).unwrap_err();
+        assert!(matches!(
+            err.without_location(),
+            HeicError::LimitExceeded(LimitExceeded {
+                limit: Limit::DerivationDepth,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_transforms_rotate_and_mirror() {
+        let mut frame = DecodedFrame::with_params(4, 2, 8, 3);
+        for (i, v) in frame.y_plane.iter_mut().enumerate() {
+            *v = i as u16;
+        }
+        frame.set_crop(1, 0, 0, 0);
+
+        // 90 degrees anticlockwise: the right column becomes the top row
+        frame.rotate_ccw(1);
+        assert_eq!((frame.width, frame.height), (2, 4));
+        assert_eq!(frame.y_plane, vec![3, 7, 2, 6, 1, 5, 0, 4]);
+        assert_eq!((frame.cropped_width(), frame.cropped_height()), (2, 3));
+        assert_eq!(frame.crop_bottom, 1);
+
+        frame.mirror(1);
+        assert_eq!(frame.y_plane, vec![0, 4, 1, 5, 2, 6, 3, 7]);
+        assert_eq!((frame.crop_top, frame.crop_bottom), (1, 0));
+    }
+
+    #[test]
+    fn test_clean_aperture_centred_crop() {
+        let clap = crate::heif::CleanAperture {
+            width: (100, 1),
+            height: (50, 1),
+            horiz_off: (0, 1),
+            vert_off: (0, 1),
+        };
+        assert_eq!(clap.crop_rect(200, 100), Some((50, 25, 100, 50)));
+    }
+}
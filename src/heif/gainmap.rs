@@ -0,0 +1,682 @@
+//! HDR gain map reconstruction
+//!
+//! HDR HEICs store an SDR base image plus a gain map describing how much
+//! brighter each pixel should be on an HDR display. Two layouts are
+//! supported:
+//!
+//! - ISO 21496-1: a `tmap` derived item whose `dimg` inputs are the base
+//!   image and the gain map, with the gain map metadata as item payload.
+//! - Apple: an auxiliary image of the primary with type
+//!   `urn:com:apple:photo:2020:aux:hdrgainmap`, whose headroom is stored in
+//!   the gain map's XMP or in the Apple MakerNote of the primary's Exif.
+
+use alloc::vec::Vec;
+use core::str;
+
+use crate::error::HeicError;
+use crate::heif::{AuxiliaryKind, HeifContainer, ItemType, decode_image_item};
+use crate::hevc::DecodedFrame;
+#[cfg(not(feature = "std"))]
+use crate::math::F32Ext as _;
+
+/// Per-channel ISO 21496-1 gain map parameters (log2 domain)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GainMapChannel {
+    /// log2 of the gain at gain map value 0
+    pub gain_map_min: f32,
+    /// log2 of the gain at gain map value 1
+    pub gain_map_max: f32,
+    /// Gain map encoding gamma
+    pub gamma: f32,
+    /// Offset added to base samples before applying the gain
+    pub base_offset: f32,
+    /// Offset subtracted from the result after applying the gain
+    pub alternate_offset: f32,
+}
+
+/// ISO 21496-1 gain map metadata (tmap item payload)
+#[derive(Debug, Clone, PartialEq)]
+pub struct GainMapMetadata {
+    /// Whether the gain map has one channel per colour component
+    pub is_multichannel: bool,
+    /// Whether the gain is applied in the base image's colour space
+    pub use_base_colour_space: bool,
+    /// log2 headroom of the base image
+    pub base_hdr_headroom: f32,
+    /// log2 headroom of the alternate (fully adapted) image
+    pub alternate_hdr_headroom: f32,
+    /// Channel parameters (replicated when the gain map has one channel)
+    pub channels: [GainMapChannel; 3],
+}
+
+/// Parse ISO 21496-1 gain map metadata from a tmap item payload
+pub fn parse_gain_map_metadata(data: &[u8]) -> Result<GainMapMetadata, HeicError> {
+    let mut pos = 0;
+    let mut take = |n: usize| -> Result<&[u8], HeicError> {
+        let bytes = data
+            .get(pos..pos + n)
+            .ok_or(HeicError::InvalidData("tmap metadata too short"))?;
+        pos += n;
+        Ok(bytes)
+    };
+
+    if take(1)?[0] != 0 {
+        return Err(HeicError::Unsupported("tmap metadata version"));
+    }
+    let minimum_version = u16::from_be_bytes(take(2)?.try_into().unwrap());
+    let _writer_version = u16::from_be_bytes(take(2)?.try_into().unwrap());
+    if minimum_version != 0 {
+        return Err(HeicError::Unsupported("tmap metadata minimum version"));
+    }
+
+    let flags = take(1)?[0];
+    let is_multichannel = (flags & 0x80) != 0;
+    let use_base_colour_space = (flags & 0x40) != 0;
+
+    let mut read_u32 = || -> Result<u32, HeicError> {
+        Ok(u32::from_be_bytes(take(4)?.try_into().unwrap()))
+    };
+    let mut fraction = |signed: bool| -> Result<f32, HeicError> {
+        let numerator = read_u32()?;
+        let denominator = read_u32()?;
+        if denominator == 0 {
+            return Err(HeicError::InvalidData("tmap metadata zero denominator"));
+        }
+        let numerator = if signed {
+            numerator as i32 as f64
+        } else {
+            numerator as f64
+        };
+        Ok((numerator / denominator as f64) as f32)
+    };
+
+    let base_hdr_headroom = fraction(false)?;
+    let alternate_hdr_headroom = fraction(false)?;
+
+    let channel_count = if is_multichannel { 3 } else { 1 };
+    let mut channels = [GainMapChannel {
+        gain_map_min: 0.0,
+        gain_map_max: 0.0,
+        gamma: 1.0,
+        base_offset: 0.0,
+        alternate_offset: 0.0,
+    }; 3];
+    for channel in channels.iter_mut().take(channel_count) {
+        *channel = GainMapChannel {
+            gain_map_min: fraction(true)?,
+            gain_map_max: fraction(true)?,
+            gamma: fraction(false)?,
+            base_offset: fraction(true)?,
+            alternate_offset: fraction(true)?,
+        };
+        if channel.gamma <= 0.0 {
+            return Err(HeicError::InvalidData("tmap metadata non-positive gamma"));
+        }
+    }
+    if !is_multichannel {
+        channels[1] = channels[0];
+        channels[2] = channels[0];
+    }
+
+    Ok(GainMapMetadata {
+        is_multichannel,
+        use_base_colour_space,
+        base_hdr_headroom,
+        alternate_hdr_headroom,
+        channels,
+    })
+}
+
+/// How a gain map is encoded
+#[derive(Debug, Clone, PartialEq)]
+pub enum GainMapSource {
+    /// ISO 21496-1 `tmap` derived item
+    Iso21496 {
+        /// The tmap item ID
+        tmap_item_id: u32,
+        /// Parsed gain map metadata
+        metadata: GainMapMetadata,
+    },
+    /// Apple auxiliary gain map
+    Apple {
+        /// Linear headroom of the fully applied gain map, if signalled
+        headroom: Option<f32>,
+    },
+}
+
+/// Gain map attached to the primary image
+#[derive(Debug, Clone, PartialEq)]
+pub struct GainMapInfo {
+    /// SDR base image item
+    pub base_item_id: u32,
+    /// Gain map image item
+    pub gain_map_item_id: u32,
+    /// Gain map layout and parameters
+    pub source: GainMapSource,
+}
+
+/// Find the gain map for the primary image, if any
+///
+/// A `tmap` item takes precedence over an Apple auxiliary gain map.
+pub fn find_gain_map(container: &HeifContainer<'_>) -> Result<Option<GainMapInfo>, HeicError> {
+    let primary = container.primary_item_id;
+
+    for info in &container.item_infos {
+        if ItemType::from(info.item_type) != ItemType::Tmap {
+            continue;
+        }
+        let Some(inputs) = container.get_tile_item_ids(info.item_id) else {
+            continue;
+        };
+        if let &[base_item_id, gain_map_item_id] = inputs.as_slice()
+            && (base_item_id == primary || info.item_id == primary)
+        {
+            let payload = container
+                .get_item_data(info.item_id)
+                .map(|d| d.to_vec())
+                .or_else(|| container.get_item_data_owned(info.item_id))
+                .ok_or(HeicError::InvalidData("Missing tmap item data"))?;
+            return Ok(Some(GainMapInfo {
+                base_item_id,
+                gain_map_item_id,
+                source: GainMapSource::Iso21496 {
+                    tmap_item_id: info.item_id,
+                    metadata: parse_gain_map_metadata(&payload)?,
+                },
+            }));
+        }
+    }
+
+    let apple_gain_map = container
+        .get_auxiliary_item_ids(primary)
+        .into_iter()
+        .find(|&id| {
+            container
+                .auxiliary_type(id)
+                .is_some_and(|aux| aux.kind() == AuxiliaryKind::HdrGainMap)
+        });
+
+    Ok(apple_gain_map.map(|gain_map_item_id| GainMapInfo {
+        base_item_id: primary,
+        gain_map_item_id,
+        source: GainMapSource::Apple {
+            headroom: apple_xmp_headroom(container, gain_map_item_id)
+                .or_else(|| apple_maker_note_headroom(container, primary)),
+        },
+    }))
+}
+
+/// Output sample format for HDR reconstruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdrFormat {
+    /// IEEE 754 half precision, stored as raw bits
+    F16,
+    /// IEEE 754 single precision
+    F32,
+}
+
+/// HDR sample data (interleaved RGBA)
+#[derive(Debug, Clone)]
+pub enum HdrData {
+    /// Half precision samples as raw bits
+    F16(Vec<u16>),
+    /// Single precision samples
+    F32(Vec<f32>),
+}
+
+/// Linear-light HDR image
+///
+/// Samples are linear RGB in the base image's colour primaries, scaled so
+/// that SDR diffuse white is 1.0. Values above 1.0 use the display headroom.
+#[derive(Debug, Clone)]
+pub struct HdrImage {
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Interleaved RGBA samples
+    pub data: HdrData,
+    /// Display headroom the gain map was applied for (linear ratio)
+    pub headroom: f32,
+}
+
+/// Reconstruct the HDR rendition of the primary image for a display headroom
+///
+/// `display_headroom` is the linear ratio of the display's peak brightness
+/// to SDR white (e.g. 4.0 for two stops). Without a gain map the base image
+/// is returned in linear light.
+pub fn decode_hdr(
+    container: &HeifContainer<'_>,
+    display_headroom: f32,
+    format: HdrFormat,
+) -> Result<HdrImage, HeicError> {
+    let gain_map = find_gain_map(container)?;
+    let base_item_id = gain_map
+        .as_ref()
+        .map_or(container.primary_item_id, |g| g.base_item_id);
+
+    let base = decode_image_item(container, base_item_id)?;
+    let width = base.cropped_width();
+    let height = base.cropped_height();
+    let mut rgba = linear_rgba(&base);
+
+    if let Some(ref gain_map) = gain_map {
+        let gain_frame = decode_image_item(container, gain_map.gain_map_item_id)?;
+        let gains = resample_rgb_signal(&gain_frame, width, height);
+        let log2_display = display_headroom.max(1.0).log2();
+
+        match gain_map.source {
+            GainMapSource::Iso21496 { ref metadata, .. } => {
+                let range = metadata.alternate_hdr_headroom - metadata.base_hdr_headroom;
+                let weight = if range == 0.0 {
+                    0.0
+                } else {
+                    ((log2_display - metadata.base_hdr_headroom) / range).clamp(0.0, 1.0)
+                };
+                for (px, gain) in rgba.chunks_exact_mut(4).zip(&gains) {
+                    for c in 0..3 {
+                        let ch = &metadata.channels[c];
+                        let g = gain[c].clamp(0.0, 1.0).powf(1.0 / ch.gamma);
+                        let log_gain = ch.gain_map_min * (1.0 - g) + ch.gain_map_max * g;
+                        px[c] = (px[c] + ch.base_offset) * (log_gain * weight).exp2()
+                            - ch.alternate_offset;
+                    }
+                }
+            }
+            GainMapSource::Apple { headroom } => {
+                let headroom = headroom
+                    .ok_or(HeicError::InvalidData("Missing Apple HDR headroom metadata"))?;
+                let weight = if headroom > 1.0 {
+                    (log2_display / headroom.log2()).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                for (px, gain) in rgba.chunks_exact_mut(4).zip(&gains) {
+                    // Apple gain maps are single channel with the gain map's transfer curve
+                    let g = gain_frame.colorspace.apply_eotf(gain[0].clamp(0.0, 1.0));
+                    let scale = (1.0 + (headroom - 1.0) * g).powf(weight);
+                    for v in &mut px[..3] {
+                        *v *= scale;
+                    }
+                }
+            }
+        }
+    }
+
+    let data = match format {
+        HdrFormat::F32 => HdrData::F32(rgba),
+        HdrFormat::F16 => HdrData::F16(rgba.iter().map(|&v| f32_to_f16_bits(v)).collect()),
+    };
+
+    Ok(HdrImage {
+        width,
+        height,
+        data,
+        headroom: if gain_map.is_some() {
+            display_headroom.max(1.0)
+        } else {
+            1.0
+        },
+    })
+}
+
+/// Convert a frame to interleaved linear RGBA (alpha from the alpha plane, else 1.0)
+fn linear_rgba(frame: &DecodedFrame) -> Vec<f32> {
+    let width = frame.cropped_width();
+    let height = frame.cropped_height();
+    let alpha_scale = 1.0 / ((1u32 << frame.bit_depth) - 1) as f32;
+    let mut out = Vec::with_capacity(width as usize * height as usize * 4);
+
+    for y in frame.crop_top..frame.height - frame.crop_bottom {
+        for x in frame.crop_left..frame.width - frame.crop_right {
+            let (r, g, b) = signal_rgb(frame, x, y);
+            let cs = &frame.colorspace;
+            out.push(cs.apply_eotf(r.clamp(0.0, 1.0)));
+            out.push(cs.apply_eotf(g.clamp(0.0, 1.0)));
+            out.push(cs.apply_eotf(b.clamp(0.0, 1.0)));
+            out.push(frame.alpha_plane.as_ref().map_or(1.0, |alpha| {
+                alpha[(y * frame.width + x) as usize] as f32 * alpha_scale
+            }));
+        }
+    }
+    out
+}
+
+/// Non-linear RGB signal of the sample at absolute frame position (x, y)
+fn signal_rgb(frame: &DecodedFrame, x: u32, y: u32) -> (f32, f32, f32) {
+    let (sub_x, sub_y) = frame.chroma_subsampling();
+    let (cb, cr) = if frame.chroma_format == 0 {
+        let neutral = 1 << (frame.bit_depth - 1);
+        (neutral, neutral)
+    } else {
+        (frame.get_cb(x / sub_x, y / sub_y), frame.get_cr(x / sub_x, y / sub_y))
+    };
+    frame
+        .colorspace
+        .ycbcr_to_rgb(frame.get_y(x, y), cb, cr, frame.bit_depth)
+}
+
+/// Bilinearly resample a frame's RGB signal to `width` x `height`
+fn resample_rgb_signal(frame: &DecodedFrame, width: u32, height: u32) -> Vec<[f32; 3]> {
+    let src_w = frame.cropped_width();
+    let src_h = frame.cropped_height();
+    let mut out = Vec::with_capacity(width as usize * height as usize);
+    if src_w == 0 || src_h == 0 {
+        out.resize(width as usize * height as usize, [0.0; 3]);
+        return out;
+    }
+
+    let mut src = Vec::with_capacity(src_w as usize * src_h as usize);
+    for y in 0..src_h {
+        for x in 0..src_w {
+            let (r, g, b) = signal_rgb(frame, x + frame.crop_left, y + frame.crop_top);
+            src.push([r, g, b]);
+        }
+    }
+    if src_w == width && src_h == height {
+        return src;
+    }
+
+    let at = |x: u32, y: u32| src[(y * src_w + x) as usize];
+    let x_ratio = src_w as f32 / width as f32;
+    let y_ratio = src_h as f32 / height as f32;
+    for y in 0..height {
+        let fy = ((y as f32 + 0.5) * y_ratio - 0.5).clamp(0.0, (src_h - 1) as f32);
+        let y0 = fy as u32;
+        let y1 = (y0 + 1).min(src_h - 1);
+        let wy = fy - y0 as f32;
+        for x in 0..width {
+            let fx = ((x as f32 + 0.5) * x_ratio - 0.5).clamp(0.0, (src_w - 1) as f32);
+            let x0 = fx as u32;
+            let x1 = (x0 + 1).min(src_w - 1);
+            let wx = fx - x0 as f32;
+
+            let mut px = [0.0; 3];
+            for (c, v) in px.iter_mut().enumerate() {
+                let top = at(x0, y0)[c] * (1.0 - wx) + at(x1, y0)[c] * wx;
+                let bottom = at(x0, y1)[c] * (1.0 - wx) + at(x1, y1)[c] * wx;
+                *v = top * (1.0 - wy) + bottom * wy;
+            }
+            out.push(px);
+        }
+    }
+    out
+}
+
+/// Read the headroom from `HDRGainMap:HDRGainMapHeadroom` in XMP attached to the gain map
+///
+/// The XMP value is in stops, so the linear headroom is 2^value.
+fn apple_xmp_headroom(container: &HeifContainer<'_>, gain_map_item_id: u32) -> Option<f32> {
+    const KEY: &str = "HDRGainMapHeadroom";
+
+    container
+        .get_metadata_item_ids(gain_map_item_id)
+        .into_iter()
+        .filter(|&id| {
+            container.item_infos.iter().any(|info| {
+                info.item_id == id
+                    && ItemType::from(info.item_type) == ItemType::Mime
+                    && info.content_type == "application/rdf+xml"
+            })
+        })
+        .find_map(|id| {
+            let xmp = container
+                .get_item_data(id)
+                .map(|d| d.to_vec())
+                .or_else(|| container.get_item_data_owned(id))?;
+            let xmp = str::from_utf8(&xmp).ok()?;
+
+            // Either an attribute (KEY="1.2") or an element (<ns:KEY>1.2</ns:KEY>)
+            let rest = &xmp[xmp.find(KEY)? + KEY.len()..];
+            let value = rest.trim_start_matches(['=', '"', '>']);
+            let end = value
+                .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+                .unwrap_or(value.len());
+            let stops: f32 = value[..end].parse().ok()?;
+            Some(stops.max(0.0).exp2())
+        })
+}
+
+/// Derive the headroom from Apple MakerNote tags 33 and 48 of the primary's Exif
+///
+/// Follows Apple's published mapping from the two tags to headroom stops.
+fn apple_maker_note_headroom(container: &HeifContainer<'_>, primary: u32) -> Option<f32> {
+    container
+        .get_metadata_item_ids(primary)
+        .into_iter()
+        .filter(|&id| {
+            container
+                .item_infos
+                .iter()
+                .any(|info| info.item_id == id && ItemType::from(info.item_type) == ItemType::Exif)
+        })
+        .find_map(|id| {
+            let exif = container
+                .get_item_data(id)
+                .map(|d| d.to_vec())
+                .or_else(|| container.get_item_data_owned(id))?;
+            let (maker33, maker48) = apple_maker_note_tags(&exif)?;
+
+            let stops = match (maker33 < 1.0, maker48 <= 0.01) {
+                (true, true) => -20.0 * maker48 + 1.8,
+                (true, false) => -0.101 * maker48 + 1.601,
+                (false, true) => -70.0 * maker48 + 3.0,
+                (false, false) => -0.303 * maker48 + 2.303,
+            };
+            Some(stops.max(0.0).exp2())
+        })
+}
+
+/// Minimal TIFF reader over an Exif payload
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl Tiff<'_> {
+    fn u16_at(&self, pos: usize) -> Option<u16> {
+        let b: [u8; 2] = self.data.get(pos..pos + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32_at(&self, pos: usize) -> Option<u32> {
+        let b: [u8; 4] = self.data.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    /// Find an IFD entry by tag; returns (type, count, value/offset field position)
+    fn find_entry(&self, ifd_offset: usize, tag: u16) -> Option<(u16, u32, usize)> {
+        let count = self.u16_at(ifd_offset)? as usize;
+        (0..count.min(512)).find_map(|i| {
+            let entry = ifd_offset + 2 + i * 12;
+            (self.u16_at(entry)? == tag)
+                .then(|| Some((self.u16_at(entry + 2)?, self.u32_at(entry + 4)?, entry + 8)))
+                .flatten()
+        })
+    }
+
+    /// Read a RATIONAL (5) or SRATIONAL (10) value stored at `base + offset`
+    fn rational(&self, ifd_offset: usize, tag: u16, base: usize) -> Option<f32> {
+        let (ty, _count, field) = self.find_entry(ifd_offset, tag)?;
+        let pos = base + self.u32_at(field)? as usize;
+        let (num, den) = (self.u32_at(pos)?, self.u32_at(pos + 4)?);
+        match (ty, den) {
+            (_, 0) => None,
+            (5, _) => Some((num as f64 / den as f64) as f32),
+            (10, _) => Some((num as i32 as f64 / den as i32 as f64) as f32),
+            _ => None,
+        }
+    }
+}
+
+/// Extract Apple MakerNote tags 33 and 48 from an Exif item payload
+fn apple_maker_note_tags(exif_item: &[u8]) -> Option<(f32, f32)> {
+    // Exif item: 4-byte offset to the TIFF header, then the Exif data
+    let header_offset = u32::from_be_bytes(exif_item.get(..4)?.try_into().ok()?) as usize;
+    let tiff_data = exif_item.get(4usize.checked_add(header_offset)?..)?;
+    let little_endian = match tiff_data.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let tiff = Tiff {
+        data: tiff_data,
+        little_endian,
+    };
+
+    let ifd0 = tiff.u32_at(4)? as usize;
+    let (_, _, exif_ptr) = tiff.find_entry(ifd0, 0x8769)?;
+    let exif_ifd = tiff.u32_at(exif_ptr)? as usize;
+    let (_, note_len, note_ptr) = tiff.find_entry(exif_ifd, 0x927C)?;
+    let note_start = tiff.u32_at(note_ptr)? as usize;
+    let note = tiff_data.get(note_start..note_start.checked_add(note_len as usize)?)?;
+
+    // Apple MakerNote: "Apple iOS\0", version (2 bytes), byte order, then an IFD
+    // whose offsets are relative to the start of the MakerNote
+    if !note.starts_with(b"Apple iOS\0") {
+        return None;
+    }
+    let maker = Tiff {
+        data: note,
+        little_endian: note.get(12..14)? == b"II",
+    };
+    Some((maker.rational(14, 33, 0)?, maker.rational(14, 48, 0)?))
+}
+
+/// Convert an f32 to IEEE 754 half precision bits (round to nearest even)
+fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    if exp == 0xFF {
+        // Inf or NaN
+        return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let half_exp = exp - 127 + 15;
+    if half_exp >= 0x1F {
+        return sign | 0x7C00;
+    }
+    if half_exp <= 0 {
+        if half_exp < -10 {
+            return sign;
+        }
+        // Subnormal half
+        let m = mantissa | 0x80_0000;
+        let shift = (14 - half_exp) as u32;
+        let half_m = m >> shift;
+        let round_bit = 1 << (shift - 1);
+        let rounded = if (m & round_bit) != 0 && ((m & (round_bit - 1)) != 0 || (half_m & 1) != 0) {
+            half_m + 1
+        } else {
+            half_m
+        };
+        return sign | rounded as u16;
+    }
+
+    let half = ((half_exp as u32) << 10) | (mantissa >> 13);
+    let round = mantissa & 0x1FFF;
+    let half = if round > 0x1000 || (round == 0x1000 && (half & 1) != 0) {
+        half + 1
+    } else {
+        half
+    };
+    sign | half as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn push_fraction(out: &mut Vec<u8>, num: i32, den: u32) {
+        out.extend_from_slice(&num.to_be_bytes());
+        out.extend_from_slice(&den.to_be_bytes());
+    }
+
+    #[test]
+    fn test_parse_single_channel_metadata() {
+        let mut data = vec![0, 0, 0, 0, 0, 0x40];
+        push_fraction(&mut data, 0, 1); // base headroom
+        push_fraction(&mut data, 3, 1); // alternate headroom
+        push_fraction(&mut data, 0, 1); // gain map min
+        push_fraction(&mut data, 3, 1); // gain map max
+        push_fraction(&mut data, 1, 1); // gamma
+        push_fraction(&mut data, 1, 64); // base offset
+        push_fraction(&mut data, 1, 64); // alternate offset
+
+        let metadata = parse_gain_map_metadata(&data).unwrap();
+        assert!(!metadata.is_multichannel);
+        assert!(metadata.use_base_colour_space);
+        assert_eq!(metadata.alternate_hdr_headroom, 3.0);
+        assert_eq!(metadata.channels[2].gain_map_max, 3.0);
+        assert_eq!(metadata.channels[1].base_offset, 1.0 / 64.0);
+    }
+
+    #[test]
+    fn test_parse_metadata_rejects_zero_denominator() {
+        let mut data = vec![0, 0, 0, 0, 0, 0];
+        push_fraction(&mut data, 1, 0);
+        assert!(parse_gain_map_metadata(&data).is_err());
+    }
+
+    #[test]
+    fn test_f32_to_f16_bits() {
+        assert_eq!(f32_to_f16_bits(0.0), 0x0000);
+        assert_eq!(f32_to_f16_bits(1.0), 0x3C00);
+        assert_eq!(f32_to_f16_bits(-2.0), 0xC000);
+        assert_eq!(f32_to_f16_bits(65504.0), 0x7BFF);
+        assert_eq!(f32_to_f16_bits(1.0e6), 0x7C00);
+        assert_eq!(f32_to_f16_bits(0.000_061_035_156), 0x0400);
+    }
+
+    #[test]
+    fn test_apple_maker_note_tags() {
+        // Big-endian TIFF: IFD0 -> ExifIFD -> MakerNote with tags 33 and 48
+        let mut note = b"Apple iOS\0\x00\x01MM".to_vec();
+        note.extend_from_slice(&2u16.to_be_bytes());
+        for (tag, offset) in [(33u16, 44u32), (48, 52)] {
+            note.extend_from_slice(&tag.to_be_bytes());
+            note.extend_from_slice(&10u16.to_be_bytes());
+            note.extend_from_slice(&1u32.to_be_bytes());
+            note.extend_from_slice(&offset.to_be_bytes());
+        }
+        note.extend_from_slice(&[0; 4]);
+        note.extend_from_slice(&1i32.to_be_bytes());
+        note.extend_from_slice(&2i32.to_be_bytes()); // 0.5
+        note.extend_from_slice(&1i32.to_be_bytes());
+        note.extend_from_slice(&100i32.to_be_bytes()); // 0.01
+
+        let mut tiff = b"MM\0\x2A".to_vec();
+        tiff.extend_from_slice(&8u32.to_be_bytes());
+        // IFD0 at 8: one entry pointing at ExifIFD (26)
+        tiff.extend_from_slice(&1u16.to_be_bytes());
+        tiff.extend_from_slice(&0x8769u16.to_be_bytes());
+        tiff.extend_from_slice(&4u16.to_be_bytes());
+        tiff.extend_from_slice(&1u32.to_be_bytes());
+        tiff.extend_from_slice(&26u32.to_be_bytes());
+        tiff.extend_from_slice(&[0; 4]);
+        // ExifIFD at 26: MakerNote at 44
+        tiff.extend_from_slice(&1u16.to_be_bytes());
+        tiff.extend_from_slice(&0x927Cu16.to_be_bytes());
+        tiff.extend_from_slice(&7u16.to_be_bytes());
+        tiff.extend_from_slice(&(note.len() as u32).to_be_bytes());
+        tiff.extend_from_slice(&44u32.to_be_bytes());
+        tiff.extend_from_slice(&[0; 4]);
+        tiff.extend_from_slice(&note);
+
+        let mut item = 0u32.to_be_bytes().to_vec();
+        item.extend_from_slice(&tiff);
+        assert_eq!(apple_maker_note_tags(&item), Some((0.5, 0.01)));
+    }
+}
@@ -2,88 +2,357 @@
 
 
 
-use crate::error::HeicError;
-use crate::heif::{HeifContainer, ImageGrid, ItemType};
-use crate::hevc::DecodedFrame;
-use crate::hevc::{decode_with_config, decode};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 
-#[cfg(feature = "parallel")]
-use rayon::prelude::*;
+use crate::error::{DamagedRegion, HeicError};
+use crate::heif::{HeifContainer, ImageGrid, ItemType};
+use crate::hevc::{DecodeMode, DecodedFrame};
 
 pub fn decode_grid<'a>(
     container: &HeifContainer<'a>,
     grid_item_id: u32,
     grid_config: &ImageGrid,
+    mode: DecodeMode,
+) -> Result<DecodedFrame, HeicError> {
+    let tile_ids = grid_tile_ids(container, grid_item_id, grid_config)?;
+
+    let results = decode_tiles(container, &tile_ids, mode);
+
+    let (tiles, failed) = if container.conceals_errors() {
+        replace_failed_tiles(container, &tile_ids, grid_config, results)?
+    } else {
+        (results.into_iter().collect::<Result<Vec<_>, _>>()?, Vec::new())
+    };
+
+    check_tiles(container, &tile_ids, &tiles)?;
+    let sizes: Vec<(u32, u32)> = tiles
+        .iter()
+        .map(|tile| (tile.cropped_width(), tile.cropped_height()))
+        .collect();
+    let (col_x, row_y) = tile_offsets(&sizes, grid_config)?;
+
+    // Create output frame at the grid's output dimensions
+    let out_width = grid_config.output_width;
+    let out_height = grid_config.output_height;
+    container
+        .limits()
+        .check_frame(out_width, out_height, tiles[0].chroma_format)?;
+    let (bit_depth, chroma_format) = (tiles[0].bit_depth, tiles[0].chroma_format);
+    let mut output = container
+        .pool()
+        .with(|context| context.new_frame(out_width, out_height, bit_depth, chroma_format));
+
+    let columns = grid_config.columns as usize;
+    for (idx, tile) in tiles.into_iter().enumerate() {
+        let row = idx / columns;
+        let col = idx % columns;
+        stitch_tile(&tile, &mut output, i64::from(col_x[col]), i64::from(row_y[row]))?;
+        container.pool().recycle(tile);
+    }
+
+    // Fill failed tiles from their neighbours, top to bottom
+    for (idx, cause) in failed {
+        let (x, y) = (col_x[idx % columns], row_y[idx / columns]);
+        let (width, height) = sizes[idx];
+        let width = width.min(out_width.saturating_sub(x));
+        let height = height.min(out_height.saturating_sub(y));
+        if width == 0 || height == 0 {
+            continue;
+        }
+        output.conceal(x, y, width, height);
+        output.damage.push(DamagedRegion { x, y, width, height, cause });
+    }
+
+    Ok(output)
+}
+
+/// Index and error of each grid tile that failed to decode
+type FailedTiles = Vec<(usize, Arc<HeicError>)>;
+
+/// Substitute blank frames for the tiles of a grid that failed to decode
+///
+/// Returns the tiles, with the index and error of each failed one. A
+/// substitute takes its size from the tile's `ispe`, or from the decoded
+/// tiles of its column and row, and its format from the first decoded
+/// tile. If no tile decoded, the first tile's error is returned.
+fn replace_failed_tiles(
+    container: &HeifContainer<'_>,
+    tile_ids: &[u32],
+    grid_config: &ImageGrid,
+    results: Vec<Result<DecodedFrame, HeicError>>,
+) -> Result<(Vec<DecodedFrame>, FailedTiles), HeicError> {
+    let columns = grid_config.columns as usize;
+    let decoded_size = |idx: usize| {
+        results[idx]
+            .as_ref()
+            .ok()
+            .map(|tile| (tile.cropped_width(), tile.cropped_height()))
+    };
+    let column_width = |col: usize| {
+        (col..results.len())
+            .step_by(columns)
+            .find_map(|idx| decoded_size(idx).map(|(width, _)| width))
+    };
+    let row_height = |row: usize| {
+        (row * columns..(row + 1) * columns)
+            .find_map(|idx| decoded_size(idx).map(|(_, height)| height))
+    };
+    let Some(first) = results.iter().find_map(|result| result.as_ref().ok()) else {
+        let err = results.into_iter().find_map(Result::err);
+        return Err(err.unwrap_or(HeicError::InvalidData("Grid has no tiles")));
+    };
+    let (bit_depth, chroma_format) = (first.bit_depth, first.chroma_format);
+    let colorspace = first.colorspace;
+    let fallback = (first.cropped_width(), first.cropped_height());
+    let sizes: Vec<(u32, u32)> = (0..results.len())
+        .map(|idx| {
+            let extents = container.get_item(tile_ids[idx]).and_then(|item| item.dimensions);
+            extents.or_else(|| decoded_size(idx)).unwrap_or((
+                column_width(idx % columns).unwrap_or(fallback.0),
+                row_height(idx / columns).unwrap_or(fallback.1),
+            ))
+        })
+        .collect();
+
+    let mut tiles = Vec::with_capacity(results.len());
+    let mut failed = Vec::new();
+    for (idx, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(tile) => tiles.push(tile),
+            Err(e) => {
+                let (width, height) = sizes[idx];
+                container.limits().check_frame(width, height, chroma_format)?;
+                let mut tile = DecodedFrame::with_params(width, height, bit_depth, chroma_format);
+                tile.colorspace = colorspace;
+                tiles.push(tile);
+                failed.push((idx, Arc::new(e)));
+            }
+        }
+    }
+    Ok((tiles, failed))
+}
+
+/// Decode the part of a grid image covering a rectangle of its output
+///
+/// Only the tiles intersecting the rectangle are decoded. The returned
+/// frame's crop window is exactly the rectangle; the frame itself may be
+/// slightly larger to keep chroma samples aligned. Tile positions come from
+/// the tiles' `ispe` properties; grids without them are decoded in full.
+pub(crate) fn decode_grid_region(
+    container: &HeifContainer<'_>,
+    grid_item_id: u32,
+    grid_config: &ImageGrid,
+    (x, y, width, height): (u32, u32, u32, u32),
 ) -> Result<DecodedFrame, HeicError> {
+    let tile_ids = grid_tile_ids(container, grid_item_id, grid_config)?;
+    let Some(sizes) = tile_extents(container, &tile_ids) else {
+        let mut frame = decode_grid(container, grid_item_id, grid_config, DecodeMode::Full)?;
+        frame.crop_to(x, y, width, height);
+        return Ok(frame);
+    };
+    let (col_x, row_y) = tile_offsets(&sizes, grid_config)?;
+
+    // Align the decoded area to even luma positions so chroma stays in phase
+    let x0 = x & !1;
+    let y0 = y & !1;
+    let x1 = (x + width).next_multiple_of(2);
+    let y1 = (y + height).next_multiple_of(2);
+
+    let columns = grid_config.columns as usize;
+    let selected: Vec<usize> = (0..tile_ids.len())
+        .filter(|&idx| {
+            let (tx, ty) = (col_x[idx % columns], row_y[idx / columns]);
+            let (tw, th) = sizes[idx];
+            tx < x1 && tx + tw > x0 && ty < y1 && ty + th > y0
+        })
+        .collect();
+    let selected_ids: Vec<u32> = selected.iter().map(|&idx| tile_ids[idx]).collect();
+
+    let results = decode_tiles(container, &selected_ids, DecodeMode::Full);
+
+    let tiles = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    check_tiles(container, &selected_ids, &tiles)?;
+    let first = tiles
+        .first()
+        .ok_or(HeicError::InvalidData("Region does not intersect the grid"))?;
+    container
+        .limits()
+        .check_frame(x1 - x0, y1 - y0, first.chroma_format)?;
+    let (bit_depth, chroma_format) = (first.bit_depth, first.chroma_format);
+    let mut output = container
+        .pool()
+        .with(|context| context.new_frame(x1 - x0, y1 - y0, bit_depth, chroma_format));
+
+    for (&idx, tile) in selected.iter().zip(tiles) {
+        let dst_x = i64::from(col_x[idx % columns]) - i64::from(x0);
+        let dst_y = i64::from(row_y[idx / columns]) - i64::from(y0);
+        stitch_tile(&tile, &mut output, dst_x, dst_y)?;
+        container.pool().recycle(tile);
+    }
+
+    output.set_crop(x - x0, x1 - x - width, y - y0, y1 - y - height);
+    Ok(output)
+}
+
+/// Get the (width, height) of every tile from its `ispe`, if all have one
+pub(crate) fn tile_extents(container: &HeifContainer<'_>, tile_ids: &[u32]) -> Option<Vec<(u32, u32)>> {
+    tile_ids
+        .iter()
+        .map(|&id| container.get_item(id).and_then(|item| item.dimensions))
+        .collect()
+}
+
+/// Get the tile item IDs of a grid, checking them against the grid layout
+///
+/// The layout itself is checked against the container's limits first, so
+/// no tile is decoded for a grid that could not be allocated.
+pub(crate) fn grid_tile_ids(
+    container: &HeifContainer<'_>,
+    grid_item_id: u32,
+    grid_config: &ImageGrid,
+) -> Result<Vec<u32>, HeicError> {
+    let limits = container.limits();
+    limits.check_tiles(u64::from(grid_config.rows) * u64::from(grid_config.columns))?;
+    limits.check_dimensions(grid_config.output_width, grid_config.output_height)?;
+
     // Get tile item IDs from iref 'dimg' reference
     let tile_ids = container
         .get_tile_item_ids(grid_item_id)
         .ok_or(HeicError::InvalidData("Grid has no dimg references in iref"))?;
 
     let expected_tiles = (grid_config.rows * grid_config.columns) as usize;
-    if tile_ids.len() != expected_tiles {
+    if tile_ids.is_empty() || tile_ids.len() != expected_tiles {
         return Err(HeicError::InvalidData(
             "Grid tile count mismatch with iref references",
         ));
     }
+    Ok(tile_ids)
+}
 
-    #[cfg(feature = "parallel")]
-    let tiles = decode_tiles_parallel(container, &tile_ids)?;
+/// Check that decoded tiles share a format and match their `ispe`
+fn check_tiles(
+    container: &HeifContainer<'_>,
+    tile_ids: &[u32],
+    tiles: &[DecodedFrame],
+) -> Result<(), HeicError> {
+    let Some(first) = tiles.first() else {
+        return Ok(());
+    };
+    for (&tile_id, tile) in tile_ids.iter().zip(tiles) {
+        if tile.bit_depth != first.bit_depth {
+            return Err(HeicError::InvalidData("Grid tiles differ in bit depth"));
+        }
+        if tile.chroma_format != first.chroma_format {
+            return Err(HeicError::InvalidData("Grid tiles differ in chroma format"));
+        }
+        let extents = container.get_item(tile_id).and_then(|item| item.dimensions);
+        if extents.is_some_and(|ext| ext != (tile.cropped_width(), tile.cropped_height())) {
+            return Err(HeicError::InvalidData("Tile dimensions disagree with its ispe"));
+        }
+    }
+    Ok(())
+}
 
-    #[cfg(not(feature = "parallel"))]
-    let tiles = decode_tiles_sequential(container, &tile_ids)?;
-    let tile_width = tiles[0].cropped_width();
-    let tile_height = tiles[0].cropped_height();
-    let bit_depth = tiles[0].bit_depth;
-    let chroma_format = tiles[0].chroma_format;
+/// Compute the output position of each tile column and row
+///
+/// `sizes` are the (width, height) of the tiles in raster order. Tiles are
+/// normally all the same size, but the tiles of the last column or row may
+/// be cropped to a smaller size. Every tile in a column must have the same
+/// width and every tile in a row the same height, and the tiles must cover
+/// the output image.
+pub(crate) fn tile_offsets(
+    sizes: &[(u32, u32)],
+    grid_config: &ImageGrid,
+) -> Result<(Vec<u32>, Vec<u32>), HeicError> {
+    let columns = grid_config.columns as usize;
+    let rows = grid_config.rows as usize;
 
-    // Create output frame at the grid's output dimensions
-    let out_width = grid_config.output_width;
-    let out_height = grid_config.output_height;
-    let mut output = DecodedFrame::with_params(out_width, out_height, bit_depth, chroma_format);
+    let mut col_x = Vec::with_capacity(columns);
+    let mut x = 0u32;
+    for &(width, _) in &sizes[..columns] {
+        col_x.push(x);
+        x = x.saturating_add(width);
+    }
+    let mut row_y = Vec::with_capacity(rows);
+    let mut y = 0u32;
+    for &(_, height) in sizes.iter().step_by(columns) {
+        row_y.push(y);
+        y = y.saturating_add(height);
+    }
+    if x < grid_config.output_width || y < grid_config.output_height {
+        return Err(HeicError::InvalidData("Grid tiles do not cover the output image"));
+    }
 
-    for (idx, tile) in tiles.iter().enumerate() {
-        let row = idx as u32 / grid_config.columns;
-        let col = idx as u32 % grid_config.columns;
-        let dst_x = col * tile_width;
-        let dst_y = row * tile_height;
+    for (idx, &(width, height)) in sizes.iter().enumerate() {
+        if width != sizes[idx % columns].0 {
+            return Err(HeicError::InvalidData("Grid tiles in a column differ in width"));
+        }
+        if height != sizes[idx - idx % columns].1 {
+            return Err(HeicError::InvalidData("Grid tiles in a row differ in height"));
+        }
+    }
+
+    Ok((col_x, row_y))
+}
 
-        stitch_tile(tile, &mut output, dst_x, dst_y, tile_width, tile_height);
+/// Decode tiles, in parallel unless the options ask for a single thread,
+/// returning the result of each
+fn decode_tiles(
+    container: &HeifContainer<'_>,
+    tile_ids: &[u32],
+    mode: DecodeMode,
+) -> Vec<Result<DecodedFrame, HeicError>> {
+    #[cfg(feature = "parallel")]
+    if container.options().threads != 1 {
+        return decode_tiles_parallel(container, tile_ids, mode);
     }
 
-    Ok(output)
+    decode_tiles_sequential(container, tile_ids, mode)
 }
 
+/// Decode tiles one after another, returning the result of each
 fn decode_tiles_sequential(
     container: &HeifContainer<'_>,
     tile_ids: &[u32],
-) -> Result<Vec<DecodedFrame>, HeicError> {
+    mode: DecodeMode,
+) -> Vec<Result<DecodedFrame, HeicError>> {
     tile_ids
         .iter()
-        .map(|&tile_id| decode_tile(container, tile_id))
+        .map(|&tile_id| decode_tile(container, tile_id, mode))
         .collect()
 }
 
+/// Decode tiles on the global thread pool, returning the result of each
+///
+/// At most the number of threads set in the options decode at once, by
+/// default up to 8.
 #[cfg(feature = "parallel")]
 fn decode_tiles_parallel(
     container: &HeifContainer<'_>,
     tile_ids: &[u32],
-) -> Result<Vec<DecodedFrame>, HeicError> {
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(8.min(rayon::current_num_threads()))
-        .build()
-        .map_err(|_| HeicError::InvalidData("Failed to create thread pool"))?;
-
-    pool.install(|| {
-        tile_ids
-            .par_iter()
-            .map(|&tile_id| decode_tile(container, tile_id))
-            .collect()
+    mode: DecodeMode,
+) -> Vec<Result<DecodedFrame, HeicError>> {
+    container.options().par_map(tile_ids.len(), 8, |i| {
+        decode_tile(container, tile_ids[i], mode)
     })
 }
 
 /// Decode a single tile item
-fn decode_tile(container: &HeifContainer<'_>, tile_id: u32) -> Result<DecodedFrame, HeicError> {
+pub(crate) fn decode_tile(
+    container: &HeifContainer<'_>,
+    tile_id: u32,
+    mode: DecodeMode,
+) -> Result<DecodedFrame, HeicError> {
+    decode_coded_tile(container, tile_id, mode).map_err(container.locate_item_error(tile_id))
+}
+
+fn decode_coded_tile(
+    container: &HeifContainer<'_>,
+    tile_id: u32,
+    mode: DecodeMode,
+) -> Result<DecodedFrame, HeicError> {
     let item = container
         .get_item(tile_id)
         .ok_or(HeicError::InvalidData("Tile item not found"))?;
@@ -93,18 +362,17 @@ fn decode_tile(container: &HeifContainer<'_>, tile_id: u32) -> Result<DecodedFra
     }
 
     // Try single-extent first, fall back to multi-extent
+    let options = container.options();
+    let decode = |image_data: &[u8]| {
+        container.pool().with(|context| match &item.hevc_config {
+            Some(config) => context.decode_with_config(config, image_data, mode, options),
+            None => context.decode(image_data, mode, options),
+        })
+    };
     let frame = if let Some(image_data) = container.get_item_data(tile_id) {
-        if let Some(ref config) = item.hevc_config {
-            decode_with_config(config, image_data).map_err(HeicError::HevcDecode)?
-        } else {
-            decode(image_data).map_err(HeicError::HevcDecode)?
-        }
+        decode(image_data)?
     } else if let Some(image_data) = container.get_item_data_owned(tile_id) {
-        if let Some(ref config) = item.hevc_config {
-            decode_with_config(config, &image_data).map_err(HeicError::HevcDecode)?
-        } else {
-            decode(&image_data).map_err(HeicError::HevcDecode)?
-        }
+        decode(&image_data)?
     } else {
         return Err(HeicError::InvalidData("Missing tile image data"));
     };
@@ -114,31 +382,35 @@ fn decode_tile(container: &HeifContainer<'_>, tile_id: u32) -> Result<DecodedFra
 
 /// Copy a decoded tile into the output frame at (dst_x, dst_y)
 ///
-/// Handles edge tiles that may extend beyond the grid output dimensions
-/// by only copying the portion that fits within output bounds.
+/// The position may be negative, and edge tiles may extend beyond the
+/// output dimensions; only the part of the tile inside the output is copied.
 fn stitch_tile(
     tile: &DecodedFrame,
     output: &mut DecodedFrame,
-    dst_x: u32,
-    dst_y: u32,
-    _tile_width: u32,
-    _tile_height: u32,
-) {
+    dst_x: i64,
+    dst_y: i64,
+) -> Result<(), HeicError> {
     let out_width = output.width;
     let out_height = output.height;
 
+    // Skip the part of the tile left of / above the output
+    let skip_x = u32::try_from((-dst_x).max(0)).unwrap_or(u32::MAX);
+    let skip_y = u32::try_from((-dst_y).max(0)).unwrap_or(u32::MAX);
+    let dst_x = u32::try_from(dst_x.max(0)).unwrap_or(u32::MAX);
+    let dst_y = u32::try_from(dst_y.max(0)).unwrap_or(u32::MAX);
+
     // Use cropped tile dimensions (conformance window)
-    let src_x_start = tile.crop_left;
-    let src_y_start = tile.crop_top;
-    let src_width = tile.cropped_width();
-    let src_height = tile.cropped_height();
+    let src_x_start = tile.crop_left + skip_x.min(tile.cropped_width());
+    let src_y_start = tile.crop_top + skip_y.min(tile.cropped_height());
+    let src_width = tile.cropped_width().saturating_sub(skip_x);
+    let src_height = tile.cropped_height().saturating_sub(skip_y);
 
     // Clamp to output bounds (edge tiles may extend past grid output size)
     let copy_width = src_width.min(out_width.saturating_sub(dst_x));
     let copy_height = src_height.min(out_height.saturating_sub(dst_y));
 
     if copy_width == 0 || copy_height == 0 {
-        return;
+        return Ok(());
     }
 
     // Copy luma plane
@@ -155,7 +427,7 @@ fn stitch_tile(
             .copy_from_slice(&tile.y_plane[src_start..src_start + copy_width as usize]);
     }
 
-    // Copy chroma planes (4:2:0 subsampled)
+    // Copy chroma planes
     if tile.chroma_format >= 1 {
         let (c_sub_x, c_sub_y) = match tile.chroma_format {
             1 => (2u32, 2u32), // 4:2:0
@@ -171,8 +443,9 @@ fn stitch_tile(
         let c_src_y = src_y_start / c_sub_y;
         let c_dst_x = dst_x / c_sub_x;
         let c_dst_y = dst_y / c_sub_y;
-        let c_copy_w = copy_width / c_sub_x;
-        let c_copy_h = copy_height / c_sub_y;
+        // Round up so odd-sized edges keep their last chroma sample
+        let c_copy_w = ((dst_x + copy_width).div_ceil(c_sub_x) - c_dst_x) as usize;
+        let c_copy_h = (dst_y + copy_height).div_ceil(c_sub_y) - c_dst_y;
 
         for row in 0..c_copy_h {
             let src_row = (c_src_y + row) as usize;
@@ -180,13 +453,147 @@ fn stitch_tile(
             let src_start = src_row * src_c_stride + c_src_x as usize;
             let dst_start = dst_row * dst_c_stride + c_dst_x as usize;
 
-            if dst_start + c_copy_w as usize <= output.cb_plane.len()
-                && src_start + c_copy_w as usize <= tile.cb_plane.len()
+            if dst_start + c_copy_w > output.cb_plane.len()
+                || src_start + c_copy_w > tile.cb_plane.len()
             {
-                output.cb_plane[dst_start..dst_start + c_copy_w as usize]
-                    .copy_from_slice(&tile.cb_plane[src_start..src_start + c_copy_w as usize]);
-                output.cr_plane[dst_start..dst_start + c_copy_w as usize]
-                    .copy_from_slice(&tile.cr_plane[src_start..src_start + c_copy_w as usize]);
+                return Err(HeicError::InvalidData("Grid tile chroma out of bounds"));
+            }
+            output.cb_plane[dst_start..dst_start + c_copy_w]
+                .copy_from_slice(&tile.cb_plane[src_start..src_start + c_copy_w]);
+            output.cr_plane[dst_start..dst_start + c_copy_w]
+                .copy_from_slice(&tile.cr_plane[src_start..src_start + c_copy_w]);
+        }
+    }
+
+    // Carry over the damaged regions of the copied area
+    for region in &tile.damage {
+        let x0 = region.x.max(src_x_start);
+        let y0 = region.y.max(src_y_start);
+        let x1 = (region.x + region.width).min(src_x_start + copy_width);
+        let y1 = (region.y + region.height).min(src_y_start + copy_height);
+        if x0 < x1 && y0 < y1 {
+            output.damage.push(DamagedRegion {
+                x: dst_x + x0 - src_x_start,
+                y: dst_y + y0 - src_y_start,
+                width: x1 - x0,
+                height: y1 - y0,
+                cause: region.cause.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heif::test_util::{TestItem, grid_tile, heif_file};
+    use crate::HeicDecoder;
+    use alloc::vec;
+
+    fn tile(width: u32, height: u32, crop_right: u32, value: u16) -> DecodedFrame {
+        let mut frame = DecodedFrame::with_params(width, height, 8, 1);
+        frame.crop_right = crop_right;
+        frame.y_plane.fill(value);
+        frame.cb_plane.fill(value);
+        frame.cr_plane.fill(value);
+        frame
+    }
+
+    #[test]
+    fn test_edge_tiles_with_different_cropping() {
+        // 2x1 grid: a 16-wide tile followed by an edge tile cropped to 5
+        let grid = ImageGrid {
+            columns: 2,
+            rows: 1,
+            output_width: 21,
+            output_height: 8,
+        };
+        let tiles = [tile(16, 8, 0, 1), tile(16, 8, 11, 2)];
+        let (col_x, row_y) = tile_offsets(&[(16, 8), (5, 8)], &grid).unwrap();
+        assert_eq!(col_x, [0, 16]);
+        assert_eq!(row_y, [0]);
+
+        let mut output = DecodedFrame::with_params(21, 8, 8, 1);
+        for (tile, &x) in tiles.iter().zip(&col_x) {
+            stitch_tile(tile, &mut output, i64::from(x), 0).unwrap();
+        }
+        assert_eq!(output.y_plane[15], 1);
+        assert_eq!(output.y_plane[20], 2);
+        // The last chroma column covers the odd luma column 20
+        assert_eq!(output.cb_plane[10], 2);
+    }
+
+    #[test]
+    fn test_stitch_tile_at_negative_offset() {
+        let mut tile = tile(8, 4, 0, 0);
+        for (i, v) in tile.y_plane.iter_mut().enumerate() {
+            *v = i as u16;
+        }
+        let mut output = DecodedFrame::with_params(4, 2, 8, 1);
+        stitch_tile(&tile, &mut output, -6, -2).unwrap();
+        // Tile samples (6..8, 2..4) land in the output's top-left corner
+        assert_eq!(&output.y_plane[..2], &[22, 23]);
+        assert_eq!(&output.y_plane[4..6], &[30, 31]);
+        assert_eq!(output.y_plane[2], 0);
+    }
+
+    #[test]
+    fn test_inconsistent_tile_sizes_rejected() {
+        let grid = ImageGrid {
+            columns: 1,
+            rows: 2,
+            output_width: 16,
+            output_height: 16,
+        };
+        assert!(tile_offsets(&[(16, 8), (12, 8)], &grid).is_err());
+
+        let grid = ImageGrid {
+            output_height: 20,
+            ..grid
+        };
+        assert!(tile_offsets(&[(16, 8), (16, 8)], &grid).is_err());
+    }
+
+    #[test]
+    fn test_stitch_tile_carries_damage() {
+        let mut tile = tile(8, 8, 2, 0);
+        let cause = Arc::new(HeicError::InvalidData("test"));
+        tile.damage.push(DamagedRegion { x: 0, y: 4, width: 8, height: 4, cause });
+        let mut output = DecodedFrame::with_params(10, 10, 8, 1);
+        stitch_tile(&tile, &mut output, 4, -2).unwrap();
+        // Clipped to the tile's crop window and the output
+        let region = &output.damage[0];
+        assert_eq!((region.x, region.y, region.width, region.height), (4, 2, 6, 4));
+    }
+
+    #[test]
+    fn test_failed_tile_is_concealed() {
+        // 2x2 grid of 64x64 tiles; the top-right tile's slice is cut short
+        let mut items = vec![TestItem::grid(1, (2, 2), (128, 128), &[2, 3, 4, 5])];
+        items.extend((0..4).map(|i| grid_tile(i as u16 + 2, i)));
+        let clean = heif_file(1, &items);
+        items[2].data.truncate(4 + 3);
+        items[2].data[..4].copy_from_slice(&3u32.to_be_bytes());
+        let damaged = heif_file(1, &items);
+
+        let decoder = HeicDecoder::new();
+        assert!(decoder.decode(&damaged).is_err());
+        let expected = decoder.decode(&clean).unwrap();
+        let result = decoder.decode_lenient(&damaged).unwrap();
+
+        let damage = &result.damage;
+        assert_eq!(damage.len(), 1);
+        assert_eq!((damage[0].x, damage[0].y, damage[0].width, damage[0].height), (64, 0, 64, 64));
+        // The other tiles are decoded as without the damage
+        let row = 128 * 3;
+        for (y, (got, want)) in result.image.data.chunks(row).zip(expected.data.chunks(row)).enumerate() {
+            if y < 64 {
+                assert_eq!(got[..row / 2], want[..row / 2]);
+                assert_ne!(got[row / 2..], want[row / 2..]);
+            } else {
+                assert_eq!(got, want);
             }
         }
     }
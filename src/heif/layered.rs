@@ -0,0 +1,283 @@
+//! Multi-layer images and stereo pairs
+//!
+//! MV-HEVC bitstreams carry several views as layers of one bitstream: the
+//! base layer is a regular HEVC picture and each further layer predicts
+//! from it. HEIF stores the layers either in one `hvc1` item whose `lhvC`
+//! property holds the parameter sets of the non-base layers, or in an
+//! `hvc1` base item plus an `lhv1` item that references it (`sbas`). The
+//! layer selector property (`lsel`) chooses the layer an item outputs.
+//!
+//! Stereo pairs may instead be stored as two independent items in a `ster`
+//! entity group.
+
+use alloc::vec::Vec;
+
+use super::boxes::FourCC;
+use crate::error::HeicError;
+use crate::heif::derivation::{apply_transforms, decode_image_item, item_payload};
+use crate::heif::{HeifContainer, HevcDecoderConfig, ItemProperty, ItemType};
+use crate::hevc::{self, DecodedFrame, bitstream};
+
+/// One view of a stereo pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eye {
+    /// Left view
+    Left,
+    /// Right view
+    Right,
+}
+
+/// Check if an item needs multi-layer decoding: an lhv1 item, or an item
+/// whose layer selector picks a non-base layer
+pub(crate) fn is_layered(container: &HeifContainer<'_>, item_id: u32) -> bool {
+    container
+        .get_item(item_id)
+        .is_some_and(|item| item.item_type == ItemType::Lhv1)
+        || layer_selector(container, item_id).is_some_and(|layer| layer > 0)
+}
+
+/// Decode the layer an item outputs (its `lsel` layer, or the highest layer)
+pub(crate) fn decode_layered_item(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+) -> Result<DecodedFrame, HeicError> {
+    let target = layer_selector(container, item_id);
+    decode_layers(container, item_id, target)?
+        .pop()
+        .ok_or(HeicError::InvalidData("No picture decoded"))
+}
+
+/// Decode the base layer and one further layer of a coded item
+///
+/// `layer_id` is the nuh_layer_id to decode alongside the base layer;
+/// `None` picks the highest layer present. Returns the base layer picture
+/// first, followed by the other layer's picture if there is one. Transforms
+/// are not applied.
+pub fn decode_layers(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+    layer_id: Option<u16>,
+) -> Result<Vec<DecodedFrame>, HeicError> {
+    let item = container
+        .get_item(item_id)
+        .ok_or(HeicError::InvalidData("Item not found"))?;
+    if !matches!(item.item_type, ItemType::Hvc1 | ItemType::Lhv1) {
+        return Err(HeicError::Unsupported(
+            "Layered decoding of a non-coded item",
+        ));
+    }
+
+    // An lhv1 item may leave the base layer to the hvc1 item it references
+    let base_item = if item.item_type == ItemType::Lhv1 {
+        container
+            .item_references
+            .iter()
+            .find(|r| {
+                r.from_item_id == item_id
+                    && (r.ref_type == FourCC::SBAS || r.ref_type == FourCC::BASE)
+            })
+            .and_then(|r| r.to_item_ids.first().copied())
+            .and_then(|id| container.get_item(id))
+    } else {
+        None
+    };
+    let layered_config = container.item_properties(item_id).find_map(|p| match p {
+        ItemProperty::LayeredHevcConfig(config) => Some(config),
+        _ => None,
+    });
+
+    // Parameter sets of every layer, then the base data, then the item's data
+    let mut configs: Vec<&HevcDecoderConfig> = Vec::new();
+    configs.extend(
+        base_item
+            .as_ref()
+            .and_then(|base| base.hevc_config.as_ref()),
+    );
+    configs.extend(item.hevc_config.as_ref());
+    configs.extend(layered_config);
+
+    let mut payloads = Vec::new();
+    if let Some(base) = &base_item {
+        let config = base
+            .hevc_config
+            .as_ref()
+            .ok_or(HeicError::InvalidData("Base layer item has no hvcC"))?;
+        let data = item_payload(container, base.id)
+            .ok_or(HeicError::InvalidData("Missing base layer image data"))?;
+        payloads.push((data, config.length_size_minus_one));
+    }
+    let config = item
+        .hevc_config
+        .as_ref()
+        .or(layered_config)
+        .ok_or(HeicError::InvalidData(
+            "Missing hvcC/lhvC for layered image",
+        ))?;
+    let data =
+        item_payload(container, item_id).ok_or(HeicError::InvalidData("Missing image data"))?;
+    payloads.push((data, config.length_size_minus_one));
+
+    let mut nal_units = Vec::new();
+    for config in &configs {
+        nal_units.extend(
+            config
+                .nal_units
+                .iter()
+                .filter_map(|data| bitstream::parse_single_nal(data).ok()),
+        );
+    }
+    for (data, length_size_minus_one) in &payloads {
+        nal_units.extend(bitstream::parse_length_prefixed_ext(
+            data,
+            *length_size_minus_one as usize + 1,
+        )?);
+    }
+
+    let target = match layer_id {
+        Some(id) => {
+            u8::try_from(id).map_err(|_| HeicError::InvalidData("Layer ID out of range"))?
+        }
+        None => nal_units
+            .iter()
+            .map(|nal| nal.nuh_layer_id)
+            .max()
+            .unwrap_or(0),
+    };
+    Ok(hevc::decode_layers(&nal_units, target, container.options())?)
+}
+
+/// Decode both views of the primary image as (left, right)
+///
+/// Uses a `ster` entity group containing the primary item if there is one.
+/// Otherwise the primary item must be a multi-layer (MV-HEVC) image; its
+/// base layer is taken as the left view, following the convention of
+/// stereo cameras that code the left eye as the base view.
+pub fn decode_stereo(container: &HeifContainer<'_>) -> Result<[DecodedFrame; 2], HeicError> {
+    if let Some((left, right)) = stereo_group(container) {
+        return Ok([
+            decode_image_item(container, left)?,
+            decode_image_item(container, right)?,
+        ]);
+    }
+
+    let item_id = container.primary_item_id;
+    let mut frames = decode_layers(container, item_id, None)?;
+    if frames.len() < 2 {
+        return Err(HeicError::Unsupported("Image has no second view"));
+    }
+    for frame in &mut frames {
+        apply_transforms(container, item_id, frame)?;
+    }
+    let right = frames.swap_remove(1);
+    let left = frames.swap_remove(0);
+    Ok([left, right])
+}
+
+/// Decode one view of the primary image
+///
+/// See [`decode_stereo`] for how the views are located. Decoding the left
+/// view of a multi-layer image only decodes the base layer.
+pub fn decode_eye(container: &HeifContainer<'_>, eye: Eye) -> Result<DecodedFrame, HeicError> {
+    if let Some((left, right)) = stereo_group(container) {
+        let item_id = if eye == Eye::Left { left } else { right };
+        return decode_image_item(container, item_id);
+    }
+
+    let item_id = container.primary_item_id;
+    let layer_id = if eye == Eye::Left { Some(0) } else { None };
+    let mut frames = decode_layers(container, item_id, layer_id)?;
+    if eye == Eye::Right && frames.len() < 2 {
+        return Err(HeicError::Unsupported("Image has no second view"));
+    }
+    let mut frame = frames
+        .pop()
+        .ok_or(HeicError::InvalidData("No picture decoded"))?;
+    apply_transforms(container, item_id, &mut frame)?;
+    Ok(frame)
+}
+
+/// The (left, right) members of a stereo pair group containing the primary item
+fn stereo_group(container: &HeifContainer<'_>) -> Option<(u32, u32)> {
+    container
+        .entity_groups_of(container.primary_item_id)
+        .find(|g| g.is_stereo_pair())
+        .and_then(|g| match g.entity_ids.as_slice() {
+            &[left, right, ..] => Some((left, right)),
+            _ => None,
+        })
+}
+
+/// The layer selector (lsel) of an item
+fn layer_selector(container: &HeifContainer<'_>, item_id: u32) -> Option<u16> {
+    container.item_properties(item_id).find_map(|p| match p {
+        ItemProperty::LayerSelector(layer) => Some(*layer),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HeicDecoder;
+    use crate::heif::test_util::{TestItem, entity_group, grid_tile, heif_file, heif_file_with_groups, make_box};
+    use crate::hevc::test_util::{StreamParams, TestCu};
+
+    /// Two-view image whose right view is the left one moved two luma
+    /// samples
+    fn stereo_item(id: u16) -> TestItem {
+        let cus: Vec<_> = (0..16).map(|i| TestCu::Intra(i % 5 * 4 - 8)).collect();
+        TestItem::stereo(id, &StreamParams::default(), &cus, 8)
+    }
+
+    #[test]
+    fn test_decode_multi_layer_views() {
+        let decoder = HeicDecoder::new();
+        let file = heif_file(1, &[stereo_item(1)]);
+        let pair = decoder.decode_stereo(&file).unwrap();
+        assert_ne!(pair.left.data, pair.right.data);
+        assert_eq!(decoder.decode_eye(&file, Eye::Left).unwrap().data, pair.left.data);
+        assert_eq!(decoder.decode_eye(&file, Eye::Right).unwrap().data, pair.right.data);
+        // The primary image is the base view
+        assert_eq!(decoder.decode(&file).unwrap().data, pair.left.data);
+
+        // Without the parameter sets of the second view (the lhvC), the
+        // left view still decodes since only the base layer is decoded
+        let mut item = stereo_item(1);
+        item.properties.remove(1);
+        let file = heif_file(1, &[item]);
+        assert_eq!(decoder.decode_eye(&file, Eye::Left).unwrap().data, pair.left.data);
+        assert!(decoder.decode_eye(&file, Eye::Right).is_err());
+        assert!(decoder.decode_stereo(&file).is_err());
+    }
+
+    #[test]
+    fn test_layer_selector() {
+        let decoder = HeicDecoder::new();
+        let pair = decoder.decode_stereo(&heif_file(1, &[stereo_item(1)])).unwrap();
+        for (layer, expected) in [(0u16, &pair.left), (1, &pair.right)] {
+            let mut item = stereo_item(1);
+            item.properties.push(make_box(b"lsel", &layer.to_be_bytes()));
+            let decoded = decoder.decode(&heif_file(1, &[item])).unwrap();
+            assert_eq!(decoded.data, expected.data, "layer {layer}");
+        }
+    }
+
+    #[test]
+    fn test_stereo_group_takes_priority() {
+        let decoder = HeicDecoder::new();
+        let views = decoder.decode_stereo(&heif_file(1, &[stereo_item(1)])).unwrap();
+
+        // A ster group pairs the multi-layer primary image with another one
+        let other = TestItem { hidden: false, ..grid_tile(2, 3) };
+        let group = entity_group(b"ster", 10, &[1, 2]);
+        let file = heif_file_with_groups(1, &[stereo_item(1), other], &[group]);
+        let right = decoder.decode_item(&file, 2).unwrap();
+        assert_ne!(right.data, views.right.data);
+
+        let pair = decoder.decode_stereo(&file).unwrap();
+        assert_eq!(pair.left.data, views.left.data);
+        assert_eq!(pair.right.data, right.data);
+        assert_eq!(decoder.decode_eye(&file, Eye::Left).unwrap().data, views.left.data);
+        assert_eq!(decoder.decode_eye(&file, Eye::Right).unwrap().data, right.data);
+    }
+}
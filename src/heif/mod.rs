@@ -4,9 +4,34 @@
 //! used by HEIF/HEIC files. The container consists of nested "boxes" that
 //! describe the file structure and contain image data.
 
+pub mod auxiliary;
 mod boxes;
+pub mod derivation;
+pub mod gainmap;
 pub mod grid;
+pub mod layered;
+pub mod overlay;
 mod parser;
+#[cfg(feature = "std")]
+pub mod reader;
+pub mod region;
+pub mod sequence;
+pub mod stream;
+#[cfg(test)]
+mod test_util;
 
-pub use boxes::{HevcDecoderConfig, ItemProperty};
-pub use parser::{HeifContainer, ImageGrid, Item, ItemType, parse, parse_grid_config};
+pub use auxiliary::{AuxiliaryData, AuxiliaryFormat, AuxiliaryImage};
+pub use boxes::{
+    AuxiliaryKind, AuxiliaryType, CleanAperture, EntityGroup, HevcDecoderConfig, ItemProperty,
+    SemanticMatte,
+};
+pub use derivation::{decode_image_item, decode_image_item_with_mode};
+pub use gainmap::{GainMapInfo, GainMapMetadata, HdrData, HdrFormat, HdrImage};
+pub use layered::Eye;
+#[cfg(feature = "std")]
+pub use reader::HeifReader;
+pub use parser::{
+    HeifContainer, ImageGrid, ImageHandle, ImageOverlay, Item, ItemType, parse, parse_grid_config,
+    parse_overlay_config, parse_with_limits,
+};
+pub use sequence::{Sample, SequenceFrame, SequenceFrames, Track};
@@ -0,0 +1,203 @@
+//! HEIF image overlay (iovl) decoder
+
+use alloc::vec;
+
+use crate::error::{DamagedRegion, HeicError};
+use crate::heif::ImageOverlay;
+use crate::hevc::DecodedFrame;
+
+/// Composite decoded overlay inputs onto the canvas
+///
+/// `inputs` are the decoded `dimg` references of the overlay item, in
+/// reference order, so later inputs cover earlier ones. Inputs carrying an
+/// alpha plane are alpha-blended onto the canvas.
+pub fn composite_overlay(
+    overlay: &ImageOverlay,
+    inputs: &[DecodedFrame],
+) -> Result<DecodedFrame, HeicError> {
+    let first = inputs
+        .first()
+        .ok_or(HeicError::InvalidData("Overlay has no input images"))?;
+    if overlay.offsets.len() < inputs.len() {
+        return Err(HeicError::InvalidData(
+            "Overlay offset count mismatch with iref references",
+        ));
+    }
+
+    let mut canvas = DecodedFrame::with_params(
+        overlay.output_width,
+        overlay.output_height,
+        first.bit_depth,
+        first.chroma_format,
+    );
+    canvas.colorspace = first.colorspace;
+    fill_canvas(&mut canvas, overlay.canvas_fill_value);
+
+    for (input, &(offset_x, offset_y)) in inputs.iter().zip(&overlay.offsets) {
+        if input.bit_depth != canvas.bit_depth || input.chroma_format != canvas.chroma_format {
+            return Err(HeicError::Unsupported(
+                "Overlay inputs with differing bit depth or chroma format",
+            ));
+        }
+        composite(&mut canvas, input, offset_x, offset_y);
+    }
+
+    Ok(canvas)
+}
+
+/// Fill the whole canvas with the overlay's 16-bit RGBA fill colour
+fn fill_canvas(canvas: &mut DecodedFrame, fill: [u16; 4]) {
+    let (y, cb, cr) = canvas.colorspace.rgb_to_ycbcr(
+        fill[0] as f32 / 65535.0,
+        fill[1] as f32 / 65535.0,
+        fill[2] as f32 / 65535.0,
+        canvas.bit_depth,
+    );
+    canvas.y_plane.fill(y);
+    canvas.cb_plane.fill(cb);
+    canvas.cr_plane.fill(cr);
+
+    // Only keep an alpha plane when the canvas itself is not opaque
+    if fill[3] != u16::MAX {
+        let alpha = fill[3] >> (16 - canvas.bit_depth);
+        canvas.alpha_plane = Some(vec![alpha; canvas.y_plane.len()]);
+    }
+}
+
+/// Blend `src` over `dst` with alpha `a` in [0, max]
+#[inline]
+fn blend(src: u16, dst: u16, a: u32, max: u32) -> u16 {
+    ((src as u32 * a + dst as u32 * (max - a) + max / 2) / max) as u16
+}
+
+/// Draw `input` onto `canvas` with its cropped top-left corner at (offset_x, offset_y)
+///
+/// Parts of the input outside the canvas are clipped.
+fn composite(canvas: &mut DecodedFrame, input: &DecodedFrame, offset_x: i32, offset_y: i32) {
+    let max = (1u32 << canvas.bit_depth) - 1;
+    let offset_x = offset_x as i64;
+    let offset_y = offset_y as i64;
+
+    // Intersection of the input with the canvas, in canvas luma coordinates
+    let x_start = offset_x.max(0);
+    let y_start = offset_y.max(0);
+    let x_end = (offset_x + input.cropped_width() as i64).min(canvas.width as i64);
+    let y_end = (offset_y + input.cropped_height() as i64).min(canvas.height as i64);
+    if x_start >= x_end || y_start >= y_end {
+        return;
+    }
+
+    // Damaged regions of the input, clipped to the canvas
+    for region in &input.damage {
+        let x = i64::from(region.x) - i64::from(input.crop_left) + offset_x;
+        let y = i64::from(region.y) - i64::from(input.crop_top) + offset_y;
+        let (x0, x1) = (x.max(x_start), (x + i64::from(region.width)).min(x_end));
+        let (y0, y1) = (y.max(y_start), (y + i64::from(region.height)).min(y_end));
+        if x0 < x1 && y0 < y1 {
+            canvas.damage.push(DamagedRegion {
+                x: x0 as u32,
+                y: y0 as u32,
+                width: (x1 - x0) as u32,
+                height: (y1 - y0) as u32,
+                cause: region.cause.clone(),
+            });
+        }
+    }
+
+    let input_alpha = |sx: u32, sy: u32| -> u32 {
+        input
+            .alpha_plane
+            .as_ref()
+            .map_or(max, |alpha| alpha[(sy * input.width + sx) as usize] as u32)
+    };
+
+    // Luma (and canvas alpha)
+    for y in y_start..y_end {
+        let sy = (y - offset_y) as u32 + input.crop_top;
+        let row = y as usize * canvas.width as usize;
+        for x in x_start..x_end {
+            let sx = (x - offset_x) as u32 + input.crop_left;
+            let a = input_alpha(sx, sy);
+            let idx = row + x as usize;
+            canvas.y_plane[idx] = blend(input.get_y(sx, sy), canvas.y_plane[idx], a, max);
+            if let Some(ref mut alpha) = canvas.alpha_plane {
+                alpha[idx] = (a + (alpha[idx] as u32 * (max - a) + max / 2) / max) as u16;
+            }
+        }
+    }
+
+    // Chroma: visit every canvas chroma sample whose co-sited luma sample is covered
+    if canvas.chroma_format == 0 {
+        return;
+    }
+    let (sub_x, sub_y) = canvas.chroma_subsampling();
+    let (sub_x, sub_y) = (sub_x as i64, sub_y as i64);
+    let c_stride = canvas.c_stride();
+
+    for cy in (y_start + sub_y - 1) / sub_y..(y_end + sub_y - 1) / sub_y {
+        let sy = (cy * sub_y - offset_y) as u32 + input.crop_top;
+        for cx in (x_start + sub_x - 1) / sub_x..(x_end + sub_x - 1) / sub_x {
+            let sx = (cx * sub_x - offset_x) as u32 + input.crop_left;
+            let a = input_alpha(sx, sy);
+            let (scx, scy) = (sx / sub_x as u32, sy / sub_y as u32);
+            let idx = cy as usize * c_stride + cx as usize;
+            canvas.cb_plane[idx] = blend(input.get_cb(scx, scy), canvas.cb_plane[idx], a, max);
+            canvas.cr_plane[idx] = blend(input.get_cr(scx, scy), canvas.cr_plane[idx], a, max);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heif::parse_overlay_config;
+
+    fn solid_frame(width: u32, height: u32, y: u16, c: u16) -> DecodedFrame {
+        let mut frame = DecodedFrame::with_params(width, height, 8, 1);
+        frame.y_plane.fill(y);
+        frame.cb_plane.fill(c);
+        frame.cr_plane.fill(c);
+        frame
+    }
+
+    #[test]
+    fn test_parse_overlay_config() {
+        let data = [
+            0, 0, // version, flags (16-bit fields)
+            0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF, // canvas fill: opaque red
+            0, 64, 0, 32, // 64x32 output
+            0, 0, 0, 0, // input 0 at (0, 0)
+            0xFF, 0xF0, 0, 8, // input 1 at (-16, 8)
+        ];
+        let overlay = parse_overlay_config(&data).unwrap();
+        assert_eq!(overlay.canvas_fill_value, [0xFFFF, 0, 0, 0xFFFF]);
+        assert_eq!((overlay.output_width, overlay.output_height), (64, 32));
+        assert_eq!(overlay.offsets, vec![(0, 0), (-16, 8)]);
+    }
+
+    #[test]
+    fn test_composite_clips_negative_offsets() {
+        let mut canvas = solid_frame(8, 8, 16, 128);
+        let input = solid_frame(4, 4, 200, 90);
+        composite(&mut canvas, &input, -2, 6);
+
+        assert_eq!(canvas.get_y(0, 6), 200);
+        assert_eq!(canvas.get_y(1, 7), 200);
+        assert_eq!(canvas.get_y(2, 6), 16);
+        assert_eq!(canvas.get_y(0, 5), 16);
+        assert_eq!(canvas.get_cb(0, 3), 90);
+        assert_eq!(canvas.get_cb(1, 3), 128);
+    }
+
+    #[test]
+    fn test_composite_alpha_blends() {
+        let mut canvas = solid_frame(4, 4, 0, 128);
+        canvas.alpha_plane = Some(vec![0; 16]);
+        let mut input = solid_frame(4, 4, 200, 128);
+        input.alpha_plane = Some(vec![51; 16]); // 20% opacity
+        composite(&mut canvas, &input, 0, 0);
+
+        assert_eq!(canvas.get_y(1, 1), 40);
+        assert_eq!(canvas.alpha_plane.as_ref().unwrap()[5], 51);
+    }
+}
@@ -1,14 +1,20 @@
 //! HEIF container parser
 
 use alloc::string::{String, ToString};
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::ops::Range;
 use core::str;
 
 use super::boxes::{
-    Box, BoxIterator, ColorInfo, FourCC, HevcDecoderConfig, ImageSpatialExtents, ItemInfo,
-    ItemLocation, ItemProperty, ItemReference, PropertyAssociation,
+    AuxiliaryType, Box, BoxIterator, CleanAperture, ColorInfo, EntityGroup, FourCC, HevcDecoderConfig, ImageSpatialExtents,
+    ItemInfo, ItemLocation, ItemProperty, ItemReference, PropertyAssociation,
 };
+use super::sequence::{HANDLER_PICT, Track, parse_moov};
 use crate::error::{HeicError, Result};
+use crate::hevc::ContextPool;
+use crate::limits::DecoderLimits;
+use crate::options::DecoderOptions;
 
 /// Parsed HEIF container
 #[derive(Debug)]
@@ -37,6 +43,10 @@ pub struct HeifContainer<'a> {
     pub property_associations: Vec<PropertyAssociation>,
     /// Item references (from iref box)
     pub item_references: Vec<ItemReference>,
+    /// Entity groups (from grpl box), e.g. alternatives, stereo pairs and bursts
+    pub entity_groups: Vec<EntityGroup>,
+    /// Tracks (from moov box), e.g. image sequences
+    pub tracks: Vec<Track>,
     /// Media data offset
     mdat_offset: Option<usize>,
     /// Media data length
@@ -45,6 +55,14 @@ pub struct HeifContainer<'a> {
     idat_offset: Option<usize>,
     /// Item data (idat) length
     idat_length: Option<usize>,
+    /// `data` holds only the metadata boxes, not the whole file
+    metadata_only: bool,
+    /// File-based item data read separately, e.g. by `HeifReader`
+    loaded_items: Vec<(u32, Vec<u8>)>,
+    /// Options applied while decoding, including the limits applied while parsing
+    options: DecoderOptions,
+    /// Decoder contexts whose buffers the container's images are decoded with
+    pool: Arc<ContextPool>,
 }
 
 /// Item type enumeration
@@ -52,12 +70,16 @@ pub struct HeifContainer<'a> {
 pub enum ItemType {
     /// HEVC coded image
     Hvc1,
+    /// Layered HEVC coded image (non-base layers, e.g. a second MV-HEVC view)
+    Lhv1,
     /// Image grid
     Grid,
     /// Image overlay
     Iovl,
     /// Identity transform
     Iden,
+    /// Tone-mapped derived image (ISO 21496-1 gain map)
+    Tmap,
     /// EXIF metadata
     Exif,
     /// MIME data
@@ -70,9 +92,11 @@ impl From<FourCC> for ItemType {
     fn from(fourcc: FourCC) -> Self {
         match &fourcc.0 {
             b"hvc1" => Self::Hvc1,
+            b"lhv1" => Self::Lhv1,
             b"grid" => Self::Grid,
             b"iovl" => Self::Iovl,
             b"iden" => Self::Iden,
+            b"tmap" => Self::Tmap,
             b"Exif" => Self::Exif,
             b"mime" => Self::Mime,
             _ => Self::Unknown(fourcc),
@@ -80,6 +104,68 @@ impl From<FourCC> for ItemType {
     }
 }
 
+impl ItemType {
+    /// Check if this item type is an image (coded or derived)
+    pub fn is_image(self) -> bool {
+        matches!(
+            self,
+            Self::Hvc1 | Self::Lhv1 | Self::Grid | Self::Iovl | Self::Iden | Self::Tmap
+        )
+    }
+}
+
+/// Public view of an image item and its relationships to other items
+#[derive(Debug, Clone)]
+pub struct ImageHandle {
+    /// Item ID
+    pub item_id: u32,
+    /// Item type
+    pub item_type: ItemType,
+    /// Item name
+    pub name: String,
+    /// Whether this is the primary item
+    pub is_primary: bool,
+    /// Coded image dimensions from ispe (before transformative properties)
+    pub dimensions: Option<(u32, u32)>,
+    /// All associated properties, in ipma order
+    pub properties: Vec<ItemProperty>,
+    /// Item this image is a thumbnail of ('thmb' reference)
+    pub thumbnail_of: Option<u32>,
+    /// Item this image is an auxiliary image of ('auxl' reference)
+    pub auxiliary_of: Option<u32>,
+    /// Input items this image is derived from ('dimg' references)
+    pub derived_from: Vec<u32>,
+}
+
+impl ImageHandle {
+    /// Output dimensions after applying clap and irot, if known
+    pub fn output_dimensions(&self) -> Option<(u32, u32)> {
+        let (mut width, mut height) = self.dimensions?;
+        for prop in &self.properties {
+            match prop {
+                ItemProperty::CleanAperture(clap) => {
+                    if let Some((_, _, w, h)) = clap.crop_rect(width, height) {
+                        (width, height) = (w, h);
+                    }
+                }
+                ItemProperty::Rotation(turns) if turns % 2 == 1 => {
+                    core::mem::swap(&mut width, &mut height);
+                }
+                _ => {}
+            }
+        }
+        Some((width, height))
+    }
+
+    /// Auxiliary type (auxC) of this image, if it is an auxiliary image
+    pub fn auxiliary_type(&self) -> Option<&AuxiliaryType> {
+        self.properties.iter().find_map(|p| match p {
+            ItemProperty::AuxiliaryType(aux) => Some(aux),
+            _ => None,
+        })
+    }
+}
+
 /// Parsed item with resolved properties
 #[derive(Debug)]
 pub struct Item {
@@ -116,8 +202,11 @@ impl<'a> HeifContainer<'a> {
 
         if let Some(assoc) = assoc {
             for &(prop_idx, _essential) in &assoc.properties {
-                let idx = prop_idx as usize - 1; // 1-based index in ipma
-                if let Some(prop) = self.properties.get(idx) {
+                // 1-based index in ipma; 0 means no property
+                let prop = (prop_idx as usize)
+                    .checked_sub(1)
+                    .and_then(|idx| self.properties.get(idx));
+                if let Some(prop) = prop {
                     match prop {
                         ItemProperty::ImageExtents(ext) => {
                             dimensions = Some((ext.width, ext.height));
@@ -142,45 +231,24 @@ impl<'a> HeifContainer<'a> {
 
     /// Get raw data for an item
     pub fn get_item_data(&self, item_id: u32) -> Option<&[u8]> {
-        let loc = self.item_locations.iter().find(|l| l.item_id == item_id)?;
-
-        if loc.extents.is_empty() {
-            return None;
+        if let Some(data) = self.loaded_item_data(item_id) {
+            return Some(data);
         }
+        let loc = self.item_locations.iter().find(|l| l.item_id == item_id)?;
 
-        // For single-extent items, return a direct slice
-        if loc.extents.len() == 1 {
-            let (offset, length) = loc.extents[0];
-            let length = length as usize;
-
-            match loc.construction_method {
-                0 => {
-                    // File-based: offset is absolute within file
-                    let abs_offset = (loc.base_offset + offset) as usize;
-                    if abs_offset + length <= self.data.len() {
-                        return Some(&self.data[abs_offset..abs_offset + length]);
-                    }
-                }
-                1 => {
-                    // idat-based: offset is within idat box content
-                    if let Some(idat_off) = self.idat_offset {
-                        let abs_offset = idat_off + (loc.base_offset + offset) as usize;
-                        if abs_offset + length <= self.data.len() {
-                            return Some(&self.data[abs_offset..abs_offset + length]);
-                        }
-                    }
-                }
-                _ => {} // construction_method=2 (item) not supported yet
-            }
-            return None;
+        // For single-extent items, return a direct slice; multi-extent
+        // items need get_item_data_owned
+        match loc.extents.as_slice() {
+            &[(offset, length)] => self.extent_range(loc, offset, length).map(|range| &self.data[range]),
+            _ => None,
         }
-
-        // For multi-extent items, use get_item_data_owned
-        None
     }
 
     /// Get raw data for an item, concatenating multiple extents if needed
     pub fn get_item_data_owned(&self, item_id: u32) -> Option<Vec<u8>> {
+        if let Some(data) = self.loaded_item_data(item_id) {
+            return Some(data.to_vec());
+        }
         let loc = self.item_locations.iter().find(|l| l.item_id == item_id)?;
 
         if loc.extents.is_empty() {
@@ -189,33 +257,267 @@ impl<'a> HeifContainer<'a> {
 
         let mut result = Vec::new();
         for &(offset, length) in &loc.extents {
-            let len = length as usize;
-            let abs_offset = match loc.construction_method {
-                0 => (loc.base_offset + offset) as usize,
-                1 => {
-                    self.idat_offset? + (loc.base_offset + offset) as usize
-                }
-                _ => return None,
-            };
-            if abs_offset + len > self.data.len() {
-                return None;
-            }
-            result.extend_from_slice(&self.data[abs_offset..abs_offset + len]);
+            let range = self.extent_range(loc, offset, length)?;
+            result.extend_from_slice(&self.data[range]);
         }
 
         Some(result)
     }
 
+    /// Byte range of an item extent within `data`, if it lies inside it
+    fn extent_range(&self, loc: &ItemLocation, offset: u64, length: u64) -> Option<Range<usize>> {
+        let offset = loc.base_offset.checked_add(offset)?;
+        let start = match loc.construction_method {
+            // File-based: offset is absolute within file
+            0 if !self.metadata_only => offset,
+            // idat-based: offset is within idat box content
+            1 => (self.idat_offset? as u64).checked_add(offset)?,
+            // construction_method=2 (item) not supported yet
+            _ => return None,
+        };
+        let start = usize::try_from(start).ok()?;
+        let end = if length == 0 {
+            // A zero length extends to the end of the file, or of the idat box
+            match loc.construction_method {
+                1 => self.idat_offset?.checked_add(self.idat_length?)?,
+                _ => self.data.len(),
+            }
+        } else {
+            start.checked_add(usize::try_from(length).ok()?)?
+        };
+        (start <= end && end <= self.data.len()).then_some(start..end)
+    }
+
+    /// Offset of the start of an item's data within the parsed input
+    pub fn item_data_offset(&self, item_id: u32) -> Option<u64> {
+        let loc = self.item_locations.iter().find(|l| l.item_id == item_id)?;
+        let &(offset, _) = loc.extents.first()?;
+        let offset = loc.base_offset.checked_add(offset)?;
+        match loc.construction_method {
+            0 => Some(offset),
+            1 => (self.idat_offset? as u64).checked_add(offset),
+            _ => None,
+        }
+    }
+
+    /// Add an item and the offset of its data to the location of an error
+    /// from decoding it
+    pub(crate) fn locate_item_error(
+        &self,
+        item_id: u32,
+    ) -> impl FnOnce(HeicError) -> HeicError + use<> {
+        let offset = self.item_data_offset(item_id);
+        move |e| match offset {
+            Some(offset) => e.for_item(item_id).at_offset(offset),
+            None => e.for_item(item_id),
+        }
+    }
+
+    /// Item data stored with [`Self::insert_item_data`]
+    fn loaded_item_data(&self, item_id: u32) -> Option<&[u8]> {
+        self.loaded_items
+            .iter()
+            .find(|(id, _)| *id == item_id)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    /// Mark the container as parsed from the metadata boxes only
+    ///
+    /// File-based item data is then only available once inserted with
+    /// [`Self::insert_item_data`].
+    pub(crate) fn set_metadata_only(&mut self) {
+        self.metadata_only = true;
+    }
+
+    /// Provide the complete data of an item read from elsewhere
+    pub(crate) fn insert_item_data(&mut self, item_id: u32, data: Vec<u8>) {
+        self.loaded_items.push((item_id, data));
+    }
+
+    /// Resource limits applied while parsing and decoding
+    pub fn limits(&self) -> &DecoderLimits {
+        &self.options.limits
+    }
+
+    /// Options the container's images are decoded with
+    pub fn options(&self) -> &DecoderOptions {
+        &self.options
+    }
+
+    /// Whether coded images are decoded leniently, concealing errors
+    pub fn conceals_errors(&self) -> bool {
+        self.options.conceals_errors()
+    }
+
+    /// Set the options the container's images are decoded with
+    ///
+    /// With [`Strictness::Lenient`](crate::Strictness::Lenient), grid tiles
+    /// and CTUs that fail to decode are filled from their neighbours and
+    /// listed in the decoded frame's `damage` instead of failing the decode.
+    pub(crate) fn set_options(&mut self, options: DecoderOptions) {
+        self.options = options;
+    }
+
+    /// Decoder contexts whose buffers the container's images are decoded with
+    pub(crate) fn pool(&self) -> &ContextPool {
+        &self.pool
+    }
+
+    /// Decode with the buffers of a pool kept beyond the container
+    pub(crate) fn set_pool(&mut self, pool: Arc<ContextPool>) {
+        self.pool = pool;
+    }
+
+    /// Raw file data the container was parsed from
+    pub(crate) fn file_data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Get the first decodable HEVC image sequence track, preferring 'pict' tracks
+    pub fn image_sequence_track(&self) -> Option<&Track> {
+        let mut tracks = self.tracks.iter().filter(|t| t.is_hevc_image_sequence());
+        tracks.clone().find(|t| t.handler == HANDLER_PICT).or_else(|| tracks.next())
+    }
+
     /// Get tile item IDs for a grid item (from iref 'dimg' references)
     pub fn get_tile_item_ids(&self, grid_item_id: u32) -> Option<Vec<u32>> {
         self.item_references.iter()
             .find(|r| r.from_item_id == grid_item_id && r.ref_type == FourCC::DIMG)
             .map(|r| r.to_item_ids.clone())
     }
+
+    /// Get handles for every non-hidden image item, in iinf order
+    pub fn image_handles(&self) -> Vec<ImageHandle> {
+        self.item_infos
+            .iter()
+            .filter(|info| !info.hidden && ItemType::from(info.item_type).is_image())
+            .filter_map(|info| self.image_handle(info.item_id))
+            .collect()
+    }
+
+    /// Get a handle for an image item by ID (including hidden items)
+    pub fn image_handle(&self, item_id: u32) -> Option<ImageHandle> {
+        let item = self.get_item(item_id)?;
+        if !item.item_type.is_image() {
+            return None;
+        }
+
+        let reference_to = |ref_type: FourCC| {
+            self.item_references
+                .iter()
+                .find(|r| r.from_item_id == item_id && r.ref_type == ref_type)
+                .and_then(|r| r.to_item_ids.first().copied())
+        };
+
+        Some(ImageHandle {
+            item_id,
+            item_type: item.item_type,
+            name: item.name,
+            is_primary: item_id == self.primary_item_id,
+            dimensions: item.dimensions,
+            properties: self.item_properties(item_id).cloned().collect(),
+            thumbnail_of: reference_to(FourCC::THMB),
+            auxiliary_of: reference_to(FourCC::AUXL),
+            derived_from: self.get_tile_item_ids(item_id).unwrap_or_default(),
+        })
+    }
+
+    /// Get all properties associated with an item, in ipma order
+    pub fn item_properties(&self, item_id: u32) -> impl Iterator<Item = &ItemProperty> + '_ {
+        self.property_associations
+            .iter()
+            .filter(move |a| a.item_id == item_id)
+            .flat_map(|a| a.properties.iter())
+            .filter_map(|&(prop_idx, _essential)| {
+                (prop_idx as usize).checked_sub(1).and_then(|idx| self.properties.get(idx))
+            })
+    }
+
+    /// Get the auxiliary type (auxC) of an item, if it is an auxiliary image
+    pub fn auxiliary_type(&self, item_id: u32) -> Option<&AuxiliaryType> {
+        self.item_properties(item_id).find_map(|p| match p {
+            ItemProperty::AuxiliaryType(aux) => Some(aux),
+            _ => None,
+        })
+    }
+
+    /// Get IDs of auxiliary items ('auxl' references) attached to an item
+    pub fn get_auxiliary_item_ids(&self, item_id: u32) -> Vec<u32> {
+        self.item_references
+            .iter()
+            .filter(|r| r.ref_type == FourCC::AUXL && r.to_item_ids.contains(&item_id))
+            .map(|r| r.from_item_id)
+            .collect()
+    }
+
+    /// Get IDs of metadata items ('cdsc' references, e.g. Exif or XMP) describing an item
+    pub fn get_metadata_item_ids(&self, item_id: u32) -> Vec<u32> {
+        self.item_references
+            .iter()
+            .filter(|r| r.ref_type == FourCC::CDSC && r.to_item_ids.contains(&item_id))
+            .map(|r| r.from_item_id)
+            .collect()
+    }
+
+    /// Get the entity groups an item belongs to
+    pub fn entity_groups_of(&self, item_id: u32) -> impl Iterator<Item = &EntityGroup> + '_ {
+        self.entity_groups
+            .iter()
+            .filter(move |g| g.entity_ids.contains(&item_id))
+    }
+
+    /// Get alternatives for an item from 'altr' groups, in order of preference
+    ///
+    /// The item itself is not included. Members of an 'altr' group are listed
+    /// in the writer's order of preference, so callers should try them in turn.
+    pub fn alternative_item_ids(&self, item_id: u32) -> Vec<u32> {
+        let mut alternatives = Vec::new();
+        for group in self.entity_groups_of(item_id).filter(|g| g.is_alternatives()) {
+            for &id in &group.entity_ids {
+                if id != item_id && !alternatives.contains(&id) && self.get_item(id).is_some() {
+                    alternatives.push(id);
+                }
+            }
+        }
+        alternatives
+    }
+
+    /// Get the alpha plane item for an item, if present
+    pub fn alpha_item_id(&self, item_id: u32) -> Option<u32> {
+        self.get_auxiliary_item_ids(item_id)
+            .into_iter()
+            .find(|&id| self.auxiliary_type(id).is_some_and(AuxiliaryType::is_alpha))
+    }
 }
 
 /// Parse a HEIF container
 pub fn parse(data: &[u8]) -> Result<HeifContainer<'_>> {
+    parse_with_limits(data, DecoderLimits::default())
+}
+
+/// Parse a HEIF container, enforcing `limits` while parsing and decoding
+pub fn parse_with_limits(data: &[u8], limits: DecoderLimits) -> Result<HeifContainer<'_>> {
+    parse_top_level(data, limits, None)
+}
+
+/// Parse top-level boxes copied out of a file, where `file_offsets` holds
+/// the offset in the file of each box of `data`
+///
+/// Error locations then refer to the file rather than to `data`.
+#[cfg(feature = "std")]
+pub(crate) fn parse_relocated<'a>(
+    data: &'a [u8],
+    limits: DecoderLimits,
+    file_offsets: &[u64],
+) -> Result<HeifContainer<'a>> {
+    parse_top_level(data, limits, Some(file_offsets))
+}
+
+fn parse_top_level<'a>(
+    data: &'a [u8],
+    limits: DecoderLimits,
+    file_offsets: Option<&[u64]>,
+) -> Result<HeifContainer<'a>> {
     let mut container = HeifContainer {
         data,
         brand: FourCC(*b"    "),
@@ -229,17 +531,28 @@ pub fn parse(data: &[u8]) -> Result<HeifContainer<'_>> {
         color_infos: Vec::new(),
         property_associations: Vec::new(),
         item_references: Vec::new(),
+        entity_groups: Vec::new(),
+        tracks: Vec::new(),
         mdat_offset: None,
         mdat_length: None,
         idat_offset: None,
         idat_length: None,
+        metadata_only: false,
+        loaded_items: Vec::new(),
+        options: DecoderOptions::new().limits(limits),
+        pool: Arc::default(),
     };
 
     // Parse top-level boxes
-    for top_box in BoxIterator::new(data) {
+    for (i, mut top_box) in BoxIterator::new(data).enumerate() {
+        if let Some(&offset) = file_offsets.and_then(|offsets| offsets.get(i)) {
+            top_box.file_offset = offset;
+        }
+        let locate = top_box.locate_error(None);
         match top_box.box_type() {
-            FourCC::FTYP => parse_ftyp(&top_box, &mut container)?,
-            FourCC::META => parse_meta(&top_box, &mut container)?,
+            FourCC::FTYP => parse_ftyp(&top_box, &mut container).map_err(locate)?,
+            FourCC::META => parse_meta(&top_box, &mut container).map_err(locate)?,
+            FourCC::MOOV => container.tracks = parse_moov(&top_box, data, container.limits()).map_err(locate)?,
             FourCC::MDAT => {
                 container.mdat_offset = Some(top_box.header.content_offset);
                 container.mdat_length = Some(top_box.content.len());
@@ -302,17 +615,18 @@ fn parse_meta(meta: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
         return Err(HeicError::InvalidContainer("meta box too short"));
     }
 
-    let content = &meta.content[4..];
     // Base offset for child boxes within this meta content (for idat resolution)
     let meta_content_base = meta.header.content_offset + 4;
 
-    for child in BoxIterator::new(content) {
+    for child in meta.children(4) {
+        let locate = child.locate_error(None);
         match child.box_type() {
-            FourCC::PITM => parse_pitm(&child, container)?,
-            FourCC::ILOC => parse_iloc(&child, container)?,
-            FourCC::IINF => parse_iinf(&child, container)?,
-            FourCC::IPRP => parse_iprp(&child, container)?,
-            FourCC::IREF => parse_iref(&child, container)?,
+            FourCC::PITM => parse_pitm(&child, container).map_err(locate)?,
+            FourCC::ILOC => parse_iloc(&child, container).map_err(locate)?,
+            FourCC::IINF => parse_iinf(&child, container).map_err(locate)?,
+            FourCC::IPRP => parse_iprp(&child, container).map_err(locate)?,
+            FourCC::IREF => parse_iref(&child, container).map_err(locate)?,
+            FourCC::GRPL => parse_grpl(&child, container),
             FourCC::IDAT => {
                 // Store absolute file offset for idat content
                 container.idat_offset = Some(meta_content_base + child.header.content_offset);
@@ -355,69 +669,49 @@ fn parse_iloc(iloc: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
     }
 
     let version = content[0];
-    let offset_size = (content[4] >> 4) & 0xF;
-    let length_size = content[4] & 0xF;
-    let base_offset_size = (content[5] >> 4) & 0xF;
-    let index_size = if version >= 1 { content[5] & 0xF } else { 0 };
+    let offset_size = usize::from((content[4] >> 4) & 0xF);
+    let length_size = usize::from(content[4] & 0xF);
+    let base_offset_size = usize::from((content[5] >> 4) & 0xF);
+    let index_size = if version >= 1 { usize::from(content[5] & 0xF) } else { 0 };
+    if [offset_size, length_size, base_offset_size, index_size]
+        .iter()
+        .any(|&size| size > 8)
+    {
+        return Err(HeicError::InvalidContainer("iloc field size too large"));
+    }
+    let extent_size = index_size + offset_size + length_size;
 
-    let mut pos = 6;
-
-    let item_count = if version < 2 {
-        let count = u16::from_be_bytes([content[pos], content[pos + 1]]) as u32;
-        pos += 2;
-        count
-    } else {
-        let count = u32::from_be_bytes([
-            content[pos],
-            content[pos + 1],
-            content[pos + 2],
-            content[pos + 3],
-        ]);
-        pos += 4;
-        count
-    };
+    let mut reader = ByteReader::new(content, 6, "iloc truncated");
+    let item_count = if version < 2 { u32::from(reader.u16()?) } else { reader.u32()? };
+    container.limits().check_items(item_count.into())?;
 
     for _ in 0..item_count {
-        let item_id = if version < 2 {
-            let id = u16::from_be_bytes([content[pos], content[pos + 1]]) as u32;
-            pos += 2;
-            id
-        } else {
-            let id = u32::from_be_bytes([
-                content[pos],
-                content[pos + 1],
-                content[pos + 2],
-                content[pos + 3],
-            ]);
-            pos += 4;
-            id
-        };
+        let item_id = if version < 2 { u32::from(reader.u16()?) } else { reader.u32()? };
 
         let construction_method = if version >= 1 {
-            let method = content[pos + 1] & 0xF;
-            pos += 2;
-            method
+            (reader.u16()? & 0xF) as u8
         } else {
             0
         };
 
         // Data reference index (2 bytes) - skip
-        pos += 2;
+        reader.skip(2)?;
 
-        let base_offset = read_sized_int(content, &mut pos, base_offset_size as usize);
+        let base_offset = reader.uint(base_offset_size)?;
 
-        let extent_count = u16::from_be_bytes([content[pos], content[pos + 1]]);
-        pos += 2;
+        let extent_count = usize::from(reader.u16()?);
+        // Extents without any fields would repeat the same empty extent
+        if extent_size == 0 && extent_count > 1 {
+            return Err(HeicError::InvalidContainer("iloc extents have no fields"));
+        }
 
-        let mut extents = Vec::with_capacity(extent_count as usize);
+        let mut extents = Vec::with_capacity(extent_count.min(reader.remaining() / extent_size.max(1)));
         for _ in 0..extent_count {
-            if version >= 1 && index_size > 0 {
-                // Extent index - skip
-                pos += index_size as usize;
-            }
+            // Extent index - skip
+            reader.skip(index_size)?;
 
-            let extent_offset = read_sized_int(content, &mut pos, offset_size as usize);
-            let extent_length = read_sized_int(content, &mut pos, length_size as usize);
+            let extent_offset = reader.uint(offset_size)?;
+            let extent_length = reader.uint(length_size)?;
             extents.push((extent_offset, extent_length));
         }
 
@@ -432,17 +726,58 @@ fn parse_iloc(iloc: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
     Ok(())
 }
 
-fn read_sized_int(data: &[u8], pos: &mut usize, size: usize) -> u64 {
-    if size == 0 || *pos + size > data.len() {
-        return 0;
+/// Bounds-checked big-endian reader over box content
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Error reported when the content ends early
+    truncated: &'static str,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8], pos: usize, truncated: &'static str) -> Self {
+        Self { data, pos, truncated }
+    }
+
+    /// Number of bytes left
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    /// The bytes left, without consuming them
+    fn rest(&self) -> &'a [u8] {
+        self.data.get(self.pos..).unwrap_or(&[])
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or(HeicError::InvalidContainer(self.truncated))?;
+        self.pos += len;
+        Ok(bytes)
     }
 
-    let mut value = 0u64;
-    for i in 0..size {
-        value = (value << 8) | data[*pos + i] as u64;
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        self.uint(2).map(|v| v as u16)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.uint(4).map(|v| v as u32)
+    }
+
+    /// Read an unsigned integer of `size` bytes (0 to 8); 0 bytes read as 0
+    fn uint(&mut self, size: usize) -> Result<u64> {
+        Ok(self
+            .bytes(size)?
+            .iter()
+            .fold(0u64, |value, &b| (value << 8) | u64::from(b)))
     }
-    *pos += size;
-    value
 }
 
 fn parse_iinf(iinf: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
@@ -452,27 +787,13 @@ fn parse_iinf(iinf: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
     }
 
     let version = content[0];
-    let mut pos = 4;
-
-    let entry_count = if version == 0 {
-        let count = u16::from_be_bytes([content[pos], content[pos + 1]]) as u32;
-        pos += 2;
-        count
-    } else {
-        let count = u32::from_be_bytes([
-            content[pos],
-            content[pos + 1],
-            content[pos + 2],
-            content[pos + 3],
-        ]);
-        pos += 4;
-        count
-    };
+    let mut reader = ByteReader::new(content, 4, "iinf truncated");
+    let entry_count = if version == 0 { u32::from(reader.u16()?) } else { reader.u32()? };
+    container.limits().check_items(entry_count.into())?;
 
     // Parse infe boxes
-    let remaining = &content[pos..];
     let mut infe_count = 0;
-    for child in BoxIterator::new(remaining) {
+    for child in iinf.children(reader.pos) {
         if child.box_type() == FourCC::INFE
             && let Ok(info) = parse_infe(&child)
         {
@@ -497,50 +818,27 @@ fn parse_infe(infe: &Box<'_>) -> Result<ItemInfo> {
     let flags = u32::from_be_bytes([0, content[1], content[2], content[3]]);
     let hidden = (flags & 1) != 0;
 
-    let mut pos = 4;
-
-    let item_id = if version < 3 {
-        let id = u16::from_be_bytes([content[pos], content[pos + 1]]) as u32;
-        pos += 2;
-        id
-    } else {
-        let id = u32::from_be_bytes([
-            content[pos],
-            content[pos + 1],
-            content[pos + 2],
-            content[pos + 3],
-        ]);
-        pos += 4;
-        id
-    };
+    let mut reader = ByteReader::new(content, 4, "infe truncated");
+    let item_id = if version < 3 { u32::from(reader.u16()?) } else { reader.u32()? };
 
     // Item protection index (2 bytes) - skip
-    pos += 2;
+    reader.skip(2)?;
 
     let item_type = if version >= 2 {
-        let ft = FourCC::from_bytes(&content[pos..]).unwrap_or(FourCC(*b"    "));
-        pos += 4;
-        ft
+        FourCC::from_bytes(reader.bytes(4)?).unwrap_or(FourCC(*b"    "))
     } else {
         FourCC(*b"    ")
     };
 
     // Item name (null-terminated string)
-    let name_end = content[pos..].iter().position(|&b| b == 0).unwrap_or(0);
-    let item_name = str::from_utf8(&content[pos..pos + name_end])
-        .unwrap_or("")
-        .to_string();
-    pos += name_end + 1;
+    let rest = reader.rest();
+    let name_end = rest.iter().position(|&b| b == 0).unwrap_or(0);
+    let item_name = str::from_utf8(&rest[..name_end]).unwrap_or("").to_string();
+    let rest = rest.get(name_end + 1..).unwrap_or(&[]);
 
     // Content type (null-terminated string, optional)
-    let content_type = if pos < content.len() {
-        let ct_end = content[pos..].iter().position(|&b| b == 0).unwrap_or(0);
-        str::from_utf8(&content[pos..pos + ct_end])
-            .unwrap_or("")
-            .to_string()
-    } else {
-        String::new()
-    };
+    let ct_end = rest.iter().position(|&b| b == 0).unwrap_or(0);
+    let content_type = str::from_utf8(&rest[..ct_end]).unwrap_or("").to_string();
 
     Ok(ItemInfo {
         item_id,
@@ -552,10 +850,11 @@ fn parse_infe(infe: &Box<'_>) -> Result<ItemInfo> {
 }
 
 fn parse_iprp(iprp: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
-    for child in BoxIterator::new(iprp.content) {
+    for child in iprp.children(0) {
+        let locate = child.locate_error(None);
         match child.box_type() {
-            FourCC::IPCO => parse_ipco(&child, container)?,
-            FourCC::IPMA => parse_ipma(&child, container)?,
+            FourCC::IPCO => parse_ipco(&child, container).map_err(locate)?,
+            FourCC::IPMA => parse_ipma(&child, container).map_err(locate)?,
             _ => {}
         }
     }
@@ -564,7 +863,7 @@ fn parse_iprp(iprp: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
 
 fn parse_ipco(ipco: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
     // Properties are stored in order - index is implicit (1-based in ipma, 0-based here)
-    for child in BoxIterator::new(ipco.content) {
+    for child in ipco.children(0) {
         let prop = match child.box_type() {
             FourCC::ISPE => {
                 if let Ok(ext) = parse_ispe(&child) {
@@ -582,6 +881,14 @@ fn parse_ipco(ipco: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
                     ItemProperty::Unknown
                 }
             }
+            FourCC::LHVC => match parse_lhvc(&child) {
+                Ok(config) => ItemProperty::LayeredHevcConfig(config),
+                Err(_) => ItemProperty::Unknown,
+            },
+            FourCC::LSEL => match child.content {
+                &[hi, lo, ..] => ItemProperty::LayerSelector(u16::from_be_bytes([hi, lo])),
+                _ => ItemProperty::Unknown,
+            },
             FourCC::COLR => {
                 if let Ok(color) = parse_colr(&child) {
                     container.color_infos.push(color.clone()); // Keep deprecated for now
@@ -590,6 +897,28 @@ fn parse_ipco(ipco: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
                     ItemProperty::Unknown
                 }
             }
+            FourCC::AUXC => {
+                if let Ok(aux) = parse_auxc(&child) {
+                    ItemProperty::AuxiliaryType(aux)
+                } else {
+                    ItemProperty::Unknown
+                }
+            }
+            FourCC::IROT => match child.content.first() {
+                Some(&b) => ItemProperty::Rotation(b & 0x3),
+                None => ItemProperty::Unknown,
+            },
+            FourCC::IMIR => match child.content.first() {
+                Some(&b) => ItemProperty::Mirror(b & 0x1),
+                None => ItemProperty::Unknown,
+            },
+            FourCC::CLAP => {
+                if let Ok(clap) = parse_clap(&child) {
+                    ItemProperty::CleanAperture(clap)
+                } else {
+                    ItemProperty::Unknown
+                }
+            }
             _ => ItemProperty::Unknown,
         };
         container.properties.push(prop);
@@ -611,7 +940,7 @@ fn parse_ispe(ispe: &Box<'_>) -> Result<ImageSpatialExtents> {
     Ok(ImageSpatialExtents { width, height })
 }
 
-fn parse_hvcc(hvcc: &Box<'_>) -> Result<HevcDecoderConfig> {
+pub(super) fn parse_hvcc(hvcc: &Box<'_>) -> Result<HevcDecoderConfig> {
     let content = hvcc.content;
     if content.len() < 23 {
         return Err(HeicError::InvalidContainer("hvcC too short"));
@@ -642,8 +971,53 @@ fn parse_hvcc(hvcc: &Box<'_>) -> Result<HevcDecoderConfig> {
     // Skip avgFrameRate (2 bytes)
     let length_size_minus_one = content[21] & 0x3;
 
-    let num_arrays = content[22];
-    let mut pos = 23;
+    let nal_units = parse_nal_unit_arrays(&content[22..]);
+
+    Ok(HevcDecoderConfig {
+        config_version,
+        general_profile_space,
+        general_tier_flag,
+        general_profile_idc,
+        general_profile_compatibility_flags,
+        general_constraint_indicator_flags,
+        general_level_idc,
+        chroma_format,
+        bit_depth_luma_minus8,
+        bit_depth_chroma_minus8,
+        length_size_minus_one,
+        nal_units,
+    })
+}
+
+/// Parse an L-HEVC decoder configuration record (ISO/IEC 14496-15, 9.6.3)
+fn parse_lhvc(lhvc: &Box<'_>) -> Result<HevcDecoderConfig> {
+    let content = lhvc.content;
+    if content.len() < 6 {
+        return Err(HeicError::InvalidContainer("lhvC too short"));
+    }
+
+    Ok(HevcDecoderConfig {
+        config_version: content[0],
+        general_profile_space: 0,
+        general_tier_flag: false,
+        general_profile_idc: 0,
+        general_profile_compatibility_flags: 0,
+        general_constraint_indicator_flags: 0,
+        general_level_idc: 0,
+        chroma_format: 0,
+        bit_depth_luma_minus8: 0,
+        bit_depth_chroma_minus8: 0,
+        length_size_minus_one: content[4] & 0x3,
+        nal_units: parse_nal_unit_arrays(&content[5..]),
+    })
+}
+
+/// Parse the NAL unit arrays shared by hvcC and lhvC, starting at numOfArrays
+fn parse_nal_unit_arrays(content: &[u8]) -> Vec<Vec<u8>> {
+    let Some(&num_arrays) = content.first() else {
+        return Vec::new();
+    };
+    let mut pos = 1;
     let mut nal_units = Vec::new();
 
     for _ in 0..num_arrays {
@@ -675,20 +1049,7 @@ fn parse_hvcc(hvcc: &Box<'_>) -> Result<HevcDecoderConfig> {
         }
     }
 
-    Ok(HevcDecoderConfig {
-        config_version,
-        general_profile_space,
-        general_tier_flag,
-        general_profile_idc,
-        general_profile_compatibility_flags,
-        general_constraint_indicator_flags,
-        general_level_idc,
-        chroma_format,
-        bit_depth_luma_minus8,
-        bit_depth_chroma_minus8,
-        length_size_minus_one,
-        nal_units,
-    })
+    nal_units
 }
 
 fn parse_colr(colr: &Box<'_>) -> Result<ColorInfo> {
@@ -719,6 +1080,49 @@ fn parse_colr(colr: &Box<'_>) -> Result<ColorInfo> {
     }
 }
 
+fn parse_auxc(auxc: &Box<'_>) -> Result<AuxiliaryType> {
+    let content = auxc.content;
+    if content.len() < 5 {
+        return Err(HeicError::InvalidContainer("auxC too short"));
+    }
+
+    // Skip version/flags (4 bytes), then null-terminated URN
+    let urn = &content[4..];
+    let urn_end = urn.iter().position(|&b| b == 0).unwrap_or(urn.len());
+    let aux_type = str::from_utf8(&urn[..urn_end])
+        .map_err(|_| HeicError::InvalidContainer("auxC type is not UTF-8"))?
+        .to_string();
+    let aux_subtype = urn.get(urn_end + 1..).unwrap_or(&[]).to_vec();
+
+    Ok(AuxiliaryType {
+        aux_type,
+        aux_subtype,
+    })
+}
+
+fn parse_clap(clap: &Box<'_>) -> Result<CleanAperture> {
+    let content = clap.content;
+    if content.len() < 32 {
+        return Err(HeicError::InvalidContainer("clap too short"));
+    }
+
+    let field = |i: usize| {
+        u32::from_be_bytes([
+            content[i * 4],
+            content[i * 4 + 1],
+            content[i * 4 + 2],
+            content[i * 4 + 3],
+        ])
+    };
+
+    Ok(CleanAperture {
+        width: (field(0), field(1)),
+        height: (field(2), field(3)),
+        horiz_off: (field(4) as i32, field(5)),
+        vert_off: (field(6) as i32, field(7)),
+    })
+}
+
 fn parse_ipma(ipma: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
     let content = ipma.content;
     if content.len() < 8 {
@@ -737,8 +1141,11 @@ fn parse_ipma(ipma: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
     ]);
     pos += 4;
 
+    container.limits().check_items(entry_count.into())?;
+
+    let id_size = if version < 1 { 2 } else { 4 };
     for _ in 0..entry_count {
-        if pos + 2 > content.len() {
+        if pos + id_size > content.len() {
             break;
         }
 
@@ -798,6 +1205,37 @@ fn parse_ipma(ipma: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
     Ok(())
 }
 
+/// Entity groups only describe optional relationships between items, so a
+/// group that cannot be read is skipped rather than failing the file
+fn parse_grpl(grpl: &Box<'_>, container: &mut HeifContainer<'_>) {
+    for group in grpl.children(0) {
+        // EntityToGroupBox: version/flags, group_id, num_entities_in_group, entity_ids
+        let content = group.content;
+        if content.len() < 12 {
+            continue;
+        }
+
+        let group_id = u32::from_be_bytes([content[4], content[5], content[6], content[7]]);
+        let num_entities =
+            u32::from_be_bytes([content[8], content[9], content[10], content[11]]) as usize;
+        if num_entities > (content.len() - 12) / 4 {
+            continue;
+        }
+
+        // Grouping-type specific data may follow the entity list; it is not interpreted
+        let entity_ids = content[12..12 + num_entities * 4]
+            .chunks_exact(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        container.entity_groups.push(EntityGroup {
+            group_type: group.box_type(),
+            group_id,
+            entity_ids,
+        });
+    }
+}
+
 fn parse_iref(iref: &Box<'_>, container: &mut HeifContainer<'_>) -> Result<()> {
     let content = iref.content;
     if content.len() < 4 {
@@ -927,3 +1365,266 @@ pub fn parse_grid_config(data: &[u8]) -> core::result::Result<ImageGrid, HeicErr
         output_height,
     })
 }
+
+/// Parsed image overlay configuration (ISO/IEC 23008-12, 6.6.2.3)
+#[derive(Debug, Clone)]
+pub struct ImageOverlay {
+    /// Canvas fill colour as 16-bit RGBA
+    pub canvas_fill_value: [u16; 4],
+    /// Output width in pixels
+    pub output_width: u32,
+    /// Output height in pixels
+    pub output_height: u32,
+    /// Signed (horizontal, vertical) offset of each input image, in `dimg` order
+    pub offsets: Vec<(i32, i32)>,
+}
+
+/// Parse overlay item data into ImageOverlay configuration
+pub fn parse_overlay_config(data: &[u8]) -> core::result::Result<ImageOverlay, HeicError> {
+    // ImageOverlay: version(1) + flags(1) + canvas_fill_value(4x16) + output_width
+    //               + output_height + (horizontal_offset, vertical_offset) per input
+    if data.len() < 14 {
+        return Err(HeicError::InvalidData("overlay data too short"));
+    }
+
+    let _version = data[0];
+    let flags = data[1];
+
+    let mut canvas_fill_value = [0u16; 4];
+    for (i, value) in canvas_fill_value.iter_mut().enumerate() {
+        *value = u16::from_be_bytes([data[2 + i * 2], data[3 + i * 2]]);
+    }
+
+    // fields_length: 0 = 16-bit, 1 = 32-bit dimensions and offsets
+    let field_size = if (flags & 1) != 0 { 4 } else { 2 };
+    let read_field = |pos: usize| -> Option<u32> {
+        let bytes = data.get(pos..pos + field_size)?;
+        Some(if field_size == 4 {
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        } else {
+            u16::from_be_bytes([bytes[0], bytes[1]]) as u32
+        })
+    };
+    let to_signed = |value: u32| -> i32 {
+        if field_size == 4 {
+            value as i32
+        } else {
+            value as u16 as i16 as i32
+        }
+    };
+
+    let mut pos = 10;
+    let output_width =
+        read_field(pos).ok_or(HeicError::InvalidData("overlay data too short for dims"))?;
+    let output_height =
+        read_field(pos + field_size).ok_or(HeicError::InvalidData("overlay data too short for dims"))?;
+    pos += field_size * 2;
+
+    let mut offsets = Vec::new();
+    while let (Some(h), Some(v)) = (read_field(pos), read_field(pos + field_size)) {
+        offsets.push((to_signed(h), to_signed(v)));
+        pos += field_size * 2;
+    }
+
+    Ok(ImageOverlay {
+        canvas_fill_value,
+        output_width,
+        output_height,
+        offsets,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorKind, Limit, LimitExceeded};
+    use crate::heif::test_util::{TestItem, entity_group, grid_tile, heif_file, make_box};
+    use alloc::vec;
+
+    #[test]
+    fn test_parse_entity_groups() {
+        let mut iinf = vec![0, 0, 0, 0, 0, 3];
+        for id in 1u16..=3 {
+            let mut infe = vec![2, 0, 0, 0];
+            infe.extend_from_slice(&id.to_be_bytes());
+            infe.extend_from_slice(&[0, 0]);
+            infe.extend_from_slice(b"hvc1\0");
+            iinf.extend(make_box(b"infe", &infe));
+        }
+
+        let mut grpl = entity_group(b"altr", 10, &[1, 3, 2]);
+        grpl.extend(entity_group(b"ster", 11, &[2, 3]));
+
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend(make_box(b"pitm", &[0, 0, 0, 0, 0, 1]));
+        meta.extend(make_box(b"iinf", &iinf));
+        meta.extend(make_box(b"grpl", &grpl));
+        let mut data = make_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        data.extend(make_box(b"meta", &meta));
+
+        let container = parse(&data).unwrap();
+        assert_eq!(container.entity_groups.len(), 2);
+        assert_eq!(container.entity_groups[1].group_type, FourCC(*b"ster"));
+        assert_eq!(container.entity_groups[1].group_id, 11);
+        assert_eq!(container.entity_groups[1].entity_ids, vec![2, 3]);
+        assert_eq!(container.alternative_item_ids(1), vec![3, 2]);
+        assert!(container.alternative_item_ids(2).contains(&1));
+        assert_eq!(container.entity_groups_of(3).count(), 2);
+    }
+
+    #[test]
+    fn test_parse_lhvc() {
+        // configurationVersion, min_spatial_segmentation, parallelism,
+        // numTemporalLayers/lengthSizeMinusOne = 3, one array with one VPS
+        let content = [1, 0xF0, 0, 0xFC, 0x0F, 1, 0x20, 0, 1, 0, 3, 0x40, 0x01, 0x0C];
+        let data = make_box(b"lhvC", &content);
+        let lhvc = BoxIterator::new(&data).next().unwrap();
+        let config = parse_lhvc(&lhvc).unwrap();
+        assert_eq!(config.length_size_minus_one, 3);
+        assert_eq!(config.nal_units, vec![vec![0x40, 0x01, 0x0C]]);
+    }
+
+    #[test]
+    fn test_entity_group_count_is_bounded() {
+        // num_entities_in_group = 1000 with no entity IDs present
+        let mut group = entity_group(b"brst", 1, &[]);
+        let len = group.len();
+        group[len - 4..].copy_from_slice(&1000u32.to_be_bytes());
+        // The unreadable groups are skipped, later groups are still parsed
+        group.extend(make_box(b"altr", &[0; 8]));
+        group.extend(entity_group(b"altr", 2, &[1, 2]));
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend(make_box(b"grpl", &group));
+        let mut data = make_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        data.extend(make_box(b"meta", &meta));
+        let container = parse(&data).unwrap();
+        assert_eq!(container.entity_groups.len(), 1);
+        assert_eq!(container.entity_groups[0].group_id, 2);
+    }
+
+    fn file_with_meta_child(child: Vec<u8>) -> Vec<u8> {
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend(child);
+        let mut data = make_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        data.extend(make_box(b"meta", &meta));
+        data
+    }
+
+    #[test]
+    fn test_truncated_boxes_are_rejected() {
+        // iloc v2 declaring one item, cut off inside the item entry
+        let iloc = make_box(b"iloc", &[2, 0, 0, 0, 0x44, 0x00, 0, 0, 0, 1, 0, 0]);
+        let err = parse(&file_with_meta_child(iloc)).unwrap_err();
+        assert!(matches!(
+            err.without_location(),
+            HeicError::InvalidContainer("iloc truncated")
+        ));
+        // After the 24-byte ftyp and the meta header and version
+        let location = err.location().unwrap();
+        assert_eq!((location.box_path.as_str(), location.offset), ("meta/iloc", Some(36)));
+        assert_eq!(err.kind(), ErrorKind::InvalidContainer);
+
+        // iinf v1 with a 16-bit entry count
+        let iinf = make_box(b"iinf", &[1, 0, 0, 0, 0, 1]);
+        assert!(parse(&file_with_meta_child(iinf)).is_err());
+
+        // infe v3 ending after the item ID
+        let mut iinf = vec![0, 0, 0, 0, 0, 1];
+        iinf.extend(make_box(b"infe", &[3, 0, 0, 0, 0, 0, 0, 1]));
+        let data = file_with_meta_child(make_box(b"iinf", &iinf));
+        let container = parse(&data).unwrap();
+        assert!(container.item_infos.is_empty());
+
+        // A box whose size is smaller than its header ends the box list
+        let mut data = make_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        data.extend_from_slice(&[0, 0, 0, 4, b'm', b'e', b't', b'a']);
+        assert_eq!(BoxIterator::new(&data).count(), 1);
+    }
+
+    #[test]
+    fn test_item_count_is_limited() {
+        // iloc claiming 65535 items, far more than the limit below
+        let iloc = make_box(b"iloc", &[0, 0, 0, 0, 0x44, 0x00, 0xFF, 0xFF]);
+        let limits = DecoderLimits {
+            max_items: 100,
+            ..DecoderLimits::default()
+        };
+        let err = parse_with_limits(&file_with_meta_child(iloc), limits).unwrap_err();
+        assert!(matches!(
+            err.without_location(),
+            HeicError::LimitExceeded(LimitExceeded {
+                limit: Limit::Items,
+                requested: 65535,
+                max: 100,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_property_index_zero() {
+        let mut infe = vec![2, 0, 0, 0, 0, 1, 0, 0];
+        infe.extend_from_slice(b"hvc1\0");
+        let mut iinf = vec![0, 0, 0, 0, 0, 1];
+        iinf.extend(make_box(b"infe", &infe));
+        // Item 1 associated with property index 0, which means no property
+        let mut iprp = make_box(b"ipco", &[]);
+        iprp.extend(make_box(b"ipma", &[0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 0]));
+        let mut children = make_box(b"iinf", &iinf);
+        children.extend(make_box(b"iprp", &iprp));
+
+        let data = file_with_meta_child(children);
+        let container = parse(&data).unwrap();
+        let item = container.get_item(1).unwrap();
+        assert!(item.dimensions.is_none());
+    }
+
+    #[test]
+    fn test_extent_range_overflow() {
+        let data = make_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        let mut container = parse(&data).unwrap();
+        container.item_locations.push(ItemLocation {
+            item_id: 1,
+            construction_method: 0,
+            base_offset: u64::MAX,
+            extents: vec![(1, 1)],
+        });
+        assert!(container.get_item_data(1).is_none());
+        assert!(container.get_item_data_owned(1).is_none());
+    }
+
+    #[test]
+    fn test_image_handles_and_secondary_item() {
+        use crate::HeicDecoder;
+        use crate::hevc::test_util::{SliceHeaderSpec, StreamParams, TestCu, annex_b};
+
+        // A 64x64 primary image, a visible 48x32 image and a hidden tile
+        let params = StreamParams { width: 48, height: 32, ..StreamParams::default() };
+        let cus: Vec<_> = (0..6).map(|i| TestCu::Intra(i * 3 - 6)).collect();
+        let slice = SliceHeaderSpec::idr().slice(&params, &cus);
+        let items = [
+            TestItem { hidden: false, ..grid_tile(1, 0) },
+            TestItem::hvc1(2, &params, core::slice::from_ref(&slice)),
+            grid_tile(3, 2),
+        ];
+        let file = heif_file(1, &items);
+
+        let decoder = HeicDecoder::new();
+        let handles = decoder.image_handles(&file).unwrap();
+        let summary: Vec<_> = handles
+            .iter()
+            .map(|h| (h.item_id, h.item_type, h.is_primary, h.dimensions))
+            .collect();
+        assert_eq!(
+            summary,
+            [(1, ItemType::Hvc1, true, Some((64, 64))), (2, ItemType::Hvc1, false, Some((48, 32)))]
+        );
+
+        let image = decoder.decode_item(&file, 2).unwrap();
+        assert_eq!((image.width, image.height), (48, 32));
+        assert_eq!(image.data.len(), 48 * 32 * 3);
+        let frame = decoder.decode_item_to_frame(&file, 2).unwrap();
+        let expected = crate::hevc::decode(&annex_b(&[params.sps(), params.pps(), slice])).unwrap();
+        assert_eq!(frame.y_plane, expected.y_plane);
+        assert_ne!(decoder.decode(&file).unwrap().data.len(), image.data.len());
+    }
+}
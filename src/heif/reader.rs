@@ -0,0 +1,351 @@
+//! Lazy HEIF reading from a seekable source
+//!
+//! [`HeifReader`] reads only the `ftyp` and `meta` boxes up front. Item
+//! data is fetched from the source on demand, one `iloc` extent at a time,
+//! so inspecting a file or decoding a thumbnail does not read the rest of
+//! the media data.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use alloc::vec::Vec;
+
+use super::boxes::FourCC;
+use super::parser::parse_relocated;
+use crate::error::HeicError;
+use crate::heif::HeifContainer;
+use crate::limits::DecoderLimits;
+
+/// HEIF file reader that fetches item data lazily
+pub struct HeifReader<R> {
+    reader: R,
+    /// The ftyp and meta boxes, in file order
+    metadata: Vec<u8>,
+    /// File offset of each box in `metadata`
+    box_offsets: Vec<u64>,
+    /// Total length of the source
+    file_len: u64,
+    /// Limits applied to the metadata, item data and decoding
+    limits: DecoderLimits,
+}
+
+impl<R: Read + Seek> HeifReader<R> {
+    /// Read the metadata boxes of a HEIF file
+    ///
+    /// Only the top-level box headers and the `ftyp` and `meta` boxes are
+    /// read. Boxes with 64-bit sizes are supported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error on I/O failure or if the file has no `ftyp` or
+    /// `meta` box.
+    pub fn new(reader: R) -> Result<Self, HeicError> {
+        Self::with_limits(reader, DecoderLimits::default())
+    }
+
+    /// Read the metadata boxes of a HEIF file, enforcing `limits`
+    ///
+    /// The metadata boxes and each item's data count against the allocation
+    /// limit before they are read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error on I/O failure, if the file has no `ftyp` or `meta`
+    /// box, or if a box exceeds the limits.
+    pub fn with_limits(mut reader: R, limits: DecoderLimits) -> Result<Self, HeicError> {
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        let mut metadata = Vec::new();
+        let mut box_offsets = Vec::new();
+        let (mut have_ftyp, mut have_meta) = (false, false);
+
+        let mut pos = 0u64;
+        while pos + 8 <= file_len && !(have_ftyp && have_meta) {
+            reader.seek(SeekFrom::Start(pos))?;
+            let mut header = [0u8; 16];
+            reader.read_exact(&mut header[..8])?;
+            let size_32 = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let box_type = FourCC([header[4], header[5], header[6], header[7]]);
+
+            let (size, header_len) = match size_32 {
+                // Box extends to end of file
+                0 => (file_len - pos, 8),
+                // 64-bit size follows the type
+                1 => {
+                    reader.read_exact(&mut header[8..])?;
+                    let mut large = [0u8; 8];
+                    large.copy_from_slice(&header[8..]);
+                    (u64::from_be_bytes(large), 16)
+                }
+                size => (u64::from(size), 8),
+            };
+            if size < header_len as u64 || size > file_len - pos {
+                return Err(HeicError::InvalidContainer("Invalid top-level box size"));
+            }
+
+            if box_type == FourCC::FTYP || box_type == FourCC::META {
+                limits.check_alloc(metadata.len() as u64 + size)?;
+                let len = usize::try_from(size)
+                    .map_err(|_| HeicError::InvalidContainer("Metadata box too large"))?;
+                let start = metadata.len();
+                metadata.extend_from_slice(&header[..header_len]);
+                metadata.resize(start + len, 0);
+                reader.read_exact(&mut metadata[start + header_len..])?;
+                box_offsets.push(pos);
+                have_ftyp |= box_type == FourCC::FTYP;
+                have_meta |= box_type == FourCC::META;
+            }
+            pos += size;
+        }
+
+        if !have_ftyp {
+            return Err(HeicError::InvalidContainer("missing ftyp box"));
+        }
+        if !have_meta {
+            return Err(HeicError::InvalidContainer("missing meta box"));
+        }
+        Ok(Self {
+            reader,
+            metadata,
+            box_offsets,
+            file_len,
+            limits,
+        })
+    }
+
+    /// Parse the metadata without loading any file-based item data
+    ///
+    /// Items stored in `idat` are available; items stored elsewhere in the
+    /// file have no data in the returned container.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the metadata boxes are malformed.
+    pub fn container(&self) -> Result<HeifContainer<'_>, HeicError> {
+        let mut container = parse_relocated(&self.metadata, self.limits, &self.box_offsets)?;
+        container.set_metadata_only();
+        Ok(container)
+    }
+
+    /// Parse the metadata and load the data needed to decode the given items
+    ///
+    /// Besides the items themselves this loads the items they are derived
+    /// from (`dimg`), the base layers of layered items (`sbas`, `base`) and
+    /// the alpha planes of overlay inputs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error on I/O failure or if the metadata is malformed.
+    pub fn load(&mut self, item_ids: &[u32]) -> Result<HeifContainer<'_>, HeicError> {
+        let mut container = parse_relocated(&self.metadata, self.limits, &self.box_offsets)?;
+        container.set_metadata_only();
+
+        for item_id in required_items(&container, item_ids) {
+            if let Some(data) = read_item(&mut self.reader, self.file_len, &container, item_id)? {
+                container.insert_item_data(item_id, data);
+            }
+        }
+        Ok(container)
+    }
+
+    /// Read the complete data of one item
+    ///
+    /// # Errors
+    ///
+    /// Returns an error on I/O failure or if the item has no data.
+    pub fn read_item_data(&mut self, item_id: u32) -> Result<Vec<u8>, HeicError> {
+        let container = self.load(&[item_id])?;
+        container
+            .get_item_data_owned(item_id)
+            .ok_or(HeicError::InvalidData("Missing item data"))
+    }
+}
+
+/// The given items plus every item needed to decode them
+fn required_items(container: &HeifContainer<'_>, item_ids: &[u32]) -> Vec<u32> {
+    let mut items = item_ids.to_vec();
+    let mut next = 0;
+    while next < items.len() {
+        let item_id = items[next];
+        next += 1;
+
+        let inputs = container
+            .item_references
+            .iter()
+            .filter(|r| {
+                r.from_item_id == item_id
+                    && (r.ref_type == FourCC::DIMG
+                        || r.ref_type == FourCC::SBAS
+                        || r.ref_type == FourCC::BASE)
+            })
+            .flat_map(|r| r.to_item_ids.iter().copied());
+        let alpha = container.alpha_item_id(item_id);
+        for id in inputs.chain(alpha) {
+            if !items.contains(&id) {
+                items.push(id);
+            }
+        }
+    }
+    items
+}
+
+/// Read the extents of a file-based item; `None` for items in `idat`
+fn read_item<R: Read + Seek>(
+    reader: &mut R,
+    file_len: u64,
+    container: &HeifContainer<'_>,
+    item_id: u32,
+) -> Result<Option<Vec<u8>>, HeicError> {
+    let Some(loc) = container
+        .item_locations
+        .iter()
+        .find(|l| l.item_id == item_id)
+    else {
+        return Ok(None);
+    };
+    if loc.construction_method != 0 {
+        return Ok(None);
+    }
+
+    let mut data = Vec::new();
+    let mut total = 0u64;
+    for &(offset, length) in &loc.extents {
+        let start = loc
+            .base_offset
+            .checked_add(offset)
+            .filter(|&start| start <= file_len)
+            .ok_or(HeicError::InvalidData("Item extent outside file"))?;
+        // A zero length extends to the end of the file
+        let length = if length == 0 {
+            file_len - start
+        } else {
+            length
+        };
+        if length > file_len - start {
+            return Err(HeicError::InvalidData("Item extent outside file"));
+        }
+        total = total.saturating_add(length);
+        container.limits().check_alloc(total)?;
+
+        reader.seek(SeekFrom::Start(start))?;
+        let pos = data.len();
+        data.resize(pos + length as usize, 0);
+        reader.read_exact(&mut data[pos..])?;
+    }
+    Ok(Some(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HeicDecoder;
+    use crate::heif::test_util::{TestItem, grid_tile, heif_file, make_box};
+    use std::io::Cursor;
+
+    /// Reader that records the byte ranges read
+    struct Tracking {
+        inner: Cursor<Vec<u8>>,
+        reads: Vec<(u64, usize)>,
+    }
+
+    impl Read for Tracking {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let pos = self.inner.position();
+            let n = self.inner.read(buf)?;
+            self.reads.push((pos, n));
+            Ok(n)
+        }
+    }
+
+    impl Seek for Tracking {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_lazy_item_data() {
+        let ftyp = make_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        // mdat with 64-bit size placed before meta: 16-byte header, then 1000 bytes
+        let mut mdat = vec![0, 0, 0, 1];
+        mdat.extend_from_slice(b"mdat");
+        mdat.extend_from_slice(&1016u64.to_be_bytes());
+        mdat.extend((0..1000).map(|i| i as u8));
+        let item_offset = (ftyp.len() + 16 + 500) as u32;
+
+        let mut infe = vec![2, 0, 0, 0, 0, 1, 0, 0];
+        infe.extend_from_slice(b"Exif\0");
+        let mut iinf = vec![0, 0, 0, 0, 0, 1];
+        iinf.extend(make_box(b"infe", &infe));
+        // Version 0, 4-byte offsets and lengths, one item with one extent
+        let mut iloc = vec![0, 0, 0, 0, 0x44, 0x00, 0, 1, 0, 1, 0, 0, 0, 1];
+        iloc.extend_from_slice(&item_offset.to_be_bytes());
+        iloc.extend_from_slice(&4u32.to_be_bytes());
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend(make_box(b"pitm", &[0, 0, 0, 0, 0, 1]));
+        meta.extend(make_box(b"iinf", &iinf));
+        meta.extend(make_box(b"iloc", &iloc));
+
+        let mut file = ftyp;
+        file.extend(mdat);
+        file.extend(make_box(b"meta", &meta));
+
+        let tracking = Tracking {
+            inner: Cursor::new(file),
+            reads: Vec::new(),
+        };
+        let mut reader = HeifReader::new(tracking).unwrap();
+        assert!(reader.container().unwrap().get_item_data(1).is_none());
+        assert_eq!(reader.read_item_data(1).unwrap(), [244, 245, 246, 247]);
+
+        // Only the mdat header and the item's extent were read from mdat
+        let mdat_start = 24;
+        let mdat_bytes: usize = reader
+            .reader
+            .reads
+            .iter()
+            .filter(|&&(pos, _)| pos >= mdat_start && pos < mdat_start + 1016)
+            .map(|&(_, n)| n)
+            .sum();
+        assert_eq!(mdat_bytes, 16 + 4);
+    }
+
+    #[test]
+    fn test_error_offsets_refer_to_the_file() {
+        let ftyp = make_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        let mdat = make_box(b"mdat", &[0; 100]);
+        // pitm too short to hold an item ID
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend(make_box(b"pitm", &[0, 0, 0, 0]));
+        let pitm_offset = (ftyp.len() + mdat.len() + 8 + 4) as u64;
+
+        let mut file = ftyp;
+        file.extend(mdat);
+        file.extend(make_box(b"meta", &meta));
+
+        let in_memory = crate::heif::parse(&file).err().unwrap();
+        assert_eq!(in_memory.location().unwrap().offset, Some(pitm_offset));
+        let reader = HeifReader::new(Cursor::new(file)).unwrap();
+        let lazy = reader.container().err().unwrap();
+        assert_eq!(lazy.location().unwrap().offset, Some(pitm_offset));
+    }
+
+    #[test]
+    fn test_zero_length_extent_reaches_end_of_file() {
+        // The only item's data ends the file
+        let item = TestItem { hidden: false, ..grid_tile(1, 0) };
+        let length = item.data.len() as u32;
+        let file = heif_file(1, &[item]);
+        let offset = (file.len() as u32 - length).to_be_bytes();
+        let mut extent = offset.to_vec();
+        extent.extend_from_slice(&length.to_be_bytes());
+        let pos = file.windows(8).position(|w| w == extent.as_slice()).unwrap();
+        let mut open_ended = file.clone();
+        open_ended[pos + 4..pos + 8].fill(0);
+
+        let decoder = HeicDecoder::new();
+        let expected = decoder.decode(&file).unwrap();
+        let in_memory = decoder.decode(&open_ended).unwrap();
+        let streamed = decoder.decode_reader(Cursor::new(open_ended)).unwrap();
+        assert_eq!(in_memory.data, expected.data);
+        assert_eq!(streamed.data, expected.data);
+    }
+}
@@ -0,0 +1,192 @@
+//! Region-of-interest decoding
+//!
+//! Decodes a rectangle of an image's displayed output. The rectangle is
+//! mapped back through the item's transformative properties (clap, irot,
+//! imir) to the coordinates of the underlying image; for grid images only
+//! the tiles intersecting it are decoded.
+
+use alloc::vec::Vec;
+
+use crate::error::HeicError;
+use crate::heif::derivation::{decode_image_item, item_payload};
+use crate::heif::grid::decode_grid_region;
+use crate::heif::{HeifContainer, ItemProperty, ItemType, parse_grid_config};
+use crate::hevc::DecodedFrame;
+
+/// A rectangle as (left, top, width, height)
+type Rect = (u32, u32, u32, u32);
+
+/// Geometric transform of an image of a known size
+#[derive(Debug, Clone, Copy)]
+enum Transform {
+    /// Crop to a rectangle
+    Crop(Rect),
+    /// Rotate anticlockwise by quarter turns
+    Rotate(u8),
+    /// Mirror about the vertical (0) or horizontal (1) axis
+    Mirror(u8),
+}
+
+/// Decode a rectangle of an item's displayed image
+///
+/// The rectangle is given in output coordinates, after the item's
+/// transforms. The returned frame's crop window is the rectangle.
+pub fn decode_region(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+    region: Rect,
+) -> Result<DecodedFrame, HeicError> {
+    let item = container
+        .get_item(item_id)
+        .ok_or(HeicError::InvalidData("Item not found"))?;
+    if region.2 == 0 || region.3 == 0 {
+        return Err(HeicError::InvalidData("Empty region"));
+    }
+
+    if item.item_type != ItemType::Grid {
+        // Only grids can be decoded partially
+        let mut frame = decode_image_item(container, item_id)?;
+        check_bounds(region, (frame.cropped_width(), frame.cropped_height()))?;
+        frame.crop_to(region.0, region.1, region.2, region.3);
+        return Ok(frame);
+    }
+
+    let grid_bytes = item_payload(container, item_id)
+        .ok_or(HeicError::InvalidData("Missing grid item data"))?;
+    let grid_config = parse_grid_config(&grid_bytes)?;
+
+    // Transforms in ipma order, with the image size before each of them
+    let mut size = (grid_config.output_width, grid_config.output_height);
+    let mut transforms = Vec::new();
+    for prop in container.item_properties(item_id) {
+        let transform = match *prop {
+            ItemProperty::CleanAperture(ref clap) => match clap.crop_rect(size.0, size.1) {
+                Some(rect) => Transform::Crop(rect),
+                None => continue,
+            },
+            ItemProperty::Rotation(quarter_turns) => Transform::Rotate(quarter_turns % 4),
+            ItemProperty::Mirror(axis) => Transform::Mirror(axis),
+            _ => continue,
+        };
+        transforms.push((transform, size));
+        size = transformed_size(transform, size);
+    }
+    check_bounds(region, size)?;
+
+    let source = transforms
+        .iter()
+        .rev()
+        .fold(region, |rect, &(transform, size)| source_rect(transform, size, rect));
+
+    let mut frame = decode_grid_region(container, item_id, &grid_config, source)?;
+    for &(transform, _) in &transforms {
+        match transform {
+            // Already applied by mapping the region
+            Transform::Crop(_) => {}
+            Transform::Rotate(quarter_turns) => {
+                if quarter_turns % 2 == 1 && frame.chroma_format == 2 {
+                    return Err(HeicError::Unsupported("irot rotation of 4:2:2 images"));
+                }
+                frame.rotate_ccw(quarter_turns);
+            }
+            Transform::Mirror(axis) => frame.mirror(axis),
+        }
+    }
+    Ok(frame)
+}
+
+/// Check that a rectangle lies within an image of the given size
+fn check_bounds(rect: Rect, (width, height): (u32, u32)) -> Result<(), HeicError> {
+    let (x, y, w, h) = rect;
+    if x.checked_add(w).is_none_or(|right| right > width)
+        || y.checked_add(h).is_none_or(|bottom| bottom > height)
+    {
+        return Err(HeicError::InvalidData("Region outside image bounds"));
+    }
+    Ok(())
+}
+
+/// Image size after a transform
+fn transformed_size(transform: Transform, (width, height): (u32, u32)) -> (u32, u32) {
+    match transform {
+        Transform::Crop((_, _, w, h)) => (w, h),
+        Transform::Rotate(quarter_turns) if quarter_turns % 2 == 1 => (height, width),
+        Transform::Rotate(_) | Transform::Mirror(_) => (width, height),
+    }
+}
+
+/// Map a rectangle of a transform's output back to its input of `size`
+fn source_rect(transform: Transform, (width, height): (u32, u32), rect: Rect) -> Rect {
+    let (x, y, w, h) = rect;
+    match transform {
+        Transform::Crop((left, top, _, _)) => (x + left, y + top, w, h),
+        Transform::Rotate(quarter_turns) => {
+            // One anticlockwise turn moves input (px, py) to (py, width - 1 - px)
+            let (mut rect, mut size) = (rect, transformed_size(transform, (width, height)));
+            for _ in 0..quarter_turns {
+                let (x, y, w, h) = rect;
+                rect = (size.1 - y - h, x, h, w);
+                size = (size.1, size.0);
+            }
+            rect
+        }
+        Transform::Mirror(0) => (width - x - w, y, w, h),
+        Transform::Mirror(_) => (x, height - y - h, w, h),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply a transform to a whole frame
+    fn apply(transform: Transform, mut frame: DecodedFrame) -> DecodedFrame {
+        match transform {
+            Transform::Crop((x, y, w, h)) => frame.crop_to(x, y, w, h),
+            Transform::Rotate(turns) => frame.rotate_ccw(turns),
+            Transform::Mirror(axis) => frame.mirror(axis),
+        }
+        frame
+    }
+
+    fn cropped(frame: &DecodedFrame) -> Vec<u16> {
+        let mut out = Vec::new();
+        for y in 0..frame.cropped_height() {
+            for x in 0..frame.cropped_width() {
+                out.push(frame.get_y(x + frame.crop_left, y + frame.crop_top));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_source_rect_matches_transforms() {
+        let mut image = DecodedFrame::with_params(6, 4, 8, 0);
+        for (i, v) in image.y_plane.iter_mut().enumerate() {
+            *v = i as u16;
+        }
+        let region = (1, 2, 2, 1);
+        for transform in [
+            Transform::Crop((1, 1, 4, 3)),
+            Transform::Rotate(1),
+            Transform::Rotate(2),
+            Transform::Rotate(3),
+            Transform::Mirror(0),
+            Transform::Mirror(1),
+        ] {
+            // Region of the transformed image
+            let mut expected = apply(transform, image.clone());
+            expected.crop_to(region.0, region.1, region.2, region.3);
+
+            // Transformed region of the source image
+            let (x, y, w, h) = source_rect(transform, (6, 4), region);
+            let mut actual = image.clone();
+            actual.crop_to(x, y, w, h);
+            let actual = match transform {
+                Transform::Crop(_) => actual,
+                _ => apply(transform, actual),
+            };
+            assert_eq!(cropped(&actual), cropped(&expected), "{transform:?}");
+        }
+    }
+}
@@ -0,0 +1,776 @@
+//! HEIF image sequence (track) parsing and decoding
+//!
+//! Image sequences (`msf1` brand: Live Photos, animated HEICS, burst
+//! tracks) store their frames as samples of an ISOBMFF track in `moov`
+//! rather than as items in `meta`. Each sample is located through the
+//! sample table (`stbl`): sizes from `stsz`, chunk offsets from
+//! `stco`/`co64`, the sample-to-chunk map from `stsc`, timing from `stts`
+//! (and `ctts`) and sync samples from `stss`.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::boxes::{Box, FourCC, HevcDecoderConfig};
+use super::parser::{HeifContainer, parse_hvcc};
+use crate::error::{ErrorKind, HeicError, Result};
+use crate::hevc::{DecodedFrame, SequenceDecoder};
+use crate::limits::DecoderLimits;
+
+const TRAK: FourCC = FourCC(*b"trak");
+const TKHD: FourCC = FourCC(*b"tkhd");
+const MDIA: FourCC = FourCC(*b"mdia");
+const MDHD: FourCC = FourCC(*b"mdhd");
+const MINF: FourCC = FourCC(*b"minf");
+const STBL: FourCC = FourCC(*b"stbl");
+const STSD: FourCC = FourCC(*b"stsd");
+const STSZ: FourCC = FourCC(*b"stsz");
+const STCO: FourCC = FourCC(*b"stco");
+const CO64: FourCC = FourCC(*b"co64");
+const STSC: FourCC = FourCC(*b"stsc");
+const STTS: FourCC = FourCC(*b"stts");
+const CTTS: FourCC = FourCC(*b"ctts");
+const STSS: FourCC = FourCC(*b"stss");
+
+/// Handler type of image sequence tracks
+pub const HANDLER_PICT: FourCC = FourCC(*b"pict");
+/// Handler type of video tracks
+pub const HANDLER_VIDE: FourCC = FourCC(*b"vide");
+
+/// Location and timing of one track sample
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// Absolute file offset of the sample data
+    pub offset: u64,
+    /// Sample size in bytes
+    pub size: u32,
+    /// Decoding time in media timescale units
+    pub decode_time: u64,
+    /// Composition (presentation) time offset from the decoding time
+    pub composition_offset: i64,
+    /// Sample duration in media timescale units
+    pub duration: u32,
+    /// Whether this is a sync sample (decodable on its own)
+    pub is_sync: bool,
+}
+
+impl Sample {
+    /// Presentation time in media timescale units
+    pub fn presentation_time(&self) -> i64 {
+        self.decode_time as i64 + self.composition_offset
+    }
+}
+
+/// A track from the `moov` box
+#[derive(Debug, Clone)]
+pub struct Track {
+    /// Track ID
+    pub track_id: u32,
+    /// Media handler type ("pict" for image sequences, "vide" for video)
+    pub handler: FourCC,
+    /// Presentation width from tkhd
+    pub width: u32,
+    /// Presentation height from tkhd
+    pub height: u32,
+    /// Media timescale (units per second)
+    pub timescale: u32,
+    /// Media duration in timescale units
+    pub duration: u64,
+    /// Sample entry type of the first sample description (e.g. "hvc1")
+    pub sample_entry: FourCC,
+    /// HEVC decoder configuration from the sample entry's hvcC
+    pub hevc_config: Option<HevcDecoderConfig>,
+    /// Samples in decoding order
+    pub samples: Vec<Sample>,
+}
+
+impl Track {
+    /// Check if this track holds HEVC coded pictures we can decode
+    pub fn is_hevc_image_sequence(&self) -> bool {
+        (self.handler == HANDLER_PICT || self.handler == HANDLER_VIDE)
+            && matches!(&self.sample_entry.0, b"hvc1" | b"hev1")
+            && self.hevc_config.is_some()
+    }
+}
+
+/// A decoded sequence frame with its timing
+#[derive(Debug)]
+pub struct SequenceFrame {
+    /// Decoded picture, shared with the decoder while later frames still
+    /// predict from it
+    pub frame: Arc<DecodedFrame>,
+    /// Index of the sample in the track (decoding order)
+    pub sample_index: usize,
+    /// Presentation time in timescale units
+    pub timestamp: i64,
+    /// Duration in timescale units
+    pub duration: u32,
+    /// Media timescale (units per second)
+    pub timescale: u32,
+    /// Whether the sample is a sync sample
+    pub is_sync: bool,
+}
+
+impl SequenceFrame {
+    /// Presentation time in seconds
+    pub fn timestamp_seconds(&self) -> f64 {
+        self.timestamp as f64 / self.timescale.max(1) as f64
+    }
+
+    /// Duration in seconds
+    pub fn duration_seconds(&self) -> f64 {
+        self.duration as f64 / self.timescale.max(1) as f64
+    }
+}
+
+/// Iterator decoding the samples of a track in order
+///
+/// Samples are fed to one decoder in decoding order, so inter-coded
+/// samples predict from the frames decoded before them. Frames are
+/// yielded in presentation order, each with the timing of the decoded
+/// sample that comes first in presentation order.
+pub struct SequenceFrames<'a> {
+    container: HeifContainer<'a>,
+    track_index: usize,
+    next_sample: usize,
+    decoder: SequenceDecoder,
+    length_size: usize,
+    /// Decoded samples whose frames have not been output yet
+    pending: Vec<usize>,
+    /// Frames output by the decoder and not yet yielded
+    ready: VecDeque<SequenceFrame>,
+    /// Items yielded so far
+    yielded: usize,
+    flushed: bool,
+}
+
+impl<'a> SequenceFrames<'a> {
+    /// Create an iterator over a track's frames
+    pub fn new(container: HeifContainer<'a>, track_id: u32) -> Result<Self> {
+        let track_index = container
+            .tracks
+            .iter()
+            .position(|t| t.track_id == track_id)
+            .ok_or(HeicError::InvalidData("Track not found"))?;
+        if !container.tracks[track_index].is_hevc_image_sequence() {
+            return Err(HeicError::Unsupported("Track is not an HEVC image sequence"));
+        }
+        let config = container.tracks[track_index]
+            .hevc_config
+            .as_ref()
+            .ok_or(HeicError::InvalidData("Missing hvcC in sample entry"))?;
+        let mut decoder = SequenceDecoder::with_config(config)?;
+        decoder.set_limits(*container.limits());
+        let length_size = (config.length_size_minus_one + 1) as usize;
+
+        Ok(Self {
+            container,
+            track_index,
+            next_sample: 0,
+            decoder,
+            length_size,
+            pending: Vec::new(),
+            ready: VecDeque::new(),
+            yielded: 0,
+            flushed: false,
+        })
+    }
+
+    /// The track being decoded
+    pub fn track(&self) -> &Track {
+        &self.container.tracks[self.track_index]
+    }
+
+    fn decode_sample(&mut self, index: usize) -> Result<()> {
+        let track = &self.container.tracks[self.track_index];
+        let sample = track.samples[index];
+
+        let data = self.container.file_data();
+        let start = usize::try_from(sample.offset)
+            .map_err(|_| HeicError::InvalidData("Sample offset out of range"))?;
+        let bytes = start
+            .checked_add(sample.size as usize)
+            .and_then(|end| data.get(start..end))
+            .ok_or(HeicError::InvalidData("Sample data out of bounds"))?;
+
+        let frames = self.decoder.decode_sample(bytes, self.length_size)?;
+        self.pending.push(index);
+        self.queue(frames);
+        Ok(())
+    }
+
+    /// Pair output frames with the pending samples in presentation order
+    fn queue(&mut self, frames: Vec<Arc<DecodedFrame>>) {
+        let track = &self.container.tracks[self.track_index];
+        for frame in frames {
+            let Some(pos) = (0..self.pending.len())
+                .min_by_key(|&i| track.samples[self.pending[i]].presentation_time())
+            else {
+                break;
+            };
+            let index = self.pending.swap_remove(pos);
+            let sample = track.samples[index];
+            self.ready.push_back(SequenceFrame {
+                frame,
+                sample_index: index,
+                timestamp: sample.presentation_time(),
+                duration: sample.duration,
+                timescale: track.timescale,
+                is_sync: sample.is_sync,
+            });
+        }
+    }
+}
+
+impl Iterator for SequenceFrames<'_> {
+    type Item = Result<SequenceFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(frame) = self.ready.pop_front() {
+                self.yielded += 1;
+                return Some(Ok(frame));
+            }
+            if self.next_sample < self.track().samples.len() {
+                let index = self.next_sample;
+                self.next_sample += 1;
+                if let Err(e) = self.decode_sample(index) {
+                    self.yielded += 1;
+                    return Some(Err(e));
+                }
+            } else if !self.flushed {
+                self.flushed = true;
+                let frames = self.decoder.flush();
+                self.queue(frames);
+            } else {
+                return None;
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // One item per sample, unless pictures are not output
+        let remaining = self.track().samples.len().saturating_sub(self.yielded);
+        (remaining, Some(remaining))
+    }
+}
+
+/// Parse the tracks in a `moov` box of `file`
+///
+/// Tracks are optional next to the primary image, so a track that cannot be
+/// parsed is dropped instead of failing the whole file. Exceeded limits
+/// still fail it.
+pub(super) fn parse_moov(moov: &Box<'_>, file: &[u8], limits: &DecoderLimits) -> Result<Vec<Track>> {
+    let mut tracks = Vec::new();
+    for trak in moov.children(0).filter(|child| child.box_type() == TRAK) {
+        match parse_trak(&trak, file, limits) {
+            Ok(track) => tracks.push(track),
+            Err(e) if e.kind() == ErrorKind::LimitExceeded => return Err(e),
+            Err(_) => {}
+        }
+    }
+    Ok(tracks)
+}
+
+fn parse_trak(trak: &Box<'_>, file: &[u8], limits: &DecoderLimits) -> Result<Track> {
+    let mut track = Track {
+        track_id: 0,
+        handler: FourCC(*b"    "),
+        width: 0,
+        height: 0,
+        timescale: 0,
+        duration: 0,
+        sample_entry: FourCC(*b"    "),
+        hevc_config: None,
+        samples: Vec::new(),
+    };
+
+    for child in trak.children(0) {
+        let locate = child.locate_error(None);
+        match child.box_type() {
+            TKHD => parse_tkhd(&child, &mut track).map_err(locate)?,
+            MDIA => parse_mdia(&child, &mut track, file, limits).map_err(locate)?,
+            _ => {}
+        }
+    }
+
+    Ok(track)
+}
+
+fn parse_tkhd(tkhd: &Box<'_>, track: &mut Track) -> Result<()> {
+    let content = tkhd.content;
+    let version = *content.first().ok_or(HeicError::InvalidContainer("tkhd too short"))?;
+    // creation/modification time, track_ID, reserved, duration
+    let (id_pos, size_pos) = if version == 1 { (20, 88) } else { (12, 76) };
+    if content.len() < size_pos + 8 {
+        return Err(HeicError::InvalidContainer("tkhd too short"));
+    }
+
+    track.track_id = read_u32(content, id_pos);
+    // Width and height are 16.16 fixed point
+    track.width = read_u32(content, size_pos) >> 16;
+    track.height = read_u32(content, size_pos + 4) >> 16;
+    Ok(())
+}
+
+fn parse_mdia(mdia: &Box<'_>, track: &mut Track, file: &[u8], limits: &DecoderLimits) -> Result<()> {
+    for child in mdia.children(0) {
+        let result = match child.box_type() {
+            MDHD => parse_mdhd(&child, track),
+            FourCC::HDLR => parse_hdlr(&child, track),
+            MINF => match child.children(0).find(|b| b.box_type() == STBL) {
+                Some(stbl) => parse_stbl(&stbl, track, file, limits).map_err(stbl.locate_error(None)),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        };
+        result.map_err(child.locate_error(None))?;
+    }
+    Ok(())
+}
+
+fn parse_mdhd(mdhd: &Box<'_>, track: &mut Track) -> Result<()> {
+    let content = mdhd.content;
+    let version = *content.first().ok_or(HeicError::InvalidContainer("mdhd too short"))?;
+    if version == 1 {
+        if content.len() < 32 {
+            return Err(HeicError::InvalidContainer("mdhd v1 too short"));
+        }
+        track.timescale = read_u32(content, 20);
+        track.duration = read_u64(content, 24);
+    } else {
+        if content.len() < 20 {
+            return Err(HeicError::InvalidContainer("mdhd too short"));
+        }
+        track.timescale = read_u32(content, 12);
+        track.duration = read_u32(content, 16) as u64;
+    }
+    Ok(())
+}
+
+fn parse_hdlr(hdlr: &Box<'_>, track: &mut Track) -> Result<()> {
+    let content = hdlr.content;
+    if content.len() < 12 {
+        return Err(HeicError::InvalidContainer("hdlr too short"));
+    }
+    track.handler = FourCC::from_bytes(&content[8..12]).unwrap();
+    Ok(())
+}
+
+/// Raw sample table contents before expansion into per-sample entries
+#[derive(Default)]
+struct SampleTable {
+    sample_count: usize,
+    /// Size of every sample, or 0 if the sizes are in `sample_sizes`
+    sample_size: u32,
+    sample_sizes: Vec<u32>,
+    chunk_offsets: Vec<u64>,
+    /// (first_chunk, samples_per_chunk), first_chunk 1-based
+    sample_to_chunk: Vec<(u32, u32)>,
+    /// (sample_count, sample_delta)
+    time_to_sample: Vec<(u32, u32)>,
+    /// (sample_count, sample_offset)
+    composition_offsets: Vec<(u32, i64)>,
+    /// 1-based sync sample numbers; None means every sample is a sync sample
+    sync_samples: Option<Vec<u32>>,
+}
+
+impl SampleTable {
+    /// Size of the sample at `index`
+    fn size(&self, index: usize) -> u32 {
+        if self.sample_size != 0 {
+            self.sample_size
+        } else {
+            self.sample_sizes[index]
+        }
+    }
+}
+
+fn parse_stbl(stbl: &Box<'_>, track: &mut Track, file: &[u8], limits: &DecoderLimits) -> Result<()> {
+    let mut table = SampleTable::default();
+    let mut seen = Vec::new();
+    for child in stbl.children(0) {
+        // Chunk offsets come from either stco or co64
+        let table_type = match child.box_type() {
+            CO64 => STCO,
+            box_type => box_type,
+        };
+        if [STSZ, STCO, STSC, STTS, CTTS, STSS].contains(&table_type) {
+            if seen.contains(&table_type) {
+                return Err(HeicError::InvalidContainer("Duplicate sample table box"));
+            }
+            seen.push(table_type);
+        }
+        parse_sample_table_box(&child, track, &mut table, file.len(), limits)
+            .map_err(child.locate_error(None))?;
+    }
+
+    track.samples = expand_samples(&table, limits)?;
+    Ok(())
+}
+
+/// Parse one child box of `stbl` into the track or sample table
+fn parse_sample_table_box(
+    child: &Box<'_>,
+    track: &mut Track,
+    table: &mut SampleTable,
+    file_len: usize,
+    limits: &DecoderLimits,
+) -> Result<()> {
+    let content = child.content;
+    match child.box_type() {
+        STSD => parse_stsd(child, track)?,
+        STSZ => {
+            let (_, count) = table_header(content, 8, "stsz too short")?;
+            limits.check_items(count as u64)?;
+            table.sample_count = count;
+            table.sample_size = read_u32(content, 4);
+            if table.sample_size != 0 {
+                // Every sample has at least one byte, so the file bounds the count
+                if count > file_len {
+                    return Err(HeicError::InvalidContainer("stsz sample count too large"));
+                }
+            } else {
+                table.sample_sizes = read_table(content, 12, count, 4, "stsz table exceeds box", limits, |pos| {
+                    read_u32(content, pos)
+                })?;
+            }
+        }
+        STCO => {
+            let (_, count) = table_header(content, 4, "stco too short")?;
+            table.chunk_offsets = read_table(content, 8, count, 4, "stco table exceeds box", limits, |pos| {
+                u64::from(read_u32(content, pos))
+            })?;
+        }
+        CO64 => {
+            let (_, count) = table_header(content, 4, "co64 too short")?;
+            table.chunk_offsets = read_table(content, 8, count, 8, "co64 table exceeds box", limits, |pos| {
+                read_u64(content, pos)
+            })?;
+        }
+        STSC => {
+            let (_, count) = table_header(content, 4, "stsc too short")?;
+            table.sample_to_chunk = read_table(content, 8, count, 12, "stsc table exceeds box", limits, |pos| {
+                (read_u32(content, pos), read_u32(content, pos + 4))
+            })?;
+        }
+        STTS => {
+            let (_, count) = table_header(content, 4, "stts too short")?;
+            table.time_to_sample = read_table(content, 8, count, 8, "stts table exceeds box", limits, |pos| {
+                (read_u32(content, pos), read_u32(content, pos + 4))
+            })?;
+        }
+        CTTS => {
+            let (version, count) = table_header(content, 4, "ctts too short")?;
+            table.composition_offsets = read_table(content, 8, count, 8, "ctts table exceeds box", limits, |pos| {
+                let raw = read_u32(content, pos + 4);
+                // Version 1 offsets are signed
+                let offset = if version == 1 { raw as i32 as i64 } else { raw as i64 };
+                (read_u32(content, pos), offset)
+            })?;
+        }
+        STSS => {
+            let (_, count) = table_header(content, 4, "stss too short")?;
+            table.sync_samples = Some(read_table(content, 8, count, 4, "stss table exceeds box", limits, |pos| {
+                read_u32(content, pos)
+            })?);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Parse the first sample description; HEVC entries carry an hvcC box
+fn parse_stsd(stsd: &Box<'_>, track: &mut Track) -> Result<()> {
+    if stsd.content.len() < 8 {
+        return Err(HeicError::InvalidContainer("stsd too short"));
+    }
+    let Some(entry) = stsd.children(8).next() else {
+        return Ok(());
+    };
+    track.sample_entry = entry.box_type();
+
+    if matches!(&entry.box_type().0, b"hvc1" | b"hev1") {
+        // VisualSampleEntry fields precede the child boxes
+        const VISUAL_SAMPLE_ENTRY_SIZE: usize = 78;
+        if entry.content.len() < VISUAL_SAMPLE_ENTRY_SIZE {
+            return Err(HeicError::InvalidContainer("Visual sample entry too short"));
+        }
+        track.hevc_config = entry
+            .children(VISUAL_SAMPLE_ENTRY_SIZE)
+            .find(|b| b.box_type() == FourCC::HVCC)
+            .map(|hvcc| parse_hvcc(&hvcc))
+            .transpose()?;
+    }
+    Ok(())
+}
+
+/// Expand the run-length coded sample tables into per-sample entries
+fn expand_samples(table: &SampleTable, limits: &DecoderLimits) -> Result<Vec<Sample>> {
+    let sample_count = table.sample_count;
+    limits.check_alloc((sample_count * core::mem::size_of::<Sample>()) as u64)?;
+    let mut samples = Vec::with_capacity(sample_count);
+
+    // Locate samples chunk by chunk
+    let mut sample_index = 0;
+    for (run, &(first_chunk, samples_per_chunk)) in table.sample_to_chunk.iter().enumerate() {
+        let last_chunk = match table.sample_to_chunk.get(run + 1) {
+            Some(&(next_first, _)) => next_first.saturating_sub(1),
+            None => table.chunk_offsets.len() as u32,
+        };
+        if first_chunk == 0 || last_chunk < first_chunk.saturating_sub(1) {
+            return Err(HeicError::InvalidContainer("Invalid stsc chunk run"));
+        }
+
+        for chunk in first_chunk..=last_chunk {
+            let mut offset = *table
+                .chunk_offsets
+                .get(chunk as usize - 1)
+                .ok_or(HeicError::InvalidContainer("stsc references missing chunk"))?;
+            for _ in 0..samples_per_chunk {
+                if sample_index == sample_count {
+                    break;
+                }
+                let size = table.size(sample_index);
+                samples.push(Sample {
+                    offset,
+                    size,
+                    decode_time: 0,
+                    composition_offset: 0,
+                    duration: 0,
+                    is_sync: table.sync_samples.is_none(),
+                });
+                offset = offset.saturating_add(u64::from(size));
+                sample_index += 1;
+            }
+        }
+    }
+    if samples.len() != sample_count {
+        return Err(HeicError::InvalidContainer("Sample table does not cover all samples"));
+    }
+
+    let mut time = 0u64;
+    let durations = table
+        .time_to_sample
+        .iter()
+        .flat_map(|&(count, delta)| core::iter::repeat_n(delta, count as usize));
+    for (sample, duration) in samples.iter_mut().zip(durations) {
+        sample.decode_time = time;
+        sample.duration = duration;
+        time += duration as u64;
+    }
+
+    let offsets = table
+        .composition_offsets
+        .iter()
+        .flat_map(|&(count, offset)| core::iter::repeat_n(offset, count as usize));
+    for (sample, offset) in samples.iter_mut().zip(offsets) {
+        sample.composition_offset = offset;
+    }
+
+    if let Some(ref sync) = table.sync_samples {
+        for &number in sync {
+            if let Some(sample) = (number as usize).checked_sub(1).and_then(|i| samples.get_mut(i)) {
+                sample.is_sync = true;
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Read a full box's version and entry count (at `count_pos`)
+fn table_header(content: &[u8], count_pos: usize, err: &'static str) -> Result<(u8, usize)> {
+    if content.len() < count_pos + 4 {
+        return Err(HeicError::InvalidContainer(err));
+    }
+    Ok((content[0], read_u32(content, count_pos) as usize))
+}
+
+/// Read a table of `count` entries of `entry_size` bytes after `start`,
+/// passing the position of each entry to `read`
+///
+/// The entries must fit in the box, and the table in the allocation limit.
+fn read_table<T>(
+    content: &[u8],
+    start: usize,
+    count: usize,
+    entry_size: usize,
+    err: &'static str,
+    limits: &DecoderLimits,
+    read: impl Fn(usize) -> T,
+) -> Result<Vec<T>> {
+    if count > content.len().saturating_sub(start) / entry_size {
+        return Err(HeicError::InvalidContainer(err));
+    }
+    limits.check_alloc((count * core::mem::size_of::<T>()) as u64)?;
+    Ok((0..count).map(|i| read(start + i * entry_size)).collect())
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn read_u64(data: &[u8], pos: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[pos..pos + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heif::{parse, parse_with_limits};
+    use crate::heif::test_util::make_box;
+    use alloc::vec;
+
+    fn full_box(box_type: &[u8; 4], version: u8, fields: &[u32]) -> Vec<u8> {
+        let mut content = vec![version, 0, 0, 0];
+        for field in fields {
+            content.extend_from_slice(&field.to_be_bytes());
+        }
+        make_box(box_type, &content)
+    }
+
+    /// Build a 'pict' track with five samples in two chunks (3 + 2)
+    fn sequence_trak() -> Vec<u8> {
+        let mut tkhd = vec![0u8; 84];
+        tkhd[12..16].copy_from_slice(&7u32.to_be_bytes());
+        tkhd[76..80].copy_from_slice(&(64u32 << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(48u32 << 16).to_be_bytes());
+
+        let mut hvc1 = vec![0u8; 78];
+        hvc1.extend(make_box(b"hvcC", &[0; 23]));
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(make_box(b"hvc1", &hvc1));
+
+        let mut stbl = make_box(b"stsd", &stsd);
+        stbl.extend(full_box(b"stsz", 0, &[0, 5, 10, 20, 30, 40, 50]));
+        stbl.extend(full_box(b"stsc", 0, &[2, 1, 3, 1, 2, 2, 1]));
+        let mut co64 = vec![0, 0, 0, 0, 0, 0, 0, 2];
+        co64.extend_from_slice(&1000u64.to_be_bytes());
+        co64.extend_from_slice(&5_000_000_000u64.to_be_bytes());
+        stbl.extend(make_box(b"co64", &co64));
+        stbl.extend(full_box(b"stts", 0, &[2, 4, 100, 1, 200]));
+        stbl.extend(full_box(b"stss", 0, &[2, 1, 4]));
+
+        let mut mdia = full_box(b"mdhd", 0, &[0, 0, 600, 1000, 0]);
+        mdia.extend(full_box(b"hdlr", 0, &[0, u32::from_be_bytes(*b"pict"), 0, 0, 0]));
+        mdia.extend(make_box(b"minf", &make_box(b"stbl", &stbl)));
+
+        let mut trak = make_box(b"tkhd", &tkhd);
+        trak.extend(make_box(b"mdia", &mdia));
+        make_box(b"trak", &trak)
+    }
+
+    fn sequence_file() -> Vec<u8> {
+        let mut file = make_box(b"ftyp", b"msf1\0\0\0\0msf1hevc");
+        file.extend(make_box(b"moov", &sequence_trak()));
+        file
+    }
+
+    #[test]
+    fn test_parse_track_sample_table() {
+        let data = sequence_file();
+        let container = parse(&data).unwrap();
+        let track = container.image_sequence_track().unwrap();
+
+        assert_eq!(track.track_id, 7);
+        assert_eq!((track.width, track.height), (64, 48));
+        assert_eq!(track.timescale, 600);
+        assert_eq!(track.duration, 1000);
+        assert!(track.hevc_config.is_some());
+
+        let offsets: Vec<u64> = track.samples.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![1000, 1010, 1030, 5_000_000_000, 5_000_000_040]);
+        let times: Vec<u64> = track.samples.iter().map(|s| s.decode_time).collect();
+        assert_eq!(times, vec![0, 100, 200, 300, 400]);
+        assert_eq!(track.samples[4].duration, 200);
+        let sync: Vec<bool> = track.samples.iter().map(|s| s.is_sync).collect();
+        assert_eq!(sync, vec![true, false, false, true, false]);
+    }
+
+    #[test]
+    fn test_unreadable_tracks_are_dropped() {
+        // A truncated tkhd and a sample size table that overruns its box
+        let mut moov = make_box(b"trak", &make_box(b"tkhd", &[0; 4]));
+        let stbl = make_box(b"stbl", &full_box(b"stsz", 0, &[0, 1000]));
+        let mdia = make_box(b"minf", &stbl);
+        moov.extend(make_box(b"trak", &make_box(b"mdia", &mdia)));
+        moov.extend(sequence_trak());
+
+        let mut data = make_box(b"ftyp", b"msf1\0\0\0\0msf1hevc");
+        data.extend(make_box(b"moov", &moov));
+        let container = parse(&data).unwrap();
+        assert_eq!(container.tracks.len(), 1);
+        assert_eq!(container.image_sequence_track().unwrap().track_id, 7);
+    }
+
+    #[test]
+    fn test_repeated_sample_size_boxes_are_rejected() {
+        // Thousands of fixed-size stsz boxes, each declaring many samples
+        let stsz = full_box(b"stsz", 0, &[1, 10_000]);
+        let stbl = make_box(b"stbl", &stsz.repeat(20_000));
+        let mdia = make_box(b"minf", &stbl);
+        let mut moov = make_box(b"trak", &make_box(b"mdia", &mdia));
+        moov.extend(sequence_trak());
+
+        let mut data = make_box(b"ftyp", b"msf1\0\0\0\0msf1hevc");
+        data.extend(make_box(b"moov", &moov));
+        let container = parse(&data).unwrap();
+        assert_eq!(container.tracks.len(), 1);
+        assert_eq!(container.tracks[0].track_id, 7);
+    }
+
+    #[test]
+    fn test_sample_tables_are_limited() {
+        let data = sequence_file();
+        // Five samples exceed the item limit
+        let limits = DecoderLimits { max_items: 4, ..DecoderLimits::default() };
+        let err = parse_with_limits(&data, limits).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        // The 20-byte sample size table exceeds the allocation limit
+        let limits = DecoderLimits { max_alloc: 15, ..DecoderLimits::default() };
+        let err = parse_with_limits(&data, limits).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+    }
+
+    #[test]
+    fn test_fixed_sample_size_is_not_expanded() {
+        let table = SampleTable {
+            sample_count: 3,
+            sample_size: 10,
+            chunk_offsets: vec![100],
+            sample_to_chunk: vec![(1, 3)],
+            ..SampleTable::default()
+        };
+        assert!(table.sample_sizes.is_empty());
+        let samples = expand_samples(&table, &DecoderLimits::default()).unwrap();
+        let offsets: Vec<u64> = samples.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, [100, 110, 120]);
+
+        // The samples are bounded by the allocation limit
+        let limits = DecoderLimits { max_alloc: 64, ..DecoderLimits::default() };
+        assert!(expand_samples(&table, &limits).is_err());
+    }
+
+    #[test]
+    fn test_sample_table_must_cover_all_samples() {
+        let table = SampleTable {
+            sample_count: 4,
+            sample_size: 10,
+            chunk_offsets: vec![0],
+            sample_to_chunk: vec![(1, 2)],
+            ..SampleTable::default()
+        };
+        assert!(expand_samples(&table, &DecoderLimits::default()).is_err());
+    }
+
+    #[test]
+    fn test_out_of_bounds_sample_is_an_error() {
+        let data = sequence_file();
+        let mut frames = SequenceFrames::new(parse(&data).unwrap(), 7).unwrap();
+        assert_eq!(frames.size_hint(), (5, Some(5)));
+        assert!(frames.next().unwrap().is_err());
+    }
+}
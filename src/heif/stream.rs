@@ -0,0 +1,184 @@
+//! Band-by-band decoding of grid images
+//!
+//! Decodes a grid image one row of tiles at a time, handing each stitched
+//! band to a [`BandSink`] before decoding the next. Peak memory is then
+//! proportional to one row of tiles instead of the whole image.
+
+use alloc::vec::Vec;
+
+use crate::error::HeicError;
+use crate::heif::derivation::{decode_image_item, item_payload};
+use crate::heif::grid::{decode_grid_region, grid_tile_ids, tile_extents, tile_offsets};
+use crate::heif::{HeifContainer, ItemProperty, ItemType, parse_grid_config};
+use crate::hevc::DecodedFrame;
+
+/// Receiver of decoded horizontal bands, top to bottom
+pub trait BandSink {
+    /// Called once with the output size before any band
+    fn begin(&mut self, width: u32, height: u32) -> Result<(), HeicError>;
+
+    /// Receive the next band, starting at output row `y`
+    ///
+    /// The band's cropped area spans the full output width.
+    fn band(&mut self, y: u32, frame: &DecodedFrame) -> Result<(), HeicError>;
+}
+
+/// Decode an item band by band
+///
+/// Grid images whose only transform is a clean aperture crop, and whose
+/// tiles all have `ispe` properties, are decoded one tile row at a time.
+/// Any other image is decoded in full and passed on as a single band.
+pub fn decode_bands(
+    container: &HeifContainer<'_>,
+    item_id: u32,
+    sink: &mut dyn BandSink,
+) -> Result<(), HeicError> {
+    let item = container
+        .get_item(item_id)
+        .ok_or(HeicError::InvalidData("Item not found"))?;
+
+    // Rotation and mirroring need the whole image; a single crop does not
+    let mut crops = 0;
+    let mut reorients = false;
+    for prop in container.item_properties(item_id) {
+        match prop {
+            ItemProperty::CleanAperture(_) => crops += 1,
+            ItemProperty::Rotation(_) | ItemProperty::Mirror(_) => reorients = true,
+            _ => {}
+        }
+    }
+    let streamable = item.item_type == ItemType::Grid && crops <= 1 && !reorients;
+    if !streamable {
+        return emit_whole(container, item_id, sink);
+    }
+
+    let grid_bytes = item_payload(container, item_id)
+        .ok_or(HeicError::InvalidData("Missing grid item data"))?;
+    let grid_config = parse_grid_config(&grid_bytes)?;
+    let tile_ids = grid_tile_ids(container, item_id, &grid_config)?;
+    let Some(sizes) = tile_extents(container, &tile_ids) else {
+        return emit_whole(container, item_id, sink);
+    };
+    let (_, row_y) = tile_offsets(&sizes, &grid_config)?;
+
+    // Visible area of the grid after its clean aperture
+    let (grid_width, grid_height) = (grid_config.output_width, grid_config.output_height);
+    let visible = container
+        .item_properties(item_id)
+        .find_map(|p| match p {
+            ItemProperty::CleanAperture(clap) => Some(clap),
+            _ => None,
+        })
+        .and_then(|clap| clap.crop_rect(grid_width, grid_height))
+        .unwrap_or((0, 0, grid_width, grid_height));
+    let (x, y, width, height) = visible;
+
+    let columns = grid_config.columns as usize;
+    let row_heights: Vec<u32> = sizes.iter().step_by(columns).map(|&(_, h)| h).collect();
+
+    sink.begin(width, height)?;
+    for (band_y, band_height) in bands(&row_y, &row_heights, (y, height)) {
+        let frame = decode_grid_region(container, item_id, &grid_config, (x, band_y, width, band_height))?;
+        sink.band(band_y - y, &frame)?;
+    }
+    Ok(())
+}
+
+/// Decode an item in full and pass it on as one band
+fn emit_whole(container: &HeifContainer<'_>, item_id: u32, sink: &mut dyn BandSink) -> Result<(), HeicError> {
+    let frame = decode_image_item(container, item_id)?;
+    sink.begin(frame.cropped_width(), frame.cropped_height())?;
+    sink.band(0, &frame)
+}
+
+/// Split the rows `(top, height)` of a grid into one band per tile row
+fn bands(row_y: &[u32], row_heights: &[u32], (top, height): (u32, u32)) -> Vec<(u32, u32)> {
+    let bottom = top + height;
+    row_y
+        .iter()
+        .zip(row_heights)
+        .filter_map(|(&row_top, &row_height)| {
+            let start = row_top.max(top);
+            let end = (row_top + row_height).min(bottom);
+            (start < end).then(|| (start, end - start))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HeicDecoder;
+    use crate::heif::test_util::{TestItem, grid_tile, heif_file, make_box};
+    use alloc::vec;
+
+    /// 2x2 grid of distinct tiles cropped to 128x112, with extra properties
+    fn grid_file(properties: &[Vec<u8>]) -> Vec<u8> {
+        let mut grid = TestItem::grid(1, (2, 2), (128, 112), &[2, 3, 4, 5]);
+        grid.properties.extend_from_slice(properties);
+        let mut items = vec![grid];
+        items.extend((0..4).map(|i| grid_tile(i as u16 + 2, i)));
+        heif_file(1, &items)
+    }
+
+    /// Sink collecting the rows it receives and where they started
+    #[derive(Default)]
+    struct Rows {
+        size: (u32, u32),
+        starts: Vec<u32>,
+        rgb: Vec<u8>,
+    }
+
+    impl crate::RowSink for Rows {
+        fn begin(&mut self, width: u32, height: u32) -> Result<(), HeicError> {
+            self.size = (width, height);
+            Ok(())
+        }
+
+        fn rows(&mut self, y: u32, rgb: &[u8]) -> Result<(), HeicError> {
+            assert_eq!(y as usize * self.size.0 as usize * 3, self.rgb.len());
+            self.starts.push(y);
+            self.rgb.extend_from_slice(rgb);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_grid_rows_match_full_decode() {
+        let file = grid_file(&[]);
+        let decoder = HeicDecoder::new();
+        let image = decoder.decode(&file).unwrap();
+        let mut rows = Rows::default();
+        decoder.decode_rows(&file, &mut rows).unwrap();
+
+        // One band per tile row, the last cropped to the output height
+        assert_eq!(rows.size, (128, 112));
+        assert_eq!(rows.starts, [0, 64]);
+        assert_eq!(rows.rgb, image.data);
+    }
+
+    #[test]
+    fn test_rotated_grid_is_emitted_whole() {
+        // Rotated by 90 degrees anti-clockwise
+        let file = grid_file(&[make_box(b"irot", &[1])]);
+        let decoder = HeicDecoder::new();
+        let image = decoder.decode(&file).unwrap();
+        let mut rows = Rows::default();
+        decoder.decode_rows(&file, &mut rows).unwrap();
+
+        assert_eq!(rows.size, (112, 128));
+        assert_eq!((image.width, image.height), (112, 128));
+        assert_eq!(rows.starts, [0]);
+        assert_eq!(rows.rgb, image.data);
+    }
+
+    #[test]
+    fn test_bands_follow_tile_rows() {
+        // Three 512-row tile rows, the last one cropped by the output size
+        let row_y = [0, 512, 1024];
+        let heights = [512, 512, 512];
+        assert_eq!(bands(&row_y, &heights, (0, 1200)), [(0, 512), (512, 512), (1024, 176)]);
+        // A clean aperture starting inside the first tile row
+        assert_eq!(bands(&row_y, &heights, (100, 500)), [(100, 412), (512, 88)]);
+    }
+}
@@ -0,0 +1,220 @@
+//! Box builders shared by the container tests
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::hevc::bitstream::NalType;
+use crate::hevc::test_util::{SliceHeaderSpec, StreamParams, TestCu, stereo_slices};
+
+/// Serialize a plain box with the given type and payload
+pub(crate) fn make_box(box_type: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut out = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(box_type);
+    out.extend_from_slice(content);
+    out
+}
+
+/// Image item of a test file
+#[derive(Debug, Clone)]
+pub(crate) struct TestItem {
+    pub(crate) id: u16,
+    pub(crate) item_type: [u8; 4],
+    pub(crate) hidden: bool,
+    /// Item data, stored in the `mdat`
+    pub(crate) data: Vec<u8>,
+    /// Property boxes associated with the item, in order
+    pub(crate) properties: Vec<Vec<u8>>,
+    /// Items referenced through `dimg`
+    pub(crate) dimg: Vec<u16>,
+}
+
+impl TestItem {
+    /// Coded image item holding `slices`, with the parameter sets of
+    /// `params` in its `hvcC`
+    pub(crate) fn hvc1(id: u16, params: &StreamParams, slices: &[Vec<u8>]) -> Self {
+        let mut data = Vec::new();
+        for nal in slices {
+            data.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+            data.extend_from_slice(nal);
+        }
+        Self {
+            id,
+            item_type: *b"hvc1",
+            hidden: false,
+            data,
+            properties: vec![hvcc(params), ispe(params.width, params.height)],
+            dimg: Vec::new(),
+        }
+    }
+
+    /// Two-view MV-HEVC image item of [`StreamParams::vps`], with the VPS
+    /// in its `hvcC` and the second view's parameter sets in its `lhvC`
+    ///
+    /// The second view is the base view displaced by `disparity` quarter
+    /// luma samples.
+    pub(crate) fn stereo(id: u16, params: &StreamParams, base: &[TestCu], disparity: i32) -> Self {
+        let mut item = Self::hvc1(id, params, &stereo_slices(params, base, disparity));
+        item.properties[0] = hevc_config(
+            b"hvcC",
+            &[(NalType::VpsNut, params.vps()), (NalType::SpsNut, params.sps()), (NalType::PpsNut, params.pps())],
+        );
+        item.properties.insert(
+            1,
+            hevc_config(
+                b"lhvC",
+                &[(NalType::SpsNut, params.layer_sps()), (NalType::PpsNut, params.layer_pps(1))],
+            ),
+        );
+        item
+    }
+
+    /// Grid item of `rows` x `columns` tiles, in raster order
+    pub(crate) fn grid(id: u16, (rows, columns): (u8, u8), (width, height): (u16, u16), tiles: &[u16]) -> Self {
+        let mut data = vec![0, 0, rows - 1, columns - 1];
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        Self {
+            id,
+            item_type: *b"grid",
+            hidden: false,
+            data,
+            properties: vec![ispe(width.into(), height.into())],
+            dimg: tiles.to_vec(),
+        }
+    }
+}
+
+/// Hidden 64x64 grid tile whose CTB levels depend on `seed`
+pub(crate) fn grid_tile(id: u16, seed: i32) -> TestItem {
+    let params = StreamParams::default();
+    let cus: Vec<_> = (0..16).map(|i| TestCu::Intra((i + seed) % 5 * 4 - 8)).collect();
+    let slice = SliceHeaderSpec::idr().slice(&params, &cus);
+    TestItem { hidden: true, ..TestItem::hvc1(id, &params, &[slice]) }
+}
+
+/// `hvcC` box with 4-byte NAL unit lengths and the SPS and PPS of `params`
+pub(crate) fn hvcc(params: &StreamParams) -> Vec<u8> {
+    hevc_config(b"hvcC", &[(NalType::SpsNut, params.sps()), (NalType::PpsNut, params.pps())])
+}
+
+/// `hvcC` or `lhvC` box with 4-byte NAL unit lengths and an array for each
+/// of the NAL units
+fn hevc_config(box_type: &[u8; 4], arrays: &[(NalType, Vec<u8>)]) -> Vec<u8> {
+    let mut content = if box_type == b"hvcC" {
+        // Main profile, level 3.1, 4:2:0 8-bit
+        let mut content = vec![1, 0x01, 0x60, 0, 0, 0, 0x90, 0, 0, 0, 0, 0, 93];
+        content.extend_from_slice(&[0xF0, 0, 0xFC, 0xFD, 0xF8, 0xF8, 0, 0, 0x0F]);
+        content
+    } else {
+        vec![1, 0xF0, 0, 0xFC, 0x0F]
+    };
+    content.push(arrays.len() as u8);
+    for (nal_type, nal) in arrays {
+        content.push(0x80 | *nal_type as u8);
+        content.extend_from_slice(&1u16.to_be_bytes());
+        content.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+        content.extend_from_slice(nal);
+    }
+    make_box(box_type, &content)
+}
+
+/// `ispe` box
+pub(crate) fn ispe(width: u32, height: u32) -> Vec<u8> {
+    let mut content = vec![0, 0, 0, 0];
+    content.extend_from_slice(&width.to_be_bytes());
+    content.extend_from_slice(&height.to_be_bytes());
+    make_box(b"ispe", &content)
+}
+
+/// EntityToGroupBox of the given type
+pub(crate) fn entity_group(group_type: &[u8; 4], group_id: u32, entity_ids: &[u32]) -> Vec<u8> {
+    let mut content = vec![0, 0, 0, 0];
+    content.extend_from_slice(&group_id.to_be_bytes());
+    content.extend_from_slice(&(entity_ids.len() as u32).to_be_bytes());
+    for id in entity_ids {
+        content.extend_from_slice(&id.to_be_bytes());
+    }
+    make_box(group_type, &content)
+}
+
+/// HEIF file with the given items, whose data follows the `meta` box in an
+/// `mdat`
+pub(crate) fn heif_file(primary: u16, items: &[TestItem]) -> Vec<u8> {
+    heif_file_with_groups(primary, items, &[])
+}
+
+/// [`heif_file`] with the given [entity groups](entity_group) in a `grpl`
+pub(crate) fn heif_file_with_groups(primary: u16, items: &[TestItem], groups: &[Vec<u8>]) -> Vec<u8> {
+    let ftyp = make_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+    // The item offsets depend on the size of the meta box, not its content
+    let meta_len = meta(primary, items, groups, 0).len();
+    let mdat_offset = (ftyp.len() + meta_len + 8) as u32;
+
+    let mut file = ftyp;
+    file.extend(meta(primary, items, groups, mdat_offset));
+    let data: Vec<u8> = items.iter().flat_map(|item| item.data.iter().copied()).collect();
+    file.extend(make_box(b"mdat", &data));
+    file
+}
+
+/// `meta` box of [`heif_file_with_groups`], with the item data starting at
+/// `data_offset`
+fn meta(primary: u16, items: &[TestItem], groups: &[Vec<u8>], data_offset: u32) -> Vec<u8> {
+    let count = (items.len() as u16).to_be_bytes();
+    let mut iinf = vec![0, 0, 0, 0];
+    iinf.extend_from_slice(&count);
+    let mut iloc = vec![0, 0, 0, 0, 0x44, 0x00];
+    iloc.extend_from_slice(&count);
+    let mut iref = vec![0, 0, 0, 0];
+    let mut ipco = Vec::new();
+    let mut ipma = vec![0, 0, 0, 0];
+    ipma.extend_from_slice(&(items.len() as u32).to_be_bytes());
+    let mut offset = data_offset;
+    let mut property_index = 1;
+    for item in items {
+        let mut infe = vec![2, 0, 0, u8::from(item.hidden)];
+        infe.extend_from_slice(&item.id.to_be_bytes());
+        infe.extend_from_slice(&[0, 0]);
+        infe.extend_from_slice(&item.item_type);
+        infe.push(0);
+        iinf.extend(make_box(b"infe", &infe));
+
+        iloc.extend_from_slice(&item.id.to_be_bytes());
+        iloc.extend_from_slice(&[0, 0, 0, 1]);
+        iloc.extend_from_slice(&offset.to_be_bytes());
+        iloc.extend_from_slice(&(item.data.len() as u32).to_be_bytes());
+        offset += item.data.len() as u32;
+
+        if !item.dimg.is_empty() {
+            let mut dimg = item.id.to_be_bytes().to_vec();
+            dimg.extend_from_slice(&(item.dimg.len() as u16).to_be_bytes());
+            for id in &item.dimg {
+                dimg.extend_from_slice(&id.to_be_bytes());
+            }
+            iref.extend(make_box(b"dimg", &dimg));
+        }
+
+        ipma.extend_from_slice(&item.id.to_be_bytes());
+        ipma.push(item.properties.len() as u8);
+        for property in &item.properties {
+            ipco.extend_from_slice(property);
+            ipma.push(0x80 | property_index);
+            property_index += 1;
+        }
+    }
+
+    let mut iprp = make_box(b"ipco", &ipco);
+    iprp.extend(make_box(b"ipma", &ipma));
+    let mut pitm = vec![0, 0, 0, 0];
+    pitm.extend_from_slice(&primary.to_be_bytes());
+    let mut content = vec![0, 0, 0, 0];
+    content.extend(make_box(b"pitm", &pitm));
+    content.extend(make_box(b"iinf", &iinf));
+    content.extend(make_box(b"iloc", &iloc));
+    content.extend(make_box(b"iref", &iref));
+    content.extend(make_box(b"iprp", &iprp));
+    if !groups.is_empty() {
+        content.extend(make_box(b"grpl", &groups.concat()));
+    }
+    make_box(b"meta", &content)
+}
@@ -149,6 +149,12 @@ impl NalType {
         matches!(self, Self::RadlN | Self::RadlR)
     }
 
+    /// Check if this is a sub-layer non-reference picture
+    pub fn is_sub_layer_non_ref(self) -> bool {
+        let value = self as u8;
+        value <= 14 && value.is_multiple_of(2)
+    }
+
     /// Check if this is an IRAP picture
     pub fn is_irap(self) -> bool {
         matches!(
@@ -147,6 +147,26 @@ impl ContextModel {
     }
 }
 
+#[cfg(test)]
+impl ContextModel {
+    /// rangeTabLps entry for the current state and a coder range
+    pub(crate) fn lps_range(&self, range: u32) -> u32 {
+        LPS_TABLE[self.state as usize][((range >> 6) & 3) as usize] as u32
+    }
+
+    /// State transition after coding `bin` (9.3.4.3.2.2)
+    pub(crate) fn update(&mut self, bin: u8) {
+        if bin == self.mps {
+            self.state = STATE_TRANS_MPS[self.state as usize];
+        } else {
+            if self.state == 0 {
+                self.mps = 1 - self.mps;
+            }
+            self.state = STATE_TRANS_LPS[self.state as usize];
+        }
+    }
+}
+
 /// CABAC decoder (libde265-compatible implementation)
 ///
 /// This uses the same byte-at-a-time approach as libde265, with a 32-bit value
@@ -405,7 +425,7 @@ impl<'a> CabacDecoder<'a> {
 
     /// Decode unsigned Exp-Golomb code using bypass bins
     pub fn decode_eg(&mut self, k: u8) -> Result<u32> {
-        // Count leading zeros
+        // Unary prefix of one-bins
         let mut n = 0;
         while self.decode_bypass()? != 0 {
             n += 1;
@@ -419,7 +439,7 @@ impl<'a> CabacDecoder<'a> {
             value = (value << 1) | self.decode_bypass()? as u32;
         }
 
-        Ok((1 << n) - 1 + value)
+        Ok((((1 << n) - 1) << k) + value)
     }
 }
 
@@ -489,8 +509,10 @@ pub mod context {
     pub const LOG2_RES_SCALE_ABS_PLUS1: usize = 160;
     /// Res scale sign flag
     pub const RES_SCALE_SIGN_FLAG: usize = 168;
+    /// RQT root CBF
+    pub const RQT_ROOT_CBF: usize = 170;
     /// Total number of contexts
-    pub const NUM_CONTEXTS: usize = 170;
+    pub const NUM_CONTEXTS: usize = 171;
 }
 
 /// Initial context values from H.265 spec
@@ -532,5 +554,104 @@ pub static INIT_VALUES: [u8; context::NUM_CONTEXTS] = [
     154, // CU_CHROMA_QP_OFFSET_IDX (1)
     154, // LOG2_RES_SCALE_ABS_PLUS1 (8)
     154, 154, 154, 154, 154, 154, 154, 154, // RES_SCALE_SIGN_FLAG (2)
-    154, 154,
+    154, 154, // RQT_ROOT_CBF (1)
+    79,
 ];
+
+/// Initial context values for initType 1 (P slices, or B slices with cabac_init_flag)
+pub static INIT_VALUES_TYPE1: [u8; context::NUM_CONTEXTS] = [
+    // SPLIT_CU_FLAG (3)
+    107, 139, 126, // CU_TRANSQUANT_BYPASS_FLAG (1)
+    154, // CU_SKIP_FLAG (3)
+    197, 185, 201, // PALETTE_MODE_FLAG (1)
+    154, // PRED_MODE_FLAG (1)
+    149, // PART_MODE (4)
+    154, 139, 154, 154, // PREV_INTRA_LUMA_PRED_FLAG (1)
+    154, // INTRA_CHROMA_PRED_MODE (1)
+    152, // INTER_PRED_IDC (5)
+    95, 79, 63, 31, 31, // MERGE_FLAG (1)
+    110, // MERGE_IDX (1)
+    122, // MVP_LX_FLAG (1)
+    168, // REF_IDX (2)
+    153, 153, // ABS_MVD_GREATER0_FLAG (2)
+    140, 198, // ABS_MVD_GREATER1_FLAG (1)
+    198, // SPLIT_TRANSFORM_FLAG (3)
+    124, 138, 94, // CBF_LUMA (2)
+    153, 111, // CBF_CBCR (5)
+    149, 107, 167, 154, 154, // TRANSFORM_SKIP_FLAG (2)
+    139, 139, // LAST_SIG_COEFF_X_PREFIX (18)
+    125, 110, 94, 110, 95, 79, 125, 111, 110, 78, 110, 111, 111, 95, 94, 108, 123, 108,
+    // LAST_SIG_COEFF_Y_PREFIX (18)
+    125, 110, 94, 110, 95, 79, 125, 111, 110, 78, 110, 111, 111, 95, 94, 108, 123, 108,
+    // CODED_SUB_BLOCK_FLAG (4)
+    121, 140, 61, 154, // SIG_COEFF_FLAG (44)
+    155, 154, 139, 153, 139, 123, 123, 63, 153, 166, 183, 140, 136, 153, 154, 166, 183, 140, 136,
+    153, 154, 166, 183, 140, 136, 153, 154, 170, 153, 123, 123, 107, 121, 107, 121, 167, 151, 183,
+    140, 151, 183, 140, 140, 140, // COEFF_ABS_LEVEL_GREATER1_FLAG (24)
+    154, 196, 196, 167, 154, 152, 167, 182, 182, 134, 149, 136, 153, 121, 136, 137, 169, 194, 166,
+    167, 154, 167, 137, 182, // COEFF_ABS_LEVEL_GREATER2_FLAG (6)
+    107, 167, 91, 122, 107, 167, // SAO_MERGE_FLAG (1)
+    153, // SAO_TYPE_IDX (1)
+    185, // CU_QP_DELTA_ABS (2)
+    154, 154, // CU_CHROMA_QP_OFFSET_FLAG (1)
+    154, // CU_CHROMA_QP_OFFSET_IDX (1)
+    154, // LOG2_RES_SCALE_ABS_PLUS1 (8)
+    154, 154, 154, 154, 154, 154, 154, 154, // RES_SCALE_SIGN_FLAG (2)
+    154, 154, // RQT_ROOT_CBF (1)
+    79,
+];
+
+/// Initial context values for initType 2 (B slices, or P slices with cabac_init_flag)
+pub static INIT_VALUES_TYPE2: [u8; context::NUM_CONTEXTS] = [
+    // SPLIT_CU_FLAG (3)
+    107, 139, 126, // CU_TRANSQUANT_BYPASS_FLAG (1)
+    154, // CU_SKIP_FLAG (3)
+    197, 185, 201, // PALETTE_MODE_FLAG (1)
+    154, // PRED_MODE_FLAG (1)
+    134, // PART_MODE (4)
+    154, 139, 154, 154, // PREV_INTRA_LUMA_PRED_FLAG (1)
+    183, // INTRA_CHROMA_PRED_MODE (1)
+    152, // INTER_PRED_IDC (5)
+    95, 79, 63, 31, 31, // MERGE_FLAG (1)
+    154, // MERGE_IDX (1)
+    137, // MVP_LX_FLAG (1)
+    168, // REF_IDX (2)
+    153, 153, // ABS_MVD_GREATER0_FLAG (2)
+    169, 198, // ABS_MVD_GREATER1_FLAG (1)
+    198, // SPLIT_TRANSFORM_FLAG (3)
+    224, 167, 122, // CBF_LUMA (2)
+    153, 111, // CBF_CBCR (5)
+    149, 92, 167, 154, 154, // TRANSFORM_SKIP_FLAG (2)
+    139, 139, // LAST_SIG_COEFF_X_PREFIX (18)
+    125, 110, 124, 110, 95, 94, 125, 111, 111, 79, 125, 126, 111, 111, 79, 108, 123, 93,
+    // LAST_SIG_COEFF_Y_PREFIX (18)
+    125, 110, 124, 110, 95, 94, 125, 111, 111, 79, 125, 126, 111, 111, 79, 108, 123, 93,
+    // CODED_SUB_BLOCK_FLAG (4)
+    121, 140, 61, 154, // SIG_COEFF_FLAG (44)
+    170, 154, 139, 153, 139, 123, 123, 63, 124, 166, 183, 140, 136, 153, 154, 166, 183, 140, 136,
+    153, 154, 166, 183, 140, 136, 153, 154, 170, 153, 138, 138, 122, 121, 122, 121, 167, 151, 183,
+    140, 151, 183, 140, 140, 140, // COEFF_ABS_LEVEL_GREATER1_FLAG (24)
+    154, 196, 167, 167, 154, 152, 167, 182, 182, 134, 149, 136, 153, 121, 136, 122, 169, 208, 166,
+    167, 154, 152, 167, 182, // COEFF_ABS_LEVEL_GREATER2_FLAG (6)
+    107, 167, 91, 107, 107, 167, // SAO_MERGE_FLAG (1)
+    153, // SAO_TYPE_IDX (1)
+    160, // CU_QP_DELTA_ABS (2)
+    154, 154, // CU_CHROMA_QP_OFFSET_FLAG (1)
+    154, // CU_CHROMA_QP_OFFSET_IDX (1)
+    154, // LOG2_RES_SCALE_ABS_PLUS1 (8)
+    154, 154, 154, 154, 154, 154, 154, 154, // RES_SCALE_SIGN_FLAG (2)
+    154, 154, // RQT_ROOT_CBF (1)
+    79,
+];
+
+/// Initial context values for a CABAC initType (H.265 9.3.2.2)
+///
+/// initType 0 is used for I slices; P and B slices use 1 or 2
+/// depending on `cabac_init_flag`.
+pub fn init_values(init_type: u8) -> &'static [u8; context::NUM_CONTEXTS] {
+    match init_type {
+        1 => &INIT_VALUES_TYPE1,
+        2 => &INIT_VALUES_TYPE2,
+        _ => &INIT_VALUES,
+    }
+}
@@ -15,6 +15,10 @@
 
 use alloc::vec::Vec;
 
+use crate::options::{ColorTarget, ToneMapping};
+#[cfg(not(feature = "std"))]
+use crate::math::F32Ext as _;
+
 /// Color primaries (ITU-T H.265 Table E.3)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -278,16 +282,17 @@ impl ColorSpace {
     /// Input: Y, Cb, Cr in range [0, 2^bit_depth - 1]
     /// Output: R, G, B in range [0.0, 1.0] (linear light)
     pub fn ycbcr_to_rgb(&self, y: u16, cb: u16, cr: u16, bit_depth: u8) -> (f32, f32, f32) {
+        self.rgb_matrix(bit_depth).apply(y, cb, cr)
+    }
+
+    /// Constants of [`ycbcr_to_rgb`](Self::ycbcr_to_rgb) at `bit_depth`
+    pub(crate) fn rgb_matrix(&self, bit_depth: u8) -> RgbMatrix {
         let max_val = (1 << bit_depth) - 1;
 
         // Normalize to [0.0, 1.0]
-        let (y_norm, cb_norm, cr_norm) = if self.full_range {
+        let (y_min, y_range, c_min, c_range) = if self.full_range {
             // Full range: 0-255 (8-bit) or 0-1023 (10-bit)
-            (
-                y as f32 / max_val as f32,
-                cb as f32 / max_val as f32,
-                cr as f32 / max_val as f32,
-            )
+            (0.0, max_val as f32, 0.0, max_val as f32)
         } else {
             // Limited range: 16-235 (8-bit) or 64-940 (10-bit) for luma
             //                16-240 (8-bit) or 64-960 (10-bit) for chroma
@@ -296,28 +301,59 @@ impl ColorSpace {
             let y_max = 235.0 * scale;
             let c_min = 16.0 * scale;
             let c_max = 240.0 * scale;
-
-            (
-                ((y as f32 - y_min) / (y_max - y_min)).clamp(0.0, 1.0),
-                ((cb as f32 - c_min) / (c_max - c_min)).clamp(0.0, 1.0),
-                ((cr as f32 - c_min) / (c_max - c_min)).clamp(0.0, 1.0),
-            )
+            (y_min, y_max - y_min, c_min, c_max - c_min)
         };
 
-        // Center chroma values
-        let pb = cb_norm - 0.5;
-        let pr = cr_norm - 0.5;
-
         // Get matrix coefficients
         let (kr, kb) = self.get_matrix_coefficients();
         let kg = 1.0 - kr - kb;
 
         // YCbCr to RGB matrix derivation (ITU-R BT.709/2020)
-        let r = y_norm + 2.0 * (1.0 - kr) * pr;
-        let g = y_norm - 2.0 * kb * (1.0 - kb) / kg * pb - 2.0 * kr * (1.0 - kr) / kg * pr;
-        let b = y_norm + 2.0 * (1.0 - kb) * pb;
+        RgbMatrix {
+            y_min,
+            y_range,
+            c_min,
+            c_range,
+            cr_to_r: 2.0 * (1.0 - kr),
+            cb_to_g: 2.0 * kb * (1.0 - kb) / kg,
+            cr_to_g: 2.0 * kr * (1.0 - kr) / kg,
+            cb_to_b: 2.0 * (1.0 - kb),
+        }
+    }
 
-        (r, g, b)
+    /// Convert RGB to YCbCr using appropriate matrix (inverse of `ycbcr_to_rgb`)
+    ///
+    /// Input: R, G, B signal values in range [0.0, 1.0]
+    /// Output: Y, Cb, Cr in range [0, 2^bit_depth - 1]
+    pub fn rgb_to_ycbcr(&self, r: f32, g: f32, b: f32, bit_depth: u8) -> (u16, u16, u16) {
+        let (kr, kb) = self.get_matrix_coefficients();
+        let kg = 1.0 - kr - kb;
+
+        let y_norm = kr * r + kg * g + kb * b;
+        let pb = (b - y_norm) / (2.0 * (1.0 - kb));
+        let pr = (r - y_norm) / (2.0 * (1.0 - kr));
+
+        let max_val = ((1u32 << bit_depth) - 1) as f32;
+        let (y, cb, cr) = if self.full_range {
+            (
+                y_norm * max_val,
+                (pb + 0.5) * max_val,
+                (pr + 0.5) * max_val,
+            )
+        } else {
+            let scale = (1 << (bit_depth - 8)) as f32;
+            (
+                16.0 * scale + y_norm * 219.0 * scale,
+                16.0 * scale + (pb + 0.5) * 224.0 * scale,
+                16.0 * scale + (pr + 0.5) * 224.0 * scale,
+            )
+        };
+
+        (
+            y.round().clamp(0.0, max_val) as u16,
+            cb.round().clamp(0.0, max_val) as u16,
+            cr.round().clamp(0.0, max_val) as u16,
+        )
     }
 
     /// Apply transfer function (OETF inverse / EOTF) to convert to linear light
@@ -439,6 +475,20 @@ impl ColorSpace {
         }
     }
 
+    /// Bring HDR into SDR range by clipping above SDR white (100 nits)
+    ///
+    /// Input and output are as for [`tone_map_to_sdr`](Self::tone_map_to_sdr).
+    pub fn clip_to_sdr(&self, linear: f32) -> f32 {
+        let sdr = match self.transfer {
+            // 1.0 is 10000 nits
+            TransferCharacteristics::Pq => linear * 100.0,
+            // Displayed on a 1000 nit display, like tone_map_to_sdr
+            TransferCharacteristics::Hlg => Self::hlg_ootf(linear, 1000.0) * 10.0,
+            _ => linear,
+        };
+        sdr.clamp(0.0, 1.0)
+    }
+
     /// Reinhard tone mapping
     fn reinhard_tone_map(linear: f32, peak_nits: f32, target_nits: f32) -> f32 {
         if linear <= 0.0 {
@@ -481,30 +531,55 @@ impl ColorSpace {
 
     /// Full pipeline: YCbCr → RGB (linear) → tone map → sRGB signal → 8-bit
     pub fn ycbcr_to_rgb8(&self, y: u16, cb: u16, cr: u16, bit_depth: u8) -> (u8, u8, u8) {
-        // Convert YCbCr to RGB in signal domain
-        let (r_signal, g_signal, b_signal) = self.ycbcr_to_rgb(y, cb, cr, bit_depth);
-
-        // Apply EOTF to get linear light
-        let r_linear = self.apply_eotf(r_signal);
-        let g_linear = self.apply_eotf(g_signal);
-        let b_linear = self.apply_eotf(b_signal);
+        self.ycbcr_to_rgb8_with(y, cb, cr, bit_depth, ToneMapping::Reinhard, ColorTarget::Srgb)
+    }
 
-        // Tone map HDR to SDR if needed
-        let r_sdr = self.tone_map_to_sdr(r_linear);
-        let g_sdr = self.tone_map_to_sdr(g_linear);
-        let b_sdr = self.tone_map_to_sdr(b_linear);
+    /// Full pipeline to 8-bit with the given tone mapping and output transfer
+    ///
+    /// [`ColorTarget::Passthrough`] skips the transfer functions and tone
+    /// mapping, returning the matrixed signal.
+    ///
+    /// This gives the same bytes as converting whole frames: passthrough
+    /// uses the same fixed-point matrix, and the transfer functions see the
+    /// signal rounded to a step of the same table.
+    pub fn ycbcr_to_rgb8_with(
+        &self,
+        y: u16,
+        cb: u16,
+        cr: u16,
+        bit_depth: u8,
+        tone_mapping: ToneMapping,
+        target: ColorTarget,
+    ) -> (u8, u8, u8) {
+        let [r, g, b] = if target == ColorTarget::Passthrough {
+            self.rgb_matrix(bit_depth).to_fixed().apply(y, cb, cr)
+        } else {
+            // Convert YCbCr to RGB in signal domain
+            let (r_signal, g_signal, b_signal) = self.ycbcr_to_rgb(y, cb, cr, bit_depth);
+            [r_signal, g_signal, b_signal].map(|signal| {
+                self.transfer_step_to_rgb8(transfer_step(signal), tone_mapping, target)
+            })
+        };
+        (r, g, b)
+    }
 
-        // Apply sRGB OETF
-        let r_out = self.apply_sdr_oetf(r_sdr);
-        let g_out = self.apply_sdr_oetf(g_sdr);
-        let b_out = self.apply_sdr_oetf(b_sdr);
+    /// 8-bit output for `target` of the signal value at `step` of the
+    /// transfer table
+    fn transfer_step_to_rgb8(&self, step: usize, tone_mapping: ToneMapping, target: ColorTarget) -> u8 {
+        let signal = step as f32 / TRANSFER_STEPS;
+        // Apply EOTF to get linear light, then tone map HDR to SDR if needed
+        let linear = self.apply_eotf(signal);
+        let sdr = match tone_mapping {
+            ToneMapping::Reinhard => self.tone_map_to_sdr(linear),
+            ToneMapping::Clip => self.clip_to_sdr(linear),
+        };
+        let out = match target {
+            ColorTarget::Srgb => self.apply_sdr_oetf(sdr),
+            _ => sdr,
+        };
 
         // Convert to 8-bit
-        (
-            (r_out * 255.0).round().clamp(0.0, 255.0) as u8,
-            (g_out * 255.0).round().clamp(0.0, 255.0) as u8,
-            (b_out * 255.0).round().clamp(0.0, 255.0) as u8,
-        )
+        (out * 255.0).round().clamp(0.0, 255.0) as u8
     }
 
     /// Full pipeline outputting 16-bit RGB (for high bit depth preservation)
@@ -550,6 +625,250 @@ impl ColorSpace {
     }
 }
 
+/// Constants of the YCbCr to RGB signal conversion at one bit depth
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RgbMatrix {
+    /// Luma black level
+    pub(crate) y_min: f32,
+    /// Luma black to white distance
+    pub(crate) y_range: f32,
+    /// Lowest chroma value
+    pub(crate) c_min: f32,
+    /// Chroma value range
+    pub(crate) c_range: f32,
+    /// Weight of Pr in R
+    pub(crate) cr_to_r: f32,
+    /// Weight of Pb subtracted from G
+    pub(crate) cb_to_g: f32,
+    /// Weight of Pr subtracted from G
+    pub(crate) cr_to_g: f32,
+    /// Weight of Pb in B
+    pub(crate) cb_to_b: f32,
+}
+
+impl RgbMatrix {
+    /// Convert one sample to RGB signal values
+    #[inline]
+    pub(crate) fn apply(&self, y: u16, cb: u16, cr: u16) -> (f32, f32, f32) {
+        let y_norm = ((y as f32 - self.y_min) / self.y_range).clamp(0.0, 1.0);
+        let cb_norm = ((cb as f32 - self.c_min) / self.c_range).clamp(0.0, 1.0);
+        let cr_norm = ((cr as f32 - self.c_min) / self.c_range).clamp(0.0, 1.0);
+
+        // Center chroma values
+        let pb = cb_norm - 0.5;
+        let pr = cr_norm - 0.5;
+
+        let r = y_norm + self.cr_to_r * pr;
+        let g = y_norm - self.cb_to_g * pb - self.cr_to_g * pr;
+        let b = y_norm + self.cb_to_b * pb;
+
+        (r, g, b)
+    }
+
+    /// Convert a row of samples to RGB signal values, one plane each
+    pub(crate) fn apply_row(&self, [y, cb, cr]: [&[u16]; 3], [r, g, b]: [&mut [f32]; 3]) {
+        #[cfg(all(
+            feature = "unsafe-simd",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        if let Some(isa) = super::simd::Isa::detect() {
+            super::colorspace_simd::ycbcr_to_rgb_row(isa, self, [y, cb, cr], [r, g, b]);
+            return;
+        }
+
+        self.apply_row_scalar([y, cb, cr], [r, g, b]);
+    }
+
+    /// Scalar version of [`apply_row`](Self::apply_row)
+    pub(crate) fn apply_row_scalar(&self, [y, cb, cr]: [&[u16]; 3], [r, g, b]: [&mut [f32]; 3]) {
+        let samples = y.iter().zip(cb).zip(cr);
+        let outputs = r.iter_mut().zip(g.iter_mut()).zip(b.iter_mut());
+        for (((&y, &cb), &cr), ((r, g), b)) in samples.zip(outputs) {
+            (*r, *g, *b) = self.apply(y, cb, cr);
+        }
+    }
+}
+
+impl RgbMatrix {
+    /// Fixed-point version mapping samples straight to 8-bit output
+    pub(crate) fn to_fixed(self) -> FixedRgbMatrix {
+        // All weights are positive, so adding a half rounds to nearest
+        let fixed = |weight: f64| (weight * f64::from(1 << FixedRgbMatrix::SHIFT) + 0.5) as i32;
+        // Chroma is taken as 2 * sample - (c_min + c_max), twice its
+        // distance from the centre
+        let chroma_weight = |weight: f32| fixed(255.0 * f64::from(weight) / (2.0 * f64::from(self.c_range)));
+        FixedRgbMatrix {
+            y_min: self.y_min as i32,
+            y_max: (self.y_min + self.y_range) as i32,
+            c_min: self.c_min as i32,
+            c_max: (self.c_min + self.c_range) as i32,
+            y_to_rgb: fixed(255.0 / f64::from(self.y_range)),
+            cr_to_r: chroma_weight(self.cr_to_r),
+            cb_to_g: chroma_weight(self.cb_to_g),
+            cr_to_g: chroma_weight(self.cr_to_g),
+            cb_to_b: chroma_weight(self.cb_to_b),
+        }
+    }
+}
+
+/// YCbCr to 8-bit RGB in fixed-point integer arithmetic
+///
+/// For output that is the matrixed signal itself. Gives the same bytes as
+/// [`RgbMatrix::apply`] scaled to 8 bits and rounded, except for values
+/// within about 2^(bit depth - [`SHIFT`](Self::SHIFT)) levels of halfway
+/// between two levels, where the rounding error of the weights decides.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FixedRgbMatrix {
+    /// Luma black level
+    pub(crate) y_min: i32,
+    /// Luma white level
+    pub(crate) y_max: i32,
+    /// Lowest chroma value
+    pub(crate) c_min: i32,
+    /// Highest chroma value
+    pub(crate) c_max: i32,
+    /// Weight of luma above black in each component
+    pub(crate) y_to_rgb: i32,
+    /// Weight of doubled Cr in R
+    pub(crate) cr_to_r: i32,
+    /// Weight of doubled Cb subtracted from G
+    pub(crate) cb_to_g: i32,
+    /// Weight of doubled Cr subtracted from G
+    pub(crate) cr_to_g: i32,
+    /// Weight of doubled Cb in B
+    pub(crate) cb_to_b: i32,
+}
+
+impl FixedRgbMatrix {
+    /// Fraction bits of the weights
+    pub(crate) const SHIFT: u32 = 20;
+
+    /// Convert one sample to 8-bit RGB
+    #[inline]
+    pub(crate) fn apply(&self, y: u16, cb: u16, cr: u16) -> [u8; 3] {
+        let luma = (i32::from(y).clamp(self.y_min, self.y_max) - self.y_min) * self.y_to_rgb
+            + (1 << (Self::SHIFT - 1));
+        let chroma = |c: u16| 2 * i32::from(c).clamp(self.c_min, self.c_max) - (self.c_min + self.c_max);
+        let (pb, pr) = (chroma(cb), chroma(cr));
+
+        let r = luma + self.cr_to_r * pr;
+        let g = luma - self.cb_to_g * pb - self.cr_to_g * pr;
+        let b = luma + self.cb_to_b * pb;
+        [r, g, b].map(|v| (v >> Self::SHIFT).clamp(0, 255) as u8)
+    }
+
+    /// Convert a row of samples to interleaved 8-bit RGB
+    pub(crate) fn apply_row(&self, [y, cb, cr]: [&[u16]; 3], rgb: &mut [u8]) {
+        #[cfg(all(
+            feature = "unsafe-simd",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        if let Some(isa) = super::simd::Isa::detect() {
+            super::colorspace_simd::ycbcr_to_rgb8_row(isa, self, [y, cb, cr], rgb);
+            return;
+        }
+
+        self.apply_row_scalar([y, cb, cr], rgb);
+    }
+
+    /// Scalar version of [`apply_row`](Self::apply_row)
+    pub(crate) fn apply_row_scalar(&self, [y, cb, cr]: [&[u16]; 3], rgb: &mut [u8]) {
+        let samples = y.iter().zip(cb).zip(cr);
+        for (((&y, &cb), &cr), out) in samples.zip(rgb.chunks_exact_mut(3)) {
+            out.copy_from_slice(&self.apply(y, cb, cr));
+        }
+    }
+}
+
+/// Transfer table entries per unit of signal
+const TRANSFER_STEPS: f32 = 4096.0;
+/// Transfer table entries, covering signal values up to 2 (the matrices
+/// give less than 1.95); negative values give the same output as 0
+const TRANSFER_TABLE_LEN: usize = 2 * 4096 + 1;
+
+/// Transfer table step nearest to a signal value
+#[inline]
+fn transfer_step(signal: f32) -> usize {
+    ((signal * TRANSFER_STEPS + 0.5) as usize).min(TRANSFER_TABLE_LEN - 1)
+}
+
+/// Converter of YCbCr rows to 8-bit RGB for one frame and set of options
+///
+/// Gives the same bytes as [`ColorSpace::ycbcr_to_rgb8_with`] sample by
+/// sample.
+pub(crate) struct Rgb8Converter {
+    colorspace: ColorSpace,
+    tone_mapping: ToneMapping,
+    target: ColorTarget,
+    matrix: RgbMatrix,
+    path: Rgb8Path,
+}
+
+/// How [`Rgb8Converter`] gets from samples to output
+enum Rgb8Path {
+    /// Passthrough: fixed-point matrix straight to 8 bits
+    Signal(FixedRgbMatrix),
+    /// Transfer functions looked up in a table of 8-bit output indexed by
+    /// signal step
+    Table(Vec<u8>),
+    /// Transfer functions evaluated for every value
+    Exact,
+}
+
+impl Rgb8Converter {
+    /// Samples converted at once on the transfer function paths
+    const CHUNK: usize = 64;
+
+    /// Prepare the conversion of about `samples` samples
+    ///
+    /// The transfer function table is only built when there are more
+    /// samples than entries.
+    pub(crate) fn new(
+        colorspace: &ColorSpace,
+        bit_depth: u8,
+        tone_mapping: ToneMapping,
+        target: ColorTarget,
+        samples: usize,
+    ) -> Self {
+        let matrix = colorspace.rgb_matrix(bit_depth);
+        let path = if target == ColorTarget::Passthrough {
+            Rgb8Path::Signal(matrix.to_fixed())
+        } else if samples > TRANSFER_TABLE_LEN {
+            let table = (0..TRANSFER_TABLE_LEN)
+                .map(|step| colorspace.transfer_step_to_rgb8(step, tone_mapping, target))
+                .collect();
+            Rgb8Path::Table(table)
+        } else {
+            Rgb8Path::Exact
+        };
+        Self { colorspace: *colorspace, tone_mapping, target, matrix, path }
+    }
+
+    /// Convert a row of samples to interleaved RGB, three bytes per sample
+    /// of `y`
+    pub(crate) fn convert_row(&self, [y, cb, cr]: [&[u16]; 3], rgb: &mut [u8]) {
+        if let Rgb8Path::Signal(fixed) = &self.path {
+            fixed.apply_row([y, cb, cr], rgb);
+            return;
+        }
+
+        let (mut r, mut g, mut b) = ([0f32; Self::CHUNK], [0f32; Self::CHUNK], [0f32; Self::CHUNK]);
+        for (start, out) in (0..y.len()).step_by(Self::CHUNK).zip(rgb.chunks_mut(3 * Self::CHUNK)) {
+            let len = (y.len() - start).min(Self::CHUNK);
+            let samples = [&y[start..][..len], &cb[start..][..len], &cr[start..][..len]];
+            self.matrix.apply_row(samples, [&mut r[..len], &mut g[..len], &mut b[..len]]);
+            let signals = r[..len].iter().zip(&g[..len]).zip(&b[..len]);
+            for (((&r, &g), &b), out) in signals.zip(out.chunks_exact_mut(3)) {
+                let rgb = [r, g, b].map(|signal| match &self.path {
+                    Rgb8Path::Table(table) => table[transfer_step(signal)],
+                    _ => self.colorspace.transfer_step_to_rgb8(transfer_step(signal), self.tone_mapping, self.target),
+                });
+                out.copy_from_slice(&rgb);
+            }
+        }
+    }
+}
+
 /// Batch convert YCbCr frame to RGB8
 pub fn convert_frame_to_rgb8(
     y_plane: &[u16],
@@ -605,6 +924,7 @@ pub fn convert_frame_to_rgb8(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
 
     #[test]
     fn test_bt709_conversion() {
@@ -623,6 +943,77 @@ mod tests {
         assert_eq!(b, 0);
     }
 
+    #[test]
+    fn test_fixed_matrix_matches_float() {
+        for (bit_depth, full_range, matrix) in [
+            (8, false, MatrixCoefficients::Bt709),
+            (8, true, MatrixCoefficients::Bt601),
+            (10, false, MatrixCoefficients::Bt2020Ncl),
+            (12, true, MatrixCoefficients::Smpte240M),
+        ] {
+            let cs = ColorSpace { matrix, full_range, ..ColorSpace::default() };
+            let float = cs.rgb_matrix(bit_depth);
+            let fixed = float.to_fixed();
+            let tolerance = 2f32.powi(i32::from(bit_depth) - FixedRgbMatrix::SHIFT as i32);
+            let step = 1 << (bit_depth - 5);
+            for y in (0..1u16 << bit_depth).step_by(step / 4) {
+                for cb in (0..1u16 << bit_depth).step_by(step) {
+                    for cr in (0..1u16 << bit_depth).step_by(step) {
+                        let (r, g, b) = float.apply(y, cb, cr);
+                        for (actual, v) in fixed.apply(y, cb, cr).into_iter().zip([r, g, b]) {
+                            // Only values about halfway between two levels may round apart
+                            let level = (v * 255.0).clamp(0.0, 255.0);
+                            assert!(
+                                f32::from(actual) == level.round() || (level.fract() - 0.5).abs() < tolerance,
+                                "{bit_depth}-bit {matrix:?} ({y}, {cb}, {cr}): {actual} for {level}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_transfer_table_matches_exact() {
+        let pq = ColorSpace::from_vui(9, 16, 9, false);
+        let hlg = ColorSpace::from_vui(9, 18, 9, false);
+        let gamma22 = ColorSpace { transfer: TransferCharacteristics::Gamma22, ..ColorSpace::default() };
+        let len = 4096;
+        let y: Vec<u16> = (0..len).map(|i| (i % 1024) as u16).collect();
+        let cb: Vec<u16> = (0..len).map(|i| (i * 7 % 1024) as u16).collect();
+        let cr: Vec<u16> = (0..len).map(|i| (i * 13 % 1024) as u16).collect();
+
+        for cs in [pq, hlg, gamma22] {
+            for (tone_mapping, target) in [
+                (ToneMapping::Reinhard, ColorTarget::Srgb),
+                (ToneMapping::Clip, ColorTarget::Srgb),
+                (ToneMapping::Reinhard, ColorTarget::Linear),
+            ] {
+                let mut exact = vec![0u8; 3 * len];
+                let mut table = vec![0u8; 3 * len];
+                Rgb8Converter::new(&cs, 10, tone_mapping, target, 0).convert_row([&y, &cb, &cr], &mut exact);
+                Rgb8Converter::new(&cs, 10, tone_mapping, target, usize::MAX)
+                    .convert_row([&y, &cb, &cr], &mut table);
+                assert_eq!(table, exact, "{:?} {target:?}", cs.transfer);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rgb_to_ycbcr_roundtrip() {
+        let cs = ColorSpace::default();
+
+        assert_eq!(cs.rgb_to_ycbcr(1.0, 1.0, 1.0, 8), (235, 128, 128));
+        assert_eq!(cs.rgb_to_ycbcr(0.0, 0.0, 0.0, 8), (16, 128, 128));
+
+        let (y, cb, cr) = cs.rgb_to_ycbcr(1.0, 0.0, 0.0, 10);
+        let (r, g, b) = cs.ycbcr_to_rgb(y, cb, cr, 10);
+        assert!((r - 1.0).abs() < 0.01);
+        assert!(g.abs() < 0.01);
+        assert!(b.abs() < 0.01);
+    }
+
     #[test]
     fn test_pq_eotf() {
         // Test that PQ EOTF is monotonic
@@ -0,0 +1,310 @@
+//! SIMD YCbCr to RGB conversion
+//!
+//! Converts eight samples of a row at a time with AVX2 on x86_64 and NEON
+//! on aarch64, selected at runtime. The signal kernels do the same f32
+//! operations in the same order as [`RgbMatrix::apply`], without fused
+//! multiply-adds, so they match it bit for bit; the transfer functions stay
+//! scalar. The 8-bit kernels are the integer arithmetic of
+//! [`FixedRgbMatrix::apply`], with saturating narrows as the final clamp.
+//!
+//! Enabled only when the `unsafe-simd` feature is active.
+
+#[cfg(target_arch = "aarch64")]
+use core::arch::aarch64::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use super::colorspace::{FixedRgbMatrix, RgbMatrix};
+use super::simd::Isa;
+#[cfg(target_arch = "aarch64")]
+use super::simd::load_u16x8_neon;
+#[cfg(target_arch = "x86_64")]
+use super::simd::load_u16x8_avx2;
+
+/// [`RgbMatrix::apply_row`] with the kernels of `isa`
+pub(super) fn ycbcr_to_rgb_row(
+    isa: Isa,
+    matrix: &RgbMatrix,
+    [y, cb, cr]: [&[u16]; 3],
+    [r, g, b]: [&mut [f32]; 3],
+) {
+    let len = y.len().min(cb.len()).min(cr.len()).min(r.len()).min(g.len()).min(b.len());
+    let simd = len / 8 * 8;
+    // SAFETY: the CPU supports `isa` and the kernel accesses `simd`
+    // samples of each slice
+    unsafe {
+        match isa {
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 | Isa::Avx512 => ycbcr_to_rgb_avx2(matrix, [y, cb, cr], [&mut *r, &mut *g, &mut *b], simd),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => ycbcr_to_rgb_neon(matrix, [y, cb, cr], [&mut *r, &mut *g, &mut *b], simd),
+        }
+    }
+    matrix.apply_row_scalar(
+        [&y[simd..len], &cb[simd..len], &cr[simd..len]],
+        [&mut r[simd..len], &mut g[simd..len], &mut b[simd..len]],
+    );
+}
+
+/// [`FixedRgbMatrix::apply_row`] with the kernels of `isa`
+pub(super) fn ycbcr_to_rgb8_row(isa: Isa, matrix: &FixedRgbMatrix, [y, cb, cr]: [&[u16]; 3], rgb: &mut [u8]) {
+    let len = y.len().min(cb.len()).min(cr.len()).min(rgb.len() / 3);
+    let simd = len / 8 * 8;
+    // SAFETY: the CPU supports `isa` and the kernel accesses `simd`
+    // samples of each plane and `3 * simd` bytes of `rgb`
+    unsafe {
+        match isa {
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 | Isa::Avx512 => ycbcr_to_rgb8_avx2(matrix, [y, cb, cr], rgb, simd),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => ycbcr_to_rgb8_neon(matrix, [y, cb, cr], rgb, simd),
+        }
+    }
+    matrix.apply_row_scalar(
+        [&y[simd..len], &cb[simd..len], &cr[simd..len]],
+        &mut rgb[3 * simd..3 * len],
+    );
+}
+
+/// YCbCr to RGB signal values with AVX2
+///
+/// # Safety
+/// The CPU must support AVX2 and all slices hold `len` samples, a multiple
+/// of 8.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn ycbcr_to_rgb_avx2(m: &RgbMatrix, [y, cb, cr]: [&[u16]; 3], [r, g, b]: [&mut [f32]; 3], len: usize) {
+    let (zero, one, half) = (_mm256_setzero_ps(), _mm256_set1_ps(1.0), _mm256_set1_ps(0.5));
+    let (y_min, y_range) = (_mm256_set1_ps(m.y_min), _mm256_set1_ps(m.y_range));
+    let (c_min, c_range) = (_mm256_set1_ps(m.c_min), _mm256_set1_ps(m.c_range));
+    let normalize = |v, min, range| {
+        let v = _mm256_div_ps(_mm256_sub_ps(_mm256_cvtepi32_ps(v), min), range);
+        _mm256_min_ps(_mm256_max_ps(v, zero), one)
+    };
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (y_v, cb_v, cr_v) = unsafe {
+            (
+                load_u16x8_avx2(y.as_ptr().add(i)),
+                load_u16x8_avx2(cb.as_ptr().add(i)),
+                load_u16x8_avx2(cr.as_ptr().add(i)),
+            )
+        };
+        let luma = normalize(y_v, y_min, y_range);
+        let pb = _mm256_sub_ps(normalize(cb_v, c_min, c_range), half);
+        let pr = _mm256_sub_ps(normalize(cr_v, c_min, c_range), half);
+
+        let red = _mm256_add_ps(luma, _mm256_mul_ps(_mm256_set1_ps(m.cr_to_r), pr));
+        let green = _mm256_sub_ps(luma, _mm256_mul_ps(_mm256_set1_ps(m.cb_to_g), pb));
+        let green = _mm256_sub_ps(green, _mm256_mul_ps(_mm256_set1_ps(m.cr_to_g), pr));
+        let blue = _mm256_add_ps(luma, _mm256_mul_ps(_mm256_set1_ps(m.cb_to_b), pb));
+        // SAFETY: the caller guarantees `len` samples
+        unsafe {
+            _mm256_storeu_ps(r.as_mut_ptr().add(i), red);
+            _mm256_storeu_ps(g.as_mut_ptr().add(i), green);
+            _mm256_storeu_ps(b.as_mut_ptr().add(i), blue);
+        }
+    }
+}
+
+/// YCbCr to RGB signal values with NEON
+///
+/// # Safety
+/// The CPU must support NEON and all slices hold `len` samples, a multiple
+/// of 8.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn ycbcr_to_rgb_neon(m: &RgbMatrix, [y, cb, cr]: [&[u16]; 3], [r, g, b]: [&mut [f32]; 3], len: usize) {
+    let (zero, one, half) = (vdupq_n_f32(0.0), vdupq_n_f32(1.0), vdupq_n_f32(0.5));
+    let normalize = |v: int32x4_t, min: f32, range: f32| {
+        let v = vdivq_f32(vsubq_f32(vcvtq_f32_s32(v), vdupq_n_f32(min)), vdupq_n_f32(range));
+        vminq_f32(vmaxq_f32(v, zero), one)
+    };
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (y_v, cb_v, cr_v) = unsafe {
+            (
+                load_u16x8_neon(y.as_ptr().add(i)),
+                load_u16x8_neon(cb.as_ptr().add(i)),
+                load_u16x8_neon(cr.as_ptr().add(i)),
+            )
+        };
+        for half_index in 0..2 {
+            let luma = normalize(y_v[half_index], m.y_min, m.y_range);
+            let pb = vsubq_f32(normalize(cb_v[half_index], m.c_min, m.c_range), half);
+            let pr = vsubq_f32(normalize(cr_v[half_index], m.c_min, m.c_range), half);
+
+            let red = vaddq_f32(luma, vmulq_n_f32(pr, m.cr_to_r));
+            let green = vsubq_f32(luma, vmulq_n_f32(pb, m.cb_to_g));
+            let green = vsubq_f32(green, vmulq_n_f32(pr, m.cr_to_g));
+            let blue = vaddq_f32(luma, vmulq_n_f32(pb, m.cb_to_b));
+            let at = i + half_index * 4;
+            // SAFETY: the caller guarantees `len` samples
+            unsafe {
+                vst1q_f32(r.as_mut_ptr().add(at), red);
+                vst1q_f32(g.as_mut_ptr().add(at), green);
+                vst1q_f32(b.as_mut_ptr().add(at), blue);
+            }
+        }
+    }
+}
+
+/// YCbCr to interleaved 8-bit RGB with AVX2
+///
+/// # Safety
+/// The CPU must support AVX2, the planes hold `len` samples, a multiple of
+/// 8, and `rgb` holds `3 * len` bytes.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn ycbcr_to_rgb8_avx2(m: &FixedRgbMatrix, [y, cb, cr]: [&[u16]; 3], rgb: &mut [u8], len: usize) {
+    const SHIFT: i32 = FixedRgbMatrix::SHIFT as i32;
+    let (y_min, y_max) = (_mm256_set1_epi32(m.y_min), _mm256_set1_epi32(m.y_max));
+    let (c_min, c_max) = (_mm256_set1_epi32(m.c_min), _mm256_set1_epi32(m.c_max));
+    let c_sum = _mm256_set1_epi32(m.c_min + m.c_max);
+    let rounding = _mm256_set1_epi32(1 << (SHIFT - 1));
+    let weighted = |v, weight: i32| _mm256_mullo_epi32(v, _mm256_set1_epi32(weight));
+    let chroma = |v| _mm256_sub_epi32(_mm256_slli_epi32::<1>(_mm256_min_epi32(_mm256_max_epi32(v, c_min), c_max)), c_sum);
+    let to_i16 = |v| _mm_packs_epi32(_mm256_castsi256_si128(v), _mm256_extracti128_si256::<1>(v));
+    // Interleave [r0..r7, g0..g7] and [b0..b7] into 16 and then 8 bytes
+    let rg_first = _mm_setr_epi8(0, 8, -1, 1, 9, -1, 2, 10, -1, 3, 11, -1, 4, 12, -1, 5);
+    let b_first = _mm_setr_epi8(-1, -1, 0, -1, -1, 1, -1, -1, 2, -1, -1, 3, -1, -1, 4, -1);
+    let rg_last = _mm_setr_epi8(13, -1, 6, 14, -1, 7, 15, -1, -1, -1, -1, -1, -1, -1, -1, -1);
+    let b_last = _mm_setr_epi8(-1, 5, -1, -1, 6, -1, -1, 7, -1, -1, -1, -1, -1, -1, -1, -1);
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (y_v, cb_v, cr_v) = unsafe {
+            (
+                load_u16x8_avx2(y.as_ptr().add(i)),
+                load_u16x8_avx2(cb.as_ptr().add(i)),
+                load_u16x8_avx2(cr.as_ptr().add(i)),
+            )
+        };
+        let luma = _mm256_sub_epi32(_mm256_min_epi32(_mm256_max_epi32(y_v, y_min), y_max), y_min);
+        let luma = _mm256_add_epi32(weighted(luma, m.y_to_rgb), rounding);
+        let (pb, pr) = (chroma(cb_v), chroma(cr_v));
+
+        let red = _mm256_add_epi32(luma, weighted(pr, m.cr_to_r));
+        let green = _mm256_sub_epi32(luma, weighted(pb, m.cb_to_g));
+        let green = _mm256_sub_epi32(green, weighted(pr, m.cr_to_g));
+        let blue = _mm256_add_epi32(luma, weighted(pb, m.cb_to_b));
+        let [red, green, blue] = [red, green, blue].map(|v| to_i16(_mm256_srai_epi32::<SHIFT>(v)));
+
+        let rg = _mm_packus_epi16(red, green);
+        let b = _mm_packus_epi16(blue, blue);
+        let first = _mm_or_si128(_mm_shuffle_epi8(rg, rg_first), _mm_shuffle_epi8(b, b_first));
+        let last = _mm_or_si128(_mm_shuffle_epi8(rg, rg_last), _mm_shuffle_epi8(b, b_last));
+        // SAFETY: the caller guarantees `3 * len` bytes
+        unsafe {
+            let out = rgb.as_mut_ptr().add(3 * i);
+            _mm_storeu_si128(out.cast(), first);
+            _mm_storel_epi64(out.add(16).cast(), last);
+        }
+    }
+}
+
+/// YCbCr to interleaved 8-bit RGB with NEON
+///
+/// # Safety
+/// The CPU must support NEON, the planes hold `len` samples, a multiple of
+/// 8, and `rgb` holds `3 * len` bytes.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn ycbcr_to_rgb8_neon(m: &FixedRgbMatrix, [y, cb, cr]: [&[u16]; 3], rgb: &mut [u8], len: usize) {
+    const SHIFT: i32 = FixedRgbMatrix::SHIFT as i32;
+    let clamp = |v, min, max| vminq_s32(vmaxq_s32(v, vdupq_n_s32(min)), vdupq_n_s32(max));
+    let chroma = |v| vsubq_s32(vshlq_n_s32::<1>(clamp(v, m.c_min, m.c_max)), vdupq_n_s32(m.c_min + m.c_max));
+    let to_u8 = |low, high| vqmovun_s16(vcombine_s16(low, high));
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (y_v, cb_v, cr_v) = unsafe {
+            (
+                load_u16x8_neon(y.as_ptr().add(i)),
+                load_u16x8_neon(cb.as_ptr().add(i)),
+                load_u16x8_neon(cr.as_ptr().add(i)),
+            )
+        };
+        let [low, high] = [0, 1].map(|half| {
+            let luma = vsubq_s32(clamp(y_v[half], m.y_min, m.y_max), vdupq_n_s32(m.y_min));
+            let luma = vmlaq_n_s32(vdupq_n_s32(1 << (SHIFT - 1)), luma, m.y_to_rgb);
+            let (pb, pr) = (chroma(cb_v[half]), chroma(cr_v[half]));
+
+            let red = vmlaq_n_s32(luma, pr, m.cr_to_r);
+            let green = vmlsq_n_s32(vmlsq_n_s32(luma, pb, m.cb_to_g), pr, m.cr_to_g);
+            let blue = vmlaq_n_s32(luma, pb, m.cb_to_b);
+            [red, green, blue].map(|v| vqmovn_s32(vshrq_n_s32::<SHIFT>(v)))
+        });
+        let out = uint8x8x3_t(to_u8(low[0], high[0]), to_u8(low[1], high[1]), to_u8(low[2], high[2]));
+        // SAFETY: the caller guarantees `3 * len` bytes
+        unsafe { vst3_u8(rgb.as_mut_ptr().add(3 * i), out) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hevc::colorspace::{ColorSpace, MatrixCoefficients};
+
+    #[test]
+    fn test_rgb_simd_matches_scalar() {
+        for (bit_depth, full_range, matrix) in [
+            (8, false, MatrixCoefficients::Bt709),
+            (8, true, MatrixCoefficients::Bt601),
+            (10, false, MatrixCoefficients::Bt2020Ncl),
+            (12, true, MatrixCoefficients::Bt709),
+        ] {
+            let colorspace = ColorSpace { matrix, full_range, ..ColorSpace::default() };
+            let m = colorspace.rgb_matrix(bit_depth);
+            // Every value of each plane, including those outside the limited range
+            let len = 75;
+            let sample = |i: usize, step: usize| ((i * step) % (1 << bit_depth)) as u16;
+            let y: Vec<u16> = (0..len).map(|i| sample(i, 37)).collect();
+            let cb: Vec<u16> = (0..len).map(|i| sample(i, 101)).collect();
+            let cr: Vec<u16> = (0..len).map(|i| sample(i + 3, 59)).collect();
+
+            let mut expected = [vec![0f32; len], vec![0f32; len], vec![0f32; len]];
+            let [r, g, b] = &mut expected;
+            m.apply_row_scalar([&y, &cb, &cr], [r, g, b]);
+            for isa in Isa::supported() {
+                let mut actual = [vec![0f32; len], vec![0f32; len], vec![0f32; len]];
+                let [r, g, b] = &mut actual;
+                ycbcr_to_rgb_row(isa, &m, [&y, &cb, &cr], [r, g, b]);
+                // Compare bits so that -0.0 and 0.0 differ
+                let bits = |planes: &[Vec<f32>; 3]| -> Vec<u32> {
+                    planes.iter().flatten().map(|v| v.to_bits()).collect()
+                };
+                assert_eq!(bits(&actual), bits(&expected), "{isa:?} {bit_depth}-bit {matrix:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_rgb8_simd_matches_scalar() {
+        for (bit_depth, full_range, matrix) in [
+            (8, false, MatrixCoefficients::Bt709),
+            (8, true, MatrixCoefficients::Bt601),
+            (10, false, MatrixCoefficients::Bt2020Ncl),
+            (12, true, MatrixCoefficients::Bt709),
+            (16, false, MatrixCoefficients::Smpte240M),
+        ] {
+            let colorspace = ColorSpace { matrix, full_range, ..ColorSpace::default() };
+            let m = colorspace.rgb_matrix(bit_depth).to_fixed();
+            let len = 203;
+            let sample = |i: usize, step: usize| ((i * step) % (1 << bit_depth)) as u16;
+            let y: Vec<u16> = (0..len).map(|i| sample(i, 37 << (bit_depth - 8))).collect();
+            let cb: Vec<u16> = (0..len).map(|i| sample(i, 101 << (bit_depth - 8))).collect();
+            let cr: Vec<u16> = (0..len).map(|i| sample(i + 3, 59 << (bit_depth - 8))).collect();
+
+            let mut expected = vec![0u8; 3 * len];
+            m.apply_row_scalar([&y, &cb, &cr], &mut expected);
+            for isa in Isa::supported() {
+                let mut actual = vec![0u8; 3 * len];
+                ycbcr_to_rgb8_row(isa, &m, [&y, &cb, &cr], &mut actual);
+                assert_eq!(actual, expected, "{isa:?} {bit_depth}-bit {matrix:?}");
+            }
+        }
+    }
+}
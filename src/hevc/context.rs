@@ -0,0 +1,230 @@
+//! Buffers reused between decodes
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::ctu::SliceBuffers;
+use super::deblock::DeblockingContext;
+use super::picture::DecodedFrame;
+use super::{DecodeMode, Result, bitstream, config_nal_units, decode_nal_units};
+use crate::heif::HevcDecoderConfig;
+use crate::options::DecoderOptions;
+
+/// Frame planes kept by a context; enough for a tile and a grid output of
+/// 4:2:0 pictures with alpha
+const MAX_PLANES: usize = 8;
+
+/// Buffers recycled between the pictures, tiles and images one thread decodes
+///
+/// Decoding a picture needs its frame planes and picture-sized arrays for
+/// prediction state, deblocking and SAO. A context keeps them once a
+/// decode is done, so the next picture of the same or a smaller size
+/// reuses the allocations. Frames handed back with
+/// [`recycle`](Self::recycle) provide the planes of later frames.
+///
+/// ```
+/// use heic_decoder::DecoderOptions;
+/// use heic_decoder::hevc::{DecodeMode, DecoderContext};
+///
+/// # let files: Vec<Vec<u8>> = Vec::new();
+/// let mut context = DecoderContext::new();
+/// let options = DecoderOptions::new();
+/// for data in &files {
+///     let frame = context.decode(data, DecodeMode::Full, &options)?;
+///     // ... use the frame ...
+///     context.recycle(frame);
+/// }
+/// # Ok::<(), heic_decoder::HevcError>(())
+/// ```
+#[derive(Default)]
+pub struct DecoderContext {
+    /// Arrays of the slice context
+    pub(super) slice: SliceBuffers,
+    /// Edge flags and boundary strengths of the deblocking filter
+    pub(super) deblocking: DeblockingContext,
+    /// Pre-SAO copies of the line being filtered and the one above it
+    pub(super) sao_lines: [Vec<u16>; 2],
+    /// Planes of recycled frames
+    planes: Vec<Vec<u16>>,
+}
+
+impl fmt::Debug for DecoderContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecoderContext")
+            .field("planes", &self.planes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl DecoderContext {
+    /// Create a context with no buffers
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode HEVC bitstream to pixels (Annex B or raw format) like
+    /// [`decode_with_options`](super::decode_with_options), with this
+    /// context's buffers
+    pub fn decode(
+        &mut self,
+        data: &[u8],
+        mode: DecodeMode,
+        options: &DecoderOptions,
+    ) -> Result<DecodedFrame> {
+        let nal_units = bitstream::parse_nal_units(data)?;
+        decode_nal_units(self, &nal_units, mode, options)
+    }
+
+    /// Decode HEVC from HEIC container (config + image data) like
+    /// [`decode_with_config_options`](super::decode_with_config_options),
+    /// with this context's buffers
+    pub fn decode_with_config(
+        &mut self,
+        config: &HevcDecoderConfig,
+        image_data: &[u8],
+        mode: DecodeMode,
+        options: &DecoderOptions,
+    ) -> Result<DecodedFrame> {
+        let nal_units = config_nal_units(config, image_data)?;
+        decode_nal_units(self, &nal_units, mode, options)
+    }
+
+    /// Keep the planes of a frame that is no longer needed for later frames
+    ///
+    /// At most a few planes are kept, the largest ones.
+    pub fn recycle(&mut self, frame: DecodedFrame) {
+        let planes = [frame.y_plane, frame.cb_plane, frame.cr_plane];
+        for plane in planes.into_iter().chain(frame.alpha_plane) {
+            if plane.capacity() > 0 {
+                self.planes.push(plane);
+            }
+        }
+        if self.planes.len() > MAX_PLANES {
+            self.planes.sort_unstable_by_key(|plane| core::cmp::Reverse(plane.capacity()));
+            self.planes.truncate(MAX_PLANES);
+        }
+    }
+
+    /// Free all buffers
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Create a zeroed frame, with planes from recycled frames where they fit
+    pub(crate) fn new_frame(
+        &mut self,
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        chroma_format: u8,
+    ) -> DecodedFrame {
+        DecodedFrame::with_planes(width, height, bit_depth, chroma_format, |len| {
+            self.take_plane(len)
+        })
+    }
+
+    /// A zeroed plane of `len` samples: the smallest recycled plane that
+    /// holds them, or a new one
+    fn take_plane(&mut self, len: usize) -> Vec<u16> {
+        let best = (self.planes.iter().enumerate())
+            .filter(|(_, plane)| plane.capacity() >= len)
+            .min_by_key(|(_, plane)| plane.capacity())
+            .map(|(idx, _)| idx);
+        match best {
+            Some(idx) if len > 0 => {
+                let mut plane = self.planes.swap_remove(idx);
+                plane.clear();
+                plane.resize(len, 0);
+                plane
+            }
+            _ => vec![0; len],
+        }
+    }
+}
+
+/// Decoder contexts shared by the threads decoding the tiles of images
+///
+/// Each decode takes an idle context, or a new one when all are in use,
+/// and returns it afterwards. The pool thus grows to one context per
+/// thread decoding at the same time. Without `std`, contexts are not
+/// kept and every decode starts with new buffers.
+#[derive(Debug, Default)]
+pub(crate) struct ContextPool {
+    #[cfg(feature = "std")]
+    idle: std::sync::Mutex<Vec<DecoderContext>>,
+}
+
+impl ContextPool {
+    /// Run `f` with an idle context
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut DecoderContext) -> R) -> R {
+        let mut context = self.take();
+        let result = f(&mut context);
+        self.put(context);
+        result
+    }
+
+    /// Keep the planes of a frame for later decodes
+    pub(crate) fn recycle(&self, frame: DecodedFrame) {
+        self.with(|context| context.recycle(frame));
+    }
+
+    /// Free the buffers of all idle contexts
+    pub(crate) fn clear(&self) {
+        #[cfg(feature = "std")]
+        self.lock().clear();
+    }
+
+    fn take(&self) -> DecoderContext {
+        #[cfg(feature = "std")]
+        if let Some(context) = self.lock().pop() {
+            return context;
+        }
+        DecoderContext::new()
+    }
+
+    fn put(&self, context: DecoderContext) {
+        #[cfg(feature = "std")]
+        self.lock().push(context);
+        #[cfg(not(feature = "std"))]
+        drop(context);
+    }
+
+    /// The idle contexts, also after a thread panicked holding the lock
+    #[cfg(feature = "std")]
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<DecoderContext>> {
+        self.idle.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recycled_planes_are_reused_and_zeroed() {
+        let mut context = DecoderContext::new();
+        let mut frame = context.new_frame(64, 32, 8, 1);
+        frame.y_plane.fill(200);
+        frame.cb_plane.fill(100);
+        let (y_ptr, cb_ptr) = (frame.y_plane.as_ptr(), frame.cb_plane.as_ptr());
+        context.recycle(frame);
+
+        // A smaller frame takes the best-fitting plane of each size
+        let frame = context.new_frame(32, 32, 8, 1);
+        assert_eq!(frame.y_plane.as_ptr(), y_ptr);
+        assert!(frame.y_plane.iter().all(|&v| v == 0));
+        assert_eq!(frame.y_plane.len(), 32 * 32);
+        assert!([frame.cb_plane.as_ptr(), frame.cr_plane.as_ptr()].contains(&cb_ptr));
+        assert_eq!(frame.cb_plane.len(), 16 * 16);
+
+        // Only the largest planes are kept
+        for _ in 0..4 {
+            context.recycle(DecodedFrame::with_params(16, 16, 8, 1));
+        }
+        context.recycle(DecodedFrame::with_params(128, 128, 8, 1));
+        assert_eq!(context.planes.len(), MAX_PLANES);
+        assert_eq!(context.planes[0].capacity(), 128 * 128);
+    }
+}
@@ -6,12 +6,16 @@
 //! - PU: Prediction Unit (for motion/intra prediction)
 //! - TU: Transform Unit (for residual coding)
 
+use alloc::vec;
 use alloc::vec::Vec;
 
-use super::cabac::{CabacDecoder, ContextModel, INIT_VALUES, context};
+use super::cabac::{self, CabacDecoder, ContextModel, context};
+#[cfg(feature = "trace-coefficients")]
 use super::debug;
-use super::deblock::DeblockMetadata;
+use super::deblock::{DeblockMetadata, EdgeMotion};
+use super::inter::{self, MotionField, Mv, PbMotion, RefPic, RefPicLists};
 use super::intra::{self, ReconstructionMap};
+use super::mvpred::{MvPredictor, PbGeom};
 use super::params::{Pps, Sps};
 use super::picture::DecodedFrame;
 use super::residual::{self, ScanOrder};
@@ -89,14 +93,34 @@ pub struct SaoParams {
 #[inline]
 fn chroma_qp_mapping(qp_i: i32) -> i32 {
     // Table 8-10: qPi to QpC mapping
-    // For qPi 0-29, QpC = qPi
+    // For qPi below 30 (negative at high bit depths), QpC = qPi
     // For qPi 30-57, QpC follows the table
-    static CHROMA_QP_TABLE: [i32; 58] = [
-        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
-        25, 26, 27, 28, 29, 29, 30, 31, 32, 33, 33, 34, 34, 35, 35, 36, 36, 37, 37, 38, 39, 40, 41,
-        42, 43, 44, 45, 46, 47, 48, 49, 50, 51,
+    static CHROMA_QP_TABLE: [i32; 28] = [
+        29, 30, 31, 32, 33, 33, 34, 34, 35, 35, 36, 36, 37, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46,
+        47, 48, 49, 50, 51,
     ];
-    CHROMA_QP_TABLE[qp_i.clamp(0, 57) as usize]
+    if qp_i < 30 {
+        qp_i
+    } else {
+        CHROMA_QP_TABLE[(qp_i.min(57) - 30) as usize]
+    }
+}
+
+/// Prediction block rectangles (x, y, w, h) of a coding block, relative to
+/// its top-left corner; unused trailing entries have zero width
+fn partition_rects(part_mode: PartMode, cb_size: u32) -> [(u32, u32, u32, u32); 4] {
+    let (n, h, q) = (cb_size, cb_size / 2, cb_size / 4);
+    let none = (0, 0, 0, 0);
+    match part_mode {
+        PartMode::Part2Nx2N => [(0, 0, n, n), none, none, none],
+        PartMode::Part2NxN => [(0, 0, n, h), (0, h, n, h), none, none],
+        PartMode::PartNx2N => [(0, 0, h, n), (h, 0, h, n), none, none],
+        PartMode::Part2NxnU => [(0, 0, n, q), (0, q, n, n - q), none, none],
+        PartMode::Part2NxnD => [(0, 0, n, n - q), (0, n - q, n, q), none, none],
+        PartMode::PartnLx2N => [(0, 0, q, n), (q, 0, n - q, n), none, none],
+        PartMode::PartnRx2N => [(0, 0, n - q, n), (n - q, 0, q, n), none, none],
+        PartMode::PartNxN => [(0, 0, h, h), (h, 0, h, h), (0, h, h, h), (h, h, h, h)],
+    }
 }
 
 /// Decoding context for a slice
@@ -161,26 +185,76 @@ pub struct SliceContext<'a> {
     ctb_addr_in_ts: u32,
     /// Metadata for deblocking filter
     deblock_metadata: DeblockMetadata,
+    /// Prediction mode of the current coding unit
+    cu_pred_mode: PredMode,
+    /// Partition mode of the current coding unit
+    cu_part_mode: PartMode,
+    /// cu_skip_flag per min_cb_size block (same grid as ct_depth_map)
+    cu_skip_map: Vec<bool>,
+    /// Reference picture lists (empty for I-slices)
+    ref_lists: RefPicLists<'a>,
+    /// Motion field of the current picture
+    motion: MotionField,
+    /// Index of this slice within the motion field
+    slice_idx: u16,
+    /// PicOrderCntVal of the current picture
+    cur_poc: i32,
+    /// Draft reconstruction: DC intra prediction and DC-only residuals
+    draft: bool,
+    /// Skip to the next substream when a CTU fails to decode
+    conceal: bool,
+    /// Per-CTB flags of the picture, set as CTBs are decoded (concealment only)
+    ctb_decoded: Vec<bool>,
+    /// Errors skipped in concealment mode, located at their CTB
+    skipped: Vec<HevcError>,
+}
+
+/// Picture-sized arrays of a [`SliceContext`], kept to be reused by later slices
+#[derive(Default)]
+pub struct SliceBuffers {
+    ct_depth_map: Vec<u8>,
+    cu_skip_map: Vec<bool>,
+    intra_pred_mode_y: Vec<u8>,
+    intra_pred_mode_c: Vec<u8>,
+    sao_params: Vec<SaoParams>,
+    qp_y_map: Vec<i32>,
+    reco_map: ReconstructionMap,
+    deblock_metadata: DeblockMetadata,
+}
+
+/// Clear `buf` and fill it with `len` copies of `value`, keeping its allocation
+fn refill<T: Clone>(mut buf: Vec<T>, len: u32, value: T) -> Vec<T> {
+    buf.clear();
+    buf.resize(len as usize, value);
+    buf
+}
+
+/// Context models initialized for the start of a slice (9.3.2.2)
+fn initial_contexts(header: &SliceHeader) -> [ContextModel; context::NUM_CONTEXTS] {
+    let mut ctx = [ContextModel::new(154); context::NUM_CONTEXTS];
+    for (i, init_val) in cabac::init_values(header.init_type()).iter().enumerate() {
+        ctx[i].init(*init_val, header.slice_qp_y);
+    }
+    ctx
 }
 
 impl<'a> SliceContext<'a> {
     /// Create a new slice context
+    ///
+    /// The picture-sized arrays are built in `buffers`, reusing their
+    /// allocations; get them back with [`Self::into_buffers`].
     pub fn new(
         sps: &'a Sps,
         pps: &'a Pps,
         header: &'a SliceHeader,
         slice_data: &'a [u8],
+        buffers: SliceBuffers,
     ) -> Result<Self> {
         let cabac = CabacDecoder::new(slice_data)?;
 
-        // Initialize context models
-        let mut ctx = [ContextModel::new(154); context::NUM_CONTEXTS];
+        let ctx = initial_contexts(header);
         let slice_qp = header.slice_qp_y;
 
-        for (i, init_val) in INIT_VALUES.iter().enumerate() {
-            ctx[i].init(*init_val, slice_qp);
-        }
-
         // Calculate chroma QP values (H.265 Table 8-10 and section 8.6.1)
         // qPi_Cb = qP_Y + pps_cb_qp_offset + slice_cb_qp_offset
         // qPi_Cr = qP_Y + pps_cr_qp_offset + slice_cr_qp_offset
@@ -188,33 +262,41 @@ impl<'a> SliceContext<'a> {
         let qp_i_cr = slice_qp + pps.pps_cr_qp_offset as i32 + header.slice_cr_qp_offset as i32;
 
         // Apply chroma QP mapping table (H.265 Table 8-10)
-        let qp_cb = chroma_qp_mapping(qp_i_cb.clamp(0, 57));
-        let qp_cr = chroma_qp_mapping(qp_i_cr.clamp(0, 57));
+        let qp_bd_offset_c = 6 * (sps.bit_depth_c() as i32 - 8);
+        let qp_cb = chroma_qp_mapping(qp_i_cb.clamp(-qp_bd_offset_c, 57));
+        let qp_cr = chroma_qp_mapping(qp_i_cr.clamp(-qp_bd_offset_c, 57));
 
         // Initialize ct_depth_map for split_cu_flag context derivation
         // Map is in units of min_cb_size (typically 8x8)
         let min_cb_size = 1u32 << sps.log2_min_cb_size();
         let ct_depth_map_stride = sps.pic_width_in_luma_samples.div_ceil(min_cb_size);
         let ct_depth_map_height = sps.pic_height_in_luma_samples.div_ceil(min_cb_size);
-        let ct_depth_map = vec![0xFF; (ct_depth_map_stride * ct_depth_map_height) as usize];
+        let ct_depth_map_size = ct_depth_map_stride * ct_depth_map_height;
+        let ct_depth_map = refill(buffers.ct_depth_map, ct_depth_map_size, 0xFF);
+        let cu_skip_map = refill(buffers.cu_skip_map, ct_depth_map_size, false);
 
         // Initialize per-4x4-block intra prediction mode map
         let intra_pred_stride = sps.pic_width_in_luma_samples.div_ceil(4);
         let intra_pred_height = sps.pic_height_in_luma_samples.div_ceil(4);
-        let intra_pred_mode_y = vec![0u8; (intra_pred_stride * intra_pred_height) as usize];
-        let intra_pred_mode_c = vec![0u8; (intra_pred_stride * intra_pred_height) as usize];
+        let intra_pred_mode_y = refill(buffers.intra_pred_mode_y, intra_pred_stride * intra_pred_height, 0);
+        let intra_pred_mode_c = refill(buffers.intra_pred_mode_c, intra_pred_stride * intra_pred_height, 0);
 
         // Initialize SAO parameters storage
         let ctb_size = sps.ctb_size();
         let ctbs_per_row = sps.pic_width_in_luma_samples.div_ceil(ctb_size);
         let ctbs_per_col = sps.pic_height_in_luma_samples.div_ceil(ctb_size);
-        let sao_params = vec![SaoParams::default(); (ctbs_per_row * ctbs_per_col) as usize];
+        let sao_params = refill(buffers.sao_params, ctbs_per_row * ctbs_per_col, SaoParams::default());
 
         // Initialize QP map (at min_tb_size granularity)
         let min_tb_size = 1u32 << sps.log2_min_tb_size();
         let qp_y_map_stride = sps.pic_width_in_luma_samples.div_ceil(min_tb_size);
         let qp_y_map_height = sps.pic_height_in_luma_samples.div_ceil(min_tb_size);
-        let qp_y_map = vec![slice_qp; (qp_y_map_stride * qp_y_map_height) as usize];
+        let qp_y_map = refill(buffers.qp_y_map, qp_y_map_stride * qp_y_map_height, slice_qp);
+
+        let mut reco_map = buffers.reco_map;
+        reco_map.reset(sps.pic_width_in_luma_samples, sps.pic_height_in_luma_samples);
+        let mut deblock_metadata = buffers.deblock_metadata;
+        deblock_metadata.reset(sps.pic_width_in_luma_samples, sps.pic_height_in_luma_samples);
 
         Ok(Self {
             sps,
@@ -237,10 +319,7 @@ impl<'a> SliceContext<'a> {
             intra_pred_stride,
             slice_data,
             wpp_saved_ctx: Vec::new(),
-            reco_map: ReconstructionMap::new(
-                sps.pic_width_in_luma_samples,
-                sps.pic_height_in_luma_samples,
-            ),
+            reco_map,
             sao_params,
             ctbs_per_row,
             qp_y_map,
@@ -249,13 +328,66 @@ impl<'a> SliceContext<'a> {
             current_qg_x: -1,
             current_qg_y: -1,
             ctb_addr_in_ts: 0,
-            deblock_metadata: DeblockMetadata::new(
-                sps.pic_width_in_luma_samples,
-                sps.pic_height_in_luma_samples,
-            ),
+            deblock_metadata,
+            cu_pred_mode: PredMode::Intra,
+            cu_part_mode: PartMode::Part2Nx2N,
+            cu_skip_map,
+            ref_lists: [Vec::new(), Vec::new()],
+            motion: MotionField::default(),
+            slice_idx: 0,
+            cur_poc: 0,
+            draft: false,
+            conceal: false,
+            ctb_decoded: Vec::new(),
+            skipped: Vec::new(),
         })
     }
 
+    /// Provide reference pictures and the picture's motion field for a P/B slice
+    ///
+    /// The motion field is shared by all slices of a picture; take it back
+    /// with [`Self::take_motion_field`] once the slice is decoded.
+    pub fn set_inter_prediction(&mut self, ref_lists: RefPicLists<'a>, motion: MotionField, poc: i32) {
+        let refs = [0, 1].map(|l| ref_lists[l].iter().map(RefPic::info).collect());
+        self.motion = motion;
+        self.slice_idx = self.motion.begin_slice(refs);
+        self.ref_lists = ref_lists;
+        self.cur_poc = poc;
+    }
+
+    /// Reconstruct approximately for previews
+    ///
+    /// Syntax is parsed as usual, but intra blocks use DC prediction and
+    /// residuals keep only their DC coefficient.
+    pub fn set_draft(&mut self, draft: bool) {
+        self.draft = draft;
+    }
+
+    /// Conceal CTUs that fail to decode instead of failing the slice
+    ///
+    /// Decoding resumes at the next WPP substream, or the slice ends early.
+    /// `ctb_decoded` flags the CTBs of the picture decoded so far, in raster
+    /// order, and is updated with those of this slice; take it back with
+    /// [`Self::take_concealment`] together with the skipped errors.
+    pub fn set_concealment(&mut self, ctb_decoded: Vec<bool>) {
+        self.conceal = true;
+        self.ctb_decoded = ctb_decoded;
+    }
+
+    /// Return the CTB flags passed to [`Self::set_concealment`] and the
+    /// errors skipped, each located at its CTB
+    pub fn take_concealment(&mut self) -> (Vec<bool>, Vec<HevcError>) {
+        (
+            core::mem::take(&mut self.ctb_decoded),
+            core::mem::take(&mut self.skipped),
+        )
+    }
+
+    /// Return the motion field passed to [`Self::set_inter_prediction`]
+    pub fn take_motion_field(&mut self) -> MotionField {
+        core::mem::take(&mut self.motion)
+    }
+
     /// Store intra prediction mode for luma at a given position covering size×size pixels
     fn set_intra_pred_mode(&mut self, x: u32, y: u32, size: u32, mode: IntraPredMode) {
         let blocks = (size / 4).max(1);
@@ -403,8 +535,12 @@ impl<'a> SliceContext<'a> {
     }
 
     /// Decode all CTUs in the slice
-    pub fn decode_slice(&mut self, frame: &mut DecodedFrame) -> Result<(DeblockMetadata, Vec<SaoParams>)> {
+    ///
+    /// The metadata for the in-loop filters is then available from
+    /// [`Self::deblock_metadata`] and [`Self::sao_params`].
+    pub fn decode_slice(&mut self, frame: &mut DecodedFrame) -> Result<()> {
         // Initialize CABAC tracker for debugging
+        #[cfg(feature = "trace-coefficients")]
         debug::init_tracker();
 
         let ctb_size = self.sps.ctb_size();
@@ -431,7 +567,38 @@ impl<'a> SliceContext<'a> {
             // Set CTB address for same-CTB neighbor check in QP prediction
             self.ctb_addr_in_ts = ctu_count;
 
-            self.decode_ctu(x_ctb, y_ctb, frame)?;
+            // Decode the CTU and end_of_slice_segment_flag (end_of_sub_stream_one_bit for WPP)
+            let decoded = self
+                .decode_ctu(x_ctb, y_ctb, frame)
+                .and_then(|()| self.cabac.decode_terminate());
+            let end_of_slice = match decoded {
+                Ok(end_of_slice) => end_of_slice,
+                Err(e) => {
+                    let e = e.at_ctb(self.ctb_x, self.ctb_y);
+                    if !self.conceal {
+                        return Err(e);
+                    }
+                    self.skipped.push(e);
+                    // The rest of the substream is lost; resume at the next one
+                    if !self.resync_wpp(wpp_entry_idx) {
+                        break;
+                    }
+                    wpp_entry_idx += 1;
+                    ctu_count += 1;
+                    self.ctb_x = 0;
+                    self.ctb_y += 1;
+                    if self.ctb_y >= pic_height_in_ctbs {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            if let Some(flag) = self
+                .ctb_decoded
+                .get_mut((self.ctb_y * pic_width_in_ctbs + self.ctb_x) as usize)
+            {
+                *flag = true;
+            }
             ctu_count += 1;
 
             // WPP: save context models after the 2nd CTU in each row (ctb_x == 1)
@@ -444,9 +611,6 @@ impl<'a> SliceContext<'a> {
                 self.wpp_saved_ctx[row] = self.ctx;
             }
 
-            // Check for end of slice segment (end_of_sub_stream_one_bit for WPP)
-            let end_of_slice = self.cabac.decode_terminate()?;
-
             // Move to next CTB
             self.ctb_x += 1;
             let row_complete = self.ctb_x >= pic_width_in_ctbs;
@@ -487,15 +651,62 @@ impl<'a> SliceContext<'a> {
         }
 
         // Print CABAC tracker summary
+        #[cfg(feature = "trace-coefficients")]
         debug::print_tracker_summary();
 
-        // Return metadata for deblocking filter and SAO params
-        let deblock_meta = core::mem::replace(
-            &mut self.deblock_metadata,
-            DeblockMetadata::new(self.sps.pic_width_in_luma_samples, self.sps.pic_height_in_luma_samples),
-        );
-        let sao = core::mem::take(&mut self.sao_params);
-        Ok((deblock_meta, sao))
+        Ok(())
+    }
+
+    /// Metadata for the deblocking filter of the decoded slice
+    pub fn deblock_metadata(&self) -> &DeblockMetadata {
+        &self.deblock_metadata
+    }
+
+    /// SAO parameters per CTB of the picture, set for those of the decoded slice
+    pub fn sao_params(&self) -> &[SaoParams] {
+        &self.sao_params
+    }
+
+    /// Return the picture-sized arrays for reuse by a later slice
+    pub fn into_buffers(self) -> SliceBuffers {
+        SliceBuffers {
+            ct_depth_map: self.ct_depth_map,
+            cu_skip_map: self.cu_skip_map,
+            intra_pred_mode_y: self.intra_pred_mode_y,
+            intra_pred_mode_c: self.intra_pred_mode_c,
+            sao_params: self.sao_params,
+            qp_y_map: self.qp_y_map,
+            reco_map: self.reco_map,
+            deblock_metadata: self.deblock_metadata,
+        }
+    }
+
+    /// Restart CABAC at the WPP substream following the current CTB row
+    ///
+    /// Used after an error in concealment mode. The context models are those
+    /// saved after the row's second CTB, or initial ones if it was lost.
+    /// Returns false if there is no further substream.
+    fn resync_wpp(&mut self, entry_idx: usize) -> bool {
+        if !self.pps.entropy_coding_sync_enabled_flag {
+            return false;
+        }
+        let Some(&offset) = self.header.entry_point_offsets.get(entry_idx) else {
+            return false;
+        };
+        let Some(Ok(cabac)) = self
+            .slice_data
+            .get(offset as usize..)
+            .map(CabacDecoder::new)
+        else {
+            return false;
+        };
+        self.cabac = cabac;
+        let row = self.ctb_y as usize;
+        self.ctx = match self.wpp_saved_ctx.get(row) {
+            Some(saved) if self.ctb_x > 1 => *saved,
+            _ => initial_contexts(self.header),
+        };
+        true
     }
 
     /// Decode a single CTU (Coding Tree Unit)
@@ -1067,22 +1278,10 @@ impl<'a> SliceContext<'a> {
         frame: &mut DecodedFrame,
     ) -> Result<()> {
         let cb_size = 1u32 << log2_cb_size;
-        let _ = cb_size; // Used in PartNxN
 
         // Set ct_depth for this CU (used by split_cu_flag context derivation)
         self.set_ct_depth(x0, y0, log2_cb_size, ct_depth);
 
-        // For I-slices, prediction mode is always INTRA
-        let pred_mode = PredMode::Intra;
-
-        // Track prediction mode for deblocking filter
-        // Mark all 4x4 blocks in this CU as intra
-        for y in (0..cb_size).step_by(4) {
-            for x in (0..cb_size).step_by(4) {
-                self.deblock_metadata.set_pred_mode(x0 + x, y0 + y, true);
-            }
-        }
-
         // Decode transquant_bypass_flag if enabled
         self.cu_transquant_bypass_flag = if self.pps.transquant_bypass_enabled_flag {
             let ctx_idx = context::CU_TRANSQUANT_BYPASS_FLAG;
@@ -1093,6 +1292,83 @@ impl<'a> SliceContext<'a> {
             false
         };
 
+        // cu_skip_flag and pred_mode_flag are only present in P/B slices
+        let slice_is_intra = self.header.slice_type.is_intra();
+        let cu_skip_flag = if !slice_is_intra {
+            self.decode_cu_skip_flag(x0, y0)?
+        } else {
+            false
+        };
+        self.set_cu_skip_flag(x0, y0, log2_cb_size, cu_skip_flag);
+
+        let pred_mode = if cu_skip_flag {
+            PredMode::Skip
+        } else if slice_is_intra {
+            PredMode::Intra
+        } else {
+            let ctx_idx = context::PRED_MODE_FLAG;
+            #[cfg(feature = "trace-coefficients")]
+            { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+            if self.cabac.decode_bin(&mut self.ctx[ctx_idx])? != 0 {
+                PredMode::Intra
+            } else {
+                PredMode::Inter
+            }
+        };
+        self.cu_pred_mode = pred_mode;
+
+        // Track prediction mode for deblocking filter
+        for y in (0..cb_size).step_by(4) {
+            for x in (0..cb_size).step_by(4) {
+                self.deblock_metadata.set_pred_mode(x0 + x, y0 + y, pred_mode == PredMode::Intra);
+            }
+        }
+
+        if pred_mode != PredMode::Intra {
+            self.decode_inter_coding_unit(x0, y0, log2_cb_size, ct_depth, cu_skip_flag, frame)?;
+        } else {
+            self.decode_intra_coding_unit(x0, y0, log2_cb_size, frame)?;
+        }
+
+        // Per H.265 8.6.1 / libde265 decode_quantization_parameters:
+        // Always derive and store QPY for every CU, not just those with coded cu_qp_delta.
+        // This ensures the QP map is correct for future neighbor lookups.
+        // For CUs without coded delta, QPY = qPY_PRED + 0 = qPY_PRED.
+        if self.pps.cu_qp_delta_enabled_flag {
+            let qp_y_pred = self.derive_qp_y_pred(x0, y0);
+            let qp_bd_offset_y = 6 * (self.sps.bit_depth_y() as i32 - 8);
+            self.qp_y = ((qp_y_pred + self.cu_qp_delta + 52 + 2 * qp_bd_offset_y)
+                % (52 + qp_bd_offset_y))
+                - qp_bd_offset_y;
+
+            // Update chroma QP
+            let qp_i_cb = self.qp_y + self.pps.pps_cb_qp_offset as i32
+                + self.header.slice_cb_qp_offset as i32;
+            let qp_i_cr = self.qp_y + self.pps.pps_cr_qp_offset as i32
+                + self.header.slice_cr_qp_offset as i32;
+            let qp_bd_offset_c = 6 * (self.sps.bit_depth_c() as i32 - 8);
+            self.qp_cb = chroma_qp_mapping(qp_i_cb.clamp(-qp_bd_offset_c, 57));
+            self.qp_cr = chroma_qp_mapping(qp_i_cr.clamp(-qp_bd_offset_c, 57));
+
+            // Store QPY in the map for the CU's area
+            let cb_size_cu = 1u32 << log2_cb_size;
+            self.set_qpy(x0, y0, cb_size_cu, self.qp_y);
+        }
+
+        Ok(())
+    }
+
+    /// Decode the prediction and residual syntax of an intra coding unit
+    fn decode_intra_coding_unit(
+        &mut self,
+        x0: u32,
+        y0: u32,
+        log2_cb_size: u8,
+        frame: &mut DecodedFrame,
+    ) -> Result<()> {
+        let cb_size = 1u32 << log2_cb_size;
+        let pred_mode = PredMode::Intra;
+
         // Decode partition mode
         let part_mode = if log2_cb_size == self.sps.log2_min_cb_size() {
             // At minimum size, can be 2Nx2N or NxN
@@ -1102,6 +1378,7 @@ impl<'a> SliceContext<'a> {
             // Larger sizes are always 2Nx2N for intra
             PartMode::Part2Nx2N
         };
+        self.cu_part_mode = part_mode;
 
         // Decode prediction info and get intra mode for scan order
         let intra_mode = match part_mode {
@@ -1185,33 +1462,303 @@ impl<'a> SliceContext<'a> {
             )?;
         }
 
-        // Per H.265 8.6.1 / libde265 decode_quantization_parameters:
-        // Always derive and store QPY for every CU, not just those with coded cu_qp_delta.
-        // This ensures the QP map is correct for future neighbor lookups.
-        // For CUs without coded delta, QPY = qPY_PRED + 0 = qPY_PRED.
-        if self.pps.cu_qp_delta_enabled_flag {
-            let qp_y_pred = self.derive_qp_y_pred(x0, y0);
-            let qp_bd_offset_y = 6 * (self.sps.bit_depth_y() as i32 - 8);
-            self.qp_y = ((qp_y_pred + self.cu_qp_delta + 52 + 2 * qp_bd_offset_y)
-                % (52 + qp_bd_offset_y))
-                - qp_bd_offset_y;
+        Ok(())
+    }
 
-            // Update chroma QP
-            let qp_i_cb = self.qp_y + self.pps.pps_cb_qp_offset as i32
-                + self.header.slice_cb_qp_offset as i32;
-            let qp_i_cr = self.qp_y + self.pps.pps_cr_qp_offset as i32
-                + self.header.slice_cr_qp_offset as i32;
-            self.qp_cb = chroma_qp_mapping(qp_i_cb.clamp(0, 57));
-            self.qp_cr = chroma_qp_mapping(qp_i_cr.clamp(0, 57));
+    /// Decode an inter coding unit: prediction units, motion compensation
+    /// and the residual quadtree (H.265 7.3.8.5, 7.3.8.6)
+    fn decode_inter_coding_unit(
+        &mut self,
+        x0: u32,
+        y0: u32,
+        log2_cb_size: u8,
+        ct_depth: u8,
+        cu_skip_flag: bool,
+        frame: &mut DecodedFrame,
+    ) -> Result<()> {
+        let cb_size = 1u32 << log2_cb_size;
 
-            // Store QPY in the map for the CU's area
-            let cb_size_cu = 1u32 << log2_cb_size;
-            self.set_qpy(x0, y0, cb_size_cu, self.qp_y);
+        // Inter CUs count as INTRA_DC for neighbouring MPM derivation
+        self.set_intra_pred_mode(x0, y0, cb_size, IntraPredMode::Dc);
+
+        let part_mode = if cu_skip_flag {
+            PartMode::Part2Nx2N
+        } else {
+            self.decode_part_mode(PredMode::Inter, log2_cb_size)?
+        };
+        self.cu_part_mode = part_mode;
+
+        let mut merge_2nx2n = cu_skip_flag;
+        for (part_idx, (px, py, w, h)) in partition_rects(part_mode, cb_size).into_iter().enumerate() {
+            if w == 0 {
+                break;
+            }
+            let pb = PbGeom {
+                x_cb: x0,
+                y_cb: y0,
+                n_cb_s: cb_size,
+                x_pb: x0 + px,
+                y_pb: y0 + py,
+                w,
+                h,
+                part_idx: part_idx as u32,
+                part_mode,
+            };
+            let (motion, merge_flag) = self.decode_prediction_unit(pb, cu_skip_flag, ct_depth)?;
+            if part_mode == PartMode::Part2Nx2N {
+                merge_2nx2n = merge_flag;
+            }
+
+            // Store motion before the next PU so it can serve as a candidate
+            self.motion.set(pb.x_pb, pb.y_pb, w, h, self.slice_idx, motion);
+            let edge_motion = EdgeMotion {
+                refs: [0, 1].map(|l| {
+                    motion.pred_flag[l]
+                        .then(|| self.ref_lists[l].get(motion.ref_idx[l] as usize).map(|r| r.poc))
+                        .flatten()
+                }),
+                mv: motion.mv,
+            };
+            self.deblock_metadata.set_motion(pb.x_pb, pb.y_pb, w, h, edge_motion);
+
+            inter::predict_inter(
+                frame,
+                &self.ref_lists,
+                &motion,
+                pb.x_pb,
+                pb.y_pb,
+                w,
+                h,
+                self.header.pred_weight_table.as_ref(),
+            );
+        }
+
+        let rqt_root_cbf = if cu_skip_flag {
+            false
+        } else if part_mode == PartMode::Part2Nx2N && merge_2nx2n {
+            true
+        } else {
+            let ctx_idx = context::RQT_ROOT_CBF;
+            #[cfg(feature = "trace-coefficients")]
+            { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+            self.cabac.decode_bin(&mut self.ctx[ctx_idx])? != 0
+        };
+
+        if rqt_root_cbf && !self.cu_transquant_bypass_flag {
+            self.decode_transform_tree(x0, y0, log2_cb_size, 0, IntraPredMode::Dc, false, frame)?;
+        } else {
+            // No residual: the prediction is the reconstruction
+            self.reco_map.mark_reconstructed(x0, y0, cb_size, 0);
+            if frame.chroma_format != 0 {
+                self.reco_map.mark_reconstructed(x0 / 2, y0 / 2, cb_size / 2, 1);
+                self.reco_map.mark_reconstructed(x0 / 2, y0 / 2, cb_size / 2, 2);
+            }
         }
 
         Ok(())
     }
 
+    /// Decode prediction_unit syntax and derive its motion (H.265 7.3.8.6)
+    ///
+    /// Returns the motion data and merge_flag.
+    fn decode_prediction_unit(&mut self, pb: PbGeom, cu_skip_flag: bool, ct_depth: u8) -> Result<(PbMotion, bool)> {
+        let merge_flag = if cu_skip_flag {
+            true
+        } else {
+            let ctx_idx = context::MERGE_FLAG;
+            #[cfg(feature = "trace-coefficients")]
+            { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+            self.cabac.decode_bin(&mut self.ctx[ctx_idx])? != 0
+        };
+
+        if merge_flag {
+            let merge_idx = self.decode_merge_idx()?;
+            return Ok((self.mv_predictor().merge_motion(pb, merge_idx), true));
+        }
+
+        // inter_pred_idc: 0 = PRED_L0, 1 = PRED_L1, 2 = PRED_BI
+        let inter_pred_idc = if self.header.slice_type.is_b() {
+            self.decode_inter_pred_idc(pb.w, pb.h, ct_depth)?
+        } else {
+            0
+        };
+
+        let mut ref_idx = [-1i8; 2];
+        let mut mvd = [Mv::default(); 2];
+        let mut mvp_flag = [false; 2];
+        for list in 0..2 {
+            let uses_list = match list {
+                0 => inter_pred_idc != 1,
+                _ => inter_pred_idc != 0,
+            };
+            if !uses_list {
+                continue;
+            }
+            let num_ref_idx = self.header.num_ref_idx_active(list);
+            ref_idx[list] = if num_ref_idx > 1 {
+                self.decode_ref_idx(num_ref_idx as u32 - 1)? as i8
+            } else {
+                0
+            };
+            mvd[list] = if list == 1 && self.header.mvd_l1_zero_flag && inter_pred_idc == 2 {
+                Mv::default()
+            } else {
+                self.decode_mvd()?
+            };
+            let ctx_idx = context::MVP_LX_FLAG;
+            #[cfg(feature = "trace-coefficients")]
+            { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+            mvp_flag[list] = self.cabac.decode_bin(&mut self.ctx[ctx_idx])? != 0;
+        }
+
+        // Luma motion vectors (8.5.3.2.1): mvLX = mvpLX + mvdLX, wrapped to 16 bits
+        let mut motion = PbMotion::default();
+        let predictor = self.mv_predictor();
+        for list in 0..2 {
+            if ref_idx[list] < 0 {
+                continue;
+            }
+            let mvp = predictor.amvp(pb, list, ref_idx[list], mvp_flag[list]);
+            let wrap = |v: i32| v as i16 as i32;
+            motion.set(
+                list,
+                ref_idx[list],
+                Mv::new(wrap(mvp.x + mvd[list].x), wrap(mvp.y + mvd[list].y)),
+            );
+        }
+        Ok((motion, false))
+    }
+
+    /// Motion vector predictor over the current slice state
+    fn mv_predictor(&self) -> MvPredictor<'_, 'a> {
+        MvPredictor {
+            motion: &self.motion,
+            slice_idx: self.slice_idx,
+            refs: &self.ref_lists,
+            cur_poc: self.cur_poc,
+            header: self.header,
+            pic_width: self.sps.pic_width_in_luma_samples,
+            pic_height: self.sps.pic_height_in_luma_samples,
+            log2_ctb_size: self.sps.log2_ctb_size(),
+            log2_par_mrg_level: self.pps.log2_parallel_merge_level_minus2 + 2,
+        }
+    }
+
+    /// Decode cu_skip_flag (context from left/above skip flags, 9.3.4.2.2)
+    fn decode_cu_skip_flag(&mut self, x0: u32, y0: u32) -> Result<bool> {
+        let cond_l = x0 > 0 && self.get_cu_skip_flag(x0 - 1, y0);
+        let cond_a = y0 > 0 && self.get_cu_skip_flag(x0, y0 - 1);
+        let ctx_idx = context::CU_SKIP_FLAG + cond_l as usize + cond_a as usize;
+        #[cfg(feature = "trace-coefficients")]
+        { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+        Ok(self.cabac.decode_bin(&mut self.ctx[ctx_idx])? != 0)
+    }
+
+    /// Get cu_skip_flag at a pixel position (false outside the slice's decoded area)
+    fn get_cu_skip_flag(&self, x: u32, y: u32) -> bool {
+        let log2_min_cb = self.sps.log2_min_cb_size();
+        let (map_x, map_y) = (x >> log2_min_cb, y >> log2_min_cb);
+        map_x < self.ct_depth_map_stride
+            && self
+                .cu_skip_map
+                .get((map_y * self.ct_depth_map_stride + map_x) as usize)
+                .copied()
+                .unwrap_or(false)
+    }
+
+    /// Set cu_skip_flag for a CU region
+    fn set_cu_skip_flag(&mut self, x0: u32, y0: u32, log2_cb_size: u8, skip: bool) {
+        let log2_min_cb = self.sps.log2_min_cb_size();
+        let num_blocks = 1u32 << (log2_cb_size - log2_min_cb);
+        let (start_x, start_y) = (x0 >> log2_min_cb, y0 >> log2_min_cb);
+        for map_y in start_y..start_y + num_blocks {
+            for map_x in start_x..(start_x + num_blocks).min(self.ct_depth_map_stride) {
+                if let Some(flag) = self.cu_skip_map.get_mut((map_y * self.ct_depth_map_stride + map_x) as usize) {
+                    *flag = skip;
+                }
+            }
+        }
+    }
+
+    /// Decode merge_idx (truncated rice, first bin context coded)
+    fn decode_merge_idx(&mut self) -> Result<u8> {
+        let c_max = self.header.max_num_merge_cand.saturating_sub(1);
+        if c_max == 0 {
+            return Ok(0);
+        }
+        let ctx_idx = context::MERGE_IDX;
+        #[cfg(feature = "trace-coefficients")]
+        { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+        if self.cabac.decode_bin(&mut self.ctx[ctx_idx])? == 0 {
+            return Ok(0);
+        }
+        let mut idx = 1;
+        while idx < c_max && self.cabac.decode_bypass()? != 0 {
+            idx += 1;
+        }
+        Ok(idx)
+    }
+
+    /// Decode inter_pred_idc (9.3.4.2.2: first bin uses CtDepth, except for 8x4/4x8)
+    fn decode_inter_pred_idc(&mut self, n_pb_w: u32, n_pb_h: u32, ct_depth: u8) -> Result<u8> {
+        if n_pb_w + n_pb_h != 12 {
+            let ctx_idx = context::INTER_PRED_IDC + ct_depth as usize;
+            #[cfg(feature = "trace-coefficients")]
+            { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+            if self.cabac.decode_bin(&mut self.ctx[ctx_idx])? != 0 {
+                return Ok(2);
+            }
+        }
+        let ctx_idx = context::INTER_PRED_IDC + 4;
+        #[cfg(feature = "trace-coefficients")]
+        { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+        self.cabac.decode_bin(&mut self.ctx[ctx_idx])
+    }
+
+    /// Decode ref_idx_lX (truncated rice, first two bins context coded)
+    fn decode_ref_idx(&mut self, c_max: u32) -> Result<u32> {
+        let mut idx = 0;
+        while idx < c_max {
+            let bin = if idx < 2 {
+                let ctx_idx = context::REF_IDX + idx as usize;
+                #[cfg(feature = "trace-coefficients")]
+                { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+                self.cabac.decode_bin(&mut self.ctx[ctx_idx])?
+            } else {
+                self.cabac.decode_bypass()?
+            };
+            if bin == 0 {
+                break;
+            }
+            idx += 1;
+        }
+        Ok(idx)
+    }
+
+    /// Decode mvd_coding (H.265 7.3.8.9)
+    fn decode_mvd(&mut self) -> Result<Mv> {
+        let ctx_g0 = context::ABS_MVD_GREATER0_FLAG;
+        let ctx_g1 = context::ABS_MVD_GREATER1_FLAG;
+        let gr0 = [
+            self.cabac.decode_bin(&mut self.ctx[ctx_g0])? != 0,
+            self.cabac.decode_bin(&mut self.ctx[ctx_g0])? != 0,
+        ];
+        let mut gr1 = [false; 2];
+        for c in 0..2 {
+            if gr0[c] {
+                gr1[c] = self.cabac.decode_bin(&mut self.ctx[ctx_g1])? != 0;
+            }
+        }
+        let mut mvd = [0i32; 2];
+        for c in 0..2 {
+            if gr0[c] {
+                let abs = if gr1[c] { self.cabac.decode_eg(1)? as i32 + 2 } else { 1 };
+                mvd[c] = if self.cabac.decode_bypass()? != 0 { -abs } else { abs };
+            }
+        }
+        Ok(Mv::new(mvd[0], mvd[1]))
+    }
+
     /// Decode transform tree recursively
     fn decode_transform_tree(
         &mut self,
@@ -1253,8 +1800,18 @@ impl<'a> SliceContext<'a> {
         frame: &mut DecodedFrame,
     ) -> Result<()> {
         // H.265 spec (7.4.9.8): MaxTrafoDepth = max_transform_hierarchy_depth_intra + IntraSplitFlag
-        let max_trafo_depth = self.sps.max_transform_hierarchy_depth_intra
-            + if intra_split_flag { 1 } else { 0 };
+        // for intra CUs, max_transform_hierarchy_depth_inter for inter CUs
+        let is_intra = self.cu_pred_mode == PredMode::Intra;
+        let max_trafo_depth = if is_intra {
+            self.sps.max_transform_hierarchy_depth_intra + if intra_split_flag { 1 } else { 0 }
+        } else {
+            self.sps.max_transform_hierarchy_depth_inter
+        };
+        // interSplitFlag: non-2Nx2N inter CUs split once when no inter depth is allowed
+        let inter_split_flag = !is_intra
+            && self.sps.max_transform_hierarchy_depth_inter == 0
+            && self.cu_part_mode != PartMode::Part2Nx2N
+            && trafo_depth == 0;
         let log2_min_trafo_size = self.sps.log2_min_tb_size();
         let log2_max_trafo_size = self.sps.log2_max_tb_size();
 
@@ -1286,7 +1843,7 @@ impl<'a> SliceContext<'a> {
         } else if log2_size > log2_max_trafo_size {
             true // Must split if larger than max
         } else {
-            false
+            inter_split_flag
         };
 
         // Track split transform flag for deblocking filter
@@ -1383,18 +1940,20 @@ impl<'a> SliceContext<'a> {
             // (because 4x4 children can't have chroma TUs)
             if log2_size == 3 {
                 // Apply chroma prediction for the deferred chroma TU before adding residuals
+                // (inter CUs already hold their motion-compensated prediction)
                 let chroma_mode = self.get_intra_pred_mode_c(x0, y0);
-                intra::predict_intra(frame, x0 / 2, y0 / 2, 2, chroma_mode, 1, &self.reco_map, self.sps.strong_intra_smoothing_enabled_flag);
-                intra::predict_intra(frame, x0 / 2, y0 / 2, 2, chroma_mode, 2, &self.reco_map, self.sps.strong_intra_smoothing_enabled_flag);
+                if is_intra {
+                    self.predict_intra(frame, x0 / 2, y0 / 2, 2, chroma_mode, 1);
+                    self.predict_intra(frame, x0 / 2, y0 / 2, 2, chroma_mode, 2);
+                }
 
                 // Use stored chroma intra mode for chroma scan order
-                let scan_order_cb = residual::get_scan_order(2, chroma_mode.as_u8(), 1);
-
                 if cbf_cb {
+                    let scan_order_cb = self.residual_scan_order(2, chroma_mode, 1);
                     self.decode_and_apply_residual(x0 / 2, y0 / 2, 2, 1, scan_order_cb, frame)?;
                 }
                 if cbf_cr {
-                    let scan_order_cr = residual::get_scan_order(2, chroma_mode.as_u8(), 2);
+                    let scan_order_cr = self.residual_scan_order(2, chroma_mode, 2);
                     self.decode_and_apply_residual(x0 / 2, y0 / 2, 2, 2, scan_order_cr, frame)?;
                 }
                 // Mark deferred chroma blocks as reconstructed
@@ -1431,23 +1990,24 @@ impl<'a> SliceContext<'a> {
         cbf_cr: bool,
         frame: &mut DecodedFrame,
     ) -> Result<()> {
-        let actual_luma_mode = self.get_intra_pred_mode(x0, y0);
-        intra::predict_intra(frame, x0, y0, log2_size, actual_luma_mode, 0, &self.reco_map, self.sps.strong_intra_smoothing_enabled_flag);
+        let is_intra = self.cu_pred_mode == PredMode::Intra;
+        if is_intra {
+            let actual_luma_mode = self.get_intra_pred_mode(x0, y0);
+            self.predict_intra(frame, x0, y0, log2_size, actual_luma_mode, 0);
+        }
 
         // Apply chroma prediction if this TU handles chroma (log2_size >= 3)
-        if log2_size >= 3 {
+        if is_intra && log2_size >= 3 {
             let chroma_mode = self.get_intra_pred_mode_c(x0, y0);
             let chroma_x = x0 / 2;
             let chroma_y = y0 / 2;
             let chroma_log2_size = log2_size - 1;
-            intra::predict_intra(frame, chroma_x, chroma_y, chroma_log2_size, chroma_mode, 1, &self.reco_map, self.sps.strong_intra_smoothing_enabled_flag);
-            intra::predict_intra(frame, chroma_x, chroma_y, chroma_log2_size, chroma_mode, 2, &self.reco_map, self.sps.strong_intra_smoothing_enabled_flag);
+            self.predict_intra(frame, chroma_x, chroma_y, chroma_log2_size, chroma_mode, 1);
+            self.predict_intra(frame, chroma_x, chroma_y, chroma_log2_size, chroma_mode, 2);
         }
 
         // Decode cbf_luma - Per H.265 spec 7.3.8.8:
         // Condition: CuPredMode == MODE_INTRA || trafoDepth != 0 || cbf_cb || cbf_cr
-        // For I-slice, CuPredMode is always INTRA, so cbf_luma is ALWAYS decoded
-        let is_intra = true; // I-slice: always intra
         let cbf_luma = if is_intra || trafo_depth != 0 || cbf_cb || cbf_cr {
             // Context: offset 0 if trafo_depth > 0, offset 1 if trafo_depth == 0
             let ctx_offset = if trafo_depth == 0 { 1 } else { 0 };
@@ -1494,6 +2054,9 @@ impl<'a> SliceContext<'a> {
                                 break;
                             }
                             k += 1;
+                            if k > 16 {
+                                return Err(HevcError::InvalidBitstream("cu_qp_delta_abs too long"));
+                            }
                         }
                         let mut val = 0i32;
                         for _ in 0..k {
@@ -1506,6 +2069,11 @@ impl<'a> SliceContext<'a> {
                     // Decode sign flag (bypass)
                     let sign = self.cabac.decode_bypass()?;
                     self.cu_qp_delta = if sign != 0 { -cu_qp_delta_abs } else { cu_qp_delta_abs };
+                    // CuQpDeltaVal range (7.4.9.14)
+                    let half_bd_offset = 3 * (self.sps.bit_depth_y() as i32 - 8);
+                    if !(-(26 + half_bd_offset)..=25 + half_bd_offset).contains(&self.cu_qp_delta) {
+                        return Err(HevcError::InvalidBitstream("CuQpDeltaVal out of range"));
+                    }
                 } else {
                     self.cu_qp_delta = 0;
                 }
@@ -1533,8 +2101,9 @@ impl<'a> SliceContext<'a> {
                     + self.header.slice_cb_qp_offset as i32;
                 let qp_i_cr = self.qp_y + self.pps.pps_cr_qp_offset as i32
                     + self.header.slice_cr_qp_offset as i32;
-                self.qp_cb = chroma_qp_mapping(qp_i_cb.clamp(0, 57));
-                self.qp_cr = chroma_qp_mapping(qp_i_cr.clamp(0, 57));
+                let qp_bd_offset_c = 6 * (self.sps.bit_depth_c() as i32 - 8);
+                self.qp_cb = chroma_qp_mapping(qp_i_cb.clamp(-qp_bd_offset_c, 57));
+                self.qp_cr = chroma_qp_mapping(qp_i_cr.clamp(-qp_bd_offset_c, 57));
 
             }
         }
@@ -1544,7 +2113,7 @@ impl<'a> SliceContext<'a> {
             // Use per-position intra mode for scan order (critical for NxN partitions
             // where each sub-TU has a different intra prediction mode)
             let actual_mode = self.get_intra_pred_mode(x0, y0);
-            let scan_order = residual::get_scan_order(log2_size, actual_mode.as_u8(), 0);
+            let scan_order = self.residual_scan_order(log2_size, actual_mode, 0);
             self.decode_and_apply_residual(x0, y0, log2_size, 0, scan_order, frame)?;
         }
 
@@ -1553,7 +2122,7 @@ impl<'a> SliceContext<'a> {
             let chroma_log2_size = log2_size - 1;
             if cbf_cb {
                 let chroma_mode = self.get_intra_pred_mode_c(x0, y0);
-                let scan_order = residual::get_scan_order(chroma_log2_size, chroma_mode.as_u8(), 1);
+                let scan_order = self.residual_scan_order(chroma_log2_size, chroma_mode, 1);
                 self.decode_and_apply_residual(
                     x0 / 2,
                     y0 / 2,
@@ -1565,7 +2134,7 @@ impl<'a> SliceContext<'a> {
             }
             if cbf_cr {
                 let chroma_mode = self.get_intra_pred_mode_c(x0, y0);
-                let scan_order = residual::get_scan_order(chroma_log2_size, chroma_mode.as_u8(), 2);
+                let scan_order = self.residual_scan_order(chroma_log2_size, chroma_mode, 2);
                 self.decode_and_apply_residual(
                     x0 / 2,
                     y0 / 2,
@@ -1591,6 +2160,21 @@ impl<'a> SliceContext<'a> {
         Ok(())
     }
 
+    /// Scan order for a residual block: mode dependent for intra, diagonal for inter
+    fn residual_scan_order(&self, log2_size: u8, mode: IntraPredMode, c_idx: u8) -> ScanOrder {
+        if self.cu_pred_mode == PredMode::Intra {
+            residual::get_scan_order(log2_size, mode.as_u8(), c_idx)
+        } else {
+            ScanOrder::Diagonal
+        }
+    }
+
+    /// Intra prediction of one block, using DC prediction in draft mode
+    fn predict_intra(&self, frame: &mut DecodedFrame, x: u32, y: u32, log2_size: u8, mode: IntraPredMode, c_idx: u8) {
+        let mode = if self.draft { IntraPredMode::Dc } else { mode };
+        intra::predict_intra(frame, x, y, log2_size, mode, c_idx, &self.reco_map, self.sps.strong_intra_smoothing_enabled_flag);
+    }
+
     /// Decode residual coefficients and apply to frame
     fn decode_and_apply_residual(
         &mut self,
@@ -1645,16 +2229,23 @@ impl<'a> SliceContext<'a> {
             _ => (self.qp_y, self.sps.bit_depth_y()),
         };
         let dequant_params = transform::DequantParams {
-            qp,
+            // Qp' = Qp + QpBdOffset (H.265 8.6.2), never negative
+            qp: qp + 6 * (bit_depth as i32 - 8),
             bit_depth,
             log2_tr_size: log2_size,
         };
-        transform::dequantize(&mut coeffs[..num_coeffs], dequant_params);
-
-        // Apply inverse transform
         let mut residual = [0i16; 1024];
-        let is_intra_4x4_luma = log2_size == 2 && c_idx == 0;
-        transform::inverse_transform(&coeffs, &mut residual, size, bit_depth, is_intra_4x4_luma);
+        if self.draft {
+            // Keep only the DC coefficient: a flat residual
+            transform::dequantize(&mut coeffs[..1], dequant_params);
+            residual[..num_coeffs].fill(transform::idct_dc(coeffs[0], bit_depth));
+        } else {
+            transform::dequantize(&mut coeffs[..num_coeffs], dequant_params);
+
+            // Apply inverse transform
+            let is_intra_4x4_luma = log2_size == 2 && c_idx == 0 && self.cu_pred_mode == PredMode::Intra;
+            transform::inverse_transform(&coeffs, &mut residual, size, bit_depth, is_intra_4x4_luma);
+        }
 
         // Add residual to prediction
         let max_val = (1i32 << bit_depth) - 1;
@@ -1708,11 +2299,42 @@ impl<'a> SliceContext<'a> {
                 }
             }
         } else {
-            // Inter partition modes (not implemented)
-            Err(HevcError::Unsupported("inter partition modes"))
+            // Inter binarization (H.265 Table 9-43)
+            if self.decode_part_mode_bin(0)? != 0 {
+                return Ok(PartMode::Part2Nx2N);
+            }
+            let horizontal = self.decode_part_mode_bin(1)? != 0;
+            if log2_cb_size == self.sps.log2_min_cb_size() {
+                // No AMP at minimum size; NxN only above 8x8
+                if horizontal {
+                    Ok(PartMode::Part2NxN)
+                } else if log2_cb_size == 3 || self.decode_part_mode_bin(2)? != 0 {
+                    Ok(PartMode::PartNx2N)
+                } else {
+                    Ok(PartMode::PartNxN)
+                }
+            } else if !self.sps.amp_enabled_flag || self.decode_part_mode_bin(3)? != 0 {
+                Ok(if horizontal { PartMode::Part2NxN } else { PartMode::PartNx2N })
+            } else {
+                let second = self.cabac.decode_bypass()? != 0;
+                Ok(match (horizontal, second) {
+                    (true, false) => PartMode::Part2NxnU,
+                    (true, true) => PartMode::Part2NxnD,
+                    (false, false) => PartMode::PartnLx2N,
+                    (false, true) => PartMode::PartnRx2N,
+                })
+            }
         }
     }
 
+    /// Decode one context-coded part_mode bin
+    fn decode_part_mode_bin(&mut self, ctx_offset: usize) -> Result<u8> {
+        let ctx_idx = context::PART_MODE + ctx_offset;
+        #[cfg(feature = "trace-coefficients")]
+        { self.cabac.trace_ctx_idx = ctx_idx as i32; }
+        self.cabac.decode_bin(&mut self.ctx[ctx_idx])
+    }
+
     /// Decode intra prediction modes and apply prediction
     fn decode_intra_prediction(
         &mut self,
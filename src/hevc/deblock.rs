@@ -10,10 +10,10 @@
 //! 2. Derive boundary strength bS (8.7.2.4)
 //! 3. Apply filtering decisions and filters (8.7.2.5)
 
+use super::inter::Mv;
 use super::params::{Pps, Sps};
 use super::picture::DecodedFrame;
 use super::slice::SliceHeader;
-use alloc::vec;
 use alloc::vec::Vec;
 
 /// Beta table for deblocking threshold (H.265 Table 8-17)
@@ -41,6 +41,7 @@ pub enum EdgeType {
 /// Tracks edge flags and boundary strength values during deblocking.
 /// Edge flags mark which 4x4 grid boundaries need filtering.
 /// Boundary strength (bS) values determine filter strength: 0=skip, 1=weak, 2=strong.
+#[derive(Default)]
 pub struct DeblockingContext {
     /// Edge flags for vertical edges (per 4x4 block)
     ver_edge_flags: Vec<u8>,
@@ -57,18 +58,28 @@ pub struct DeblockingContext {
 impl DeblockingContext {
     /// Create new deblocking context for image dimensions
     pub fn new(width: u32, height: u32) -> Self {
+        let mut ctx = Self::default();
+        ctx.reset(width, height);
+        ctx
+    }
+
+    /// Clear all edges for image dimensions, reusing the context's allocations
+    pub fn reset(&mut self, width: u32, height: u32) {
         // Edge flags and bS are stored per 4x4 block
         let width_4x4 = width.div_ceil(4) as usize;
         let height_4x4 = height.div_ceil(4) as usize;
         let size = width_4x4 * height_4x4;
 
-        Self {
-            ver_edge_flags: vec![0; size],
-            hor_edge_flags: vec![0; size],
-            ver_bs: vec![0; size],
-            hor_bs: vec![0; size],
-            stride: width_4x4,
+        for edges in [
+            &mut self.ver_edge_flags,
+            &mut self.hor_edge_flags,
+            &mut self.ver_bs,
+            &mut self.hor_bs,
+        ] {
+            edges.clear();
+            edges.resize(size, 0);
         }
+        self.stride = width_4x4;
     }
 
     /// Get index for 4x4 block at (x, y) in pixel coordinates
@@ -119,21 +130,41 @@ impl DeblockingContext {
         }
     }
 
-    /// Clear all edge flags and boundary strength
-    fn clear(&mut self) {
-        self.ver_edge_flags.fill(0);
-        self.hor_edge_flags.fill(0);
-        self.ver_bs.fill(0);
-        self.hor_bs.fill(0);
+    /// Clear the edge flags and boundary strength of the 4x4 blocks of a
+    /// `width` x `height` region at (x0, y0)
+    fn clear(&mut self, x0: u32, y0: u32, width: u32, height: u32) {
+        let columns = width.div_ceil(4) as usize;
+        for y in (y0..y0 + height).step_by(4) {
+            let start = self.idx(x0, y);
+            for edges in [
+                &mut self.ver_edge_flags,
+                &mut self.hor_edge_flags,
+                &mut self.ver_bs,
+                &mut self.hor_bs,
+            ] {
+                edges[start..start + columns].fill(0);
+            }
+        }
     }
 }
 
+/// Motion of an inter block as seen by the boundary strength derivation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EdgeMotion {
+    /// POC of the reference picture used from each list (None if unused)
+    pub refs: [Option<i32>; 2],
+    /// Motion vector for each list
+    pub mv: [Mv; 2],
+}
+
 /// Metadata tracker for deblocking filter decisions
 ///
 /// Stores per-block information needed for boundary strength derivation:
 /// - Transform block boundaries (split_transform_flag)
 /// - Prediction modes (intra vs inter)
 /// - Non-zero coefficient flags
+/// - Motion of inter blocks (allocated on first use)
+#[derive(Default)]
 pub struct DeblockMetadata {
     /// Split transform flags (per 4x4 block, stores whether TU was split)
     split_transform: Vec<bool>,
@@ -141,22 +172,33 @@ pub struct DeblockMetadata {
     pred_mode: Vec<u8>,
     /// Non-zero coefficient flags (per 4x4 block: has any non-zero coeffs in TU)
     nonzero_coeff: Vec<bool>,
+    /// Inter block motion (per 4x4 block, empty for intra-only slices)
+    motion: Vec<EdgeMotion>,
     /// Stride in 4x4 blocks
     stride: usize,
 }
 
 impl DeblockMetadata {
     pub fn new(width: u32, height: u32) -> Self {
+        let mut metadata = Self::default();
+        metadata.reset(width, height);
+        metadata
+    }
+
+    /// Clear all blocks for image dimensions, reusing the allocations
+    pub fn reset(&mut self, width: u32, height: u32) {
         let width_4x4 = width.div_ceil(4) as usize;
         let height_4x4 = height.div_ceil(4) as usize;
         let size = width_4x4 * height_4x4;
 
-        Self {
-            split_transform: vec![false; size],
-            pred_mode: vec![0; size],
-            nonzero_coeff: vec![false; size],
-            stride: width_4x4,
-        }
+        self.split_transform.clear();
+        self.split_transform.resize(size, false);
+        self.pred_mode.clear();
+        self.pred_mode.resize(size, 0);
+        self.nonzero_coeff.clear();
+        self.nonzero_coeff.resize(size, false);
+        self.motion.clear();
+        self.stride = width_4x4;
     }
 
     #[inline]
@@ -198,6 +240,60 @@ impl DeblockMetadata {
         let idx = self.idx(x, y);
         self.nonzero_coeff[idx]
     }
+
+    /// Set motion for a w×h inter prediction block
+    pub fn set_motion(&mut self, x: u32, y: u32, w: u32, h: u32, motion: EdgeMotion) {
+        if self.motion.is_empty() {
+            self.motion.resize(self.nonzero_coeff.len(), EdgeMotion::default());
+        }
+        for by in (y..y + h).step_by(4) {
+            for bx in (x..x + w).step_by(4) {
+                let idx = self.idx(bx, by);
+                if let Some(m) = self.motion.get_mut(idx) {
+                    *m = motion;
+                }
+            }
+        }
+    }
+
+    pub fn get_motion(&self, x: u32, y: u32) -> EdgeMotion {
+        let idx = self.idx(x, y);
+        self.motion.get(idx).copied().unwrap_or_default()
+    }
+}
+
+/// Boundary strength between two inter blocks without coded residual
+/// (H.265 8.7.2.4): 1 if they use different reference pictures, a different
+/// number of motion vectors, or vectors at least one luma sample apart
+fn inter_boundary_strength(p: &EdgeMotion, q: &EdgeMotion) -> u8 {
+    let far = |a: Mv, b: Mv| (a.x - b.x).abs() >= 4 || (a.y - b.y).abs() >= 4;
+    let used = |m: &EdgeMotion| [m.refs[0].is_some(), m.refs[1].is_some()];
+
+    match (used(p), used(q)) {
+        (up @ ([true, false] | [false, true]), uq @ ([true, false] | [false, true])) => {
+            // A single list is in use on each side: index 0 for L0, 1 for L1
+            let (lp, lq) = (up[1] as usize, uq[1] as usize);
+            (p.refs[lp] != q.refs[lq] || far(p.mv[lp], q.mv[lq])) as u8
+        }
+        ([true, true], [true, true]) => {
+            let (p0, p1, q0, q1) = (p.refs[0], p.refs[1], q.refs[0], q.refs[1]);
+            if !((p0 == q0 && p1 == q1) || (p0 == q1 && p1 == q0)) {
+                1
+            } else if p0 != p1 {
+                // Two different pictures: compare vectors referring to the same one
+                if p0 == q0 {
+                    (far(p.mv[0], q.mv[0]) || far(p.mv[1], q.mv[1])) as u8
+                } else {
+                    (far(p.mv[0], q.mv[1]) || far(p.mv[1], q.mv[0])) as u8
+                }
+            } else {
+                // Both vectors refer to the same picture: either pairing may match
+                ((far(p.mv[0], q.mv[0]) || far(p.mv[1], q.mv[1]))
+                    && (far(p.mv[0], q.mv[1]) || far(p.mv[1], q.mv[0]))) as u8
+            }
+        }
+        _ => 1,
+    }
 }
 
 /// Apply deblocking filter to decoded frame
@@ -207,12 +303,14 @@ impl DeblockMetadata {
 /// 2. Horizontal edges second (top to bottom, using filtered vertical edges)
 ///
 /// For I-slices (HEIC), most edges will be intra-predicted with bS=2 (strong filter).
+/// `ctx` is reset before use, so it can be kept between slices and pictures.
 pub fn apply_deblocking_filter(
     frame: &mut DecodedFrame,
     sps: &Sps,
     pps: &Pps,
     header: &SliceHeader,
     metadata: &DeblockMetadata,
+    ctx: &mut DeblockingContext,
 ) {
     // Skip if deblocking disabled
     if header.slice_deblocking_filter_disabled_flag {
@@ -222,7 +320,7 @@ pub fn apply_deblocking_filter(
     let width = frame.width;
     let height = frame.height;
 
-    let mut ctx = DeblockingContext::new(width, height);
+    ctx.reset(width, height);
 
     // Process each CTB
     let log2_ctb_size = sps.log2_min_luma_coding_block_size_minus3 + 3 + sps.log2_diff_max_min_luma_coding_block_size;
@@ -238,7 +336,7 @@ pub fn apply_deblocking_filter(
             // For each CTB, process vertical then horizontal edges
             process_ctb_edges(
                 frame,
-                &mut ctx,
+                ctx,
                 metadata,
                 sps,
                 pps,
@@ -271,7 +369,7 @@ fn process_ctb_edges(
     let ctb_height = ctb_size.min(height - y0);
 
     // Clear context for this CTB
-    ctx.clear();
+    ctx.clear(x0, y0, ctb_width, ctb_height);
 
     // 1. Mark vertical edges and derive boundary strength
     let filter_left_edge = x0 > 0 && !is_slice_or_tile_boundary(sps, pps, header, x0 - 1, y0, x0, y0);
@@ -415,8 +513,8 @@ fn derive_boundary_strength_ctb(
                 // Transform edge with non-zero coefficients -> weak filter
                 1
             } else {
-                // No filtering needed (would check motion vectors for inter)
-                0
+                // Both inter: compare reference pictures and motion vectors
+                inter_boundary_strength(&metadata.get_motion(x_p, y_p), &metadata.get_motion(x_q, y_q))
             };
 
             ctx.set_bs(abs_x, abs_y, edge_type, bs);
@@ -425,6 +523,8 @@ fn derive_boundary_strength_ctb(
 }
 
 /// Filter luma edges for a CTB (H.265 8.7.2.5)
+///
+/// Edges are filtered a whole edge row or column of the CTB at a time.
 fn filter_edges_luma(
     frame: &mut DecodedFrame,
     ctx: &DeblockingContext,
@@ -442,37 +542,66 @@ fn filter_edges_luma(
     let qp_offset = pps.pps_beta_offset_div2 * 2;
     let base_qp = 0; // Would use slice QP + cu_qp_delta
 
-    for y in (0..height).step_by(4) {
-        for x in (0..width).step_by(4) {
-            let abs_x = x0 + x;
-            let abs_y = y0 + y;
+    // Calculate QP for threshold lookup
+    let qp_l = (base_qp + qp_offset).clamp(0, 51) as usize;
+    let tc_offset = pps.pps_tc_offset_div2 * 2;
+    let tc = TC_TABLE[(qp_l as i32 + tc_offset as i32 + 2).clamp(0, 53) as usize] as i32;
 
-            let bs = ctx.get_bs(abs_x, abs_y, edge_type);
-            if bs == 0 {
-                continue;
-            }
+    // bS of each sample along the edge, 0 where the 4-sample segment is not
+    // filtered or lies partly outside the plane
+    let mut bs = [0u8; MAX_CTB_SIZE];
+    let samples = &mut frame.y_plane;
+    let (x0, y0) = (x0 as usize, y0 as usize);
 
-            // Calculate QP for threshold lookup
-            let qp_l = (base_qp + qp_offset).clamp(0, 51) as usize;
-            let beta = BETA_TABLE[qp_l] as i32;
-            let tc_offset = pps.pps_tc_offset_div2 * 2;
-            let tc_val = TC_TABLE[(qp_l as i32 + tc_offset as i32 + 2).clamp(0, 53) as usize] as i32;
-
-            filter_luma_edge(
-                &mut frame.y_plane,
-                stride,
-                abs_x,
-                abs_y,
-                edge_type,
-                bs,
-                beta,
-                tc_val,
-            );
+    match edge_type {
+        EdgeType::Vertical => {
+            // P side: column left of the edge, Q side: column at the edge
+            let mut p = [0u16; MAX_CTB_SIZE];
+            let mut q = [0u16; MAX_CTB_SIZE];
+            for x in (x0..x0 + width as usize).step_by(4).filter(|&x| x > 0) {
+                let n = segment_bs(&mut bs, height as usize, |y| {
+                    let last = (y0 + y + 3) * stride + x;
+                    let bs = ctx.get_bs(x as u32, (y0 + y) as u32, edge_type);
+                    if last < samples.len() { bs } else { 0 }
+                });
+                if n == 0 {
+                    continue;
+                }
+                for y in 0..n {
+                    let idx = (y0 + y) * stride + x;
+                    p[y] = samples[idx - 1];
+                    q[y] = samples[idx];
+                }
+                filter_luma_pairs(&mut p[..n], &mut q[..n], &bs[..n], tc);
+                for y in (0..n).filter(|&y| bs[y] != 0) {
+                    let idx = (y0 + y) * stride + x;
+                    samples[idx - 1] = p[y];
+                    samples[idx] = q[y];
+                }
+            }
+        }
+        EdgeType::Horizontal => {
+            // P side: row above the edge, Q side: row at the edge
+            for y in (y0..y0 + height as usize).step_by(4).filter(|&y| y > 0) {
+                let start = y * stride + x0;
+                let n = segment_bs(&mut bs, width as usize, |x| {
+                    let bs = ctx.get_bs((x0 + x) as u32, y as u32, edge_type);
+                    if start + x + 3 < samples.len() { bs } else { 0 }
+                });
+                if n == 0 {
+                    continue;
+                }
+                let (above, below) = samples.split_at_mut(start);
+                let p = &mut above[start - stride..][..n];
+                filter_luma_pairs(p, &mut below[..n], &bs[..n], tc);
+            }
         }
     }
 }
 
 /// Filter chroma edges for a CTB (H.265 8.7.2.5)
+///
+/// Each edge segment of 8 luma samples filters 2 chroma samples.
 fn filter_edges_chroma(
     frame: &mut DecodedFrame,
     ctx: &DeblockingContext,
@@ -485,157 +614,256 @@ fn filter_edges_chroma(
     edge_type: EdgeType,
 ) {
     // Chroma is half resolution for 4:2:0
-    let chroma_stride = (frame.width / 2) as usize;
+    let stride = (frame.width / 2) as usize;
     let qp_offset = pps.pps_beta_offset_div2 * 2;
     let base_qp = 0;
 
-    for y in (0..height).step_by(8) {
-        for x in (0..width).step_by(8) {
-            let abs_x = x0 + x;
-            let abs_y = y0 + y;
+    let qp_c = (base_qp + qp_offset).clamp(0, 51) as usize;
+    let tc = TC_TABLE[(qp_c as i32 + pps.pps_tc_offset_div2 as i32 * 2 + 2).clamp(0, 53) as usize] as i32;
+
+    // Filtered chroma samples along the edge: chroma is only filtered at
+    // strong boundaries (bS=2), and only where the segment lies in the planes
+    let len = frame.cb_plane.len().min(frame.cr_plane.len());
+    let mut on = [0u8; MAX_CTB_SIZE / 2];
+    let (cx0, cy0) = (x0 as usize / 2, y0 as usize / 2);
+    let edge_on = |x: usize, y: usize, last: usize| {
+        let bs = ctx.get_bs(x as u32, y as u32, edge_type);
+        (bs >= 2 && last < len) as u8
+    };
 
-            let bs = ctx.get_bs(abs_x, abs_y, edge_type);
-            if bs < 2 {
-                // Chroma only filtered at strong boundaries (bS=2)
-                continue;
+    match edge_type {
+        EdgeType::Vertical => {
+            let mut p = [0u16; MAX_CTB_SIZE / 2];
+            let mut q = [0u16; MAX_CTB_SIZE / 2];
+            for x in (x0 as usize..(x0 + width) as usize).step_by(8).filter(|&x| x > 0) {
+                let cx = x / 2;
+                let n = chroma_segments(&mut on, height as usize, |y| {
+                    edge_on(x, y0 as usize + y, (cy0 + y / 2 + 1) * stride + cx)
+                });
+                if n == 0 {
+                    continue;
+                }
+                for plane in [&mut frame.cb_plane, &mut frame.cr_plane] {
+                    for y in 0..n {
+                        let idx = (cy0 + y) * stride + cx;
+                        p[y] = plane[idx - 1];
+                        q[y] = plane[idx];
+                    }
+                    filter_chroma_pairs(&mut p[..n], &mut q[..n], &on[..n], tc);
+                    for y in (0..n).filter(|&y| on[y] != 0) {
+                        let idx = (cy0 + y) * stride + cx;
+                        plane[idx - 1] = p[y];
+                        plane[idx] = q[y];
+                    }
+                }
+            }
+        }
+        EdgeType::Horizontal => {
+            for y in (y0 as usize..(y0 + height) as usize).step_by(8).filter(|&y| y > 0) {
+                let start = y / 2 * stride + cx0;
+                let n = chroma_segments(&mut on, width as usize, |x| {
+                    edge_on(x0 as usize + x, y, start + x / 2 + 1)
+                });
+                if n == 0 {
+                    continue;
+                }
+                for plane in [&mut frame.cb_plane, &mut frame.cr_plane] {
+                    let (above, below) = plane.split_at_mut(start);
+                    let p = &mut above[start - stride..][..n];
+                    filter_chroma_pairs(p, &mut below[..n], &on[..n], tc);
+                }
             }
-
-            let qp_c = (base_qp + qp_offset).clamp(0, 51) as usize;
-            let tc_val = TC_TABLE[(qp_c as i32 + pps.pps_tc_offset_div2 as i32 * 2 + 2).clamp(0, 53) as usize] as i32;
-
-            // Chroma coordinates (half resolution)
-            let cx = abs_x / 2;
-            let cy = abs_y / 2;
-
-            filter_chroma_edge(&mut frame.cb_plane, chroma_stride, cx, cy, edge_type, tc_val);
-            filter_chroma_edge(&mut frame.cr_plane, chroma_stride, cx, cy, edge_type, tc_val);
         }
     }
 }
 
-/// Apply luma edge filter at specific edge
-fn filter_luma_edge(
-    samples: &mut [u16],
-    stride: usize,
-    x: u32,
-    y: u32,
-    edge_type: EdgeType,
-    bs: u8,
-    beta: i32,
-    tc: i32,
-) {
-    let x = x as usize;
-    let y = y as usize;
+/// Largest CTB size, and so the longest edge filtered at once
+const MAX_CTB_SIZE: usize = 64;
 
-    // Get sample indices for P and Q sides (4 samples each)
-    let (p_idx, q_idx): (Vec<usize>, Vec<usize>) = match edge_type {
-        EdgeType::Vertical => {
-            // P side: 4 samples to left of edge, Q side: 4 samples at/right of edge
-            let p = (0..4).map(|i| (y + i) * stride + x.saturating_sub(1)).collect();
-            let q = (0..4).map(|i| (y + i) * stride + x).collect();
-            (p, q)
-        }
-        EdgeType::Horizontal => {
-            // P side: 4 samples above edge, Q side: 4 samples at/below edge
-            let p = (0..4).map(|i| (y.saturating_sub(1)) * stride + x + i).collect();
-            let q = (0..4).map(|i| y * stride + x + i).collect();
-            (p, q)
+/// Spread the bS of the 4-sample segments of an edge of `len` samples,
+/// given by `segment` for each segment start, over `bs`
+///
+/// Returns the number of samples up to the last filtered segment.
+fn segment_bs(bs: &mut [u8], len: usize, mut segment: impl FnMut(usize) -> u8) -> usize {
+    let mut end = 0;
+    for start in (0..len).step_by(4) {
+        let value = segment(start);
+        bs[start..start + 4].fill(value);
+        if value != 0 {
+            end = start + 4;
         }
-    };
+    }
+    end
+}
 
-    // Check all indices are valid
-    for &idx in p_idx.iter().chain(q_idx.iter()) {
-        if idx >= samples.len() {
-            return;
+/// Mark the 2 chroma samples of each 8-sample luma segment of an edge of
+/// `len` luma samples that `segment` filters
+///
+/// Returns the number of chroma samples up to the last filtered segment.
+fn chroma_segments(on: &mut [u8], len: usize, mut segment: impl FnMut(usize) -> u8) -> usize {
+    let mut end = 0;
+    for start in (0..len).step_by(8) {
+        let value = segment(start);
+        let c = start / 2;
+        on[c..c + 4].copy_from_slice(&[value, value, 0, 0]);
+        if value != 0 {
+            end = c + 2;
         }
     }
+    end
+}
 
-    // Apply weak or strong filter based on bS
-    if bs == 2 {
-        // Strong filter for intra edges
-        apply_strong_luma_filter(samples, &p_idx, &q_idx, beta, tc);
-    } else {
-        // Weak filter
-        apply_weak_luma_filter(samples, &p_idx, &q_idx, beta, tc);
+/// Filter the sample pairs across a luma edge, `p[i]` before and `q[i]`
+/// after it, with the filter selected by `bs[i]`
+fn filter_luma_pairs(p: &mut [u16], q: &mut [u16], bs: &[u8], tc: i32) {
+    #[cfg(all(
+        feature = "unsafe-simd",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    if let Some(isa) = super::simd::Isa::detect() {
+        super::filter_simd::luma_pairs(isa, p, q, bs, tc);
+        return;
     }
+
+    luma_pairs(p, q, bs, tc);
 }
 
-/// Apply strong luma filter (H.265 8.7.2.5.7)
-fn apply_strong_luma_filter(
-    samples: &mut [u16],
-    p_idx: &[usize],
-    q_idx: &[usize],
-    _beta: i32,
-    tc: i32,
-) {
-    // Simplified strong filter
-    for i in 0..4.min(p_idx.len()).min(q_idx.len()) {
-        let p0 = samples[p_idx[i]] as i32;
-        let q0 = samples[q_idx[i]] as i32;
+/// Filter the sample pairs across a chroma edge where `on[i]` is set
+fn filter_chroma_pairs(p: &mut [u16], q: &mut [u16], on: &[u8], tc: i32) {
+    #[cfg(all(
+        feature = "unsafe-simd",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    if let Some(isa) = super::simd::Isa::detect() {
+        super::filter_simd::chroma_pairs(isa, p, q, on, tc);
+        return;
+    }
+
+    chroma_pairs(p, q, on, tc);
+}
 
-        let delta = (q0 - p0).clamp(-tc, tc);
-        samples[p_idx[i]] = (p0 + delta / 2).clamp(0, 255) as u16;
-        samples[q_idx[i]] = (q0 - delta / 2).clamp(0, 255) as u16;
+/// Scalar luma edge filter: strong filter (H.265 8.7.2.5.7) where bS is 2,
+/// weak filter (H.265 8.7.2.5.8) where it is 1
+pub(super) fn luma_pairs(p: &mut [u16], q: &mut [u16], bs: &[u8], tc: i32) {
+    for ((p, q), &bs) in p.iter_mut().zip(q.iter_mut()).zip(bs) {
+        let p0 = *p as i32;
+        let q0 = *q as i32;
+
+        let (p_delta, q_delta) = match bs {
+            0 => continue,
+            // Simplified strong filter
+            2 => {
+                let delta = (q0 - p0).clamp(-tc, tc);
+                (delta / 2, delta / 2)
+            }
+            // Simplified weak filter
+            _ => {
+                let delta = ((q0 - p0) * 9 / 16).clamp(-tc, tc);
+                (delta, delta)
+            }
+        };
+        *p = (p0 + p_delta).clamp(0, 255) as u16;
+        *q = (q0 - q_delta).clamp(0, 255) as u16;
     }
 }
 
-/// Apply weak luma filter (H.265 8.7.2.5.8)
-fn apply_weak_luma_filter(
-    samples: &mut [u16],
-    p_idx: &[usize],
-    q_idx: &[usize],
-    _beta: i32,
-    tc: i32,
-) {
-    // Simplified weak filter
-    for i in 0..4.min(p_idx.len()).min(q_idx.len()) {
-        let p0 = samples[p_idx[i]] as i32;
-        let q0 = samples[q_idx[i]] as i32;
+/// Scalar chroma edge filter (H.265 8.7.2.5.9)
+pub(super) fn chroma_pairs(p: &mut [u16], q: &mut [u16], on: &[u8], tc: i32) {
+    for ((p, q), _) in p.iter_mut().zip(q.iter_mut()).zip(on).filter(|(_, on)| **on != 0) {
+        let p0 = *p as i32;
+        let q0 = *q as i32;
 
-        let delta = ((q0 - p0) * 9 / 16).clamp(-tc, tc);
-        samples[p_idx[i]] = (p0 + delta).clamp(0, 255) as u16;
-        samples[q_idx[i]] = (q0 - delta).clamp(0, 255) as u16;
+        let delta = ((q0 - p0) / 2).clamp(-tc, tc);
+        *p = (p0 + delta).clamp(0, 255) as u16;
+        *q = (q0 - delta).clamp(0, 255) as u16;
     }
 }
 
-/// Apply chroma edge filter (H.265 8.7.2.5.9)
-fn filter_chroma_edge(
-    samples: &mut [u16],
-    stride: usize,
-    x: u32,
-    y: u32,
-    edge_type: EdgeType,
-    tc: i32,
-) {
-    let x = x as usize;
-    let y = y as usize;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
 
-    // Chroma filter is simpler - only filters 2 samples per side
-    let (p_idx, q_idx): (Vec<usize>, Vec<usize>) = match edge_type {
-        EdgeType::Vertical => {
-            let p = (0..2).map(|i| (y + i) * stride + x.saturating_sub(1)).collect();
-            let q = (0..2).map(|i| (y + i) * stride + x).collect();
-            (p, q)
-        }
-        EdgeType::Horizontal => {
-            let p = (0..2).map(|i| (y.saturating_sub(1)) * stride + x + i).collect();
-            let q = (0..2).map(|i| y * stride + x + i).collect();
-            (p, q)
+    fn uni(poc: i32, x: i32, y: i32) -> EdgeMotion {
+        EdgeMotion {
+            refs: [Some(poc), None],
+            mv: [Mv::new(x, y), Mv::default()],
         }
-    };
+    }
 
-    for &idx in p_idx.iter().chain(q_idx.iter()) {
-        if idx >= samples.len() {
-            return;
-        }
+    #[test]
+    fn test_inter_boundary_strength() {
+        // Same reference, vectors less than one sample apart
+        assert_eq!(inter_boundary_strength(&uni(0, 0, 0), &uni(0, 3, -3)), 0);
+        // One full sample apart
+        assert_eq!(inter_boundary_strength(&uni(0, 0, 0), &uni(0, 4, 0)), 1);
+        // Different reference pictures
+        assert_eq!(inter_boundary_strength(&uni(0, 0, 0), &uni(4, 0, 0)), 1);
+
+        // Same picture reached through list 1 is still the same reference
+        let l1 = EdgeMotion {
+            refs: [None, Some(0)],
+            mv: [Mv::default(), Mv::new(1, 1)],
+        };
+        assert_eq!(inter_boundary_strength(&uni(0, 0, 0), &l1), 0);
+
+        // Bi-prediction against uni-prediction
+        let bi = EdgeMotion {
+            refs: [Some(0), Some(8)],
+            mv: [Mv::default(); 2],
+        };
+        assert_eq!(inter_boundary_strength(&bi, &uni(0, 0, 0)), 1);
+
+        // Bi-prediction with swapped lists pairs vectors by picture
+        let swapped = EdgeMotion {
+            refs: [Some(8), Some(0)],
+            mv: [Mv::new(0, 2), Mv::new(2, 0)],
+        };
+        assert_eq!(inter_boundary_strength(&bi, &swapped), 0);
     }
 
-    for i in 0..2.min(p_idx.len()).min(q_idx.len()) {
-        let p0 = samples[p_idx[i]] as i32;
-        let q0 = samples[q_idx[i]] as i32;
+    #[test]
+    fn test_boundary_strength_of_decoded_motion() {
+        use crate::hevc::test_util::{self, SliceHeaderSpec, StreamParams, TestCu};
+        use crate::hevc::{SequenceDecoder, bitstream, slice::SliceType};
+
+        // Columns of 16x16 CTBs moving by (16, 0), (16, 0), (0, 0) and
+        // (3, 0) quarter samples
+        let mut cus = vec![TestCu::SKIP; 16];
+        cus[0] = TestCu::Amvp { mvd: [16, 0], mvp_flag: false, level: 0 };
+        cus[2] = TestCu::Amvp { mvd: [-16, 0], mvp_flag: false, level: 0 };
+        cus[3] = TestCu::Amvp { mvd: [3, 0], mvp_flag: false, level: 0 };
+        for row in 1..4 {
+            // Merge lists (A1, B1): take the vector of the CTB above
+            cus[row * 4 + 2] = TestCu::Skip { merge_idx: 1 };
+            cus[row * 4 + 3] = TestCu::Skip { merge_idx: 1 };
+        }
+        let params = StreamParams::default();
+        let header = SliceHeaderSpec {
+            nal_type: bitstream::NalType::TrailR,
+            slice_type: SliceType::P,
+            poc_lsb: 1,
+            refs_before: vec![1],
+            max_num_merge_cand: 2,
+            ..SliceHeaderSpec::idr()
+        };
+        let access_units = [
+            vec![params.sps(), params.pps(), SliceHeaderSpec::idr().slice(&params, &[TestCu::Intra(0); 16])],
+            vec![header.slice(&params, &cus)],
+        ];
+        let mut decoder = SequenceDecoder::new();
+        for access_unit in access_units {
+            let data = test_util::annex_b(&access_unit);
+            decoder.decode_access_unit(&bitstream::parse_nal_units(&data).unwrap()).unwrap();
+        }
 
-        let delta = ((q0 - p0) / 2).clamp(-tc, tc);
-        samples[p_idx[i]] = (p0 + delta).clamp(0, 255) as u16;
-        samples[q_idx[i]] = (q0 - delta).clamp(0, 255) as u16;
+        // Only the edge between (16, 0) and (0, 0) is a full sample apart
+        let ctx = &decoder.context.deblocking;
+        for y in (0..64).step_by(4) {
+            for x in (8..64).step_by(8) {
+                assert_eq!(ctx.get_bs(x, y, EdgeType::Vertical), (x == 32) as u8, "({x}, {y})");
+                assert_eq!(ctx.get_bs(y, x, EdgeType::Horizontal), 0, "({y}, {x})");
+            }
+        }
     }
 }
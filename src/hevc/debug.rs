@@ -5,13 +5,7 @@
 
 extern crate alloc;
 
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-
-/// Enable verbose coefficient logging
-pub static VERBOSE_COEFFS: AtomicBool = AtomicBool::new(false);
-
-/// Enable CABAC state logging
-pub static VERBOSE_CABAC: AtomicBool = AtomicBool::new(false);
+use std::sync::atomic::{AtomicU32, Ordering};
 
 /// Count of detected invariant violations
 pub static INVARIANT_VIOLATIONS: AtomicU32 = AtomicU32::new(0);
@@ -0,0 +1,512 @@
+//! Decoded picture buffer (H.265 section 8.3)
+//!
+//! Picture order count derivation, reference picture set marking,
+//! reference picture list construction for P and B slices and output in
+//! POC order by "bumping" (C.5.2).
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::inter::{MotionField, RefPic, RefPicLists};
+use super::params::{DpbOrdering, Sps};
+use super::picture::DecodedFrame;
+use super::slice::SliceHeader;
+use crate::error::HevcError;
+
+type Result<T> = core::result::Result<T, HevcError>;
+
+/// Reference marking of a stored picture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefMarking {
+    /// Not used for reference (removed at the next RPS update)
+    Unused,
+    /// Used for short-term reference
+    ShortTerm,
+    /// Used for long-term reference
+    LongTerm,
+}
+
+/// A decoded picture kept for reference or output
+pub struct DpbPicture {
+    /// Reconstructed samples (after in-loop filters), shared with the
+    /// output once the picture is bumped
+    pub frame: Arc<DecodedFrame>,
+    /// Motion field for temporal motion vector prediction
+    pub motion: MotionField,
+    /// PicOrderCntVal
+    pub poc: i32,
+    /// Current reference marking
+    pub marking: RefMarking,
+    /// "Needed for output": not yet bumped
+    pub needed_for_output: bool,
+    /// PicLatencyCount: pictures decoded since this one while it waited
+    /// for output
+    pub latency: u32,
+}
+
+/// Reference pictures usable by the current picture, as DPB indices
+#[derive(Debug, Clone, Default)]
+pub struct RefPicSet {
+    /// RefPicSetStCurrBefore
+    pub st_curr_before: Vec<usize>,
+    /// RefPicSetStCurrAfter
+    pub st_curr_after: Vec<usize>,
+    /// RefPicSetLtCurr
+    pub lt_curr: Vec<usize>,
+}
+
+/// Derive PicOrderCntVal (8.3.1)
+///
+/// `prev_tid0_poc` is the POC of the previous TemporalId 0 picture that is
+/// not a RASL, RADL or sub-layer non-reference picture. `reset_msb` is set
+/// for IRAP pictures with NoRaslOutputFlag equal to 1.
+pub fn pic_order_cnt(prev_tid0_poc: i32, poc_lsb: u32, log2_max_poc_lsb: u8, reset_msb: bool) -> i32 {
+    let poc_lsb = poc_lsb as i32;
+    if reset_msb {
+        return poc_lsb;
+    }
+    let max_lsb = 1i32 << log2_max_poc_lsb;
+    let prev_lsb = prev_tid0_poc & (max_lsb - 1);
+    let prev_msb = prev_tid0_poc - prev_lsb;
+    let msb = if poc_lsb < prev_lsb && prev_lsb - poc_lsb >= max_lsb / 2 {
+        prev_msb + max_lsb
+    } else if poc_lsb > prev_lsb && poc_lsb - prev_lsb > max_lsb / 2 {
+        prev_msb - max_lsb
+    } else {
+        prev_msb
+    };
+    msb + poc_lsb
+}
+
+/// Decoded picture buffer
+#[derive(Default)]
+pub struct DecodedPictureBuffer {
+    pictures: Vec<DpbPicture>,
+}
+
+impl DecodedPictureBuffer {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the current picture after decoding, marked as short-term
+    ///
+    /// `output` is PicOutputFlag. Pictures waiting for output age by one
+    /// picture and are bumped while `ordering` is exceeded (C.5.2.3);
+    /// bumped pictures are appended to `out` in POC order.
+    pub fn insert(
+        &mut self,
+        frame: DecodedFrame,
+        motion: MotionField,
+        poc: i32,
+        output: bool,
+        ordering: &DpbOrdering,
+        out: &mut Vec<Arc<DecodedFrame>>,
+    ) {
+        for pic in self.pictures.iter_mut().filter(|p| p.needed_for_output) {
+            pic.latency += 1;
+        }
+        self.pictures.push(DpbPicture {
+            frame: Arc::new(frame),
+            motion,
+            poc,
+            marking: RefMarking::ShortTerm,
+            needed_for_output: output,
+            latency: 0,
+        });
+        while self.exceeds_reorder(ordering) {
+            self.bump(out);
+        }
+    }
+
+    /// Most recently inserted picture
+    pub fn last(&self) -> Option<&DpbPicture> {
+        self.pictures.last()
+    }
+
+    /// Make room for the current picture before decoding it (C.5.2.2)
+    ///
+    /// Call after [`apply_ref_pic_set`](Self::apply_ref_pic_set). For an
+    /// IRAP picture with NoRaslOutputFlag, the pictures waiting for output
+    /// are all bumped, or discarded with `no_output_of_prior_pics`.
+    pub fn prepare(
+        &mut self,
+        ordering: &DpbOrdering,
+        irap_no_rasl_output: bool,
+        no_output_of_prior_pics: bool,
+        out: &mut Vec<Arc<DecodedFrame>>,
+    ) {
+        if irap_no_rasl_output {
+            if no_output_of_prior_pics {
+                self.pictures.clear();
+            } else {
+                self.flush(out);
+            }
+            return;
+        }
+        while self.exceeds_reorder(ordering)
+            || self.pictures.len() > ordering.max_dec_pic_buffering_minus1 as usize
+        {
+            if !self.bump(out) {
+                break;
+            }
+        }
+    }
+
+    /// Bump every picture waiting for output, then empty the buffer
+    pub fn flush(&mut self, out: &mut Vec<Arc<DecodedFrame>>) {
+        while self.bump(out) {}
+        self.pictures.clear();
+    }
+
+    /// Remove all pictures without output, returning their frames
+    pub fn drain(&mut self) -> impl Iterator<Item = Arc<DecodedFrame>> + '_ {
+        self.pictures.drain(..).map(|pic| pic.frame)
+    }
+
+    /// More pictures wait for output than sps_max_num_reorder_pics allows,
+    /// or one has waited longer than SpsMaxLatencyPictures
+    fn exceeds_reorder(&self, ordering: &DpbOrdering) -> bool {
+        let waiting = self.pictures.iter().filter(|p| p.needed_for_output);
+        waiting.clone().count() > ordering.max_num_reorder_pics as usize
+            || ordering
+                .max_latency_pictures()
+                .is_some_and(|max| waiting.clone().any(|p| p.latency >= max))
+    }
+
+    /// Output the waiting picture with the smallest POC ("bumping", C.5.2.4);
+    /// false if no picture waits for output
+    fn bump(&mut self, out: &mut Vec<Arc<DecodedFrame>>) -> bool {
+        let Some(pic) = self
+            .pictures
+            .iter_mut()
+            .filter(|p| p.needed_for_output)
+            .min_by_key(|p| p.poc)
+        else {
+            return false;
+        };
+        pic.needed_for_output = false;
+        out.push(Arc::clone(&pic.frame));
+        self.remove_unneeded();
+        true
+    }
+
+    /// Empty the buffers of pictures neither referenced nor waiting for output
+    fn remove_unneeded(&mut self) {
+        self.pictures
+            .retain(|p| p.marking != RefMarking::Unused || p.needed_for_output);
+    }
+
+    /// Decoding process for the reference picture set (8.3.2)
+    ///
+    /// Updates the marking of stored pictures, drops pictures that are no
+    /// longer referenced and generates unavailable reference pictures
+    /// (8.3.3) so that decoding can continue after lost or skipped frames.
+    pub fn apply_ref_pic_set(
+        &mut self,
+        sps: &Sps,
+        header: &SliceHeader,
+        poc: i32,
+        is_irap_no_rasl_output: bool,
+    ) -> RefPicSet {
+        if is_irap_no_rasl_output {
+            for pic in &mut self.pictures {
+                pic.marking = RefMarking::Unused;
+            }
+        }
+
+        let log2_max_lsb = sps.log2_max_pic_order_cnt_lsb_minus4 + 4;
+        let max_lsb = 1i32 << log2_max_lsb;
+        let rps = &header.short_term_ref_pic_set;
+
+        let mut marking = vec![RefMarking::Unused; self.pictures.len()];
+
+        // Long-term entries: matched on POC LSBs unless the MSBs are signalled
+        let mut lt_curr = Vec::new();
+        for lt in &header.long_term_ref_pics {
+            let mut lt_poc = lt.poc_lsb_lt as i32;
+            if lt.delta_poc_msb_present_flag {
+                lt_poc += poc - lt.delta_poc_msb_cycle_lt as i32 * max_lsb - (poc & (max_lsb - 1));
+            }
+            let found = self.pictures.iter().position(|p| {
+                p.marking != RefMarking::Unused
+                    && if lt.delta_poc_msb_present_flag {
+                        p.poc == lt_poc
+                    } else {
+                        p.poc & (max_lsb - 1) == lt_poc
+                    }
+            });
+            if let Some(i) = found {
+                marking[i] = RefMarking::LongTerm;
+            }
+            if lt.used_by_curr_pic_lt {
+                lt_curr.push((found.map(|i| self.pictures[i].poc).unwrap_or(lt_poc), RefMarking::LongTerm));
+            }
+        }
+
+        // Short-term entries: matched on full POC among short-term pictures
+        let mut st_before = Vec::new();
+        let mut st_after = Vec::new();
+        let short_term = rps
+            .delta_poc_s0
+            .iter()
+            .zip(&rps.used_by_curr_pic_s0)
+            .map(|(d, u)| (d, u, true))
+            .chain(rps.delta_poc_s1.iter().zip(&rps.used_by_curr_pic_s1).map(|(d, u)| (d, u, false)));
+        for (&delta, &used, before) in short_term {
+            let st_poc = poc + delta;
+            if let Some(i) = self.pictures.iter().position(|p| {
+                p.marking == RefMarking::ShortTerm && p.poc == st_poc
+            }) && marking[i] == RefMarking::Unused
+            {
+                marking[i] = RefMarking::ShortTerm;
+            }
+            if used {
+                let list = if before { &mut st_before } else { &mut st_after };
+                list.push((st_poc, RefMarking::ShortTerm));
+            }
+        }
+
+        for (pic, marking) in self.pictures.iter_mut().zip(marking) {
+            pic.marking = marking;
+        }
+
+        // Generate pictures the RPS requires but the buffer lacks (8.3.3.2)
+        for &(ref_poc, ref_marking) in st_before.iter().chain(&st_after).chain(&lt_curr) {
+            if self.find(ref_poc, ref_marking).is_none() {
+                self.pictures.push(DpbPicture {
+                    frame: Arc::new(generate_missing_picture(sps)),
+                    motion: MotionField::default(),
+                    poc: ref_poc,
+                    marking: ref_marking,
+                    needed_for_output: false,
+                    latency: 0,
+                });
+            }
+        }
+
+        self.remove_unneeded();
+
+        let resolve = |entries: &[(i32, RefMarking)]| -> Vec<usize> {
+            entries.iter().filter_map(|&(p, m)| self.find(p, m)).collect()
+        };
+        RefPicSet {
+            st_curr_before: resolve(&st_before),
+            st_curr_after: resolve(&st_after),
+            lt_curr: resolve(&lt_curr),
+        }
+    }
+
+    fn find(&self, poc: i32, marking: RefMarking) -> Option<usize> {
+        self.pictures
+            .iter()
+            .position(|p| p.poc == poc && p.marking == marking)
+    }
+
+    /// Construct RefPicList0 and RefPicList1 for a slice (8.3.4, F.8.3.4)
+    ///
+    /// `inter_layer` holds RefPicSetInterLayer0 and RefPicSetInterLayer1:
+    /// pictures of other layers in the same access unit, which are treated
+    /// as long-term references.
+    pub fn ref_pic_lists<'a>(
+        &'a self,
+        rps: &RefPicSet,
+        header: &SliceHeader,
+        inter_layer: [Vec<RefPic<'a>>; 2],
+    ) -> Result<RefPicLists<'a>> {
+        self.build_ref_pic_lists(
+            rps,
+            [header.num_ref_idx_active(0), header.num_ref_idx_active(1)],
+            [header.list_entry_l0.as_deref(), header.list_entry_l1.as_deref()],
+            inter_layer,
+        )
+    }
+
+    fn build_ref_pic_lists<'a>(
+        &'a self,
+        rps: &RefPicSet,
+        num_active: [usize; 2],
+        list_entries: [Option<&[u8]>; 2],
+        inter_layer: [Vec<RefPic<'a>>; 2],
+    ) -> Result<RefPicLists<'a>> {
+        let total = rps.st_curr_before.len()
+            + rps.st_curr_after.len()
+            + rps.lt_curr.len()
+            + inter_layer[0].len()
+            + inter_layer[1].len();
+        if total == 0 {
+            return Err(HevcError::InvalidBitstream("no reference pictures for inter slice"));
+        }
+
+        let entry = |idx: usize, is_long_term: bool| {
+            let pic = &self.pictures[idx];
+            RefPic {
+                frame: &pic.frame,
+                motion: &pic.motion,
+                poc: pic.poc,
+                is_long_term,
+            }
+        };
+
+        let mut lists: RefPicLists<'_> = [Vec::new(), Vec::new()];
+        for (list, out) in lists.iter_mut().enumerate() {
+            let num_active = num_active[list];
+            if num_active == 0 {
+                continue;
+            }
+            let (first, second) = if list == 0 {
+                (&rps.st_curr_before, &rps.st_curr_after)
+            } else {
+                (&rps.st_curr_after, &rps.st_curr_before)
+            };
+            let cycle: Vec<RefPic<'_>> = first
+                .iter()
+                .map(|&i| entry(i, false))
+                .chain(inter_layer[list].iter().copied())
+                .chain(second.iter().map(|&i| entry(i, false)))
+                .chain(rps.lt_curr.iter().map(|&i| entry(i, true)))
+                .chain(inter_layer[1 - list].iter().copied())
+                .collect();
+            let num_temp = num_active.max(total);
+            let temp: Vec<RefPic<'_>> = cycle.iter().copied().cycle().take(num_temp).collect();
+
+            for r in 0..num_active {
+                let idx = list_entries[list].map_or(r, |e| e.get(r).copied().unwrap_or(0) as usize);
+                out.push(
+                    *temp
+                        .get(idx)
+                        .ok_or(HevcError::InvalidBitstream("list_entry out of range"))?,
+                );
+            }
+        }
+        Ok(lists)
+    }
+}
+
+/// Generate an unavailable reference picture (8.3.3.2): mid-grey samples
+fn generate_missing_picture(sps: &Sps) -> DecodedFrame {
+    let mut frame = DecodedFrame::with_params(
+        sps.pic_width_in_luma_samples,
+        sps.pic_height_in_luma_samples,
+        sps.bit_depth_y(),
+        sps.chroma_format_idc,
+    );
+    frame.y_plane.fill(1 << (sps.bit_depth_y() - 1));
+    frame.cb_plane.fill(1 << (sps.bit_depth_c() - 1));
+    frame.cr_plane.fill(1 << (sps.bit_depth_c() - 1));
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pic_order_cnt_wraps() {
+        // log2_max_poc_lsb = 4: MaxPicOrderCntLsb = 16
+        assert_eq!(pic_order_cnt(0, 5, 4, true), 5);
+        assert_eq!(pic_order_cnt(14, 1, 4, false), 17);
+        assert_eq!(pic_order_cnt(17, 15, 4, false), 15);
+        assert_eq!(pic_order_cnt(30, 2, 4, false), 34);
+        assert_eq!(pic_order_cnt(3, 14, 4, false), -2);
+    }
+
+    #[test]
+    fn test_ref_pic_list_construction() {
+        let mut dpb = DecodedPictureBuffer::new();
+        for poc in [0, 4, 8] {
+            dpb.insert(
+                DecodedFrame::with_params(8, 8, 8, 0),
+                MotionField::default(),
+                poc,
+                false,
+                &DpbOrdering::default(),
+                &mut Vec::new(),
+            );
+        }
+        let rps = RefPicSet {
+            st_curr_before: vec![1, 0],
+            st_curr_after: vec![2],
+            lt_curr: Vec::new(),
+        };
+        let lists = dpb.build_ref_pic_lists(&rps, [4, 2], [None, None], Default::default()).unwrap();
+        let pocs = |l: usize| lists[l].iter().map(|r| r.poc).collect::<Vec<_>>();
+        // L0 cycles before, after; L1 starts with after
+        assert_eq!(pocs(0), [4, 0, 8, 4]);
+        assert_eq!(pocs(1), [8, 4]);
+
+        let lists = dpb
+            .build_ref_pic_lists(&rps, [4, 2], [Some(&[2, 2, 0, 1]), None], Default::default())
+            .unwrap();
+        assert_eq!(lists[0].iter().map(|r| r.poc).collect::<Vec<_>>(), [8, 8, 4, 0]);
+    }
+
+    #[test]
+    fn test_bumping_outputs_in_poc_order() {
+        let ordering = DpbOrdering {
+            max_dec_pic_buffering_minus1: 3,
+            max_num_reorder_pics: 2,
+            max_latency_increase_plus1: 0,
+        };
+        let mut dpb = DecodedPictureBuffer::new();
+        let mut out = Vec::new();
+        let mut output_pocs = Vec::new();
+        for poc in [0, 4, 2, 1, 3] {
+            let mut frame = DecodedFrame::with_params(8, 8, 8, 0);
+            frame.width = poc as u32;
+            dpb.insert(frame, MotionField::default(), poc, true, &ordering, &mut out);
+            output_pocs.push(out.drain(..).map(|f| f.width).collect::<Vec<_>>());
+        }
+        // At most two pictures wait; the smallest POC goes first
+        assert_eq!(output_pocs, [vec![], vec![], vec![0], vec![1], vec![2]]);
+
+        // An IDR picture outputs the rest, unless told to discard them
+        dpb.prepare(&ordering, true, false, &mut out);
+        assert_eq!(out.iter().map(|f| f.width).collect::<Vec<_>>(), [3, 4]);
+        out.clear();
+        for poc in [0, 2] {
+            dpb.insert(DecodedFrame::with_params(8, 8, 8, 0), MotionField::default(), poc, true, &ordering, &mut out);
+        }
+        dpb.prepare(&ordering, true, true, &mut out);
+        assert!(out.is_empty());
+        assert!(dpb.last().is_none());
+    }
+
+    #[test]
+    fn test_inter_layer_ref_list_order() {
+        let mut dpb = DecodedPictureBuffer::new();
+        for poc in [0, 8] {
+            dpb.insert(
+                DecodedFrame::with_params(8, 8, 8, 0),
+                MotionField::default(),
+                poc,
+                false,
+                &DpbOrdering::default(),
+                &mut Vec::new(),
+            );
+        }
+        let base = DecodedFrame::with_params(8, 8, 8, 0);
+        let motion = MotionField::default();
+        let inter_layer = RefPic {
+            frame: &base,
+            motion: &motion,
+            poc: 4,
+            is_long_term: true,
+        };
+        let rps = RefPicSet {
+            st_curr_before: vec![0],
+            st_curr_after: vec![1],
+            lt_curr: Vec::new(),
+        };
+        let lists = dpb
+            .build_ref_pic_lists(&rps, [3, 3], [None, None], [vec![inter_layer], Vec::new()])
+            .unwrap();
+        let pocs = |l: usize| lists[l].iter().map(|r| r.poc).collect::<Vec<_>>();
+        // RefPicSetInterLayer0 follows StCurrBefore in L0 and ends L1
+        assert_eq!(pocs(0), [0, 4, 8]);
+        assert_eq!(pocs(1), [8, 0, 4]);
+        assert!(lists[0][1].is_long_term);
+    }
+}
@@ -0,0 +1,491 @@
+//! SIMD in-loop filter kernels for HEVC
+//!
+//! Deblocking filters the sample pairs across an edge row or column eight
+//! at a time; SAO filters eight samples of a line at a time. The kernels
+//! use AVX2 on x86_64 and NEON on aarch64, selected at runtime, and leave
+//! the samples past the last multiple of eight to the scalar code in
+//! `deblock` and `sao`, which they match bit for bit.
+//!
+//! Enabled only when the `unsafe-simd` feature is active.
+
+#[cfg(target_arch = "aarch64")]
+use core::arch::aarch64::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use super::simd::Isa;
+#[cfg(target_arch = "aarch64")]
+use super::simd::{load_u16x8_neon, store_u16x8_neon};
+#[cfg(target_arch = "x86_64")]
+use super::simd::{load_u16x8_avx2, store_u16x8_avx2};
+use super::{deblock, sao};
+
+/// Luma edge filter of [`deblock::luma_pairs`] with the kernels of `isa`
+pub(super) fn luma_pairs(isa: Isa, p: &mut [u16], q: &mut [u16], bs: &[u8], tc: i32) {
+    let len = p.len().min(q.len()).min(bs.len());
+    let simd = len / 8 * 8;
+    // SAFETY: the CPU supports `isa` and the kernel accesses `simd`
+    // samples of each slice
+    unsafe {
+        match isa {
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 | Isa::Avx512 => luma_pairs_avx2(p, q, bs, simd, tc),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => luma_pairs_neon(p, q, bs, simd, tc),
+        }
+    }
+    deblock::luma_pairs(&mut p[simd..len], &mut q[simd..len], &bs[simd..len], tc);
+}
+
+/// Chroma edge filter of [`deblock::chroma_pairs`] with the kernels of `isa`
+pub(super) fn chroma_pairs(isa: Isa, p: &mut [u16], q: &mut [u16], on: &[u8], tc: i32) {
+    let len = p.len().min(q.len()).min(on.len());
+    let simd = len / 8 * 8;
+    // SAFETY: the CPU supports `isa` and the kernel accesses `simd`
+    // samples of each slice
+    unsafe {
+        match isa {
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 | Isa::Avx512 => chroma_pairs_avx2(p, q, on, simd, tc),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => chroma_pairs_neon(p, q, on, simd, tc),
+        }
+    }
+    deblock::chroma_pairs(&mut p[simd..len], &mut q[simd..len], &on[simd..len], tc);
+}
+
+/// SAO band offset of [`sao::band_row`] with the kernels of `isa`
+pub(super) fn sao_band_row(isa: Isa, src: &[u16], dst: &mut [u16], band: &sao::BandOffset) {
+    let len = src.len().min(dst.len());
+    let simd = len / 8 * 8;
+    // SAFETY: the CPU supports `isa` and the kernel accesses `simd`
+    // samples of each slice
+    unsafe {
+        match isa {
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 | Isa::Avx512 => sao_band_avx2(src, dst, simd, band),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => sao_band_neon(src, dst, simd, band),
+        }
+    }
+    sao::band_row(&src[simd..len], &mut dst[simd..len], band);
+}
+
+/// SAO edge offset of [`sao::edge_row`] with the kernels of `isa`
+pub(super) fn sao_edge_row(
+    isa: Isa,
+    [src, n1, n2]: [&[u16]; 3],
+    dst: &mut [u16],
+    edge: &sao::EdgeOffset,
+) {
+    let len = src.len().min(n1.len()).min(n2.len()).min(dst.len());
+    let simd = len / 8 * 8;
+    // SAFETY: the CPU supports `isa` and the kernel accesses `simd`
+    // samples of each slice
+    unsafe {
+        match isa {
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 | Isa::Avx512 => sao_edge_avx2([src, n1, n2], dst, simd, edge),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => sao_edge_neon([src, n1, n2], dst, simd, edge),
+        }
+    }
+    let tail = [&src[simd..len], &n1[simd..len], &n2[simd..len]];
+    sao::edge_row(tail, &mut dst[simd..len], edge);
+}
+
+/// Luma edge filter with AVX2
+///
+/// The divisions of the scalar filter truncate towards zero; the kernel
+/// adds the divisor minus one to negative dividends before shifting.
+///
+/// # Safety
+/// The CPU must support AVX2 and all slices hold `len` samples, a multiple
+/// of 8.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn luma_pairs_avx2(p: &mut [u16], q: &mut [u16], bs: &[u8], len: usize, tc: i32) {
+    let (tc_min, tc_max) = (_mm256_set1_epi32(-tc), _mm256_set1_epi32(tc));
+    let (zero, max) = (_mm256_setzero_si256(), _mm256_set1_epi32(255));
+    let (two, nine, fifteen) = (_mm256_set1_epi32(2), _mm256_set1_epi32(9), _mm256_set1_epi32(15));
+    let clamp_tc = |v| _mm256_min_epi32(_mm256_max_epi32(v, tc_min), tc_max);
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (p0, q0, bs) = unsafe {
+            let bs = _mm_loadl_epi64(bs.as_ptr().add(i).cast());
+            (load_u16x8_avx2(p.as_ptr().add(i)), load_u16x8_avx2(q.as_ptr().add(i)), _mm256_cvtepu8_epi32(bs))
+        };
+        let diff = _mm256_sub_epi32(q0, p0);
+
+        // Strong: clamp(q0 - p0) / 2
+        let strong = clamp_tc(diff);
+        let strong = _mm256_srai_epi32::<1>(_mm256_add_epi32(strong, _mm256_srli_epi32::<31>(strong)));
+        // Weak: clamp((q0 - p0) * 9 / 16)
+        let weak = _mm256_mullo_epi32(diff, nine);
+        let weak = _mm256_add_epi32(weak, _mm256_and_si256(_mm256_srai_epi32::<31>(weak), fifteen));
+        let weak = clamp_tc(_mm256_srai_epi32::<4>(weak));
+
+        let delta = _mm256_blendv_epi8(weak, strong, _mm256_cmpeq_epi32(bs, two));
+        let unfiltered = _mm256_cmpeq_epi32(bs, zero);
+        let p1 = _mm256_min_epi32(_mm256_max_epi32(_mm256_add_epi32(p0, delta), zero), max);
+        let q1 = _mm256_min_epi32(_mm256_max_epi32(_mm256_sub_epi32(q0, delta), zero), max);
+        // SAFETY: the caller guarantees `len` samples
+        unsafe {
+            store_u16x8_avx2(p.as_mut_ptr().add(i), _mm256_blendv_epi8(p1, p0, unfiltered));
+            store_u16x8_avx2(q.as_mut_ptr().add(i), _mm256_blendv_epi8(q1, q0, unfiltered));
+        }
+    }
+}
+
+/// Chroma edge filter with AVX2
+///
+/// # Safety
+/// The CPU must support AVX2 and all slices hold `len` samples, a multiple
+/// of 8.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn chroma_pairs_avx2(p: &mut [u16], q: &mut [u16], on: &[u8], len: usize, tc: i32) {
+    let (tc_min, tc_max) = (_mm256_set1_epi32(-tc), _mm256_set1_epi32(tc));
+    let (zero, max) = (_mm256_setzero_si256(), _mm256_set1_epi32(255));
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (p0, q0, on) = unsafe {
+            let on = _mm_loadl_epi64(on.as_ptr().add(i).cast());
+            (load_u16x8_avx2(p.as_ptr().add(i)), load_u16x8_avx2(q.as_ptr().add(i)), _mm256_cvtepu8_epi32(on))
+        };
+
+        // clamp((q0 - p0) / 2)
+        let diff = _mm256_sub_epi32(q0, p0);
+        let delta = _mm256_srai_epi32::<1>(_mm256_add_epi32(diff, _mm256_srli_epi32::<31>(diff)));
+        let delta = _mm256_min_epi32(_mm256_max_epi32(delta, tc_min), tc_max);
+
+        let unfiltered = _mm256_cmpeq_epi32(on, zero);
+        let p1 = _mm256_min_epi32(_mm256_max_epi32(_mm256_add_epi32(p0, delta), zero), max);
+        let q1 = _mm256_min_epi32(_mm256_max_epi32(_mm256_sub_epi32(q0, delta), zero), max);
+        // SAFETY: the caller guarantees `len` samples
+        unsafe {
+            store_u16x8_avx2(p.as_mut_ptr().add(i), _mm256_blendv_epi8(p1, p0, unfiltered));
+            store_u16x8_avx2(q.as_mut_ptr().add(i), _mm256_blendv_epi8(q1, q0, unfiltered));
+        }
+    }
+}
+
+/// SAO band offset with AVX2: the offset of each band is looked up with a
+/// lane permute
+///
+/// # Safety
+/// The CPU must support AVX2 and both slices hold `len` samples, a
+/// multiple of 8.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn sao_band_avx2(src: &[u16], dst: &mut [u16], len: usize, band: &sao::BandOffset) {
+    let [o0, o1, o2, o3] = band.offsets;
+    let table = _mm256_setr_epi32(o0, o1, o2, o3, 0, 0, 0, 0);
+    let shift = _mm_cvtsi32_si128(band.shift as i32);
+    let position = _mm256_set1_epi32(band.position);
+    let (zero, four, max) = (_mm256_setzero_si256(), _mm256_set1_epi32(4), _mm256_set1_epi32(band.max));
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (sample, current) =
+            unsafe { (load_u16x8_avx2(src.as_ptr().add(i)), load_u16x8_avx2(dst.as_ptr().add(i))) };
+        let relative = _mm256_sub_epi32(_mm256_srl_epi32(sample, shift), position);
+        let in_bands = _mm256_andnot_si256(_mm256_cmpgt_epi32(zero, relative), _mm256_cmpgt_epi32(four, relative));
+        let offset = _mm256_permutevar8x32_epi32(table, relative);
+        let filtered = _mm256_min_epi32(_mm256_max_epi32(_mm256_add_epi32(sample, offset), zero), max);
+        // SAFETY: the caller guarantees `len` samples
+        unsafe { store_u16x8_avx2(dst.as_mut_ptr().add(i), _mm256_blendv_epi8(current, filtered, in_bands)) };
+    }
+}
+
+/// SAO edge offset with AVX2: the edge category indexes the offsets with a
+/// lane permute
+///
+/// # Safety
+/// The CPU must support AVX2 and all slices hold `len` samples, a multiple
+/// of 8.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn sao_edge_avx2([src, n1, n2]: [&[u16]; 3], dst: &mut [u16], len: usize, edge: &sao::EdgeOffset) {
+    let [o0, o1, o2, o3, o4] = edge.offsets;
+    let table = _mm256_setr_epi32(o0, o1, o2, o3, o4, 0, 0, 0);
+    let (zero, two, max) = (_mm256_setzero_si256(), _mm256_set1_epi32(2), _mm256_set1_epi32(edge.max));
+    let sign = |a, b| _mm256_sub_epi32(_mm256_cmpgt_epi32(b, a), _mm256_cmpgt_epi32(a, b));
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (sample, a, b, current) = unsafe {
+            (
+                load_u16x8_avx2(src.as_ptr().add(i)),
+                load_u16x8_avx2(n1.as_ptr().add(i)),
+                load_u16x8_avx2(n2.as_ptr().add(i)),
+                load_u16x8_avx2(dst.as_ptr().add(i)),
+            )
+        };
+        let category = _mm256_add_epi32(_mm256_add_epi32(sign(sample, a), sign(sample, b)), two);
+        let offset = _mm256_permutevar8x32_epi32(table, category);
+        let filtered = _mm256_min_epi32(_mm256_max_epi32(_mm256_add_epi32(sample, offset), zero), max);
+        let unchanged = _mm256_cmpeq_epi32(offset, zero);
+        // SAFETY: the caller guarantees `len` samples
+        unsafe { store_u16x8_avx2(dst.as_mut_ptr().add(i), _mm256_blendv_epi8(filtered, current, unchanged)) };
+    }
+}
+
+/// Load eight u8 flags as two vectors of four i32 lanes
+///
+/// # Safety
+/// The CPU must support NEON and `src` must point to eight flags.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn load_u8x8_neon(src: *const u8) -> [int32x4_t; 2] {
+    // SAFETY: the caller provides eight flags
+    let v = vmovl_u8(unsafe { vld1_u8(src) });
+    [
+        vreinterpretq_s32_u32(vmovl_u16(vget_low_u16(v))),
+        vreinterpretq_s32_u32(vmovl_u16(vget_high_u16(v))),
+    ]
+}
+
+/// `v / 2^SHIFT` rounded towards zero, like the scalar division
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+fn div_pow2_neon<const SHIFT: i32>(v: int32x4_t) -> int32x4_t {
+    let bias = vandq_s32(vshrq_n_s32::<31>(v), vdupq_n_s32((1 << SHIFT) - 1));
+    vshlq_s32(vaddq_s32(v, bias), vdupq_n_s32(-SHIFT))
+}
+
+/// Luma edge filter with NEON, see [`luma_pairs_avx2`]
+///
+/// # Safety
+/// The CPU must support NEON and all slices hold `len` samples, a multiple
+/// of 8.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn luma_pairs_neon(p: &mut [u16], q: &mut [u16], bs: &[u8], len: usize, tc: i32) {
+    let (tc_min, tc_max) = (vdupq_n_s32(-tc), vdupq_n_s32(tc));
+    let (zero, max) = (vdupq_n_s32(0), vdupq_n_s32(255));
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (p0, q0, bs) = unsafe {
+            (
+                load_u16x8_neon(p.as_ptr().add(i)),
+                load_u16x8_neon(q.as_ptr().add(i)),
+                load_u8x8_neon(bs.as_ptr().add(i)),
+            )
+        };
+        let mut p1 = p0;
+        let mut q1 = q0;
+        for half in 0..2 {
+            let diff = vsubq_s32(q0[half], p0[half]);
+            let strong = div_pow2_neon::<1>(vminq_s32(vmaxq_s32(diff, tc_min), tc_max));
+            let weak = div_pow2_neon::<4>(vmulq_n_s32(diff, 9));
+            let weak = vminq_s32(vmaxq_s32(weak, tc_min), tc_max);
+            let delta = vbslq_s32(vceqq_s32(bs[half], vdupq_n_s32(2)), strong, weak);
+            let filtered = vmvnq_u32(vceqq_s32(bs[half], zero));
+            let p = vminq_s32(vmaxq_s32(vaddq_s32(p0[half], delta), zero), max);
+            let q = vminq_s32(vmaxq_s32(vsubq_s32(q0[half], delta), zero), max);
+            p1[half] = vbslq_s32(filtered, p, p0[half]);
+            q1[half] = vbslq_s32(filtered, q, q0[half]);
+        }
+        // SAFETY: the caller guarantees `len` samples
+        unsafe {
+            store_u16x8_neon(p.as_mut_ptr().add(i), p1);
+            store_u16x8_neon(q.as_mut_ptr().add(i), q1);
+        }
+    }
+}
+
+/// Chroma edge filter with NEON
+///
+/// # Safety
+/// The CPU must support NEON and all slices hold `len` samples, a multiple
+/// of 8.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn chroma_pairs_neon(p: &mut [u16], q: &mut [u16], on: &[u8], len: usize, tc: i32) {
+    let (tc_min, tc_max) = (vdupq_n_s32(-tc), vdupq_n_s32(tc));
+    let (zero, max) = (vdupq_n_s32(0), vdupq_n_s32(255));
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (p0, q0, on) = unsafe {
+            (
+                load_u16x8_neon(p.as_ptr().add(i)),
+                load_u16x8_neon(q.as_ptr().add(i)),
+                load_u8x8_neon(on.as_ptr().add(i)),
+            )
+        };
+        let mut p1 = p0;
+        let mut q1 = q0;
+        for half in 0..2 {
+            let delta = div_pow2_neon::<1>(vsubq_s32(q0[half], p0[half]));
+            let delta = vminq_s32(vmaxq_s32(delta, tc_min), tc_max);
+            let filtered = vmvnq_u32(vceqq_s32(on[half], zero));
+            let p = vminq_s32(vmaxq_s32(vaddq_s32(p0[half], delta), zero), max);
+            let q = vminq_s32(vmaxq_s32(vsubq_s32(q0[half], delta), zero), max);
+            p1[half] = vbslq_s32(filtered, p, p0[half]);
+            q1[half] = vbslq_s32(filtered, q, q0[half]);
+        }
+        // SAFETY: the caller guarantees `len` samples
+        unsafe {
+            store_u16x8_neon(p.as_mut_ptr().add(i), p1);
+            store_u16x8_neon(q.as_mut_ptr().add(i), q1);
+        }
+    }
+}
+
+/// Offset of `table` selected by `index` in 0..5, 0 for other indices
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+fn lookup_neon(index: int32x4_t, table: &[i32]) -> int32x4_t {
+    let mut offset = vdupq_n_s32(0);
+    for (k, &value) in table.iter().enumerate() {
+        offset = vbslq_s32(vceqq_s32(index, vdupq_n_s32(k as i32)), vdupq_n_s32(value), offset);
+    }
+    offset
+}
+
+/// SAO band offset with NEON
+///
+/// # Safety
+/// The CPU must support NEON and both slices hold `len` samples, a
+/// multiple of 8.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn sao_band_neon(src: &[u16], dst: &mut [u16], len: usize, band: &sao::BandOffset) {
+    let shift = vdupq_n_s32(-(band.shift as i32));
+    let position = vdupq_n_s32(band.position);
+    let (zero, max) = (vdupq_n_s32(0), vdupq_n_s32(band.max));
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (sample, mut current) =
+            unsafe { (load_u16x8_neon(src.as_ptr().add(i)), load_u16x8_neon(dst.as_ptr().add(i))) };
+        for half in 0..2 {
+            let relative = vsubq_s32(vshlq_s32(sample[half], shift), position);
+            let in_bands = vandq_u32(vcgeq_s32(relative, zero), vcltq_s32(relative, vdupq_n_s32(4)));
+            let offset = lookup_neon(relative, &band.offsets);
+            let filtered = vminq_s32(vmaxq_s32(vaddq_s32(sample[half], offset), zero), max);
+            current[half] = vbslq_s32(in_bands, filtered, current[half]);
+        }
+        // SAFETY: the caller guarantees `len` samples
+        unsafe { store_u16x8_neon(dst.as_mut_ptr().add(i), current) };
+    }
+}
+
+/// SAO edge offset with NEON
+///
+/// # Safety
+/// The CPU must support NEON and all slices hold `len` samples, a multiple
+/// of 8.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn sao_edge_neon([src, n1, n2]: [&[u16]; 3], dst: &mut [u16], len: usize, edge: &sao::EdgeOffset) {
+    let (zero, max) = (vdupq_n_s32(0), vdupq_n_s32(edge.max));
+    let sign = |a: int32x4_t, b: int32x4_t| {
+        let greater = vreinterpretq_s32_u32(vcgtq_s32(a, b));
+        let less = vreinterpretq_s32_u32(vcltq_s32(a, b));
+        vsubq_s32(less, greater)
+    };
+
+    for i in (0..len).step_by(8) {
+        // SAFETY: the caller guarantees `len` samples
+        let (sample, a, b, mut current) = unsafe {
+            (
+                load_u16x8_neon(src.as_ptr().add(i)),
+                load_u16x8_neon(n1.as_ptr().add(i)),
+                load_u16x8_neon(n2.as_ptr().add(i)),
+                load_u16x8_neon(dst.as_ptr().add(i)),
+            )
+        };
+        for half in 0..2 {
+            let category = vaddq_s32(vaddq_s32(sign(sample[half], a[half]), sign(sample[half], b[half])), vdupq_n_s32(2));
+            let offset = lookup_neon(category, &edge.offsets);
+            let filtered = vminq_s32(vmaxq_s32(vaddq_s32(sample[half], offset), zero), max);
+            current[half] = vbslq_s32(vceqq_s32(offset, zero), current[half], filtered);
+        }
+        // SAFETY: the caller guarantees `len` samples
+        unsafe { store_u16x8_neon(dst.as_mut_ptr().add(i), current) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random values below `limit`
+    fn noise<T: TryFrom<u32>>(len: usize, limit: u32, seed: u32) -> Vec<T> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                T::try_from((state >> 8) % limit).ok().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_deblocking_simd_matches_scalar() {
+        for (seed, limit, tc) in [(1, 256, 0), (2, 256, 4), (3, 256, 13), (4, 256, 24), (5, 1024, 24)] {
+            let p: Vec<u16> = noise(253, limit, seed);
+            // Steps of up to 20 across the edge exercise the rounding
+            let steps: Vec<u16> = noise(253, 41, seed + 10);
+            let q: Vec<u16> = p.iter().zip(steps).map(|(&s, d)| (s + d).saturating_sub(20)).collect();
+            let bs: Vec<u8> = noise(253, 3, seed + 20);
+            for isa in Isa::supported() {
+                let (mut p_scalar, mut q_scalar) = (p.clone(), q.clone());
+                deblock::luma_pairs(&mut p_scalar, &mut q_scalar, &bs, tc);
+                let (mut p_simd, mut q_simd) = (p.clone(), q.clone());
+                luma_pairs(isa, &mut p_simd, &mut q_simd, &bs, tc);
+                assert_eq!((p_simd, q_simd), (p_scalar, q_scalar), "luma {isa:?} tc {tc}");
+
+                let (mut p_scalar, mut q_scalar) = (p.clone(), q.clone());
+                deblock::chroma_pairs(&mut p_scalar, &mut q_scalar, &bs, tc);
+                let (mut p_simd, mut q_simd) = (p.clone(), q.clone());
+                chroma_pairs(isa, &mut p_simd, &mut q_simd, &bs, tc);
+                assert_eq!((p_simd, q_simd), (p_scalar, q_scalar), "chroma {isa:?} tc {tc}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sao_simd_matches_scalar() {
+        for bit_depth in [8u8, 10, 12] {
+            let max = (1i32 << bit_depth) - 1;
+            let src: Vec<u16> = noise(75, 1 << bit_depth, bit_depth as u32);
+            // Neighbours close to the samples give every edge category
+            let near = |seed| -> Vec<u16> {
+                let steps: Vec<u16> = noise(75, 3, seed);
+                src.iter().zip(steps).map(|(&s, d)| (s + d).saturating_sub(1)).collect()
+            };
+            let (n1, n2) = (near(7), near(8));
+            let dst: Vec<u16> = noise(75, 1 << bit_depth, 99);
+
+            for isa in Isa::supported() {
+                for position in [0, 5, 29] {
+                    let band = sao::BandOffset {
+                        shift: bit_depth - 5,
+                        position,
+                        offsets: [7, -3, 0, -max],
+                        max,
+                    };
+                    let mut scalar = dst.clone();
+                    sao::band_row(&src, &mut scalar, &band);
+                    let mut simd = dst.clone();
+                    sao_band_row(isa, &src, &mut simd, &band);
+                    assert_eq!(simd, scalar, "band {isa:?} at {position}");
+                }
+
+                let edge = sao::EdgeOffset { offsets: [5, 2, 0, -2, -max], max };
+                let mut scalar = dst.clone();
+                sao::edge_row([&src, &n1, &n2], &mut scalar, &edge);
+                let mut simd = dst.clone();
+                sao_edge_row(isa, [&src, &n1, &n2], &mut simd, &edge);
+                assert_eq!(simd, scalar, "edge {isa:?}");
+            }
+        }
+    }
+}
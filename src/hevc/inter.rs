@@ -0,0 +1,610 @@
+//! Inter prediction (H.265 section 8.5.3)
+//!
+//! Motion data storage, fractional sample interpolation (8.5.3.3.3) and
+//! weighted sample prediction (8.5.3.3.4). Motion vector prediction lives
+//! in `mvpred`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::picture::DecodedFrame;
+use super::slice::PredWeightTable;
+
+/// Motion vector in quarter-sample units (luma)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Mv {
+    /// Horizontal component
+    pub x: i32,
+    /// Vertical component
+    pub y: i32,
+}
+
+impl Mv {
+    /// Create a motion vector
+    pub const fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+}
+
+/// Motion data of one prediction block
+///
+/// Lists that are not used have `ref_idx == -1` and a zero vector, so two
+/// blocks with the same motion compare equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PbMotion {
+    /// predFlagL0 / predFlagL1
+    pub pred_flag: [bool; 2],
+    /// refIdxL0 / refIdxL1 (-1 when the list is unused)
+    pub ref_idx: [i8; 2],
+    /// mvL0 / mvL1
+    pub mv: [Mv; 2],
+}
+
+impl Default for PbMotion {
+    fn default() -> Self {
+        Self {
+            pred_flag: [false; 2],
+            ref_idx: [-1; 2],
+            mv: [Mv::default(); 2],
+        }
+    }
+}
+
+impl PbMotion {
+    /// Motion using a single reference list
+    pub fn uni(list: usize, ref_idx: i8, mv: Mv) -> Self {
+        let mut motion = Self::default();
+        motion.set(list, ref_idx, mv);
+        motion
+    }
+
+    /// Enable prediction from `list`
+    pub fn set(&mut self, list: usize, ref_idx: i8, mv: Mv) {
+        self.pred_flag[list] = true;
+        self.ref_idx[list] = ref_idx;
+        self.mv[list] = mv;
+    }
+
+    /// Disable prediction from `list`
+    pub fn clear(&mut self, list: usize) {
+        self.pred_flag[list] = false;
+        self.ref_idx[list] = -1;
+        self.mv[list] = Mv::default();
+    }
+
+    /// True if the block is inter predicted
+    pub fn is_inter(&self) -> bool {
+        self.pred_flag[0] || self.pred_flag[1]
+    }
+}
+
+/// Identity of a reference picture as seen from a slice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefPicInfo {
+    /// PicOrderCntVal of the reference picture
+    pub poc: i32,
+    /// Marked as "used for long-term reference" when the slice was decoded
+    pub is_long_term: bool,
+}
+
+/// Slice index stored for blocks that have not been decoded
+const NOT_DECODED: u16 = u16::MAX;
+
+/// Per-picture motion field at 4x4 granularity
+///
+/// Kept with every reference picture for temporal motion vector prediction.
+/// Intra-only pictures never allocate the block arrays.
+#[derive(Debug, Clone, Default)]
+pub struct MotionField {
+    width_4x4: u32,
+    height_4x4: u32,
+    motion: Vec<PbMotion>,
+    /// Index of the slice that decoded each block (into `slice_refs`)
+    slice_idx: Vec<u16>,
+    /// Reference picture lists of every slice of the picture
+    slice_refs: Vec<[Vec<RefPicInfo>; 2]>,
+}
+
+impl MotionField {
+    /// Create an empty motion field for a picture of the given size
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width_4x4: width.div_ceil(4),
+            height_4x4: height.div_ceil(4),
+            ..Self::default()
+        }
+    }
+
+    /// Register a new slice and its reference lists, returning its index
+    pub fn begin_slice(&mut self, refs: [Vec<RefPicInfo>; 2]) -> u16 {
+        if self.motion.is_empty() {
+            let size = (self.width_4x4 * self.height_4x4) as usize;
+            self.motion = vec![PbMotion::default(); size];
+            self.slice_idx = vec![NOT_DECODED; size];
+        }
+        self.slice_refs.push(refs);
+        (self.slice_refs.len() - 1).min(NOT_DECODED as usize - 1) as u16
+    }
+
+    #[inline]
+    fn idx(&self, x: u32, y: u32) -> Option<usize> {
+        let (bx, by) = (x >> 2, y >> 2);
+        (bx < self.width_4x4 && by < self.height_4x4 && !self.motion.is_empty())
+            .then(|| (by * self.width_4x4 + bx) as usize)
+    }
+
+    /// Store motion for a w×h block decoded by slice `slice_idx`
+    ///
+    /// Also used for intra blocks (with default motion) so they count as
+    /// decoded for neighbour availability.
+    pub fn set(&mut self, x: u32, y: u32, w: u32, h: u32, slice_idx: u16, motion: PbMotion) {
+        if self.motion.is_empty() {
+            return;
+        }
+        let x_end = ((x + w) >> 2).min(self.width_4x4);
+        let y_end = ((y + h) >> 2).min(self.height_4x4);
+        for by in (y >> 2)..y_end {
+            let row = (by * self.width_4x4) as usize;
+            for bx in (x >> 2)..x_end {
+                self.motion[row + bx as usize] = motion;
+                self.slice_idx[row + bx as usize] = slice_idx;
+            }
+        }
+    }
+
+    /// Motion and slice index of the block covering (x, y), if decoded
+    pub fn get(&self, x: u32, y: u32) -> Option<(u16, &PbMotion)> {
+        let idx = self.idx(x, y)?;
+        let slice = self.slice_idx[idx];
+        (slice != NOT_DECODED).then(|| (slice, &self.motion[idx]))
+    }
+
+    /// Reference picture used by `ref_idx` of `list` in slice `slice_idx`
+    pub fn ref_info(&self, slice_idx: u16, list: usize, ref_idx: i8) -> Option<RefPicInfo> {
+        let refs = self.slice_refs.get(slice_idx as usize)?;
+        refs[list].get(usize::try_from(ref_idx).ok()?).copied()
+    }
+}
+
+/// Reference picture available to the current slice
+#[derive(Clone, Copy)]
+pub struct RefPic<'a> {
+    /// Decoded samples
+    pub frame: &'a DecodedFrame,
+    /// Motion field (for temporal prediction)
+    pub motion: &'a MotionField,
+    /// PicOrderCntVal
+    pub poc: i32,
+    /// Long-term reference marking
+    pub is_long_term: bool,
+}
+
+impl RefPic<'_> {
+    /// Identity of this picture for motion comparisons
+    pub fn info(&self) -> RefPicInfo {
+        RefPicInfo {
+            poc: self.poc,
+            is_long_term: self.is_long_term,
+        }
+    }
+}
+
+/// RefPicList0 and RefPicList1
+pub type RefPicLists<'a> = [Vec<RefPic<'a>>; 2];
+
+/// Luma interpolation filter coefficients for quarter positions (Table 8-11)
+const LUMA_FILTER: [[i32; 8]; 4] = [
+    [0, 0, 0, 64, 0, 0, 0, 0],
+    [-1, 4, -10, 58, 17, -5, 1, 0],
+    [-1, 4, -11, 40, 40, -11, 4, -1],
+    [0, 1, -5, 17, 58, -10, 4, -1],
+];
+
+/// Chroma interpolation filter coefficients for eighth positions (Table 8-12)
+const CHROMA_FILTER: [[i32; 4]; 8] = [
+    [0, 64, 0, 0],
+    [-2, 58, 10, -2],
+    [-4, 54, 16, -2],
+    [-6, 46, 28, -4],
+    [-4, 36, 36, -4],
+    [-4, 28, 46, -6],
+    [-2, 16, 54, -4],
+    [-2, 10, 58, -2],
+];
+
+/// Reference sample plane with picture-boundary padding
+struct RefPlane<'a> {
+    samples: &'a [u16],
+    stride: usize,
+    width: i32,
+    height: i32,
+}
+
+impl RefPlane<'_> {
+    #[inline]
+    fn at(&self, x: i32, y: i32) -> i32 {
+        let x = x.clamp(0, self.width - 1) as usize;
+        let y = y.clamp(0, self.height - 1) as usize;
+        self.samples[y * self.stride + x] as i32
+    }
+}
+
+/// Interpolate a w×h block at integer position (x_int, y_int) plus fraction
+///
+/// `taps` holds the filter for each fractional phase; output samples are at
+/// 14-bit intermediate precision (8.5.3.3.3.1 / 8.5.3.3.3.2).
+#[allow(clippy::too_many_arguments)]
+fn interpolate<const N: usize>(
+    plane: &RefPlane<'_>,
+    x_int: i32,
+    y_int: i32,
+    frac_x: usize,
+    frac_y: usize,
+    w: usize,
+    h: usize,
+    taps: &[[i32; N]],
+    bit_depth: u8,
+    out: &mut [i16],
+) {
+    let shift1 = (bit_depth as i32 - 8).min(4);
+    let shift3 = (14 - bit_depth as i32).max(2);
+    let half = (N / 2 - 1) as i32;
+    let fx = &taps[frac_x];
+    let fy = &taps[frac_y];
+
+    let filter_h = |x: i32, y: i32| -> i32 {
+        fx.iter()
+            .enumerate()
+            .map(|(i, c)| c * plane.at(x + i as i32 - half, y))
+            .sum()
+    };
+
+    match (frac_x, frac_y) {
+        (0, 0) => {
+            for j in 0..h {
+                for i in 0..w {
+                    out[j * w + i] = (plane.at(x_int + i as i32, y_int + j as i32) << shift3) as i16;
+                }
+            }
+        }
+        (_, 0) => {
+            for j in 0..h {
+                for i in 0..w {
+                    out[j * w + i] = (filter_h(x_int + i as i32, y_int + j as i32) >> shift1) as i16;
+                }
+            }
+        }
+        (0, _) => {
+            for j in 0..h {
+                for i in 0..w {
+                    let (x, y) = (x_int + i as i32, y_int + j as i32);
+                    let sum: i32 = fy
+                        .iter()
+                        .enumerate()
+                        .map(|(k, c)| c * plane.at(x, y + k as i32 - half))
+                        .sum();
+                    out[j * w + i] = (sum >> shift1) as i16;
+                }
+            }
+        }
+        _ => {
+            // Horizontal pass over the extra rows, then vertical over the result
+            let rows = h + N - 1;
+            let mut tmp = vec![0i32; rows * w];
+            for j in 0..rows {
+                for i in 0..w {
+                    tmp[j * w + i] =
+                        filter_h(x_int + i as i32, y_int + j as i32 - half) >> shift1;
+                }
+            }
+            for j in 0..h {
+                for i in 0..w {
+                    let sum: i32 = fy
+                        .iter()
+                        .enumerate()
+                        .map(|(k, c)| c * tmp[(j + k) * w + i])
+                        .sum();
+                    out[j * w + i] = (sum >> 6) as i16;
+                }
+            }
+        }
+    }
+}
+
+/// Explicit weighting parameters for one list (8.5.3.3.4.3)
+#[derive(Clone, Copy)]
+struct Weight {
+    w: i32,
+    o: i32,
+}
+
+/// Combine one or two interpolated blocks and write the result (8.5.3.3.4)
+#[allow(clippy::too_many_arguments)]
+fn write_weighted(
+    dst: &mut [u16],
+    stride: usize,
+    x0: usize,
+    y0: usize,
+    w: usize,
+    h: usize,
+    preds: [Option<&[i16]>; 2],
+    weights: Option<([Weight; 2], u8)>,
+    bit_depth: u8,
+) {
+    let max_val = (1i32 << bit_depth) - 1;
+    let shift1 = 14 - bit_depth as i32;
+
+    for j in 0..h {
+        let row = (y0 + j) * stride + x0;
+        for i in 0..w {
+            let k = j * w + i;
+            let value = match (preds, weights) {
+                ([Some(a), Some(b)], None) => {
+                    let shift2 = 15 - bit_depth as i32;
+                    (a[k] as i32 + b[k] as i32 + (1 << (shift2 - 1))) >> shift2
+                }
+                ([Some(a), Some(b)], Some(([w0, w1], denom))) => {
+                    let log2_wd = denom as i32 + shift1;
+                    (a[k] as i32 * w0.w + b[k] as i32 * w1.w + ((w0.o + w1.o + 1) << log2_wd))
+                        >> (log2_wd + 1)
+                }
+                ([Some(p), None], Some(([wt, _], denom))) | ([None, Some(p)], Some(([_, wt], denom))) => {
+                    let log2_wd = denom as i32 + shift1;
+                    if log2_wd >= 1 {
+                        ((p[k] as i32 * wt.w + (1 << (log2_wd - 1))) >> log2_wd) + wt.o
+                    } else {
+                        p[k] as i32 * wt.w + wt.o
+                    }
+                }
+                ([Some(p), None], None) | ([None, Some(p)], None) => {
+                    (p[k] as i32 + (1 << (shift1 - 1))) >> shift1
+                }
+                ([None, None], _) => 0,
+            };
+            dst[row + i] = value.clamp(0, max_val) as u16;
+        }
+    }
+}
+
+/// Decode inter prediction samples for one prediction block (8.5.3.3)
+///
+/// Writes luma and chroma prediction for the w×h luma block at (x0, y0)
+/// into `frame`. `weights` is the slice's explicit weight table, if any.
+#[allow(clippy::too_many_arguments)]
+pub fn predict_inter(
+    frame: &mut DecodedFrame,
+    refs: &RefPicLists<'_>,
+    motion: &PbMotion,
+    x0: u32,
+    y0: u32,
+    w: u32,
+    h: u32,
+    weights: Option<&PredWeightTable>,
+) {
+    let (x0, y0, w, h) = (x0 as i32, y0 as i32, w as usize, h as usize);
+
+    // Resolve the reference pictures; a missing entry drops that list
+    let mut ref_pics: [Option<&RefPic<'_>>; 2] = [None, None];
+    for list in 0..2 {
+        if motion.pred_flag[list] {
+            ref_pics[list] = usize::try_from(motion.ref_idx[list])
+                .ok()
+                .and_then(|idx| refs[list].get(idx));
+        }
+    }
+    if ref_pics.iter().all(Option::is_none) {
+        return;
+    }
+
+    let bit_depth = frame.bit_depth;
+    let list_weights = |c_idx: usize| -> Option<([Weight; 2], u8)> {
+        let table = weights?;
+        let mut out = [Weight { w: 1, o: 0 }; 2];
+        let denom = if c_idx == 0 {
+            table.luma_log2_weight_denom
+        } else {
+            table.chroma_log2_weight_denom
+        };
+        for list in 0..2 {
+            if ref_pics[list].is_none() {
+                continue;
+            }
+            let f = table.factors[list].get(motion.ref_idx[list] as usize)?;
+            let (wt, off) = match c_idx {
+                0 => (f.luma_weight, f.luma_offset),
+                c => (f.chroma_weight[c - 1], f.chroma_offset[c - 1]),
+            };
+            out[list] = Weight {
+                w: wt,
+                o: off << (bit_depth - 8),
+            };
+        }
+        Some((out, denom))
+    };
+
+    let mut pred = [vec![0i16; w * h], vec![0i16; w * h]];
+
+    // Luma
+    for list in 0..2 {
+        let Some(r) = ref_pics[list] else { continue };
+        let plane = RefPlane {
+            samples: &r.frame.y_plane,
+            stride: r.frame.y_stride(),
+            width: r.frame.width as i32,
+            height: r.frame.height as i32,
+        };
+        let mv = motion.mv[list];
+        interpolate(
+            &plane,
+            x0 + (mv.x >> 2),
+            y0 + (mv.y >> 2),
+            (mv.x & 3) as usize,
+            (mv.y & 3) as usize,
+            w,
+            h,
+            &LUMA_FILTER,
+            bit_depth,
+            &mut pred[list],
+        );
+    }
+    let stride = frame.y_stride();
+    write_weighted(
+        &mut frame.y_plane,
+        stride,
+        x0 as usize,
+        y0 as usize,
+        w,
+        h,
+        [
+            ref_pics[0].map(|_| &pred[0][..]),
+            ref_pics[1].map(|_| &pred[1][..]),
+        ],
+        list_weights(0),
+        bit_depth,
+    );
+
+    if frame.chroma_format == 0 {
+        return;
+    }
+
+    // Chroma (8.5.3.2.10: mvC in units of 1/8 chroma sample)
+    let (sub_w, sub_h) = frame.chroma_subsampling();
+    let (xc, yc) = (x0 / sub_w as i32, y0 / sub_h as i32);
+    let (wc, hc) = (w / sub_w as usize, h / sub_h as usize);
+    let c_stride = frame.c_stride();
+    let c_height = frame.height.div_ceil(sub_h) as i32;
+
+    for c_idx in 1..3 {
+        for list in 0..2 {
+            let Some(r) = ref_pics[list] else { continue };
+            let plane = RefPlane {
+                samples: if c_idx == 1 { &r.frame.cb_plane } else { &r.frame.cr_plane },
+                stride: c_stride,
+                width: r.frame.width.div_ceil(sub_w) as i32,
+                height: c_height,
+            };
+            let mv_c = Mv::new(
+                motion.mv[list].x * 2 / sub_w as i32,
+                motion.mv[list].y * 2 / sub_h as i32,
+            );
+            interpolate(
+                &plane,
+                xc + (mv_c.x >> 3),
+                yc + (mv_c.y >> 3),
+                (mv_c.x & 7) as usize,
+                (mv_c.y & 7) as usize,
+                wc,
+                hc,
+                &CHROMA_FILTER,
+                bit_depth,
+                &mut pred[list][..wc * hc],
+            );
+        }
+        let weights = list_weights(c_idx);
+        let dst = if c_idx == 1 { &mut frame.cb_plane } else { &mut frame.cr_plane };
+        write_weighted(
+            dst,
+            c_stride,
+            xc as usize,
+            yc as usize,
+            wc,
+            hc,
+            [
+                ref_pics[0].map(|_| &pred[0][..wc * hc]),
+                ref_pics[1].map(|_| &pred[1][..wc * hc]),
+            ],
+            weights,
+            bit_depth,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp_frame() -> DecodedFrame {
+        let mut frame = DecodedFrame::with_params(16, 16, 8, 1);
+        for y in 0..16 {
+            for x in 0..16 {
+                frame.set_y(x, y, (x * 8 + y) as u16);
+            }
+        }
+        for y in 0..8 {
+            for x in 0..8 {
+                frame.set_cb(x, y, 100 + x as u16);
+                frame.set_cr(x, y, 50 + y as u16);
+            }
+        }
+        frame
+    }
+
+    fn refs<'a>(frame: &'a DecodedFrame, motion: &'a MotionField) -> RefPicLists<'a> {
+        let pic = RefPic {
+            frame,
+            motion,
+            poc: 0,
+            is_long_term: false,
+        };
+        [vec![pic], vec![pic]]
+    }
+
+    #[test]
+    fn test_integer_mv_copies_reference() {
+        let reference = ramp_frame();
+        let motion = MotionField::default();
+        let lists = refs(&reference, &motion);
+
+        let mut frame = DecodedFrame::with_params(16, 16, 8, 1);
+        let pb = PbMotion::uni(0, 0, Mv::new(4 * 2, 4 * 1));
+        predict_inter(&mut frame, &lists, &pb, 4, 4, 8, 8, None);
+
+        assert_eq!(frame.get_y(4, 4), reference.get_y(6, 5));
+        assert_eq!(frame.get_y(11, 11), reference.get_y(13, 12));
+        // Chroma MV is half the luma MV for 4:2:0: (1, 0.5)
+        assert_eq!(frame.get_cb(2, 2), reference.get_cb(3, 2));
+        assert_eq!(frame.get_cr(2, 2), 53); // half-sample between 52 and 53 rounds up
+    }
+
+    #[test]
+    fn test_half_sample_on_linear_ramp() {
+        let reference = ramp_frame();
+        let motion = MotionField::default();
+        let lists = refs(&reference, &motion);
+
+        // Horizontal half-sample position away from the borders
+        let mut frame = DecodedFrame::with_params(16, 16, 8, 1);
+        let pb = PbMotion::uni(0, 0, Mv::new(2, 0));
+        predict_inter(&mut frame, &lists, &pb, 4, 4, 4, 4, None);
+        // Between 8*4+4 = 36 and 44 -> 40
+        assert_eq!(frame.get_y(4, 4), 40);
+    }
+
+    #[test]
+    fn test_bi_prediction_averages() {
+        let mut a = DecodedFrame::with_params(8, 8, 8, 0);
+        let mut b = DecodedFrame::with_params(8, 8, 8, 0);
+        a.y_plane.fill(10);
+        b.y_plane.fill(21);
+        let motion = MotionField::default();
+        let lists: RefPicLists<'_> = [
+            vec![RefPic { frame: &a, motion: &motion, poc: 0, is_long_term: false }],
+            vec![RefPic { frame: &b, motion: &motion, poc: 2, is_long_term: false }],
+        ];
+
+        let mut pb = PbMotion::uni(0, 0, Mv::default());
+        pb.set(1, 0, Mv::default());
+        let mut frame = DecodedFrame::with_params(8, 8, 8, 0);
+        predict_inter(&mut frame, &lists, &pb, 0, 0, 8, 8, None);
+        assert_eq!(frame.get_y(3, 3), 16); // (10 + 21 + 1) / 2
+    }
+
+    #[test]
+    fn test_explicit_weighting() {
+        let pred = [100i16 << 6; 4];
+        let mut dst = [0u16; 4];
+        let weights = [Weight { w: 32, o: 3 }, Weight { w: 1, o: 0 }];
+        write_weighted(&mut dst, 2, 0, 0, 2, 2, [Some(&pred), None], Some((weights, 5)), 8);
+        assert_eq!(dst, [103; 4]); // 100 * 32/32 + 3
+    }
+}
@@ -1,10 +1,13 @@
 //! Intra prediction for HEVC
 
+use alloc::vec::Vec;
+
 use super::picture::DecodedFrame;
 use super::slice::IntraPredMode;
 
 /// Tracks which samples in the frame have been reconstructed.
 /// Used for intra prediction reference sample availability (H.265 8.4.4.2.1).
+#[derive(Default)]
 pub(super) struct ReconstructionMap {
     luma: Vec<u8>,
     cb: Vec<u8>,
@@ -17,6 +20,14 @@ pub(super) struct ReconstructionMap {
 
 impl ReconstructionMap {
     pub(super) fn new(width: u32, height: u32) -> Self {
+        let mut map = Self::default();
+        map.reset(width, height);
+        map
+    }
+
+    /// Mark every sample of a `width` x `height` picture as not reconstructed,
+    /// reusing the map's allocations
+    pub(super) fn reset(&mut self, width: u32, height: u32) {
         let luma_bits = (width * height) as usize;
         let luma_bytes = luma_bits.div_ceil(8);
         let cw = width.div_ceil(2);
@@ -24,15 +35,18 @@ impl ReconstructionMap {
         let chroma_bits = (cw * ch) as usize;
         let chroma_bytes = chroma_bits.div_ceil(8);
 
-        Self {
-            luma: vec![0; luma_bytes],
-            cb: vec![0; chroma_bytes],
-            cr: vec![0; chroma_bytes],
-            width,
-            height,
-            chroma_width: cw,
-            chroma_height: ch,
+        for (map, bytes) in [
+            (&mut self.luma, luma_bytes),
+            (&mut self.cb, chroma_bytes),
+            (&mut self.cr, chroma_bytes),
+        ] {
+            map.clear();
+            map.resize(bytes, 0);
         }
+        self.width = width;
+        self.height = height;
+        self.chroma_width = cw;
+        self.chroma_height = ch;
     }
 
     pub(super) fn mark_reconstructed(&mut self, x: u32, y: u32, size: u32, c_idx: u8) {
@@ -110,31 +124,40 @@ fn get_inv_angle(mode: u8) -> i32 {
     }
 }
 
-/// Reference sample filtering (H.265 8.4.4.2.3)
-/// Applies 3-tap smoothing filter or strong intra smoothing to border samples
-/// BEFORE prediction. This is critical for correct intra prediction.
+/// Reference sample filter selected for a block (H.265 8.4.4.2.3)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum RefFilter {
+    /// Unfiltered reference samples
+    None,
+    /// 3-tap [1 2 1] smoothing
+    ThreeTap,
+    /// Strong intra smoothing: bilinear interpolation between the corners
+    Strong,
+}
+
+/// Select the filter applied to the border samples of a block
 ///
 /// Per libde265: only applied for luma in 4:2:0 mode (cIdx==0 || ChromaArrayType==444)
-fn filter_reference_samples(
-    border: &mut [i32],
+pub(super) fn reference_filter(
+    border: &[i32],
     center: usize,
     size: u32,
     mode: IntraPredMode,
     c_idx: u8,
     strong_intra_smoothing_enabled: bool,
     bit_depth: u8,
-) {
-    let n = size as i32;
+) -> RefFilter {
+    let n = size as usize;
     let mode_val = mode.as_u8() as i32;
 
     // No filtering for 4:2:0 chroma (only luma)
     if c_idx != 0 {
-        return;
+        return RefFilter::None;
     }
 
     // No filtering for DC mode or 4x4 blocks
     if mode == IntraPredMode::Dc || size == 4 {
-        return;
+        return RefFilter::None;
     }
 
     // Compute minimum distance to horizontal (mode 10) and vertical (mode 26)
@@ -149,74 +172,90 @@ fn filter_reference_samples(
     };
 
     if !filter_flag {
-        return;
+        return RefFilter::None;
     }
 
     // Check for strong intra smoothing (bilinear interpolation)
-    let bi_int_flag = strong_intra_smoothing_enabled
-        && c_idx == 0
-        && size == 32
-        && {
-            // Smoothness check: boundary samples should be approximately linear
-            let threshold = 1i32 << (bit_depth as i32 - 5);
-            let p0 = border[center]; // top-left corner
-            let p_top_end = border[center + 2 * n as usize]; // top-right corner (p[+64])
-            let p_top_mid = border[center + n as usize]; // top midpoint (p[+32])
-            let p_left_end = border[center - 2 * n as usize]; // bottom-left corner (p[-64])
-            let p_left_mid = border[center - n as usize]; // left midpoint (p[-32])
-
-            (p0 + p_top_end - 2 * p_top_mid).abs() < threshold
-                && (p0 + p_left_end - 2 * p_left_mid).abs() < threshold
-        };
-
-    // Allocate temporary filtered array
-    let total = 4 * size as usize + 1;
-    let f_center = 2 * size as usize;
-    let mut filtered = vec![0i32; total];
+    let bi_int_flag = strong_intra_smoothing_enabled && size == 32 && {
+        // Smoothness check: boundary samples should be approximately linear
+        let threshold = 1i32 << (bit_depth as i32 - 5);
+        let p0 = border[center]; // top-left corner
+        let p_top_end = border[center + 2 * n]; // top-right corner (p[+64])
+        let p_top_mid = border[center + n]; // top midpoint (p[+32])
+        let p_left_end = border[center - 2 * n]; // bottom-left corner (p[-64])
+        let p_left_mid = border[center - n]; // left midpoint (p[-32])
+
+        (p0 + p_top_end - 2 * p_top_mid).abs() < threshold
+            && (p0 + p_left_end - 2 * p_left_mid).abs() < threshold
+    };
 
     if bi_int_flag {
-        // Strong intra smoothing: bilinear interpolation from corners
-        // Only for size==32, so 2*nT = 64, shift = 6, rounding = 32
-        let p0 = border[center];
-        let p_top_end = border[center + 2 * n as usize];
-        let p_left_end = border[center - 2 * n as usize];
-        let two_n = 2 * n; // = 64 for size=32
-
-        filtered[f_center] = p0; // top-left corner preserved
-        filtered[0] = p_left_end; // bottom-left preserved
-        filtered[total - 1] = p_top_end; // top-right preserved
-
-        // libde265: pF[-i] = p[0] + ((i*(p[-64]-p[0])+32)>>6)
-        //           pF[+i] = p[0] + ((i*(p[+64]-p[0])+32)>>6)
-        for i in 1..(two_n as usize) {
-            // Left side: interpolate from p0 to p_left_end
-            filtered[f_center - i] = p0 + ((i as i32 * (p_left_end - p0) + (two_n / 2)) >> 6);
-            // Top side: interpolate from p0 to p_top_end
-            filtered[f_center + i] = p0 + ((i as i32 * (p_top_end - p0) + (two_n / 2)) >> 6);
-        }
+        RefFilter::Strong
     } else {
-        // Regular 3-tap filter: f[i] = (p[i-1] + 2*p[i] + p[i+1] + 2) >> 2
-        // Copy border to filtered first
-        for i in 0..total {
-            filtered[i] = border[center - 2 * n as usize + i];
-        }
-
-        // Note: SIMD 3-tap filter disabled - overhead too high for small filter sizes
+        RefFilter::ThreeTap
+    }
+}
 
-        // Scalar fallback - filter all interior samples
-        for i in 1..=(total - 2) {
-            filtered[i] = (filtered[i - 1] + 2 * filtered[i] + filtered[i + 1] + 2) >> 2;
+/// Reference sample filtering (H.265 8.4.4.2.3)
+/// Applies 3-tap smoothing filter or strong intra smoothing to border samples
+/// BEFORE prediction. This is critical for correct intra prediction.
+fn filter_reference_samples(
+    border: &mut [i32],
+    center: usize,
+    size: u32,
+    mode: IntraPredMode,
+    c_idx: u8,
+    strong_intra_smoothing_enabled: bool,
+    bit_depth: u8,
+) {
+    let filter = reference_filter(
+        border,
+        center,
+        size,
+        mode,
+        c_idx,
+        strong_intra_smoothing_enabled,
+        bit_depth,
+    );
+    match filter {
+        RefFilter::None => {}
+        RefFilter::ThreeTap => filter_3tap(border, center, size),
+        RefFilter::Strong => {
+            // Strong intra smoothing: bilinear interpolation from corners
+            // Only for size==32, so 2*nT = 64, shift = 6, rounding = 32
+            let two_n = 2 * size as usize;
+            let p0 = border[center]; // top-left corner preserved
+            let p_top_end = border[center + two_n]; // top-right preserved
+            let p_left_end = border[center - two_n]; // bottom-left preserved
+
+            // libde265: pF[-i] = p[0] + ((i*(p[-64]-p[0])+32)>>6)
+            //           pF[+i] = p[0] + ((i*(p[+64]-p[0])+32)>>6)
+            for i in 1..two_n {
+                // Left side: interpolate from p0 to p_left_end
+                border[center - i] = p0 + ((i as i32 * (p_left_end - p0) + 32) >> 6);
+                // Top side: interpolate from p0 to p_top_end
+                border[center + i] = p0 + ((i as i32 * (p_top_end - p0) + 32) >> 6);
+            }
         }
     }
+}
 
-    // Copy filtered samples back to border
-    for i in 0..total {
-        let border_idx = center - 2 * n as usize + i;
-        border[border_idx] = filtered[i];
+/// Regular 3-tap filter: f[i] = (p[i-1] + 2*p[i] + p[i+1] + 2) >> 2
+///
+/// The filter runs in place from the bottom-left sample to the top-right one,
+/// so each output feeds the next; it stays scalar in the SIMD path as well.
+pub(super) fn filter_3tap(border: &mut [i32], center: usize, size: u32) {
+    let two_n = 2 * size as usize;
+    let samples = &mut border[center - two_n..=center + two_n];
+    for i in 1..samples.len() - 1 {
+        samples[i] = (samples[i - 1] + 2 * samples[i] + samples[i + 1] + 2) >> 2;
     }
 }
 
 /// Perform intra prediction for a block
+///
+/// Uses the SIMD path when the CPU supports it, the scalar one otherwise.
+#[allow(clippy::too_many_arguments)]
 pub fn predict_intra(
     frame: &mut DecodedFrame,
     x: u32,
@@ -226,6 +265,49 @@ pub fn predict_intra(
     c_idx: u8, // 0=Y, 1=Cb, 2=Cr
     reco_map: &ReconstructionMap,
     strong_intra_smoothing_enabled: bool,
+) {
+    #[cfg(all(
+        feature = "unsafe-simd",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    if let Some(isa) = super::simd::Isa::detect() {
+        super::intra_simd::predict_intra(
+            isa,
+            frame,
+            x,
+            y,
+            log2_size,
+            mode,
+            c_idx,
+            reco_map,
+            strong_intra_smoothing_enabled,
+        );
+        return;
+    }
+
+    predict_intra_scalar(
+        frame,
+        x,
+        y,
+        log2_size,
+        mode,
+        c_idx,
+        reco_map,
+        strong_intra_smoothing_enabled,
+    );
+}
+
+/// Scalar intra prediction, writing each sample to the frame
+#[allow(clippy::too_many_arguments)]
+pub(super) fn predict_intra_scalar(
+    frame: &mut DecodedFrame,
+    x: u32,
+    y: u32,
+    log2_size: u8,
+    mode: IntraPredMode,
+    c_idx: u8, // 0=Y, 1=Cb, 2=Cr
+    reco_map: &ReconstructionMap,
+    strong_intra_smoothing_enabled: bool,
 ) {
     let size = 1u32 << log2_size;
 
@@ -236,17 +318,15 @@ pub fn predict_intra(
     fill_border_samples(frame, x, y, size, c_idx, &mut border, border_center, reco_map);
 
     // Apply reference sample filtering (H.265 8.4.4.2.3) BEFORE prediction
-    if !std::env::var("HEVC_NO_REF_FILTER").is_ok() {
-        filter_reference_samples(
-            &mut border,
-            border_center,
-            size,
-            mode,
-            c_idx,
-            strong_intra_smoothing_enabled,
-            frame.bit_depth as u8,
-        );
-    }
+    filter_reference_samples(
+        &mut border,
+        border_center,
+        size,
+        mode,
+        c_idx,
+        strong_intra_smoothing_enabled,
+        frame.bit_depth as u8,
+    );
 
     // Apply prediction based on mode
     match mode {
@@ -261,14 +341,13 @@ pub fn predict_intra(
             predict_angular(frame, x, y, size, c_idx, mode_val, &border, border_center);
         }
     }
-
 }
 
 /// Fill border samples from neighboring pixels using z-scan availability (H.265 8.4.4.2.1)
 ///
 /// Uses the ReconstructionMap to determine which reference samples have actually
 /// been decoded, rather than relying on pixel values as sentinels.
-fn fill_border_samples(
+pub(super) fn fill_border_samples(
     frame: &DecodedFrame,
     x: u32,
     y: u32,
@@ -285,6 +364,12 @@ fn fill_border_samples(
     };
 
     let default_val = 1i32 << (frame.bit_depth - 1);
+    let (plane, stride) = plane(frame, c_idx);
+    // Outside the plane, read like `get_sample`
+    let outside = if c_idx == 0 { 0 } else { 128 << (frame.bit_depth - 8) };
+    let sample = |sx: u32, sy: u32| -> i32 {
+        plane.get(sy as usize * stride + sx as usize).map_or(outside, |&v| v as i32)
+    };
 
     // Helper: check if a sample at (sx, sy) is available for reference
     let is_avail = |sx: u32, sy: u32| -> bool {
@@ -294,8 +379,8 @@ fn fill_border_samples(
     // Build availability + sample arrays per H.265 8.4.4.2.1
     // Total 4*size + 1 samples: 2*size left (bottom-left to top), corner, 2*size top (left to top-right)
     let total = 4 * size as usize + 1;
-    let mut avail = vec![false; total];
-    let mut samples = vec![0i32; total];
+    let mut avail = [false; 4 * MAX_INTRA_PRED_BLOCK_SIZE + 1];
+    let mut samples = [0i32; 4 * MAX_INTRA_PRED_BLOCK_SIZE + 1];
 
     // Index mapping: [0..2*size-1] = bottom-left to left, [2*size] = corner, [2*size+1..4*size] = top to top-right
     let corner_idx = 2 * size as usize;
@@ -305,14 +390,14 @@ fn fill_border_samples(
         let sy = y + 2 * size - 1 - i;
         if x > 0 && is_avail(x - 1, sy) {
             avail[i as usize] = true;
-            samples[i as usize] = get_sample(frame, x - 1, sy, c_idx) as i32;
+            samples[i as usize] = sample(x - 1, sy);
         }
     }
 
     // Top-left corner
     if x > 0 && y > 0 && is_avail(x - 1, y - 1) {
         avail[corner_idx] = true;
-        samples[corner_idx] = get_sample(frame, x - 1, y - 1, c_idx) as i32;
+        samples[corner_idx] = sample(x - 1, y - 1);
     }
 
     // Top and top-right samples
@@ -320,7 +405,7 @@ fn fill_border_samples(
         let sx = x + i;
         if y > 0 && is_avail(sx, y - 1) {
             avail[corner_idx + 1 + i as usize] = true;
-            samples[corner_idx + 1 + i as usize] = get_sample(frame, sx, y - 1, c_idx) as i32;
+            samples[corner_idx + 1 + i as usize] = sample(sx, y - 1);
         }
     }
 
@@ -406,6 +491,16 @@ fn reference_sample_substitution(border: &mut [i32], center: usize, size: usize)
     }
 }
 
+/// The plane of a colour component and its stride
+#[inline]
+pub(super) fn plane(frame: &DecodedFrame, c_idx: u8) -> (&[u16], usize) {
+    match c_idx {
+        0 => (&frame.y_plane, frame.width as usize),
+        1 => (&frame.cb_plane, frame.c_stride()),
+        _ => (&frame.cr_plane, frame.c_stride()),
+    }
+}
+
 /// Get a sample from the frame
 #[inline]
 fn get_sample(frame: &DecodedFrame, x: u32, y: u32, c_idx: u8) -> u16 {
@@ -428,12 +523,6 @@ fn set_sample(frame: &mut DecodedFrame, x: u32, y: u32, c_idx: u8, value: u16) {
     }
 }
 
-/// Public version of set_sample for SIMD code
-#[inline]
-pub(super) fn set_sample_public(frame: &mut DecodedFrame, x: u32, y: u32, c_idx: u8, value: u16) {
-    set_sample(frame, x, y, c_idx, value);
-}
-
 /// Planar prediction (mode 0) - H.265 8.4.4.2.4
 fn predict_planar(
     frame: &mut DecodedFrame,
@@ -469,7 +558,7 @@ pub(super) fn predict_planar_scalar(
     bit_depth: u8,
 ) {
     let n = size as i32;
-    let log2_size = (size as f32).log2() as u32;
+    let log2_size = size.trailing_zeros();
 
     for py in 0..size {
         for px in 0..size {
@@ -497,6 +586,19 @@ pub(super) fn predict_planar_scalar(
     }
 }
 
+/// Average of the top and left samples of a block
+pub(super) fn dc_value(border: &[i32], center: usize, size: u32) -> i32 {
+    let n = size as i32;
+    let log2_size = size.trailing_zeros();
+
+    let mut dc_val = 0i32;
+    for i in 0..size {
+        dc_val += border[center + 1 + i as usize]; // top
+        dc_val += border[center - 1 - i as usize]; // left
+    }
+    (dc_val + n) >> (log2_size + 1)
+}
+
 /// DC prediction (mode 1) - H.265 8.4.4.2.5
 fn predict_dc(
     frame: &mut DecodedFrame,
@@ -507,16 +609,7 @@ fn predict_dc(
     border: &[i32],
     center: usize,
 ) {
-    let n = size as i32;
-    let log2_size = (size as f32).log2() as u32;
-
-    // Calculate DC value as average of top and left samples
-    let mut dc_val = 0i32;
-    for i in 0..size {
-        dc_val += border[center + 1 + i as usize]; // top
-        dc_val += border[center - 1 - i as usize]; // left
-    }
-    dc_val = (dc_val + n) >> (log2_size + 1);
+    let dc_val = dc_value(border, center, size);
 
     let max_val = (1 << frame.bit_depth) - 1;
 
@@ -556,6 +649,59 @@ fn predict_dc(
     }
 }
 
+/// Reference samples of an angular mode (H.265 8.4.4.2.6)
+///
+/// `ref[REF_CENTER + i]` holds the main reference for `i` in `0..=2*nT`,
+/// extended to the left by the projected side samples for negative angles.
+/// Modes 2-17 use the left samples, mirrored, as main reference.
+pub(super) fn angular_reference(
+    border: &[i32],
+    center: usize,
+    size: u32,
+    mode: u8,
+) -> [i32; 4 * MAX_INTRA_PRED_BLOCK_SIZE + 1] {
+    let n = size as i32;
+    let intra_pred_angle = INTRA_PRED_ANGLE[mode as usize] as i32;
+    // Main reference runs along the top (+1) or the left (-1) of the border
+    let dir: i32 = if mode >= 18 { 1 } else { -1 };
+    let at = |i: i32| border[(center as i32 + dir * i) as usize];
+
+    let mut ref_arr = [0i32; 4 * MAX_INTRA_PRED_BLOCK_SIZE + 1];
+    let ref_center = REF_CENTER;
+
+    // Copy main samples to ref[0..nT]
+    for i in 0..=n {
+        ref_arr[ref_center + i as usize] = at(i);
+    }
+
+    if intra_pred_angle < 0 {
+        // Negative angle: extend reference to the left with side samples
+        let inv_angle = get_inv_angle(mode);
+        let ext = (n * intra_pred_angle) >> 5;
+
+        if ext < -1 {
+            for xx in ext..=-1 {
+                // Note: xx is negative, inv_angle is negative for modes 11-25
+                // So xx * inv_angle is positive, giving a positive idx
+                let idx = (xx * inv_angle + 128) >> 8;
+                if idx >= 0 && idx <= (2 * n) {
+                    ref_arr[(ref_center as i32 + xx) as usize] = at(-idx);
+                }
+            }
+        }
+    } else {
+        // Positive angle: extend reference to the right
+        for xx in (n + 1)..=(2 * n) {
+            ref_arr[ref_center + xx as usize] = at(xx);
+        }
+    }
+
+    ref_arr
+}
+
+/// Centre of the reference array of [`angular_reference`]
+pub(super) const REF_CENTER: usize = 2 * MAX_INTRA_PRED_BLOCK_SIZE;
+
 /// Angular prediction (modes 2-34) - H.265 8.4.4.2.6
 #[allow(clippy::too_many_arguments)]
 fn predict_angular(
@@ -572,8 +718,8 @@ fn predict_angular(
     let intra_pred_angle = INTRA_PRED_ANGLE[mode as usize] as i32;
 
     // Build reference array
-    let mut ref_arr = [0i32; 4 * MAX_INTRA_PRED_BLOCK_SIZE + 1];
-    let ref_center = 2 * MAX_INTRA_PRED_BLOCK_SIZE;
+    let ref_arr = angular_reference(border, center, size, mode);
+    let ref_center = REF_CENTER;
 
     let max_val = (1 << frame.bit_depth) - 1;
 
@@ -581,34 +727,6 @@ fn predict_angular(
         // Horizontal-ish modes (18-34)
         // Reference is top samples
 
-        // Copy top samples to ref[0..nT]
-        for i in 0..=n {
-            ref_arr[ref_center + i as usize] = border[center + i as usize];
-        }
-
-        if intra_pred_angle < 0 {
-            // Negative angle: need to extend reference to the left
-            let inv_angle = get_inv_angle(mode);
-            let ext = (n * intra_pred_angle) >> 5;
-
-            if ext < -1 {
-                for xx in ext..=-1 {
-                    // Note: xx is negative, inv_angle is negative for modes 19-25
-                    // So xx * inv_angle is positive, giving a positive idx
-                    let idx = (xx * inv_angle + 128) >> 8;
-                    if idx >= 0 && idx <= (2 * n) {
-                        ref_arr[(ref_center as i32 + xx) as usize] =
-                            border[(center as i32 - idx) as usize];
-                    }
-                }
-            }
-        } else {
-            // Positive angle: extend reference to the right
-            for xx in (n + 1)..=(2 * n) {
-                ref_arr[ref_center + xx as usize] = border[center + xx as usize];
-            }
-        }
-
         // Generate prediction
         for py in 0..n {
             for px in 0..n {
@@ -652,32 +770,6 @@ fn predict_angular(
         // Vertical-ish modes (2-17)
         // Reference is left samples (mirrored)
 
-        // Copy left samples (negated indices) to ref[0..nT]
-        for i in 0..=n {
-            ref_arr[ref_center + i as usize] = border[center - i as usize];
-        }
-
-        if intra_pred_angle < 0 {
-            // Negative angle: extend reference
-            let inv_angle = get_inv_angle(mode);
-            let ext = (n * intra_pred_angle) >> 5;
-
-            if ext < -1 {
-                for xx in ext..=-1 {
-                    let idx = (xx * inv_angle + 128) >> 8;
-                    if idx >= 0 && idx <= (2 * n) {
-                        ref_arr[(ref_center as i32 + xx) as usize] =
-                            border[(center as i32 + idx) as usize];
-                    }
-                }
-            }
-        } else {
-            // Positive angle: extend reference
-            for xx in (n + 1)..=(2 * n) {
-                ref_arr[ref_center + xx as usize] = border[center - xx as usize];
-            }
-        }
-
         // Generate prediction (transposed compared to mode >= 18)
         for py in 0..n {
             for px in 0..n {
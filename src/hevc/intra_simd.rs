@@ -1,462 +1,617 @@
-//! SIMD-optimized intra prediction for HEVC
+//! SIMD intra prediction for HEVC
 //!
-//! Provides AVX2/SSE2 implementations of critical intra prediction modes:
-//! - Reference sample 3-tap filtering
-//! - DC mode prediction
-//! - Planar mode prediction
-//! - Angular mode prediction (horizontal/vertical)
+//! Predicts a whole block into a buffer and stores it to the plane row by
+//! row, instead of writing every sample through the frame accessors. Planar
+//! and angular rows, and strong intra smoothing of the reference samples,
+//! run in vector registers:
+//! - x86_64: AVX2 (SSE4.1 for 4x4 blocks), AVX-512F for rows of 16 or more
+//! - aarch64: NEON
+//!
+//! DC blocks are plain row fills, and the 3-tap reference filter runs in
+//! place with each output feeding the next, so both share the scalar code.
+//! The instruction set is detected at runtime and the output is bit-exact
+//! with [`predict_intra_scalar`](super::intra::predict_intra_scalar).
+//!
+//! Enabled only when the `unsafe-simd` feature is active.
 
-#[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
+#[cfg(target_arch = "aarch64")]
+use core::arch::aarch64::*;
+#[cfg(target_arch = "x86_64")]
 use core::arch::x86_64::*;
 
+use super::intra::{
+    self, INTRA_PRED_ANGLE, MAX_INTRA_PRED_BLOCK_SIZE, REF_CENTER, ReconstructionMap, RefFilter,
+};
 use super::picture::DecodedFrame;
+#[cfg(target_arch = "x86_64")]
+use super::simd::store_u16x8_avx2;
+use super::simd::Isa;
+use super::slice::IntraPredMode;
+
+/// Perform intra prediction for a block with the kernels of `isa`
+///
+/// `isa` must be supported by the CPU, see [`Isa::detect`].
+#[allow(clippy::too_many_arguments)]
+pub(super) fn predict_intra(
+    isa: Isa,
+    frame: &mut DecodedFrame,
+    x: u32,
+    y: u32,
+    log2_size: u8,
+    mode: IntraPredMode,
+    c_idx: u8,
+    reco_map: &ReconstructionMap,
+    strong_intra_smoothing_enabled: bool,
+) {
+    let size = 1u32 << log2_size;
+
+    let mut border = [0i32; 4 * MAX_INTRA_PRED_BLOCK_SIZE + 1];
+    let center = 2 * MAX_INTRA_PRED_BLOCK_SIZE;
+    intra::fill_border_samples(frame, x, y, size, c_idx, &mut border, center, reco_map);
+
+    let filter = intra::reference_filter(
+        &border,
+        center,
+        size,
+        mode,
+        c_idx,
+        strong_intra_smoothing_enabled,
+        frame.bit_depth,
+    );
+    match filter {
+        RefFilter::None => {}
+        RefFilter::ThreeTap => intra::filter_3tap(&mut border, center, size),
+        RefFilter::Strong => strong_smoothing(isa, &mut border, center, size as usize),
+    }
 
-/// SIMD-optimized 3-tap reference sample filter
-/// Applies (p[i-1] + 2*p[i] + p[i+1] + 2) >> 2 to interior samples
-#[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
-#[target_feature(enable = "sse2")]
-pub unsafe fn filter_reference_3tap_sse2(samples: &mut [i32], start: usize, count: usize) {
-    if count < 8 {
-        // Fallback to scalar for small counts
-        filter_reference_3tap_scalar(samples, start, count);
-        return;
+    let block = Block {
+        isa,
+        border: &border,
+        center,
+        mode,
+        c_idx,
+        max: (1 << frame.bit_depth) - 1,
+    };
+    match size {
+        4 => store_block(frame, x, y, c_idx, block.predict::<4>().as_flattened(), 4),
+        8 => store_block(frame, x, y, c_idx, block.predict::<8>().as_flattened(), 8),
+        16 => store_block(frame, x, y, c_idx, block.predict::<16>().as_flattened(), 16),
+        32 => store_block(frame, x, y, c_idx, block.predict::<32>().as_flattened(), 32),
+        _ => store_block(frame, x, y, c_idx, block.predict::<64>().as_flattened(), 64),
     }
+}
 
-    // Process 4 samples at a time with SSE2
-    let mut i = start + 1;
-    let end = start + count - 1;
-
-    while i + 4 <= end {
-        // SAFETY: We're within bounds and using target_feature sse2
-        unsafe {
-            // Load 6 consecutive samples (for 4 output samples we need indices i-1 through i+4)
-            let p0 = _mm_loadu_si128(samples.as_ptr().add(i - 1) as *const __m128i);
-            let p1 = _mm_loadu_si128(samples.as_ptr().add(i) as *const __m128i);
-            let p2 = _mm_loadu_si128(samples.as_ptr().add(i + 1) as *const __m128i);
-
-            // Compute: (p[i-1] + 2*p[i] + p[i+1] + 2) >> 2
-            let two = _mm_set1_epi32(2);
-            let doubled = _mm_slli_epi32(p1, 1); // 2 * p[i]
-            let sum = _mm_add_epi32(_mm_add_epi32(p0, doubled), _mm_add_epi32(p2, two));
-            let result = _mm_srai_epi32(sum, 2);
-
-            _mm_storeu_si128(samples.as_mut_ptr().add(i) as *mut __m128i, result);
+/// Inputs of the prediction of one block
+struct Block<'a> {
+    isa: Isa,
+    /// Filtered reference samples
+    border: &'a [i32],
+    center: usize,
+    mode: IntraPredMode,
+    c_idx: u8,
+    /// Largest sample value
+    max: i32,
+}
+
+impl Block<'_> {
+    /// Predict the samples of an `N` x `N` block
+    fn predict<const N: usize>(&self) -> [[u16; N]; N] {
+        let mut block = [[0u16; N]; N];
+        match self.mode {
+            IntraPredMode::Planar => {
+                planar(
+                    self.isa,
+                    self.border,
+                    self.center,
+                    N,
+                    self.max,
+                    block.as_flattened_mut(),
+                );
+            }
+            IntraPredMode::Dc => self.predict_dc(&mut block),
+            _ => self.predict_angular(&mut block),
         }
-        i += 4;
+        block
     }
 
-    // Handle remaining samples with scalar
-    while i < end {
-        samples[i] = (samples[i - 1] + 2 * samples[i] + samples[i + 1] + 2) >> 2;
-        i += 1;
+    /// DC prediction (mode 1) - H.265 8.4.4.2.5
+    fn predict_dc<const N: usize>(&self, block: &mut [[u16; N]; N]) {
+        let (border, center) = (self.border, self.center);
+        let dc_val = intra::dc_value(border, center, N as u32);
+        block
+            .as_flattened_mut()
+            .fill(dc_val.clamp(0, self.max) as u16);
+
+        // Edge filtering for luma and small blocks
+        if self.c_idx == 0 && N < 32 {
+            let blend = |edge: i32| ((edge + 3 * dc_val + 2) >> 2).clamp(0, self.max) as u16;
+            let corner = (border[center - 1] + 2 * dc_val + border[center + 1] + 2) >> 2;
+            block[0][0] = corner.clamp(0, self.max) as u16;
+            for i in 1..N {
+                block[0][i] = blend(border[center + 1 + i]);
+                block[i][0] = blend(border[center - 1 - i]);
+            }
+        }
     }
-}
 
-/// Scalar fallback for 3-tap filter
-#[inline]
-fn filter_reference_3tap_scalar(samples: &mut [i32], start: usize, count: usize) {
-    for i in (start + 1)..(start + count - 1) {
-        samples[i] = (samples[i - 1] + 2 * samples[i] + samples[i + 1] + 2) >> 2;
+    /// Angular prediction (modes 2-34) - H.265 8.4.4.2.6
+    fn predict_angular<const N: usize>(&self, block: &mut [[u16; N]; N]) {
+        let (border, center) = (self.border, self.center);
+        let mode = self.mode.as_u8();
+        let angle = INTRA_PRED_ANGLE[mode as usize] as i32;
+        let reference = intra::angular_reference(border, center, N as u32, mode);
+        let edge = |main: i32, side: i32| {
+            (main + ((side - border[center]) >> 1)).clamp(0, self.max) as u16
+        };
+
+        angular_rows(
+            self.isa,
+            &reference,
+            N,
+            angle,
+            self.max,
+            block.as_flattened_mut(),
+        );
+        if mode >= 18 {
+            // Boundary filter for mode 26 (vertical)
+            if mode == 26 && self.c_idx == 0 && N < 32 {
+                for (py, row) in block.iter_mut().enumerate() {
+                    row[0] = edge(border[center + 1], border[center - 1 - py]);
+                }
+            }
+        } else {
+            // Modes 2-17 predict columns along the left reference; they were
+            // predicted as rows, so transpose
+            let rows = *block;
+            for (py, row) in block.iter_mut().enumerate() {
+                for (px, sample) in row.iter_mut().enumerate() {
+                    *sample = rows[px][py];
+                }
+            }
+
+            // Boundary filter for mode 10 (horizontal)
+            if mode == 10 && self.c_idx == 0 && N < 32 {
+                for px in 0..N {
+                    block[0][px] = edge(border[center - 1], border[center + 1 + px]);
+                }
+            }
+        }
     }
 }
 
-/// SIMD-optimized DC mode prediction - fills block with constant value
-#[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
-#[target_feature(enable = "avx2")]
-pub unsafe fn predict_dc_fill_avx2(
-    frame: &mut DecodedFrame,
-    x: u32,
-    y: u32,
-    size: u32,
-    c_idx: u8,
-    dc_val: u16,
-) {
-    let stride = if c_idx == 0 {
-        frame.width as usize
-    } else {
-        frame.width as usize / 2
+/// Copy an `n` x `n` block to the plane of component `c_idx` at (`x`, `y`)
+fn store_block(frame: &mut DecodedFrame, x: u32, y: u32, c_idx: u8, block: &[u16], n: usize) {
+    let stride = match c_idx {
+        0 => frame.width as usize,
+        _ => frame.c_stride(),
     };
-
     let plane = match c_idx {
         0 => &mut frame.y_plane,
         1 => &mut frame.cb_plane,
-        2 => &mut frame.cr_plane,
-        _ => return,
+        _ => &mut frame.cr_plane,
     };
 
-    let base_idx = (y as usize * stride) + x as usize;
-    // SAFETY: We're using AVX2 target_feature
-    let v_dc = unsafe { _mm256_set1_epi16(dc_val as i16) };
-
-    // Process rows
-    for row in 0..size as usize {
-        let row_start = base_idx + row * stride;
-        let mut col = 0;
-
-        // Process 16 pixels at a time with AVX2
-        while col + 16 <= size as usize {
-            // SAFETY: We're within bounds and using AVX2 target_feature
-            unsafe {
-                _mm256_storeu_si256(
-                    plane.as_mut_ptr().add(row_start + col) as *mut __m256i,
-                    v_dc,
-                );
-            }
-            col += 16;
+    for (py, row) in block.chunks_exact(n).enumerate() {
+        let start = (y as usize + py) * stride + x as usize;
+        if start >= plane.len() {
+            break;
         }
+        let end = (start + n).min(plane.len());
+        plane[start..end].copy_from_slice(&row[..end - start]);
+    }
+}
 
-        // Handle remaining pixels with scalar
-        for px in col..(size as usize) {
-            plane[row_start + px] = dc_val;
+/// Planar prediction of an `n` x `n` block into `out`
+fn planar(isa: Isa, border: &[i32], center: usize, n: usize, max: i32, out: &mut [u16]) {
+    assert!(out.len() >= n * n && center > n && border.len() > center + n + 1);
+    // SAFETY: the CPU supports `isa`, and the kernels access the border and
+    // `out` within the bounds checked above
+    unsafe {
+        match isa {
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx512 if n >= 16 => planar_avx512(border, center, n, max, out),
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 | Isa::Avx512 => planar_avx2(border, center, n, max, out),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => planar_neon(border, center, n, max, out),
         }
     }
 }
 
-/// SSE2 version for DC fill (for CPUs without AVX2)
-#[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
-#[target_feature(enable = "sse2")]
-pub unsafe fn predict_dc_fill_sse2(
-    frame: &mut DecodedFrame,
-    x: u32,
-    y: u32,
-    size: u32,
-    c_idx: u8,
-    dc_val: u16,
-) {
-    let stride = if c_idx == 0 {
-        frame.width as usize
-    } else {
-        frame.width as usize / 2
-    };
-
-    let plane = match c_idx {
-        0 => &mut frame.y_plane,
-        1 => &mut frame.cb_plane,
-        2 => &mut frame.cr_plane,
-        _ => return,
-    };
-
-    let base_idx = (y as usize * stride) + x as usize;
-    // SAFETY: We're using SSE2 target_feature
-    let v_dc = unsafe { _mm_set1_epi16(dc_val as i16) };
-
-    for row in 0..size as usize {
-        let row_start = base_idx + row * stride;
-        let mut col = 0;
-
-        // Process 8 pixels at a time with SSE2
-        while col + 8 <= size as usize {
-            // SAFETY: We're within bounds and using SSE2 target_feature
-            unsafe {
-                _mm_storeu_si128(
-                    plane.as_mut_ptr().add(row_start + col) as *mut __m128i,
-                    v_dc,
-                );
-            }
-            col += 8;
+/// Angular prediction of `n` rows of `n` samples along `reference` into `out`
+///
+/// Row `y` interpolates the reference at `(y + 1) * angle / 32` samples past
+/// its column; see [`intra::angular_reference`] for the layout.
+fn angular_rows(isa: Isa, reference: &[i32], n: usize, angle: i32, max: i32, out: &mut [u16]) {
+    assert!(out.len() >= n * n && n <= MAX_INTRA_PRED_BLOCK_SIZE);
+    assert!(reference.len() > REF_CENTER + 2 * n && REF_CENTER >= n);
+    // SAFETY: the CPU supports `isa`; with |angle| <= 32 the rows read
+    // `reference` within `REF_CENTER - n ..= REF_CENTER + 2n`, and the
+    // sample past the row only when it is interpolated, with |angle| < 32
+    unsafe {
+        match isa {
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx512 if n >= 16 => angular_avx512(reference, n, angle, max, out),
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 | Isa::Avx512 => angular_avx2(reference, n, angle, max, out),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => angular_neon(reference, n, angle, max, out),
         }
+    }
+}
 
-        // Handle remaining pixels
-        for px in col..(size as usize) {
-            plane[row_start + px] = dc_val;
+/// Strong intra smoothing of the `2n` left and top reference samples
+fn strong_smoothing(isa: Isa, border: &mut [i32], center: usize, n: usize) {
+    assert!(center >= 2 * n && border.len() > center + 2 * n && n.is_multiple_of(4));
+    // SAFETY: the CPU supports `isa`, and the kernels write the border
+    // within the bounds checked above
+    unsafe {
+        match isa {
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 | Isa::Avx512 => strong_smoothing_avx2(border, center, n),
+            #[cfg(target_arch = "aarch64")]
+            Isa::Neon => strong_smoothing_neon(border, center, n),
         }
     }
 }
 
-/// Public wrapper with runtime CPU detection
-#[inline]
-pub fn predict_dc_fill_optimized(
-    frame: &mut DecodedFrame,
-    x: u32,
-    y: u32,
-    size: u32,
-    c_idx: u8,
-    dc_val: u16,
-) {
-    #[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
-    {
-        if is_x86_feature_detected!("avx2") {
-            unsafe {
-                predict_dc_fill_avx2(frame, x, y, size, c_idx, dc_val);
+/// Row angle of an angular mode: offset of the first reference sample past
+/// `REF_CENTER` and interpolation weight of the next one
+#[inline(always)]
+fn row_position(y: usize, angle: i32) -> (usize, i32) {
+    let pos = (y as i32 + 1) * angle;
+    ((REF_CENTER as i32 + (pos >> 5) + 1) as usize, pos & 31)
+}
+
+/// Planar prediction with AVX2
+///
+/// Row `y` of column `x` is `base[x] + y * delta[x] + (n - 1 - x) * left[y]`
+/// before the rounding shift, with the top and top-right terms in `base`.
+///
+/// # Safety
+/// The CPU must support AVX2; `out` holds `n * n` samples and the border
+/// `n + 1` samples on both sides of `center`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn planar_avx2(border: &[i32], center: usize, n: usize, max: i32, out: &mut [u16]) {
+    let right = border[center + 1 + n];
+    let bottom = border[center - 1 - n];
+    let shift = n.trailing_zeros() as i32 + 1;
+    let top = border[center + 1..].as_ptr();
+    let out = out.as_mut_ptr();
+
+    // SAFETY: the caller guarantees the bounds of every access
+    unsafe {
+        if n == 4 {
+            let shift = _mm_cvtsi32_si128(shift);
+            let (zero, max) = (_mm_setzero_si128(), _mm_set1_epi32(max));
+            let top = _mm_loadu_si128(top.cast());
+            let wl = _mm_setr_epi32(3, 2, 1, 0);
+            let wr = _mm_setr_epi32(1, 2, 3, 4);
+            let delta = _mm_sub_epi32(_mm_set1_epi32(bottom), top);
+            let mut acc = _mm_add_epi32(
+                _mm_add_epi32(
+                    _mm_mullo_epi32(wr, _mm_set1_epi32(right)),
+                    _mm_mullo_epi32(top, _mm_set1_epi32(3)),
+                ),
+                _mm_set1_epi32(bottom + 4),
+            );
+            for y in 0..4 {
+                let left = _mm_set1_epi32(border[center - 1 - y]);
+                let v = _mm_sra_epi32(_mm_add_epi32(acc, _mm_mullo_epi32(wl, left)), shift);
+                let v = _mm_min_epi32(_mm_max_epi32(v, zero), max);
+                _mm_storel_epi64(out.add(y * 4).cast(), _mm_packus_epi32(v, v));
+                acc = _mm_add_epi32(acc, delta);
             }
             return;
         }
-        if is_x86_feature_detected!("sse2") {
-            unsafe {
-                predict_dc_fill_sse2(frame, x, y, size, c_idx, dc_val);
+
+        let shift = _mm_cvtsi32_si128(shift);
+        let (zero, max) = (_mm256_setzero_si256(), _mm256_set1_epi32(max));
+        let lanes = _mm256_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7);
+        let rounding = _mm256_set1_epi32(bottom + n as i32);
+        for x in (0..n).step_by(8) {
+            let xs = _mm256_add_epi32(lanes, _mm256_set1_epi32(x as i32));
+            let top = _mm256_loadu_si256(top.add(x).cast());
+            let wl = _mm256_sub_epi32(_mm256_set1_epi32(n as i32 - 1), xs);
+            let wr = _mm256_add_epi32(xs, _mm256_set1_epi32(1));
+            let delta = _mm256_sub_epi32(_mm256_set1_epi32(bottom), top);
+            let mut acc = _mm256_add_epi32(
+                _mm256_add_epi32(
+                    _mm256_mullo_epi32(wr, _mm256_set1_epi32(right)),
+                    _mm256_mullo_epi32(top, _mm256_set1_epi32(n as i32 - 1)),
+                ),
+                rounding,
+            );
+            for y in 0..n {
+                let left = _mm256_set1_epi32(border[center - 1 - y]);
+                let v =
+                    _mm256_sra_epi32(_mm256_add_epi32(acc, _mm256_mullo_epi32(wl, left)), shift);
+                let v = _mm256_min_epi32(_mm256_max_epi32(v, zero), max);
+                store_u16x8_avx2(out.add(y * n + x), v);
+                acc = _mm256_add_epi32(acc, delta);
             }
-            return;
         }
     }
-
-    // Scalar fallback
-    predict_dc_fill_scalar(frame, x, y, size, c_idx, dc_val);
 }
 
-/// Scalar fallback for DC fill
-fn predict_dc_fill_scalar(
-    frame: &mut DecodedFrame,
-    x: u32,
-    y: u32,
-    size: u32,
-    c_idx: u8,
-    dc_val: u16,
-) {
-    for py in 0..size {
-        for px in 0..size {
-            super::intra::set_sample_public(frame, x + px, y + py, c_idx, dc_val);
+/// Planar prediction with AVX-512F, see [`planar_avx2`]
+///
+/// # Safety
+/// The CPU must support AVX-512F; `n` is a multiple of 16, `out` holds
+/// `n * n` samples and the border `n + 1` samples on both sides of `center`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+unsafe fn planar_avx512(border: &[i32], center: usize, n: usize, max: i32, out: &mut [u16]) {
+    let right = border[center + 1 + n];
+    let bottom = border[center - 1 - n];
+    let shift = _mm_cvtsi32_si128(n.trailing_zeros() as i32 + 1);
+    let top = border[center + 1..].as_ptr();
+    let out = out.as_mut_ptr();
+
+    let (zero, max) = (_mm512_setzero_si512(), _mm512_set1_epi32(max));
+    let lanes = _mm512_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+    let rounding = _mm512_set1_epi32(bottom + n as i32);
+    // SAFETY: the caller guarantees the bounds of every access
+    unsafe {
+        for x in (0..n).step_by(16) {
+            let xs = _mm512_add_epi32(lanes, _mm512_set1_epi32(x as i32));
+            let top = _mm512_loadu_si512(top.add(x).cast());
+            let wl = _mm512_sub_epi32(_mm512_set1_epi32(n as i32 - 1), xs);
+            let wr = _mm512_add_epi32(xs, _mm512_set1_epi32(1));
+            let delta = _mm512_sub_epi32(_mm512_set1_epi32(bottom), top);
+            let mut acc = _mm512_add_epi32(
+                _mm512_add_epi32(
+                    _mm512_mullo_epi32(wr, _mm512_set1_epi32(right)),
+                    _mm512_mullo_epi32(top, _mm512_set1_epi32(n as i32 - 1)),
+                ),
+                rounding,
+            );
+            for y in 0..n {
+                let left = _mm512_set1_epi32(border[center - 1 - y]);
+                let v =
+                    _mm512_sra_epi32(_mm512_add_epi32(acc, _mm512_mullo_epi32(wl, left)), shift);
+                let v = _mm512_min_epi32(_mm512_max_epi32(v, zero), max);
+                _mm256_storeu_si256(out.add(y * n + x).cast(), _mm512_cvtepi32_epi16(v));
+                acc = _mm512_add_epi32(acc, delta);
+            }
         }
     }
 }
 
-/// SIMD-optimized planar prediction using AVX2
-#[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
+/// Angular rows with AVX2, see [`angular_rows`]
+///
+/// # Safety
+/// The CPU must support AVX2 and the bounds of [`angular_rows`] hold.
+#[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "avx2")]
-pub unsafe fn predict_planar_avx2(
-    frame: &mut DecodedFrame,
-    x: u32,
-    y: u32,
-    size: u32,
-    c_idx: u8,
-    border: &[i32],
-    center: usize,
-    bit_depth: u8,
-) {
-    let n = size as i32;
-    let log2_size = (size as f32).log2() as u32;
-    let shift = log2_size + 1;
-    let max_val = (1 << bit_depth) - 1;
-
-    let stride = if c_idx == 0 {
-        frame.width as usize
-    } else {
-        frame.width as usize / 2
-    };
-
-    let plane = match c_idx {
-        0 => &mut frame.y_plane,
-        1 => &mut frame.cb_plane,
-        2 => &mut frame.cr_plane,
-        _ => return,
-    };
-
-    let base_idx = (y as usize * stride) + x as usize;
-    let right = border[center + 1 + size as usize]; // border[nT+1]
-    let bottom = border[center - 1 - size as usize]; // border[-1-nT]
-    // SAFETY: We're using AVX2 target_feature
-    let (v_n, v_max, v_zero) = unsafe {
-        (
-            _mm256_set1_epi32(n),
-            _mm256_set1_epi32(max_val),
-            _mm256_setzero_si256(),
-        )
-    };
-
-    // For each row
-    for py in 0..size {
-        let py_i = py as i32;
-        let left = border[center - 1 - py as usize];
-        let top_coef = n - 1 - py_i;
-        let bottom_coef = py_i + 1;
-
-        // SAFETY: We're using AVX2 target_feature
-        let (v_left, v_right, v_top_coef, v_bottom, v_bottom_coef) = unsafe {
-            (
-                _mm256_set1_epi32(left),
-                _mm256_set1_epi32(right),
-                _mm256_set1_epi32(top_coef),
-                _mm256_set1_epi32(bottom),
-                _mm256_set1_epi32(bottom_coef),
-            )
-        };
-
-        let row_start = base_idx + py as usize * stride;
-        let mut px = 0;
-
-        // Process 8 pixels at a time
-        while px + 8 <= size {
-            // Load top border values for this row
-            let mut top_vals = [0i32; 8];
-            for i in 0..8 {
-                top_vals[i] = border[center + 1 + px as usize + i];
-            }
-
-            // SAFETY: We're using AVX2 target_feature and within bounds
-            let final_vals = unsafe {
-                // Build px vector: [px, px+1, px+2, ..., px+7]
-                let px_vals = _mm256_setr_epi32(
-                    px as i32,
-                    px as i32 + 1,
-                    px as i32 + 2,
-                    px as i32 + 3,
-                    px as i32 + 4,
-                    px as i32 + 5,
-                    px as i32 + 6,
-                    px as i32 + 7,
-                );
-
-                let v_top = _mm256_loadu_si256(top_vals.as_ptr() as *const __m256i);
-
-                // Compute (n - 1 - px)
-                let v_n_minus_1 = _mm256_sub_epi32(v_n, _mm256_set1_epi32(1));
-                let left_coef = _mm256_sub_epi32(v_n_minus_1, px_vals);
-
-                // Compute (px + 1)
-                let right_coef = _mm256_add_epi32(px_vals, _mm256_set1_epi32(1));
-
-                // Planar formula:
-                // pred = ((n-1-x)*left + (x+1)*right + (n-1-y)*top + (y+1)*bottom + n) >> (log2+1)
-                let term1 = _mm256_mullo_epi32(left_coef, v_left);
-                let term2 = _mm256_mullo_epi32(right_coef, v_right);
-                let term3 = _mm256_mullo_epi32(v_top_coef, v_top);
-                let term4 = _mm256_mullo_epi32(v_bottom_coef, v_bottom);
-
-                let sum = _mm256_add_epi32(
-                    _mm256_add_epi32(term1, term2),
-                    _mm256_add_epi32(_mm256_add_epi32(term3, term4), v_n),
-                );
-
-                // Shift (variable shift, need to extract and shift manually)
-                let mut results = [0i32; 8];
-                _mm256_storeu_si256(results.as_mut_ptr() as *mut __m256i, sum);
-                for val in results.iter_mut() {
-                    *val >>= shift;
+unsafe fn angular_avx2(reference: &[i32], n: usize, angle: i32, max: i32, out: &mut [u16]) {
+    let reference = reference.as_ptr();
+    let out = out.as_mut_ptr();
+
+    // SAFETY: the caller guarantees the bounds of every access
+    unsafe {
+        if n == 4 {
+            let (zero, max) = (_mm_setzero_si128(), _mm_set1_epi32(max));
+            for y in 0..4 {
+                let (start, fact) = row_position(y, angle);
+                let mut v = _mm_loadu_si128(reference.add(start).cast());
+                if fact != 0 {
+                    let next = _mm_loadu_si128(reference.add(start + 1).cast());
+                    let sum = _mm_add_epi32(
+                        _mm_mullo_epi32(v, _mm_set1_epi32(32 - fact)),
+                        _mm_mullo_epi32(next, _mm_set1_epi32(fact)),
+                    );
+                    v = _mm_srai_epi32::<5>(_mm_add_epi32(sum, _mm_set1_epi32(16)));
                 }
-
-                // Clamp to [0, max_val]
-                let v_pred = _mm256_loadu_si256(results.as_ptr() as *const __m256i);
-                let v_clamped_low = _mm256_max_epi32(v_pred, v_zero);
-                let v_clamped = _mm256_min_epi32(v_clamped_low, v_max);
-
-                // Convert to final values
-                let mut fvals = [0i32; 8];
-                _mm256_storeu_si256(fvals.as_mut_ptr() as *mut __m256i, v_clamped);
-                fvals
-            };
-
-            // Store final values
-            for i in 0..8 {
-                plane[row_start + px as usize + i] = final_vals[i] as u16;
+                let v = _mm_min_epi32(_mm_max_epi32(v, zero), max);
+                _mm_storel_epi64(out.add(y * 4).cast(), _mm_packus_epi32(v, v));
             }
-
-            px += 8;
+            return;
         }
 
-        // Handle remaining pixels with scalar
-        for px_rem in px..size {
-            let px_i = px_rem as i32;
-            let top = border[center + 1 + px_rem as usize];
-            let pred = ((n - 1 - px_i) * left
-                + (px_i + 1) * right
-                + top_coef * top
-                + bottom_coef * bottom
-                + n)
-                >> shift;
-            plane[row_start + px_rem as usize] = pred.clamp(0, max_val) as u16;
+        let (zero, max) = (_mm256_setzero_si256(), _mm256_set1_epi32(max));
+        let rounding = _mm256_set1_epi32(16);
+        for y in 0..n {
+            let (start, fact) = row_position(y, angle);
+            let (w0, w1) = (_mm256_set1_epi32(32 - fact), _mm256_set1_epi32(fact));
+            for x in (0..n).step_by(8) {
+                let mut v = _mm256_loadu_si256(reference.add(start + x).cast());
+                if fact != 0 {
+                    let next = _mm256_loadu_si256(reference.add(start + x + 1).cast());
+                    let sum =
+                        _mm256_add_epi32(_mm256_mullo_epi32(v, w0), _mm256_mullo_epi32(next, w1));
+                    v = _mm256_srai_epi32::<5>(_mm256_add_epi32(sum, rounding));
+                }
+                let v = _mm256_min_epi32(_mm256_max_epi32(v, zero), max);
+                store_u16x8_avx2(out.add(y * n + x), v);
+            }
         }
     }
 }
 
-/// Public wrapper for planar with runtime CPU detection
-#[inline]
-pub fn predict_planar_optimized(
-    frame: &mut DecodedFrame,
-    x: u32,
-    y: u32,
-    size: u32,
-    c_idx: u8,
-    border: &[i32],
-    center: usize,
-    bit_depth: u8,
-) {
-    #[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
-    {
-        if is_x86_feature_detected!("avx2") {
-            unsafe {
-                predict_planar_avx2(frame, x, y, size, c_idx, border, center, bit_depth);
+/// Angular rows with AVX-512F, see [`angular_rows`]
+///
+/// # Safety
+/// The CPU must support AVX-512F, `n` is a multiple of 16 and the bounds of
+/// [`angular_rows`] hold.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+unsafe fn angular_avx512(reference: &[i32], n: usize, angle: i32, max: i32, out: &mut [u16]) {
+    let reference = reference.as_ptr();
+    let out = out.as_mut_ptr();
+
+    let (zero, max) = (_mm512_setzero_si512(), _mm512_set1_epi32(max));
+    let rounding = _mm512_set1_epi32(16);
+    // SAFETY: the caller guarantees the bounds of every access
+    unsafe {
+        for y in 0..n {
+            let (start, fact) = row_position(y, angle);
+            let (w0, w1) = (_mm512_set1_epi32(32 - fact), _mm512_set1_epi32(fact));
+            for x in (0..n).step_by(16) {
+                let mut v = _mm512_loadu_si512(reference.add(start + x).cast());
+                if fact != 0 {
+                    let next = _mm512_loadu_si512(reference.add(start + x + 1).cast());
+                    let sum =
+                        _mm512_add_epi32(_mm512_mullo_epi32(v, w0), _mm512_mullo_epi32(next, w1));
+                    v = _mm512_srai_epi32::<5>(_mm512_add_epi32(sum, rounding));
+                }
+                let v = _mm512_min_epi32(_mm512_max_epi32(v, zero), max);
+                _mm256_storeu_si256(out.add(y * n + x).cast(), _mm512_cvtepi32_epi16(v));
             }
-            return;
         }
     }
-
-    // Fallback to scalar implementation in intra.rs
-    super::intra::predict_planar_scalar(frame, x, y, size, c_idx, border, center, bit_depth);
 }
 
-/// SIMD-optimized angular prediction for horizontal/vertical modes
-/// Optimizes the inner loop interpolation: ((32 - fact) * ref[i] + fact * ref[i+1] + 16) >> 5
-#[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
+/// Strong intra smoothing with AVX2
+///
+/// Sample `i` away from the corner on either side becomes
+/// `p0 + ((i * (end - p0) + 32) >> 6)`; for `i = 0` and `i = 2n` this is the
+/// corner itself, so eight-sample chunks cover the sides without a tail.
+///
+/// # Safety
+/// The CPU must support AVX2, `n` is a multiple of 4 and the border holds
+/// `2n` samples on both sides of `center`.
+#[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "avx2")]
-pub unsafe fn angular_interpolate_row_avx2(
-    output: &mut [u16],
-    ref_arr: &[i32],
-    ref_offset: usize,
-    i_idx: i32,
-    i_fact: i32,
-    count: usize,
-    max_val: i32,
-) {
-    if i_fact == 0 {
-        // No interpolation needed - direct copy
-        for i in 0..count {
-            let val = ref_arr[ref_offset + i_idx as usize + 1 + i];
-            output[i] = val.clamp(0, max_val) as u16;
+unsafe fn strong_smoothing_avx2(border: &mut [i32], center: usize, n: usize) {
+    let two_n = 2 * n as i32;
+    let shift = _mm_cvtsi32_si128(two_n.trailing_zeros() as i32);
+    let p0 = border[center];
+    let top_step = _mm256_set1_epi32(border[center + 2 * n] - p0);
+    let left_step = _mm256_set1_epi32(border[center - 2 * n] - p0);
+    let (p0, rounding) = (_mm256_set1_epi32(p0), _mm256_set1_epi32(two_n / 2));
+    let lanes = _mm256_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7);
+    let border = border.as_mut_ptr();
+
+    // SAFETY: the caller guarantees the bounds of every access
+    unsafe {
+        for j in (0..2 * n).step_by(8) {
+            let j_vec = _mm256_add_epi32(lanes, _mm256_set1_epi32(j as i32));
+            // Top: border[center + i] for i = j..j + 8
+            let top = _mm256_mullo_epi32(j_vec, top_step);
+            let top =
+                _mm256_add_epi32(p0, _mm256_sra_epi32(_mm256_add_epi32(top, rounding), shift));
+            _mm256_storeu_si256(border.add(center + j).cast(), top);
+            // Left: border[center - 2n + j] for i = 2n - j
+            let i_vec = _mm256_sub_epi32(_mm256_set1_epi32(two_n), j_vec);
+            let left = _mm256_mullo_epi32(i_vec, left_step);
+            let left = _mm256_add_epi32(
+                p0,
+                _mm256_sra_epi32(_mm256_add_epi32(left, rounding), shift),
+            );
+            _mm256_storeu_si256(border.add(center - 2 * n + j).cast(), left);
         }
-        return;
     }
+}
 
-    // SAFETY: We're using AVX2 target_feature
-    let (v_fact, v_32_minus_fact, v_16, v_max, v_zero) = unsafe {
-        (
-            _mm256_set1_epi32(i_fact),
-            _mm256_set1_epi32(32 - i_fact),
-            _mm256_set1_epi32(16),
-            _mm256_set1_epi32(max_val),
-            _mm256_setzero_si256(),
-        )
-    };
-
-    let mut px = 0;
-
-    // Process 8 pixels at a time
-    while px + 8 <= count {
-        let base_idx = (ref_offset as i32 + i_idx + 1 + px as i32) as usize;
-
-        // SAFETY: We're using AVX2 target_feature and within bounds
-        let results = unsafe {
-            // Load ref_arr[idx] and ref_arr[idx+1] for 8 consecutive pixels
-            let v_ref0 = _mm256_loadu_si256(ref_arr.as_ptr().add(base_idx) as *const __m256i);
-            let v_ref1 = _mm256_loadu_si256(ref_arr.as_ptr().add(base_idx + 1) as *const __m256i);
-
-            // Compute: ((32 - fact) * ref[i] + fact * ref[i+1] + 16) >> 5
-            let term1 = _mm256_mullo_epi32(v_32_minus_fact, v_ref0);
-            let term2 = _mm256_mullo_epi32(v_fact, v_ref1);
-            let sum = _mm256_add_epi32(_mm256_add_epi32(term1, term2), v_16);
-            let shifted = _mm256_srai_epi32(sum, 5);
-
-            // Clamp to [0, max_val]
-            let v_clamped_low = _mm256_max_epi32(shifted, v_zero);
-            let v_clamped = _mm256_min_epi32(v_clamped_low, v_max);
-
-            // Extract results
-            let mut res = [0i32; 8];
-            _mm256_storeu_si256(res.as_mut_ptr() as *mut __m256i, v_clamped);
-            res
-        };
+/// Clamp four i32 lanes to `0..=max`, narrow them to u16 and store them
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn store_u16x4_neon(dst: *mut u16, v: int32x4_t, max: int32x4_t) {
+    let v = vminq_s32(vmaxq_s32(v, vdupq_n_s32(0)), max);
+    // SAFETY: the caller provides room for four samples
+    unsafe { vst1_u16(dst, vqmovun_s32(v)) };
+}
 
-        // Store as u16
-        for i in 0..8 {
-            output[px + i] = results[i] as u16;
+/// Planar prediction with NEON, see [`planar_avx2`]
+///
+/// # Safety
+/// The CPU must support NEON; `out` holds `n * n` samples and the border
+/// `n + 1` samples on both sides of `center`.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn planar_neon(border: &[i32], center: usize, n: usize, max: i32, out: &mut [u16]) {
+    let right = border[center + 1 + n];
+    let bottom = border[center - 1 - n];
+    let shift = vdupq_n_s32(-(n.trailing_zeros() as i32 + 1));
+    let top = border[center + 1..].as_ptr();
+    let out = out.as_mut_ptr();
+
+    let max = vdupq_n_s32(max);
+    let lanes: [i32; 4] = [0, 1, 2, 3];
+    // SAFETY: the caller guarantees the bounds of every access
+    unsafe {
+        let lanes = vld1q_s32(lanes.as_ptr());
+        for x in (0..n).step_by(4) {
+            let xs = vaddq_s32(lanes, vdupq_n_s32(x as i32));
+            let top = vld1q_s32(top.add(x));
+            let wl = vsubq_s32(vdupq_n_s32(n as i32 - 1), xs);
+            let wr = vaddq_s32(xs, vdupq_n_s32(1));
+            let delta = vsubq_s32(vdupq_n_s32(bottom), top);
+            let acc = vmlaq_s32(vdupq_n_s32(bottom + n as i32), wr, vdupq_n_s32(right));
+            let mut acc = vmlaq_s32(acc, top, vdupq_n_s32(n as i32 - 1));
+            for y in 0..n {
+                let v = vmlaq_s32(acc, wl, vdupq_n_s32(border[center - 1 - y]));
+                store_u16x4_neon(out.add(y * n + x), vshlq_s32(v, shift), max);
+                acc = vaddq_s32(acc, delta);
+            }
         }
+    }
+}
 
-        px += 8;
+/// Angular rows with NEON, see [`angular_rows`]
+///
+/// # Safety
+/// The CPU must support NEON and the bounds of [`angular_rows`] hold.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn angular_neon(reference: &[i32], n: usize, angle: i32, max: i32, out: &mut [u16]) {
+    let reference = reference.as_ptr();
+    let out = out.as_mut_ptr();
+
+    let max = vdupq_n_s32(max);
+    // SAFETY: the caller guarantees the bounds of every access
+    unsafe {
+        for y in 0..n {
+            let (start, fact) = row_position(y, angle);
+            let (w0, w1) = (vdupq_n_s32(32 - fact), vdupq_n_s32(fact));
+            for x in (0..n).step_by(4) {
+                let mut v = vld1q_s32(reference.add(start + x));
+                if fact != 0 {
+                    let next = vld1q_s32(reference.add(start + x + 1));
+                    let sum = vmlaq_s32(vmulq_s32(v, w0), next, w1);
+                    v = vshrq_n_s32::<5>(vaddq_s32(sum, vdupq_n_s32(16)));
+                }
+                store_u16x4_neon(out.add(y * n + x), v, max);
+            }
+        }
     }
+}
 
-    // Handle remaining pixels
-    for i in px..count {
-        let idx = (ref_offset as i32 + i_idx + 1 + i as i32) as usize;
-        let pred = ((32 - i_fact) * ref_arr[idx] + i_fact * ref_arr[idx + 1] + 16) >> 5;
-        output[i] = pred.clamp(0, max_val) as u16;
+/// Strong intra smoothing with NEON, see [`strong_smoothing_avx2`]
+///
+/// # Safety
+/// The CPU must support NEON, `n` is a multiple of 4 and the border holds
+/// `2n` samples on both sides of `center`.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn strong_smoothing_neon(border: &mut [i32], center: usize, n: usize) {
+    let two_n = 2 * n as i32;
+    let shift = vdupq_n_s32(-(two_n.trailing_zeros() as i32));
+    let p0 = border[center];
+    let top_step = vdupq_n_s32(border[center + 2 * n] - p0);
+    let left_step = vdupq_n_s32(border[center - 2 * n] - p0);
+    let (p0, rounding) = (vdupq_n_s32(p0), vdupq_n_s32(two_n / 2));
+    let lanes: [i32; 4] = [0, 1, 2, 3];
+    let border = border.as_mut_ptr();
+
+    // SAFETY: the caller guarantees the bounds of every access
+    unsafe {
+        let lanes = vld1q_s32(lanes.as_ptr());
+        for j in (0..2 * n).step_by(4) {
+            let j_vec = vaddq_s32(lanes, vdupq_n_s32(j as i32));
+            // Top: border[center + i] for i = j..j + 4
+            let top = vmlaq_s32(rounding, j_vec, top_step);
+            vst1q_s32(border.add(center + j), vaddq_s32(p0, vshlq_s32(top, shift)));
+            // Left: border[center - 2n + j] for i = 2n - j
+            let i_vec = vsubq_s32(vdupq_n_s32(two_n), j_vec);
+            let left = vmlaq_s32(rounding, i_vec, left_step);
+            vst1q_s32(
+                border.add(center - 2 * n + j),
+                vaddq_s32(p0, vshlq_s32(left, shift)),
+            );
+        }
     }
 }
 
@@ -464,33 +619,115 @@ pub unsafe fn angular_interpolate_row_avx2(
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_filter_3tap_scalar() {
-        let mut samples = [10, 20, 30, 40, 50];
-        filter_reference_3tap_scalar(&mut samples, 0, 5);
-        // samples[1] = (10 + 2*20 + 30 + 2) >> 2 = (10 + 40 + 30 + 2) >> 2 = 82 >> 2 = 20
-        // samples[2] = (20 + 2*30 + 40 + 2) >> 2 = (20 + 60 + 40 + 2) >> 2 = 122 >> 2 = 30
-        // samples[3] = (30 + 2*40 + 50 + 2) >> 2 = (30 + 80 + 50 + 2) >> 2 = 162 >> 2 = 40
-        // Endpoints preserved
-        assert_eq!(samples[0], 10);
-        assert_eq!(samples[4], 50);
+    /// A 96x96 4:2:0 frame of pseudo-random samples
+    fn noise_frame(bit_depth: u8, seed: u32) -> DecodedFrame {
+        let mut frame = DecodedFrame::with_params(96, 96, bit_depth, 1);
+        let mut state = seed;
+        let planes = [&mut frame.y_plane, &mut frame.cb_plane, &mut frame.cr_plane];
+        for sample in planes.into_iter().flat_map(|plane| plane.iter_mut()) {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            *sample = ((state >> 8) % (1 << bit_depth)) as u16;
+        }
+        frame
     }
 
-    #[test]
-    #[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
-    fn test_filter_3tap_sse2() {
-        if !is_x86_feature_detected!("sse2") {
-            return;
+    /// Reconstruction map with the blocks above and left of (`x`, `y`) done,
+    /// including the top-right and `below_left` rows of the bottom-left
+    fn reco_map_before(x: u32, y: u32, size: u32, below_left: u32, c_idx: u8) -> ReconstructionMap {
+        let scale = if c_idx == 0 { 1 } else { 2 };
+        let mut map = ReconstructionMap::new(96, 96);
+        for py in 0..96 / scale {
+            for px in 0..96 / scale {
+                if py < y || (px < x && py < y + size + below_left) {
+                    map.mark_reconstructed(px, py, 1, c_idx);
+                }
+            }
         }
+        map
+    }
 
-        let mut samples_scalar = [10, 20, 30, 40, 50, 60, 70, 80, 90, 100];
-        let mut samples_simd = samples_scalar.clone();
+    /// Predict a block at (`x`, `y`) in every mode with each supported
+    /// instruction set and compare with the scalar prediction
+    fn assert_matches_scalar(
+        frame: &DecodedFrame,
+        (x, y): (u32, u32),
+        log2_size: u8,
+        c_idx: u8,
+        reco_map: &ReconstructionMap,
+        strong: bool,
+    ) {
+        for mode in 0..35 {
+            let mode = IntraPredMode::from_u8(mode).unwrap();
+            let mut expected = frame.clone();
+            intra::predict_intra_scalar(
+                &mut expected,
+                x,
+                y,
+                log2_size,
+                mode,
+                c_idx,
+                reco_map,
+                strong,
+            );
+            for isa in Isa::supported() {
+                let mut actual = frame.clone();
+                predict_intra(
+                    isa,
+                    &mut actual,
+                    x,
+                    y,
+                    log2_size,
+                    mode,
+                    c_idx,
+                    reco_map,
+                    strong,
+                );
+                assert!(
+                    actual.y_plane == expected.y_plane
+                        && actual.cb_plane == expected.cb_plane
+                        && actual.cr_plane == expected.cr_plane,
+                    "{isa:?} differs from scalar for {log2_size}, {mode:?}, {c_idx} at {x}, {y}",
+                );
+            }
+        }
+    }
 
-        filter_reference_3tap_scalar(&mut samples_scalar, 0, 10);
-        unsafe {
-            filter_reference_3tap_sse2(&mut samples_simd, 0, 10);
+    #[test]
+    fn test_intra_simd_matches_scalar() {
+        for bit_depth in [8, 10, 12] {
+            let frame = noise_frame(bit_depth, bit_depth as u32);
+            for log2_size in 2..=5 {
+                let size = 1u32 << log2_size;
+                for c_idx in 0..3 {
+                    // Chroma blocks are at most 16x16 in 4:2:0
+                    if c_idx > 0 && log2_size > 4 {
+                        continue;
+                    }
+                    for (x, y) in [(0, 0), (size, 0), (0, size), (16, 16)] {
+                        let reco_map = reco_map_before(x, y, size, size / 2, c_idx);
+                        assert_matches_scalar(&frame, (x, y), log2_size, c_idx, &reco_map, true);
+                    }
+                }
+            }
         }
+    }
 
-        assert_eq!(samples_scalar, samples_simd);
+    #[test]
+    fn test_intra_simd_strong_smoothing_matches_scalar() {
+        // A smooth ramp selects strong intra smoothing for 32x32 luma blocks
+        let mut frame = DecodedFrame::with_params(96, 96, 10, 1);
+        for (idx, sample) in frame.y_plane.iter_mut().enumerate() {
+            let (px, py) = (idx % 96, idx / 96);
+            *sample = (200 + 3 * px + 2 * py) as u16;
+        }
+        let reco_map = reco_map_before(32, 32, 32, 32, 0);
+        let mut border = [0i32; 4 * MAX_INTRA_PRED_BLOCK_SIZE + 1];
+        let center = 2 * MAX_INTRA_PRED_BLOCK_SIZE;
+        intra::fill_border_samples(&frame, 32, 32, 32, 0, &mut border, center, &reco_map);
+        let filter =
+            intra::reference_filter(&border, center, 32, IntraPredMode::Planar, 0, true, 10);
+        assert_eq!(filter, RefFilter::Strong);
+
+        assert_matches_scalar(&frame, (32, 32), 5, 0, &reco_map, true);
     }
 }
@@ -6,33 +6,99 @@
 pub mod bitstream;
 mod cabac;
 pub mod colorspace;
+#[cfg(all(
+    feature = "unsafe-simd",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod colorspace_simd;
+mod context;
 mod ctu;
+#[cfg(feature = "std")]
 pub mod debug;
 mod deblock;
+mod dpb;
+#[cfg(all(
+    feature = "unsafe-simd",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod filter_simd;
+mod inter;
 mod intra;
-// mod intra_simd; // Disabled - SIMD overhead too high for typical HEVC block sizes (4x4 to 32x32)
+#[cfg(all(
+    feature = "unsafe-simd",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod intra_simd;
+mod mvpred;
 pub mod params;
 mod picture;
 mod residual;
+mod sao;
+pub mod sei;
+#[cfg(all(
+    feature = "unsafe-simd",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod simd;
 pub mod slice;
+#[cfg(test)]
+pub(crate) mod test_util;
 mod transform;
 mod transform_simd;
 
+pub use context::DecoderContext;
+pub(crate) use context::ContextPool;
 pub use picture::DecodedFrame;
 
-use crate::error::HevcError;
+use crate::error::{DamagedRegion, ErrorKind, HeicError, HevcError};
 use crate::heif::HevcDecoderConfig;
+use crate::limits::DecoderLimits;
+use crate::options::{DecoderOptions, Strictness};
+use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 
-use ctu::{SaoComponentParams, SaoEoClass, SaoParams, SaoType};
-
 type Result<T> = core::result::Result<T, HevcError>;
 
+/// Reconstruction quality
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Full, standard-conforming reconstruction
+    #[default]
+    Full,
+    /// Fast approximate reconstruction for previews
+    ///
+    /// The bitstream is still fully parsed, but intra blocks are predicted
+    /// with DC prediction only, residuals keep only their DC coefficient and
+    /// the deblocking and SAO filters are skipped.
+    Draft,
+}
+
 /// Decode HEVC bitstream to pixels (Annex B or raw format)
 pub fn decode(data: &[u8]) -> Result<DecodedFrame> {
-    // Parse NAL units
-    let nal_units = bitstream::parse_nal_units(data)?;
-    decode_nal_units(&nal_units)
+    decode_with_mode(data, DecodeMode::Full, &DecoderLimits::default())
+}
+
+/// Decode HEVC bitstream to pixels (Annex B or raw format) in the given
+/// mode, rejecting pictures that exceed `limits`
+pub fn decode_with_mode(data: &[u8], mode: DecodeMode, limits: &DecoderLimits) -> Result<DecodedFrame> {
+    decode_with_options(data, mode, &DecoderOptions::new().limits(*limits))
+}
+
+/// Decode HEVC bitstream to pixels (Annex B or raw format) in the given
+/// mode with the given options
+///
+/// With [`Strictness::Lenient`](crate::Strictness::Lenient), a CTU that
+/// fails to decode ends its slice, or its WPP substream, and the CTBs not
+/// decoded are filled from their neighbours and listed in the frame's
+/// `damage`. Errors are then only returned when no picture could be
+/// started at all or a limit is exceeded.
+pub fn decode_with_options(
+    data: &[u8],
+    mode: DecodeMode,
+    options: &DecoderOptions,
+) -> Result<DecodedFrame> {
+    DecoderContext::new().decode(data, mode, options)
 }
 
 /// Decode HEVC from HEIC container (config + image data)
@@ -40,6 +106,36 @@ pub fn decode(data: &[u8]) -> Result<DecodedFrame> {
 /// This is the preferred method for HEIC files where parameter sets
 /// are stored separately in the hvcC box.
 pub fn decode_with_config(config: &HevcDecoderConfig, image_data: &[u8]) -> Result<DecodedFrame> {
+    decode_with_config_mode(config, image_data, DecodeMode::Full, &DecoderLimits::default())
+}
+
+/// Decode HEVC from HEIC container (config + image data) in the given
+/// mode, rejecting pictures that exceed `limits`
+pub fn decode_with_config_mode(
+    config: &HevcDecoderConfig,
+    image_data: &[u8],
+    mode: DecodeMode,
+    limits: &DecoderLimits,
+) -> Result<DecodedFrame> {
+    decode_with_config_options(config, image_data, mode, &DecoderOptions::new().limits(*limits))
+}
+
+/// Decode HEVC from HEIC container (config + image data) in the given
+/// mode with the given options, as [`decode_with_options`] does
+pub fn decode_with_config_options(
+    config: &HevcDecoderConfig,
+    image_data: &[u8],
+    mode: DecodeMode,
+    options: &DecoderOptions,
+) -> Result<DecodedFrame> {
+    DecoderContext::new().decode_with_config(config, image_data, mode, options)
+}
+
+/// Parameter sets of an hvcC configuration followed by the NAL units of the image data
+fn config_nal_units<'a>(
+    config: &'a HevcDecoderConfig,
+    image_data: &'a [u8],
+) -> Result<Vec<bitstream::NalUnit<'a>>> {
     let mut nal_units = Vec::new();
 
     // Parse parameter sets from hvcC
@@ -53,8 +149,7 @@ pub fn decode_with_config(config: &HevcDecoderConfig, image_data: &[u8]) -> Resu
     let length_size = (config.length_size_minus_one + 1) as usize;
     let mut slice_nals = bitstream::parse_length_prefixed_ext(image_data, length_size)?;
     nal_units.append(&mut slice_nals);
-
-    decode_nal_units(&nal_units)
+    Ok(nal_units)
 }
 
 /// Get image info from HEIC config
@@ -71,36 +166,723 @@ pub fn get_info_from_config(config: &HevcDecoderConfig) -> Result<ImageInfo> {
     Err(HevcError::MissingParameterSet("SPS"))
 }
 
-/// Internal: decode from parsed NAL units
-fn decode_nal_units(nal_units: &[bitstream::NalUnit<'_>]) -> Result<DecodedFrame> {
-    // Find and parse parameter sets
-    let mut _vps = None;
-    let mut sps = None;
-    let mut pps = None;
+/// Internal: decode from parsed NAL units with the buffers of `context`,
+/// returning the last picture
+fn decode_nal_units(
+    context: &mut DecoderContext,
+    nal_units: &[bitstream::NalUnit<'_>],
+    mode: DecodeMode,
+    options: &DecoderOptions,
+) -> Result<DecodedFrame> {
+    let mut decoder = SequenceDecoder::new();
+    decoder.context = core::mem::take(context);
+    decoder.set_mode(mode);
+    decoder.set_options(options);
+    let result = decoder.process_nal_units(nal_units).and_then(|()| {
+        if !decoder.finish_picture(0) {
+            // Report why nothing decoded rather than that nothing did
+            let mut errors = core::mem::take(&mut decoder.layers[0].errors);
+            return Err(if errors.is_empty() {
+                HevcError::InvalidBitstream("no picture decoded")
+            } else {
+                errors.swap_remove(0)
+            });
+        }
+        // The emptied DPB no longer shares the picture, so it moves out
+        let layer = &mut decoder.layers[0];
+        layer.dpb.flush(&mut layer.output);
+        layer
+            .output
+            .pop()
+            .map(Arc::unwrap_or_clone)
+            .ok_or(HevcError::InvalidBitstream("no picture decoded"))
+    });
+    *context = decoder.into_context();
+    result
+}
+
+/// Decode the base layer and one dependent layer of a multi-layer access unit
+///
+/// For MV-HEVC (multiview) bitstreams such as stereo images, `layer_id` is
+/// the nuh_layer_id of the second view. Returns the base layer picture
+/// first, followed by the picture of `layer_id` if the data contains it.
+pub fn decode_layers(
+    nal_units: &[bitstream::NalUnit<'_>],
+    layer_id: u8,
+    options: &DecoderOptions,
+) -> Result<Vec<DecodedFrame>> {
+    let mut decoder = SequenceDecoder::new();
+    decoder.set_options(options);
+    decoder.set_target_layer(layer_id);
+    decoder.process_nal_units(nal_units)?;
+    let decoded = decoder.finish_access_unit();
+    if !decoded[0] {
+        return Err(HevcError::InvalidBitstream("no picture decoded"));
+    }
+    Ok(decoder
+        .layers
+        .iter_mut()
+        .zip(decoded)
+        .filter(|&(_, decoded)| decoded)
+        .filter_map(|(layer, _)| {
+            layer.dpb.flush(&mut layer.output);
+            layer.output.pop().map(Arc::unwrap_or_clone)
+        })
+        .collect())
+}
+
+/// Layers decoded at once: the base layer and one dependent layer
+const MAX_LAYERS: usize = 2;
+
+/// Stateful HEVC decoder for coded video sequences
+///
+/// Parameter sets and the decoded picture buffer persist between calls, so
+/// P and B pictures can reference pictures decoded earlier. Pictures are
+/// returned in output (POC) order as the decoded picture buffer releases
+/// them (C.5.2), so an access unit may return no picture or several; call
+/// [`flush`](Self::flush) at the end of the stream for the rest. Pictures
+/// still used for reference are shared with the buffer rather than copied.
+///
+/// Only the base layer is decoded unless a target layer is set with
+/// [`set_target_layer`](Self::set_target_layer); the target layer is then
+/// decoded alongside the base layer, predicting from it (MV-HEVC).
+pub struct SequenceDecoder {
+    vps: Vec<Option<Arc<params::Vps>>>,
+    sps: Vec<Option<Arc<params::Sps>>>,
+    pps: Vec<Option<Arc<params::Pps>>>,
+    /// Base layer state, then the target layer's
+    layers: [LayerState; MAX_LAYERS],
+    /// nuh_layer_id decoded alongside the base layer (0: base layer only)
+    target_layer: u8,
+    /// PicOrderCntVal of the current access unit's base layer picture
+    au_poc: Option<i32>,
+    /// Reconstruction quality
+    mode: DecodeMode,
+    /// Limits, in-loop filter switches and error handling
+    options: DecoderOptions,
+    /// Buffers reused from picture to picture
+    context: DecoderContext,
+}
+
+/// Decoding state of one layer
+struct LayerState {
+    dpb: dpb::DecodedPictureBuffer,
+    current: Option<CurrentPicture>,
+    /// PicOrderCntVal of the previous TemporalId 0 reference picture
+    prev_tid0_poc: i32,
+    /// Next IRAP picture starts a new coded video sequence (start or after EOS)
+    first_in_sequence: bool,
+    /// A picture of this layer was completed in the current access unit
+    decoded_in_au: bool,
+    /// Errors concealed since the last picture was completed
+    errors: Vec<HevcError>,
+    /// NoRaslOutputFlag of the last IRAP picture: its RASL pictures are
+    /// decoded from generated references and not output
+    rasl_skipped: bool,
+    /// Pictures bumped from the DPB and not yet returned, in output order
+    output: Vec<Arc<DecodedFrame>>,
+}
+
+impl Default for LayerState {
+    fn default() -> Self {
+        Self {
+            dpb: dpb::DecodedPictureBuffer::new(),
+            current: None,
+            prev_tid0_poc: 0,
+            first_in_sequence: true,
+            decoded_in_au: false,
+            errors: Vec::new(),
+            rasl_skipped: false,
+            output: Vec::new(),
+        }
+    }
+}
+
+/// Picture being decoded
+struct CurrentPicture {
+    frame: DecodedFrame,
+    motion: inter::MotionField,
+    poc: i32,
+    rps: dpb::RefPicSet,
+    /// PicOutputFlag
+    output: bool,
+    /// DPB sizes of the picture's SPS
+    dpb_ordering: params::DpbOrdering,
+    /// Per-CTB decoded flags in raster order, in concealment mode
+    ctb_decoded: Vec<bool>,
+    /// CTB size in luma samples
+    ctb_size: u32,
+}
+
+impl Default for SequenceDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SequenceDecoder {
+    /// Create a decoder with no parameter sets
+    pub fn new() -> Self {
+        Self {
+            vps: vec![None; 16],
+            sps: vec![None; 16],
+            pps: vec![None; 64],
+            layers: Default::default(),
+            target_layer: 0,
+            au_poc: None,
+            mode: DecodeMode::Full,
+            options: DecoderOptions::default(),
+            context: DecoderContext::new(),
+        }
+    }
+
+    /// Create a decoder with the parameter sets of an hvcC configuration
+    pub fn with_config(config: &HevcDecoderConfig) -> Result<Self> {
+        let mut decoder = Self::new();
+        decoder.add_config(config)?;
+        Ok(decoder)
+    }
+
+    /// Add the parameter sets of a decoder configuration (hvcC or lhvC)
+    pub fn add_config(&mut self, config: &HevcDecoderConfig) -> Result<()> {
+        let nal_units: Vec<_> = config
+            .nal_units
+            .iter()
+            .filter_map(|data| bitstream::parse_single_nal(data).ok())
+            .collect();
+        self.process_nal_units(&nal_units)
+    }
+
+    /// Stop decoding and return the buffers, with the planes of the
+    /// pictures still in the decoded picture buffers
+    pub fn into_context(mut self) -> DecoderContext {
+        for layer in &mut self.layers {
+            layer.current = None;
+            layer.output.clear();
+            for frame in layer.dpb.drain() {
+                // Frames the caller still holds are not recycled
+                if let Ok(frame) = Arc::try_unwrap(frame) {
+                    self.context.recycle(frame);
+                }
+            }
+        }
+        self.context
+    }
+
+    /// Also decode the layer with the given nuh_layer_id (MV-HEVC)
+    ///
+    /// The layer must depend on the base layer only. Its pictures use the
+    /// base layer picture of the same access unit as an inter-layer
+    /// reference. 0 restores base-layer-only decoding.
+    pub fn set_target_layer(&mut self, nuh_layer_id: u8) {
+        self.target_layer = nuh_layer_id;
+    }
+
+    /// Set the reconstruction quality of subsequent pictures
+    ///
+    /// Pictures decoded in [`DecodeMode::Draft`] are inexact, so inter
+    /// pictures predicted from them drift further from the true image.
+    pub fn set_mode(&mut self, mode: DecodeMode) {
+        self.mode = mode;
+    }
+
+    /// Set the limits checked before allocating each picture
+    pub fn set_limits(&mut self, limits: DecoderLimits) {
+        self.options.limits = limits;
+    }
+
+    /// Set the options of subsequent pictures
+    ///
+    /// Of the options, the decoder uses the limits, the deblocking and SAO
+    /// switches and the strictness (see [`set_concealment`](Self::set_concealment)).
+    pub fn set_options(&mut self, options: &DecoderOptions) {
+        self.options = *options;
+    }
+
+    /// Conceal errors in slices instead of failing
+    ///
+    /// NAL units that fail to decode are skipped, and CTUs that fail end
+    /// their slice or WPP substream. When a picture is complete, its CTBs
+    /// that were not decoded are filled from their neighbours and listed
+    /// in [`DecodedFrame::damage`] with the error that caused them to be
+    /// lost. Exceeded limits are never concealed.
+    pub fn set_concealment(&mut self, conceal: bool) {
+        self.options.strictness = if conceal {
+            Strictness::Lenient
+        } else {
+            Strictness::Strict
+        };
+    }
+
+    /// Decode one length-prefixed access unit (an HEIF/MP4 track sample)
+    /// and return the pictures it outputs
+    pub fn decode_sample(&mut self, data: &[u8], length_size: usize) -> Result<Vec<Arc<DecodedFrame>>> {
+        let nal_units = bitstream::parse_length_prefixed_ext(data, length_size)?;
+        self.decode_access_unit(&nal_units)
+    }
+
+    /// Decode the NAL units of one access unit and return the pictures it
+    /// outputs, in output order
+    ///
+    /// With a target layer set, these are the target layer's pictures.
+    pub fn decode_access_unit(
+        &mut self,
+        nal_units: &[bitstream::NalUnit<'_>],
+    ) -> Result<Vec<Arc<DecodedFrame>>> {
+        let mut layers = self.decode_access_unit_layers(nal_units)?;
+        Ok(layers.pop().unwrap_or_default())
+    }
+
+    /// Decode the NAL units of one access unit and return the pictures it
+    /// outputs for each decoded layer, base layer first
+    pub fn decode_access_unit_layers(
+        &mut self,
+        nal_units: &[bitstream::NalUnit<'_>],
+    ) -> Result<Vec<Vec<Arc<DecodedFrame>>>> {
+        self.process_nal_units(nal_units)?;
+        let decoded = self.finish_access_unit();
+        if !decoded.contains(&true) {
+            return Err(HevcError::InvalidBitstream("no picture decoded"));
+        }
+        Ok(self.take_output())
+    }
+
+    /// End the stream: complete the current pictures and return all
+    /// pictures still waiting for output, in output order
+    ///
+    /// With a target layer set, these are the target layer's pictures.
+    pub fn flush(&mut self) -> Vec<Arc<DecodedFrame>> {
+        self.flush_layers().pop().unwrap_or_default()
+    }
+
+    /// [`flush`](Self::flush) for each decoded layer, base layer first
+    pub fn flush_layers(&mut self) -> Vec<Vec<Arc<DecodedFrame>>> {
+        self.finish_access_unit();
+        for layer in &mut self.layers {
+            layer.dpb.flush(&mut layer.output);
+            layer.first_in_sequence = true;
+        }
+        self.take_output()
+    }
+
+    /// Pictures bumped for each decoded layer, base layer first
+    fn take_output(&mut self) -> Vec<Vec<Arc<DecodedFrame>>> {
+        let count = if self.target_layer != 0 { MAX_LAYERS } else { 1 };
+        self.layers[..count]
+            .iter_mut()
+            .map(|layer| core::mem::take(&mut layer.output))
+            .collect()
+    }
+
+    /// Complete the pictures of the current access unit, reporting which
+    /// layers decoded one
+    fn finish_access_unit(&mut self) -> [bool; MAX_LAYERS] {
+        for idx in 0..MAX_LAYERS {
+            self.finish_picture(idx);
+        }
+        self.au_poc = None;
+        self.layers
+            .each_mut()
+            .map(|layer| core::mem::take(&mut layer.decoded_in_au))
+    }
+
+    /// Index into `layers` of a nuh_layer_id, if the layer is decoded
+    fn layer_index(&self, nuh_layer_id: u8) -> Option<usize> {
+        match nuh_layer_id {
+            0 => Some(0),
+            id if id == self.target_layer => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Handle parameter sets, end-of-sequence markers and slices
+    fn process_nal_units(&mut self, nal_units: &[bitstream::NalUnit<'_>]) -> Result<()> {
+        for (index, nal) in nal_units.iter().enumerate() {
+            // Only the base layer and the target layer are decoded
+            let Some(layer_idx) = self.layer_index(nal.nuh_layer_id) else {
+                continue;
+            };
+            let errors_before = self.layers[layer_idx].errors.len();
+            let result = self.process_nal_unit(nal, layer_idx);
+            // Starting a new picture hands the errors so far to the previous one
+            let errors = &mut self.layers[layer_idx].errors;
+            let skipped = errors.split_off(errors_before.min(errors.len()));
+            errors.extend(skipped.into_iter().map(|e| e.at_nal(index, nal.nal_type)));
+            match result.map_err(|e| e.at_nal(index, nal.nal_type)) {
+                Err(e) if self.options.conceals_errors() && e.kind() != ErrorKind::LimitExceeded => errors.push(e),
+                result => result?,
+            }
+        }
+        Ok(())
+    }
 
-    for nal in nal_units {
+    /// Handle one NAL unit of a decoded layer
+    fn process_nal_unit(&mut self, nal: &bitstream::NalUnit<'_>, layer_idx: usize) -> Result<()> {
         match nal.nal_type {
             bitstream::NalType::VpsNut => {
-                _vps = Some(params::parse_vps(&nal.payload)?);
+                let vps = params::parse_vps(&nal.payload)?;
+                let vps_id = vps.vps_id as usize;
+                self.vps[vps_id] = Some(Arc::new(vps));
             }
             bitstream::NalType::SpsNut => {
-                sps = Some(params::parse_sps(&nal.payload)?);
+                let vps_id = nal.payload.first().map_or(0, |&b| b >> 4);
+                let vps = self.vps[vps_id as usize].as_deref();
+                let sps = params::parse_layer_sps(&nal.payload, nal.nuh_layer_id, vps)?;
+                let slot = self
+                    .sps
+                    .get_mut(sps.sps_id as usize)
+                    .ok_or(HevcError::InvalidBitstream("SPS ID out of range"))?;
+                *slot = Some(Arc::new(sps));
             }
             bitstream::NalType::PpsNut => {
-                pps = Some(params::parse_pps(&nal.payload)?);
+                let pps = params::parse_pps(&nal.payload)?;
+                let slot = self
+                    .pps
+                    .get_mut(pps.pps_id as usize)
+                    .ok_or(HevcError::InvalidBitstream("PPS ID out of range"))?;
+                *slot = Some(Arc::new(pps));
+            }
+            bitstream::NalType::EosNut => {
+                self.finish_picture(layer_idx);
+                self.layers[layer_idx].first_in_sequence = true;
             }
+            nal_type if nal_type.is_slice() => self.decode_slice(nal, layer_idx)?,
             _ => {}
         }
+        Ok(())
     }
 
-    let sps = sps.ok_or(HevcError::MissingParameterSet("SPS"))?;
-    let pps = pps.ok_or(HevcError::MissingParameterSet("PPS"))?;
+    /// Store a layer's picture in progress in its DPB; false if there was none
+    fn finish_picture(&mut self, layer_idx: usize) -> bool {
+        let layer = &mut self.layers[layer_idx];
+        match layer.current.take() {
+            Some(mut cur) => {
+                if self.options.conceals_errors() {
+                    conceal_picture(&mut cur, core::mem::take(&mut layer.errors));
+                }
+                layer.dpb.insert(
+                    cur.frame,
+                    cur.motion,
+                    cur.poc,
+                    cur.output,
+                    &cur.dpb_ordering,
+                    &mut layer.output,
+                );
+                layer.decoded_in_au = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Begin a new picture: POC, reference picture set and frame buffer
+    fn start_picture(
+        &mut self,
+        nal: &bitstream::NalUnit<'_>,
+        layer_idx: usize,
+        sps: &params::Sps,
+        header: &slice::SliceHeader,
+    ) -> Result<()> {
+        let layer = &mut self.layers[layer_idx];
+        let nal_type = nal.nal_type;
+        // NoRaslOutputFlag (8.1.3)
+        let no_rasl_output = nal_type.is_irap() && (nal_type.is_idr() || layer.first_in_sequence);
+        if nal_type.is_irap() {
+            layer.first_in_sequence = false;
+            layer.rasl_skipped = no_rasl_output;
+        }
 
+        let poc = if layer_idx == 0 {
+            let poc = dpb::pic_order_cnt(
+                layer.prev_tid0_poc,
+                header.slice_pic_order_cnt_lsb,
+                sps.log2_max_pic_order_cnt_lsb_minus4 + 4,
+                no_rasl_output,
+            );
+            self.au_poc = Some(poc);
+            poc
+        } else {
+            // All pictures of an access unit share the base layer's POC
+            self.au_poc
+                .ok_or(HevcError::InvalidBitstream("layer picture without base layer picture"))?
+        };
+        if nal.nuh_temporal_id_plus1 == 1
+            && !nal_type.is_rasl()
+            && !nal_type.is_radl()
+            && !nal_type.is_sub_layer_non_ref()
+        {
+            layer.prev_tid0_poc = poc;
+        }
+
+        self.options.limits.check_frame(
+            sps.pic_width_in_luma_samples,
+            sps.pic_height_in_luma_samples,
+            sps.chroma_format_idc,
+        )?;
+        let rps = layer.dpb.apply_ref_pic_set(sps, header, poc, no_rasl_output);
+        layer.dpb.prepare(
+            &sps.dpb_ordering,
+            no_rasl_output,
+            header.no_output_of_prior_pics_flag,
+            &mut layer.output,
+        );
+        let ctb_decoded = if self.options.conceals_errors() {
+            vec![false; (sps.pic_width_in_ctbs() * sps.pic_height_in_ctbs()) as usize]
+        } else {
+            Vec::new()
+        };
+        layer.current = Some(CurrentPicture {
+            frame: new_frame(&mut self.context, sps),
+            motion: inter::MotionField::new(sps.pic_width_in_luma_samples, sps.pic_height_in_luma_samples),
+            poc,
+            rps,
+            output: header.pic_output_flag && !(nal_type.is_rasl() && layer.rasl_skipped),
+            dpb_ordering: sps.dpb_ordering,
+            ctb_decoded,
+            ctb_size: sps.ctb_size(),
+        });
+        Ok(())
+    }
+
+    fn decode_slice(&mut self, nal: &bitstream::NalUnit<'_>, layer_idx: usize) -> Result<()> {
+        // 1. Parse slice header with the parameter sets it refers to
+        let pps_id = slice::SliceHeader::peek_pps_id(nal)?;
+        let pps = self.pps[pps_id as usize]
+            .clone()
+            .ok_or(HevcError::MissingParameterSet("PPS"))?;
+        let sps = self
+            .sps
+            .get(pps.sps_id as usize)
+            .cloned()
+            .flatten()
+            .ok_or(HevcError::MissingParameterSet("SPS"))?;
+        // PPS fields bounded by the coding block sizes of the SPS (7.4.3.3.1)
+        if pps.diff_cu_qp_delta_depth > sps.log2_diff_max_min_luma_coding_block_size
+            || pps.log2_parallel_merge_level_minus2 + 2 > sps.log2_ctb_size()
+        {
+            return Err(HevcError::InvalidBitstream("PPS does not match its SPS"));
+        }
+        let vps_ext = self.vps[sps.vps_id as usize]
+            .as_ref()
+            .and_then(|vps| vps.extension.clone());
+        let layer_info = if layer_idx > 0 {
+            let info = vps_ext
+                .as_ref()
+                .and_then(|ext| ext.layer_info(nal.nuh_layer_id))
+                .ok_or(HevcError::MissingParameterSet("VPS extension"))?;
+            Some(info)
+        } else {
+            None
+        };
+        let parse_result = slice::SliceHeader::parse_layer(nal, &sps, &pps, layer_info.as_ref())?;
+        let slice_header = parse_result.header;
+        let data_offset = parse_result.data_offset;
+
+        if slice_header.first_slice_segment_in_pic_flag || self.layers[layer_idx].current.is_none() {
+            if layer_idx > 0 {
+                // The base layer picture is complete once the next layer starts
+                self.finish_picture(0);
+            }
+            self.finish_picture(layer_idx);
+            self.start_picture(nal, layer_idx, &sps, &slice_header)?;
+        }
+
+        let (lower, upper) = self.layers.split_at_mut(layer_idx);
+        let layer = &mut upper[0];
+        let Some(cur) = layer.current.as_mut() else {
+            return Err(HevcError::InvalidBitstream("slice without picture"));
+        };
+
+        // 2. Get slice data (after header)
+        let slice_data = &nal.payload[data_offset..];
+
+        // 3. Create slice context, with reference pictures for P/B slices
+        let slice_address = slice_header.slice_segment_address;
+        let buffers = core::mem::take(&mut self.context.slice);
+        let mut ctx = ctu::SliceContext::new(&sps, &pps, &slice_header, slice_data, buffers)
+            .map_err(|e| e.at_slice(slice_address))?;
+        let draft = self.mode == DecodeMode::Draft;
+        ctx.set_draft(draft);
+        let is_inter = !slice_header.slice_type.is_intra();
+        if is_inter {
+            let inter_layer = match (&layer_info, lower.first()) {
+                (Some(info), Some(base)) => {
+                    inter_layer_refs(&base.dpb, info, vps_ext.as_ref(), &slice_header, &sps, cur.poc)?
+                }
+                _ => Default::default(),
+            };
+            let ref_lists = layer.dpb.ref_pic_lists(&cur.rps, &slice_header, inter_layer)?;
+            ctx.set_inter_prediction(ref_lists, core::mem::take(&mut cur.motion), cur.poc);
+        }
+
+        // 4. Decode all CTUs in the slice
+        if self.options.conceals_errors() {
+            ctx.set_concealment(core::mem::take(&mut cur.ctb_decoded));
+        }
+        let decoded = ctx.decode_slice(&mut cur.frame);
+        if is_inter {
+            cur.motion = ctx.take_motion_field();
+        }
+        if self.options.conceals_errors() {
+            let (ctb_decoded, skipped) = ctx.take_concealment();
+            cur.ctb_decoded = ctb_decoded;
+            layer
+                .errors
+                .extend(skipped.into_iter().map(|e| e.at_slice(slice_address)));
+        }
+        if let Err(e) = decoded {
+            self.context.slice = ctx.into_buffers();
+            return Err(e.at_slice(slice_address));
+        }
+        let frame = &mut cur.frame;
+
+        // 5. Apply in-loop filters (H.265 8.7.1)
+        // 5a. Deblocking filter
+        if !draft && self.options.deblocking && !slice_header.slice_deblocking_filter_disabled_flag {
+            deblock::apply_deblocking_filter(
+                frame,
+                &sps,
+                &pps,
+                &slice_header,
+                ctx.deblock_metadata(),
+                &mut self.context.deblocking,
+            );
+        }
+        // 5b. SAO (Sample Adaptive Offset) - applied after deblocking
+        if !draft && self.options.sao && sps.sample_adaptive_offset_enabled_flag {
+            sao::apply_sao(frame, &sps, &slice_header, ctx.sao_params(), &mut self.context.sao_lines);
+        }
+        self.context.slice = ctx.into_buffers();
+
+        Ok(())
+    }
+}
+
+/// Fill the CTBs of a picture that were not decoded and record them as damaged
+///
+/// Each run of missing CTBs in a CTB row is attributed to the error
+/// located last at or before its start, else to the first error without a
+/// position. Runs spanning the same columns in consecutive rows with the
+/// same cause are merged into one region.
+fn conceal_picture(cur: &mut CurrentPicture, errors: Vec<HevcError>) {
+    if !cur.ctb_decoded.contains(&false) {
+        return;
+    }
+    let ctb_size = cur.ctb_size;
+    let frame = &mut cur.frame;
+    let width_in_ctbs = frame.width.div_ceil(ctb_size);
+
+    // Raster address of the first CTB each error affects, if known
+    let errors: Vec<(Option<u32>, Arc<HeicError>)> = errors
+        .into_iter()
+        .map(|e| {
+            let location = e.location().copied().unwrap_or_default();
+            let address = location
+                .ctb
+                .map(|(x, y)| y * width_in_ctbs + x)
+                .or(location.slice_address);
+            (address, Arc::new(HeicError::from(e)))
+        })
+        .collect();
+    let unlocated = errors
+        .iter()
+        .find(|(address, _)| address.is_none())
+        .map(|(_, cause)| cause.clone())
+        .unwrap_or_else(|| {
+            Arc::new(HevcError::InvalidBitstream("CTBs not covered by any slice").into())
+        });
+    let cause_at = |address: u32| {
+        errors
+            .iter()
+            .filter_map(|(start, cause)| Some((start.filter(|&start| start <= address)?, cause)))
+            .max_by_key(|&(start, _)| start)
+            .map_or_else(|| unlocated.clone(), |(_, cause)| cause.clone())
+    };
+
+    let mut regions: Vec<DamagedRegion> = Vec::new();
+    for (row, flags) in cur.ctb_decoded.chunks(width_in_ctbs as usize).enumerate() {
+        let row = row as u32;
+        let mut col = 0;
+        while col < flags.len() {
+            if flags[col] {
+                col += 1;
+                continue;
+            }
+            let start = col;
+            while col < flags.len() && !flags[col] {
+                col += 1;
+            }
+            let x = start as u32 * ctb_size;
+            let y = row * ctb_size;
+            let width = (col as u32 * ctb_size).min(frame.width) - x;
+            let height = ctb_size.min(frame.height - y);
+            frame.conceal(x, y, width, height);
+
+            let cause = cause_at(row * width_in_ctbs + start as u32);
+            match regions.last_mut() {
+                Some(last)
+                    if last.x == x
+                        && last.width == width
+                        && last.y + last.height == y
+                        && Arc::ptr_eq(&last.cause, &cause) =>
+                {
+                    last.height += height;
+                }
+                _ => regions.push(DamagedRegion { x, y, width, height, cause }),
+            }
+        }
+    }
+    frame.damage.extend(regions);
+}
+
+/// Collect RefPicSetInterLayer0 and RefPicSetInterLayer1 (F.8.1.4)
+///
+/// The inter-layer reference is the base layer picture of the current
+/// access unit. Views on the same side of the base view as the current
+/// view go into set 0, the others into set 1.
+fn inter_layer_refs<'a>(
+    base_dpb: &'a dpb::DecodedPictureBuffer,
+    layer: &params::LayerInfo,
+    vps_ext: Option<&params::VpsExtension>,
+    header: &slice::SliceHeader,
+    sps: &params::Sps,
+    poc: i32,
+) -> Result<[Vec<inter::RefPic<'a>>; 2]> {
+    let mut sets: [Vec<inter::RefPic<'a>>; 2] = Default::default();
+    for &ref_layer in &header.inter_layer_ref_layers {
+        if ref_layer != 0 {
+            return Err(HevcError::Unsupported("inter-layer reference to a non-base layer"));
+        }
+        let pic = base_dpb
+            .last()
+            .filter(|pic| pic.poc == poc)
+            .ok_or(HevcError::InvalidBitstream("missing inter-layer reference picture"))?;
+        if pic.frame.width != sps.pic_width_in_luma_samples
+            || pic.frame.height != sps.pic_height_in_luma_samples
+        {
+            return Err(HevcError::Unsupported("inter-layer resampling"));
+        }
+
+        let ref_view = vps_ext.map_or(0, |ext| ext.view_id_of(ref_layer));
+        let (view, base_view) = (layer.view_id, layer.base_view_id);
+        let same_side = (view <= base_view && view <= ref_view) || (view >= base_view && view >= ref_view);
+        sets[if same_side { 0 } else { 1 }].push(inter::RefPic {
+            frame: &pic.frame,
+            motion: &pic.motion,
+            poc: pic.poc,
+            is_long_term: true,
+        });
+    }
+    Ok(sets)
+}
+
+/// Create a frame buffer with the format, colour space and cropping of an SPS
+fn new_frame(context: &mut DecoderContext, sps: &params::Sps) -> DecodedFrame {
     // Create frame buffer with proper bit depth and chroma format
     let bit_depth = sps.bit_depth_y();
     let chroma_format = sps.chroma_format_idc;
 
-    let mut frame = DecodedFrame::with_params(
+    let mut frame = context.new_frame(
         sps.pic_width_in_luma_samples,
         sps.pic_height_in_luma_samples,
         bit_depth,
@@ -137,14 +919,7 @@ fn decode_nal_units(nal_units: &[bitstream::NalUnit<'_>]) -> Result<DecodedFrame
         );
     }
 
-    // Decode slice data
-    for nal in nal_units {
-        if nal.nal_type.is_slice() {
-            decode_slice(nal, &sps, &pps, &mut frame)?;
-        }
-    }
-
-    Ok(frame)
+    frame
 }
 
 /// Get image info without full decoding
@@ -197,209 +972,378 @@ pub struct ImageInfo {
     pub height: u32,
 }
 
-fn decode_slice(
-    nal: &bitstream::NalUnit<'_>,
-    sps: &params::Sps,
-    pps: &params::Pps,
-    frame: &mut DecodedFrame,
-) -> Result<()> {
-    // 1. Parse slice header and get data offset
-    let parse_result = slice::SliceHeader::parse(nal, sps, pps)?;
-    let slice_header = parse_result.header;
-    let data_offset = parse_result.data_offset;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Verify this is an I-slice (required for HEIC still images)
-    if !slice_header.slice_type.is_intra() {
-        return Err(HevcError::Unsupported(
-            "only I-slices supported for still images",
-        ));
-    }
+    #[test]
+    fn test_conceal_picture_regions() {
+        // 3x3 CTBs of 16 samples in a 40x40 picture
+        let mut cur = CurrentPicture {
+            frame: DecodedFrame::with_params(40, 40, 8, 1),
+            motion: inter::MotionField::default(),
+            poc: 0,
+            rps: Default::default(),
+            output: true,
+            dpb_ordering: Default::default(),
+            ctb_decoded: vec![true, true, true, true, false, false, true, false, false],
+            ctb_size: 16,
+        };
+        cur.frame.y_plane[..40 * 16].fill(100);
+        let errors = vec![HevcError::CabacError("desync").at_ctb(1, 1).at_slice(3)];
+        conceal_picture(&mut cur, errors);
 
-    // 2. Get slice data (after header)
-    // Use the offset from slice header parsing to skip the header bytes
-    let slice_data = &nal.payload[data_offset..];
+        // CTBs (1, 1) to (2, 2) form one region, clipped to the picture
+        let damage = &cur.frame.damage;
+        assert_eq!(damage.len(), 1);
+        assert_eq!((damage[0].x, damage[0].y, damage[0].width, damage[0].height), (16, 16, 24, 24));
+        assert_eq!(damage[0].cause.bitstream_location().and_then(|l| l.ctb), Some((1, 1)));
+        // Extended down from the last decoded row; CTB (0, 1) is untouched
+        assert_eq!(cur.frame.y_plane[39 * 40 + 39], 100);
+        assert_eq!(cur.frame.y_plane[20 * 40 + 20], 100);
+        assert_eq!(cur.frame.y_plane[20 * 40 + 5], 0);
+    }
 
-    // 3. Create slice context and decode CTUs
-    let mut ctx = ctu::SliceContext::new(sps, pps, &slice_header, slice_data)?;
+    #[test]
+    fn test_corrupted_wpp_substream_is_concealed() {
+        use test_util::{SliceHeaderSpec, StreamParams, TestCu, annex_b};
 
-    // 4. Decode all CTUs in the slice
-    let (deblock_metadata, sao_params) = ctx.decode_slice(frame)?;
+        // 4x4 CTBs of 16 samples, one WPP substream per CTB row. The third
+        // CTB of the second row has a DC level too large to be decoded.
+        let params = StreamParams { wpp: true, ..StreamParams::default() };
+        let mut cus = vec![TestCu::Intra(0); 16];
+        cus[6] = TestCu::Intra(1 << 20);
+        cus[9] = TestCu::Intra(8);
+        let idr = SliceHeaderSpec::idr().slice(&params, &cus);
+        let data = annex_b(&[params.sps(), params.pps(), idr]);
 
-    // 5. Apply in-loop filters (H.265 8.7.1)
-    // 5a. Deblocking filter
-    if !slice_header.slice_deblocking_filter_disabled_flag && !std::env::var("HEVC_NO_FILTER").is_ok() {
-        deblock::apply_deblocking_filter(frame, sps, pps, &slice_header, &deblock_metadata);
-    }
-    // 5b. SAO (Sample Adaptive Offset) - applied after deblocking
-    if sps.sample_adaptive_offset_enabled_flag && !std::env::var("HEVC_NO_FILTER").is_ok() {
-        apply_sao(frame, sps, &slice_header, &sao_params);
+        assert!(decode(&data).is_err());
+        let options = DecoderOptions::new().strictness(Strictness::Lenient);
+        let frame = decode_with_options(&data, DecodeMode::Full, &options).unwrap();
+        // The rest of the second row is lost; decoding resumes at the third
+        let damage = &frame.damage;
+        assert_eq!(damage.len(), 1);
+        assert_eq!((damage[0].x, damage[0].y, damage[0].width, damage[0].height), (32, 16, 32, 16));
+        assert_eq!(damage[0].cause.bitstream_location().and_then(|l| l.ctb), Some((2, 1)));
+        // The residual of CTB (1, 2) was decoded from the resumed substream
+        assert!(frame.y_plane[40 * 64 + 24] > 130);
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_draft_mode_approximates_full_decode() {
+        use test_util::{SliceHeaderSpec, StreamParams, TestCu, annex_b};
 
-/// Apply SAO (Sample Adaptive Offset) filtering to the entire frame
-/// Per H.265 section 8.7.3, SAO is applied after deblocking using pre-SAO samples.
-fn apply_sao(
-    frame: &mut DecodedFrame,
-    sps: &params::Sps,
-    header: &slice::SliceHeader,
-    sao_params: &[SaoParams],
-) {
-    if !header.slice_sao_luma_flag && !header.slice_sao_chroma_flag {
-        return;
+        // Intra CTBs with DC levels varying across the picture, so that
+        // planar prediction and deblocking both change the reconstruction
+        let params = StreamParams::default();
+        let cus: Vec<_> = (0..16).map(|i| TestCu::Intra((i % 4 + i / 4) % 3 * 6 - 6)).collect();
+        let idr = SliceHeaderSpec::idr().slice(&params, &cus);
+        let data = annex_b(&[params.sps(), params.pps(), idr]);
+
+        let limits = DecoderLimits::default();
+        let full = decode_with_mode(&data, DecodeMode::Full, &limits).unwrap();
+        let draft = decode_with_mode(&data, DecodeMode::Draft, &limits).unwrap();
+        assert_eq!((draft.width, draft.height), (full.width, full.height));
+        assert_eq!(draft.y_plane.len(), full.y_plane.len());
+        assert_ne!(draft.y_plane, full.y_plane);
+        let total: u32 = draft
+            .y_plane
+            .iter()
+            .zip(&full.y_plane)
+            .map(|(&d, &f)| u32::from(d.abs_diff(f)))
+            .sum();
+        let mean = total as f64 / full.y_plane.len() as f64;
+        assert!(mean < 2.0, "mean luma difference {mean}");
+        assert_eq!(draft.cb_plane, full.cb_plane);
+    }
+    /// Decode a test stream of one slice per access unit, returning the
+    /// pictures in output order
+    fn decode_slices(params: &test_util::StreamParams, slices: Vec<Vec<u8>>) -> Vec<DecodedFrame> {
+        let mut decoder = SequenceDecoder::new();
+        let mut frames = Vec::new();
+        for (i, slice) in slices.into_iter().enumerate() {
+            let access_unit = match i {
+                0 => vec![params.sps(), params.pps(), slice],
+                _ => vec![slice],
+            };
+            let data = test_util::annex_b(&access_unit);
+            let nal_units = bitstream::parse_nal_units(&data).unwrap();
+            frames.extend(decoder.decode_access_unit(&nal_units).unwrap());
+        }
+        frames.extend(decoder.flush());
+        frames.into_iter().map(Arc::unwrap_or_clone).collect()
     }
 
-    let ctb_size = sps.ctb_size();
-    let pic_width = sps.pic_width_in_luma_samples;
-    let pic_height = sps.pic_height_in_luma_samples;
-    let ctbs_per_row = pic_width.div_ceil(ctb_size);
-    let ctbs_per_col = pic_height.div_ceil(ctb_size);
+    /// Header of a P slice predicted from the picture `distance` POCs
+    /// before it
+    fn p_slice(poc: u32, distance: u32) -> test_util::SliceHeaderSpec {
+        test_util::SliceHeaderSpec {
+            nal_type: bitstream::NalType::TrailR,
+            slice_type: slice::SliceType::P,
+            poc_lsb: poc,
+            refs_before: vec![distance],
+            ..test_util::SliceHeaderSpec::idr()
+        }
+    }
 
-    // Snapshot pre-SAO planes for edge offset neighbor lookups
-    let y_snapshot = frame.y_plane.clone();
-    let cb_snapshot = frame.cb_plane.clone();
-    let cr_snapshot = frame.cr_plane.clone();
+    /// 64x64 intra picture of 16x16 CTBs with distinct Y, Cb and Cr levels
+    fn textured_picture() -> Vec<test_util::TestCu> {
+        (0..16)
+            .map(|i| test_util::TestCu::IntraYCbCr([(i * 7 % 11 - 5) * 3, (i * 5 % 7 - 3) * 4, (i * 3 % 5 - 2) * 5]))
+            .collect()
+    }
 
-    for ctb_y in 0..ctbs_per_col {
-        for ctb_x in 0..ctbs_per_row {
-            let addr = (ctb_y * ctbs_per_row + ctb_x) as usize;
-            if addr >= sao_params.len() {
-                continue;
-            }
-            let params = &sao_params[addr];
-            let x0 = ctb_x * ctb_size;
-            let y0 = ctb_y * ctb_size;
-            let x_end = (x0 + ctb_size).min(pic_width);
-            let y_end = (y0 + ctb_size).min(pic_height);
-
-            // Luma
-            if header.slice_sao_luma_flag && params.luma.sao_type != SaoType::None {
-                apply_sao_ctb(
-                    &params.luma,
-                    &y_snapshot,
-                    &mut frame.y_plane,
-                    pic_width as usize,
-                    pic_height as usize,
-                    x0 as usize, y0 as usize,
-                    x_end as usize, y_end as usize,
-                    sps.bit_depth_y(),
-                );
-            }
+    /// Uni-directional prediction of a sample of `plane` (8.5.3.3.3) with
+    /// default weighting, at full-sample position (x, y) displaced by `mv`
+    ///
+    /// `mv` is in quarter luma samples, or eighth chroma samples for the
+    /// chroma planes of 4:2:0 pictures.
+    fn predict_sample(plane: &[u16], width: i32, (x, y): (i32, i32), mv: (i32, i32)) -> u16 {
+        const LUMA: [[i32; 8]; 4] = [
+            [0, 0, 0, 64, 0, 0, 0, 0],
+            [-1, 4, -10, 58, 17, -5, 1, 0],
+            [-1, 4, -11, 40, 40, -11, 4, -1],
+            [0, 1, -5, 17, 58, -10, 4, -1],
+        ];
+        const CHROMA: [[i32; 4]; 8] = [
+            [0, 64, 0, 0],
+            [-2, 58, 10, -2],
+            [-4, 54, 16, -2],
+            [-6, 46, 28, -4],
+            [-4, 36, 36, -4],
+            [-4, 28, 46, -6],
+            [-2, 16, 54, -4],
+            [-2, 10, 58, -2],
+        ];
+        let height = plane.len() as i32 / width;
+        let luma = width == 64;
+        let frac_bits = if luma { 2 } else { 3 };
+        let filter = |frac: i32| -> &[i32] {
+            if luma { &LUMA[frac as usize] } else { &CHROMA[frac as usize] }
+        };
+        let mask = (1 << frac_bits) - 1;
+        let (fx, fy) = (filter(mv.0 & mask), filter(mv.1 & mask));
+        let (x, y) = (x + (mv.0 >> frac_bits), y + (mv.1 >> frac_bits));
+        let half = fx.len() as i32 / 2 - 1;
+        // Reference samples are padded by repeating the picture edges
+        let at = |x: i32, y: i32| {
+            i32::from(plane[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize])
+        };
+        // Horizontal then vertical filtering; the 8-bit intermediate shift
+        // is 0 and the full-sample filter is 64 so all cases share this
+        let row = |y: i32| (0..fx.len() as i32).map(|i| fx[i as usize] * at(x + i - half, y)).sum::<i32>();
+        let pred = (0..fy.len() as i32).map(|j| fy[j as usize] * row(y + j - half)).sum::<i32>() >> 6;
+        ((pred + 32) >> 6).clamp(0, 255) as u16
+    }
 
-            // Chroma (4:2:0)
-            if header.slice_sao_chroma_flag {
-                let cx0 = (x0 / 2) as usize;
-                let cy0 = (y0 / 2) as usize;
-                let cx_end = (x_end / 2) as usize;
-                let cy_end = (y_end / 2) as usize;
-                let cpw = (pic_width / 2) as usize;
-                let cph = (pic_height / 2) as usize;
-
-                if params.cb.sao_type != SaoType::None {
-                    apply_sao_ctb(
-                        &params.cb,
-                        &cb_snapshot,
-                        &mut frame.cb_plane,
-                        cpw, cph,
-                        cx0, cy0, cx_end, cy_end,
-                        sps.bit_depth_c(),
-                    );
-                }
-                if params.cr.sao_type != SaoType::None {
-                    apply_sao_ctb(
-                        &params.cr,
-                        &cr_snapshot,
-                        &mut frame.cr_plane,
-                        cpw, cph,
-                        cx0, cy0, cx_end, cy_end,
-                        sps.bit_depth_c(),
-                    );
-                }
+    /// Expected picture predicted from `reference` with one motion vector
+    /// per 16x16 CTB, in quarter luma samples
+    fn predict_picture(reference: &DecodedFrame, mvs: &[(i32, i32)]) -> DecodedFrame {
+        let mut frame = reference.clone();
+        for (plane, predicted, width) in [
+            (&reference.y_plane, &mut frame.y_plane, 64),
+            (&reference.cb_plane, &mut frame.cb_plane, 32),
+            (&reference.cr_plane, &mut frame.cr_plane, 32),
+        ] {
+            let ctb_size = width / 4;
+            for (i, sample) in predicted.iter_mut().enumerate() {
+                let (x, y) = (i as i32 % width, i as i32 / width);
+                let mv = mvs[(y / ctb_size * 4 + x / ctb_size) as usize];
+                *sample = predict_sample(plane, width, (x, y), mv);
             }
         }
+        frame
     }
-}
 
-/// Apply SAO to a single CTB for a single component
-/// Uses `src` (pre-SAO snapshot) for neighbor reads, writes to `dst`.
-#[allow(clippy::too_many_arguments)]
-fn apply_sao_ctb(
-    params: &SaoComponentParams,
-    src: &[u16],
-    dst: &mut [u16],
-    pic_w: usize,
-    pic_h: usize,
-    x_start: usize, y_start: usize,
-    x_end: usize, y_end: usize,
-    bit_depth: u8,
-) {
-    let max_val = (1i32 << bit_depth) - 1;
-
-    match params.sao_type {
-        SaoType::None => {}
-        SaoType::Band => {
-            let band_shift = bit_depth - 5;
-            let band_pos = params.band_position as i32;
-
-            for y in y_start..y_end {
-                for x in x_start..x_end {
-                    let idx = y * pic_w + x;
-                    let sample = src[idx] as i32;
-                    let band = sample >> band_shift;
-                    let relative = band - band_pos;
-                    if relative >= 0 && relative < 4 {
-                        let offset = params.offsets[relative as usize];
-                        dst[idx] = (sample + offset).clamp(0, max_val) as u16;
-                    }
-                }
-            }
+    #[test]
+    fn test_amvp_and_merge_candidates() {
+        use test_util::{StreamParams, TestCu};
+
+        let params = StreamParams { deblocking: false, ..StreamParams::default() };
+        let amvp = |mvd, mvp_flag| TestCu::Amvp { mvd, mvp_flag, level: 0 };
+        let mut cus = vec![TestCu::SKIP; 16];
+        // The first CTB has no neighbours: its predictor is zero
+        cus[0] = amvp([16, -8], false);
+        // Predicted from the left neighbour (A1), then from the zero
+        // candidate after it
+        cus[2] = amvp([-8, -8], false);
+        cus[3] = amvp([8, 8], true);
+        // Merge lists (B1, zero) and (A1, B1, B0): B0 repeats B1 in the
+        // first and B2 repeats B1 in the second, so both are pruned
+        cus[4] = TestCu::Skip { merge_idx: 1 };
+        cus[5] = TestCu::Skip { merge_idx: 2 };
+        let header = test_util::SliceHeaderSpec { max_num_merge_cand: 5, ..p_slice(1, 1) };
+        let slices = vec![
+            test_util::SliceHeaderSpec::idr().slice(&params, &textured_picture()),
+            header.slice(&params, &cus),
+        ];
+        let frames = decode_slices(&params, slices);
+
+        let mut mvs = [(0, 0); 16];
+        mvs[..8].copy_from_slice(&[(16, -8), (16, -8), (8, -16), (8, 8), (0, 0), (8, -16), (8, -16), (8, -16)]);
+        let expected = predict_picture(&frames[0], &mvs);
+        assert_ne!(frames[1].y_plane, frames[0].y_plane);
+        assert_eq!(frames[1].y_plane, expected.y_plane);
+        assert_eq!(frames[1].cb_plane, expected.cb_plane);
+        assert_eq!(frames[1].cr_plane, expected.cr_plane);
+    }
+
+    #[test]
+    fn test_fractional_motion_vector_interpolation() {
+        use test_util::{StreamParams, TestCu};
+
+        // Half-sample horizontal and quarter-sample vertical luma offsets;
+        // a quarter and an eighth of a chroma sample. The other CTBs merge
+        // the same motion.
+        let params = StreamParams::default();
+        let mut cus = vec![TestCu::SKIP; 16];
+        cus[0] = TestCu::Amvp { mvd: [2, 1], mvp_flag: false, level: 0 };
+        let slices = vec![
+            test_util::SliceHeaderSpec::idr().slice(&params, &textured_picture()),
+            p_slice(1, 1).slice(&params, &cus),
+        ];
+        let frames = decode_slices(&params, slices);
+
+        let expected = predict_picture(&frames[0], &[(2, 1); 16]);
+        assert_eq!(frames[1].y_plane, expected.y_plane);
+        assert_eq!(frames[1].cb_plane, expected.cb_plane);
+        assert_eq!(frames[1].cr_plane, expected.cr_plane);
+    }
+
+    #[test]
+    fn test_weighted_prediction() {
+        use test_util::{StreamParams, TestCu};
+
+        // Luma scaled by 96/64 and offset by -20; chroma keeps the default
+        // weights
+        let params = StreamParams { weighted_pred: true, ..StreamParams::default() };
+        let header = test_util::SliceHeaderSpec { luma_weight: Some((96, -20)), ..p_slice(1, 1) };
+        let slices = vec![
+            test_util::SliceHeaderSpec::idr().slice(&params, &textured_picture()),
+            header.slice(&params, &[TestCu::SKIP; 16]),
+        ];
+        let frames = decode_slices(&params, slices);
+
+        // (8-252) with log2WD = 6 + 6 and the 14-bit prediction
+        let weighted = |y: u16| ((((i32::from(y) << 6) * 96 + (1 << 11)) >> 12) - 20).clamp(0, 255) as u16;
+        let expected: Vec<u16> = frames[0].y_plane.iter().map(|&y| weighted(y)).collect();
+        assert_ne!(expected, frames[0].y_plane);
+        assert_eq!(frames[1].y_plane, expected);
+        assert_eq!(frames[1].cb_plane, frames[0].cb_plane);
+        assert_eq!(frames[1].cr_plane, frames[0].cr_plane);
+    }
+
+    #[test]
+    fn test_temporal_merge_candidate() {
+        use test_util::{StreamParams, TestCu};
+
+        // The first P picture moves everything by (16, -8); the second only
+        // merges, and its first CTB has just the temporal candidate
+        let params = StreamParams { tmvp: true, ..StreamParams::default() };
+        let mut cus = vec![TestCu::SKIP; 16];
+        cus[0] = TestCu::Amvp { mvd: [16, -8], mvp_flag: false, level: 0 };
+        let second = test_util::SliceHeaderSpec { temporal_mvp: true, ..p_slice(2, 1) };
+        let slices = vec![
+            test_util::SliceHeaderSpec::idr().slice(&params, &textured_picture()),
+            p_slice(1, 1).slice(&params, &cus),
+            second.slice(&params, &[TestCu::SKIP; 16]),
+        ];
+        let frames = decode_slices(&params, slices);
+
+        // The collocated motion vector needs no scaling: both pictures are
+        // one POC from their reference
+        assert_eq!(frames[1].y_plane, predict_picture(&frames[0], &[(16, -8); 16]).y_plane);
+        let expected = predict_picture(&frames[1], &[(16, -8); 16]);
+        assert_ne!(frames[2].y_plane, frames[1].y_plane);
+        assert_eq!(frames[2].y_plane, expected.y_plane);
+        assert_eq!(frames[2].cb_plane, expected.cb_plane);
+    }
+
+    #[test]
+    fn test_decode_two_views() {
+        let params = test_util::StreamParams::default();
+        let [base, second] = test_util::stereo_slices(&params, &textured_picture(), 8);
+        let data = test_util::annex_b(&[
+            params.vps(),
+            params.sps(),
+            params.pps(),
+            params.layer_sps(),
+            params.layer_pps(1),
+            base,
+            second,
+        ]);
+        let nal_units = bitstream::parse_nal_units(&data).unwrap();
+        let options = DecoderOptions::default();
+
+        // The second view is the base view two luma samples to the right
+        let views = decode_layers(&nal_units, 1, &options).unwrap();
+        assert_eq!(views.len(), 2);
+        let expected = predict_picture(&views[0], &[(8, 0); 16]);
+        assert_ne!(views[1].y_plane, views[0].y_plane);
+        assert_eq!(views[1].y_plane, expected.y_plane);
+        assert_eq!(views[1].cb_plane, expected.cb_plane);
+        assert_eq!(views[1].cr_plane, expected.cr_plane);
+
+        // Without a target layer, only the base view is decoded
+        let base_view = decode_layers(&nal_units, 0, &options).unwrap();
+        assert_eq!(base_view.len(), 1);
+        assert_eq!(base_view[0].y_plane, views[0].y_plane);
+        let decoded = decode(&data).unwrap();
+        assert_eq!(decoded.y_plane, views[0].y_plane);
+    }
+
+    #[test]
+    fn test_inter_pictures_are_output_in_poc_order() {
+        use bitstream::NalType;
+        use slice::SliceType;
+        use test_util::{SliceHeaderSpec, StreamParams, TestCu, annex_b};
+
+        // 64x64 picture of 16x16 CTBs; one picture may be reordered
+        let params = StreamParams { max_num_reorder_pics: 1, ..StreamParams::default() };
+        let picture = |cu: TestCu| vec![cu; 16];
+        // Flat mid-grey I picture, P picture two POCs later adding a DC
+        // residual to it, then the B picture between them averaging both
+        let idr = SliceHeaderSpec::idr().slice(&params, &picture(TestCu::Intra(0)));
+        let p = SliceHeaderSpec {
+            nal_type: NalType::TrailR,
+            slice_type: SliceType::P,
+            poc_lsb: 2,
+            refs_before: vec![2],
+            ..SliceHeaderSpec::idr()
         }
-        SaoType::Edge => {
-            // Edge offset direction patterns
-            let (dx1, dy1, dx2, dy2): (i32, i32, i32, i32) = match params.eo_class {
-                SaoEoClass::Horizontal  => (-1,  0,  1,  0),
-                SaoEoClass::Vertical    => ( 0, -1,  0,  1),
-                SaoEoClass::Diagonal135 => (-1, -1,  1,  1),
-                SaoEoClass::Diagonal45  => ( 1, -1, -1,  1),
-            };
+        .slice(&params, &picture(TestCu::Merge { merge_idx: 0, level: 5 }));
+        let b = SliceHeaderSpec {
+            nal_type: NalType::TrailN,
+            slice_type: SliceType::B,
+            poc_lsb: 1,
+            refs_before: vec![1],
+            refs_after: vec![1],
+            ..SliceHeaderSpec::idr()
+        }
+        .slice(&params, &picture(TestCu::SKIP));
 
-            for y in y_start..y_end {
-                for x in x_start..x_end {
-                    let nx1 = x as i32 + dx1;
-                    let ny1 = y as i32 + dy1;
-                    let nx2 = x as i32 + dx2;
-                    let ny2 = y as i32 + dy2;
-
-                    // Skip if neighbors outside picture
-                    if nx1 < 0 || nx1 >= pic_w as i32 || ny1 < 0 || ny1 >= pic_h as i32
-                        || nx2 < 0 || nx2 >= pic_w as i32 || ny2 < 0 || ny2 >= pic_h as i32
-                    {
-                        continue;
-                    }
-
-                    let idx = y * pic_w + x;
-                    let sample = src[idx] as i32;
-                    let n1 = src[ny1 as usize * pic_w + nx1 as usize] as i32;
-                    let n2 = src[ny2 as usize * pic_w + nx2 as usize] as i32;
-
-                    let c1 = (sample > n1) as i32 - (sample < n1) as i32;
-                    let c2 = (sample > n2) as i32 - (sample < n2) as i32;
-                    let edge_idx = 2 + c1 + c2; // 0..4
-
-                    let offset = match edge_idx {
-                        0 => params.offsets[0],      // Valley
-                        1 => params.offsets[1],      // Half valley
-                        2 => 0,                       // Flat
-                        3 => -params.offsets[2],     // Half peak
-                        _ => -params.offsets[3],     // Peak
-                    };
-
-                    if offset != 0 {
-                        dst[idx] = (sample + offset).clamp(0, max_val) as u16;
-                    }
-                }
-            }
+        let mut decoder = SequenceDecoder::new();
+        let mut outputs = Vec::new();
+        for access_unit in [vec![params.sps(), params.pps(), idr], vec![p], vec![b]] {
+            let data = annex_b(&access_unit);
+            let nal_units = bitstream::parse_nal_units(&data).unwrap();
+            outputs.push(decoder.decode_access_unit(&nal_units).unwrap());
         }
+        outputs.push(decoder.flush());
+        // Each picture waits for one later picture before it is output
+        assert_eq!(outputs.iter().map(Vec::len).collect::<Vec<_>>(), [0, 1, 1, 1]);
+        let frames: Vec<_> = outputs.into_iter().flatten().collect();
+        // The emptied decoder shares no frame
+        assert!(frames.iter().all(|frame| Arc::strong_count(frame) == 1));
+
+        let luma = |frame: &DecodedFrame| {
+            let first = frame.y_plane[0];
+            assert!(frame.y_plane.iter().all(|&y| y == first));
+            first
+        };
+        let (i, b, p) = (luma(&frames[0]), luma(&frames[1]), luma(&frames[2]));
+        assert_eq!(i, 128);
+        assert!(p > i + 2);
+        assert_eq!(b, (i + p).div_ceil(2));
     }
 }
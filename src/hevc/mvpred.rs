@@ -0,0 +1,392 @@
+//! Motion vector prediction (H.265 section 8.5.3.2)
+//!
+//! Merge mode candidate list construction, advanced motion vector
+//! prediction (AMVP) and temporal motion vector prediction (TMVP).
+
+use super::inter::{MotionField, Mv, PbMotion, RefPicInfo, RefPicLists};
+use super::slice::{PartMode, SliceHeader};
+
+/// Geometry of a prediction block within its coding block
+#[derive(Debug, Clone, Copy)]
+pub struct PbGeom {
+    /// Coding block top-left
+    pub x_cb: u32,
+    pub y_cb: u32,
+    /// Coding block size
+    pub n_cb_s: u32,
+    /// Prediction block top-left
+    pub x_pb: u32,
+    pub y_pb: u32,
+    /// Prediction block size
+    pub w: u32,
+    pub h: u32,
+    /// Partition index within the coding unit
+    pub part_idx: u32,
+    /// Partition mode of the coding unit
+    pub part_mode: PartMode,
+}
+
+/// Combined bi-predictive candidate order (Table 8-7)
+const COMB_IDX: [(usize, usize); 12] = [
+    (0, 1),
+    (1, 0),
+    (0, 2),
+    (2, 0),
+    (1, 2),
+    (2, 1),
+    (0, 3),
+    (3, 0),
+    (1, 3),
+    (3, 1),
+    (2, 3),
+    (3, 2),
+];
+
+/// Maximum number of merge candidates (MaxNumMergeCand upper bound)
+const MAX_MERGE_CANDS: usize = 5;
+
+/// Motion vector predictor for the current slice
+pub struct MvPredictor<'b, 'a> {
+    /// Motion field of the current picture
+    pub motion: &'b MotionField,
+    /// Index of the current slice within `motion`
+    pub slice_idx: u16,
+    /// Reference picture lists of the current slice
+    pub refs: &'b RefPicLists<'a>,
+    /// PicOrderCntVal of the current picture
+    pub cur_poc: i32,
+    /// Current slice header
+    pub header: &'b SliceHeader,
+    /// Picture size in luma samples
+    pub pic_width: u32,
+    pub pic_height: u32,
+    /// CtbLog2SizeY
+    pub log2_ctb_size: u8,
+    /// Log2ParMrgLevel
+    pub log2_par_mrg_level: u8,
+}
+
+/// Scale a motion vector by the ratio of POC distances (8-178 to 8-182)
+fn scale_mv(mv: Mv, td: i32, tb: i32) -> Mv {
+    let td = td.clamp(-128, 127);
+    let tb = tb.clamp(-128, 127);
+    if td == 0 {
+        return mv;
+    }
+    let tx = (16384 + (td.abs() >> 1)) / td;
+    let dsf = ((tb * tx + 32) >> 6).clamp(-4096, 4095);
+    let scale = |v: i32| {
+        let p = dsf * v;
+        (p.signum() * ((p.abs() + 127) >> 8)).clamp(-32768, 32767)
+    };
+    Mv::new(scale(mv.x), scale(mv.y))
+}
+
+impl MvPredictor<'_, '_> {
+    /// Motion of an already decoded inter block in the current slice (6.4.2)
+    fn neighbour(&self, x: i32, y: i32) -> Option<PbMotion> {
+        if x < 0 || y < 0 || x as u32 >= self.pic_width || y as u32 >= self.pic_height {
+            return None;
+        }
+        let (slice, motion) = self.motion.get(x as u32, y as u32)?;
+        (slice == self.slice_idx && motion.is_inter()).then_some(*motion)
+    }
+
+    fn ref_info(&self, list: usize, ref_idx: i8) -> Option<RefPicInfo> {
+        self.refs[list].get(usize::try_from(ref_idx).ok()?).map(|r| r.info())
+    }
+
+    /// Derive merge motion for `merge_idx` (8.5.3.2.2)
+    pub fn merge_motion(&self, pb: PbGeom, merge_idx: u8) -> PbMotion {
+        let (orig_w, orig_h) = (pb.w, pb.h);
+        let mut pb = pb;
+        // singleMCLFlag: all PUs of an 8x8 CU share the 2Nx2N candidate list
+        if self.log2_par_mrg_level > 2 && pb.n_cb_s == 8 {
+            pb.x_pb = pb.x_cb;
+            pb.y_pb = pb.y_cb;
+            pb.w = pb.n_cb_s;
+            pb.h = pb.n_cb_s;
+            pb.part_idx = 0;
+        }
+
+        let mut cands = [PbMotion::default(); MAX_MERGE_CANDS];
+        let mut count = self.spatial_merge_candidates(pb, &mut cands);
+        let max = (self.header.max_num_merge_cand as usize).clamp(1, MAX_MERGE_CANDS);
+
+        // Temporal candidate (only reachable when the spatial list is short)
+        if count < max {
+            let mut col = PbMotion::default();
+            if let Some(mv) = self.temporal_mv(pb, 0, 0) {
+                col.set(0, 0, mv);
+            }
+            if self.header.slice_type.is_b()
+                && let Some(mv) = self.temporal_mv(pb, 1, 0)
+            {
+                col.set(1, 0, mv);
+            }
+            if col.is_inter() && count < MAX_MERGE_CANDS {
+                cands[count] = col;
+                count += 1;
+            }
+        }
+
+        // Combined bi-predictive candidates (8.5.3.2.4)
+        let num_orig = count;
+        if self.header.slice_type.is_b() && num_orig > 1 && num_orig < max {
+            for &(l0, l1) in COMB_IDX.iter().take(num_orig * (num_orig - 1)) {
+                let (c0, c1) = (cands[l0], cands[l1]);
+                if c0.pred_flag[0] && c1.pred_flag[1] {
+                    let p0 = self.ref_info(0, c0.ref_idx[0]).map(|r| r.poc);
+                    let p1 = self.ref_info(1, c1.ref_idx[1]).map(|r| r.poc);
+                    if p0 != p1 || c0.mv[0] != c1.mv[1] {
+                        let mut comb = PbMotion::uni(0, c0.ref_idx[0], c0.mv[0]);
+                        comb.set(1, c1.ref_idx[1], c1.mv[1]);
+                        cands[count] = comb;
+                        count += 1;
+                    }
+                }
+                if count == max {
+                    break;
+                }
+            }
+        }
+
+        // Zero candidates (8.5.3.2.5)
+        let num_ref_idx = if self.header.slice_type.is_b() {
+            self.header.num_ref_idx_active(0).min(self.header.num_ref_idx_active(1))
+        } else {
+            self.header.num_ref_idx_active(0)
+        };
+        let mut zero_idx = 0usize;
+        while count < max {
+            let ref_idx = if zero_idx < num_ref_idx { zero_idx as i8 } else { 0 };
+            let mut zero = PbMotion::uni(0, ref_idx, Mv::default());
+            if self.header.slice_type.is_b() {
+                zero.set(1, ref_idx, Mv::default());
+            }
+            cands[count] = zero;
+            count += 1;
+            zero_idx += 1;
+        }
+
+        let mut motion = cands[(merge_idx as usize).min(count - 1)];
+        // 8x4 and 4x8 blocks are restricted to uni-prediction
+        if motion.pred_flag[0] && motion.pred_flag[1] && orig_w + orig_h == 12 {
+            motion.clear(1);
+        }
+        motion
+    }
+
+    /// Spatial merge candidates A1, B1, B0, A0, B2 (8.5.3.2.3)
+    fn spatial_merge_candidates(&self, pb: PbGeom, cands: &mut [PbMotion; MAX_MERGE_CANDS]) -> usize {
+        let (x, y) = (pb.x_pb as i32, pb.y_pb as i32);
+        let (w, h) = (pb.w as i32, pb.h as i32);
+        let lvl = self.log2_par_mrg_level;
+        // Neighbours in the same merge estimation region are unavailable
+        let same_mer = |xn: i32, yn: i32| (x >> lvl) == (xn >> lvl) && (y >> lvl) == (yn >> lvl);
+        let fetch = |xn: i32, yn: i32| {
+            if same_mer(xn, yn) {
+                None
+            } else {
+                self.neighbour(xn, yn)
+            }
+        };
+
+        let a1 = if pb.part_idx == 1
+            && matches!(pb.part_mode, PartMode::PartNx2N | PartMode::PartnLx2N | PartMode::PartnRx2N)
+        {
+            None
+        } else {
+            fetch(x - 1, y + h - 1)
+        };
+        let b1 = if pb.part_idx == 1
+            && matches!(pb.part_mode, PartMode::Part2NxN | PartMode::Part2NxnU | PartMode::Part2NxnD)
+        {
+            None
+        } else {
+            fetch(x + w - 1, y - 1)
+        };
+        let b0 = fetch(x + w, y - 1);
+        let a0 = fetch(x - 1, y + h);
+        let b2 = fetch(x - 1, y - 1);
+
+        // Pruning compares against neighbour motion even when that
+        // neighbour was itself pruned from the list
+        let differs = |cand: &PbMotion, other: Option<PbMotion>| other != Some(*cand);
+        let b1_flag = b1.filter(|m| differs(m, a1));
+        let b0_flag = b0.filter(|m| differs(m, b1));
+        let a0_flag = a0.filter(|m| differs(m, a1));
+        let b2_flag = if [a0_flag, a1, b0_flag, b1_flag].iter().all(Option::is_some) {
+            None
+        } else {
+            b2.filter(|m| differs(m, a1) && differs(m, b1))
+        };
+
+        let mut count = 0;
+        for cand in [a1, b1_flag, b0_flag, a0_flag, b2_flag].into_iter().flatten() {
+            cands[count] = cand;
+            count += 1;
+        }
+        count
+    }
+
+    /// Derive the luma motion vector predictor for `list` (8.5.3.2.6)
+    pub fn amvp(&self, pb: PbGeom, list: usize, ref_idx: i8, mvp_flag: bool) -> Mv {
+        let (x, y) = (pb.x_pb as i32, pb.y_pb as i32);
+        let (w, h) = (pb.w as i32, pb.h as i32);
+        let Some(target) = self.ref_info(list, ref_idx) else {
+            return Mv::default();
+        };
+        let other = 1 - list;
+
+        // Candidate referring to the same picture, from list X then list Y
+        let same_pic = |m: &PbMotion| -> Option<Mv> {
+            [list, other].into_iter().find_map(|l| {
+                (m.pred_flag[l] && self.ref_info(l, m.ref_idx[l]).map(|r| r.poc) == Some(target.poc))
+                    .then_some(m.mv[l])
+            })
+        };
+        // Candidate with matching long-term marking, scaled when both are short-term
+        let scaled = |m: &PbMotion| -> Option<Mv> {
+            [list, other].into_iter().find_map(|l| {
+                let r = self.ref_info(l, m.ref_idx[l])?;
+                if !m.pred_flag[l] || r.is_long_term != target.is_long_term {
+                    return None;
+                }
+                Some(if r.is_long_term {
+                    m.mv[l]
+                } else {
+                    scale_mv(m.mv[l], self.cur_poc - r.poc, self.cur_poc - target.poc)
+                })
+            })
+        };
+
+        let a_nbs = [self.neighbour(x - 1, y + h), self.neighbour(x - 1, y + h - 1)];
+        let is_scaled = a_nbs.iter().any(Option::is_some);
+        let mut mv_a = a_nbs.iter().flatten().find_map(&same_pic);
+        if mv_a.is_none() {
+            mv_a = a_nbs.iter().flatten().find_map(&scaled);
+        }
+
+        let b_nbs = [
+            self.neighbour(x + w, y - 1),
+            self.neighbour(x + w - 1, y - 1),
+            self.neighbour(x - 1, y - 1),
+        ];
+        let mut mv_b = b_nbs.iter().flatten().find_map(&same_pic);
+        if !is_scaled {
+            if mv_b.is_some() && mv_a.is_none() {
+                mv_a = mv_b;
+            }
+            mv_b = b_nbs.iter().flatten().find_map(&scaled);
+        }
+
+        let mut cands: [Mv; 3] = [Mv::default(); 3];
+        let mut count = 0;
+        if let Some(a) = mv_a {
+            cands[count] = a;
+            count += 1;
+        }
+        if let Some(b) = mv_b
+            && mv_a != Some(b)
+        {
+            cands[count] = b;
+            count += 1;
+        }
+        if count < 2
+            && let Some(col) = self.temporal_mv(pb, list, ref_idx)
+        {
+            cands[count] = col;
+        }
+        cands[mvp_flag as usize]
+    }
+
+    /// Temporal luma motion vector prediction (8.5.3.2.8)
+    fn temporal_mv(&self, pb: PbGeom, list: usize, ref_idx: i8) -> Option<Mv> {
+        if !self.header.slice_temporal_mvp_enabled_flag {
+            return None;
+        }
+        let col_list = if self.header.slice_type.is_b() && !self.header.collocated_from_l0_flag {
+            1
+        } else {
+            0
+        };
+        let col_pic = self.refs[col_list].get(self.header.collocated_ref_idx as usize)?;
+
+        // Bottom-right candidate, restricted to the current CTB row
+        let x_br = pb.x_pb + pb.w;
+        let y_br = pb.y_pb + pb.h;
+        if (pb.y_pb >> self.log2_ctb_size) == (y_br >> self.log2_ctb_size)
+            && y_br < self.pic_height
+            && x_br < self.pic_width
+            && let Some(mv) = self.collocated_mv(col_pic.poc, col_pic.motion, (x_br >> 4) << 4, (y_br >> 4) << 4, list, ref_idx)
+        {
+            return Some(mv);
+        }
+
+        let x_ctr = pb.x_pb + (pb.w >> 1);
+        let y_ctr = pb.y_pb + (pb.h >> 1);
+        self.collocated_mv(col_pic.poc, col_pic.motion, (x_ctr >> 4) << 4, (y_ctr >> 4) << 4, list, ref_idx)
+    }
+
+    /// Collocated motion vector derivation (8.5.3.2.9)
+    fn collocated_mv(
+        &self,
+        col_poc: i32,
+        col_motion: &MotionField,
+        x: u32,
+        y: u32,
+        list: usize,
+        ref_idx: i8,
+    ) -> Option<Mv> {
+        let (col_slice, col) = col_motion.get(x, y)?;
+        if !col.is_inter() {
+            return None;
+        }
+        let col_list = if !col.pred_flag[0] {
+            1
+        } else if !col.pred_flag[1] {
+            0
+        } else if self.no_backward_pred() {
+            list
+        } else {
+            self.header.collocated_from_l0_flag as usize
+        };
+        let col_ref = col_motion.ref_info(col_slice, col_list, col.ref_idx[col_list])?;
+        let target = self.ref_info(list, ref_idx)?;
+        if col_ref.is_long_term != target.is_long_term {
+            return None;
+        }
+
+        let mv = col.mv[col_list];
+        let col_poc_diff = col_poc - col_ref.poc;
+        let cur_poc_diff = self.cur_poc - target.poc;
+        if target.is_long_term || col_poc_diff == cur_poc_diff {
+            Some(mv)
+        } else {
+            Some(scale_mv(mv, col_poc_diff, cur_poc_diff))
+        }
+    }
+
+    /// NoBackwardPredFlag: no reference picture follows the current one
+    fn no_backward_pred(&self) -> bool {
+        self.refs.iter().flatten().all(|r| r.poc <= self.cur_poc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_mv() {
+        // Same distance keeps the vector
+        assert_eq!(scale_mv(Mv::new(16, -8), 2, 2), Mv::new(16, -8));
+        // Half the distance halves the vector
+        assert_eq!(scale_mv(Mv::new(16, -8), 4, 2), Mv::new(8, -4));
+        // Opposite direction flips the sign
+        assert_eq!(scale_mv(Mv::new(10, 3), 1, -1), Mv::new(-10, -3));
+        // Large ratios saturate the scale factor at 16x
+        assert_eq!(scale_mv(Mv::new(1, 0), 1, 100), Mv::new(16, 0));
+    }
+}
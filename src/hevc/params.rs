@@ -1,6 +1,7 @@
 //! HEVC parameter set parsing (VPS, SPS, PPS)
 
 use alloc::string::ToString;
+use alloc::vec;
 use alloc::vec::Vec;
 
 use super::bitstream::BitstreamReader;
@@ -25,6 +26,124 @@ pub struct Vps {
     pub temporal_id_nesting_flag: bool,
     /// Profile tier level
     pub ptl: ProfileTierLevel,
+    /// DPB sizes of the highest sub-layer, inherited by extension SPSs
+    pub dpb_ordering: DpbOrdering,
+    /// Multi-layer extension (MV-HEVC), if present and understood
+    pub extension: Option<VpsExtension>,
+}
+
+/// VPS extension (H.265 F.7.3.2.1.1)
+///
+/// Only the parts needed to decode the layers of a multiview bitstream
+/// are kept. Per-layer vectors are indexed by LayerIdxInVps.
+#[derive(Debug, Clone, Default)]
+pub struct VpsExtension {
+    /// nuh_layer_id of each layer
+    pub layer_id_in_nuh: Vec<u8>,
+    /// ViewId of each layer
+    pub view_id: Vec<u32>,
+    /// direct_dependency_flag[i][j]: layer i may use layer j (j < i) for inter-layer prediction
+    pub direct_dependency_flag: Vec<Vec<bool>>,
+    /// Inter-layer references default to all direct reference layers
+    pub default_ref_layers_active_flag: bool,
+    /// At most one inter-layer reference picture per picture
+    pub max_one_active_ref_layer_flag: bool,
+    /// Slices of IDR pictures in the layer carry no POC LSB
+    pub poc_lsb_not_present_flag: Vec<bool>,
+    /// Representation formats for SPSs that inherit them
+    pub rep_formats: Vec<RepFormat>,
+    /// Representation format index of each layer
+    pub rep_format_idx: Vec<usize>,
+}
+
+impl VpsExtension {
+    /// LayerIdxInVps of a nuh_layer_id
+    pub fn layer_idx(&self, nuh_layer_id: u8) -> Option<usize> {
+        self.layer_id_in_nuh.iter().position(|&id| id == nuh_layer_id)
+    }
+
+    /// Inter-layer decoding properties of a non-base layer
+    pub fn layer_info(&self, nuh_layer_id: u8) -> Option<LayerInfo> {
+        let idx = self.layer_idx(nuh_layer_id)?;
+        let ref_layers = self.direct_dependency_flag[idx]
+            .iter()
+            .enumerate()
+            .filter(|&(_, &dep)| dep)
+            .map(|(j, _)| self.layer_id_in_nuh[j])
+            .collect();
+        Some(LayerInfo {
+            nuh_layer_id,
+            view_id: self.view_id[idx],
+            base_view_id: self.view_id[0],
+            direct_ref_layers: ref_layers,
+            default_ref_layers_active_flag: self.default_ref_layers_active_flag,
+            max_one_active_ref_layer_flag: self.max_one_active_ref_layer_flag,
+            poc_lsb_not_present_flag: self.poc_lsb_not_present_flag[idx],
+        })
+    }
+
+    /// ViewId of a layer, 0 if unknown
+    pub fn view_id_of(&self, nuh_layer_id: u8) -> u32 {
+        self.layer_idx(nuh_layer_id).map_or(0, |idx| self.view_id[idx])
+    }
+}
+
+/// Representation format (H.265 F.7.3.2.1.2)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RepFormat {
+    /// Picture width in luma samples
+    pub pic_width_in_luma_samples: u32,
+    /// Picture height in luma samples
+    pub pic_height_in_luma_samples: u32,
+    /// Chroma format IDC
+    pub chroma_format_idc: u8,
+    /// Separate colour plane flag
+    pub separate_colour_plane_flag: bool,
+    /// Bit depth luma minus 8
+    pub bit_depth_luma_minus8: u8,
+    /// Bit depth chroma minus 8
+    pub bit_depth_chroma_minus8: u8,
+    /// Conformance window offsets (left, right, top, bottom), if present
+    pub conf_win_offset: Option<(u32, u32, u32, u32)>,
+}
+
+/// Decoded picture buffer sizes of the highest sub-layer (sub_layer_ordering_info)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DpbOrdering {
+    /// max_dec_pic_buffering_minus1: DPB capacity in pictures, minus 1
+    pub max_dec_pic_buffering_minus1: u32,
+    /// max_num_reorder_pics: pictures that may precede any picture in
+    /// decoding order and follow it in output order
+    pub max_num_reorder_pics: u32,
+    /// max_latency_increase_plus1 (0: no latency limit)
+    pub max_latency_increase_plus1: u32,
+}
+
+impl DpbOrdering {
+    /// SpsMaxLatencyPictures, if the latency is limited (7.4.3.2.1)
+    pub fn max_latency_pictures(&self) -> Option<u32> {
+        (self.max_latency_increase_plus1 != 0)
+            .then(|| self.max_num_reorder_pics + self.max_latency_increase_plus1 - 1)
+    }
+}
+
+/// What the slice header parser needs to know about a non-base layer
+#[derive(Debug, Clone, Default)]
+pub struct LayerInfo {
+    /// nuh_layer_id of the layer
+    pub nuh_layer_id: u8,
+    /// ViewId of the layer
+    pub view_id: u32,
+    /// ViewId of the base layer
+    pub base_view_id: u32,
+    /// nuh_layer_id of each direct reference layer, in increasing order
+    pub direct_ref_layers: Vec<u8>,
+    /// default_ref_layers_active_flag from the VPS extension
+    pub default_ref_layers_active_flag: bool,
+    /// max_one_active_ref_layer_flag from the VPS extension
+    pub max_one_active_ref_layer_flag: bool,
+    /// poc_lsb_not_present_flag of the layer
+    pub poc_lsb_not_present_flag: bool,
 }
 
 /// Sequence Parameter Set
@@ -60,6 +179,8 @@ pub struct Sps {
     pub log2_max_pic_order_cnt_lsb_minus4: u8,
     /// Sub-layer ordering info present flag
     pub sub_layer_ordering_info_present_flag: bool,
+    /// DPB sizes of the highest sub-layer
+    pub dpb_ordering: DpbOrdering,
     /// Log2 min luma coding block size minus 3
     pub log2_min_luma_coding_block_size_minus3: u8,
     /// Log2 diff max min luma coding block size
@@ -84,8 +205,14 @@ pub struct Sps {
     pub pcm_params: Option<PcmParams>,
     /// Number of short-term reference picture sets
     pub num_short_term_ref_pic_sets: u8,
+    /// Short-term reference picture sets
+    pub st_ref_pic_sets: Vec<ShortTermRefPicSet>,
     /// Long-term reference pictures present flag
     pub long_term_ref_pics_present_flag: bool,
+    /// Candidate long-term reference picture POC LSBs
+    pub lt_ref_pic_poc_lsb_sps: Vec<u32>,
+    /// Whether each candidate long-term picture is used by the current picture
+    pub used_by_curr_pic_lt_sps_flag: Vec<bool>,
     /// Temporal MVP enabled flag
     pub sps_temporal_mvp_enabled_flag: bool,
     /// Strong intra smoothing enabled flag
@@ -152,6 +279,38 @@ impl Sps {
     }
 }
 
+/// Short-term reference picture set (H.265 7.3.7 / 7.4.8)
+///
+/// Stored in derived form: POC deltas relative to the current picture,
+/// with inter-RPS prediction already resolved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShortTermRefPicSet {
+    /// DeltaPocS0: negative POC deltas, closest first
+    pub delta_poc_s0: Vec<i32>,
+    /// UsedByCurrPicS0
+    pub used_by_curr_pic_s0: Vec<bool>,
+    /// DeltaPocS1: positive POC deltas, closest first
+    pub delta_poc_s1: Vec<i32>,
+    /// UsedByCurrPicS1
+    pub used_by_curr_pic_s1: Vec<bool>,
+}
+
+impl ShortTermRefPicSet {
+    /// NumDeltaPocs
+    pub fn num_delta_pocs(&self) -> usize {
+        self.delta_poc_s0.len() + self.delta_poc_s1.len()
+    }
+
+    /// Number of entries used by the current picture
+    pub fn num_used_by_curr(&self) -> usize {
+        self.used_by_curr_pic_s0
+            .iter()
+            .chain(&self.used_by_curr_pic_s1)
+            .filter(|&&used| used)
+            .count()
+    }
+}
+
 /// PCM parameters
 #[derive(Debug, Clone)]
 pub struct PcmParams {
@@ -308,6 +467,29 @@ pub struct TileInfo {
     pub loop_filter_across_tiles_enabled_flag: bool,
 }
 
+impl TileInfo {
+    /// Check that the tile layout fits a picture of the given SPS (H.265 7.4.3.3)
+    ///
+    /// Each tile must be at least one CTB wide and high, so explicit sizes
+    /// have to leave room for the last column and row.
+    pub fn validate(&self, sps: &Sps) -> Result<()> {
+        let fits = |count_minus1: u16, sizes: &[u16], pic_size_in_ctbs: u32| {
+            if u32::from(count_minus1) >= pic_size_in_ctbs {
+                return false;
+            }
+            let explicit: u32 = sizes.iter().map(|&size| u32::from(size) + 1).sum();
+            self.uniform_spacing_flag || explicit < pic_size_in_ctbs
+        };
+        if !fits(self.num_tile_columns_minus1, &self.column_widths, sps.pic_width_in_ctbs()) {
+            return Err(HevcError::InvalidBitstream("tile columns exceed picture width"));
+        }
+        if !fits(self.num_tile_rows_minus1, &self.row_heights, sps.pic_height_in_ctbs()) {
+            return Err(HevcError::InvalidBitstream("tile rows exceed picture height"));
+        }
+        Ok(())
+    }
+}
+
 /// Profile tier level information
 #[derive(Debug, Clone, Default)]
 pub struct ProfileTierLevel {
@@ -353,6 +535,21 @@ pub fn parse_vps(data: &[u8]) -> Result<Vps> {
 
     let ptl = parse_profile_tier_level(&mut reader, true, max_sub_layers_minus1)?;
 
+    // The rest only matters for non-base layers; a VPS whose extension we
+    // cannot follow still describes a decodable base layer
+    let ordering = parse_sub_layer_ordering(&mut reader, max_sub_layers_minus1).ok();
+    let extension = ordering.and_then(|_| {
+        parse_vps_extension(
+            &mut reader,
+            base_layer_internal_flag,
+            max_layers_minus1,
+            max_sub_layers_minus1,
+        )
+        .ok()
+        .flatten()
+    });
+    let dpb_ordering = ordering.map(|(_, ordering)| ordering).unwrap_or_default();
+
     Ok(Vps {
         vps_id,
         base_layer_internal_flag,
@@ -361,71 +558,507 @@ pub fn parse_vps(data: &[u8]) -> Result<Vps> {
         max_sub_layers_minus1,
         temporal_id_nesting_flag,
         ptl,
+        dpb_ordering,
+        extension,
     })
 }
 
-/// Parse Sequence Parameter Set
-pub fn parse_sps(data: &[u8]) -> Result<Sps> {
-    let mut reader = BitstreamReader::new(data);
+/// Parse sub_layer_ordering_info, returning its present flag and the
+/// values of the highest sub-layer
+///
+/// Values beyond the limits of 7.4.3.2.1 are clamped to them rather than
+/// rejected, since they only size the output queue.
+fn parse_sub_layer_ordering(
+    reader: &mut BitstreamReader<'_>,
+    max_sub_layers_minus1: u8,
+) -> Result<(bool, DpbOrdering)> {
+    let present = reader.read_bit()? != 0;
+    let start = if present { 0 } else { max_sub_layers_minus1 };
+    let mut ordering = DpbOrdering::default();
+    for _ in start..=max_sub_layers_minus1 {
+        // MaxDpbSize is at most 16
+        let max_dec_pic_buffering_minus1 = reader.read_ue()?.min(15);
+        let max_num_reorder_pics = reader.read_ue()?.min(max_dec_pic_buffering_minus1);
+        let max_latency_increase_plus1 = reader.read_ue()?;
+        ordering = DpbOrdering {
+            max_dec_pic_buffering_minus1,
+            max_num_reorder_pics,
+            max_latency_increase_plus1,
+        };
+    }
+    Ok((present, ordering))
+}
 
-    let vps_id = reader.read_bits(4)? as u8;
-    let max_sub_layers_minus1 = reader.read_bits(3)? as u8;
-    let temporal_id_nesting_flag = reader.read_bit()? != 0;
+/// Parse the VPS from the layer sets through the extension
+///
+/// Returns `None` if the VPS has no extension. Layer sets added in the
+/// extension (num_add_layer_sets) are not supported.
+fn parse_vps_extension(
+    reader: &mut BitstreamReader<'_>,
+    base_layer_internal_flag: bool,
+    max_layers_minus1: u8,
+    max_sub_layers_minus1: u8,
+) -> Result<Option<VpsExtension>> {
+    let unsupported = |msg: &str| HevcError::InvalidParameterSet {
+        kind: "VPS",
+        msg: msg.to_string(),
+    };
 
-    let ptl = parse_profile_tier_level(&mut reader, true, max_sub_layers_minus1)?;
+    let max_layer_id = reader.read_bits(6)? as usize;
+    let num_layer_sets_minus1 = reader.read_ue()? as usize;
+    if num_layer_sets_minus1 > 1023 {
+        return Err(unsupported("too many layer sets"));
+    }
+    // LayerSetLayerIdList: set 0 is the base layer alone
+    let mut layer_sets = vec![vec![0u8]];
+    for _ in 1..=num_layer_sets_minus1 {
+        let mut ids = Vec::new();
+        for j in 0..=max_layer_id {
+            if reader.read_bit()? != 0 {
+                ids.push(j as u8);
+            }
+        }
+        layer_sets.push(ids);
+    }
+
+    if reader.read_bit()? != 0 {
+        // vps_timing_info_present_flag
+        reader.read_bits(32)?; // vps_num_units_in_tick
+        reader.read_bits(32)?; // vps_time_scale
+        if reader.read_bit()? != 0 {
+            reader.read_ue()?; // vps_num_ticks_poc_diff_one_minus1
+        }
+        let num_hrd_parameters = reader.read_ue()?;
+        if num_hrd_parameters > 1024 {
+            return Err(unsupported("too many HRD parameter sets"));
+        }
+        for i in 0..num_hrd_parameters {
+            reader.read_ue()?; // hrd_layer_set_idx
+            let cprms_present_flag = i == 0 || reader.read_bit()? != 0;
+            skip_hrd_parameters(reader, cprms_present_flag, max_sub_layers_minus1)?;
+        }
+    }
 
-    let sps_id = reader.read_ue()? as u8;
-    let chroma_format_idc = reader.read_ue()? as u8;
+    if reader.read_bit()? == 0 {
+        return Ok(None);
+    }
+    while !reader.is_byte_aligned() {
+        reader.read_bit()?; // vps_extension_alignment_bit_equal_to_one
+    }
 
-    let separate_colour_plane_flag = if chroma_format_idc == 3 {
-        reader.read_bit()? != 0
+    // F.7.3.2.1.1 vps_extension()
+    let num_layers = max_layers_minus1.min(62) as usize + 1;
+    if max_layers_minus1 > 0 && base_layer_internal_flag {
+        parse_profile_tier_level(reader, false, max_sub_layers_minus1)?;
+    }
+
+    let splitting_flag = reader.read_bit()? != 0;
+    let mut scalability_mask_flag = [false; 16];
+    for flag in &mut scalability_mask_flag {
+        *flag = reader.read_bit()? != 0;
+    }
+    let num_scalability_types = scalability_mask_flag.iter().filter(|&&f| f).count();
+    if num_scalability_types == 0 && splitting_flag {
+        return Err(unsupported("splitting without scalability types"));
+    }
+    let mut dimension_id_len = Vec::with_capacity(num_scalability_types);
+    for _ in 0..num_scalability_types - splitting_flag as usize {
+        dimension_id_len.push(reader.read_bits(3)? as u8 + 1);
+    }
+    if splitting_flag {
+        // The last dimension takes the remaining bits of nuh_layer_id
+        let used: u8 = dimension_id_len.iter().sum();
+        if used > 6 {
+            return Err(unsupported("dimension IDs longer than nuh_layer_id"));
+        }
+        dimension_id_len.push(6 - used);
+    }
+
+    let nuh_layer_id_present_flag = reader.read_bit()? != 0;
+    let mut layer_id_in_nuh = vec![0u8; num_layers];
+    let mut dimension_id = vec![vec![0u32; num_scalability_types]; num_layers];
+    for i in 1..num_layers {
+        layer_id_in_nuh[i] = if nuh_layer_id_present_flag {
+            reader.read_bits(6)? as u8
+        } else {
+            i as u8
+        };
+        if layer_id_in_nuh[i] <= layer_id_in_nuh[i - 1] {
+            return Err(unsupported("layer IDs not increasing"));
+        }
+        let mut offset = 0;
+        for (j, &len) in dimension_id_len.iter().enumerate() {
+            dimension_id[i][j] = if splitting_flag {
+                (layer_id_in_nuh[i] as u32 >> offset) & ((1 << len) - 1)
+            } else {
+                reader.read_bits(len)?
+            };
+            offset += len;
+        }
+    }
+
+    // ViewOrderIdx is the scalability dimension with mask index 1 (multiview)
+    let view_order_idx: Vec<u32> = if scalability_mask_flag[1] {
+        let dim = scalability_mask_flag[..1].iter().filter(|&&f| f).count();
+        dimension_id.iter().map(|ids| ids[dim]).collect()
     } else {
-        false
+        vec![0; num_layers]
     };
+    let num_views = view_order_idx
+        .iter()
+        .enumerate()
+        .filter(|&(i, v)| !view_order_idx[..i].contains(v))
+        .count();
+
+    let view_id_len = reader.read_bits(4)? as u8;
+    let mut view_id_val = vec![0u32; num_views];
+    if view_id_len > 0 {
+        for val in &mut view_id_val {
+            *val = reader.read_bits(view_id_len)?;
+        }
+    }
+    let view_id = view_order_idx
+        .iter()
+        .map(|&v| view_id_val.get(v as usize).copied().unwrap_or(v))
+        .collect();
+
+    let mut direct_dependency_flag = vec![Vec::new(); num_layers];
+    for (i, deps) in direct_dependency_flag.iter_mut().enumerate().skip(1) {
+        for _ in 0..i {
+            deps.push(reader.read_bit()? != 0);
+        }
+    }
+    let num_direct_ref_layers: Vec<usize> = direct_dependency_flag
+        .iter()
+        .map(|deps| deps.iter().filter(|&&d| d).count())
+        .collect();
+    // DependencyFlag: direct dependencies closed transitively
+    let mut dependency_flag = direct_dependency_flag.clone();
+    for i in 1..num_layers {
+        for j in 0..i {
+            if dependency_flag[i][j] {
+                let indirect = dependency_flag[j].clone();
+                for (dep, &flag) in dependency_flag[i].iter_mut().zip(&indirect[..j]) {
+                    *dep |= flag;
+                }
+            }
+        }
+    }
 
-    let pic_width_in_luma_samples = reader.read_ue()?;
-    let pic_height_in_luma_samples = reader.read_ue()?;
+    let num_independent_layers = num_direct_ref_layers.iter().filter(|&&n| n == 0).count();
+    if num_independent_layers > 1 && reader.read_ue()? > 0 {
+        return Err(unsupported("additional layer sets"));
+    }
+
+    if reader.read_bit()? != 0 {
+        // vps_sub_layers_max_minus1_present_flag
+        for _ in 0..num_layers {
+            reader.read_bits(3)?;
+        }
+    }
+    if reader.read_bit()? != 0 {
+        // max_tid_ref_present_flag
+        for i in 0..num_layers - 1 {
+            for deps in &direct_dependency_flag[i + 1..] {
+                if deps[i] {
+                    reader.read_bits(3)?; // max_tid_il_ref_pics_plus1
+                }
+            }
+        }
+    }
+    let default_ref_layers_active_flag = reader.read_bit()? != 0;
 
-    let conformance_window_flag = reader.read_bit()? != 0;
-    let conf_win_offset = if conformance_window_flag {
-        let left = reader.read_ue()?;
-        let right = reader.read_ue()?;
-        let top = reader.read_ue()?;
-        let bottom = reader.read_ue()?;
-        (left, right, top, bottom)
+    let num_profile_tier_level_minus1 = reader.read_ue()? as usize;
+    if num_profile_tier_level_minus1 > 63 {
+        return Err(unsupported("too many profile/tier/level entries"));
+    }
+    let first_ptl = if base_layer_internal_flag { 2 } else { 1 };
+    for _ in first_ptl..=num_profile_tier_level_minus1 {
+        let profile_present_flag = reader.read_bit()? != 0;
+        parse_profile_tier_level(reader, profile_present_flag, max_sub_layers_minus1)?;
+    }
+
+    // Output layer sets: only parsed to reach the fields after them
+    let num_layer_sets = layer_sets.len();
+    let (num_add_olss, default_output_layer_idc) = if num_layer_sets > 1 {
+        let num_add_olss = reader.read_ue()? as usize;
+        if num_add_olss > 1023 {
+            return Err(unsupported("too many output layer sets"));
+        }
+        (num_add_olss, reader.read_bits(2)?.min(2))
     } else {
-        (0, 0, 0, 0)
+        (0, 0)
     };
+    let layer_idx = |nuh_id: u8| layer_id_in_nuh.iter().position(|&id| id == nuh_id);
+    for i in 1..num_layer_sets + num_add_olss {
+        let ls_idx = if i >= num_layer_sets {
+            let bits = ceil_log2(num_layer_sets as u32 - 1);
+            let idx = if num_layer_sets > 2 { reader.read_bits(bits)? as usize + 1 } else { 1 };
+            idx.min(num_layer_sets - 1)
+        } else {
+            i
+        };
+        let set: Vec<usize> = layer_sets[ls_idx]
+            .iter()
+            .map(|&id| layer_idx(id).ok_or_else(|| unsupported("unknown layer in layer set")))
+            .collect::<Result<_>>()?;
+
+        let output_layer_flag: Vec<bool> = if i > num_layer_sets_minus1 || default_output_layer_idc == 2 {
+            set.iter().map(|_| reader.read_bit().map(|b| b != 0)).collect::<Result<_>>()?
+        } else if default_output_layer_idc == 0 {
+            vec![true; set.len()]
+        } else {
+            let highest = set.iter().copied().max();
+            set.iter().map(|&l| Some(l) == highest).collect()
+        };
 
-    let bit_depth_luma_minus8 = reader.read_ue()? as u8;
-    let bit_depth_chroma_minus8 = reader.read_ue()? as u8;
-    let log2_max_pic_order_cnt_lsb_minus4 = reader.read_ue()? as u8;
+        // NecessaryLayerFlag: output layers and everything they depend on
+        let necessary: Vec<bool> = set
+            .iter()
+            .map(|&l| {
+                set.iter()
+                    .zip(&output_layer_flag)
+                    .any(|(&o, &out)| out && (o == l || (o > l && dependency_flag[o][l])))
+            })
+            .collect();
+        if num_profile_tier_level_minus1 > 0 {
+            let bits = ceil_log2(num_profile_tier_level_minus1 as u32 + 1);
+            for _ in necessary.iter().filter(|&&n| n) {
+                reader.read_bits(bits)?; // profile_tier_level_idx
+            }
+        }
 
-    let sub_layer_ordering_info_present_flag = reader.read_bit()? != 0;
+        let outputs: Vec<usize> = set
+            .iter()
+            .zip(&output_layer_flag)
+            .filter(|&(_, &out)| out)
+            .map(|(&l, _)| l)
+            .collect();
+        if let &[only] = outputs.as_slice()
+            && num_direct_ref_layers[only] > 0
+        {
+            reader.read_bit()?; // alt_output_layer_flag
+        }
+    }
 
-    // Skip sub-layer ordering info
-    let start = if sub_layer_ordering_info_present_flag {
-        0
+    let num_rep_formats = reader.read_ue()? as usize + 1;
+    if num_rep_formats > 256 {
+        return Err(unsupported("too many representation formats"));
+    }
+    let mut rep_formats: Vec<RepFormat> = Vec::with_capacity(num_rep_formats);
+    for _ in 0..num_rep_formats {
+        let mut rep = RepFormat {
+            pic_width_in_luma_samples: reader.read_bits(16)?,
+            pic_height_in_luma_samples: reader.read_bits(16)?,
+            ..rep_formats.last().copied().unwrap_or_default()
+        };
+        if reader.read_bit()? != 0 {
+            rep.chroma_format_idc = reader.read_bits(2)? as u8;
+            rep.separate_colour_plane_flag = rep.chroma_format_idc == 3 && reader.read_bit()? != 0;
+            rep.bit_depth_luma_minus8 = reader.read_bits(4)? as u8;
+            rep.bit_depth_chroma_minus8 = reader.read_bits(4)? as u8;
+        } else if rep_formats.is_empty() {
+            return Err(unsupported("first representation format lacks chroma format"));
+        }
+        rep.conf_win_offset = if reader.read_bit()? != 0 {
+            Some((reader.read_ue()?, reader.read_ue()?, reader.read_ue()?, reader.read_ue()?))
+        } else {
+            None
+        };
+        rep_formats.push(rep);
+    }
+    let rep_format_idx_present_flag = num_rep_formats > 1 && reader.read_bit()? != 0;
+    let mut rep_format_idx: Vec<usize> = (0..num_layers).map(|i| i.min(num_rep_formats - 1)).collect();
+    if rep_format_idx_present_flag {
+        let bits = ceil_log2(num_rep_formats as u32);
+        let first = if base_layer_internal_flag { 1 } else { 0 };
+        for idx in &mut rep_format_idx[first..] {
+            *idx = (reader.read_bits(bits)? as usize).min(num_rep_formats - 1);
+        }
+    }
+
+    let max_one_active_ref_layer_flag = reader.read_bit()? != 0;
+    reader.read_bit()?; // vps_poc_lsb_aligned_flag
+    let mut poc_lsb_not_present_flag = vec![false; num_layers];
+    for i in 1..num_layers {
+        if num_direct_ref_layers[i] == 0 {
+            poc_lsb_not_present_flag[i] = reader.read_bit()? != 0;
+        }
+    }
+
+    Ok(Some(VpsExtension {
+        layer_id_in_nuh,
+        view_id,
+        direct_dependency_flag,
+        default_ref_layers_active_flag,
+        max_one_active_ref_layer_flag,
+        poc_lsb_not_present_flag,
+        rep_formats,
+        rep_format_idx,
+    }))
+}
+
+/// Ceil(Log2(n)) for the bit widths of u(v) syntax elements
+fn ceil_log2(n: u32) -> u8 {
+    if n <= 1 { 0 } else { (32 - (n - 1).leading_zeros()) as u8 }
+}
+
+/// Parse Sequence Parameter Set
+pub fn parse_sps(data: &[u8]) -> Result<Sps> {
+    parse_layer_sps(data, 0, None)
+}
+
+/// Parse a Sequence Parameter Set carried in a layer (H.265 F.7.3.2.2.1)
+///
+/// SPSs of non-base layers may inherit their sub-layer count and
+/// representation format from the VPS extension, so `vps` is required for
+/// those.
+pub fn parse_layer_sps(data: &[u8], nuh_layer_id: u8, vps: Option<&Vps>) -> Result<Sps> {
+    let mut reader = BitstreamReader::new(data);
+
+    let vps_id = reader.read_bits(4)? as u8;
+    let ext_or_max_sub_layers_minus1 = reader.read_bits(3)? as u8;
+    let multi_layer_ext_sps_flag = nuh_layer_id != 0 && ext_or_max_sub_layers_minus1 == 7;
+
+    let (max_sub_layers_minus1, temporal_id_nesting_flag, ptl) = if multi_layer_ext_sps_flag {
+        let vps = vps.ok_or(HevcError::MissingParameterSet("VPS"))?;
+        (vps.max_sub_layers_minus1, vps.temporal_id_nesting_flag, ProfileTierLevel::default())
     } else {
-        max_sub_layers_minus1
+        let temporal_id_nesting_flag = reader.read_bit()? != 0;
+        let ptl = parse_profile_tier_level(&mut reader, true, ext_or_max_sub_layers_minus1)?;
+        (ext_or_max_sub_layers_minus1, temporal_id_nesting_flag, ptl)
     };
-    for _ in start..=max_sub_layers_minus1 {
-        let _max_dec_pic_buffering_minus1 = reader.read_ue()?;
-        let _max_num_reorder_pics = reader.read_ue()?;
-        let _max_latency_increase_plus1 = reader.read_ue()?;
+
+    let sps_id = read_ue_max(&mut reader, 15, "sps_seq_parameter_set_id out of range")? as u8;
+
+    let rep = if multi_layer_ext_sps_flag {
+        let ext = vps
+            .and_then(|vps| vps.extension.as_ref())
+            .ok_or(HevcError::MissingParameterSet("VPS extension"))?;
+        let update_rep_format_flag = reader.read_bit()? != 0;
+        let idx = if update_rep_format_flag {
+            reader.read_bits(8)? as usize
+        } else {
+            ext.layer_idx(nuh_layer_id)
+                .map_or(0, |layer| ext.rep_format_idx[layer])
+        };
+        *ext.rep_formats
+            .get(idx)
+            .ok_or(HevcError::InvalidBitstream("representation format index out of range"))?
+    } else {
+        let chroma_format_idc =
+            read_ue_max(&mut reader, 3, "chroma_format_idc out of range")? as u8;
+        let separate_colour_plane_flag = chroma_format_idc == 3 && reader.read_bit()? != 0;
+        let pic_width_in_luma_samples = reader.read_ue()?;
+        let pic_height_in_luma_samples = reader.read_ue()?;
+
+        let conformance_window_flag = reader.read_bit()? != 0;
+        let conf_win_offset = if conformance_window_flag {
+            let left = reader.read_ue()?;
+            let right = reader.read_ue()?;
+            let top = reader.read_ue()?;
+            let bottom = reader.read_ue()?;
+            Some((left, right, top, bottom))
+        } else {
+            None
+        };
+
+        let bit_depth_luma_minus8 =
+            read_ue_max(&mut reader, 8, "bit_depth_luma_minus8 out of range")? as u8;
+        let bit_depth_chroma_minus8 =
+            read_ue_max(&mut reader, 8, "bit_depth_chroma_minus8 out of range")? as u8;
+        RepFormat {
+            pic_width_in_luma_samples,
+            pic_height_in_luma_samples,
+            chroma_format_idc,
+            separate_colour_plane_flag,
+            bit_depth_luma_minus8,
+            bit_depth_chroma_minus8,
+            conf_win_offset,
+        }
+    };
+    let RepFormat {
+        pic_width_in_luma_samples,
+        pic_height_in_luma_samples,
+        chroma_format_idc,
+        separate_colour_plane_flag,
+        bit_depth_luma_minus8,
+        bit_depth_chroma_minus8,
+        ..
+    } = rep;
+    // Representation formats from the VPS extension use fixed-width fields
+    if bit_depth_luma_minus8 > 8 || bit_depth_chroma_minus8 > 8 {
+        return Err(HevcError::InvalidBitstream("bit depth out of range"));
     }
+    let conformance_window_flag = rep.conf_win_offset.is_some();
+    let conf_win_offset = rep.conf_win_offset.unwrap_or_default();
+
+    let log2_max_pic_order_cnt_lsb_minus4 =
+        read_ue_max(&mut reader, 12, "log2_max_pic_order_cnt_lsb_minus4 out of range")? as u8;
 
-    let log2_min_luma_coding_block_size_minus3 = reader.read_ue()? as u8;
-    let log2_diff_max_min_luma_coding_block_size = reader.read_ue()? as u8;
-    let log2_min_luma_transform_block_size_minus2 = reader.read_ue()? as u8;
-    let log2_diff_max_min_luma_transform_block_size = reader.read_ue()? as u8;
-    let max_transform_hierarchy_depth_inter = reader.read_ue()? as u8;
-    let max_transform_hierarchy_depth_intra = reader.read_ue()? as u8;
+    // Sub-layer ordering info (inherited from the VPS by extension SPSs)
+    let (sub_layer_ordering_info_present_flag, dpb_ordering) = if multi_layer_ext_sps_flag {
+        (false, vps.map(|vps| vps.dpb_ordering).unwrap_or_default())
+    } else {
+        parse_sub_layer_ordering(&mut reader, max_sub_layers_minus1)?
+    };
+
+    // Block sizes (7.4.3.2.1): CTBs of 8..64, transform blocks of 4..32
+    // that are smaller than the minimum coding block
+    let log2_min_luma_coding_block_size_minus3 =
+        read_ue_max(&mut reader, 3, "coding block size out of range")? as u8;
+    let log2_diff_max_min_luma_coding_block_size =
+        read_ue_max(&mut reader, 3, "coding block size out of range")? as u8;
+    let log2_min_cb_size = log2_min_luma_coding_block_size_minus3 + 3;
+    let log2_ctb_size = log2_min_cb_size + log2_diff_max_min_luma_coding_block_size;
+    if log2_ctb_size > 6 {
+        return Err(HevcError::InvalidBitstream("coding block size out of range"));
+    }
+    let min_cb_size = 1u32 << log2_min_cb_size;
+    if pic_width_in_luma_samples == 0
+        || pic_height_in_luma_samples == 0
+        || !pic_width_in_luma_samples.is_multiple_of(min_cb_size)
+        || !pic_height_in_luma_samples.is_multiple_of(min_cb_size)
+    {
+        return Err(HevcError::InvalidBitstream(
+            "picture size is not a multiple of the coding block size",
+        ));
+    }
+    // The conformance window must leave at least one sample (7.4.3.2.1)
+    let (sub_width_c, sub_height_c) = match chroma_format_idc {
+        1 => (2, 2),
+        2 => (2, 1),
+        _ => (1, 1),
+    };
+    let (left, right, top, bottom) = conf_win_offset;
+    if sub_width_c * (u64::from(left) + u64::from(right)) >= u64::from(pic_width_in_luma_samples)
+        || sub_height_c * (u64::from(top) + u64::from(bottom))
+            >= u64::from(pic_height_in_luma_samples)
+    {
+        return Err(HevcError::InvalidBitstream("conformance window larger than the picture"));
+    }
+    let log2_min_luma_transform_block_size_minus2 =
+        read_ue_max(&mut reader, 3, "transform block size out of range")? as u8;
+    let log2_diff_max_min_luma_transform_block_size =
+        read_ue_max(&mut reader, 3, "transform block size out of range")? as u8;
+    let log2_min_tb_size = log2_min_luma_transform_block_size_minus2 + 2;
+    let log2_max_tb_size = log2_min_tb_size + log2_diff_max_min_luma_transform_block_size;
+    if log2_min_tb_size >= log2_min_cb_size || log2_max_tb_size > log2_ctb_size.min(5) {
+        return Err(HevcError::InvalidBitstream("transform block size out of range"));
+    }
+    let max_depth = u32::from(log2_ctb_size - log2_min_tb_size);
+    let max_transform_hierarchy_depth_inter =
+        read_ue_max(&mut reader, max_depth, "max_transform_hierarchy_depth out of range")? as u8;
+    let max_transform_hierarchy_depth_intra =
+        read_ue_max(&mut reader, max_depth, "max_transform_hierarchy_depth out of range")? as u8;
 
     let scaling_list_enabled_flag = reader.read_bit()? != 0;
     if scaling_list_enabled_flag {
-        let scaling_list_data_present = reader.read_bit()? != 0;
-        if scaling_list_data_present {
+        // sps_infer_scaling_list_flag: the lists come from a reference layer
+        let infer_scaling_list = multi_layer_ext_sps_flag && reader.read_bit()? != 0;
+        if infer_scaling_list {
+            let _sps_scaling_list_ref_layer_id = reader.read_bits(6)?;
+        } else if reader.read_bit()? != 0 {
             // Skip scaling list data (complex, rarely used for photos)
             skip_scaling_list_data(&mut reader)?;
         }
@@ -438,8 +1071,16 @@ pub fn parse_sps(data: &[u8]) -> Result<Sps> {
     let pcm_params = if pcm_enabled_flag {
         let pcm_sample_bit_depth_luma_minus1 = reader.read_bits(4)? as u8;
         let pcm_sample_bit_depth_chroma_minus1 = reader.read_bits(4)? as u8;
-        let log2_min_pcm_luma_coding_block_size_minus3 = reader.read_ue()? as u8;
-        let log2_diff_max_min_pcm_luma_coding_block_size = reader.read_ue()? as u8;
+        let log2_min_pcm_luma_coding_block_size_minus3 =
+            read_ue_max(&mut reader, 2, "PCM block size out of range")? as u8;
+        let log2_diff_max_min_pcm_luma_coding_block_size =
+            read_ue_max(&mut reader, 2, "PCM block size out of range")? as u8;
+        let log2_max_pcm_size = log2_min_pcm_luma_coding_block_size_minus3
+            + 3
+            + log2_diff_max_min_pcm_luma_coding_block_size;
+        if log2_max_pcm_size > log2_ctb_size.min(5) {
+            return Err(HevcError::InvalidBitstream("PCM block size out of range"));
+        }
         let pcm_loop_filter_disabled_flag = reader.read_bit()? != 0;
         Some(PcmParams {
             pcm_sample_bit_depth_luma_minus1,
@@ -452,19 +1093,28 @@ pub fn parse_sps(data: &[u8]) -> Result<Sps> {
         None
     };
 
-    let num_short_term_ref_pic_sets = reader.read_ue()? as u8;
-    // Skip short term ref pic sets (not needed for still images)
+    let num_short_term_ref_pic_sets = reader.read_ue()?;
+    if num_short_term_ref_pic_sets > 64 {
+        return Err(HevcError::InvalidBitstream("too many short-term ref pic sets"));
+    }
+    let num_short_term_ref_pic_sets = num_short_term_ref_pic_sets as u8;
+    let mut st_ref_pic_sets = Vec::with_capacity(num_short_term_ref_pic_sets as usize);
     for i in 0..num_short_term_ref_pic_sets {
-        skip_short_term_ref_pic_set(&mut reader, i, num_short_term_ref_pic_sets)?;
+        let rps = parse_short_term_ref_pic_set(&mut reader, i, &st_ref_pic_sets)?;
+        st_ref_pic_sets.push(rps);
     }
 
     let long_term_ref_pics_present_flag = reader.read_bit()? != 0;
+    let mut lt_ref_pic_poc_lsb_sps = Vec::new();
+    let mut used_by_curr_pic_lt_sps_flag = Vec::new();
     if long_term_ref_pics_present_flag {
         let num_long_term_ref_pics_sps = reader.read_ue()?;
+        if num_long_term_ref_pics_sps > 32 {
+            return Err(HevcError::InvalidBitstream("too many long-term ref pics"));
+        }
         for _ in 0..num_long_term_ref_pics_sps {
-            let _lt_ref_pic_poc_lsb_sps =
-                reader.read_bits(log2_max_pic_order_cnt_lsb_minus4 + 4)?;
-            let _used_by_curr_pic_lt_sps_flag = reader.read_bit()?;
+            lt_ref_pic_poc_lsb_sps.push(reader.read_bits(log2_max_pic_order_cnt_lsb_minus4 + 4)?);
+            used_by_curr_pic_lt_sps_flag.push(reader.read_bit()? != 0);
         }
     }
 
@@ -473,7 +1123,7 @@ pub fn parse_sps(data: &[u8]) -> Result<Sps> {
 
     let vui_parameters_present_flag = reader.read_bit()? != 0;
     let vui_parameters = if vui_parameters_present_flag {
-        Some(parse_vui_parameters(&mut reader)?)
+        Some(parse_vui_parameters(&mut reader, max_sub_layers_minus1)?)
     } else {
         None
     };
@@ -494,6 +1144,7 @@ pub fn parse_sps(data: &[u8]) -> Result<Sps> {
         bit_depth_chroma_minus8,
         log2_max_pic_order_cnt_lsb_minus4,
         sub_layer_ordering_info_present_flag,
+        dpb_ordering,
         log2_min_luma_coding_block_size_minus3,
         log2_diff_max_min_luma_coding_block_size,
         log2_min_luma_transform_block_size_minus2,
@@ -506,7 +1157,10 @@ pub fn parse_sps(data: &[u8]) -> Result<Sps> {
         pcm_enabled_flag,
         pcm_params,
         num_short_term_ref_pic_sets,
+        st_ref_pic_sets,
         long_term_ref_pics_present_flag,
+        lt_ref_pic_poc_lsb_sps,
+        used_by_curr_pic_lt_sps_flag,
         sps_temporal_mvp_enabled_flag,
         strong_intra_smoothing_enabled_flag,
         vui_parameters_present_flag,
@@ -514,32 +1168,62 @@ pub fn parse_sps(data: &[u8]) -> Result<Sps> {
     })
 }
 
+/// Read an unsigned Exp-Golomb value, failing if it exceeds `max`
+fn read_ue_max(reader: &mut BitstreamReader<'_>, max: u32, msg: &'static str) -> Result<u32> {
+    let value = reader.read_ue()?;
+    if value > max {
+        return Err(HevcError::InvalidBitstream(msg));
+    }
+    Ok(value)
+}
+
+/// Read a signed Exp-Golomb value, failing if it is outside `min..=max`
+fn read_se_range(
+    reader: &mut BitstreamReader<'_>,
+    min: i32,
+    max: i32,
+    msg: &'static str,
+) -> Result<i32> {
+    let value = reader.read_se()?;
+    if !(min..=max).contains(&value) {
+        return Err(HevcError::InvalidBitstream(msg));
+    }
+    Ok(value)
+}
+
 /// Parse Picture Parameter Set
 pub fn parse_pps(data: &[u8]) -> Result<Pps> {
     let mut reader = BitstreamReader::new(data);
 
-    let pps_id = reader.read_ue()? as u8;
-    let sps_id = reader.read_ue()? as u8;
+    let pps_id = read_ue_max(&mut reader, 63, "pps_pic_parameter_set_id out of range")? as u8;
+    let sps_id = read_ue_max(&mut reader, 15, "pps_seq_parameter_set_id out of range")? as u8;
     let dependent_slice_segments_enabled_flag = reader.read_bit()? != 0;
     let output_flag_present_flag = reader.read_bit()? != 0;
     let num_extra_slice_header_bits = reader.read_bits(3)? as u8;
     let sign_data_hiding_enabled_flag = reader.read_bit()? != 0;
     let cabac_init_present_flag = reader.read_bit()? != 0;
-    let num_ref_idx_l0_default_active_minus1 = reader.read_ue()? as u8;
-    let num_ref_idx_l1_default_active_minus1 = reader.read_ue()? as u8;
-    let init_qp_minus26 = reader.read_se()? as i8;
+    let num_ref_idx_l0_default_active_minus1 =
+        read_ue_max(&mut reader, 14, "num_ref_idx_default_active_minus1 out of range")? as u8;
+    let num_ref_idx_l1_default_active_minus1 =
+        read_ue_max(&mut reader, 14, "num_ref_idx_default_active_minus1 out of range")? as u8;
+    // The lower bound depends on the bit depth; -74 allows up to 16 bits
+    let init_qp_minus26 =
+        read_se_range(&mut reader, -74, 25, "init_qp_minus26 out of range")? as i8;
     let constrained_intra_pred_flag = reader.read_bit()? != 0;
     let transform_skip_enabled_flag = reader.read_bit()? != 0;
     let cu_qp_delta_enabled_flag = reader.read_bit()? != 0;
 
+    // Checked against the SPS's coding block sizes when the PPS is used
     let diff_cu_qp_delta_depth = if cu_qp_delta_enabled_flag {
-        reader.read_ue()? as u8
+        read_ue_max(&mut reader, 3, "diff_cu_qp_delta_depth out of range")? as u8
     } else {
         0
     };
 
-    let pps_cb_qp_offset = reader.read_se()? as i8;
-    let pps_cr_qp_offset = reader.read_se()? as i8;
+    let pps_cb_qp_offset =
+        read_se_range(&mut reader, -12, 12, "pps_cb_qp_offset out of range")? as i8;
+    let pps_cr_qp_offset =
+        read_se_range(&mut reader, -12, 12, "pps_cr_qp_offset out of range")? as i8;
     let pps_slice_chroma_qp_offsets_present_flag = reader.read_bit()? != 0;
     let weighted_pred_flag = reader.read_bit()? != 0;
     let weighted_bipred_flag = reader.read_bit()? != 0;
@@ -548,18 +1232,24 @@ pub fn parse_pps(data: &[u8]) -> Result<Pps> {
     let entropy_coding_sync_enabled_flag = reader.read_bit()? != 0;
 
     let tile_info = if tiles_enabled_flag {
-        let num_tile_columns_minus1 = reader.read_ue()? as u16;
-        let num_tile_rows_minus1 = reader.read_ue()? as u16;
+        // No level allows more than 20 x 22 tiles
+        let num_tile_columns_minus1 =
+            read_ue_max(&mut reader, 19, "num_tile_columns_minus1 out of range")? as u16;
+        let num_tile_rows_minus1 =
+            read_ue_max(&mut reader, 21, "num_tile_rows_minus1 out of range")? as u16;
         let uniform_spacing_flag = reader.read_bit()? != 0;
 
         let (column_widths, row_heights) = if !uniform_spacing_flag {
             let mut cols = Vec::with_capacity(num_tile_columns_minus1 as usize);
             let mut rows = Vec::with_capacity(num_tile_rows_minus1 as usize);
+            // Bounded against the picture size once the SPS is known
             for _ in 0..num_tile_columns_minus1 {
-                cols.push(reader.read_ue()? as u16);
+                let width = read_ue_max(&mut reader, u16::MAX.into(), "column_width_minus1 out of range")?;
+                cols.push(width as u16);
             }
             for _ in 0..num_tile_rows_minus1 {
-                rows.push(reader.read_ue()? as u16);
+                let height = read_ue_max(&mut reader, u16::MAX.into(), "row_height_minus1 out of range")?;
+                rows.push(height as u16);
             }
             (cols, rows)
         } else {
@@ -592,7 +1282,10 @@ pub fn parse_pps(data: &[u8]) -> Result<Pps> {
         let override_enabled = reader.read_bit()? != 0;
         let disabled = reader.read_bit()? != 0;
         let (beta, tc) = if !disabled {
-            (reader.read_se()? as i8, reader.read_se()? as i8)
+            (
+                read_se_range(&mut reader, -6, 6, "pps_beta_offset_div2 out of range")? as i8,
+                read_se_range(&mut reader, -6, 6, "pps_tc_offset_div2 out of range")? as i8,
+            )
         } else {
             (0, 0)
         };
@@ -607,7 +1300,8 @@ pub fn parse_pps(data: &[u8]) -> Result<Pps> {
     }
 
     let lists_modification_present_flag = reader.read_bit()? != 0;
-    let log2_parallel_merge_level_minus2 = reader.read_ue()? as u8;
+    let log2_parallel_merge_level_minus2 =
+        read_ue_max(&mut reader, 4, "log2_parallel_merge_level_minus2 out of range")? as u8;
     let slice_segment_header_extension_present_flag = reader.read_bit()? != 0;
 
     Ok(Pps {
@@ -726,11 +1420,16 @@ fn skip_scaling_list_data(reader: &mut BitstreamReader<'_>) -> Result<()> {
     Ok(())
 }
 
-fn skip_short_term_ref_pic_set(
+/// Parse a short-term reference picture set (H.265 7.3.7)
+///
+/// `idx` is stRpsIdx: sets in the SPS use their index, a set coded in a
+/// slice header uses `num_short_term_ref_pic_sets`. `sets` holds the SPS
+/// sets parsed so far, which inter-RPS prediction refers to.
+pub(crate) fn parse_short_term_ref_pic_set(
     reader: &mut BitstreamReader<'_>,
     idx: u8,
-    _num_sets: u8,
-) -> Result<()> {
+    sets: &[ShortTermRefPicSet],
+) -> Result<ShortTermRefPicSet> {
     let inter_ref_pic_set_prediction_flag = if idx != 0 {
         reader.read_bit()? != 0
     } else {
@@ -738,27 +1437,116 @@ fn skip_short_term_ref_pic_set(
     };
 
     if inter_ref_pic_set_prediction_flag {
-        // Simplified - skip inter prediction
-        if idx == _num_sets {
-            let _delta_idx_minus1 = reader.read_ue()?;
+        let delta_idx_minus1 = if idx as usize == sets.len() {
+            reader.read_ue()?
+        } else {
+            0
+        };
+        let delta_rps_sign = reader.read_bit()?;
+        let abs_delta_rps_minus1 = reader.read_ue()?;
+        if abs_delta_rps_minus1 > 0x7FFF {
+            return Err(HevcError::InvalidBitstream("abs_delta_rps_minus1 out of range"));
         }
-        let _delta_rps_sign = reader.read_bit()?;
-        let _abs_delta_rps_minus1 = reader.read_ue()?;
-        // Would need previous set info to properly parse
+
+        let ref_idx = (idx as usize)
+            .checked_sub(delta_idx_minus1 as usize + 1)
+            .ok_or(HevcError::InvalidBitstream("invalid ref RPS index"))?;
+        let ref_rps = sets
+            .get(ref_idx)
+            .ok_or(HevcError::InvalidBitstream("invalid ref RPS index"))?;
+        let delta_rps = (1 - 2 * delta_rps_sign as i32) * (abs_delta_rps_minus1 as i32 + 1);
+
+        // One flag pair per entry of the reference set, plus one for the
+        // reference picture itself
+        let num_delta_pocs = ref_rps.num_delta_pocs();
+        let mut used_by_curr_pic_flag = Vec::with_capacity(num_delta_pocs + 1);
+        let mut use_delta_flag = Vec::with_capacity(num_delta_pocs + 1);
+        for _ in 0..=num_delta_pocs {
+            let used = reader.read_bit()? != 0;
+            let use_delta = if used { true } else { reader.read_bit()? != 0 };
+            used_by_curr_pic_flag.push(used);
+            use_delta_flag.push(use_delta);
+        }
+
+        Ok(predict_ref_pic_set(ref_rps, delta_rps, &used_by_curr_pic_flag, &use_delta_flag))
     } else {
         let num_negative_pics = reader.read_ue()?;
         let num_positive_pics = reader.read_ue()?;
+        if num_negative_pics > 16 || num_positive_pics > 16 {
+            return Err(HevcError::InvalidBitstream("too many pictures in RPS"));
+        }
+
+        let mut rps = ShortTermRefPicSet::default();
+        let mut poc = 0i32;
         for _ in 0..num_negative_pics {
-            let _delta_poc_s0_minus1 = reader.read_ue()?;
-            let _used_by_curr_pic_s0_flag = reader.read_bit()?;
+            let delta_poc_s0_minus1 = reader.read_ue()?;
+            poc -= delta_poc_s0_minus1.min(0x7FFF) as i32 + 1;
+            rps.delta_poc_s0.push(poc);
+            rps.used_by_curr_pic_s0.push(reader.read_bit()? != 0);
         }
+        poc = 0;
         for _ in 0..num_positive_pics {
-            let _delta_poc_s1_minus1 = reader.read_ue()?;
-            let _used_by_curr_pic_s1_flag = reader.read_bit()?;
+            let delta_poc_s1_minus1 = reader.read_ue()?;
+            poc += delta_poc_s1_minus1.min(0x7FFF) as i32 + 1;
+            rps.delta_poc_s1.push(poc);
+            rps.used_by_curr_pic_s1.push(reader.read_bit()? != 0);
         }
+        Ok(rps)
     }
+}
 
-    Ok(())
+/// Derive a short-term RPS predicted from another set (H.265 7.4.8, eq. 7-61/7-62)
+fn predict_ref_pic_set(
+    ref_rps: &ShortTermRefPicSet,
+    delta_rps: i32,
+    used_by_curr_pic_flag: &[bool],
+    use_delta_flag: &[bool],
+) -> ShortTermRefPicSet {
+    let num_negative = ref_rps.delta_poc_s0.len();
+    let num_delta_pocs = ref_rps.num_delta_pocs();
+    let mut rps = ShortTermRefPicSet::default();
+
+    // Negative pictures, closest first
+    for j in (0..ref_rps.delta_poc_s1.len()).rev() {
+        let d_poc = ref_rps.delta_poc_s1[j] + delta_rps;
+        if d_poc < 0 && use_delta_flag[num_negative + j] {
+            rps.delta_poc_s0.push(d_poc);
+            rps.used_by_curr_pic_s0.push(used_by_curr_pic_flag[num_negative + j]);
+        }
+    }
+    if delta_rps < 0 && use_delta_flag[num_delta_pocs] {
+        rps.delta_poc_s0.push(delta_rps);
+        rps.used_by_curr_pic_s0.push(used_by_curr_pic_flag[num_delta_pocs]);
+    }
+    for j in 0..num_negative {
+        let d_poc = ref_rps.delta_poc_s0[j] + delta_rps;
+        if d_poc < 0 && use_delta_flag[j] {
+            rps.delta_poc_s0.push(d_poc);
+            rps.used_by_curr_pic_s0.push(used_by_curr_pic_flag[j]);
+        }
+    }
+
+    // Positive pictures, closest first
+    for j in (0..num_negative).rev() {
+        let d_poc = ref_rps.delta_poc_s0[j] + delta_rps;
+        if d_poc > 0 && use_delta_flag[j] {
+            rps.delta_poc_s1.push(d_poc);
+            rps.used_by_curr_pic_s1.push(used_by_curr_pic_flag[j]);
+        }
+    }
+    if delta_rps > 0 && use_delta_flag[num_delta_pocs] {
+        rps.delta_poc_s1.push(delta_rps);
+        rps.used_by_curr_pic_s1.push(used_by_curr_pic_flag[num_delta_pocs]);
+    }
+    for j in 0..ref_rps.delta_poc_s1.len() {
+        let d_poc = ref_rps.delta_poc_s1[j] + delta_rps;
+        if d_poc > 0 && use_delta_flag[num_negative + j] {
+            rps.delta_poc_s1.push(d_poc);
+            rps.used_by_curr_pic_s1.push(used_by_curr_pic_flag[num_negative + j]);
+        }
+    }
+
+    rps
 }
 
 /// Parse VUI (Video Usability Information) parameters
@@ -766,7 +1554,10 @@ fn skip_short_term_ref_pic_set(
 ///
 /// We focus on color space parameters for still images.
 /// Video-specific parameters (timing, HRD) are skipped.
-fn parse_vui_parameters(reader: &mut BitstreamReader<'_>) -> Result<VuiParameters> {
+fn parse_vui_parameters(
+    reader: &mut BitstreamReader<'_>,
+    max_sub_layers_minus1: u8,
+) -> Result<VuiParameters> {
     let mut vui = VuiParameters::default();
 
     // Aspect ratio info
@@ -839,7 +1630,7 @@ fn parse_vui_parameters(reader: &mut BitstreamReader<'_>) -> Result<VuiParameter
         // HRD parameters (skip - not needed for still images)
         let vui_hrd_parameters_present_flag = reader.read_bit()? != 0;
         if vui_hrd_parameters_present_flag {
-            skip_hrd_parameters(reader)?;
+            skip_hrd_parameters(reader, true, max_sub_layers_minus1)?;
         }
     }
 
@@ -859,39 +1650,201 @@ fn parse_vui_parameters(reader: &mut BitstreamReader<'_>) -> Result<VuiParameter
     Ok(vui)
 }
 
-/// Skip HRD (Hypothetical Reference Decoder) parameters
+/// Skip HRD (Hypothetical Reference Decoder) parameters (H.265 E.2.2)
 /// Not needed for still images
-fn skip_hrd_parameters(reader: &mut BitstreamReader<'_>) -> Result<()> {
-    let nal_hrd_parameters_present_flag = reader.read_bit()? != 0;
-    let vcl_hrd_parameters_present_flag = reader.read_bit()? != 0;
+fn skip_hrd_parameters(
+    reader: &mut BitstreamReader<'_>,
+    common_inf_present_flag: bool,
+    max_sub_layers_minus1: u8,
+) -> Result<()> {
+    let mut nal_hrd_parameters_present_flag = false;
+    let mut vcl_hrd_parameters_present_flag = false;
+    let mut sub_pic_hrd_params_present_flag = false;
+
+    if common_inf_present_flag {
+        nal_hrd_parameters_present_flag = reader.read_bit()? != 0;
+        vcl_hrd_parameters_present_flag = reader.read_bit()? != 0;
+        if nal_hrd_parameters_present_flag || vcl_hrd_parameters_present_flag {
+            sub_pic_hrd_params_present_flag = reader.read_bit()? != 0;
+            if sub_pic_hrd_params_present_flag {
+                let _tick_divisor_minus2 = reader.read_bits(8)?;
+                let _du_cpb_removal_delay_increment_length_minus1 = reader.read_bits(5)?;
+                let _sub_pic_cpb_params_in_pic_timing_sei_flag = reader.read_bit()?;
+                let _dpb_output_delay_du_length_minus1 = reader.read_bits(5)?;
+            }
+            let _bit_rate_scale = reader.read_bits(4)?;
+            let _cpb_size_scale = reader.read_bits(4)?;
+            if sub_pic_hrd_params_present_flag {
+                let _cpb_size_du_scale = reader.read_bits(4)?;
+            }
+            let _initial_cpb_removal_delay_length_minus1 = reader.read_bits(5)?;
+            let _au_cpb_removal_delay_length_minus1 = reader.read_bits(5)?;
+            let _dpb_output_delay_length_minus1 = reader.read_bits(5)?;
+        }
+    }
 
-    let sub_pic_hrd_params_present_flag = if nal_hrd_parameters_present_flag
-        || vcl_hrd_parameters_present_flag
-    {
-        let flag = reader.read_bit()? != 0;
-        if flag {
-            let _tick_divisor_minus2 = reader.read_bits(8)?;
-            let _du_cpb_removal_delay_increment_length_minus1 = reader.read_bits(5)?;
-            let _sub_pic_cpb_params_in_pic_timing_sei_flag = reader.read_bit()?;
-            let _dpb_output_delay_du_length_minus1 = reader.read_bits(5)?;
-        }
-        flag
-    } else {
-        false
-    };
+    for _ in 0..=max_sub_layers_minus1 {
+        let fixed_pic_rate_general_flag = reader.read_bit()? != 0;
+        let fixed_pic_rate_within_cvs_flag =
+            fixed_pic_rate_general_flag || reader.read_bit()? != 0;
+        let mut low_delay_hrd_flag = false;
+        if fixed_pic_rate_within_cvs_flag {
+            let _elemental_duration_in_tc_minus1 = reader.read_ue()?;
+        } else {
+            low_delay_hrd_flag = reader.read_bit()? != 0;
+        }
+        let cpb_cnt_minus1 = if low_delay_hrd_flag { 0 } else { reader.read_ue()? };
+        if cpb_cnt_minus1 > 31 {
+            return Err(HevcError::InvalidBitstream("cpb_cnt_minus1 out of range"));
+        }
 
-    let _bit_rate_scale = reader.read_bits(4)?;
-    let _cpb_size_scale = reader.read_bits(4)?;
-    if sub_pic_hrd_params_present_flag {
-        let _cpb_size_du_scale = reader.read_bits(4)?;
+        // sub_layer_hrd_parameters() for NAL and VCL HRDs
+        let num_sub_layer_hrd =
+            nal_hrd_parameters_present_flag as u8 + vcl_hrd_parameters_present_flag as u8;
+        for _ in 0..num_sub_layer_hrd {
+            for _ in 0..=cpb_cnt_minus1 {
+                let _bit_rate_value_minus1 = reader.read_ue()?;
+                let _cpb_size_value_minus1 = reader.read_ue()?;
+                if sub_pic_hrd_params_present_flag {
+                    let _cpb_size_du_value_minus1 = reader.read_ue()?;
+                    let _bit_rate_du_value_minus1 = reader.read_ue()?;
+                }
+                let _cbr_flag = reader.read_bit()?;
+            }
+        }
     }
 
-    let _initial_cpb_removal_delay_length_minus1 = reader.read_bits(5)?;
-    let _au_cpb_removal_delay_length_minus1 = reader.read_bits(5)?;
-    let _dpb_output_delay_length_minus1 = reader.read_bits(5)?;
+    Ok(())
+}
 
-    // Skip sublayer HRD parameters (complex, not needed)
-    // This is a simplified skip - full parsing would require tracking sublayers
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hevc::test_util::BitWriter;
+
+    /// SPS of a 64x64 4:2:0 8-bit picture with the given POC LSB bits,
+    /// minimum coding block and CTB sizes and bottom conformance offset
+    fn sps(log2_poc_lsb: u32, log2_min_cb: u32, log2_ctb: u32, crop_bottom: u32) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        // vps_id, max_sub_layers_minus1, temporal_id_nesting, then a
+        // general-only profile_tier_level
+        w.bits(0x01, 8);
+        for _ in 0..12 {
+            w.bits(0, 8);
+        }
+        w.ue(0); // sps_id
+        w.ue(1); // chroma_format_idc
+        w.ue(64);
+        w.ue(64);
+        if crop_bottom > 0 {
+            w.bits(1, 1); // conformance_window_flag
+            for offset in [0, 0, 0, crop_bottom] {
+                w.ue(offset);
+            }
+        } else {
+            w.bits(0, 1);
+        }
+        w.ue(0);
+        w.ue(0);
+        w.ue(log2_poc_lsb - 4);
+        w.bits(1, 1); // sub_layer_ordering_info_present_flag
+        for _ in 0..3 {
+            w.ue(0);
+        }
+        w.ue(log2_min_cb - 3);
+        w.ue(log2_ctb - log2_min_cb);
+        w.ue(0); // 4x4 minimum transform blocks
+        w.ue(3); // 32x32 maximum transform blocks
+        w.ue(1);
+        w.ue(1);
+        // scaling lists, AMP, SAO, PCM, no reference picture sets, no long
+        // term pictures, TMVP, strong intra smoothing, VUI
+        w.bits(0, 4);
+        w.ue(0);
+        w.bits(0, 4);
+        w.finish()
+    }
 
-    Ok(())
+    #[test]
+    fn test_sps_ranges() {
+        let parsed = parse_sps(&sps(8, 3, 6, 0)).unwrap();
+        assert_eq!(parsed.log2_ctb_size(), 6);
+        assert!(parse_sps(&sps(8, 3, 6, 31)).is_ok());
+
+        // Out of range values that used to overflow in later arithmetic
+        assert!(parse_sps(&sps(17, 3, 6, 0)).is_err());
+        assert!(parse_sps(&sps(8, 3, 7, 0)).is_err());
+        // 64 is not a multiple of a 128-sample minimum coding block
+        assert!(parse_sps(&sps(8, 7, 7, 0)).is_err());
+        // Cropping all 64 rows (32 in chroma units)
+        assert!(parse_sps(&sps(8, 3, 6, 32)).is_err());
+    }
+
+    #[test]
+    fn test_pps_ranges() {
+        let pps = |init_qp_minus26: u32, cb_qp_offset: u32| {
+            let mut w = BitWriter::default();
+            w.ue(0);
+            w.ue(0);
+            w.bits(0, 7);
+            w.ue(0);
+            w.ue(0);
+            w.ue(init_qp_minus26);
+            w.bits(0, 3);
+            w.ue(cb_qp_offset);
+            w.ue(0);
+            w.bits(0, 10);
+            w.ue(0);
+            w.bits(0, 1);
+            w.finish()
+        };
+        assert!(parse_pps(&pps(0, 0)).is_ok());
+        // se(v) codes 51, 150 and 25 are +26, -75 and +13
+        assert!(parse_pps(&pps(51, 0)).is_err());
+        assert!(parse_pps(&pps(150, 0)).is_err());
+        assert!(parse_pps(&pps(148, 0)).is_ok());
+        assert!(parse_pps(&pps(0, 25)).is_err());
+    }
+
+    #[test]
+    fn test_vps_extension() {
+        let params = crate::hevc::test_util::StreamParams::default();
+        let payload = |nal: Vec<u8>| crate::hevc::bitstream::parse_single_nal(&nal).unwrap().payload.to_vec();
+        let vps = parse_vps(&payload(params.vps())).unwrap();
+        assert_eq!(vps.max_layers_minus1, 1);
+        let ext = vps.extension.as_ref().expect("VPS extension");
+        assert_eq!(ext.layer_id_in_nuh, [0, 1]);
+        assert_eq!(ext.view_id, [0, 1]);
+        let info = ext.layer_info(1).unwrap();
+        assert_eq!(info.direct_ref_layers, [0]);
+        assert!(info.default_ref_layers_active_flag);
+        assert!(ext.layer_info(2).is_none());
+
+        // The second view's SPS takes its picture format from the VPS
+        let sps = parse_layer_sps(&payload(params.layer_sps()), 1, Some(&vps)).unwrap();
+        assert_eq!(sps.sps_id, 1);
+        assert_eq!((sps.pic_width_in_luma_samples, sps.pic_height_in_luma_samples), (64, 64));
+        assert_eq!(sps.chroma_format_idc, 1);
+        assert!(parse_layer_sps(&payload(params.layer_sps()), 1, None).is_err());
+    }
+
+    #[test]
+    fn test_tile_layout_must_fit_picture() {
+        // 64x64 picture of 32x32 CTBs: 2x2 CTBs
+        let parsed = parse_sps(&sps(8, 3, 5, 0)).unwrap();
+        let tiles = |columns_minus1, column_widths: Vec<u16>, uniform_spacing_flag| TileInfo {
+            num_tile_columns_minus1: columns_minus1,
+            num_tile_rows_minus1: 0,
+            uniform_spacing_flag,
+            column_widths,
+            row_heights: Vec::new(),
+            loop_filter_across_tiles_enabled_flag: false,
+        };
+        assert!(tiles(1, Vec::new(), true).validate(&parsed).is_ok());
+        assert!(tiles(2, Vec::new(), true).validate(&parsed).is_err());
+        assert!(tiles(1, vec![0], false).validate(&parsed).is_ok());
+        // The last column would be empty, or start beyond the picture
+        assert!(tiles(1, vec![1], false).validate(&parsed).is_err());
+        assert!(tiles(1, vec![u16::MAX], false).validate(&parsed).is_err());
+    }
 }
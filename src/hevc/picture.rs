@@ -2,10 +2,15 @@
 
 use alloc::vec;
 use alloc::vec::Vec;
-use super::colorspace::ColorSpace;
+use super::colorspace::{ColorSpace, Rgb8Converter};
+use crate::error::DamagedRegion;
+use crate::options::{ChromaUpsampler, DecoderOptions};
+
+/// Samples of a row upsampled and converted to RGB at once
+const RGB_CHUNK: usize = 64;
 
 /// Decoded video frame
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DecodedFrame {
     /// Width in pixels (full frame, before cropping)
     pub width: u32,
@@ -31,16 +36,20 @@ pub struct DecodedFrame {
     pub crop_top: u32,
     /// Conformance window bottom offset (in luma samples)
     pub crop_bottom: u32,
+    /// Alpha plane (luma resolution, same bit depth as luma), if any
+    pub alpha_plane: Option<Vec<u16>>,
+    /// Regions concealed by lenient decoding, in full-frame luma coordinates
+    pub damage: Vec<DamagedRegion>,
 }
 
 impl DecodedFrame {
     /// Create a new frame buffer
     pub fn new(width: u32, height: u32) -> Self {
-        let luma_size = (width * height) as usize;
+        let luma_size = width as usize * height as usize;
         // Assume 4:2:0 chroma subsampling
         let chroma_width = width.div_ceil(2);
         let chroma_height = height.div_ceil(2);
-        let chroma_size = (chroma_width * chroma_height) as usize;
+        let chroma_size = chroma_width as usize * chroma_height as usize;
 
         Self {
             width,
@@ -55,12 +64,26 @@ impl DecodedFrame {
             crop_right: 0,
             crop_top: 0,
             crop_bottom: 0,
+            alpha_plane: None,
+            damage: Vec::new(),
         }
     }
 
     /// Create a frame with specific parameters
     pub fn with_params(width: u32, height: u32, bit_depth: u8, chroma_format: u8) -> Self {
-        let luma_size = (width * height) as usize;
+        Self::with_planes(width, height, bit_depth, chroma_format, |len| vec![0; len])
+    }
+
+    /// Create a frame like [`with_params`](Self::with_params), taking each
+    /// zeroed plane of the given length from `new_plane`
+    pub(crate) fn with_planes(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        chroma_format: u8,
+        mut new_plane: impl FnMut(usize) -> Vec<u16>,
+    ) -> Self {
+        let luma_size = width as usize * height as usize;
 
         let (chroma_width, chroma_height) = match chroma_format {
             0 => (0, 0),                                  // Monochrome
@@ -70,14 +93,14 @@ impl DecodedFrame {
             _ => (width.div_ceil(2), height.div_ceil(2)),
         };
 
-        let chroma_size = (chroma_width * chroma_height) as usize;
+        let chroma_size = chroma_width as usize * chroma_height as usize;
 
         Self {
             width,
             height,
-            y_plane: vec![0; luma_size],
-            cb_plane: vec![0; chroma_size],
-            cr_plane: vec![0; chroma_size],
+            y_plane: new_plane(luma_size),
+            cb_plane: new_plane(chroma_size),
+            cr_plane: new_plane(chroma_size),
             bit_depth,
             chroma_format,
             colorspace: ColorSpace::default(),
@@ -85,6 +108,8 @@ impl DecodedFrame {
             crop_right: 0,
             crop_top: 0,
             crop_bottom: 0,
+            alpha_plane: None,
+            damage: Vec::new(),
         }
     }
 
@@ -96,6 +121,137 @@ impl DecodedFrame {
         self.crop_bottom = bottom;
     }
 
+    /// Narrow the crop window to a rectangle given relative to the current cropped area
+    pub fn crop_to(&mut self, left: u32, top: u32, width: u32, height: u32) {
+        let right = self.cropped_width().saturating_sub(left.saturating_add(width));
+        let bottom = self.cropped_height().saturating_sub(top.saturating_add(height));
+        self.crop_left += left.min(self.cropped_width());
+        self.crop_top += top.min(self.cropped_height());
+        self.crop_right += right;
+        self.crop_bottom += bottom;
+    }
+
+    /// Rotate the frame anticlockwise by `quarter_turns` * 90 degrees
+    ///
+    /// Chroma planes are rotated in place of the luma grid, so 4:2:2 content
+    /// must not be rotated by an odd number of quarter turns.
+    pub fn rotate_ccw(&mut self, quarter_turns: u8) {
+        let turns = quarter_turns % 4;
+        if turns == 0 {
+            return;
+        }
+
+        let (w, h) = (self.width as usize, self.height as usize);
+        let (cw, ch) = if self.chroma_format == 0 {
+            (0, 0)
+        } else {
+            let c_stride = self.c_stride();
+            (c_stride, self.cb_plane.len() / c_stride.max(1))
+        };
+
+        self.y_plane = rotate_plane(&self.y_plane, w, h, turns);
+        self.cb_plane = rotate_plane(&self.cb_plane, cw, ch, turns);
+        self.cr_plane = rotate_plane(&self.cr_plane, cw, ch, turns);
+        if let Some(ref alpha) = self.alpha_plane {
+            self.alpha_plane = Some(rotate_plane(alpha, w, h, turns));
+        }
+
+        let (l, r, t, b) = (self.crop_left, self.crop_right, self.crop_top, self.crop_bottom);
+        (self.crop_left, self.crop_right, self.crop_top, self.crop_bottom) = match turns {
+            1 => (t, b, r, l),
+            2 => (r, l, b, t),
+            _ => (b, t, l, r),
+        };
+        for region in &mut self.damage {
+            let (x, y, rw, rh) = (region.x, region.y, region.width, region.height);
+            (region.x, region.y, region.width, region.height) = match turns {
+                1 => (y, self.width - x - rw, rh, rw),
+                2 => (self.width - x - rw, self.height - y - rh, rw, rh),
+                _ => (self.height - y - rh, x, rh, rw),
+            };
+        }
+        if turns != 2 {
+            core::mem::swap(&mut self.width, &mut self.height);
+        }
+    }
+
+    /// Mirror the frame: `axis` 0 flips left-right, 1 flips top-bottom
+    pub fn mirror(&mut self, axis: u8) {
+        let (w, h) = (self.width as usize, self.height as usize);
+        let (cw, ch) = if self.chroma_format == 0 {
+            (0, 0)
+        } else {
+            let c_stride = self.c_stride();
+            (c_stride, self.cb_plane.len() / c_stride.max(1))
+        };
+        let horizontal = axis == 0;
+
+        mirror_plane(&mut self.y_plane, w, h, horizontal);
+        mirror_plane(&mut self.cb_plane, cw, ch, horizontal);
+        mirror_plane(&mut self.cr_plane, cw, ch, horizontal);
+        if let Some(ref mut alpha) = self.alpha_plane {
+            mirror_plane(alpha, w, h, horizontal);
+        }
+
+        if horizontal {
+            core::mem::swap(&mut self.crop_left, &mut self.crop_right);
+        } else {
+            core::mem::swap(&mut self.crop_top, &mut self.crop_bottom);
+        }
+        for region in &mut self.damage {
+            if horizontal {
+                region.x = self.width - region.x - region.width;
+            } else {
+                region.y = self.height - region.y - region.height;
+            }
+        }
+    }
+
+    /// Fill a rectangle of samples that could not be decoded
+    ///
+    /// The rectangle is given in luma samples. Each plane is extended down
+    /// from the row above it, or right from the column to its left when it
+    /// touches the top edge; a rectangle in the top-left corner becomes
+    /// mid-grey. The alpha plane is left as is.
+    pub fn conceal(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        let x_end = x.saturating_add(width).min(self.width);
+        let y_end = y.saturating_add(height).min(self.height);
+        if x >= x_end || y >= y_end {
+            return;
+        }
+        let grey = 1u16 << (self.bit_depth - 1);
+        let stride = self.y_stride();
+        conceal_plane(&mut self.y_plane, stride, (x, y, x_end, y_end), grey);
+        if self.chroma_format != 0 {
+            let (sub_x, sub_y) = self.chroma_subsampling();
+            let rect = (x / sub_x, y / sub_y, x_end.div_ceil(sub_x), y_end.div_ceil(sub_y));
+            let stride = self.c_stride();
+            conceal_plane(&mut self.cb_plane, stride, rect, grey);
+            conceal_plane(&mut self.cr_plane, stride, rect, grey);
+        }
+    }
+
+    /// Damaged regions relative to the cropped image, clipped to it
+    pub fn cropped_damage(&self) -> Vec<DamagedRegion> {
+        let (width, height) = (self.cropped_width(), self.cropped_height());
+        self.damage
+            .iter()
+            .filter_map(|region| {
+                let x0 = region.x.saturating_sub(self.crop_left).min(width);
+                let y0 = region.y.saturating_sub(self.crop_top).min(height);
+                let x1 = (region.x + region.width).saturating_sub(self.crop_left).min(width);
+                let y1 = (region.y + region.height).saturating_sub(self.crop_top).min(height);
+                (x0 < x1 && y0 < y1).then(|| DamagedRegion {
+                    x: x0,
+                    y: y0,
+                    width: x1 - x0,
+                    height: y1 - y0,
+                    cause: region.cause.clone(),
+                })
+            })
+            .collect()
+    }
+
     /// Get cropped width
     pub fn cropped_width(&self) -> u32 {
         self.width - self.crop_left - self.crop_right
@@ -106,6 +262,16 @@ impl DecodedFrame {
         self.height - self.crop_top - self.crop_bottom
     }
 
+    /// Get chroma subsampling factors (horizontal, vertical) in luma samples
+    #[inline]
+    pub fn chroma_subsampling(&self) -> (u32, u32) {
+        match self.chroma_format {
+            2 => (2, 1), // 4:2:2
+            0 | 3 => (1, 1), // Monochrome, 4:4:4
+            _ => (2, 2), // 4:2:0
+        }
+    }
+
     /// Get luma stride (width)
     #[inline]
     pub fn y_stride(&self) -> usize {
@@ -124,73 +290,46 @@ impl DecodedFrame {
     }
 
     pub fn to_rgb(&self) -> Vec<u8> {
+        self.to_rgb_with(&DecoderOptions::default())
+    }
+
+    /// Convert to 8-bit RGB with conformance window cropping, using the
+    /// chroma upsampler, tone mapping and colour target of `options`
+    pub fn to_rgb_with(&self, options: &DecoderOptions) -> Vec<u8> {
+        let samples = self.cropped_width() as usize * self.cropped_height() as usize;
+        let converter = self.rgb8_converter(options, samples);
         #[cfg(feature = "parallel")]
         {
             let out_height = self.cropped_height();
-            if out_height >= 1000 {
-                return self.to_rgb_parallel();
+            if out_height >= 1000 && options.threads != 1 {
+                return self.to_rgb_parallel(options, &converter);
             }
         }
-        self.to_rgb_sequential()
+        self.to_rgb_sequential(options, &converter)
     }
 
-    fn to_rgb_sequential(&self) -> Vec<u8> {
+    fn to_rgb_sequential(&self, options: &DecoderOptions, converter: &Rgb8Converter) -> Vec<u8> {
         let out_width = self.cropped_width();
         let out_height = self.cropped_height();
         let mut rgb = Vec::with_capacity((out_width * out_height * 3) as usize);
 
-        let y_start = self.crop_top;
-        let y_end = self.height - self.crop_bottom;
-        let x_start = self.crop_left;
-        let x_end = self.width - self.crop_right;
-
-        for y in y_start..y_end {
-            for x in x_start..x_end {
-                let y_idx = (y * self.width + x) as usize;
-                let y_val = self.y_plane[y_idx];
-
-                let (cb_val, cr_val) = self.get_chroma_u16(x, y);
-
-                let (r, g, b) = self.colorspace.ycbcr_to_rgb8(y_val, cb_val, cr_val, self.bit_depth);
-                rgb.push(r);
-                rgb.push(g);
-                rgb.push(b);
-            }
+        for y in self.crop_top..self.height - self.crop_bottom {
+            self.push_rgb_row(y, options, converter, &mut rgb);
         }
 
         rgb
     }
 
     #[cfg(feature = "parallel")]
-    fn to_rgb_parallel(&self) -> Vec<u8> {
-        use rayon::prelude::*;
-
+    fn to_rgb_parallel(&self, options: &DecoderOptions, converter: &Rgb8Converter) -> Vec<u8> {
         let out_width = self.cropped_width();
         let out_height = self.cropped_height();
 
-        let y_start = self.crop_top;
-        let y_end = self.height - self.crop_bottom;
-        let x_start = self.crop_left;
-        let x_end = self.width - self.crop_right;
-
-        let row_data: Vec<Vec<u8>> = (y_start..y_end)
-            .into_par_iter()
-            .map(|y| {
-                let mut row_rgb = Vec::with_capacity((out_width * 3) as usize);
-                for x in x_start..x_end {
-                    let y_idx = (y * self.width + x) as usize;
-                    let y_val = self.y_plane[y_idx];
-
-                    let (cb_val, cr_val) = self.get_chroma_u16(x, y);
-
-                    let (r, g, b) = self.colorspace.ycbcr_to_rgb8(y_val, cb_val, cr_val, self.bit_depth);
-                    row_rgb.push(r);
-                    row_rgb.push(g);
-                    row_rgb.push(b);
-                }
-                row_rgb
-            })
-            .collect();
+        let row_data = options.par_map(out_height as usize, usize::MAX, |i| {
+            let mut row_rgb = Vec::with_capacity((out_width * 3) as usize);
+            self.push_rgb_row(self.crop_top + i as u32, options, converter, &mut row_rgb);
+            row_rgb
+        });
 
         let mut rgb = Vec::with_capacity((out_width * out_height * 3) as usize);
         for row in row_data {
@@ -200,6 +339,35 @@ impl DecodedFrame {
         rgb
     }
 
+    /// Converter to 8-bit RGB with the settings of `options`, for about
+    /// `samples` samples
+    fn rgb8_converter(&self, options: &DecoderOptions, samples: usize) -> Rgb8Converter {
+        Rgb8Converter::new(&self.colorspace, self.bit_depth, options.tone_mapping, options.color_target, samples)
+    }
+
+    /// Append the cropped part of row `y` converted to 8-bit RGB
+    ///
+    /// Chroma is upsampled in chunks of [`RGB_CHUNK`] samples, which
+    /// `converter` then converts.
+    fn push_rgb_row(&self, y: u32, options: &DecoderOptions, converter: &Rgb8Converter, rgb: &mut Vec<u8>) {
+        let row = (y * self.width) as usize;
+        let x_end = self.width - self.crop_right;
+
+        for x0 in (self.crop_left..x_end).step_by(RGB_CHUNK) {
+            let len = ((x_end - x0) as usize).min(RGB_CHUNK);
+            let luma = &self.y_plane[row + x0 as usize..][..len];
+            let mut cb = [0u16; RGB_CHUNK];
+            let mut cr = [0u16; RGB_CHUNK];
+            for (i, x) in (x0..x0 + len as u32).enumerate() {
+                (cb[i], cr[i]) = self.upsample_chroma(x, y, options.upsampler);
+            }
+
+            let start = rgb.len();
+            rgb.resize(start + 3 * len, 0);
+            converter.convert_row([luma, &cb[..len], &cr[..len]], &mut rgb[start..]);
+        }
+    }
+
     pub fn to_rgb16(&self) -> Vec<u16> {
         #[cfg(feature = "parallel")]
         {
@@ -307,13 +475,111 @@ impl DecodedFrame {
                 rgba.push(r.clamp(0, 255) as u8);
                 rgba.push(g.clamp(0, 255) as u8);
                 rgba.push(b.clamp(0, 255) as u8);
-                rgba.push(255); // Alpha
+                rgba.push(match self.alpha_plane {
+                    Some(ref alpha) => (alpha[y_idx] >> shift) as u8,
+                    None => 255,
+                });
             }
         }
 
         rgba
     }
 
+    /// Convert to RGB while box-filtering down by `factor` in each dimension
+    ///
+    /// Each output pixel is the average of a `factor` x `factor` block of
+    /// the cropped image (partial blocks at the right and bottom edges). The
+    /// output is `cropped_width().div_ceil(factor)` pixels wide; no full-size
+    /// RGB buffer is allocated.
+    pub fn to_rgb_scaled(&self, factor: u32) -> Vec<u8> {
+        self.to_rgb_scaled_with(factor, &DecoderOptions::default())
+    }
+
+    /// Convert to RGB while box-filtering down by `factor`, like
+    /// [`to_rgb_scaled`](Self::to_rgb_scaled), with the conversion settings
+    /// of `options`
+    pub fn to_rgb_scaled_with(&self, factor: u32, options: &DecoderOptions) -> Vec<u8> {
+        let factor = factor.max(1);
+        let out_width = self.cropped_width().div_ceil(factor);
+        let out_height = self.cropped_height().div_ceil(factor);
+        let mut rgb = Vec::with_capacity((out_width * out_height * 3) as usize);
+        let mut sums = vec![0u32; out_width as usize * 3];
+        let samples = self.cropped_width() as usize * self.cropped_height() as usize;
+        let converter = self.rgb8_converter(options, samples);
+        let mut row_rgb = Vec::with_capacity(self.cropped_width() as usize * 3);
+
+        let x_start = self.crop_left;
+        let x_end = self.width - self.crop_right;
+        let y_end = self.height - self.crop_bottom;
+
+        for out_y in 0..out_height {
+            sums.fill(0);
+            let y0 = self.crop_top + out_y * factor;
+            let y1 = (y0 + factor).min(y_end);
+            for y in y0..y1 {
+                row_rgb.clear();
+                self.push_rgb_row(y, options, &converter, &mut row_rgb);
+                for (i, px) in row_rgb.chunks_exact(3).enumerate() {
+                    let idx = i / factor as usize * 3;
+                    sums[idx] += px[0] as u32;
+                    sums[idx + 1] += px[1] as u32;
+                    sums[idx + 2] += px[2] as u32;
+                }
+            }
+            for (out_x, px) in sums.chunks_exact(3).enumerate() {
+                let x0 = x_start + out_x as u32 * factor;
+                let count = (x0 + factor).min(x_end) - x0;
+                let count = count * (y1 - y0);
+                rgb.extend(px.iter().map(|&sum| ((sum + count / 2) / count) as u8));
+            }
+        }
+
+        rgb
+    }
+
+    /// Get the cropped alpha plane scaled to 8 bits and box-filtered down by
+    /// `factor`, if the frame has one
+    pub fn alpha_to_u8_scaled(&self, factor: u32) -> Option<Vec<u8>> {
+        let alpha = self.alpha_plane.as_ref()?;
+        let factor = factor.max(1);
+        let shift = self.bit_depth - 8;
+        let (x_start, x_end) = (self.crop_left, self.width - self.crop_right);
+        let (y_start, y_end) = (self.crop_top, self.height - self.crop_bottom);
+
+        let mut out = Vec::new();
+        for y0 in (y_start..y_end).step_by(factor as usize) {
+            let y1 = (y0 + factor).min(y_end);
+            for x0 in (x_start..x_end).step_by(factor as usize) {
+                let x1 = (x0 + factor).min(x_end);
+                let mut sum = 0u32;
+                for y in y0..y1 {
+                    let row = (y * self.width) as usize;
+                    sum += alpha[row + x0 as usize..row + x1 as usize]
+                        .iter()
+                        .map(|&a| (a >> shift) as u32)
+                        .sum::<u32>();
+                }
+                let count = (x1 - x0) * (y1 - y0);
+                out.push(((sum + count / 2) / count) as u8);
+            }
+        }
+        Some(out)
+    }
+
+    /// Get the cropped alpha plane scaled to 8 bits, if the frame has one
+    pub fn alpha_to_u8(&self) -> Option<Vec<u8>> {
+        let alpha = self.alpha_plane.as_ref()?;
+        let shift = self.bit_depth - 8;
+        let mut out = Vec::with_capacity((self.cropped_width() * self.cropped_height()) as usize);
+        for y in self.crop_top..self.height - self.crop_bottom {
+            let row = (y * self.width) as usize;
+            for x in self.crop_left..self.width - self.crop_right {
+                out.push((alpha[row + x as usize] >> shift) as u8);
+            }
+        }
+        Some(out)
+    }
+
     /// Get chroma values for a pixel position
     fn get_chroma(&self, x: u32, y: u32, shift: u8) -> (i32, i32) {
         match self.chroma_format {
@@ -433,6 +699,49 @@ impl DecodedFrame {
         }
     }
 
+    /// Get chroma values at full bit depth for a luma position, upsampled
+    /// with `upsampler`
+    fn upsample_chroma(&self, x: u32, y: u32, upsampler: ChromaUpsampler) -> (u16, u16) {
+        match upsampler {
+            ChromaUpsampler::Nearest => self.get_chroma_u16(x, y),
+            ChromaUpsampler::Bilinear => self.get_chroma_bilinear(x, y),
+        }
+    }
+
+    /// Get chroma values at full bit depth, interpolated between samples
+    ///
+    /// Chroma samples are taken to be co-sited with even luma columns and,
+    /// for 4:2:0, centred between two luma rows (chroma_sample_loc_type 0).
+    /// Samples outside the plane are clamped to its edges.
+    fn get_chroma_bilinear(&self, x: u32, y: u32) -> (u16, u16) {
+        let (sub_x, sub_y) = self.chroma_subsampling();
+        if sub_x == 1 {
+            return self.get_chroma_u16(x, y);
+        }
+        let neutral = 1u16 << (self.bit_depth - 1);
+        let c_stride = self.c_stride();
+        let c_height = self.height.div_ceil(sub_y) as usize;
+
+        let cx0 = (x / 2) as usize;
+        let cx1 = if x % 2 == 1 { (cx0 + 1).min(c_stride - 1) } else { cx0 };
+        let cy0 = (y / sub_y) as usize;
+        let cy1 = match (sub_y, y % 2) {
+            (1, _) => cy0,
+            (_, 0) => cy0.saturating_sub(1),
+            _ => (cy0 + 1).min(c_height - 1),
+        };
+
+        let interpolate = |plane: &[u16]| {
+            let at = |cy: usize, cx: usize| {
+                u32::from(plane.get(cy * c_stride + cx).copied().unwrap_or(neutral))
+            };
+            // 3/4 of the nearer row, 1/4 of the other, then the average of two columns
+            let column = |cx: usize| 3 * at(cy0, cx) + at(cy1, cx);
+            ((column(cx0) + column(cx1) + 4) >> 3) as u16
+        };
+        (interpolate(&self.cb_plane), interpolate(&self.cr_plane))
+    }
+
     /// Set a luma sample
     #[inline]
     pub fn set_y(&mut self, x: u32, y: u32, value: u16) {
@@ -497,3 +806,141 @@ impl DecodedFrame {
         }
     }
 }
+
+/// Rotate a `w`x`h` plane anticlockwise by `turns` quarter turns (1..=3)
+fn rotate_plane(src: &[u16], w: usize, h: usize, turns: u8) -> Vec<u16> {
+    if src.is_empty() || w == 0 || h == 0 {
+        return src.to_vec();
+    }
+
+    let mut dst = vec![0u16; w * h];
+    for y in 0..h {
+        let row = &src[y * w..(y + 1) * w];
+        for (x, &v) in row.iter().enumerate() {
+            let idx = match turns {
+                // dst is h wide, w tall
+                1 => (w - 1 - x) * h + y,
+                2 => (h - 1 - y) * w + (w - 1 - x),
+                _ => x * h + (h - 1 - y),
+            };
+            dst[idx] = v;
+        }
+    }
+    dst
+}
+
+/// Fill the rectangle `x0..x1`, `y0..y1` of a plane from its neighbours, see
+/// [`DecodedFrame::conceal`]
+fn conceal_plane(
+    plane: &mut [u16],
+    stride: usize,
+    (x0, y0, x1, y1): (u32, u32, u32, u32),
+    grey: u16,
+) {
+    let (x0, y0, x1, y1) = (x0 as usize, y0 as usize, x1 as usize, y1 as usize);
+    for y in y0..y1 {
+        let row = y * stride;
+        if y0 > 0 {
+            let above = (y0 - 1) * stride;
+            plane.copy_within(above + x0..above + x1, row + x0);
+        } else if x0 > 0 {
+            let left = plane[row + x0 - 1];
+            plane[row + x0..row + x1].fill(left);
+        } else {
+            plane[row + x0..row + x1].fill(grey);
+        }
+    }
+}
+
+/// Mirror a `w`x`h` plane left-right (`horizontal`) or top-bottom
+fn mirror_plane(plane: &mut [u16], w: usize, h: usize, horizontal: bool) {
+    if plane.is_empty() || w == 0 || h == 0 {
+        return;
+    }
+
+    if horizontal {
+        for row in plane.chunks_exact_mut(w) {
+            row.reverse();
+        }
+    } else {
+        for y in 0..h / 2 {
+            let (top, bottom) = plane.split_at_mut((h - 1 - y) * w);
+            top[y * w..(y + 1) * w].swap_with_slice(&mut bottom[..w]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_rgb_scaled_box_filter() {
+        // 5x3 monochrome image: left 4 columns dark, last column bright
+        let mut frame = DecodedFrame::with_params(5, 3, 8, 0);
+        for (i, v) in frame.y_plane.iter_mut().enumerate() {
+            *v = if i % 5 == 4 { 200 } else { 100 };
+        }
+        let full = frame.to_rgb();
+        let scaled = frame.to_rgb_scaled(2);
+        assert_eq!(scaled.len(), 3 * 2 * 3);
+        // Full 2x2 blocks average to the full-resolution value
+        assert_eq!(&scaled[..3], &full[..3]);
+        // The partial right column averages only its own pixels
+        assert_eq!(&scaled[6..9], &full[12..15]);
+        // The partial bottom row
+        assert_eq!(&scaled[9..12], &full[30..33]);
+    }
+
+    #[test]
+    fn test_bilinear_chroma_upsampling() {
+        // 4x4 4:2:0 frame with a 2x2 chroma plane
+        let mut frame = DecodedFrame::with_params(4, 4, 8, 1);
+        frame.cb_plane.copy_from_slice(&[0, 80, 160, 240]);
+        frame.cr_plane.copy_from_slice(&[128; 4]);
+        let cb = |x, y, upsampler| frame.upsample_chroma(x, y, upsampler).0;
+        assert_eq!(cb(1, 1, ChromaUpsampler::Nearest), 0);
+        // Co-sited column and top edge: the sample itself
+        assert_eq!(cb(0, 0, ChromaUpsampler::Bilinear), 0);
+        // Between two columns, 3/4 of the way to the upper row
+        assert_eq!(cb(1, 1, ChromaUpsampler::Bilinear), 80);
+        assert_eq!(cb(0, 2, ChromaUpsampler::Bilinear), 120);
+        // Clamped at the right and bottom edges
+        assert_eq!(cb(3, 3, ChromaUpsampler::Bilinear), 240);
+        assert_eq!(frame.upsample_chroma(2, 1, ChromaUpsampler::Bilinear).1, 128);
+    }
+
+    #[test]
+    fn test_conceal_extrapolates_and_damage_follows_transforms() {
+        let mut frame = DecodedFrame::with_params(8, 4, 8, 1);
+        for (i, v) in frame.y_plane.iter_mut().enumerate() {
+            *v = i as u16;
+        }
+        // Extended down from row 1
+        frame.conceal(2, 2, 4, 2);
+        assert_eq!(&frame.y_plane[18..22], &[10, 11, 12, 13]);
+        assert_eq!(&frame.y_plane[26..30], &[10, 11, 12, 13]);
+        // Extended right from column 3 on the top edge
+        frame.conceal(4, 0, 4, 1);
+        assert_eq!(&frame.y_plane[4..8], &[3; 4]);
+        // Mid-grey in the top-left corner
+        frame.conceal(0, 0, 2, 2);
+        assert_eq!(frame.y_plane[9], 128);
+        assert_eq!(frame.cb_plane[0], 128);
+
+        let cause = alloc::sync::Arc::new(crate::HeicError::InvalidData("test"));
+        frame.damage.push(DamagedRegion { x: 2, y: 2, width: 4, height: 2, cause });
+        frame.set_crop(1, 0, 0, 0);
+        let damage = frame.cropped_damage();
+        assert_eq!((damage[0].x, damage[0].y, damage[0].width, damage[0].height), (1, 2, 4, 2));
+
+        // A quarter turn maps (x, y, w, h) to (y, width - x - w, h, w)
+        frame.rotate_ccw(1);
+        let region = &frame.damage[0];
+        assert_eq!((region.x, region.y, region.width, region.height), (2, 2, 2, 4));
+        frame.mirror(1);
+        assert_eq!(frame.damage[0].y, 2);
+        frame.mirror(0);
+        assert_eq!(frame.damage[0].x, 0);
+    }
+}
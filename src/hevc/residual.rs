@@ -948,15 +948,21 @@ fn decode_coeff_abs_level_remaining(
         } else {
             0
         };
-        ((prefix << rice_param) + suffix) as i16
+        (prefix << rice_param) + suffix
     } else {
         // EGk part: suffix bits = prefix - 3 + rice_param
-        let suffix_bits = (prefix - 3 + rice_param as u32) as u8;
-        let suffix = cabac.decode_bypass_bits(suffix_bits)?;
+        let suffix_bits = prefix - 3 + rice_param as u32;
+        // Coefficient levels are 16-bit, so longer codes are corrupt
+        if suffix_bits > 16 {
+            return Err(HevcError::InvalidBitstream("coeff_abs_level_remaining too long"));
+        }
+        let suffix = cabac.decode_bypass_bits(suffix_bits as u8)?;
         // value = (((1 << (prefix-3)) + 3 - 1) << rice_param) + suffix
         let base = ((1u32 << (prefix - 3)) + 2) << rice_param;
-        (base + suffix) as i16
+        base + suffix
     };
+    let value = i16::try_from(value)
+        .map_err(|_| HevcError::InvalidBitstream("coeff_abs_level_remaining out of range"))?;
 
     // Update rice parameter: if baseLevel + value > 3 * (1 << rice_param), increase
     let threshold = 3 * (1 << rice_param);
@@ -0,0 +1,316 @@
+//! Sample adaptive offset (H.265 section 8.7.3)
+//!
+//! SAO runs after deblocking and adds an offset to each sample, selected
+//! either by the band of its value or by its edge category relative to two
+//! neighbours. Neighbours are read before SAO, so the planes are filtered
+//! line by line keeping pre-SAO copies of only the current line and the one
+//! above; the line below has not been filtered yet and is read in place.
+
+use super::ctu::{SaoComponentParams, SaoEoClass, SaoParams, SaoType};
+use super::params::Sps;
+use super::picture::DecodedFrame;
+use super::slice::SliceHeader;
+use alloc::vec::Vec;
+
+/// Band offset of one CTB component
+#[derive(Clone, Copy, Debug)]
+pub(super) struct BandOffset {
+    /// Shift from sample value to band index (bit depth - 5)
+    pub(super) shift: u8,
+    /// First of the four bands with an offset
+    pub(super) position: i32,
+    /// Offsets of the four bands
+    pub(super) offsets: [i32; 4],
+    /// Largest sample value
+    pub(super) max: i32,
+}
+
+/// Edge offset of one CTB component
+#[derive(Clone, Copy, Debug)]
+pub(super) struct EdgeOffset {
+    /// Offset of each edge category: valley, half valley, flat, half peak
+    /// and peak
+    pub(super) offsets: [i32; 5],
+    /// Largest sample value
+    pub(super) max: i32,
+}
+
+/// Band offset of a line: `src` holds the pre-SAO samples of `dst`
+pub(super) fn band_row(src: &[u16], dst: &mut [u16], band: &BandOffset) {
+    for (&sample, out) in src.iter().zip(dst.iter_mut()) {
+        let sample = sample as i32;
+        let relative = (sample >> band.shift) - band.position;
+        if (0..4).contains(&relative) {
+            *out = (sample + band.offsets[relative as usize]).clamp(0, band.max) as u16;
+        }
+    }
+}
+
+/// Edge offset of a line: `src` holds the pre-SAO samples of `dst`, and
+/// `n1` and `n2` their two neighbours along the edge class direction
+pub(super) fn edge_row([src, n1, n2]: [&[u16]; 3], dst: &mut [u16], edge: &EdgeOffset) {
+    for (((&sample, &a), &b), out) in src.iter().zip(n1).zip(n2).zip(dst.iter_mut()) {
+        let category = 2 + (sample as i32 - a as i32).signum() + (sample as i32 - b as i32).signum();
+        let offset = edge.offsets[category as usize];
+        if offset != 0 {
+            *out = (sample as i32 + offset).clamp(0, edge.max) as u16;
+        }
+    }
+}
+
+/// Apply SAO to the entire frame
+///
+/// `lines` holds the pre-SAO copies of the line being filtered and the one
+/// above it, and is reused across pictures.
+pub(super) fn apply_sao(
+    frame: &mut DecodedFrame,
+    sps: &Sps,
+    header: &SliceHeader,
+    sao_params: &[SaoParams],
+    lines: &mut [Vec<u16>; 2],
+) {
+    let ctb_size = sps.ctb_size() as usize;
+    let pic_width = sps.pic_width_in_luma_samples as usize;
+    let pic_height = sps.pic_height_in_luma_samples as usize;
+    let ctbs_per_row = pic_width.div_ceil(ctb_size);
+
+    if header.slice_sao_luma_flag {
+        let luma = PlaneLayout { width: pic_width, height: pic_height, ctb_size, ctbs_per_row };
+        filter_plane(&mut frame.y_plane, &luma, sps.bit_depth_y(), lines, |ctb| {
+            sao_params.get(ctb).map(|params| &params.luma)
+        });
+    }
+
+    // Chroma (4:2:0)
+    if header.slice_sao_chroma_flag {
+        let chroma = PlaneLayout {
+            width: pic_width / 2,
+            height: pic_height / 2,
+            ctb_size: ctb_size / 2,
+            ctbs_per_row,
+        };
+        filter_plane(&mut frame.cb_plane, &chroma, sps.bit_depth_c(), lines, |ctb| {
+            sao_params.get(ctb).map(|params| &params.cb)
+        });
+        filter_plane(&mut frame.cr_plane, &chroma, sps.bit_depth_c(), lines, |ctb| {
+            sao_params.get(ctb).map(|params| &params.cr)
+        });
+    }
+}
+
+/// Dimensions and CTB grid of a plane
+struct PlaneLayout {
+    width: usize,
+    height: usize,
+    ctb_size: usize,
+    ctbs_per_row: usize,
+}
+
+/// Filter one plane line by line with the parameters of each CTB
+fn filter_plane<'a>(
+    plane: &mut [u16],
+    layout: &PlaneLayout,
+    bit_depth: u8,
+    lines: &mut [Vec<u16>; 2],
+    params_of: impl Fn(usize) -> Option<&'a SaoComponentParams>,
+) {
+    let width = layout.width;
+    if width == 0 || layout.ctb_size == 0 {
+        return;
+    }
+    let height = layout.height.min(plane.len() / width);
+    let max = (1i32 << bit_depth) - 1;
+
+    for line in lines.iter_mut() {
+        line.clear();
+        line.resize(width, 0);
+    }
+
+    for y in 0..height {
+        let (row, below) = plane[y * width..].split_at_mut(width);
+        let [prev, cur] = &mut *lines;
+        cur.copy_from_slice(row);
+
+        for ctb_x in 0..layout.ctbs_per_row {
+            let ctb = (y / layout.ctb_size) * layout.ctbs_per_row + ctb_x;
+            let Some(params) = params_of(ctb) else {
+                continue;
+            };
+            let x0 = ctb_x * layout.ctb_size;
+            let x_end = (x0 + layout.ctb_size).min(width);
+            if x0 >= x_end {
+                continue;
+            }
+
+            match params.sao_type {
+                SaoType::None => {}
+                SaoType::Band => {
+                    let band = BandOffset {
+                        shift: bit_depth - 5,
+                        position: params.band_position as i32,
+                        offsets: params.offsets,
+                        max,
+                    };
+                    filter_band(&cur[x0..x_end], &mut row[x0..x_end], &band);
+                }
+                SaoType::Edge => {
+                    let ((dx1, dy1), (dx2, dy2)) = match params.eo_class {
+                        SaoEoClass::Horizontal => ((-1, 0), (1, 0)),
+                        SaoEoClass::Vertical => ((0, -1), (0, 1)),
+                        SaoEoClass::Diagonal135 => ((-1, -1), (1, 1)),
+                        SaoEoClass::Diagonal45 => ((1, -1), (-1, 1)),
+                    };
+                    // Skip samples whose neighbours are outside the picture
+                    if (dy1 < 0 || dy2 < 0) && y == 0 || (dy1 > 0 || dy2 > 0) && y + 1 >= height {
+                        continue;
+                    }
+                    let start = x0.max(usize::from(dx1 < 0 || dx2 < 0));
+                    let end = x_end.min(width - usize::from(dx1 > 0 || dx2 > 0));
+                    if start >= end {
+                        continue;
+                    }
+
+                    let line_at = |dy: isize| -> &[u16] {
+                        match dy {
+                            -1 => prev,
+                            0 => cur,
+                            _ => &below[..width],
+                        }
+                    };
+                    let neighbours = |(dx, dy): (isize, isize)| {
+                        let line = line_at(dy);
+                        &line[start.wrapping_add_signed(dx)..end.wrapping_add_signed(dx)]
+                    };
+                    let edge = EdgeOffset {
+                        offsets: [params.offsets[0], params.offsets[1], 0, -params.offsets[2], -params.offsets[3]],
+                        max,
+                    };
+                    filter_edge(
+                        [&cur[start..end], neighbours((dx1, dy1)), neighbours((dx2, dy2))],
+                        &mut row[start..end],
+                        &edge,
+                    );
+                }
+            }
+        }
+
+        lines.swap(0, 1);
+    }
+}
+
+/// Band offset of a line with the fastest available kernels
+fn filter_band(src: &[u16], dst: &mut [u16], band: &BandOffset) {
+    #[cfg(all(
+        feature = "unsafe-simd",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    if let Some(isa) = super::simd::Isa::detect() {
+        super::filter_simd::sao_band_row(isa, src, dst, band);
+        return;
+    }
+
+    band_row(src, dst, band);
+}
+
+/// Edge offset of a line with the fastest available kernels
+fn filter_edge(lines: [&[u16]; 3], dst: &mut [u16], edge: &EdgeOffset) {
+    #[cfg(all(
+        feature = "unsafe-simd",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    if let Some(isa) = super::simd::Isa::detect() {
+        super::filter_simd::sao_edge_row(isa, lines, dst, edge);
+        return;
+    }
+
+    edge_row(lines, dst, edge);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// SAO of a whole plane from a full pre-SAO copy, as a reference
+    fn reference_sao(plane: &mut [u16], layout: &PlaneLayout, bit_depth: u8, params: &[SaoComponentParams]) {
+        let src = plane.to_vec();
+        let (w, h) = (layout.width as i32, layout.height as i32);
+        let max = (1i32 << bit_depth) - 1;
+        for y in 0..h {
+            for x in 0..w {
+                let ctb = (y as usize / layout.ctb_size) * layout.ctbs_per_row + x as usize / layout.ctb_size;
+                let Some(params) = params.get(ctb) else { continue };
+                let idx = (y * w + x) as usize;
+                let sample = src[idx] as i32;
+                let offset = match params.sao_type {
+                    SaoType::None => 0,
+                    SaoType::Band => {
+                        let relative = (sample >> (bit_depth - 5)) - params.band_position as i32;
+                        if !(0..4).contains(&relative) {
+                            continue;
+                        }
+                        params.offsets[relative as usize]
+                    }
+                    SaoType::Edge => {
+                        let (dx, dy) = match params.eo_class {
+                            SaoEoClass::Horizontal => (1, 0),
+                            SaoEoClass::Vertical => (0, 1),
+                            SaoEoClass::Diagonal135 => (1, 1),
+                            SaoEoClass::Diagonal45 => (-1, 1),
+                        };
+                        let (x1, y1, x2, y2) = (x - dx, y - dy, x + dx, y + dy);
+                        if [x1, x2].iter().any(|&v| v < 0 || v >= w) || [y1, y2].iter().any(|&v| v < 0 || v >= h) {
+                            continue;
+                        }
+                        let n1 = src[(y1 * w + x1) as usize] as i32;
+                        let n2 = src[(y2 * w + x2) as usize] as i32;
+                        match 2 + (sample - n1).signum() + (sample - n2).signum() {
+                            0 => params.offsets[0],
+                            1 => params.offsets[1],
+                            2 => 0,
+                            3 => -params.offsets[2],
+                            _ => -params.offsets[3],
+                        }
+                    }
+                };
+                if offset != 0 {
+                    plane[idx] = (sample + offset).clamp(0, max) as u16;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_sao_matches_full_plane() {
+        let mut state = 7u32;
+        let mut next = |limit: u32| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 8) % limit
+        };
+        let classes = [SaoEoClass::Horizontal, SaoEoClass::Vertical, SaoEoClass::Diagonal135, SaoEoClass::Diagonal45];
+
+        for (width, height, ctb_size, bit_depth) in [(40, 24, 16, 8), (37, 29, 8, 10), (64, 9, 32, 8)] {
+            let layout = PlaneLayout { width, height, ctb_size, ctbs_per_row: width.div_ceil(ctb_size) };
+            // Smooth content so that every edge category occurs
+            let plane: Vec<u16> = (0..width * height)
+                .map(|i| ((i % width * 3 + i / width * 5) as u32 + next(6)) as u16 % (1 << bit_depth))
+                .collect();
+            let ctbs = layout.ctbs_per_row * height.div_ceil(ctb_size);
+            let params: Vec<SaoComponentParams> = (0..ctbs)
+                .map(|_| SaoComponentParams {
+                    sao_type: [SaoType::None, SaoType::Band, SaoType::Edge, SaoType::Edge][next(4) as usize],
+                    offsets: core::array::from_fn(|_| next(15) as i32 - 7),
+                    band_position: next(32) as u8,
+                    eo_class: classes[next(4) as usize],
+                })
+                .collect();
+
+            let mut expected = plane.clone();
+            reference_sao(&mut expected, &layout, bit_depth, &params);
+            let mut actual = plane.clone();
+            let mut lines = [vec![1; 3], Vec::new()];
+            filter_plane(&mut actual, &layout, bit_depth, &mut lines, |ctb| params.get(ctb));
+            assert_eq!(actual, expected, "{width}x{height} ctb {ctb_size}");
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! SEI (Supplemental Enhancement Information) message parsing
+//!
+//! Only the messages the decoder acts on are interpreted; everything else
+//! is exposed as raw payloads.
+
+use alloc::vec::Vec;
+
+use super::bitstream::BitstreamReader;
+use crate::error::HevcError;
+#[cfg(not(feature = "std"))]
+use crate::math::F64Ext as _;
+
+type Result<T> = core::result::Result<T, HevcError>;
+
+/// SEI payload type of depth_representation_info (H.265 F.14.2.4)
+pub const DEPTH_REPRESENTATION_INFO: u32 = 177;
+
+/// Raw SEI message
+#[derive(Debug, Clone)]
+pub struct SeiMessage<'a> {
+    /// payloadType
+    pub payload_type: u32,
+    /// Payload bytes (RBSP, emulation prevention already removed)
+    pub payload: &'a [u8],
+}
+
+/// Split an SEI RBSP (NAL payload after the header) into its messages
+pub fn parse_sei_messages(rbsp: &[u8]) -> Result<Vec<SeiMessage<'_>>> {
+    let mut messages = Vec::new();
+    let mut pos = 0;
+
+    // Stop at the rbsp_trailing_bits byte (0x80)
+    while pos < rbsp.len() && rbsp[pos] != 0x80 {
+        let payload_type = read_sei_value(rbsp, &mut pos)?;
+        let payload_size = read_sei_value(rbsp, &mut pos)? as usize;
+
+        let payload = rbsp
+            .get(pos..pos + payload_size)
+            .ok_or(HevcError::InvalidBitstream("SEI payload exceeds NAL unit"))?;
+        messages.push(SeiMessage {
+            payload_type,
+            payload,
+        });
+        pos += payload_size;
+    }
+
+    Ok(messages)
+}
+
+/// Read an SEI payloadType/payloadSize value (sequence of 0xFF bytes plus a final byte)
+fn read_sei_value(data: &[u8], pos: &mut usize) -> Result<u32> {
+    let mut value = 0u32;
+    loop {
+        let byte = *data
+            .get(*pos)
+            .ok_or(HevcError::InvalidBitstream("truncated SEI message header"))?;
+        *pos += 1;
+        value = value
+            .checked_add(byte as u32)
+            .ok_or(HevcError::InvalidBitstream("SEI value overflow"))?;
+        if byte != 0xFF {
+            return Ok(value);
+        }
+    }
+}
+
+/// Depth representation type (H.265 Table F.8)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthRepresentationType {
+    /// Sample values are uniformly quantized inverse depth (1/Z)
+    UniformInverseZ,
+    /// Sample values are uniformly quantized disparity
+    UniformDisparity,
+    /// Sample values are uniformly quantized depth (Z)
+    UniformZ,
+    /// Sample values are non-uniformly quantized disparity
+    NonuniformDisparity,
+    /// Reserved value
+    Reserved(u32),
+}
+
+impl DepthRepresentationType {
+    /// Create from raw depth_representation_type value
+    pub fn from_u32(val: u32) -> Self {
+        match val {
+            0 => Self::UniformInverseZ,
+            1 => Self::UniformDisparity,
+            2 => Self::UniformZ,
+            3 => Self::NonuniformDisparity,
+            _ => Self::Reserved(val),
+        }
+    }
+}
+
+/// Depth representation information (H.265 F.14.2.4)
+#[derive(Debug, Clone)]
+pub struct DepthRepresentationInfo {
+    /// How sample values map to depth or disparity
+    pub representation_type: DepthRepresentationType,
+    /// Nearest depth value (Z), if signalled
+    pub z_near: Option<f64>,
+    /// Farthest depth value (Z), if signalled
+    pub z_far: Option<f64>,
+    /// Minimum disparity, if signalled
+    pub d_min: Option<f64>,
+    /// Maximum disparity, if signalled
+    pub d_max: Option<f64>,
+    /// View ID the disparity refers to
+    pub disparity_ref_view_id: Option<u32>,
+    /// Non-linear representation model (type 3 only)
+    pub nonlinear_model: Vec<u32>,
+}
+
+impl DepthRepresentationInfo {
+    /// Parse a depth_representation_info SEI payload
+    pub fn parse(payload: &[u8]) -> Result<Self> {
+        let mut reader = BitstreamReader::new(payload);
+
+        let z_near_flag = reader.read_bit()? != 0;
+        let z_far_flag = reader.read_bit()? != 0;
+        let d_min_flag = reader.read_bit()? != 0;
+        let d_max_flag = reader.read_bit()? != 0;
+        let representation_type = DepthRepresentationType::from_u32(reader.read_ue()?);
+
+        let disparity_ref_view_id = if d_min_flag || d_max_flag {
+            Some(reader.read_ue()?)
+        } else {
+            None
+        };
+
+        let z_near = z_near_flag.then(|| read_rep_info_element(&mut reader)).transpose()?;
+        let z_far = z_far_flag.then(|| read_rep_info_element(&mut reader)).transpose()?;
+        let d_min = d_min_flag.then(|| read_rep_info_element(&mut reader)).transpose()?;
+        let d_max = d_max_flag.then(|| read_rep_info_element(&mut reader)).transpose()?;
+
+        let mut nonlinear_model = Vec::new();
+        if representation_type == DepthRepresentationType::NonuniformDisparity {
+            let num = reader.read_ue()? as usize + 1;
+            if num > 64 {
+                return Err(HevcError::InvalidBitstream("too many depth model entries"));
+            }
+            for _ in 0..num {
+                nonlinear_model.push(reader.read_ue()?);
+            }
+        }
+
+        Ok(Self {
+            representation_type,
+            z_near,
+            z_far,
+            d_min,
+            d_max,
+            disparity_ref_view_id,
+            nonlinear_model,
+        })
+    }
+
+    /// Convert a normalized sample value in [0.0, 1.0] to depth (Z)
+    ///
+    /// Only defined for the uniform Z and uniform inverse Z representations
+    /// when both z_near and z_far are signalled.
+    pub fn sample_to_depth(&self, normalized: f32) -> Option<f32> {
+        let (near, far) = (self.z_near? as f32, self.z_far? as f32);
+        match self.representation_type {
+            DepthRepresentationType::UniformZ => Some(near + normalized * (far - near)),
+            DepthRepresentationType::UniformInverseZ => {
+                // 1/Z is linear in the sample value, with 1.0 at z_near
+                let inv = 1.0 / far + normalized * (1.0 / near - 1.0 / far);
+                (inv > 0.0).then(|| 1.0 / inv)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Read a depth_rep_info_element (H.265 F.14.2.4.1) as a floating point value
+fn read_rep_info_element(reader: &mut BitstreamReader<'_>) -> Result<f64> {
+    let sign = reader.read_bit()?;
+    let exponent = reader.read_bits(7)? as i32;
+    let mantissa_len = reader.read_bits(5)? as i32 + 1;
+    let mantissa = reader.read_bits(mantissa_len as u8)? as f64;
+
+    let magnitude = if exponent == 0 {
+        mantissa * 2f64.powi(-(30 + mantissa_len))
+    } else if exponent < 127 {
+        2f64.powi(exponent - 31) * (1.0 + mantissa / 2f64.powi(mantissa_len))
+    } else {
+        return Err(HevcError::InvalidBitstream("depth_rep_info_element exponent 127"));
+    };
+
+    Ok(if sign != 0 { -magnitude } else { magnitude })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_parse_sei_messages() {
+        // Two messages: type 5 size 2, then type 255+2 size 1, then trailing bits
+        let rbsp = [5, 2, 0xAA, 0xBB, 0xFF, 2, 1, 0xCC, 0x80];
+        let messages = parse_sei_messages(&rbsp).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].payload_type, 5);
+        assert_eq!(messages[0].payload, &[0xAA, 0xBB]);
+        assert_eq!(messages[1].payload_type, 257);
+        assert_eq!(messages[1].payload, &[0xCC]);
+    }
+
+    #[test]
+    fn test_depth_representation_info() {
+        // z_near_flag=1, z_far_flag=1, d_min=0, d_max=0, type ue(2) = 011
+        // z_near: sign 0, exponent 31 (value 1.0 * (1 + m/2^len)), len-1 = 0, mantissa 1 => 1.5
+        // z_far:  sign 0, exponent 33 (4.0), len-1 = 0, mantissa 0 => 4.0
+        let bits = "1100011\
+                    0 0011111 00000 1\
+                    0 0100001 00000 0";
+        let bits: Vec<u8> = bits.bytes().filter(|b| *b == b'0' || *b == b'1').collect();
+        let mut payload = vec![0u8; bits.len().div_ceil(8)];
+        for (i, b) in bits.iter().enumerate() {
+            if *b == b'1' {
+                payload[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+
+        let info = DepthRepresentationInfo::parse(&payload).unwrap();
+        assert_eq!(info.representation_type, DepthRepresentationType::UniformZ);
+        assert_eq!(info.z_near, Some(1.5));
+        assert_eq!(info.z_far, Some(4.0));
+        assert_eq!(info.d_min, None);
+        assert_eq!(info.sample_to_depth(0.5), Some(2.75));
+    }
+}
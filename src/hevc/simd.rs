@@ -0,0 +1,111 @@
+//! CPU instruction sets of the SIMD kernels
+//!
+//! Enabled only when the `unsafe-simd` feature is active.
+
+#[cfg(target_arch = "aarch64")]
+use core::arch::aarch64::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+/// Instruction set of the SIMD kernels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Isa {
+    /// AVX2, with SSE4.1 for short rows
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    /// AVX-512F where a kernel has a 16-lane version, AVX2 otherwise
+    #[cfg(target_arch = "x86_64")]
+    Avx512,
+    /// NEON
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+impl Isa {
+    /// The best instruction set supported by the CPU, if any
+    #[inline]
+    pub(super) fn detect() -> Option<Self> {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if !is_x86_feature_detected!("avx2") {
+                return None;
+            }
+            if is_x86_feature_detected!("avx512f") {
+                return Some(Self::Avx512);
+            }
+            Some(Self::Avx2)
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            std::arch::is_aarch64_feature_detected!("neon").then_some(Self::Neon)
+        }
+    }
+
+    /// Every instruction set the CPU supports, for testing each kernel
+    #[cfg(test)]
+    pub(super) fn supported() -> Vec<Self> {
+        let mut isas = Vec::new();
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                isas.push(Self::Avx2);
+                if is_x86_feature_detected!("avx512f") {
+                    isas.push(Self::Avx512);
+                }
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        isas.extend(Self::detect());
+        isas
+    }
+}
+
+/// Load eight u16 samples as i32 lanes
+///
+/// # Safety
+/// The CPU must support AVX2 and `src` must point to eight samples.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+pub(super) unsafe fn load_u16x8_avx2(src: *const u16) -> __m256i {
+    // SAFETY: the caller provides eight samples
+    _mm256_cvtepu16_epi32(unsafe { _mm_loadu_si128(src.cast()) })
+}
+
+/// Pack eight i32 lanes in 0..=65535 to u16 and store them
+///
+/// # Safety
+/// The CPU must support AVX2 and `dst` must have room for eight samples.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+pub(super) unsafe fn store_u16x8_avx2(dst: *mut u16, v: __m256i) {
+    let packed = _mm256_permute4x64_epi64::<0b1000>(_mm256_packus_epi32(v, v));
+    // SAFETY: the caller provides room for eight samples
+    unsafe { _mm_storeu_si128(dst.cast(), _mm256_castsi256_si128(packed)) };
+}
+
+/// Load eight u16 samples as two vectors of four i32 lanes
+///
+/// # Safety
+/// The CPU must support NEON and `src` must point to eight samples.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub(super) unsafe fn load_u16x8_neon(src: *const u16) -> [int32x4_t; 2] {
+    // SAFETY: the caller provides eight samples
+    let v = unsafe { vld1q_u16(src) };
+    [
+        vreinterpretq_s32_u32(vmovl_u16(vget_low_u16(v))),
+        vreinterpretq_s32_u32(vmovl_u16(vget_high_u16(v))),
+    ]
+}
+
+/// Narrow two vectors of four i32 lanes in 0..=65535 to u16 and store them
+///
+/// # Safety
+/// The CPU must support NEON and `dst` must have room for eight samples.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+pub(super) unsafe fn store_u16x8_neon(dst: *mut u16, [low, high]: [int32x4_t; 2]) {
+    let v = vcombine_u16(vqmovun_s32(low), vqmovun_s32(high));
+    // SAFETY: the caller provides room for eight samples
+    unsafe { vst1q_u16(dst, v) };
+}
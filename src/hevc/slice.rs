@@ -3,8 +3,10 @@
 //! This module handles parsing of slice segment headers (H.265 spec 7.3.6)
 //! and orchestrates CTU decoding for each slice.
 
-use super::bitstream::{BitstreamReader, NalUnit};
-use super::params::{Pps, Sps};
+use alloc::vec::Vec;
+
+use super::bitstream::{BitstreamReader, NalType, NalUnit};
+use super::params::{self, LayerInfo, Pps, ShortTermRefPicSet, Sps};
 use crate::error::HevcError;
 
 type Result<T> = core::result::Result<T, HevcError>;
@@ -36,6 +38,48 @@ impl SliceType {
     pub fn is_intra(self) -> bool {
         self == Self::I
     }
+
+    /// Check if this is a B slice
+    pub fn is_b(self) -> bool {
+        self == Self::B
+    }
+}
+
+/// Long-term reference picture entry of a slice header (H.265 7.4.7.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LongTermRefPic {
+    /// PocLsbLt
+    pub poc_lsb_lt: u32,
+    /// UsedByCurrPicLt
+    pub used_by_curr_pic_lt: bool,
+    /// delta_poc_msb_present_flag
+    pub delta_poc_msb_present_flag: bool,
+    /// DeltaPocMsbCycleLt
+    pub delta_poc_msb_cycle_lt: u32,
+}
+
+/// Explicit weighted prediction factors for one reference picture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeightFactors {
+    /// LumaWeightLX
+    pub luma_weight: i32,
+    /// luma_offset_lX (at 8-bit precision)
+    pub luma_offset: i32,
+    /// ChromaWeightLX for Cb and Cr
+    pub chroma_weight: [i32; 2],
+    /// ChromaOffsetLX for Cb and Cr (at 8-bit precision)
+    pub chroma_offset: [i32; 2],
+}
+
+/// Prediction weight table (H.265 7.3.6.3)
+#[derive(Debug, Clone, Default)]
+pub struct PredWeightTable {
+    /// luma_log2_weight_denom
+    pub luma_log2_weight_denom: u8,
+    /// ChromaLog2WeightDenom
+    pub chroma_log2_weight_denom: u8,
+    /// Factors per reference index, for list 0 and list 1
+    pub factors: [Vec<WeightFactors>; 2],
 }
 
 /// Partition mode for coding units
@@ -187,12 +231,41 @@ pub struct SliceHeader {
     pub colour_plane_id: u8,
     /// Picture order count LSB
     pub slice_pic_order_cnt_lsb: u32,
+    /// Active short-term reference picture set (empty for IDR pictures)
+    pub short_term_ref_pic_set: ShortTermRefPicSet,
+    /// Long-term reference pictures
+    pub long_term_ref_pics: Vec<LongTermRefPic>,
+    /// Slice temporal MVP enabled flag
+    pub slice_temporal_mvp_enabled_flag: bool,
+    /// RefPicLayerId: nuh_layer_id of each active inter-layer reference picture
+    pub inter_layer_ref_layers: Vec<u8>,
 
     /// SAO luma flag
     pub slice_sao_luma_flag: bool,
     /// SAO chroma flag
     pub slice_sao_chroma_flag: bool,
 
+    /// Num ref idx L0 active minus 1
+    pub num_ref_idx_l0_active_minus1: u8,
+    /// Num ref idx L1 active minus 1
+    pub num_ref_idx_l1_active_minus1: u8,
+    /// list_entry_l0, if ref_pic_list_modification_flag_l0 is set
+    pub list_entry_l0: Option<Vec<u8>>,
+    /// list_entry_l1, if ref_pic_list_modification_flag_l1 is set
+    pub list_entry_l1: Option<Vec<u8>>,
+    /// MVD L1 zero flag
+    pub mvd_l1_zero_flag: bool,
+    /// CABAC init flag
+    pub cabac_init_flag: bool,
+    /// Collocated picture comes from list 0
+    pub collocated_from_l0_flag: bool,
+    /// Reference index of the collocated picture
+    pub collocated_ref_idx: u8,
+    /// Explicit weighted prediction table, if enabled
+    pub pred_weight_table: Option<PredWeightTable>,
+    /// Derived: MaxNumMergeCand = 5 - five_minus_max_num_merge_cand
+    pub max_num_merge_cand: u8,
+
     /// Slice QP delta
     pub slice_qp_delta: i8,
     /// Slice Cb QP offset
@@ -234,9 +307,78 @@ pub struct SliceParseResult {
 }
 
 impl SliceHeader {
+    /// NumPicTotalCurr: reference pictures usable by the current picture
+    pub fn num_pic_total_curr(&self) -> usize {
+        self.short_term_ref_pic_set.num_used_by_curr()
+            + self.inter_layer_ref_layers.len()
+            + self
+                .long_term_ref_pics
+                .iter()
+                .filter(|lt| lt.used_by_curr_pic_lt)
+                .count()
+    }
+
+    /// Number of active entries in reference picture list 0 or 1
+    pub fn num_ref_idx_active(&self, list: usize) -> usize {
+        match (self.slice_type, list) {
+            (SliceType::I, _) | (SliceType::P, 1) => 0,
+            (_, 0) => self.num_ref_idx_l0_active_minus1 as usize + 1,
+            _ => self.num_ref_idx_l1_active_minus1 as usize + 1,
+        }
+    }
+
+    /// CABAC initType (H.265 9.3.2.2)
+    pub fn init_type(&self) -> u8 {
+        match self.slice_type {
+            SliceType::I => 0,
+            SliceType::P => {
+                if self.cabac_init_flag {
+                    2
+                } else {
+                    1
+                }
+            }
+            SliceType::B => {
+                if self.cabac_init_flag {
+                    1
+                } else {
+                    2
+                }
+            }
+        }
+    }
+
+    /// Read the PPS ID of a slice segment without parsing the full header
+    pub fn peek_pps_id(nal: &NalUnit<'_>) -> Result<u8> {
+        let mut reader = BitstreamReader::new(&nal.payload);
+        reader.read_bit()?; // first_slice_segment_in_pic_flag
+        if nal.nal_type.is_irap() {
+            reader.read_bit()?; // no_output_of_prior_pics_flag
+        }
+        let pps_id = reader.read_ue()?;
+        if pps_id > 63 {
+            return Err(HevcError::InvalidBitstream("PPS ID out of range"));
+        }
+        Ok(pps_id as u8)
+    }
+
     /// Parse slice segment header from NAL unit
     /// Returns both the header and the byte offset where slice data begins
     pub fn parse(nal: &NalUnit<'_>, sps: &Sps, pps: &Pps) -> Result<SliceParseResult> {
+        Self::parse_layer(nal, sps, pps, None)
+    }
+
+    /// Parse a slice segment header of any layer (H.265 F.7.3.6.1)
+    ///
+    /// `layer` describes the NAL unit's layer for non-base layers, which
+    /// may signal inter-layer reference pictures.
+    pub fn parse_layer(
+        nal: &NalUnit<'_>,
+        sps: &Sps,
+        pps: &Pps,
+        layer: Option<&LayerInfo>,
+    ) -> Result<SliceParseResult> {
+        let layer = layer.filter(|_| nal.nuh_layer_id > 0);
         let mut reader = BitstreamReader::new(&nal.payload);
 
         let first_slice_segment_in_pic_flag = reader.read_bit()? != 0;
@@ -252,6 +394,9 @@ impl SliceHeader {
         if pps_id != pps.pps_id {
             return Err(HevcError::InvalidBitstream("PPS ID mismatch"));
         }
+        if let Some(tiles) = pps.tile_info.as_ref().filter(|_| pps.tiles_enabled_flag) {
+            tiles.validate(sps)?;
+        }
 
         let dependent_slice_segment_flag;
         let slice_segment_address;
@@ -278,7 +423,8 @@ impl SliceHeader {
             return Err(HevcError::Unsupported("dependent slice segments"));
         }
 
-        // Skip reserved bits
+        // Skip reserved bits (discardable_flag and cross_layer_bla_flag in
+        // multi-layer streams)
         for _ in 0..pps.num_extra_slice_header_bits {
             reader.read_bit()?;
         }
@@ -299,19 +445,30 @@ impl SliceHeader {
             0
         };
 
-        // For IDR pictures, POC LSB and ref pic set are not present
-        let slice_pic_order_cnt_lsb = if !nal.nal_type.is_idr() {
+        // For IDR pictures, POC LSB and ref pic set are not present (BLA
+        // pictures still carry them). IDR pictures of non-base layers carry
+        // the POC LSB unless the VPS says otherwise.
+        let is_idr = matches!(nal.nal_type, NalType::IdrWRadl | NalType::IdrNLp);
+        let poc_lsb_present = layer.is_some_and(|l| !l.poc_lsb_not_present_flag);
+        let slice_pic_order_cnt_lsb = if !is_idr || poc_lsb_present {
             let poc_bits = sps.log2_max_pic_order_cnt_lsb_minus4 + 4;
             reader.read_bits(poc_bits)?
         } else {
             0
         };
 
-        // Skip short-term and long-term ref pic set parsing for I-slices in still images
-        // These are not needed for single-frame HEIC decoding
-        if !nal.nal_type.is_idr() {
-            skip_ref_pic_set(&mut reader, sps)?;
-        }
+        // Reference picture sets are not present for IDR pictures
+        let (short_term_ref_pic_set, long_term_ref_pics, slice_temporal_mvp_enabled_flag) =
+            if !is_idr {
+                parse_ref_pic_sets(&mut reader, sps)?
+            } else {
+                (ShortTermRefPicSet::default(), Vec::new(), false)
+            };
+
+        let inter_layer_ref_layers = match layer {
+            Some(layer) => parse_inter_layer_refs(&mut reader, layer)?,
+            None => Vec::new(),
+        };
 
         // SAO flags
         let (slice_sao_luma_flag, slice_sao_chroma_flag) =
@@ -327,14 +484,97 @@ impl SliceHeader {
                 (false, false)
             };
 
-        // For P/B slices, there would be ref idx and weight table parsing here
-        // We skip this for I-slices (HEIC still images are typically I-frames)
+        let mut num_ref_idx_l0_active_minus1 = 0;
+        let mut num_ref_idx_l1_active_minus1 = 0;
+        let mut list_entry_l0 = None;
+        let mut list_entry_l1 = None;
+        let mut mvd_l1_zero_flag = false;
+        let mut cabac_init_flag = false;
+        let mut collocated_from_l0_flag = true;
+        let mut collocated_ref_idx = 0;
+        let mut pred_weight_table = None;
+        let mut max_num_merge_cand = 5;
+
         if slice_type != SliceType::I {
-            return Err(HevcError::Unsupported("P/B slices not yet implemented"));
+            num_ref_idx_l0_active_minus1 = pps.num_ref_idx_l0_default_active_minus1;
+            if slice_type.is_b() {
+                num_ref_idx_l1_active_minus1 = pps.num_ref_idx_l1_default_active_minus1;
+            }
+            let num_ref_idx_active_override_flag = reader.read_bit()? != 0;
+            if num_ref_idx_active_override_flag {
+                num_ref_idx_l0_active_minus1 = read_num_ref_idx(&mut reader)?;
+                if slice_type.is_b() {
+                    num_ref_idx_l1_active_minus1 = read_num_ref_idx(&mut reader)?;
+                }
+            }
+
+            let num_pic_total_curr = short_term_ref_pic_set.num_used_by_curr()
+                + long_term_ref_pics.iter().filter(|lt| lt.used_by_curr_pic_lt).count()
+                + inter_layer_ref_layers.len();
+            if num_pic_total_curr == 0 {
+                return Err(HevcError::InvalidBitstream("inter slice without reference pictures"));
+            }
+
+            if pps.lists_modification_present_flag && num_pic_total_curr > 1 {
+                let entry_bits = ceil_log2(num_pic_total_curr as u32);
+                list_entry_l0 =
+                    read_list_modification(&mut reader, num_ref_idx_l0_active_minus1, entry_bits)?;
+                if slice_type.is_b() {
+                    list_entry_l1 = read_list_modification(
+                        &mut reader,
+                        num_ref_idx_l1_active_minus1,
+                        entry_bits,
+                    )?;
+                }
+            }
+
+            if slice_type.is_b() {
+                mvd_l1_zero_flag = reader.read_bit()? != 0;
+            }
+            if pps.cabac_init_present_flag {
+                cabac_init_flag = reader.read_bit()? != 0;
+            }
+
+            if slice_temporal_mvp_enabled_flag {
+                if slice_type.is_b() {
+                    collocated_from_l0_flag = reader.read_bit()? != 0;
+                }
+                let num_active = if collocated_from_l0_flag {
+                    num_ref_idx_l0_active_minus1
+                } else {
+                    num_ref_idx_l1_active_minus1
+                };
+                if num_active > 0 {
+                    collocated_ref_idx = reader.read_ue()?.min(255) as u8;
+                    if collocated_ref_idx > num_active {
+                        return Err(HevcError::InvalidBitstream("collocated_ref_idx out of range"));
+                    }
+                }
+            }
+
+            if (pps.weighted_pred_flag && slice_type == SliceType::P)
+                || (pps.weighted_bipred_flag && slice_type.is_b())
+            {
+                pred_weight_table = Some(parse_pred_weight_table(
+                    &mut reader,
+                    sps,
+                    slice_type,
+                    num_ref_idx_l0_active_minus1,
+                    num_ref_idx_l1_active_minus1,
+                )?);
+            }
+
+            let five_minus_max_num_merge_cand = reader.read_ue()?;
+            if five_minus_max_num_merge_cand > 4 {
+                return Err(HevcError::InvalidBitstream("invalid five_minus_max_num_merge_cand"));
+            }
+            max_num_merge_cand = 5 - five_minus_max_num_merge_cand as u8;
         }
 
         // slice_qp_delta
-        let slice_qp_delta = reader.read_se()? as i8;
+        let slice_qp_delta = reader.read_se()?;
+        let slice_qp_delta = i8::try_from(slice_qp_delta)
+            .map_err(|_| HevcError::InvalidBitstream("slice_qp_delta out of range"))?;
 
         // Chroma QP offsets
         let (slice_cb_qp_offset, slice_cr_qp_offset) =
@@ -390,14 +630,24 @@ impl SliceHeader {
         let (num_entry_point_offsets, entry_point_offsets) =
             if pps.tiles_enabled_flag || pps.entropy_coding_sync_enabled_flag {
                 let n = reader.read_ue()?;
+                if n > max_entry_points(sps, pps) {
+                    return Err(HevcError::InvalidBitstream("too many entry point offsets"));
+                }
                 let mut offsets = Vec::with_capacity(n as usize);
                 if n > 0 {
-                    let offset_len = reader.read_ue()? as u8 + 1;
-                    for i in 0..n {
-                        let mut offset = reader.read_bits(offset_len)? + 1; // +1 per H.265 spec
-                        if i > 0 {
-                            offset += offsets[(i - 1) as usize]; // cumulative like libde265
-                        }
+                    let offset_len_minus1 = reader.read_ue()?;
+                    if offset_len_minus1 > 31 {
+                        return Err(HevcError::InvalidBitstream("offset_len_minus1 out of range"));
+                    }
+                    let offset_len = offset_len_minus1 as u8 + 1;
+                    for _ in 0..n {
+                        // +1 per H.265 spec, kept cumulative like libde265
+                        let previous = offsets.last().copied().unwrap_or(0);
+                        let offset = reader
+                            .read_bits(offset_len)?
+                            .checked_add(1)
+                            .and_then(|delta| delta.checked_add(previous))
+                            .ok_or(HevcError::InvalidBitstream("entry point offset overflow"))?;
                         offsets.push(offset);
                     }
                 }
@@ -423,6 +673,10 @@ impl SliceHeader {
 
         // Calculate derived values
         let slice_qp_y = 26 + pps.init_qp_minus26 as i32 + slice_qp_delta as i32;
+        let qp_bd_offset_y = 6 * i32::from(sps.bit_depth_luma_minus8);
+        if !(-qp_bd_offset_y..=51).contains(&slice_qp_y) {
+            return Err(HevcError::InvalidBitstream("SliceQpY out of range"));
+        }
 
         Ok(SliceParseResult {
             header: SliceHeader {
@@ -435,8 +689,22 @@ impl SliceHeader {
                 pic_output_flag,
                 colour_plane_id,
                 slice_pic_order_cnt_lsb,
+                short_term_ref_pic_set,
+                long_term_ref_pics,
+                slice_temporal_mvp_enabled_flag,
+                inter_layer_ref_layers,
                 slice_sao_luma_flag,
                 slice_sao_chroma_flag,
+                num_ref_idx_l0_active_minus1,
+                num_ref_idx_l1_active_minus1,
+                list_entry_l0,
+                list_entry_l1,
+                mvd_l1_zero_flag,
+                cabac_init_flag,
+                collocated_from_l0_flag,
+                collocated_ref_idx,
+                pred_weight_table,
+                max_num_merge_cand,
                 slice_qp_delta,
                 slice_cb_qp_offset,
                 slice_cr_qp_offset,
@@ -455,79 +723,268 @@ impl SliceHeader {
     }
 }
 
-/// Skip reference picture set parsing (for non-IDR pictures)
-fn skip_ref_pic_set(reader: &mut BitstreamReader<'_>, sps: &Sps) -> Result<()> {
-    let short_term_ref_pic_set_sps_flag = reader.read_bit()? != 0;
+/// Largest num_entry_point_offsets allowed for a picture (H.265 7.4.7.1)
+fn max_entry_points(sps: &Sps, pps: &Pps) -> u32 {
+    let (columns, rows) = pps
+        .tile_info
+        .as_ref()
+        .filter(|_| pps.tiles_enabled_flag)
+        .map_or((1, 1), |tiles| {
+            (
+                u32::from(tiles.num_tile_columns_minus1) + 1,
+                u32::from(tiles.num_tile_rows_minus1) + 1,
+            )
+        });
+    if pps.entropy_coding_sync_enabled_flag {
+        columns.saturating_mul(sps.pic_height_in_ctbs()) - 1
+    } else {
+        columns * rows - 1
+    }
+}
 
-    if !short_term_ref_pic_set_sps_flag {
-        // Parse inline short-term ref pic set
-        skip_short_term_ref_pic_set(reader, sps.num_short_term_ref_pic_sets)?;
-    } else if sps.num_short_term_ref_pic_sets > 1 {
-        let bits = ceil_log2(sps.num_short_term_ref_pic_sets as u32);
-        reader.read_bits(bits)?;
+/// Parse the inter-layer prediction fields of a non-base layer slice
+///
+/// Returns RefPicLayerId for the NumActiveRefLayerPics active inter-layer
+/// reference pictures (H.265 F.7.4.7.1). With default_ref_layers_active_flag
+/// every direct reference layer is used; sub-layer limits on inter-layer
+/// prediction (max_tid_il_ref_pics_plus1) are not applied.
+fn parse_inter_layer_refs(reader: &mut BitstreamReader<'_>, layer: &LayerInfo) -> Result<Vec<u8>> {
+    let direct = &layer.direct_ref_layers;
+    if direct.is_empty() {
+        return Ok(Vec::new());
+    }
+    if layer.default_ref_layers_active_flag {
+        return Ok(direct.clone());
+    }
+
+    let inter_layer_pred_enabled_flag = reader.read_bit()? != 0;
+    if !inter_layer_pred_enabled_flag {
+        return Ok(Vec::new());
+    }
+    if direct.len() == 1 {
+        return Ok(direct.clone());
+    }
+
+    let bits = ceil_log2(direct.len() as u32);
+    let num_active = if layer.max_one_active_ref_layer_flag {
+        1
+    } else {
+        reader.read_bits(bits)? as usize + 1
+    };
+    if num_active > direct.len() {
+        return Err(HevcError::InvalidBitstream("num_inter_layer_ref_pics_minus1 out of range"));
     }
+    if num_active == direct.len() {
+        return Ok(direct.clone());
+    }
+    (0..num_active)
+        .map(|_| {
+            let idc = reader.read_bits(bits)? as usize;
+            direct
+                .get(idc)
+                .copied()
+                .ok_or(HevcError::InvalidBitstream("inter_layer_pred_layer_idc out of range"))
+        })
+        .collect()
+}
+
+/// Parse the short-term and long-term reference picture sets of a non-IDR
+/// slice, plus slice_temporal_mvp_enabled_flag (H.265 7.3.6.1)
+fn parse_ref_pic_sets(
+    reader: &mut BitstreamReader<'_>,
+    sps: &Sps,
+) -> Result<(ShortTermRefPicSet, Vec<LongTermRefPic>, bool)> {
+    let short_term_ref_pic_set_sps_flag = reader.read_bit()? != 0;
+
+    let st_rps = if !short_term_ref_pic_set_sps_flag {
+        // Inline short-term ref pic set, may be predicted from the SPS sets
+        params::parse_short_term_ref_pic_set(
+            reader,
+            sps.num_short_term_ref_pic_sets,
+            &sps.st_ref_pic_sets,
+        )?
+    } else {
+        let idx = if sps.num_short_term_ref_pic_sets > 1 {
+            let bits = ceil_log2(sps.num_short_term_ref_pic_sets as u32);
+            reader.read_bits(bits)? as usize
+        } else {
+            0
+        };
+        sps.st_ref_pic_sets
+            .get(idx)
+            .cloned()
+            .ok_or(HevcError::InvalidBitstream("short_term_ref_pic_set_idx out of range"))?
+    };
 
     // Long-term ref pics
+    let mut long_term_ref_pics: Vec<LongTermRefPic> = Vec::new();
     if sps.long_term_ref_pics_present_flag {
-        let num_long_term_sps = reader.read_ue()?;
-        let num_long_term_pics = reader.read_ue()?;
+        let num_long_term_ref_pics_sps = sps.lt_ref_pic_poc_lsb_sps.len();
+        let num_long_term_sps = if num_long_term_ref_pics_sps > 0 {
+            reader.read_ue()? as usize
+        } else {
+            0
+        };
+        let num_long_term_pics = reader.read_ue()? as usize;
+        if num_long_term_sps > num_long_term_ref_pics_sps || num_long_term_pics > 32 {
+            return Err(HevcError::InvalidBitstream("too many long-term ref pics"));
+        }
 
         let poc_bits = sps.log2_max_pic_order_cnt_lsb_minus4 + 4;
+        let lt_idx_bits = ceil_log2(num_long_term_ref_pics_sps as u32);
 
         for i in 0..(num_long_term_sps + num_long_term_pics) {
-            if i < num_long_term_sps {
-                // lt_idx_sps - bits depend on num_long_term_ref_pics_sps
-                // For simplicity, skip
-                reader.read_ue()?;
+            let (poc_lsb_lt, used_by_curr_pic_lt) = if i < num_long_term_sps {
+                let lt_idx_sps = if num_long_term_ref_pics_sps > 1 {
+                    reader.read_bits(lt_idx_bits)? as usize
+                } else {
+                    0
+                };
+                let poc = *sps
+                    .lt_ref_pic_poc_lsb_sps
+                    .get(lt_idx_sps)
+                    .ok_or(HevcError::InvalidBitstream("lt_idx_sps out of range"))?;
+                (poc, sps.used_by_curr_pic_lt_sps_flag[lt_idx_sps])
             } else {
-                reader.read_bits(poc_bits)?; // poc_lsb_lt
-                reader.read_bit()?; // used_by_curr_pic_lt_flag
-            }
-            let delta_poc_msb_present = reader.read_bit()? != 0;
-            if delta_poc_msb_present {
-                reader.read_ue()?; // delta_poc_msb_cycle_lt
-            }
-        }
-    }
+                let poc = reader.read_bits(poc_bits)?;
+                (poc, reader.read_bit()? != 0)
+            };
 
-    // Temporal MVP
-    if sps.sps_temporal_mvp_enabled_flag {
-        reader.read_bit()?; // slice_temporal_mvp_enabled_flag
-    }
+            let delta_poc_msb_present_flag = reader.read_bit()? != 0;
+            let delta_poc_msb_cycle = if delta_poc_msb_present_flag {
+                reader.read_ue()?
+            } else {
+                0
+            };
+            // DeltaPocMsbCycleLt accumulates within each of the two groups (7-52)
+            let delta_poc_msb_cycle_lt = if i == 0 || i == num_long_term_sps {
+                delta_poc_msb_cycle
+            } else {
+                delta_poc_msb_cycle.saturating_add(long_term_ref_pics[i - 1].delta_poc_msb_cycle_lt)
+            };
 
-    Ok(())
-}
+            long_term_ref_pics.push(LongTermRefPic {
+                poc_lsb_lt,
+                used_by_curr_pic_lt,
+                delta_poc_msb_present_flag,
+                delta_poc_msb_cycle_lt,
+            });
+        }
+    }
 
-/// Skip inline short-term ref pic set
-fn skip_short_term_ref_pic_set(reader: &mut BitstreamReader<'_>, idx: u8) -> Result<()> {
-    let inter_ref_pic_set_prediction_flag = if idx != 0 {
+    let slice_temporal_mvp_enabled_flag = if sps.sps_temporal_mvp_enabled_flag {
         reader.read_bit()? != 0
     } else {
         false
     };
 
-    if inter_ref_pic_set_prediction_flag {
-        // Would need previous set info
-        reader.read_bit()?; // delta_rps_sign
-        reader.read_ue()?; // abs_delta_rps_minus1
-        // This is incomplete - would need to loop based on previous set
-        return Err(HevcError::Unsupported("inter-predicted ref pic set"));
+    Ok((st_rps, long_term_ref_pics, slice_temporal_mvp_enabled_flag))
+}
+
+/// Read num_ref_idx_lX_active_minus1
+fn read_num_ref_idx(reader: &mut BitstreamReader<'_>) -> Result<u8> {
+    let val = reader.read_ue()?;
+    if val > 14 {
+        return Err(HevcError::InvalidBitstream("num_ref_idx_active_minus1 out of range"));
     }
+    Ok(val as u8)
+}
 
-    let num_negative_pics = reader.read_ue()?;
-    let num_positive_pics = reader.read_ue()?;
+/// Read ref_pic_list_modification_flag_lX and its list entries (H.265 7.3.6.2)
+fn read_list_modification(
+    reader: &mut BitstreamReader<'_>,
+    num_ref_idx_active_minus1: u8,
+    entry_bits: u8,
+) -> Result<Option<Vec<u8>>> {
+    let ref_pic_list_modification_flag = reader.read_bit()? != 0;
+    if !ref_pic_list_modification_flag {
+        return Ok(None);
+    }
+    let mut entries = Vec::with_capacity(num_ref_idx_active_minus1 as usize + 1);
+    for _ in 0..=num_ref_idx_active_minus1 {
+        entries.push(reader.read_bits(entry_bits)? as u8);
+    }
+    Ok(Some(entries))
+}
 
-    for _ in 0..num_negative_pics {
-        reader.read_ue()?; // delta_poc_s0_minus1
-        reader.read_bit()?; // used_by_curr_pic_s0_flag
+/// Parse pred_weight_table (H.265 7.3.6.3)
+fn parse_pred_weight_table(
+    reader: &mut BitstreamReader<'_>,
+    sps: &Sps,
+    slice_type: SliceType,
+    num_ref_idx_l0_active_minus1: u8,
+    num_ref_idx_l1_active_minus1: u8,
+) -> Result<PredWeightTable> {
+    let luma_log2_weight_denom = reader.read_ue()?;
+    if luma_log2_weight_denom > 7 {
+        return Err(HevcError::InvalidBitstream("luma_log2_weight_denom out of range"));
     }
+    let has_chroma = sps.chroma_array_type() != 0;
+    let chroma_log2_weight_denom = if has_chroma {
+        let denom = luma_log2_weight_denom as i32 + reader.read_se()?;
+        if !(0..=7).contains(&denom) {
+            return Err(HevcError::InvalidBitstream("chroma log2 weight denom out of range"));
+        }
+        denom as u8
+    } else {
+        0
+    };
+
+    let mut table = PredWeightTable {
+        luma_log2_weight_denom: luma_log2_weight_denom as u8,
+        chroma_log2_weight_denom,
+        factors: [Vec::new(), Vec::new()],
+    };
 
-    for _ in 0..num_positive_pics {
-        reader.read_ue()?; // delta_poc_s1_minus1
-        reader.read_bit()?; // used_by_curr_pic_s1_flag
+    let num_lists = if slice_type.is_b() { 2 } else { 1 };
+    for list in 0..num_lists {
+        let num_refs = if list == 0 {
+            num_ref_idx_l0_active_minus1
+        } else {
+            num_ref_idx_l1_active_minus1
+        } as usize
+            + 1;
+
+        let mut luma_weight_flags = [false; 16];
+        for flag in luma_weight_flags.iter_mut().take(num_refs) {
+            *flag = reader.read_bit()? != 0;
+        }
+        let mut chroma_weight_flags = [false; 16];
+        if has_chroma {
+            for flag in chroma_weight_flags.iter_mut().take(num_refs) {
+                *flag = reader.read_bit()? != 0;
+            }
+        }
+
+        let luma_default = 1i32 << table.luma_log2_weight_denom;
+        let chroma_default = 1i32 << table.chroma_log2_weight_denom;
+        for i in 0..num_refs {
+            let mut factors = WeightFactors {
+                luma_weight: luma_default,
+                luma_offset: 0,
+                chroma_weight: [chroma_default; 2],
+                chroma_offset: [0; 2],
+            };
+            if luma_weight_flags[i] {
+                factors.luma_weight = luma_default + reader.read_se()?.clamp(-128, 127);
+                factors.luma_offset = reader.read_se()?.clamp(-128, 127);
+            }
+            if chroma_weight_flags[i] {
+                for j in 0..2 {
+                    let weight = chroma_default + reader.read_se()?.clamp(-128, 127);
+                    let delta_offset = reader.read_se()?.clamp(-512, 511);
+                    // ChromaOffsetLX derivation (7-56) with wpOffsetHalfRangeC = 128
+                    let offset = (128 - ((128 * weight) >> table.chroma_log2_weight_denom))
+                        + delta_offset;
+                    factors.chroma_weight[j] = weight;
+                    factors.chroma_offset[j] = offset.clamp(-128, 127);
+                }
+            }
+            table.factors[list].push(factors);
+        }
     }
 
-    Ok(())
+    Ok(table)
 }
 
 /// Calculate ceil(log2(x))
@@ -562,4 +1019,36 @@ mod tests {
         assert_eq!(IntraPredMode::from_u8(34), Some(IntraPredMode::Angular34));
         assert_eq!(IntraPredMode::from_u8(35), None);
     }
+
+    #[test]
+    fn test_entry_point_offsets_are_bounded() {
+        use crate::hevc::bitstream::NalType;
+        use crate::hevc::test_util::{BitWriter, SliceHeaderSpec, StreamParams, annex_b, nal_unit};
+
+        // 64x64 picture of 16x16 CTBs with WPP: up to 3 entry points
+        let params = StreamParams { wpp: true, ..StreamParams::default() };
+        let stream = |offsets: &[u32]| {
+            let mut w = BitWriter::default();
+            let header = SliceHeaderSpec {
+                entry_point_offsets_minus1: offsets.to_vec(),
+                ..SliceHeaderSpec::idr()
+            };
+            header.write(&params, &mut w);
+            w.bits(0xFFFF_FFFF, 32);
+            let slice = nal_unit(NalType::IdrNLp, &w.finish());
+            annex_b(&[params.sps(), params.pps(), slice])
+        };
+
+        // offset_len_minus1 = 31; the offsets used to overflow when summed
+        let err = crate::hevc::decode(&stream(&[0xFFFF_FFFF, 0xFFFF_FFFF])).unwrap_err();
+        assert!(matches!(
+            err.without_location(),
+            HevcError::InvalidBitstream("entry point offset overflow")
+        ));
+        let err = crate::hevc::decode(&stream(&[0, 0, 0, 0])).unwrap_err();
+        assert!(matches!(
+            err.without_location(),
+            HevcError::InvalidBitstream("too many entry point offsets")
+        ));
+    }
 }
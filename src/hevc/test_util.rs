@@ -0,0 +1,806 @@
+//! Bitstream writers shared by the decoder tests
+//!
+//! These build small but valid parameter sets, slice headers and CABAC
+//! coded slice data, so tests do not depend on sample files.
+
+use super::bitstream::NalType;
+use super::cabac::{ContextModel, context, init_values};
+use super::slice::SliceType;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Bit writer for building RBSPs
+#[derive(Default)]
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    pub(crate) fn bits(&mut self, value: u32, n: u8) {
+        for i in (0..n).rev() {
+            if self.bits % 8 == 0 {
+                self.bytes.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            *self.bytes.last_mut().unwrap() |= bit << (7 - self.bits % 8);
+            self.bits += 1;
+        }
+    }
+
+    pub(crate) fn flag(&mut self, value: bool) {
+        self.bits(value as u32, 1);
+    }
+
+    pub(crate) fn ue(&mut self, value: u32) {
+        let code = u64::from(value) + 1;
+        let len = 64 - code.leading_zeros() as u8;
+        self.bits(0, len - 1);
+        for i in (0..len).rev() {
+            self.bits((code >> i) as u32 & 1, 1);
+        }
+    }
+
+    pub(crate) fn se(&mut self, value: i32) {
+        let code = if value > 0 { 2 * value - 1 } else { -2 * value };
+        self.ue(code as u32);
+    }
+
+    /// Pad with zero bits to the next byte boundary
+    pub(crate) fn align_zero(&mut self) {
+        while self.bits % 8 != 0 {
+            self.bits(0, 1);
+        }
+    }
+
+    /// Append rbsp_stop_one_bit and the alignment zero bits
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        self.bits(1, 1);
+        self.bytes
+    }
+}
+
+/// NAL unit with a two-byte header and emulation prevention
+pub(crate) fn nal_unit(nal_type: NalType, rbsp: &[u8]) -> Vec<u8> {
+    layer_nal_unit(nal_type, 0, rbsp)
+}
+
+/// NAL unit of the layer `nuh_layer_id` (below 32)
+pub(crate) fn layer_nal_unit(nal_type: NalType, nuh_layer_id: u8, rbsp: &[u8]) -> Vec<u8> {
+    let mut out = vec![(nal_type as u8) << 1, nuh_layer_id << 3 | 1];
+    let mut zeros = 0;
+    for &byte in rbsp {
+        if zeros >= 2 && byte <= 3 {
+            out.push(3);
+            zeros = 0;
+        }
+        out.push(byte);
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+    }
+    out
+}
+
+/// Annex B byte stream of the given NAL units
+pub(crate) fn annex_b(nal_units: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for nal in nal_units {
+        out.extend_from_slice(&[0, 0, 0, 1]);
+        out.extend_from_slice(nal);
+    }
+    out
+}
+
+/// Sequence and picture parameters of a test stream
+///
+/// 4:2:0 8-bit Main profile with 8x8 minimum coding blocks, 4x4 to 32x32
+/// transform blocks and no SAO, AMP or PCM.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StreamParams {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) log2_ctb_size: u8,
+    /// entropy_coding_sync_enabled_flag
+    pub(crate) wpp: bool,
+    /// init_qp_minus26
+    pub(crate) init_qp_minus26: i32,
+    /// sps_max_num_reorder_pics
+    pub(crate) max_num_reorder_pics: u32,
+    /// sps_temporal_mvp_enabled_flag
+    pub(crate) tmvp: bool,
+    /// weighted_pred_flag
+    pub(crate) weighted_pred: bool,
+    /// Whether the deblocking filter is enabled
+    pub(crate) deblocking: bool,
+}
+
+impl Default for StreamParams {
+    fn default() -> Self {
+        Self {
+            width: 64,
+            height: 64,
+            log2_ctb_size: 4,
+            wpp: false,
+            init_qp_minus26: 0,
+            max_num_reorder_pics: 0,
+            tmvp: false,
+            weighted_pred: false,
+            deblocking: true,
+        }
+    }
+}
+
+impl StreamParams {
+    /// log2_max_pic_order_cnt_lsb
+    pub(crate) const LOG2_POC_LSB: u8 = 8;
+
+    /// VPS NAL unit of a two-view MV-HEVC stream
+    ///
+    /// The second view has nuh_layer_id 1 and predicts from the base view;
+    /// both views are output. The extension ends after the fields the
+    /// decoder reads, before dpb_size().
+    pub(crate) fn vps(&self) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(0, 4); // vps_video_parameter_set_id
+        w.flag(true); // vps_base_layer_internal_flag
+        w.flag(true); // vps_base_layer_available_flag
+        w.bits(1, 6); // vps_max_layers_minus1
+        w.bits(0, 3); // vps_max_sub_layers_minus1
+        w.flag(true); // vps_temporal_id_nesting_flag
+        w.bits(0xFFFF, 16); // vps_reserved_0xffff_16bits
+        write_profile_tier_level(&mut w);
+        w.flag(true); // vps_sub_layer_ordering_info_present_flag
+        w.ue(self.max_num_reorder_pics + 1); // vps_max_dec_pic_buffering_minus1
+        w.ue(self.max_num_reorder_pics);
+        w.ue(0); // vps_max_latency_increase_plus1
+        w.bits(1, 6); // vps_max_layer_id
+        w.ue(1); // vps_num_layer_sets_minus1
+        w.bits(0b11, 2); // layer set 1 holds both layers
+        w.flag(false); // vps_timing_info_present_flag
+        w.flag(true); // vps_extension_flag
+        while w.bits % 8 != 0 {
+            w.flag(true); // vps_extension_alignment_bit_equal_to_one
+        }
+
+        // vps_extension(): the second layer's profile_tier_level() has
+        // only a level
+        w.bits(93, 8);
+        w.flag(true); // splitting_flag
+        w.bits(1 << 14, 16); // scalability_mask_flag: multiview
+        w.flag(false); // vps_nuh_layer_id_present_flag
+        w.bits(1, 4); // view_id_len
+        w.bits(0b01, 2); // view_id_val of both views
+        w.flag(true); // direct_dependency_flag[1][0]
+        // sub-layer and inter-layer temporal ID limits
+        w.bits(0, 2);
+        w.flag(true); // default_ref_layers_active_flag
+        w.ue(1); // vps_num_profile_tier_level_minus1
+        w.ue(0); // num_add_olss
+        w.bits(0, 2); // default_output_layer_idc: output every layer
+        w.bits(0b01, 2); // profile_tier_level_idx of both layers
+        w.ue(0); // vps_num_rep_formats_minus1
+        w.bits(self.width, 16);
+        w.bits(self.height, 16);
+        w.flag(true); // chroma_and_bit_depth_vps_present_flag
+        w.bits(1, 2); // chroma_format_vps_idc
+        w.bits(0, 8); // bit depths minus 8
+        w.flag(false); // conformance_window_vps_flag
+        w.flag(true); // max_one_active_ref_layer_flag
+        w.flag(false); // vps_poc_lsb_aligned_flag
+        nal_unit(NalType::VpsNut, &w.finish())
+    }
+
+    /// SPS NAL unit
+    pub(crate) fn sps(&self) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(0, 4); // sps_video_parameter_set_id
+        w.bits(0, 3); // sps_max_sub_layers_minus1
+        w.flag(true); // sps_temporal_id_nesting_flag
+        write_profile_tier_level(&mut w);
+        w.ue(0); // sps_seq_parameter_set_id
+        w.ue(1); // chroma_format_idc
+        w.ue(self.width);
+        w.ue(self.height);
+        w.flag(false); // conformance_window_flag
+        w.ue(0);
+        w.ue(0);
+        w.ue(u32::from(Self::LOG2_POC_LSB) - 4);
+        w.flag(true); // sps_sub_layer_ordering_info_present_flag
+        w.ue(self.max_num_reorder_pics + 1); // sps_max_dec_pic_buffering_minus1
+        w.ue(self.max_num_reorder_pics);
+        w.ue(0); // sps_max_latency_increase_plus1
+        self.write_sps_coding_tools(&mut w);
+        nal_unit(NalType::SpsNut, &w.finish())
+    }
+
+    /// SPS NAL unit of the second view of [`vps`](Self::vps), with ID 1
+    ///
+    /// The picture format and buffer sizes are inherited from the VPS.
+    pub(crate) fn layer_sps(&self) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(0, 4); // sps_video_parameter_set_id
+        w.bits(7, 3); // sps_ext_or_max_sub_layers_minus1: MultiLayerExtSpsFlag
+        w.ue(1); // sps_seq_parameter_set_id
+        w.flag(false); // update_rep_format_flag
+        w.ue(u32::from(Self::LOG2_POC_LSB) - 4);
+        self.write_sps_coding_tools(&mut w);
+        layer_nal_unit(NalType::SpsNut, 1, &w.finish())
+    }
+
+    /// SPS fields from the coding block sizes to the end
+    fn write_sps_coding_tools(&self, w: &mut BitWriter) {
+        w.ue(0); // 8x8 minimum coding blocks
+        w.ue(u32::from(self.log2_ctb_size) - 3);
+        w.ue(0); // 4x4 minimum transform blocks
+        w.ue(u32::from(self.log2_ctb_size.min(5)) - 2);
+        w.ue(1); // max_transform_hierarchy_depth_inter
+        w.ue(1); // max_transform_hierarchy_depth_intra
+        // scaling lists, AMP, SAO, PCM
+        w.bits(0, 4);
+        w.ue(0); // num_short_term_ref_pic_sets
+        w.flag(false); // long_term_ref_pics_present_flag
+        w.flag(self.tmvp);
+        // strong intra smoothing, VUI, extensions
+        w.bits(0, 3);
+    }
+
+    /// PPS NAL unit
+    pub(crate) fn pps(&self) -> Vec<u8> {
+        self.layer_pps(0)
+    }
+
+    /// PPS NAL unit of a layer of [`vps`](Self::vps), referring to its SPS
+    ///
+    /// The IDs of the parameter sets of a layer are its nuh_layer_id.
+    pub(crate) fn layer_pps(&self, nuh_layer_id: u8) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.ue(nuh_layer_id.into()); // pps_pic_parameter_set_id
+        w.ue(nuh_layer_id.into()); // pps_seq_parameter_set_id
+        // dependent slices, output flag, extra slice header bits, sign data
+        // hiding, cabac_init_present_flag
+        w.bits(0, 7);
+        w.ue(0); // num_ref_idx_l0_default_active_minus1
+        w.ue(0); // num_ref_idx_l1_default_active_minus1
+        w.se(self.init_qp_minus26);
+        // constrained intra prediction, transform skip, cu_qp_delta
+        w.bits(0, 3);
+        w.se(0); // pps_cb_qp_offset
+        w.se(0); // pps_cr_qp_offset
+        w.flag(false); // pps_slice_chroma_qp_offsets_present_flag
+        w.flag(self.weighted_pred);
+        // weighted bi-prediction, transquant bypass, tiles
+        w.bits(0, 3);
+        w.flag(self.wpp);
+        w.flag(true); // pps_loop_filter_across_slices_enabled_flag
+        w.flag(!self.deblocking); // deblocking_filter_control_present_flag
+        if !self.deblocking {
+            w.flag(false); // deblocking_filter_override_enabled_flag
+            w.flag(true); // pps_deblocking_filter_disabled_flag
+        }
+        // scaling lists, list modification
+        w.bits(0, 2);
+        w.ue(0); // log2_parallel_merge_level_minus2
+        // slice header extension, PPS extension
+        w.bits(0, 2);
+        layer_nal_unit(NalType::PpsNut, nuh_layer_id, &w.finish())
+    }
+}
+
+/// General profile_tier_level() of Main profile, level 3.1
+fn write_profile_tier_level(w: &mut BitWriter) {
+    w.bits(0, 3);
+    w.bits(1, 5);
+    w.bits(0x6000_0000, 32);
+    w.bits(0b1001, 4);
+    w.bits(0, 32);
+    w.bits(0, 12);
+    w.bits(93, 8);
+}
+
+/// Fields of a slice segment header that the tests vary
+#[derive(Debug, Clone)]
+pub(crate) struct SliceHeaderSpec {
+    pub(crate) nal_type: NalType,
+    /// nuh_layer_id; slices of the second view of [`StreamParams::vps`]
+    /// refer to its [layer PPS](StreamParams::layer_pps)
+    pub(crate) nuh_layer_id: u8,
+    pub(crate) slice_type: SliceType,
+    pub(crate) poc_lsb: u32,
+    /// POC distances of the pictures before the current one that it
+    /// references, nearest first
+    pub(crate) refs_before: Vec<u32>,
+    /// POC distances of the referenced pictures after the current one,
+    /// nearest first
+    pub(crate) refs_after: Vec<u32>,
+    /// slice_temporal_mvp_enabled_flag, with the collocated picture the
+    /// first of list 0
+    pub(crate) temporal_mvp: bool,
+    /// Explicit weight and offset of the only list 0 luma reference, in
+    /// 1/64 units
+    pub(crate) luma_weight: Option<(i32, i32)>,
+    /// MaxNumMergeCand
+    pub(crate) max_num_merge_cand: u32,
+    /// entry_point_offset_minus1 values
+    pub(crate) entry_point_offsets_minus1: Vec<u32>,
+}
+
+impl SliceHeaderSpec {
+    /// Header of an IDR picture's only slice
+    pub(crate) fn idr() -> Self {
+        Self {
+            nal_type: NalType::IdrNLp,
+            nuh_layer_id: 0,
+            slice_type: SliceType::I,
+            poc_lsb: 0,
+            refs_before: Vec::new(),
+            refs_after: Vec::new(),
+            temporal_mvp: false,
+            luma_weight: None,
+            max_num_merge_cand: 1,
+            entry_point_offsets_minus1: Vec::new(),
+        }
+    }
+
+    /// Write the header of the first slice segment of a picture, up to and
+    /// including byte_alignment()
+    pub(crate) fn write(&self, params: &StreamParams, w: &mut BitWriter) {
+        w.flag(true); // first_slice_segment_in_pic_flag
+        let is_irap = (16..=23).contains(&(self.nal_type as u8));
+        if is_irap {
+            w.flag(false); // no_output_of_prior_pics_flag
+        }
+        w.ue(self.nuh_layer_id.into()); // slice_pic_parameter_set_id
+        w.ue(self.slice_type as u32);
+        let is_idr = matches!(self.nal_type, NalType::IdrWRadl | NalType::IdrNLp);
+        // Non-base layer IDR pictures carry the POC LSB (F.7.3.6.1)
+        if !is_idr || self.nuh_layer_id > 0 {
+            w.bits(self.poc_lsb, StreamParams::LOG2_POC_LSB);
+        }
+        if !is_idr {
+            // Short-term reference picture set coded in the header
+            w.flag(false); // short_term_ref_pic_set_sps_flag
+            w.ue(self.refs_before.len() as u32);
+            w.ue(self.refs_after.len() as u32);
+            for refs in [&self.refs_before, &self.refs_after] {
+                let mut previous = 0;
+                for &distance in refs {
+                    w.ue(distance - previous - 1);
+                    w.flag(true); // used_by_curr_pic_flag
+                    previous = distance;
+                }
+            }
+            if params.tmvp {
+                w.flag(self.temporal_mvp);
+            }
+        }
+        if self.slice_type != SliceType::I {
+            w.flag(false); // num_ref_idx_active_override_flag
+            if self.slice_type == SliceType::B {
+                w.flag(false); // mvd_l1_zero_flag
+            }
+            if self.temporal_mvp && self.slice_type == SliceType::B {
+                w.flag(true); // collocated_from_l0_flag
+            }
+            if params.weighted_pred && self.slice_type == SliceType::P {
+                // pred_weight_table() for one list 0 reference
+                let (weight, offset) = self.luma_weight.unwrap_or((64, 0));
+                w.ue(6); // luma_log2_weight_denom
+                w.se(0); // delta_chroma_log2_weight_denom
+                w.flag(self.luma_weight.is_some());
+                w.flag(false); // chroma_weight_l0_flag
+                if self.luma_weight.is_some() {
+                    w.se(weight - 64);
+                    w.se(offset);
+                }
+            }
+            w.ue(5 - self.max_num_merge_cand);
+        }
+        w.se(0); // slice_qp_delta
+        if params.deblocking {
+            w.flag(true); // slice_loop_filter_across_slices_enabled_flag
+        }
+        if params.wpp {
+            let offsets = &self.entry_point_offsets_minus1;
+            w.ue(offsets.len() as u32);
+            if let Some(&max) = offsets.iter().max() {
+                let len = (32 - max.leading_zeros()).max(1) as u8;
+                w.ue(u32::from(len) - 1);
+                for &offset in offsets {
+                    w.bits(offset, len);
+                }
+            }
+        }
+        // byte_alignment()
+        w.bits(1, 1);
+        w.align_zero();
+    }
+}
+
+impl SliceHeaderSpec {
+    /// Slice NAL unit coding a whole picture with one coding unit per CTB,
+    /// in raster order
+    ///
+    /// With WPP, the entry points are set from the coded substreams.
+    pub(crate) fn slice(&self, params: &StreamParams, cus: &[TestCu]) -> Vec<u8> {
+        let (data, substreams) = slice_data(params, self, cus);
+        let mut header = self.clone();
+        if params.wpp {
+            header.entry_point_offsets_minus1 = substreams.iter().map(|&len| len - 1).collect();
+        }
+        let mut w = BitWriter::default();
+        header.write(params, &mut w);
+        let mut rbsp = w.bytes;
+        rbsp.extend_from_slice(&data);
+        layer_nal_unit(self.nal_type, self.nuh_layer_id, &rbsp)
+    }
+}
+
+/// Slices of a two-view access unit of [`StreamParams::vps`]: the base
+/// view coded as `base`, then the second view predicted from it, displaced
+/// horizontally by `disparity` quarter luma samples
+pub(crate) fn stereo_slices(params: &StreamParams, base: &[TestCu], disparity: i32) -> [Vec<u8>; 2] {
+    // An IRAP picture of a non-base layer may still use inter-layer
+    // prediction, and does so through P slices
+    let second_view = SliceHeaderSpec {
+        nuh_layer_id: 1,
+        slice_type: SliceType::P,
+        ..SliceHeaderSpec::idr()
+    };
+    let mut cus = vec![TestCu::SKIP; base.len()];
+    cus[0] = TestCu::Amvp { mvd: [disparity, 0], mvp_flag: false, level: 0 };
+    [SliceHeaderSpec::idr().slice(params, base), second_view.slice(params, &cus)]
+}
+
+/// Coding unit covering a whole CTB of a test picture
+///
+/// Residuals hold a single DC coefficient level per block, and only intra
+/// coding units have chroma residuals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TestCu {
+    /// Intra 2Nx2N from the first most probable mode, with a luma DC level
+    /// (0: no residual)
+    Intra(i32),
+    /// Intra 2Nx2N like [`Intra`](Self::Intra), with Y, Cb and Cr DC levels
+    IntraYCbCr([i32; 3]),
+    /// Inter 2Nx2N from a merge candidate, with a non-zero DC level
+    Merge { merge_idx: u32, level: i32 },
+    /// Inter 2Nx2N from the list 0 reference, its motion vector a
+    /// difference to a predictor, with a DC level (0: no residual)
+    ///
+    /// Only supported in P slices.
+    Amvp { mvd: [i32; 2], mvp_flag: bool, level: i32 },
+    /// Skipped coding unit (merge without residual)
+    Skip { merge_idx: u32 },
+}
+
+impl TestCu {
+    /// Skipped coding unit from the first merge candidate
+    pub(crate) const SKIP: Self = Self::Skip { merge_idx: 0 };
+}
+
+/// CABAC coded slice_segment_data() for `cus`, one per CTB in raster order,
+/// and the size of each WPP substream
+///
+/// CTBs of 16 or 32 luma samples are supported.
+pub(crate) fn slice_data(params: &StreamParams, header: &SliceHeaderSpec, cus: &[TestCu]) -> (Vec<u8>, Vec<u32>) {
+    let slice_type = header.slice_type;
+    let ctb_size = 1u32 << params.log2_ctb_size;
+    let width_in_ctbs = params.width.div_ceil(ctb_size) as usize;
+    assert!((4..=5).contains(&params.log2_ctb_size));
+    assert_eq!(cus.len(), width_in_ctbs * params.height.div_ceil(ctb_size) as usize);
+    assert!(!params.wpp || width_in_ctbs >= 2);
+
+    let init_type = match slice_type {
+        SliceType::I => 0,
+        SliceType::P => 1,
+        SliceType::B => 2,
+    };
+    let mut cabac = CabacWriter::new(init_type, 26 + params.init_qp_minus26);
+    let mut substreams = Vec::new();
+    let mut synced = cabac.ctx;
+    for (addr, &cu) in cus.iter().enumerate() {
+        let (x, y) = (addr % width_in_ctbs, addr / width_in_ctbs);
+        let is_skip = |cu: &TestCu| matches!(cu, TestCu::Skip { .. });
+        let left_skip = x > 0 && is_skip(&cus[addr - 1]);
+        let above_skip = y > 0 && is_skip(&cus[addr - width_in_ctbs]);
+        let neighbours = (left_skip, above_skip);
+        write_coding_unit(&mut cabac, params.log2_ctb_size, header, cu, neighbours);
+        if params.wpp && x == 1 {
+            synced = cabac.ctx;
+        }
+        let last = addr + 1 == cus.len();
+        cabac.terminate(last); // end_of_slice_segment_flag
+        if params.wpp && x + 1 == width_in_ctbs && !last {
+            cabac.terminate(true); // end_of_subset_one_bit
+            substreams.push(cabac.len() as u32 - substreams.iter().sum::<u32>());
+            cabac.ctx = synced;
+        }
+    }
+    (cabac.into_bytes(), substreams)
+}
+
+/// coding_quadtree() of a CTB coded as one coding unit, whose left and
+/// above neighbours are skipped or not
+fn write_coding_unit(
+    cabac: &mut CabacWriter,
+    log2_size: u8,
+    header: &SliceHeaderSpec,
+    cu: TestCu,
+    (left_skip, above_skip): (bool, bool),
+) {
+    let slice_type = header.slice_type;
+    cabac.decision(context::SPLIT_CU_FLAG, false);
+    if slice_type != SliceType::I {
+        let ctx_inc = usize::from(left_skip) + usize::from(above_skip);
+        cabac.decision(context::CU_SKIP_FLAG + ctx_inc, matches!(cu, TestCu::Skip { .. }));
+        if let TestCu::Skip { merge_idx } = cu {
+            write_merge_idx(cabac, header.max_num_merge_cand, merge_idx);
+            return;
+        }
+        let intra = matches!(cu, TestCu::Intra(_) | TestCu::IntraYCbCr(_));
+        cabac.decision(context::PRED_MODE_FLAG, intra);
+    }
+    // min(5 - log2TrafoSize, 2) for 16x16 and 32x32 blocks
+    let split_transform_ctx = context::SPLIT_TRANSFORM_FLAG + (5 - usize::from(log2_size));
+    match cu {
+        TestCu::Intra(level) => write_intra(cabac, log2_size, [level, 0, 0]),
+        TestCu::IntraYCbCr(levels) => write_intra(cabac, log2_size, levels),
+        TestCu::Merge { merge_idx, level } => {
+            assert_ne!(level, 0, "cbf_luma is inferred for merged coding units");
+            cabac.decision(context::PART_MODE, true); // PART_2Nx2N
+            cabac.decision(context::MERGE_FLAG, true);
+            write_merge_idx(cabac, header.max_num_merge_cand, merge_idx);
+            // rqt_root_cbf and cbf_luma are inferred
+            cabac.decision(split_transform_ctx, false);
+            cabac.decision(context::CBF_CBCR, false);
+            cabac.decision(context::CBF_CBCR, false);
+            write_dc_residual(cabac, log2_size, 0, level);
+        }
+        TestCu::Amvp { mvd, mvp_flag, level } => {
+            assert_eq!(slice_type, SliceType::P, "inter_pred_idc is not written");
+            cabac.decision(context::PART_MODE, true); // PART_2Nx2N
+            cabac.decision(context::MERGE_FLAG, false);
+            // ref_idx_l0 is inferred with one reference
+            write_mvd(cabac, mvd);
+            cabac.decision(context::MVP_LX_FLAG, mvp_flag);
+            cabac.decision(context::RQT_ROOT_CBF, level != 0);
+            if level != 0 {
+                // cbf_luma is inferred
+                cabac.decision(split_transform_ctx, false);
+                cabac.decision(context::CBF_CBCR, false);
+                cabac.decision(context::CBF_CBCR, false);
+                write_dc_residual(cabac, log2_size, 0, level);
+            }
+        }
+        TestCu::Skip { .. } => unreachable!(),
+    }
+}
+
+/// Intra prediction and transform tree of a coding unit with Y, Cb and Cr
+/// DC levels
+fn write_intra(cabac: &mut CabacWriter, log2_size: u8, levels: [i32; 3]) {
+    let split_transform_ctx = context::SPLIT_TRANSFORM_FLAG + (5 - usize::from(log2_size));
+    cabac.decision(context::PREV_INTRA_LUMA_PRED_FLAG, true);
+    cabac.bypass(false); // mpm_idx 0
+    cabac.decision(context::INTRA_CHROMA_PRED_MODE, false); // DM
+    cabac.decision(split_transform_ctx, false);
+    cabac.decision(context::CBF_CBCR, levels[1] != 0);
+    cabac.decision(context::CBF_CBCR, levels[2] != 0);
+    cabac.decision(context::CBF_LUMA + 1, levels[0] != 0);
+    // 4:2:0 chroma blocks are half the luma size
+    for (c_idx, &level) in levels.iter().enumerate() {
+        if level != 0 {
+            let log2_block = if c_idx == 0 { log2_size } else { log2_size - 1 };
+            write_dc_residual(cabac, log2_block, c_idx, level);
+        }
+    }
+}
+
+/// merge_idx, if there is more than one candidate
+fn write_merge_idx(cabac: &mut CabacWriter, max_num_merge_cand: u32, merge_idx: u32) {
+    let c_max = max_num_merge_cand - 1;
+    assert!(merge_idx <= c_max);
+    if c_max == 0 {
+        return;
+    }
+    // Truncated rice with the first bin context coded
+    cabac.decision(context::MERGE_IDX, merge_idx > 0);
+    for i in 1..c_max.min(merge_idx + 1) {
+        cabac.bypass(i < merge_idx);
+    }
+}
+
+/// mvd_coding()
+fn write_mvd(cabac: &mut CabacWriter, mvd: [i32; 2]) {
+    for v in mvd {
+        cabac.decision(context::ABS_MVD_GREATER0_FLAG, v != 0);
+    }
+    for v in mvd {
+        if v != 0 {
+            cabac.decision(context::ABS_MVD_GREATER1_FLAG, v.abs() > 1);
+        }
+    }
+    for v in mvd {
+        if v != 0 {
+            if v.abs() > 1 {
+                write_exp_golomb(cabac, v.unsigned_abs() - 2, 1);
+            }
+            cabac.bypass(v < 0); // mvd_sign_flag
+        }
+    }
+}
+
+/// k-th order Exp-Golomb bypass bins
+fn write_exp_golomb(cabac: &mut CabacWriter, value: u32, k: u32) {
+    // Prefix of n ones for values from ((1 << n) - 1) << k
+    let n = 31 - ((value >> k) + 1).leading_zeros();
+    for _ in 0..n {
+        cabac.bypass(true);
+    }
+    cabac.bypass(false);
+    let suffix = value - (((1 << n) - 1) << k);
+    for i in (0..n + k).rev() {
+        cabac.bypass((suffix >> i) & 1 != 0);
+    }
+}
+
+/// residual_coding() of a block of component `c_idx` with only a DC
+/// coefficient
+fn write_dc_residual(cabac: &mut CabacWriter, log2_size: u8, c_idx: usize, level: i32) {
+    // last_sig_coeff_x_prefix and last_sig_coeff_y_prefix of 0
+    let ctx_offset = if c_idx == 0 {
+        3 * (usize::from(log2_size) - 2) + ((usize::from(log2_size) - 1) >> 2)
+    } else {
+        15
+    };
+    cabac.decision(context::LAST_SIG_COEFF_X_PREFIX + ctx_offset, false);
+    cabac.decision(context::LAST_SIG_COEFF_Y_PREFIX + ctx_offset, false);
+    // ctxSet 0 for the DC sub-block, greater1Ctx 1; chroma contexts follow
+    // the luma ones
+    let (greater1_ctx, greater2_ctx) = if c_idx == 0 { (1, 0) } else { (17, 4) };
+    let abs = level.unsigned_abs();
+    cabac.decision(context::COEFF_ABS_LEVEL_GREATER1_FLAG + greater1_ctx, abs > 1);
+    if abs > 1 {
+        cabac.decision(context::COEFF_ABS_LEVEL_GREATER2_FLAG + greater2_ctx, abs > 2);
+    }
+    cabac.bypass(level < 0);
+    if abs > 2 {
+        // coeff_abs_level_remaining with cRiceParam 0: a unary prefix of up
+        // to 3, then Exp-Golomb
+        let remaining = abs - 3;
+        if remaining < 4 {
+            for _ in 0..remaining {
+                cabac.bypass(true);
+            }
+            cabac.bypass(false);
+        } else {
+            let k = 31 - (remaining - 2).leading_zeros();
+            for _ in 0..k + 3 {
+                cabac.bypass(true);
+            }
+            cabac.bypass(false);
+            let suffix = remaining - ((1 << k) + 2);
+            for i in (0..k).rev() {
+                cabac.bypass((suffix >> i) & 1 != 0);
+            }
+        }
+    }
+}
+
+/// CABAC encoder (H.265 9.3.5 in the 2013 edition, informative)
+pub(crate) struct CabacWriter {
+    out: BitWriter,
+    low: u32,
+    range: u32,
+    first_bit: bool,
+    bits_outstanding: u32,
+    /// Context variables, indexed like the decoder's
+    pub(crate) ctx: [ContextModel; context::NUM_CONTEXTS],
+}
+
+impl CabacWriter {
+    /// Encoder with its contexts initialized for a slice (9.3.2.2)
+    pub(crate) fn new(init_type: u8, slice_qp: i32) -> Self {
+        let mut ctx = [ContextModel::new(154); context::NUM_CONTEXTS];
+        for (ctx, &init_value) in ctx.iter_mut().zip(init_values(init_type)) {
+            ctx.init(init_value, slice_qp);
+        }
+        Self {
+            out: BitWriter::default(),
+            low: 0,
+            range: 510,
+            first_bit: true,
+            bits_outstanding: 0,
+            ctx,
+        }
+    }
+
+    /// EncodeDecision
+    pub(crate) fn decision(&mut self, ctx_idx: usize, bin: bool) {
+        let ctx = &mut self.ctx[ctx_idx];
+        let lps_range = ctx.lps_range(self.range);
+        self.range -= lps_range;
+        if u8::from(bin) != ctx.get_state().1 {
+            self.low += self.range;
+            self.range = lps_range;
+        }
+        ctx.update(u8::from(bin));
+        self.renormalize();
+    }
+
+    /// EncodeBypass
+    pub(crate) fn bypass(&mut self, bin: bool) {
+        self.low <<= 1;
+        if bin {
+            self.low += self.range;
+        }
+        if self.low >= 1024 {
+            self.put_bit(1);
+            self.low -= 1024;
+        } else if self.low < 512 {
+            self.put_bit(0);
+        } else {
+            self.low -= 512;
+            self.bits_outstanding += 1;
+        }
+    }
+
+    /// EncodeTerminate; a 1 also flushes the encoder, writes the stop or
+    /// alignment bit, byte-aligns and restarts the arithmetic coder
+    pub(crate) fn terminate(&mut self, bin: bool) {
+        self.range -= 2;
+        if !bin {
+            self.renormalize();
+            return;
+        }
+        // EncodeFlush
+        self.low += self.range;
+        self.range = 2;
+        self.renormalize();
+        self.put_bit((self.low >> 9) & 1);
+        self.out.bits(((self.low >> 7) & 3) | 1, 2);
+        self.out.align_zero();
+        self.low = 0;
+        self.range = 510;
+        self.first_bit = true;
+        self.bits_outstanding = 0;
+    }
+
+    /// Bytes written so far
+    pub(crate) fn len(&self) -> usize {
+        self.out.bytes.len()
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.out.bytes
+    }
+
+    /// RenormE
+    fn renormalize(&mut self) {
+        while self.range < 256 {
+            if self.low < 256 {
+                self.put_bit(0);
+            } else if self.low >= 512 {
+                self.low -= 512;
+                self.put_bit(1);
+            } else {
+                self.low -= 256;
+                self.bits_outstanding += 1;
+            }
+            self.range <<= 1;
+            self.low <<= 1;
+        }
+    }
+
+    /// PutBit
+    fn put_bit(&mut self, bit: u32) {
+        if self.first_bit {
+            self.first_bit = false;
+        } else {
+            self.out.bits(bit, 1);
+        }
+        while self.bits_outstanding > 0 {
+            self.out.bits(1 - bit, 1);
+            self.bits_outstanding -= 1;
+        }
+    }
+}
@@ -362,7 +362,7 @@ pub(crate) fn get_dct32_coef(row: usize, col: usize) -> i16 {
 /// Dequantization parameters
 #[derive(Debug, Clone, Copy)]
 pub struct DequantParams {
-    /// QP value
+    /// QP value including QpBdOffset (Qp'Y, Qp'Cb or Qp'Cr)
     pub qp: i32,
     /// Bit depth
     pub bit_depth: u8,
@@ -371,14 +371,26 @@ pub struct DequantParams {
 }
 
 pub fn dequantize(coeffs: &mut [i16], params: DequantParams) {
+    // The SIMD paths multiply in 32 bits, which is enough up to Qp' 59;
+    // only high bit depths go beyond
+    #[cfg(all(feature = "unsafe-simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let simd = coeffs.len() >= 16 && params.qp < 60;
     #[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
     {
-        if is_x86_feature_detected!("avx2") && coeffs.len() >= 16 {
+        if is_x86_feature_detected!("avx2") && simd {
             unsafe {
                 return dequantize_avx2(coeffs, params);
             }
         }
     }
+    #[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") && simd {
+            unsafe {
+                return dequantize_neon(coeffs, params);
+            }
+        }
+    }
     dequantize_scalar(coeffs, params);
 }
 
@@ -390,17 +402,18 @@ fn dequantize_scalar(coeffs: &mut [i16], params: DequantParams) {
     let scale = LEVEL_SCALE[qp_rem as usize];
 
     let shift = params.bit_depth as i32 - 9 + params.log2_tr_size as i32;
-    let add = if shift > 0 { 1 << (shift - 1) } else { 0 };
+    let add = if shift > 0 { 1i64 << (shift - 1) } else { 0 };
+    let multiplier = i64::from(scale) << qp_per;
 
     if shift >= 0 {
         for coef in coeffs.iter_mut() {
-            let value = (*coef as i32 * scale * (1 << qp_per) + add) >> shift;
+            let value = (*coef as i64 * multiplier + add) >> shift;
             *coef = value.clamp(-32768, 32767) as i16;
         }
     } else {
         let neg_shift = -shift;
         for coef in coeffs.iter_mut() {
-            let value = (*coef as i32 * scale * (1 << qp_per)) << neg_shift;
+            let value = (*coef as i64 * multiplier) << neg_shift;
             *coef = value.clamp(-32768, 32767) as i16;
         }
     }
@@ -409,7 +422,7 @@ fn dequantize_scalar(coeffs: &mut [i16], params: DequantParams) {
 #[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
 #[target_feature(enable = "avx2")]
 unsafe fn dequantize_avx2(coeffs: &mut [i16], params: DequantParams) {
-    use std::arch::x86_64::*;
+    use core::arch::x86_64::*;
 
     static LEVEL_SCALE: [i32; 6] = [40, 45, 51, 57, 64, 72];
 
@@ -470,6 +483,58 @@ unsafe fn dequantize_avx2(coeffs: &mut [i16], params: DequantParams) {
     }
 }
 
+/// Dequantize with NEON, 8 coefficients at a time
+///
+/// A shift by a negative amount is a left shift, so one loop covers both
+/// signs of `shift`, and the saturating narrow does the clamp.
+#[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+#[target_feature(enable = "neon")]
+unsafe fn dequantize_neon(coeffs: &mut [i16], params: DequantParams) {
+    use core::arch::aarch64::*;
+
+    static LEVEL_SCALE: [i32; 6] = [40, 45, 51, 57, 64, 72];
+
+    let qp_per = params.qp / 6;
+    let qp_rem = params.qp % 6;
+    let scale = LEVEL_SCALE[qp_rem as usize];
+    let multiplier = scale * (1 << qp_per);
+
+    let shift = params.bit_depth as i32 - 9 + params.log2_tr_size as i32;
+    let add = if shift > 0 { 1 << (shift - 1) } else { 0 };
+    let v_add = vdupq_n_s32(add);
+    let v_shift = vdupq_n_s32(-shift);
+
+    let mut chunks = coeffs.chunks_exact_mut(8);
+    for chunk in &mut chunks {
+        // SAFETY: the chunk holds eight coefficients
+        let v_coef = unsafe { vld1q_s16(chunk.as_ptr()) };
+        let lo = vmlaq_n_s32(v_add, vmovl_s16(vget_low_s16(v_coef)), multiplier);
+        let hi = vmlaq_n_s32(v_add, vmovl_s16(vget_high_s16(v_coef)), multiplier);
+        let lo = vqmovn_s32(vshlq_s32(lo, v_shift));
+        let hi = vqmovn_s32(vshlq_s32(hi, v_shift));
+        // SAFETY: the chunk has room for eight coefficients
+        unsafe { vst1q_s16(chunk.as_mut_ptr(), vcombine_s16(lo, hi)) };
+    }
+
+    for coef in chunks.into_remainder() {
+        let value = if shift >= 0 {
+            (*coef as i32 * multiplier + add) >> shift
+        } else {
+            (*coef as i32 * multiplier) << -shift
+        };
+        *coef = value.clamp(-32768, 32767) as i16;
+    }
+}
+
+/// Inverse DCT of a block whose only nonzero coefficient is DC
+///
+/// Every residual sample of such a block has this value, for any block size.
+pub fn idct_dc(dc: i16, bit_depth: u8) -> i16 {
+    let shift2 = 20 - bit_depth;
+    let tmp = (64 * dc as i32 + 64) >> 7;
+    ((64 * tmp + (1 << (shift2 - 1))) >> shift2) as i16
+}
+
 /// Generic inverse transform dispatch
 pub fn inverse_transform(
     coeffs: &[i16],
@@ -521,6 +586,8 @@ pub fn inverse_transform(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec::Vec;
+    use std::println;
 
     #[test]
     fn test_idct4_dc_only() {
@@ -547,6 +614,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_idct_dc_matches_full_transform() {
+        for dc in [-1000i16, -64, 1, 64, 777] {
+            let mut coeffs = [0i16; 64];
+            coeffs[0] = dc;
+            let mut output = [0i16; 64];
+            idct8(&coeffs, &mut output, 8);
+            assert!(output.iter().all(|&v| v == idct_dc(dc, 8)), "dc = {dc}");
+        }
+    }
+
     #[test]
     fn test_idst4_dc_only() {
         let mut coeffs = [0i16; 16];
@@ -598,4 +676,25 @@ mod tests {
             "Expected residuals: [-18, -23, -4, 23, -41, -24, 11, 22, -28, -22, 3, 18, -33, -34, 3, 44]"
         );
     }
+
+    #[test]
+    fn test_dequantize_matches_scalar() {
+        let mut state = 5u32;
+        let coeffs: Vec<i16> = (0..1024)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 8) as i16
+            })
+            .collect();
+        for (qp, bit_depth, log2_tr_size, len) in
+            [(0, 8, 2, 16), (22, 8, 3, 64), (37, 10, 4, 20), (51, 8, 5, 1024), (59, 10, 5, 1000), (75, 12, 5, 1000)]
+        {
+            let params = DequantParams { qp, bit_depth, log2_tr_size };
+            let mut expected = coeffs[..len].to_vec();
+            dequantize_scalar(&mut expected, params);
+            let mut actual = coeffs[..len].to_vec();
+            dequantize(&mut actual, params);
+            assert_eq!(actual, expected, "qp {qp} {bit_depth}-bit size {log2_tr_size}");
+        }
+    }
 }
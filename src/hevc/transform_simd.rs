@@ -1,7 +1,8 @@
 //! SIMD-optimized transform implementations
 //!
 //! This module provides high-performance SIMD versions of inverse DCT/DST transforms
-//! using AVX2 on x86_64. Falls back to scalar implementations on other platforms.
+//! using AVX2 on x86_64 and NEON on aarch64. Falls back to scalar implementations on
+//! other platforms.
 //!
 //! # Compatibility
 //!
@@ -10,15 +11,18 @@
 //! - AMD: Excavator (2015) and newer (Ryzen all generations)
 //!
 //! The code uses:
-//! - Runtime CPU feature detection (`is_x86_feature_detected!`)
+//! - Runtime CPU feature detection (`is_x86_feature_detected!`,
+//!   `is_aarch64_feature_detected!`)
 //! - Standard x86_64 intrinsics (vendor-neutral)
 //! - Conservative SIMD patterns that work well on both microarchitectures
 //! - Automatic fallback to scalar code on older CPUs
 //!
 //! Enabled only when the `unsafe-simd` feature is active.
 
+#[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+use core::arch::aarch64::*;
 #[cfg(all(feature = "unsafe-simd", target_arch = "x86_64"))]
-use std::arch::x86_64::*;
+use core::arch::x86_64::*;
 
 use super::transform::{get_dct32_coef, DCT16_MATRIX};
 
@@ -200,6 +204,16 @@ pub fn idct32_optimized(coeffs: &[i16; 1024], output: &mut [i16; 1024], bit_dept
         }
     }
 
+    #[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            unsafe {
+                idct32_neon(coeffs, output, bit_depth);
+            }
+            return;
+        }
+    }
+
     // Fallback to scalar implementation
     super::transform::idct32(coeffs, output, bit_depth);
 }
@@ -335,6 +349,16 @@ pub fn idct16_optimized(coeffs: &[i16; 256], output: &mut [i16; 256], bit_depth:
         }
     }
 
+    #[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            unsafe {
+                idct16_neon(coeffs, output, bit_depth);
+            }
+            return;
+        }
+    }
+
     // Fallback to scalar implementation
     super::transform::idct16(coeffs, output, bit_depth);
 }
@@ -475,6 +499,16 @@ pub fn idct8_optimized(coeffs: &[i16; 64], output: &mut [i16; 64], bit_depth: u8
         }
     }
 
+    #[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            unsafe {
+                idct8_neon(coeffs, output, bit_depth);
+            }
+            return;
+        }
+    }
+
     // Fallback to scalar implementation
     super::transform::idct8(coeffs, output, bit_depth);
 }
@@ -557,6 +591,16 @@ pub fn idct4_optimized(coeffs: &[i16; 16], output: &mut [i16; 16], bit_depth: u8
         return;
     }
 
+    #[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            unsafe {
+                idct4_neon(coeffs, output, bit_depth);
+            }
+            return;
+        }
+    }
+
     // Fallback to scalar implementation
     #[allow(unreachable_code)]
     super::transform::idct4(coeffs, output, bit_depth);
@@ -640,14 +684,160 @@ pub fn idst4_optimized(coeffs: &[i16; 16], output: &mut [i16; 16], bit_depth: u8
         return;
     }
 
+    #[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            unsafe {
+                idst4_neon(coeffs, output, bit_depth);
+            }
+            return;
+        }
+    }
+
     // Fallback to scalar implementation
     #[allow(unreachable_code)]
     super::transform::idst4(coeffs, output, bit_depth);
 }
 
+/// NEON inverse 32x32 DCT
+///
+/// Bit-exact with [`idct32`](super::transform::idct32): the second pass
+/// accumulates in 64 bits like the scalar version.
+#[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+#[target_feature(enable = "neon")]
+pub unsafe fn idct32_neon(coeffs: &[i16; 1024], output: &mut [i16; 1024], bit_depth: u8) {
+    let matrix: [[i16; 32]; 32] =
+        core::array::from_fn(|k| core::array::from_fn(|j| get_dct32_coef(k, j)));
+    let shift2 = 20 - bit_depth as i64;
+    let add2 = vdupq_n_s64(1 << (shift2 - 1));
+
+    let mut tmp = [[0i32; 32]; 32];
+    // SAFETY: We're inside an unsafe function with target_feature(neon) enabled
+    unsafe {
+        first_pass_neon(coeffs, &matrix, &mut tmp);
+    }
+
+    // Second pass (horizontal) - 4 outputs of a row at a time
+    for (row, out) in tmp.iter().zip(output.chunks_exact_mut(32)) {
+        for col in (0..32).step_by(4) {
+            let mut lo = vdupq_n_s64(0);
+            let mut hi = vdupq_n_s64(0);
+            for (basis, &value) in matrix.iter().zip(row) {
+                // SAFETY: the slice holds four coefficients
+                let basis = vmovl_s16(unsafe { vld1_s16(basis[col..col + 4].as_ptr()) });
+                lo = vmlal_n_s32(lo, vget_low_s32(basis), value);
+                hi = vmlal_n_s32(hi, vget_high_s32(basis), value);
+            }
+            let shift = vdupq_n_s64(-shift2);
+            let lo = vmovn_s64(vshlq_s64(vaddq_s64(lo, add2), shift));
+            let hi = vmovn_s64(vshlq_s64(vaddq_s64(hi, add2), shift));
+            // SAFETY: the slice has room for four samples
+            unsafe { vst1_s16(out[col..col + 4].as_mut_ptr(), vmovn_s32(vcombine_s32(lo, hi))) };
+        }
+    }
+}
+
+/// NEON inverse 16x16 DCT
+#[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+#[target_feature(enable = "neon")]
+pub unsafe fn idct16_neon(coeffs: &[i16; 256], output: &mut [i16; 256], bit_depth: u8) {
+    // SAFETY: We're inside an unsafe function with target_feature(neon) enabled
+    unsafe { inverse_transform_neon(coeffs, output, &DCT16_MATRIX, bit_depth) }
+}
+
+/// NEON inverse 8x8 DCT
+#[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+#[target_feature(enable = "neon")]
+pub unsafe fn idct8_neon(coeffs: &[i16; 64], output: &mut [i16; 64], bit_depth: u8) {
+    // SAFETY: We're inside an unsafe function with target_feature(neon) enabled
+    unsafe { inverse_transform_neon(coeffs, output, &super::transform::DCT8_MATRIX, bit_depth) }
+}
+
+/// NEON inverse 4x4 DCT
+#[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+#[target_feature(enable = "neon")]
+pub unsafe fn idct4_neon(coeffs: &[i16; 16], output: &mut [i16; 16], bit_depth: u8) {
+    // SAFETY: We're inside an unsafe function with target_feature(neon) enabled
+    unsafe { inverse_transform_neon(coeffs, output, &super::transform::DCT4_MATRIX, bit_depth) }
+}
+
+/// NEON inverse 4x4 DST
+#[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+#[target_feature(enable = "neon")]
+pub unsafe fn idst4_neon(coeffs: &[i16; 16], output: &mut [i16; 16], bit_depth: u8) {
+    // SAFETY: We're inside an unsafe function with target_feature(neon) enabled
+    unsafe { inverse_transform_neon(coeffs, output, &super::transform::DST4_MATRIX, bit_depth) }
+}
+
+/// NEON inverse NxN transform with basis `matrix[k][j]`, for N up to 16
+/// where the second pass fits in 32 bits
+///
+/// Neither pass needs gathers or horizontal sums: the first adds up
+/// coefficient rows weighted by one basis value, the second basis rows
+/// weighted by one intermediate value.
+#[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+#[target_feature(enable = "neon")]
+unsafe fn inverse_transform_neon<const N: usize>(
+    coeffs: &[i16],
+    output: &mut [i16],
+    matrix: &[[i16; N]; N],
+    bit_depth: u8,
+) {
+    let shift2 = 20 - bit_depth as i32;
+    let add2 = vdupq_n_s32(1 << (shift2 - 1));
+
+    let mut tmp = [[0i32; N]; N];
+    // SAFETY: We're inside an unsafe function with target_feature(neon) enabled
+    unsafe {
+        first_pass_neon(coeffs, matrix, &mut tmp);
+    }
+
+    // Second pass (horizontal) - 4 outputs of a row at a time
+    for (row, out) in tmp.iter().zip(output.chunks_exact_mut(N)) {
+        for col in (0..N).step_by(4) {
+            let mut acc = vdupq_n_s32(0);
+            for (basis, &value) in matrix.iter().zip(row) {
+                // SAFETY: the slice holds four coefficients
+                let basis = vmovl_s16(unsafe { vld1_s16(basis[col..col + 4].as_ptr()) });
+                acc = vmlaq_n_s32(acc, basis, value);
+            }
+            let result = vshlq_s32(vaddq_s32(acc, add2), vdupq_n_s32(-shift2));
+            // SAFETY: the slice has room for four samples
+            unsafe { vst1_s16(out[col..col + 4].as_mut_ptr(), vmovn_s32(result)) };
+        }
+    }
+}
+
+/// First (vertical) pass of an NxN inverse transform: row `j` of `tmp` is
+/// the sum of coefficient rows `k` weighted by `matrix[k][j]`, shifted by 7
+#[cfg(all(feature = "unsafe-simd", target_arch = "aarch64"))]
+#[target_feature(enable = "neon")]
+unsafe fn first_pass_neon<const N: usize>(
+    coeffs: &[i16],
+    matrix: &[[i16; N]; N],
+    tmp: &mut [[i32; N]; N],
+) {
+    let add1 = vdupq_n_s32(1 << 6);
+
+    for (j, out) in tmp.iter_mut().enumerate() {
+        for col in (0..N).step_by(4) {
+            let mut acc = vdupq_n_s32(0);
+            for (k, basis) in matrix.iter().enumerate() {
+                let src = &coeffs[k * N + col..k * N + col + 4];
+                // SAFETY: the slice holds four coefficients
+                acc = vmlal_n_s16(acc, unsafe { vld1_s16(src.as_ptr()) }, basis[j]);
+            }
+            let result = vshrq_n_s32::<7>(vaddq_s32(acc, add1));
+            // SAFETY: the slice has room for four values
+            unsafe { vst1q_s32(out[col..col + 4].as_mut_ptr(), result) };
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::println;
 
     #[test]
     fn test_idct32_simd_matches_scalar() {
@@ -903,4 +1093,42 @@ mod tests {
 
         println!("IDST4 DC-only output: {:?}", &output);
     }
+
+    /// Pseudo-random coefficients in -limit..limit
+    fn random_coeffs<const N: usize>(seed: u32, limit: i32) -> [i16; N] {
+        let mut state = seed;
+        core::array::from_fn(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((state >> 8) as i32 % (2 * limit) - limit) as i16
+        })
+    }
+
+    /// Compare a dispatched transform with its scalar version
+    fn assert_matches<const N: usize>(
+        name: &str,
+        scalar: fn(&[i16; N], &mut [i16; N], u8),
+        optimized: fn(&[i16; N], &mut [i16; N], u8),
+    ) {
+        for (seed, limit) in [(1, 64), (2, 1024), (3, 32768)] {
+            let coeffs = random_coeffs::<N>(seed, limit);
+            for bit_depth in [8, 10, 12] {
+                let mut expected = [0i16; N];
+                let mut actual = [0i16; N];
+                scalar(&coeffs, &mut expected, bit_depth);
+                optimized(&coeffs, &mut actual, bit_depth);
+                assert_eq!(actual, expected, "{name} limit {limit} {bit_depth}-bit");
+            }
+        }
+    }
+
+    #[test]
+    fn test_transforms_match_scalar_on_random_blocks() {
+        use super::super::transform::{idct16, idct32, idct4, idct8, idst4};
+
+        assert_matches("idst4", idst4, idst4_optimized);
+        assert_matches("idct4", idct4, idct4_optimized);
+        assert_matches("idct8", idct8, idct8_optimized);
+        assert_matches("idct16", idct16, idct16_optimized);
+        assert_matches("idct32", idct32, idct32_optimized);
+    }
 }
@@ -22,13 +22,33 @@
 #![allow(dead_code)]
 
 extern crate alloc;
+// The test harness links std; no_std test builds still need its macros
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
 
 mod error;
 pub mod heif;
 pub mod hevc;
+mod limits;
+#[cfg(not(feature = "std"))]
+mod math;
+mod options;
 
-pub use error::{HeicError, Result};
+pub use error::{
+    BitstreamLocation, ContainerLocation, DamagedRegion, ErrorKind, HeicError, HevcError, Limit,
+    LimitExceeded, Result,
+};
+pub use limits::DecoderLimits;
+pub use options::{
+    ChromaUpsampler, ColorTarget, DecoderOptions, OutputFormat, Strictness, ToneMapping,
+};
+pub use heif::{
+    AuxiliaryData, AuxiliaryFormat, AuxiliaryImage, AuxiliaryKind, EntityGroup, Eye, GainMapInfo,
+    HdrData, HdrFormat, HdrImage, ImageHandle, SequenceFrame, SequenceFrames, Track,
+};
 
+use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 
 /// Decoded image data
@@ -44,6 +64,15 @@ pub struct DecodedImage {
     pub has_alpha: bool,
 }
 
+/// Image decoded leniently, with the regions that had to be concealed
+#[derive(Debug, Clone)]
+pub struct LenientImage {
+    /// The decoded image; damaged regions hold substituted content
+    pub image: DecodedImage,
+    /// Regions of the image that could not be decoded, empty if none
+    pub damage: Vec<DamagedRegion>,
+}
+
 /// Image metadata without full decode
 #[derive(Debug, Clone, Copy)]
 pub struct ImageInfo {
@@ -55,17 +84,154 @@ pub struct ImageInfo {
     pub has_alpha: bool,
 }
 
+/// Downscaling factor of a reduced-resolution decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scale {
+    /// Full resolution
+    #[default]
+    Full,
+    /// 1/2 of the width and height
+    Half,
+    /// 1/4 of the width and height
+    Quarter,
+    /// 1/8 of the width and height
+    Eighth,
+}
+
+impl Scale {
+    /// Number of source pixels per output pixel in each dimension
+    #[must_use]
+    pub fn factor(self) -> u32 {
+        match self {
+            Self::Full => 1,
+            Self::Half => 2,
+            Self::Quarter => 4,
+            Self::Eighth => 8,
+        }
+    }
+}
+
+/// Left and right views of a stereo image
+#[derive(Debug, Clone)]
+pub struct StereoPair {
+    /// Left view
+    pub left: DecodedImage,
+    /// Right view
+    pub right: DecodedImage,
+}
+
+/// Receiver of decoded RGB rows, see [`HeicDecoder::decode_rows`]
+pub trait RowSink {
+    /// Called once with the image size before any rows
+    ///
+    /// # Errors
+    ///
+    /// An error aborts decoding and is returned to the caller.
+    fn begin(&mut self, width: u32, height: u32) -> Result<()> {
+        let _ = (width, height);
+        Ok(())
+    }
+
+    /// Receive consecutive rows starting at image row `y`
+    ///
+    /// `rgb` holds one or more complete rows of `width * 3` bytes.
+    ///
+    /// # Errors
+    ///
+    /// An error aborts decoding and is returned to the caller.
+    fn rows(&mut self, y: u32, rgb: &[u8]) -> Result<()>;
+}
+
+/// Adapter converting decoded bands to RGB rows
+struct RgbRows<'a, S: RowSink + ?Sized>(&'a mut S, &'a DecoderOptions);
+
+impl<S: RowSink + ?Sized> heif::stream::BandSink for RgbRows<'_, S> {
+    fn begin(&mut self, width: u32, height: u32) -> Result<()> {
+        self.0.begin(width, height)
+    }
+
+    fn band(&mut self, y: u32, frame: &hevc::DecodedFrame) -> Result<()> {
+        self.0.rows(y, &frame.to_rgb_with(self.1))
+    }
+}
+
 /// HEIC image decoder
+///
+/// The decoder keeps the buffers of finished decodes, one set per thread
+/// that decoded at the same time, and reuses them for later tiles and
+/// images. Keep one decoder around when decoding many images.
 #[derive(Debug, Default)]
 pub struct HeicDecoder {
-    _private: (),
+    options: DecoderOptions,
+    pool: Arc<hevc::ContextPool>,
 }
 
 impl HeicDecoder {
     /// Create a new HEIC decoder
     #[must_use]
     pub fn new() -> Self {
-        Self { _private: () }
+        Self {
+            options: DecoderOptions::default(),
+            pool: Arc::default(),
+        }
+    }
+
+    /// Create a HEIC decoder enforcing the given resource limits
+    ///
+    /// Use this when decoding untrusted input with tighter limits than the
+    /// defaults, e.g. to bound memory per request in a server.
+    #[must_use]
+    pub fn with_limits(limits: DecoderLimits) -> Self {
+        Self::with_options(DecoderOptions::new().limits(limits))
+    }
+
+    /// Create a HEIC decoder with the given options
+    #[must_use]
+    pub fn with_options(options: DecoderOptions) -> Self {
+        Self {
+            options,
+            pool: Arc::default(),
+        }
+    }
+
+    /// The options this decoder decodes with
+    #[must_use]
+    pub fn options(&self) -> &DecoderOptions {
+        &self.options
+    }
+
+    /// Free the buffers kept for reuse by later decodes
+    ///
+    /// They are allocated again as needed, so this only bounds memory
+    /// between bursts of decoding.
+    pub fn release_buffers(&self) {
+        self.pool.clear();
+    }
+
+    /// Parse a HEIF container that decodes with this decoder's options
+    fn parse<'a>(&self, data: &'a [u8]) -> Result<heif::HeifContainer<'a>> {
+        let mut container = heif::parse_with_limits(data, self.options.limits)?;
+        self.configure(&mut container);
+        Ok(container)
+    }
+
+    /// Decode a container's images with this decoder's options and buffers
+    fn configure(&self, container: &mut heif::HeifContainer<'_>) {
+        container.set_options(self.options);
+        container.set_pool(self.pool.clone());
+    }
+
+    /// Convert a decoded frame to the output format, keeping its buffers
+    fn to_image(&self, frame: hevc::DecodedFrame) -> DecodedImage {
+        self.to_image_scaled(frame, 1)
+    }
+
+    /// Convert a decoded frame to the output format, box-filtered down by
+    /// `factor`, keeping its buffers
+    fn to_image_scaled(&self, frame: hevc::DecodedFrame, factor: u32) -> DecodedImage {
+        let image = frame_to_image_scaled(&frame, factor, &self.options);
+        self.pool.recycle(frame);
+        image
     }
 
     /// Decode HEIC data to raw pixels
@@ -76,68 +242,312 @@ impl HeicDecoder {
     /// or if decoding fails.
     pub fn decode(&self, data: &[u8]) -> Result<DecodedImage> {
         // Parse HEIF container
-        let container = heif::parse(data)?;
+        let container = self.parse(data)?;
+
+        let frame = decode_primary(&container, hevc::DecodeMode::Full)?;
 
-        // Find primary image item
+        Ok(self.to_image(frame))
+    }
+
+    /// Decode HEIC data, concealing damaged tiles and CTUs instead of failing
+    ///
+    /// Grid tiles that fail to decode are filled in from the tiles above or
+    /// to their left. Within a coded image, a CTU that fails to decode ends
+    /// its slice or WPP substream, and the CTBs lost are filled in the same
+    /// way. Each filled-in region is reported with the error that caused it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the container is malformed, a limit is exceeded
+    /// or nothing of the image could be decoded.
+    pub fn decode_lenient(&self, data: &[u8]) -> Result<LenientImage> {
+        let mut container = self.parse(data)?;
+        container.set_options(self.options.strictness(Strictness::Lenient));
+        let frame = decode_primary(&container, hevc::DecodeMode::Full)?;
+        let damage = frame.cropped_damage();
+        Ok(LenientImage {
+            image: self.to_image(frame),
+            damage,
+        })
+    }
+
+    /// Decode HEIC data to a downscaled preview
+    ///
+    /// The image is reconstructed at full quality and box-filtered while
+    /// converting to RGB, so no full-size RGB buffer is allocated. The output
+    /// size is the image size divided by the scale factor, rounded up.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not valid HEIC/HEIF format
+    /// or if decoding fails.
+    pub fn decode_scaled(&self, data: &[u8], scale: Scale) -> Result<DecodedImage> {
+        let container = self.parse(data)?;
+        let frame = decode_primary(&container, hevc::DecodeMode::Full)?;
+        Ok(self.to_image_scaled(frame, scale.factor()))
+    }
+
+    /// Decode HEIC data to a fast, approximate downscaled preview
+    ///
+    /// Like [`decode_scaled`](Self::decode_scaled), but coded images are
+    /// reconstructed in [`DecodeMode::Draft`](hevc::DecodeMode::Draft): DC
+    /// intra prediction, DC-only residuals and no in-loop filters. Blocking
+    /// artifacts are mostly hidden at 1/4 and 1/8 scale.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not valid HEIC/HEIF format
+    /// or if decoding fails.
+    pub fn decode_draft(&self, data: &[u8], scale: Scale) -> Result<DecodedImage> {
+        let container = self.parse(data)?;
+        let frame = decode_primary(&container, hevc::DecodeMode::Draft)?;
+        Ok(self.to_image_scaled(frame, scale.factor()))
+    }
+
+    /// Decode HEIC data to RGB, passing rows to `sink` as they are ready
+    ///
+    /// Grid images are decoded one row of tiles at a time, and each band
+    /// of rows is converted and handed to the sink before the next is
+    /// decoded, so peak memory is proportional to one row of tiles. Rotated
+    /// or mirrored images and non-grid images are decoded in full first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not valid HEIC/HEIF format, if
+    /// decoding fails or if the sink returns an error.
+    pub fn decode_rows<S: RowSink + ?Sized>(&self, data: &[u8], sink: &mut S) -> Result<()> {
+        let container = self.parse(data)?;
         let primary_item = container.primary_item().ok_or(HeicError::NoPrimaryImage)?;
+        heif::stream::decode_bands(&container, primary_item.id, &mut RgbRows(sink, &self.options))
+    }
 
-        let frame = if primary_item.item_type == heif::ItemType::Grid {
-            // Grid image: decode all tiles and stitch
-            let grid_bytes = container.get_item_data(primary_item.id)
-                .map(|d| d.to_vec())
-                .or_else(|| container.get_item_data_owned(primary_item.id))
-                .ok_or(HeicError::InvalidData("Missing grid item data"))?;
-            let grid_config = heif::parse_grid_config(&grid_bytes)?;
-            heif::grid::decode_grid(&container, primary_item.id, &grid_config)?
-        } else {
-            // Single image
-            let image_data = container
-                .get_item_data(primary_item.id)
-                .ok_or(HeicError::InvalidData("Missing image data"))?;
-
-            if let Some(ref config) = primary_item.hevc_config {
-                hevc::decode_with_config(config, image_data)?
-            } else {
-                hevc::decode(image_data)?
-            }
-        };
+    /// List every non-hidden image item in the file
+    ///
+    /// This includes the primary image, thumbnails, auxiliary images and
+    /// any other top-level images such as burst shots or document pages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not valid HEIC/HEIF format.
+    pub fn image_handles(&self, data: &[u8]) -> Result<Vec<ImageHandle>> {
+        let container = self.parse(data)?;
+        Ok(container.image_handles())
+    }
 
-        Ok(DecodedImage {
-            data: frame.to_rgb(),
-            width: frame.cropped_width(),
-            height: frame.cropped_height(),
-            has_alpha: false,
+    /// List the auxiliary images (alpha, depth, mattes, gain maps) of the primary image
+    ///
+    /// Auxiliary items are usually hidden, so they are not part of
+    /// [`image_handles`](Self::image_handles).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not valid HEIC/HEIF format.
+    pub fn auxiliary_images(&self, data: &[u8]) -> Result<Vec<ImageHandle>> {
+        let container = self.parse(data)?;
+        Ok(container
+            .get_auxiliary_item_ids(container.primary_item_id)
+            .into_iter()
+            .filter_map(|id| container.image_handle(id))
+            .collect())
+    }
+
+    /// List the tracks of an image sequence file (msf1)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not valid HEIC/HEIF format.
+    pub fn tracks(&self, data: &[u8]) -> Result<Vec<Track>> {
+        Ok(self.parse(data)?.tracks)
+    }
+
+    /// Iterate over the frames of the file's image sequence
+    ///
+    /// Uses the first HEVC image sequence track, preferring 'pict' tracks
+    /// over 'vide' tracks. Each item yields the decoded frame together with
+    /// its presentation timestamp and duration, in presentation order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not valid HEIC/HEIF format or
+    /// contains no decodable image sequence track.
+    pub fn frames<'a>(&self, data: &'a [u8]) -> Result<SequenceFrames<'a>> {
+        let container = self.parse(data)?;
+        let track_id = container
+            .image_sequence_track()
+            .ok_or(HeicError::Unsupported("No HEVC image sequence track"))?
+            .track_id;
+        SequenceFrames::new(container, track_id)
+    }
+
+    /// Iterate over the frames of a specific track
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or is not an HEVC
+    /// image sequence.
+    pub fn track_frames<'a>(&self, data: &'a [u8], track_id: u32) -> Result<SequenceFrames<'a>> {
+        SequenceFrames::new(self.parse(data)?, track_id)
+    }
+
+    /// List the entity groups (grpl) in the file
+    ///
+    /// Groups relate items such as alternatives ('altr'), stereo pairs
+    /// ('ster'), bursts ('brst') and panoramas ('pano').
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not valid HEIC/HEIF format.
+    pub fn entity_groups(&self, data: &[u8]) -> Result<Vec<EntityGroup>> {
+        Ok(self.parse(data)?.entity_groups)
+    }
+
+    /// Decode an auxiliary image as a single channel aligned to its master image
+    ///
+    /// The result has the master image's output dimensions, so it can be
+    /// used directly as a per-pixel depth map or matte for the decoded image.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the item is not an auxiliary image or if
+    /// decoding fails.
+    pub fn decode_auxiliary(
+        &self,
+        data: &[u8],
+        item_id: u32,
+        format: AuxiliaryFormat,
+    ) -> Result<AuxiliaryImage> {
+        let container = self.parse(data)?;
+        heif::auxiliary::decode_auxiliary(&container, item_id, format)
+    }
+
+    /// Get the HDR gain map attached to the primary image, if any
+    ///
+    /// Recognizes ISO 21496-1 `tmap` items and Apple HDR gain map
+    /// auxiliary images.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the container or the gain map metadata is malformed.
+    pub fn gain_map_info(&self, data: &[u8]) -> Result<Option<GainMapInfo>> {
+        let container = self.parse(data)?;
+        heif::gainmap::find_gain_map(&container)
+    }
+
+    /// Decode the primary image as linear-light HDR for a display headroom
+    ///
+    /// `display_headroom` is the ratio of the display's peak brightness to
+    /// SDR white; 1.0 yields the SDR rendition in linear light. Images
+    /// without a gain map are returned as their base image.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if decoding fails or the gain map metadata is
+    /// missing or malformed.
+    pub fn decode_hdr(
+        &self,
+        data: &[u8],
+        display_headroom: f32,
+        format: HdrFormat,
+    ) -> Result<HdrImage> {
+        let container = self.parse(data)?;
+        heif::gainmap::decode_hdr(&container, display_headroom, format)
+    }
+
+    /// Decode a rectangle of the primary image
+    ///
+    /// The rectangle is given in displayed image coordinates, after any
+    /// rotation, mirroring and clean aperture crop. For grid images only
+    /// the tiles intersecting the rectangle are decoded, so time and memory
+    /// scale with the size of the region.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rectangle is empty or extends outside the
+    /// image, or if decoding fails.
+    pub fn decode_region(
+        &self,
+        data: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<DecodedImage> {
+        let container = self.parse(data)?;
+        let primary_item = container.primary_item().ok_or(HeicError::NoPrimaryImage)?;
+        let frame = heif::region::decode_region(&container, primary_item.id, (x, y, width, height))?;
+        Ok(self.to_image(frame))
+    }
+
+    /// Decode both views of a stereo image
+    ///
+    /// Supports stereo pairs stored as a `ster` entity group of two items
+    /// and multi-layer (MV-HEVC) images such as spatial photos, whose base
+    /// layer is the left view.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the primary image has no second view or if
+    /// decoding fails.
+    pub fn decode_stereo(&self, data: &[u8]) -> Result<StereoPair> {
+        let container = self.parse(data)?;
+        let [left, right] = heif::layered::decode_stereo(&container)?;
+        Ok(StereoPair {
+            left: self.to_image(left),
+            right: self.to_image(right),
         })
     }
 
+    /// Decode one view of a stereo image
+    ///
+    /// See [`decode_stereo`](Self::decode_stereo) for the supported layouts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the requested view does not exist or if
+    /// decoding fails.
+    pub fn decode_eye(&self, data: &[u8], eye: Eye) -> Result<DecodedImage> {
+        let container = self.parse(data)?;
+        let frame = heif::layered::decode_eye(&container, eye)?;
+        Ok(self.to_image(frame))
+    }
+
+    /// Decode a specific image item to raw pixels
+    ///
+    /// Produces the same output as [`decode`](Self::decode), but for the
+    /// item with the given ID instead of the primary item.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the item does not exist, is not an image,
+    /// or if decoding fails.
+    pub fn decode_item(&self, data: &[u8], item_id: u32) -> Result<DecodedImage> {
+        let frame = self.decode_item_to_frame(data, item_id)?;
+        Ok(self.to_image(frame))
+    }
+
+    /// Decode a specific image item to raw YCbCr frame
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the item does not exist, is not an image,
+    /// or if decoding fails.
+    pub fn decode_item_to_frame(&self, data: &[u8], item_id: u32) -> Result<hevc::DecodedFrame> {
+        let container = self.parse(data)?;
+        container
+            .image_handle(item_id)
+            .ok_or(HeicError::InvalidData("Item is not an image"))?;
+
+        heif::decode_image_item(&container, item_id)
+    }
+
     /// Decode HEIC data to raw YCbCr frame (for debugging)
     ///
     /// # Errors
     ///
     /// Returns an error if the data is not valid HEIC/HEIF format.
     pub fn decode_to_frame(&self, data: &[u8]) -> Result<hevc::DecodedFrame> {
-        let container = heif::parse(data)?;
-        let primary_item = container.primary_item().ok_or(HeicError::NoPrimaryImage)?;
-
-        if primary_item.item_type == heif::ItemType::Grid {
-            // Grid image: decode all tiles and stitch
-            let grid_bytes = container.get_item_data(primary_item.id)
-                .map(|d| d.to_vec())
-                .or_else(|| container.get_item_data_owned(primary_item.id))
-                .ok_or(HeicError::InvalidData("Missing grid item data"))?;
-            let grid_config = heif::parse_grid_config(&grid_bytes)?;
-            Ok(heif::grid::decode_grid(&container, primary_item.id, &grid_config)?)
-        } else {
-            let image_data = container
-                .get_item_data(primary_item.id)
-                .ok_or(HeicError::InvalidData("Missing image data"))?;
-
-            if let Some(ref config) = primary_item.hevc_config {
-                Ok(hevc::decode_with_config(config, image_data)?)
-            } else {
-                Ok(hevc::decode(image_data)?)
-            }
-        }
+        let container = self.parse(data)?;
+        decode_primary(&container, hevc::DecodeMode::Full)
     }
 
     /// Get image info without full decoding
@@ -146,32 +556,184 @@ impl HeicDecoder {
     ///
     /// Returns an error if the data is not valid HEIC/HEIF format.
     pub fn get_info(&self, data: &[u8]) -> Result<ImageInfo> {
-        let container = heif::parse(data)?;
+        let container = self.parse(data)?;
+        container_info(&container)
+    }
 
-        let primary_item = container.primary_item().ok_or(HeicError::NoPrimaryImage)?;
+    /// Decode the primary image of a HEIC file read from a seekable source
+    ///
+    /// Only the metadata and the data of the items needed for the primary
+    /// image (and its alternatives) are read from the source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error on I/O failure, if the file is not valid HEIC/HEIF
+    /// format or if decoding fails.
+    #[cfg(feature = "std")]
+    pub fn decode_reader<R: std::io::Read + std::io::Seek>(&self, reader: R) -> Result<DecodedImage> {
+        let mut reader = heif::HeifReader::with_limits(reader, self.options.limits)?;
+        let items = {
+            let container = reader.container()?;
+            let primary_item = container.primary_item().ok_or(HeicError::NoPrimaryImage)?;
+            let mut items = container.alternative_item_ids(primary_item.id);
+            items.insert(0, primary_item.id);
+            items
+        };
+        let mut container = reader.load(&items)?;
+        self.configure(&mut container);
+        let frame = decode_primary(&container, hevc::DecodeMode::Full)?;
+        Ok(self.to_image(frame))
+    }
 
-        // Try to get info from HEVC config first (faster, no mdat access needed)
-        if let Some(ref config) = primary_item.hevc_config
-            && let Ok(info) = hevc::get_info_from_config(config)
-        {
-            return Ok(ImageInfo {
-                width: info.width,
-                height: info.height,
-                has_alpha: false,
-            });
-        }
+    /// Decode a specific image item of a HEIC file read from a seekable source
+    ///
+    /// Only the data of the item and of the items it is derived from is
+    /// read, so decoding a thumbnail does not touch the main image's data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error on I/O failure, if the item does not exist, is not
+    /// an image, or if decoding fails.
+    #[cfg(feature = "std")]
+    pub fn decode_item_reader<R: std::io::Read + std::io::Seek>(
+        &self,
+        reader: R,
+        item_id: u32,
+    ) -> Result<DecodedImage> {
+        let mut reader = heif::HeifReader::with_limits(reader, self.options.limits)?;
+        let mut container = reader.load(&[item_id])?;
+        self.configure(&mut container);
+        container
+            .image_handle(item_id)
+            .ok_or(HeicError::InvalidData("Item is not an image"))?;
+
+        let frame = heif::decode_image_item(&container, item_id)?;
+        Ok(self.to_image(frame))
+    }
+
+    /// Get image info from a seekable source without full decoding
+    ///
+    /// When the primary item has an `hvcC` configuration only the file's
+    /// metadata is read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error on I/O failure or if the file is not valid
+    /// HEIC/HEIF format.
+    #[cfg(feature = "std")]
+    pub fn get_info_reader<R: std::io::Read + std::io::Seek>(&self, reader: R) -> Result<ImageInfo> {
+        let mut reader = heif::HeifReader::with_limits(reader, self.options.limits)?;
+        let primary_id = {
+            let container = reader.container()?;
+            if let Ok(info) = container_info(&container) {
+                return Ok(info);
+            }
+            container.primary_item().ok_or(HeicError::NoPrimaryImage)?.id
+        };
 
-        // Fallback to reading image data
-        let image_data = container
-            .get_item_data(primary_item.id)
-            .ok_or(HeicError::InvalidData("Missing image data"))?;
+        // No usable hvcC: read the primary item's parameter sets
+        let container = reader.load(&[primary_id])?;
+        container_info(&container)
+    }
+}
 
-        let info = hevc::get_info(image_data)?;
+/// Image info of a container's primary item
+///
+/// The item's `hvcC` is used when present, so the coded data is only
+/// needed as a fallback.
+fn container_info(container: &heif::HeifContainer<'_>) -> Result<ImageInfo> {
+    let primary_item = container.primary_item().ok_or(HeicError::NoPrimaryImage)?;
 
-        Ok(ImageInfo {
+    // Try to get info from HEVC config first (faster, no mdat access needed)
+    if let Some(ref config) = primary_item.hevc_config
+        && let Ok(info) = hevc::get_info_from_config(config)
+    {
+        return Ok(ImageInfo {
             width: info.width,
             height: info.height,
             has_alpha: false,
-        })
+        });
+    }
+
+    // Fallback to reading image data
+    let image_data = container
+        .get_item_data(primary_item.id)
+        .ok_or(HeicError::InvalidData("Missing image data"))?;
+
+    let info = hevc::get_info(image_data)?;
+
+    Ok(ImageInfo {
+        width: info.width,
+        height: info.height,
+        has_alpha: false,
+    })
+}
+
+/// Decode the primary image, falling back to its 'altr' alternatives
+///
+/// A reader that cannot decode the primary item should use the first
+/// decodable member of an alternatives group containing it. Only
+/// unsupported codecs and features move on to the next alternative; other
+/// errors, such as exceeded limits, are returned as they are. The primary's
+/// own error is returned if every alternative is unsupported too.
+fn decode_primary(
+    container: &heif::HeifContainer<'_>,
+    mode: hevc::DecodeMode,
+) -> Result<hevc::DecodedFrame> {
+    let primary_item = container.primary_item().ok_or(HeicError::NoPrimaryImage)?;
+
+    // Resolve grid/overlay/identity derivations down to the coded images
+    let err = match heif::decode_image_item_with_mode(container, primary_item.id, mode) {
+        Err(err) if err.kind() == ErrorKind::Unsupported => err,
+        result => return result,
+    };
+
+    for id in container.alternative_item_ids(primary_item.id) {
+        if container.image_handle(id).is_none() {
+            continue;
+        }
+        match heif::decode_image_item_with_mode(container, id, mode) {
+            Err(alt_err) if alt_err.kind() == ErrorKind::Unsupported => {}
+            result => return result,
+        }
+    }
+    Err(err)
+}
+
+/// Convert a decoded frame to the output format of `options`, box-filtered
+/// down by `factor`
+fn frame_to_image_scaled(
+    frame: &hevc::DecodedFrame,
+    factor: u32,
+    options: &DecoderOptions,
+) -> DecodedImage {
+    let (rgb, alpha) = if factor > 1 {
+        (frame.to_rgb_scaled_with(factor, options), frame.alpha_to_u8_scaled(factor))
+    } else {
+        (frame.to_rgb_with(options), frame.alpha_to_u8())
+    };
+    let alpha = match options.output_format {
+        OutputFormat::Auto => alpha,
+        OutputFormat::Rgb8 => None,
+        OutputFormat::Rgba8 => Some(alpha.unwrap_or_else(|| vec![255; rgb.len() / 3])),
+    };
+    let (data, has_alpha) = match alpha {
+        Some(alpha) => {
+            let mut rgba = Vec::with_capacity(alpha.len() * 4);
+            for (px, &a) in rgb.chunks_exact(3).zip(&alpha) {
+                rgba.extend_from_slice(px);
+                rgba.push(a);
+            }
+            (rgba, true)
+        }
+        None => (rgb, false),
+    };
+
+    let factor = factor.max(1);
+    DecodedImage {
+        data,
+        width: frame.cropped_width().div_ceil(factor),
+        height: frame.cropped_height().div_ceil(factor),
+        has_alpha,
     }
 }
@@ -0,0 +1,164 @@
+//! Resource limits for decoding untrusted input
+
+use crate::error::{Limit, LimitExceeded};
+use crate::heif::derivation::MAX_DERIVATION_DEPTH;
+
+/// Limits on the resources a file may make the decoder use
+///
+/// Sizes declared by the container (`ispe`, grid and overlay configurations)
+/// and by the bitstream (SPS) are checked against these limits before any
+/// buffer is allocated for them, so a small crafted file cannot make the
+/// decoder allocate gigabytes. Exceeding a limit fails with
+/// [`HeicError::LimitExceeded`](crate::HeicError::LimitExceeded).
+///
+/// The defaults accommodate the largest images produced by cameras and
+/// panorama stitchers; use [`DecoderLimits::unlimited`] to disable them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderLimits {
+    /// Maximum width of any decoded image in pixels
+    pub max_width: u32,
+    /// Maximum height of any decoded image in pixels
+    pub max_height: u32,
+    /// Maximum area of any decoded image in pixels
+    pub max_pixels: u64,
+    /// Maximum number of tiles in a grid image
+    pub max_tiles: u32,
+    /// Maximum number of items declared in the container
+    pub max_items: u32,
+    /// Maximum size of a single frame buffer in bytes
+    pub max_alloc: u64,
+    /// Maximum number of nested image derivations (grid, overlay, identity)
+    pub max_derivation_depth: u32,
+}
+
+impl Default for DecoderLimits {
+    fn default() -> Self {
+        Self {
+            max_width: 32768,
+            max_height: 32768,
+            max_pixels: 1 << 28,
+            max_tiles: 4096,
+            max_items: 16384,
+            max_alloc: 1 << 31,
+            max_derivation_depth: MAX_DERIVATION_DEPTH as u32,
+        }
+    }
+}
+
+impl DecoderLimits {
+    /// Limits that accept any input the decoder can represent
+    #[must_use]
+    pub fn unlimited() -> Self {
+        Self {
+            max_width: u32::MAX,
+            max_height: u32::MAX,
+            max_pixels: u64::MAX,
+            max_tiles: u32::MAX,
+            max_items: u32::MAX,
+            max_alloc: u64::MAX,
+            max_derivation_depth: u32::MAX,
+        }
+    }
+
+    /// Check an image size against the dimension and pixel limits
+    pub(crate) fn check_dimensions(&self, width: u32, height: u32) -> Result<(), LimitExceeded> {
+        check(Limit::Width, width.into(), self.max_width.into())?;
+        check(Limit::Height, height.into(), self.max_height.into())?;
+        check(
+            Limit::Pixels,
+            u64::from(width) * u64::from(height),
+            self.max_pixels,
+        )
+    }
+
+    /// Check the size of a frame buffer before allocating it
+    ///
+    /// Covers the dimension and pixel limits as well as the allocation
+    /// limit for the 16-bit sample planes of the given chroma format.
+    pub(crate) fn check_frame(
+        &self,
+        width: u32,
+        height: u32,
+        chroma_format: u8,
+    ) -> Result<(), LimitExceeded> {
+        self.check_dimensions(width, height)?;
+        let (width, height) = (u64::from(width), u64::from(height));
+        let chroma = match chroma_format {
+            0 => 0,
+            2 => width.div_ceil(2) * height,
+            3 => width * height,
+            _ => width.div_ceil(2) * height.div_ceil(2),
+        };
+        let samples = (width * height).saturating_add(chroma.saturating_mul(2));
+        check(Limit::Allocation, samples.saturating_mul(2), self.max_alloc)
+    }
+
+    /// Check the size of a buffer other than a frame before allocating it
+    pub(crate) fn check_alloc(&self, bytes: u64) -> Result<(), LimitExceeded> {
+        check(Limit::Allocation, bytes, self.max_alloc)
+    }
+
+    /// Check the number of tiles of a grid image
+    pub(crate) fn check_tiles(&self, tiles: u64) -> Result<(), LimitExceeded> {
+        check(Limit::Tiles, tiles, self.max_tiles.into())
+    }
+
+    /// Check the number of items declared by a box
+    pub(crate) fn check_items(&self, items: u64) -> Result<(), LimitExceeded> {
+        check(Limit::Items, items, self.max_items.into())
+    }
+
+    /// Check the depth of a derivation chain
+    pub(crate) fn check_derivation_depth(&self, depth: u64) -> Result<(), LimitExceeded> {
+        check(
+            Limit::DerivationDepth,
+            depth,
+            self.max_derivation_depth.into(),
+        )
+    }
+}
+
+fn check(limit: Limit, requested: u64, max: u64) -> Result<(), LimitExceeded> {
+    if requested > max {
+        return Err(LimitExceeded {
+            limit,
+            requested,
+            max,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_limits() {
+        let limits = DecoderLimits::default();
+        assert!(limits.check_frame(8064, 6048, 1).is_ok());
+
+        let err = limits.check_frame(65535, 65535, 1).unwrap_err();
+        assert_eq!(err.limit, Limit::Width);
+        // Within the dimension limits but not the pixel count
+        let err = limits.check_frame(32768, 32768, 1).unwrap_err();
+        assert_eq!(err.limit, Limit::Pixels);
+
+        // 4:4:4 planes take twice the memory of 4:2:0 ones
+        let limits = DecoderLimits {
+            max_alloc: 2 * 1024 * 1024,
+            ..DecoderLimits::default()
+        };
+        assert!(limits.check_frame(1024, 512, 1).is_ok());
+        let err = limits.check_frame(1024, 512, 3).unwrap_err();
+        assert_eq!(
+            (err.limit, err.requested),
+            (Limit::Allocation, 3 * 1024 * 1024)
+        );
+        assert!(
+            DecoderLimits::unlimited()
+                .check_frame(u32::MAX, u32::MAX, 3)
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Floating-point functions for `no_std` builds
+//!
+//! `core` does not provide the transcendental float methods (they live in
+//! `std` because they call into the platform's libm), so without the `std`
+//! feature these extension traits supply them from the pure-Rust `libm`
+//! crate under the same names. Call sites import the trait only when `std`
+//! is disabled, so the inherent methods are used whenever they exist.
+
+/// `f32` methods missing from `core`
+pub(crate) trait F32Ext {
+    fn round(self) -> Self;
+    fn powf(self, n: Self) -> Self;
+    fn exp(self) -> Self;
+    fn exp2(self) -> Self;
+    fn log2(self) -> Self;
+    fn log10(self) -> Self;
+}
+
+impl F32Ext for f32 {
+    #[inline]
+    fn round(self) -> Self {
+        libm::roundf(self)
+    }
+
+    #[inline]
+    fn powf(self, n: Self) -> Self {
+        libm::powf(self, n)
+    }
+
+    #[inline]
+    fn exp(self) -> Self {
+        libm::expf(self)
+    }
+
+    #[inline]
+    fn exp2(self) -> Self {
+        libm::exp2f(self)
+    }
+
+    #[inline]
+    fn log2(self) -> Self {
+        libm::log2f(self)
+    }
+
+    #[inline]
+    fn log10(self) -> Self {
+        libm::log10f(self)
+    }
+}
+
+/// `f64` methods missing from `core`
+pub(crate) trait F64Ext {
+    fn round(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+}
+
+impl F64Ext for f64 {
+    #[inline]
+    fn round(self) -> Self {
+        libm::round(self)
+    }
+
+    #[inline]
+    fn powi(self, n: i32) -> Self {
+        libm::pow(self, f64::from(n))
+    }
+}
@@ -0,0 +1,215 @@
+//! Per-decoder settings
+
+use crate::limits::DecoderLimits;
+
+/// Pixel layout of decoded images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// 8-bit RGB, or RGBA when the image has an alpha plane
+    #[default]
+    Auto,
+    /// 8-bit RGB; any alpha plane is dropped
+    Rgb8,
+    /// 8-bit RGBA; opaque when the image has no alpha plane
+    Rgba8,
+}
+
+/// How subsampled chroma is brought up to luma resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChromaUpsampler {
+    /// Repeat each chroma sample (fastest)
+    #[default]
+    Nearest,
+    /// Interpolate between chroma samples, assuming the HEVC default
+    /// siting: co-sited horizontally, centred between rows vertically
+    Bilinear,
+}
+
+/// How HDR (PQ and HLG) content is brought into SDR range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapping {
+    /// Extended Reinhard curve with white at the content's peak
+    #[default]
+    Reinhard,
+    /// Clip everything above SDR white
+    Clip,
+}
+
+/// Transfer function of the RGB output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorTarget {
+    /// sRGB-encoded, tone mapped to SDR
+    #[default]
+    Srgb,
+    /// Linear light, tone mapped to SDR
+    Linear,
+    /// The coded signal after the YCbCr to RGB matrix, in the source's own
+    /// transfer function and without tone mapping
+    Passthrough,
+}
+
+/// How decoding errors are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Fail on the first error
+    #[default]
+    Strict,
+    /// Conceal damaged tiles and CTUs from their neighbours, as
+    /// [`HeicDecoder::decode_lenient`](crate::HeicDecoder::decode_lenient) does
+    Lenient,
+}
+
+/// Settings of a [`HeicDecoder`](crate::HeicDecoder)
+///
+/// Options are passed down explicitly with each decode, so decoders with
+/// different options can be used concurrently.
+///
+/// ```
+/// use heic_decoder::{ChromaUpsampler, DecoderOptions, HeicDecoder};
+///
+/// let decoder = HeicDecoder::with_options(
+///     DecoderOptions::new()
+///         .upsampler(ChromaUpsampler::Bilinear)
+///         .threads(1),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderOptions {
+    /// Apply the deblocking filter (disabling it is not conforming)
+    pub deblocking: bool,
+    /// Apply sample adaptive offset (disabling it is not conforming)
+    pub sao: bool,
+    /// Pixel layout of decoded images
+    pub output_format: OutputFormat,
+    /// Chroma upsampling used when converting to RGB
+    pub upsampler: ChromaUpsampler,
+    /// HDR to SDR tone mapping used when converting to RGB
+    pub tone_mapping: ToneMapping,
+    /// Transfer function of the RGB output
+    pub color_target: ColorTarget,
+    /// Resource limits
+    pub limits: DecoderLimits,
+    /// Worker threads of the global rayon pool used for grid tiles and
+    /// colour conversion with the `parallel` feature; 0 picks
+    /// automatically, 1 decodes on the calling thread
+    pub threads: usize,
+    /// How decoding errors are handled
+    pub strictness: Strictness,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> Self {
+        Self {
+            deblocking: true,
+            sao: true,
+            output_format: OutputFormat::default(),
+            upsampler: ChromaUpsampler::default(),
+            tone_mapping: ToneMapping::default(),
+            color_target: ColorTarget::default(),
+            limits: DecoderLimits::default(),
+            threads: 0,
+            strictness: Strictness::default(),
+        }
+    }
+}
+
+impl DecoderOptions {
+    /// Default options: conforming decode to 8-bit sRGB
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable the deblocking filter
+    #[must_use]
+    pub fn deblocking(mut self, enabled: bool) -> Self {
+        self.deblocking = enabled;
+        self
+    }
+
+    /// Enable or disable sample adaptive offset
+    #[must_use]
+    pub fn sao(mut self, enabled: bool) -> Self {
+        self.sao = enabled;
+        self
+    }
+
+    /// Set the pixel layout of decoded images
+    #[must_use]
+    pub fn output_format(mut self, format: OutputFormat) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// Set the chroma upsampler
+    #[must_use]
+    pub fn upsampler(mut self, upsampler: ChromaUpsampler) -> Self {
+        self.upsampler = upsampler;
+        self
+    }
+
+    /// Set the HDR to SDR tone mapping
+    #[must_use]
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Set the transfer function of the RGB output
+    #[must_use]
+    pub fn color_target(mut self, target: ColorTarget) -> Self {
+        self.color_target = target;
+        self
+    }
+
+    /// Set the resource limits
+    #[must_use]
+    pub fn limits(mut self, limits: DecoderLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the number of worker threads (0: automatic)
+    #[must_use]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Set how decoding errors are handled
+    #[must_use]
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Whether errors are concealed rather than returned
+    pub(crate) fn conceals_errors(&self) -> bool {
+        self.strictness == Strictness::Lenient
+    }
+
+    /// Map `f` over `0..count` on the global rayon pool, returning the
+    /// results in order
+    ///
+    /// The work is split into at most `threads` jobs, or `default_threads`
+    /// with `threads` 0, so no more workers than that run at once.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn par_map<R: Send>(
+        &self,
+        count: usize,
+        default_threads: usize,
+        f: impl Fn(usize) -> R + Sync + Send,
+    ) -> Vec<R> {
+        use rayon::prelude::*;
+
+        let threads = match self.threads {
+            0 => default_threads,
+            n => n,
+        };
+        (0..count)
+            .into_par_iter()
+            .with_min_len(count.div_ceil(threads.max(1)).max(1))
+            .map(f)
+            .collect()
+    }
+}